@@ -1,4 +1,5 @@
 use std::collections::{
+    BTreeMap,
     HashMap,
     HashSet,
 };
@@ -69,6 +70,12 @@ impl AgentConfig {
         }
     }
 
+    pub fn hook_policy(&self) -> &HookPolicy {
+        match self {
+            AgentConfig::V2025_08_22(a) => &a.hook_policy,
+        }
+    }
+
     // pub fn resources(&self) -> &[impl AsRef<str>] {
     pub fn resources(&self) -> &[impl AsRef<str>] {
         match self {
@@ -129,6 +136,9 @@ pub struct AgentConfigV2025_08_22 {
     /// Hooks to add additional context
     #[serde(default)]
     pub hooks: HashMap<HookTrigger, Vec<HookConfig>>,
+    /// How the results of the hooks matching a trigger are combined
+    #[serde(default)]
+    pub hook_policy: HookPolicy,
     /// Preferences for selecting a model the agent uses to generate responses.
     ///
     /// TODO: unimplemented
@@ -170,6 +180,7 @@ impl Default for AgentConfigV2025_08_22 {
             tool_aliases: Default::default(),
             tool_schema: Default::default(),
             hooks: Default::default(),
+            hook_policy: Default::default(),
             model_preferences: Default::default(),
             mcp_servers: Default::default(),
             use_legacy_mcp_json: false,
@@ -292,6 +303,24 @@ pub enum HookTrigger {
     PreToolUse,
     /// Triggered after tool execution
     PostToolUse,
+    /// Triggered when the session ends
+    SessionEnd,
+    /// Triggered when the agent encounters an error
+    OnError,
+    /// Triggered when a tool needs approval and when a long turn completes
+    ///
+    /// Matchers match the type of notification, `approval_needed` or `turn_complete`
+    Notification,
+}
+
+impl HookTrigger {
+    /// Whether the agent carries on without waiting for hooks of this trigger to finish.
+    pub fn is_background(&self) -> bool {
+        matches!(
+            self,
+            HookTrigger::SessionEnd | HookTrigger::OnError | HookTrigger::Notification
+        )
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
@@ -311,15 +340,56 @@ impl HookConfig {
         }
     }
 
+    pub fn opts_mut(&mut self) -> &mut BaseHookConfig {
+        match self {
+            HookConfig::ShellCommand(h) => &mut h.opts,
+            HookConfig::Tool(h) => &mut h.opts,
+        }
+    }
+
     pub fn matcher(&self) -> Option<&str> {
         self.opts().matcher.as_deref()
     }
 }
 
+/// Policy applied to the blocking hooks matching a trigger, which all run concurrently.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct HookPolicy {
+    /// How the results of preToolUse hooks decide whether the tool is blocked
+    #[serde(default)]
+    pub aggregation: HookAggregation,
+    /// Max time all the hooks of a trigger can run, in milliseconds
+    ///
+    /// Hooks still running at the deadline time out, as if their own timeout was reached
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deadline_ms: Option<u64>,
+}
+
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum HookAggregation {
+    /// The tool is blocked if any hook blocks it, with exit code 2 or a "block" decision
+    #[default]
+    AnyBlock,
+    /// The tool is blocked unless every hook succeeds
+    AllMustPass,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
 pub struct CommandHook {
     /// The command to run
     pub command: String,
+    /// Working directory of the command, relative to the current directory
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+    /// Environment variables added to the environment of the command. Values may reference the
+    /// environment with `${env:VAR_NAME}` and the system keyring with `${keyring:NAME}`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    /// Shell running the command with `-c`. Defaults to `bash`, or `cmd` on Windows
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shell: Option<String>,
     #[serde(flatten)]
     pub opts: BaseHookConfig,
 }
@@ -343,6 +413,10 @@ pub struct BaseHookConfig {
     pub max_output_size: usize,
 
     /// How long the hook output is cached before it will be executed again
+    ///
+    /// Only successful results are cached, and `0` disables caching. Agent spawn hooks are always
+    /// cached for the whole session. A cached result is also discarded once a file referenced by
+    /// the command changes, e.g. the script it runs or a file it reads.
     #[serde(default = "hook_default_cache_ttl_seconds")]
     pub cache_ttl_seconds: u64,
 
@@ -351,6 +425,21 @@ pub struct BaseHookConfig {
     /// Currently used for matching tool names for PreToolUse and PostToolUse hooks
     #[serde(skip_serializing_if = "Option::is_none")]
     pub matcher: Option<String>,
+
+    /// Whether to wait for the hook to finish
+    ///
+    /// Non-blocking hooks run in the background, their output is ignored and failures are only
+    /// logged
+    #[serde(default = "hook_default_blocking", skip_serializing_if = "hook_is_blocking")]
+    pub blocking: bool,
+}
+
+fn hook_default_blocking() -> bool {
+    true
+}
+
+fn hook_is_blocking(blocking: &bool) -> bool {
+    *blocking
 }
 
 fn hook_default_timeout_ms() -> u64 {
@@ -313,6 +313,9 @@ pub enum ContentBlock {
     ToolUse(ToolUseBlock),
     ToolResult(ToolResultBlock),
     Image(ImageBlock),
+    /// Marks the end of a stable prefix of the conversation that the backend may cache, for
+    /// backends supporting prompt caching. Other backends ignore it.
+    CachePoint,
 }
 
 impl ContentBlock {
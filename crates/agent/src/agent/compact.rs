@@ -78,7 +78,7 @@ impl CompactStrategy {
                                 }
                             }
                         },
-                        ContentBlock::ToolUse(_) | ContentBlock::Image(_) => (),
+                        ContentBlock::ToolUse(_) | ContentBlock::Image(_) | ContentBlock::CachePoint => (),
                     }
                 }
                 if total_len <= self.max_message_length {
@@ -114,7 +114,7 @@ impl CompactStrategy {
                                 }
                             }
                         },
-                        ContentBlock::ToolUse(_) | ContentBlock::Image(_) => (),
+                        ContentBlock::ToolUse(_) | ContentBlock::Image(_) | ContentBlock::CachePoint => (),
                     }
                 }
             }
@@ -18,3 +18,10 @@ pub const MAX_IMAGE_SIZE_BYTES: u64 = 10 * 1024 * 1024;
 
 pub const TOOL_USE_PURPOSE_FIELD_NAME: &str = "__tool_use_purpose";
 pub const TOOL_USE_PURPOSE_FIELD_DESCRIPTION: &str = "A brief explanation why you are making this tool use.";
+
+/// Types of the notifications given to notification hooks.
+pub const NOTIFICATION_APPROVAL_NEEDED: &str = "approval_needed";
+pub const NOTIFICATION_TURN_COMPLETE: &str = "turn_complete";
+
+/// Longest response given as summary to notification hooks.
+pub const NOTIFICATION_SUMMARY_MAX_BYTES: usize = 500;
@@ -0,0 +1,308 @@
+//! Syntax of hook matchers and of the structured output of hooks, shared by every hook executor.
+
+use std::collections::hash_map::Entry;
+use std::collections::{
+    BTreeMap,
+    HashMap,
+};
+use std::sync::LazyLock;
+
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::agent::util::glob::matches_any_pattern;
+use crate::agent::util::providers::EnvProvider;
+
+/// A reference in the value of an environment variable of a hook, e.g. `${env:HOME}`, capturing
+/// its source and name.
+static ENV_REFERENCE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\$\{(\w+):([^}]*)\}").expect("valid regex"));
+
+/// Splits a hook matcher into its tool name pattern and the conditions on the tool input, e.g.
+/// `fs_write[path=**/migrations/**]`.
+///
+/// A matcher whose conditions can't be parsed is returned as a whole, hence matches no tool.
+pub fn parse_hook_matcher(matcher: &str) -> (&str, Vec<ToolInputCondition>) {
+    let Some((pattern, conditions)) = matcher.strip_suffix(']').and_then(|m| m.split_once('[')) else {
+        return (matcher, vec![]);
+    };
+    match split_conditions(conditions)
+        .map(ToolInputCondition::parse)
+        .collect::<Option<Vec<_>>>()
+    {
+        Some(conditions) => (pattern.trim(), conditions),
+        None => (matcher, vec![]),
+    }
+}
+
+/// Splits on the commas that are not part of a glob alternation, e.g. `*.{rs,toml}`
+fn split_conditions(conditions: &str) -> impl Iterator<Item = &str> {
+    let mut depth = 0;
+    conditions
+        .split(move |c| {
+            match c {
+                '{' => depth += 1,
+                '}' => depth -= 1,
+                _ => (),
+            }
+            c == ',' && depth == 0
+        })
+        .map(str::trim)
+        .filter(|c| !c.is_empty())
+}
+
+/// Condition of a hook matcher on a field of the tool input, written `key=glob` or `key!=glob`.
+///
+/// The key is a dot-separated path into the tool input, e.g. `operations.0.path`. Without an
+/// index, every element of an array is checked, e.g. `operations.path`. Numbers and booleans are
+/// compared as written in JSON.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolInputCondition {
+    path: Vec<String>,
+    pattern: String,
+    negated: bool,
+}
+
+impl ToolInputCondition {
+    fn parse(condition: &str) -> Option<Self> {
+        let (key, pattern) = condition.split_once('=')?;
+        let (key, negated) = match key.strip_suffix('!') {
+            Some(key) => (key, true),
+            None => (key, false),
+        };
+        let path = key.trim().split('.').map(str::to_string).collect::<Vec<_>>();
+        if path.iter().any(|segment| segment.is_empty()) {
+            return None;
+        }
+
+        Some(Self {
+            path,
+            pattern: pattern.trim().to_string(),
+            negated,
+        })
+    }
+
+    /// A negated condition matches when no value matches, including when the field is missing.
+    pub fn matches(&self, tool_input: &serde_json::Value) -> bool {
+        let matched = values_at(tool_input, &self.path).into_iter().any(|value| match value {
+            serde_json::Value::String(s) => matches_any_pattern([&self.pattern], s),
+            serde_json::Value::Null => false,
+            other => matches_any_pattern([&self.pattern], other.to_string()),
+        });
+        matched != self.negated
+    }
+}
+
+fn values_at<'a>(value: &'a serde_json::Value, path: &[String]) -> Vec<&'a serde_json::Value> {
+    let Some((segment, rest)) = path.split_first() else {
+        return vec![value];
+    };
+    match value {
+        serde_json::Value::Object(fields) => fields.get(segment).map(|v| values_at(v, rest)).unwrap_or_default(),
+        serde_json::Value::Array(items) => match segment.parse::<usize>() {
+            Ok(index) => items.get(index).map(|v| values_at(v, rest)).unwrap_or_default(),
+            Err(_) => items.iter().flat_map(|v| values_at(v, path)).collect(),
+        },
+        _ => vec![],
+    }
+}
+
+/// Structured output a hook may print to STDOUT instead of relying on its exit code only, e.g.
+/// `{"decision": "block", "reason": "...", "additionalContext": "..."}`.
+///
+/// A "block" decision is handled like exit code 2 with `reason` as the output, otherwise
+/// `additionalContext` replaces the output.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HookJsonOutput {
+    #[serde(default)]
+    pub decision: Option<HookDecision>,
+    /// Why the decision was made. Returned to the model when blocking, shown to the user otherwise
+    #[serde(default)]
+    pub reason: Option<String>,
+    /// Added to the context instead of STDOUT
+    #[serde(default)]
+    pub additional_context: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HookDecision {
+    Allow,
+    Block,
+}
+
+impl HookJsonOutput {
+    /// Parses the STDOUT of a successful hook. Returns [None] for plain output, including JSON
+    /// that has none of the fields above, so that existing hooks keep working.
+    pub fn parse(output: &str) -> Option<Self> {
+        let serde_json::Value::Object(fields) = serde_json::from_str(output.trim()).ok()? else {
+            return None;
+        };
+        if !["decision", "reason", "additionalContext"]
+            .iter()
+            .any(|key| fields.contains_key(*key))
+        {
+            return None;
+        }
+        serde_json::from_value(serde_json::Value::Object(fields)).ok()
+    }
+
+    pub fn is_block(&self) -> bool {
+        self.decision == Some(HookDecision::Block)
+    }
+}
+
+/// Resolves the references of the environment variables of a hook: `${env:VAR_NAME}` to the
+/// variable of the environment, empty if unset, and `${keyring:NAME}` to the secret `NAME` of the
+/// system keyring.
+///
+/// Fails on references to other sources and on secrets missing from the keyring, so that the hook
+/// doesn't run with a partial environment.
+pub async fn resolve_hook_env(
+    env: &BTreeMap<String, String>,
+    env_provider: &impl EnvProvider,
+) -> Result<BTreeMap<String, String>, String> {
+    resolve_hook_env_impl(env, env_provider, keyring_secret).await
+}
+
+async fn resolve_hook_env_impl(
+    env: &BTreeMap<String, String>,
+    env_provider: &impl EnvProvider,
+    keyring: impl AsyncFn(&str) -> Result<String, String>,
+) -> Result<BTreeMap<String, String>, String> {
+    let mut keyring_names = Vec::new();
+    for (key, value) in env {
+        for caps in ENV_REFERENCE.captures_iter(value) {
+            match &caps[1] {
+                "env" => (),
+                "keyring" => keyring_names.push((key, caps[0].to_string(), caps[2].to_string())),
+                _ => {
+                    return Err(format!(
+                        "the environment variable {key} of the hook references {}, only ${{env:VAR_NAME}} and ${{keyring:NAME}} are supported",
+                        &caps[0]
+                    ));
+                },
+            }
+        }
+    }
+
+    let mut secrets = HashMap::new();
+    for (key, reference, name) in keyring_names {
+        if let Entry::Vacant(entry) = secrets.entry(name) {
+            let secret = keyring(entry.key())
+                .await
+                .map_err(|err| format!("the environment variable {key} of the hook references {reference}: {err}"))?;
+            entry.insert(secret);
+        }
+    }
+
+    Ok(env
+        .iter()
+        .map(|(key, value)| {
+            let value = ENV_REFERENCE.replace_all(value, |caps: &regex::Captures<'_>| match &caps[1] {
+                "env" => env_provider.var(&caps[2]).unwrap_or_default(),
+                _ => secrets.get(&caps[2]).cloned().unwrap_or_default(),
+            });
+            (key.clone(), value.into_owned())
+        })
+        .collect())
+}
+
+/// Reads the secret `name` of the system keyring: the password of the generic keychain item of
+/// the service `name` on macOS, or of the Secret Service item whose `service` attribute is `name`
+/// on Linux, e.g. stored with `secret-tool store --label=NAME service NAME`.
+async fn keyring_secret(name: &str) -> Result<String, String> {
+    #[cfg(target_os = "macos")]
+    let output = tokio::process::Command::new("security")
+        .args(["find-generic-password", "-s", name, "-w"])
+        .output()
+        .await;
+    #[cfg(not(target_os = "macos"))]
+    let output = tokio::process::Command::new("secret-tool")
+        .args(["lookup", "service", name])
+        .output()
+        .await;
+
+    match output {
+        Ok(output) if output.status.success() => {
+            let secret = String::from_utf8_lossy(&output.stdout);
+            Ok(secret.strip_suffix('\n').unwrap_or(&secret).to_string())
+        },
+        Ok(_) => Err(format!("the keyring has no secret {name}")),
+        Err(err) => Err(format!("unable to read the keyring: {err}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hook_matcher_conditions() {
+        let input = serde_json::json!({
+            "path": "/repo/migrations/001_init.sql",
+            "operations": [{ "path": "src/main.rs" }, { "path": ".env" }]
+        });
+        let matches = |matcher: &str| {
+            let (_, conditions) = parse_hook_matcher(matcher);
+            conditions.iter().all(|c| c.matches(&input))
+        };
+
+        assert_eq!(parse_hook_matcher("fs_write[path=**/migrations/**]").0, "fs_write");
+        assert!(matches("fs_write[path=**/migrations/**]"));
+        assert!(!matches("fs_write[path=src/**]"));
+        assert!(matches("fs_write[path!=src/**, operations.path=.env]"));
+        assert!(matches("fs_write[path=*.{sql,rs}]"));
+        assert!(!matches("fs_write[operations.0.path=.env]"));
+        assert_eq!(parse_hook_matcher("fs_write[path]"), ("fs_write[path]", vec![]));
+    }
+
+    #[test]
+    fn test_hook_json_output() {
+        let json = HookJsonOutput::parse(r#"{"decision": "block", "reason": "migrations are read-only"}"#).unwrap();
+        assert!(json.is_block());
+        assert_eq!(json.reason.as_deref(), Some("migrations are read-only"));
+
+        let json = HookJsonOutput::parse(" {\"additionalContext\": \"use tabs\"}\n").unwrap();
+        assert!(!json.is_block());
+        assert_eq!(json.additional_context.as_deref(), Some("use tabs"));
+
+        assert_eq!(HookJsonOutput::parse("all good"), None);
+        assert_eq!(HookJsonOutput::parse(r#"{"status": "ok"}"#), None);
+        assert_eq!(HookJsonOutput::parse(r#"{"decision": "maybe"}"#), None);
+    }
+
+    #[tokio::test]
+    async fn test_resolve_hook_env() {
+        let env_provider = crate::agent::util::test::TestProvider::new().with_var("API_TOKEN", "env-token");
+        let keyring = async |name: &str| match name {
+            "api-token" => Ok("keyring-token".to_string()),
+            _ => Err(format!("the keyring has no secret {name}")),
+        };
+        let env = |value: &str| BTreeMap::from([("TOKEN".to_string(), value.to_string())]);
+
+        let resolved = resolve_hook_env_impl(&env("Bearer ${env:API_TOKEN}"), &env_provider, keyring)
+            .await
+            .unwrap();
+        assert_eq!(resolved["TOKEN"], "Bearer env-token");
+        let resolved = resolve_hook_env_impl(&env("${env:UNSET}:${keyring:api-token}"), &env_provider, keyring)
+            .await
+            .unwrap();
+        assert_eq!(resolved["TOKEN"], ":keyring-token");
+        assert_eq!(
+            resolve_hook_env_impl(&env("plain"), &env_provider, keyring)
+                .await
+                .unwrap()["TOKEN"],
+            "plain"
+        );
+
+        let err = resolve_hook_env_impl(&env("${keyring:missing}"), &env_provider, keyring)
+            .await
+            .unwrap_err();
+        assert!(err.contains("TOKEN") && err.contains("no secret missing"), "{err}");
+        let err = resolve_hook_env_impl(&env("${vault:api-token}"), &env_provider, keyring)
+            .await
+            .unwrap_err();
+        assert!(err.contains("${vault:api-token}"), "{err}");
+    }
+}
@@ -1,6 +1,7 @@
 pub mod agent_config;
 pub mod agent_loop;
 pub mod consts;
+pub mod hooks;
 pub mod mcp;
 mod permissions;
 pub mod protocol;
@@ -21,6 +22,7 @@ use std::sync::Arc;
 use agent_config::LoadedMcpServerConfigs;
 use agent_config::definitions::{
     AgentConfig,
+    HookAggregation,
     HookConfig,
     HookTrigger,
 };
@@ -132,13 +134,20 @@ use util::providers::{
     RealProvider,
     SystemProvider,
 };
-use util::read_file_with_max_limit;
 use util::request_channel::new_request_channel;
+use util::{
+    read_file_with_max_limit,
+    truncate_safe,
+};
 
 use crate::agent::consts::{
     DUMMY_TOOL_NAME,
     MAX_CONVERSATION_STATE_HISTORY_LEN,
+    NOTIFICATION_APPROVAL_NEEDED,
+    NOTIFICATION_SUMMARY_MAX_BYTES,
+    NOTIFICATION_TURN_COMPLETE,
 };
+use crate::agent::hooks::parse_hook_matcher;
 use crate::agent::mcp::McpManagerHandle;
 use crate::agent::tools::{
     BuiltInTool,
@@ -203,6 +212,18 @@ impl AgentHandle {
         }
     }
 
+    pub async fn cancel(&self) -> Result<(), AgentError> {
+        match self
+            .sender
+            .send_recv(AgentRequest::Cancel)
+            .await
+            .unwrap_or(Err(AgentError::Channel))?
+        {
+            AgentResponse::Success => Ok(()),
+            other => Err(AgentError::Custom(format!("received unexpected response: {:?}", other))),
+        }
+    }
+
     pub async fn create_snapshot(&self) -> Result<AgentSnapshot, AgentError> {
         match self
             .sender
@@ -326,6 +347,10 @@ impl Agent {
         self.sys_provider = Arc::new(provider);
     }
 
+    pub fn set_working_directory(&mut self, working_directory: PathBuf) {
+        self.working_directory = Some(working_directory);
+    }
+
     /// Starts the agent task, returning a handle from which messages can be sent and events can be
     /// received.
     pub fn spawn(mut self) -> AgentHandle {
@@ -415,20 +440,21 @@ impl Agent {
         }
 
         // Next, run agent spawn hooks.
-        let hooks = self.get_hooks(HookTrigger::AgentSpawn);
+        let hooks = self
+            .get_hooks(HookTrigger::AgentSpawn)
+            .into_iter()
+            .map(|hook| {
+                (
+                    HookExecutionId {
+                        hook,
+                        tool_context: None,
+                    },
+                    None,
+                )
+            })
+            .collect();
+        let hooks = self.start_non_blocking_hooks(hooks, None).await;
         if !hooks.is_empty() {
-            let hooks = hooks
-                .into_iter()
-                .map(|hook| {
-                    (
-                        HookExecutionId {
-                            hook,
-                            tool_context: None,
-                        },
-                        None,
-                    )
-                })
-                .collect();
             if let Err(err) = self.start_hooks_execution(hooks, HookStage::AgentSpawn, None).await {
                 error!(?err, "failed to execute agent spawn hooks");
             }
@@ -449,6 +475,7 @@ impl Agent {
                 req = request_rx.recv() => {
                     let Some(req) = req else {
                         warn!("session request receiver channel has closed, exiting");
+                        self.run_session_end_hooks().await;
                         break;
                     };
                     let res = self.handle_agent_request(req.payload).await;
@@ -492,6 +519,15 @@ impl Agent {
     }
 
     async fn set_active_state(&mut self, new_state: ActiveState) {
+        if let ActiveState::Errored(err) = &new_state {
+            let event_input = serde_json::json!({
+                "error": {
+                    "message": err.to_string(),
+                    "details": err,
+                }
+            });
+            self.start_background_hooks(HookTrigger::OnError, event_input).await;
+        }
         let from = self.execution_state.clone();
         self.execution_state.active_state = new_state;
         let to = self.execution_state.clone();
@@ -527,6 +563,85 @@ impl Agent {
             .collect::<Vec<_>>()
     }
 
+    /// Starts the hooks of a background trigger without waiting for them to finish, see
+    /// [HookTrigger::is_background]. Returns the number of hooks started.
+    ///
+    /// Notification hooks only start if their matcher matches the `notification_type` of the
+    /// event.
+    async fn start_background_hooks(&mut self, trigger: HookTrigger, event_input: serde_json::Value) -> usize {
+        debug_assert!(trigger.is_background());
+        let mut hooks = self.get_hooks(trigger);
+        if let Some(notification_type) = event_input["notification_type"].as_str() {
+            hooks.retain(|hook| {
+                hook.config
+                    .matcher()
+                    .is_none_or(|matcher| matches_any_pattern([matcher], notification_type))
+            });
+        }
+        let count = hooks.len();
+        for hook in hooks {
+            self.task_executor
+                .start_hook_execution(StartHookExecution {
+                    id: HookExecutionId {
+                        hook,
+                        tool_context: None,
+                    },
+                    prompt: None,
+                    event_input: Some(event_input.clone()),
+                })
+                .await;
+        }
+        count
+    }
+
+    /// Tells the notification hooks that a user turn completed, with a summary of it, if it lasted
+    /// at least [AgentSettings::notification_turn_threshold].
+    async fn notify_turn_complete(&mut self, md: &UserTurnMetadata) {
+        let Some(duration) = md.turn_duration else {
+            return;
+        };
+        if duration < self.settings.notification_turn_threshold {
+            return;
+        }
+        let summary = match &md.result {
+            Some(Ok(msg)) => Some(truncate_safe(&msg.text(), NOTIFICATION_SUMMARY_MAX_BYTES).to_string()),
+            _ => None,
+        };
+        let notification = serde_json::json!({
+            "notification_type": NOTIFICATION_TURN_COMPLETE,
+            "message": format!(
+                "Turn completed in {} s with {} tool uses",
+                duration.as_secs(),
+                md.number_of_cycles
+            ),
+            "duration_seconds": duration.as_secs(),
+            "tool_uses": md.number_of_cycles,
+            "summary": summary,
+        });
+        self.start_background_hooks(HookTrigger::Notification, notification)
+            .await;
+    }
+
+    /// Runs the session end hooks, waiting for them to finish since the agent is about to exit.
+    async fn run_session_end_hooks(&mut self) {
+        let event_input = serde_json::json!({ "agent_id": self.id });
+        let mut pending = self.start_background_hooks(HookTrigger::SessionEnd, event_input).await;
+        let mut event_buf = Vec::new();
+        while pending > 0 {
+            self.task_executor.recv_next(&mut event_buf).await;
+            for evt in event_buf.drain(..) {
+                let id = match &evt {
+                    TaskExecutorEvent::HookExecutionEnd(evt) => &evt.id,
+                    TaskExecutorEvent::CachedHookRun(evt) => &evt.id,
+                    _ => continue,
+                };
+                if id.hook.trigger == HookTrigger::SessionEnd {
+                    pending -= 1;
+                }
+            }
+        }
+    }
+
     fn agent_loop_handle(&mut self) -> Result<&mut AgentLoopHandle, AgentError> {
         self.agent_loop
             .as_mut()
@@ -758,6 +873,7 @@ impl Agent {
             AgentLoopEventKind::UserTurnEnd(md) => {
                 self.conversation_metadata.user_turn_metadatas.push(md.clone());
                 self.set_active_state(ActiveState::Idle).await;
+                self.notify_turn_complete(&md).await;
                 self.agent_event_buf.push(AgentEvent::EndTurn(md));
                 self.agent_event_buf.push(AgentEvent::Stop(AgentStopReason::EndTurn));
             },
@@ -892,21 +1008,22 @@ impl Agent {
         }
 
         // Run per-prompt hooks, if required.
-        let hooks = self.get_hooks(HookTrigger::UserPromptSubmit);
+        let hooks = self
+            .get_hooks(HookTrigger::UserPromptSubmit)
+            .into_iter()
+            .map(|hook| {
+                (
+                    HookExecutionId {
+                        hook,
+                        tool_context: None,
+                    },
+                    None,
+                )
+            })
+            .collect();
+        let prompt = args.text();
+        let hooks = self.start_non_blocking_hooks(hooks, prompt.clone()).await;
         if !hooks.is_empty() {
-            let hooks = hooks
-                .into_iter()
-                .map(|hook| {
-                    (
-                        HookExecutionId {
-                            hook,
-                            tool_context: None,
-                        },
-                        None,
-                    )
-                })
-                .collect();
-            let prompt = args.text();
             self.start_hooks_execution(hooks, HookStage::PrePrompt { args }, prompt)
                 .await?;
             Ok(AgentResponse::Success)
@@ -1060,16 +1177,22 @@ impl Agent {
         let hooks = self.get_hooks(HookTrigger::PreToolUse);
         let mut hooks_to_execute = Vec::new();
         for (block, tool) in &tools {
-            hooks_to_execute.extend(hooks.iter().filter(|h| hook_matches_tool(&h.config, tool)).map(|h| {
-                (
-                    HookExecutionId {
-                        hook: h.clone(),
-                        tool_context: Some((block, tool).into()),
-                    },
-                    Some((block.clone(), tool.clone())),
-                )
-            }));
+            hooks_to_execute.extend(
+                hooks
+                    .iter()
+                    .filter(|h| hook_matches_tool(&h.config, tool, &block.input))
+                    .map(|h| {
+                        (
+                            HookExecutionId {
+                                hook: h.clone(),
+                                tool_context: Some((block, tool).into()),
+                            },
+                            Some((block.clone(), tool.clone())),
+                        )
+                    }),
+            );
         }
+        let hooks_to_execute = self.start_non_blocking_hooks(hooks_to_execute, None).await;
         if !hooks_to_execute.is_empty() {
             debug!(?hooks_to_execute, "found hooks to execute for preToolUse");
             let stage = HookStage::PreToolUse {
@@ -1110,17 +1233,45 @@ impl Agent {
         self.execute_tools(tools).await
     }
 
+    /// Starts the hooks configured with `blocking: false` without tracking them in
+    /// [ActiveState::ExecutingHooks], returning the remaining hooks to wait on.
+    async fn start_non_blocking_hooks(
+        &mut self,
+        hooks: Vec<(HookExecutionId, Option<(ToolUseBlock, Tool)>)>,
+        prompt: Option<String>,
+    ) -> Vec<(HookExecutionId, Option<(ToolUseBlock, Tool)>)> {
+        let (non_blocking, blocking): (Vec<_>, Vec<_>) =
+            hooks.into_iter().partition(|(id, _)| !id.hook.config.opts().blocking);
+        for (id, _) in non_blocking {
+            self.task_executor
+                .start_hook_execution(StartHookExecution {
+                    id,
+                    prompt: prompt.clone(),
+                    event_input: None,
+                })
+                .await;
+        }
+        blocking
+    }
+
     async fn start_hooks_execution(
         &mut self,
         hooks: Vec<(HookExecutionId, Option<(ToolUseBlock, Tool)>)>,
         stage: HookStage,
         prompt: Option<String>,
     ) -> Result<(), AgentError> {
+        let deadline_ms = self.agent_config.hook_policy().deadline_ms;
         let mut hooks_state = Vec::new();
-        for (id, tool_ctx) in hooks {
+        for (mut id, tool_ctx) in hooks {
+            // Hooks all start together, so the deadline bounds each of their timeouts.
+            if let Some(deadline_ms) = deadline_ms {
+                let opts = id.hook.config.opts_mut();
+                opts.timeout_ms = opts.timeout_ms.min(deadline_ms);
+            }
             let req = StartHookExecution {
                 id: id.clone(),
                 prompt: prompt.clone(),
+                event_input: None,
             };
             hooks_state.push(ExecutingHook {
                 id: id.clone(),
@@ -1189,7 +1340,9 @@ impl Agent {
             hooks_to_execute.extend(
                 hooks
                     .iter()
-                    .filter(|h| hook_matches_tool(&h.config, &executing_tool.tool))
+                    .filter(|h| {
+                        hook_matches_tool(&h.config, &executing_tool.tool, &executing_tool.tool_use_block.input)
+                    })
                     .map(|h| {
                         (
                             HookExecutionId {
@@ -1203,6 +1356,7 @@ impl Agent {
                     }),
             );
         }
+        let hooks_to_execute = self.start_non_blocking_hooks(hooks_to_execute, None).await;
         if !hooks_to_execute.is_empty() {
             debug!("found hooks to execute for postToolUse");
             let stage = HookStage::PostToolUse {
@@ -1218,6 +1372,11 @@ impl Agent {
     }
 
     async fn handle_hook_finished_event(&mut self, id: HookExecutionId, result: HookResult) -> Result<(), AgentError> {
+        if id.hook.trigger.is_background() || !id.hook.config.opts().blocking {
+            debug!(?id, ?result, "background hook finished");
+            return Ok(());
+        }
+
         let ActiveState::ExecutingHooks(executing_hooks) = &mut self.execution_state.active_state else {
             warn!(
                 ?self.execution_state,
@@ -1261,11 +1420,13 @@ impl Agent {
                 Ok(())
             },
             HookStage::PreToolUse { tools, needs_approval } => {
-                // If any command hooks exited with status 2, then we'll block.
-                // Otherwise, execute the tools.
+                // If any command hooks exited with status 2 (or, when all hooks must pass, did not
+                // succeed), then we'll block. Otherwise, execute the tools.
+                let aggregation = self.agent_config.hook_policy().aggregation;
                 let mut denied_tools = Vec::new();
                 for (block, _) in tools {
-                    if let Some(hook) = executing_hooks.has_failure_exit_code_for_tool(&block.tool_use_id) {
+                    if let Some(hook) = executing_hooks.has_failure_exit_code_for_tool(&block.tool_use_id, aggregation)
+                    {
                         denied_tools.push((
                             block.tool_use_id.clone(),
                             hook.result.as_ref().cloned().expect("is some"),
@@ -1281,7 +1442,7 @@ impl Agent {
                                 tool_use_id,
                                 content: vec![ToolResultContentBlock::Text(format!(
                                     "PreToolHook blocked the tool execution: {}",
-                                    hook_res.output().unwrap_or("no output provided")
+                                    hook_res.output().or(hook_res.error()).unwrap_or("no output provided")
                                 ))],
                                 status: ToolResultStatus::Error,
                             })
@@ -1535,11 +1696,20 @@ impl Agent {
             let Some((block, tool)) = tools.iter().find(|(b, _)| &b.tool_use_id == tool_use_id) else {
                 continue;
             };
+            let context = tool.get_context().await;
+            let notification = serde_json::json!({
+                "notification_type": NOTIFICATION_APPROVAL_NEEDED,
+                "message": format!("{} needs your approval", block.name),
+                "tool_name": block.name,
+                "tool_input": block.input,
+            });
             self.agent_event_buf.push(AgentEvent::ApprovalRequest {
                 id: block.tool_use_id.clone(),
                 tool_use: (*block).clone(),
-                context: tool.get_context().await,
+                context,
             });
+            self.start_background_hooks(HookTrigger::Notification, notification)
+                .await;
         }
 
         Ok(())
@@ -1700,6 +1870,8 @@ where
     P: SystemProvider,
 {
     enforce_conversation_invariants(&mut messages, &mut tool_spec);
+    // Keep the tools in a stable order so that the prefix of the request can be cached.
+    tool_spec.sort_by(|a, b| a.name.cmp(&b.name));
 
     let ctx_messages = create_context_messages(agent_config, agent_spawn_hooks, provider).await;
     for msg in ctx_messages.into_iter().rev() {
@@ -1748,9 +1920,14 @@ where
     let user_msg = Message::new(Role::User, vec![ContentBlock::Text(content)], None);
     let assistant_msg = Message::new(
             Role::Assistant,
-            vec![ContentBlock::Text(
-                "I will fully incorporate this information when generating my responses, and explicitly acknowledge relevant parts of the summary when answering questions.".to_string(),
-            )],
+            vec![
+                ContentBlock::Text(
+                    "I will fully incorporate this information when generating my responses, and explicitly acknowledge relevant parts of the summary when answering questions.".to_string(),
+                ),
+                // The context only changes along with the agent's configuration, so everything up
+                // to here can be cached.
+                ContentBlock::CachePoint,
+            ],
             None,
         );
 
@@ -1961,11 +2138,16 @@ where
     return_val
 }
 
-fn hook_matches_tool(config: &HookConfig, tool: &Tool) -> bool {
+fn hook_matches_tool(config: &HookConfig, tool: &Tool, tool_input: &serde_json::Value) -> bool {
     let Some(matcher) = config.matcher() else {
         // No matcher -> hook runs for all tools.
         return true;
     };
+    // Conditions on the tool input may follow the tool name, e.g. `fs_write[path=**/migrations/**]`
+    let (matcher, conditions) = parse_hook_matcher(matcher);
+    if !conditions.iter().all(|c| c.matches(tool_input)) {
+        return false;
+    }
     let Ok(kind) = ToolNameKind::parse(matcher) else {
         return false;
     };
@@ -2116,9 +2298,18 @@ impl ExecutingHooks {
             .collect()
     }
 
-    fn has_failure_exit_code_for_tool(&self, tool_use_id: impl AsRef<str>) -> Option<&ExecutingHook> {
+    /// Returns the hook blocking the given tool use, according to the aggregation policy.
+    fn has_failure_exit_code_for_tool(
+        &self,
+        tool_use_id: impl AsRef<str>,
+        aggregation: HookAggregation,
+    ) -> Option<&ExecutingHook> {
         self.hooks.iter().find(|hook| {
-            hook.exit_code().is_some_and(|code| code == 2)
+            let blocks = match aggregation {
+                HookAggregation::AnyBlock => hook.exit_code().is_some_and(|code| code == 2),
+                HookAggregation::AllMustPass => hook.result.as_ref().is_some_and(|res| !res.is_success()),
+            };
+            blocks
                 && hook
                     .tool_use_block
                     .as_ref()
@@ -2172,6 +2363,51 @@ mod tests {
     use super::*;
     use crate::util::test::TestBase;
 
+    #[test]
+    fn test_hook_aggregation() {
+        let executing_hook = |tool_use_id: &str, result: serde_json::Value| ExecutingHook {
+            id: serde_json::from_value(serde_json::json!({
+                "hook": { "trigger": "preToolUse", "config": { "command": "check" } },
+                "toolContext": null
+            }))
+            .unwrap(),
+            tool_use_block: Some(ToolUseBlock {
+                tool_use_id: tool_use_id.to_string(),
+                name: "fs_write".to_string(),
+                input: serde_json::json!({}),
+            }),
+            tool: None,
+            result: Some(serde_json::from_value(serde_json::json!({ "Command": result })).unwrap()),
+        };
+        let hooks = ExecutingHooks {
+            hooks: vec![
+                executing_hook("passed", serde_json::json!({ "Ok": { "exit_code": 0, "output": "" } })),
+                executing_hook(
+                    "failed",
+                    serde_json::json!({ "Ok": { "exit_code": 1, "output": "oops" } }),
+                ),
+                executing_hook("timed_out", serde_json::json!({ "Err": "command timed out" })),
+                executing_hook(
+                    "blocked",
+                    serde_json::json!({ "Ok": { "exit_code": 2, "output": "no" } }),
+                ),
+            ],
+            stage: HookStage::AgentSpawn,
+        };
+        let blocked = |tool_use_id: &str, aggregation: HookAggregation| {
+            hooks.has_failure_exit_code_for_tool(tool_use_id, aggregation).is_some()
+        };
+
+        for tool_use_id in ["passed", "failed", "timed_out"] {
+            assert!(!blocked(tool_use_id, HookAggregation::AnyBlock), "{tool_use_id}");
+        }
+        assert!(blocked("blocked", HookAggregation::AnyBlock));
+        assert!(!blocked("passed", HookAggregation::AllMustPass));
+        for tool_use_id in ["failed", "timed_out", "blocked"] {
+            assert!(blocked(tool_use_id, HookAggregation::AllMustPass), "{tool_use_id}");
+        }
+    }
+
     #[tokio::test]
     async fn test_collect_resources() {
         let mut test_base = TestBase::new().await;
@@ -0,0 +1,880 @@
+//! Server side of the [Agent Client Protocol](https://agentclientprotocol.com) (ACP), which lets
+//! editors speaking ACP host the agent loop.
+//!
+//! The client and the agent exchange newline-delimited JSON-RPC 2.0 messages. The client calls
+//! `initialize`, `session/new` and `session/prompt`, and notifies `session/cancel` to interrupt a
+//! prompt. While a prompt runs, the agent streams its progress as `session/update` notifications,
+//! and asks the client to approve tool uses with `session/request_permission` requests.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::atomic::{
+    AtomicU64,
+    Ordering,
+};
+use std::sync::{
+    Arc,
+    Mutex,
+};
+
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use serde_json::{
+    Value,
+    json,
+};
+use tokio::io::{
+    AsyncBufReadExt,
+    AsyncRead,
+    AsyncWrite,
+    AsyncWriteExt,
+    BufReader,
+};
+use tokio::sync::{
+    broadcast,
+    mpsc,
+    oneshot,
+};
+use tracing::{
+    debug,
+    error,
+    warn,
+};
+use uuid::Uuid;
+
+use super::{
+    AgentEvent,
+    AgentStopReason,
+    ApprovalResult,
+    ContentChunk,
+    InternalEvent,
+    SendApprovalResultArgs,
+    SendPromptArgs,
+    ToolCall,
+    UpdateEvent,
+};
+use crate::agent::task_executor::{
+    TaskExecutorEvent,
+    ToolExecutorResult,
+};
+use crate::agent::tools::{
+    BuiltInTool,
+    Tool,
+    ToolExecutionOutputItem,
+    ToolExecutionResult,
+    ToolKind,
+};
+use crate::agent::{
+    Agent,
+    AgentHandle,
+};
+
+/// Version of the protocol implemented by the agent
+pub const PROTOCOL_VERSION: u16 = 1;
+
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const INTERNAL_ERROR: i64 = -32603;
+
+const ALLOW_ONCE_OPTION_ID: &str = "allow_once";
+const REJECT_ONCE_OPTION_ID: &str = "reject_once";
+
+/// Creates the agents of the sessions requested by the client.
+pub trait SessionFactory: Send + Sync + 'static {
+    /// Creates the agent of a new session. The agent is spawned by the server.
+    fn new_agent(&self, args: &NewSessionRequest) -> Pin<Box<dyn Future<Output = eyre::Result<Agent>> + Send + '_>>;
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InitializeRequest {
+    pub protocol_version: u16,
+    #[serde(default)]
+    pub client_capabilities: Value,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InitializeResponse {
+    pub protocol_version: u16,
+    pub agent_capabilities: AgentCapabilities,
+    pub auth_methods: Vec<Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentCapabilities {
+    pub load_session: bool,
+    pub prompt_capabilities: PromptCapabilities,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PromptCapabilities {
+    pub image: bool,
+    pub audio: bool,
+    pub embedded_context: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NewSessionRequest {
+    /// Working directory of the session
+    pub cwd: PathBuf,
+    /// MCP servers the client asks the agent to connect to
+    #[serde(default)]
+    pub mcp_servers: Vec<Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NewSessionResponse {
+    pub session_id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PromptRequest {
+    pub session_id: String,
+    pub prompt: Vec<AcpContentBlock>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PromptResponse {
+    pub stop_reason: StopReason,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StopReason {
+    EndTurn,
+    MaxTokens,
+    MaxTurnRequests,
+    Refusal,
+    Cancelled,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CancelNotification {
+    pub session_id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionNotification {
+    pub session_id: String,
+    pub update: SessionUpdate,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "sessionUpdate", rename_all = "snake_case")]
+pub enum SessionUpdate {
+    UserMessageChunk { content: AcpContentBlock },
+    AgentMessageChunk { content: AcpContentBlock },
+    AgentThoughtChunk { content: AcpContentBlock },
+    ToolCall(AcpToolCall),
+    ToolCallUpdate(AcpToolCallUpdate),
+}
+
+/// Content of prompts and messages. Content types the agent doesn't support are ignored.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AcpContentBlock {
+    Text {
+        text: String,
+    },
+    ResourceLink {
+        uri: String,
+        #[serde(default)]
+        name: String,
+    },
+    /// A resource embedded by the client, such as the content of a file mentioned in the prompt
+    Resource {
+        resource: EmbeddedResource,
+    },
+    #[serde(other)]
+    Unsupported,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmbeddedResource {
+    pub uri: String,
+    /// Absent for binary resources
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+}
+
+impl AcpContentBlock {
+    fn text(text: impl Into<String>) -> Self {
+        Self::Text { text: text.into() }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AcpToolCall {
+    pub tool_call_id: String,
+    pub title: String,
+    pub kind: AcpToolKind,
+    pub status: ToolCallStatus,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub locations: Vec<ToolCallLocation>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw_input: Option<Value>,
+}
+
+/// Update of a tool call, whose absent fields are left unchanged
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AcpToolCallUpdate {
+    pub tool_call_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<ToolCallStatus>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content: Option<Vec<ToolCallContent>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AcpToolKind {
+    Read,
+    Edit,
+    Delete,
+    Move,
+    Search,
+    Execute,
+    Think,
+    Fetch,
+    Other,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolCallStatus {
+    Pending,
+    InProgress,
+    Completed,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ToolCallContent {
+    Content { content: AcpContentBlock },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolCallLocation {
+    pub path: PathBuf,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RequestPermissionRequest {
+    pub session_id: String,
+    pub tool_call: AcpToolCallUpdate,
+    pub options: Vec<PermissionOption>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PermissionOption {
+    pub option_id: String,
+    pub name: String,
+    pub kind: PermissionOptionKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PermissionOptionKind {
+    AllowOnce,
+    AllowAlways,
+    RejectOnce,
+    RejectAlways,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RequestPermissionResponse {
+    pub outcome: RequestPermissionOutcome,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum RequestPermissionOutcome {
+    /// The prompt was cancelled before the user answered
+    Cancelled,
+    Selected {
+        #[serde(rename = "optionId")]
+        option_id: String,
+    },
+}
+
+/// Serves the client connected through `reader` and `writer` until it disconnects.
+pub async fn serve<F, R, W>(factory: F, reader: R, writer: W) -> eyre::Result<()>
+where
+    F: SessionFactory,
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin + Send + 'static,
+{
+    let (outgoing_tx, outgoing_rx) = mpsc::unbounded_channel();
+    tokio::spawn(write_messages(writer, outgoing_rx));
+
+    let mut server = Server {
+        factory,
+        connection: Connection::new(outgoing_tx),
+        sessions: HashMap::new(),
+    };
+    let mut lines = BufReader::new(reader).lines();
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        server.handle_message(&line).await;
+    }
+    debug!("client disconnected");
+    Ok(())
+}
+
+async fn write_messages<W: AsyncWrite + Unpin>(mut writer: W, mut outgoing: mpsc::UnboundedReceiver<Value>) {
+    while let Some(message) = outgoing.recv().await {
+        let mut line = message.to_string();
+        line.push('\n');
+        if let Err(err) = async {
+            writer.write_all(line.as_bytes()).await?;
+            writer.flush().await
+        }
+        .await
+        {
+            error!(?err, "failed to write a message to the client");
+            break;
+        }
+    }
+}
+
+/// A JSON-RPC request, notification or response received from the client
+#[derive(Debug, Deserialize)]
+struct Message {
+    #[serde(default)]
+    id: Option<Value>,
+    #[serde(default)]
+    method: Option<String>,
+    #[serde(default)]
+    params: Value,
+    #[serde(default)]
+    result: Option<Value>,
+    #[serde(default)]
+    error: Option<Value>,
+}
+
+type RpcError = (i64, String);
+
+/// Senders of the results of the requests sent to the client, by request id
+type PendingRequests = Arc<Mutex<HashMap<u64, oneshot::Sender<Result<Value, Value>>>>>;
+
+/// Id of the `session/prompt` request waiting for the current turn of a session to stop
+type PendingPrompt = Arc<Mutex<Option<Value>>>;
+
+#[derive(Debug)]
+struct Session {
+    agent: AgentHandle,
+    pending_prompt: PendingPrompt,
+}
+
+struct Server<F> {
+    factory: F,
+    connection: Connection,
+    sessions: HashMap<String, Session>,
+}
+
+impl<F: SessionFactory> Server<F> {
+    async fn handle_message(&mut self, line: &str) {
+        let message = match serde_json::from_str::<Message>(line) {
+            Ok(message) => message,
+            Err(err) => {
+                self.connection
+                    .respond(Value::Null, Err((PARSE_ERROR, err.to_string())));
+                return;
+            },
+        };
+
+        let Some(method) = message.method else {
+            // A response to a request sent to the client
+            if let Some(id) = message.id {
+                let result = match message.error {
+                    Some(err) => Err(err),
+                    None => Ok(message.result.unwrap_or_default()),
+                };
+                self.connection.resolve(&id, result);
+            }
+            return;
+        };
+
+        let Some(id) = message.id else {
+            self.handle_notification(&method, message.params).await;
+            return;
+        };
+        if method == "session/prompt" {
+            // Responded to once the turn stops
+            if let Err(err) = self.handle_prompt(id.clone(), message.params).await {
+                self.connection.respond(id, Err(err));
+            }
+            return;
+        }
+        let result = self.handle_request(&method, message.params).await;
+        self.connection.respond(id, result);
+    }
+
+    async fn handle_request(&mut self, method: &str, params: Value) -> Result<Value, RpcError> {
+        match method {
+            "initialize" => {
+                let _: InitializeRequest = parse_params(params)?;
+                to_result(InitializeResponse {
+                    protocol_version: PROTOCOL_VERSION,
+                    agent_capabilities: AgentCapabilities {
+                        load_session: false,
+                        prompt_capabilities: PromptCapabilities {
+                            image: false,
+                            audio: false,
+                            embedded_context: true,
+                        },
+                    },
+                    auth_methods: Vec::new(),
+                })
+            },
+            // Authentication is handled by the backend of the agent
+            "authenticate" => Ok(json!({})),
+            "session/new" => {
+                let args: NewSessionRequest = parse_params(params)?;
+                let session_id = self.new_session(args).await.map_err(|err| {
+                    error!(?err, "failed to create a session");
+                    (INTERNAL_ERROR, err.to_string())
+                })?;
+                to_result(NewSessionResponse { session_id })
+            },
+            method => Err((METHOD_NOT_FOUND, format!("Unknown method {method}"))),
+        }
+    }
+
+    async fn handle_notification(&mut self, method: &str, params: Value) {
+        match method {
+            "session/cancel" => {
+                let Ok(args) = parse_params::<CancelNotification>(params) else {
+                    warn!("received an invalid cancel notification");
+                    return;
+                };
+                if let Some(session) = self.sessions.get(&args.session_id) {
+                    if let Err(err) = session.agent.cancel().await {
+                        error!(?err, "failed to cancel the session");
+                    }
+                }
+            },
+            method => debug!(method, "ignoring an unknown notification"),
+        }
+    }
+
+    async fn new_session(&mut self, args: NewSessionRequest) -> eyre::Result<String> {
+        if !args.mcp_servers.is_empty() {
+            warn!(?args.mcp_servers, "ignoring the MCP servers of the client");
+        }
+        let mut agent = self.factory.new_agent(&args).await?;
+        agent.set_working_directory(args.cwd);
+        let mut agent = agent.spawn();
+
+        // Prompts are only accepted once the agent is initialized
+        loop {
+            match agent.recv().await {
+                Ok(AgentEvent::Initialized) => break,
+                Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => (),
+                Err(broadcast::error::RecvError::Closed) => eyre::bail!("The agent exited during initialization"),
+            }
+        }
+
+        let session_id = Uuid::new_v4().to_string();
+        let session = Session {
+            agent: agent.clone(),
+            pending_prompt: PendingPrompt::default(),
+        };
+        tokio::spawn(forward_events(
+            session_id.clone(),
+            agent,
+            self.connection.clone(),
+            session.pending_prompt.clone(),
+        ));
+        self.sessions.insert(session_id.clone(), session);
+        Ok(session_id)
+    }
+
+    async fn handle_prompt(&mut self, id: Value, params: Value) -> Result<(), RpcError> {
+        let args: PromptRequest = parse_params(params)?;
+        let Some(session) = self.sessions.get(&args.session_id) else {
+            return Err((INVALID_PARAMS, format!("Unknown session {}", args.session_id)));
+        };
+
+        let content = args.prompt.into_iter().filter_map(prompt_chunk).collect::<Vec<_>>();
+        if content.is_empty() {
+            return Err((INVALID_PARAMS, "The prompt has no supported content".to_string()));
+        }
+
+        {
+            let mut pending_prompt = session.pending_prompt.lock().expect("lock should not be poisoned");
+            if pending_prompt.is_some() {
+                return Err((INVALID_PARAMS, "The session is already running a prompt".to_string()));
+            }
+            *pending_prompt = Some(id);
+        }
+        let result = session
+            .agent
+            .send_prompt(SendPromptArgs {
+                content,
+                should_continue_turn: None,
+            })
+            .await;
+        if let Err(err) = result {
+            session
+                .pending_prompt
+                .lock()
+                .expect("lock should not be poisoned")
+                .take();
+            return Err((INTERNAL_ERROR, err.to_string()));
+        }
+        Ok(())
+    }
+}
+
+/// Forwards the events of a session to the client until the agent exits.
+async fn forward_events(
+    session_id: String,
+    mut agent: AgentHandle,
+    connection: Connection,
+    pending_prompt: PendingPrompt,
+) {
+    let notify = |update: SessionUpdate| {
+        connection.notify("session/update", SessionNotification {
+            session_id: session_id.clone(),
+            update,
+        });
+    };
+
+    loop {
+        let evt = match agent.recv().await {
+            Ok(evt) => evt,
+            Err(broadcast::error::RecvError::Lagged(count)) => {
+                warn!(count, "dropped agent events");
+                continue;
+            },
+            Err(broadcast::error::RecvError::Closed) => break,
+        };
+        match evt {
+            AgentEvent::Update(update) => {
+                if let Some(update) = session_update(update) {
+                    notify(update);
+                }
+            },
+            AgentEvent::Internal(InternalEvent::TaskExecutor(evt)) => match *evt {
+                TaskExecutorEvent::ToolExecutionStart(start) => {
+                    notify(SessionUpdate::ToolCallUpdate(AcpToolCallUpdate {
+                        tool_call_id: start.id.tool_use_id().to_string(),
+                        status: Some(ToolCallStatus::InProgress),
+                        content: None,
+                    }));
+                },
+                TaskExecutorEvent::ToolExecutionEnd(end) => {
+                    let tool_call_id = end.id.tool_use_id().to_string();
+                    notify(SessionUpdate::ToolCallUpdate(match end.result {
+                        ToolExecutorResult::Completed { result, .. } => tool_call_finished(tool_call_id, result),
+                        ToolExecutorResult::Cancelled { .. } => AcpToolCallUpdate {
+                            tool_call_id,
+                            status: Some(ToolCallStatus::Failed),
+                            content: None,
+                        },
+                    }));
+                },
+                _ => (),
+            },
+            AgentEvent::ApprovalRequest { id, .. } => {
+                tokio::spawn(request_permission(
+                    session_id.clone(),
+                    id,
+                    agent.clone(),
+                    connection.clone(),
+                ));
+            },
+            AgentEvent::Stop(reason) => {
+                let Some(id) = pending_prompt.lock().expect("lock should not be poisoned").take() else {
+                    continue;
+                };
+                let stop_reason = match reason {
+                    AgentStopReason::EndTurn => StopReason::EndTurn,
+                    AgentStopReason::MaxTurnRequests => StopReason::MaxTurnRequests,
+                    AgentStopReason::Cancelled => StopReason::Cancelled,
+                    AgentStopReason::Error(err) => {
+                        connection.respond(id, Err((INTERNAL_ERROR, err.to_string())));
+                        continue;
+                    },
+                };
+                connection.respond(id, to_result(PromptResponse { stop_reason }));
+            },
+            _ => (),
+        }
+    }
+    debug!(session_id, "agent exited");
+}
+
+/// Asks the client to approve the tool use `tool_use_id`, and sends its decision to the agent.
+async fn request_permission(session_id: String, tool_use_id: String, agent: AgentHandle, connection: Connection) {
+    let request = RequestPermissionRequest {
+        session_id: session_id.clone(),
+        tool_call: AcpToolCallUpdate {
+            tool_call_id: tool_use_id.clone(),
+            ..Default::default()
+        },
+        options: vec![
+            PermissionOption {
+                option_id: ALLOW_ONCE_OPTION_ID.to_string(),
+                name: "Allow".to_string(),
+                kind: PermissionOptionKind::AllowOnce,
+            },
+            PermissionOption {
+                option_id: REJECT_ONCE_OPTION_ID.to_string(),
+                name: "Reject".to_string(),
+                kind: PermissionOptionKind::RejectOnce,
+            },
+        ],
+    };
+    let response = connection.request("session/request_permission", request).await;
+    let approved = match response.map(serde_json::from_value::<RequestPermissionResponse>) {
+        Ok(Ok(response)) => matches!(
+            response.outcome,
+            RequestPermissionOutcome::Selected { option_id } if option_id == ALLOW_ONCE_OPTION_ID
+        ),
+        Ok(Err(err)) => {
+            warn!(?err, "received an invalid permission response");
+            false
+        },
+        Err(err) => {
+            warn!(?err, "the client failed to answer the permission request");
+            false
+        },
+    };
+
+    let result = agent
+        .send_tool_use_approval_result(SendApprovalResultArgs {
+            id: tool_use_id.clone(),
+            result: if approved {
+                ApprovalResult::Approve
+            } else {
+                ApprovalResult::Deny { reason: None }
+            },
+        })
+        .await;
+    if let Err(err) = result {
+        // Expected when the prompt was cancelled while waiting for the client
+        debug!(?err, "failed to send the approval result");
+    }
+    if !approved {
+        connection.notify("session/update", SessionNotification {
+            session_id,
+            update: SessionUpdate::ToolCallUpdate(AcpToolCallUpdate {
+                tool_call_id: tool_use_id,
+                status: Some(ToolCallStatus::Failed),
+                content: None,
+            }),
+        });
+    }
+}
+
+fn session_update(update: UpdateEvent) -> Option<SessionUpdate> {
+    match update {
+        UpdateEvent::UserContent(chunk) => {
+            content_block(chunk).map(|content| SessionUpdate::UserMessageChunk { content })
+        },
+        UpdateEvent::AgentContent(chunk) => {
+            content_block(chunk).map(|content| SessionUpdate::AgentMessageChunk { content })
+        },
+        UpdateEvent::AgentThought(chunk) => {
+            content_block(chunk).map(|content| SessionUpdate::AgentThoughtChunk { content })
+        },
+        UpdateEvent::ToolCall(tool_call) => Some(SessionUpdate::ToolCall(tool_call.into())),
+        // Doesn't tell which tool call it belongs to
+        UpdateEvent::ToolCallUpdate { .. } => None,
+        UpdateEvent::ToolCallFinished { tool_call, result } => Some(SessionUpdate::ToolCallUpdate(match result {
+            super::ToolCallResult::Success(output) => tool_call_finished(tool_call.id, Ok(output)),
+            super::ToolCallResult::Error(err) => tool_call_finished(tool_call.id, Err(err)),
+            super::ToolCallResult::Cancelled => AcpToolCallUpdate {
+                tool_call_id: tool_call.id,
+                status: Some(ToolCallStatus::Failed),
+                content: None,
+            },
+        })),
+    }
+}
+
+impl From<ToolCall> for AcpToolCall {
+    fn from(tool_call: ToolCall) -> Self {
+        Self {
+            title: tool_call
+                .tool
+                .tool_use_purpose
+                .clone()
+                .unwrap_or(tool_call.tool_use_block.name.clone()),
+            kind: tool_kind(&tool_call.tool),
+            status: ToolCallStatus::Pending,
+            locations: tool_locations(&tool_call.tool)
+                .into_iter()
+                .map(|path| ToolCallLocation { path: path.into() })
+                .collect(),
+            raw_input: Some(tool_call.tool_use_block.input),
+            tool_call_id: tool_call.id,
+        }
+    }
+}
+
+fn tool_kind(tool: &Tool) -> AcpToolKind {
+    match tool.kind() {
+        ToolKind::BuiltIn(built_in) => match built_in {
+            BuiltInTool::FileRead(_) | BuiltInTool::Ls(_) | BuiltInTool::ImageRead(_) => AcpToolKind::Read,
+            BuiltInTool::FileWrite(_) | BuiltInTool::Mkdir(_) => AcpToolKind::Edit,
+            BuiltInTool::Grep(_) => AcpToolKind::Search,
+            BuiltInTool::ExecuteCmd(_) => AcpToolKind::Execute,
+            BuiltInTool::Introspect(_) | BuiltInTool::SpawnSubagent => AcpToolKind::Other,
+        },
+        ToolKind::Mcp(_) => AcpToolKind::Other,
+    }
+}
+
+/// Paths of the files a tool use reads or edits, which clients can follow along
+fn tool_locations(tool: &Tool) -> Vec<String> {
+    match tool.kind() {
+        ToolKind::BuiltIn(BuiltInTool::FileRead(fs_read)) => fs_read.ops.iter().map(|op| op.path.clone()).collect(),
+        ToolKind::BuiltIn(BuiltInTool::FileWrite(fs_write)) => vec![fs_write.path().to_string()],
+        ToolKind::BuiltIn(BuiltInTool::Ls(ls)) => vec![ls.path.clone()],
+        _ => Vec::new(),
+    }
+}
+
+fn tool_call_finished(tool_call_id: String, result: ToolExecutionResult) -> AcpToolCallUpdate {
+    let (status, text) = match result {
+        Ok(output) => (
+            ToolCallStatus::Completed,
+            output
+                .items
+                .iter()
+                .filter_map(|item| match item {
+                    ToolExecutionOutputItem::Text(text) => Some(text.clone()),
+                    ToolExecutionOutputItem::Json(value) => Some(value.to_string()),
+                    ToolExecutionOutputItem::Image(_) => None,
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+        ),
+        Err(err) => (ToolCallStatus::Failed, err.to_string()),
+    };
+    AcpToolCallUpdate {
+        tool_call_id,
+        status: Some(status),
+        content: (!text.is_empty()).then(|| {
+            vec![ToolCallContent::Content {
+                content: AcpContentBlock::text(text),
+            }]
+        }),
+    }
+}
+
+fn content_block(chunk: ContentChunk) -> Option<AcpContentBlock> {
+    match chunk {
+        ContentChunk::Text(text) => Some(AcpContentBlock::text(text)),
+        ContentChunk::Image(_) | ContentChunk::ResourceLink(_) => None,
+    }
+}
+
+fn prompt_chunk(block: AcpContentBlock) -> Option<ContentChunk> {
+    match block {
+        AcpContentBlock::Text { text } => Some(ContentChunk::Text(text)),
+        AcpContentBlock::ResourceLink { uri, .. } => Some(ContentChunk::Text(uri)),
+        AcpContentBlock::Resource { resource } => resource
+            .text
+            .map(|text| ContentChunk::Text(format!("{}:\n{}", resource.uri, text))),
+        AcpContentBlock::Unsupported => None,
+    }
+}
+
+fn parse_params<T: serde::de::DeserializeOwned>(params: Value) -> Result<T, RpcError> {
+    serde_json::from_value(params).map_err(|err| (INVALID_PARAMS, err.to_string()))
+}
+
+fn to_result(value: impl Serialize) -> Result<Value, RpcError> {
+    serde_json::to_value(value).map_err(|err| (INTERNAL_ERROR, err.to_string()))
+}
+
+/// Sends messages to the client, and routes its responses to the requests the agent sent to it.
+#[derive(Debug, Clone)]
+struct Connection {
+    outgoing: mpsc::UnboundedSender<Value>,
+    pending_requests: PendingRequests,
+    next_request_id: Arc<AtomicU64>,
+}
+
+impl Connection {
+    fn new(outgoing: mpsc::UnboundedSender<Value>) -> Self {
+        Self {
+            outgoing,
+            pending_requests: Default::default(),
+            next_request_id: Default::default(),
+        }
+    }
+
+    fn send(&self, message: Value) {
+        if self.outgoing.send(message).is_err() {
+            debug!("the client connection has closed");
+        }
+    }
+
+    fn notify(&self, method: &str, params: impl Serialize) {
+        self.send(json!({ "jsonrpc": "2.0", "method": method, "params": params }));
+    }
+
+    fn respond(&self, id: Value, result: Result<Value, RpcError>) {
+        self.send(match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err((code, message)) => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": code, "message": message },
+            }),
+        });
+    }
+
+    /// Sends a request to the client, returning its result or error.
+    async fn request(&self, method: &str, params: impl Serialize) -> Result<Value, Value> {
+        let id = self.next_request_id.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = oneshot::channel();
+        self.pending_requests
+            .lock()
+            .expect("lock should not be poisoned")
+            .insert(id, tx);
+        self.send(json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }));
+        rx.await.unwrap_or(Err(json!("the connection closed")))
+    }
+
+    fn resolve(&self, id: &Value, result: Result<Value, Value>) {
+        let tx = id.as_u64().and_then(|id| {
+            self.pending_requests
+                .lock()
+                .expect("lock should not be poisoned")
+                .remove(&id)
+        });
+        match tx {
+            Some(tx) => {
+                let _ = tx.send(result);
+            },
+            None => warn!(?id, "received a response to an unknown request"),
+        }
+    }
+}
@@ -1,3 +1,5 @@
+pub mod acp;
+
 use std::collections::HashMap;
 
 use serde::{
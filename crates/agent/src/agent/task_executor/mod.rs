@@ -1,9 +1,14 @@
 use std::collections::HashMap;
+use std::path::{
+    Path,
+    PathBuf,
+};
 use std::pin::Pin;
 use std::process::Stdio;
 use std::time::{
     Duration,
     Instant,
+    SystemTime,
 };
 
 use bstr::ByteSlice as _;
@@ -28,12 +33,18 @@ use crate::agent::agent_config::definitions::{
     HookTrigger,
 };
 use crate::agent::agent_loop::types::ToolUseBlock;
+use crate::agent::hooks::{
+    HookJsonOutput,
+    resolve_hook_env,
+};
 use crate::agent::tools::{
     Tool,
     ToolExecutionOutput,
     ToolExecutionResult,
     ToolState,
 };
+use crate::agent::util::path::expand_path;
+use crate::agent::util::providers::RealProvider;
 use crate::agent::util::truncate_safe;
 
 #[derive(Debug, Clone)]
@@ -57,7 +68,10 @@ pub struct TaskExecutor {
     executing_tools: HashMap<ToolExecutionId, ExecutingTool>,
     executing_hooks: HashMap<HookExecutionId, ExecutingHook>,
 
-    hooks_cache: HashMap<Hook, CachedHook>,
+    /// Results of successful hooks, see [BaseHookConfig::cache_ttl_seconds].
+    ///
+    /// [BaseHookConfig::cache_ttl_seconds]: crate::agent::agent_config::definitions::BaseHookConfig::cache_ttl_seconds
+    hooks_cache: HashMap<HookExecutionId, CachedHook>,
 }
 
 impl TaskExecutor {
@@ -106,7 +120,7 @@ impl TaskExecutor {
     ///
     /// Note that [HookExecutionId] actually contains the hook config itself.
     pub async fn start_hook_execution(&mut self, req: StartHookExecution) {
-        let _ = self.execute_request_tx.send(ExecuteRequest::Hook(req)).await;
+        let _ = self.execute_request_tx.send(ExecuteRequest::Hook(Box::new(req))).await;
     }
 
     /// Cancels an executing tool
@@ -129,7 +143,7 @@ impl TaskExecutor {
         debug!(?req, "background executor received new request");
         match req {
             ExecuteRequest::Tool(t) => self.handle_tool_execute_request(t),
-            ExecuteRequest::Hook(h) => self.handle_hook_execute_request(h),
+            ExecuteRequest::Hook(h) => self.handle_hook_execute_request(*h),
         };
     }
 
@@ -168,7 +182,7 @@ impl TaskExecutor {
 
     fn handle_hook_execute_request(&mut self, req: StartHookExecution) {
         // Handle cached hooks immediately.
-        if let Some(cached) = self.get_cached_hook(&req.id.hook) {
+        if let Some(cached) = self.get_cached_hook(&req.id) {
             debug!(?cached, "found cached hook");
             self.event_buf
                 .push(TaskExecutorEvent::CachedHookRun(CachedHookRunEvent {
@@ -179,6 +193,16 @@ impl TaskExecutor {
         }
 
         let req_id = req.id.clone();
+        let cwd = std::env::current_dir()
+            .expect("current dir exists")
+            .to_string_lossy()
+            .to_string();
+        let referenced_files = match &req.id.hook.config {
+            HookConfig::ShellCommand(command) => {
+                file_modified_times(referenced_files(&command.command, &hook_cwd(command, &cwd)))
+            },
+            HookConfig::Tool(_) => Vec::new(),
+        };
 
         // Otherwise, run the hook on another task.
         let result_tx = self.execute_result_tx.clone();
@@ -189,16 +213,13 @@ impl TaskExecutor {
         match req.id.hook.config.clone() {
             HookConfig::ShellCommand(command) => {
                 tokio::spawn(async move {
-                    let cwd = std::env::current_dir()
-                        .expect("current dir exists")
-                        .to_string_lossy()
-                        .to_string();
                     let fut = run_command_hook(
                         req.id.hook.trigger,
                         command.clone(),
                         &cwd,
                         req.prompt,
                         req.id.tool_context,
+                        req.event_input,
                     );
                     tokio::select! {
                         _ = cancel_token_clone.cancelled() => {
@@ -229,23 +250,45 @@ impl TaskExecutor {
             cancel_token,
             start_instant: Instant::now(),
             start_time,
+            referenced_files,
         });
     }
 
-    fn get_cached_hook(&self, hook: &Hook) -> Option<HookResult> {
-        self.hooks_cache.get(hook).and_then(|o| {
-            if let Some(expiry) = o.expiry {
-                if Instant::now() < expiry {
-                    Some(o.result.clone())
-                } else {
-                    None
-                }
-            } else {
-                Some(o.result.clone())
-            }
+    fn get_cached_hook(&self, id: &HookExecutionId) -> Option<HookResult> {
+        self.hooks_cache.get(id).and_then(|o| {
+            let expired = o.expiry.is_some_and(|expiry| Instant::now() >= expiry);
+            let files_changed = o
+                .referenced_files
+                .iter()
+                .any(|(path, modified)| file_modified_time(path) != *modified);
+            (!expired && !files_changed).then(|| o.result.clone())
         })
     }
 
+    /// Caches the result of a hook that completed successfully, according to its
+    /// `cache_ttl_seconds`. Agent spawn hooks are cached for the whole session.
+    fn cache_hook_result(
+        &mut self,
+        id: &HookExecutionId,
+        result: &HookResult,
+        referenced_files: Vec<(PathBuf, Option<SystemTime>)>,
+    ) {
+        if !result.is_success() {
+            return;
+        }
+        let ttl = Duration::from_secs(id.hook.config.opts().cache_ttl_seconds);
+        let expiry = match id.hook.trigger {
+            HookTrigger::AgentSpawn => None,
+            _ if ttl.is_zero() => return,
+            _ => Some(Instant::now() + ttl),
+        };
+        self.hooks_cache.insert(id.clone(), CachedHook {
+            result: result.clone(),
+            expiry,
+            referenced_files,
+        });
+    }
+
     async fn handle_execute_result(&mut self, result: ExecutorResult) {
         match result {
             ExecutorResult::Tool(result) => {
@@ -268,6 +311,9 @@ impl TaskExecutor {
             ExecutorResult::Hook(result) => {
                 debug_assert!(self.executing_hooks.contains_key(result.id()));
                 if let Some(x) = self.executing_hooks.remove(result.id()) {
+                    if let HookExecutorResult::Completed { id, result, .. } = &result {
+                        self.cache_hook_result(id, result, x.referenced_files);
+                    }
                     self.event_buf
                         .push(TaskExecutorEvent::HookExecutionEnd(HookExecutionEndEvent {
                             id: result.id().clone(),
@@ -291,7 +337,7 @@ impl Default for TaskExecutor {
 #[derive(Debug)]
 pub enum ExecuteRequest {
     Tool(StartToolExecution),
-    Hook(StartHookExecution),
+    Hook(Box<StartHookExecution>),
 }
 
 /// A request to start executing a tool
@@ -323,6 +369,8 @@ pub struct StartHookExecution {
     pub id: HookExecutionId,
     /// The user prompt. Passed to the hook as context if available.
     pub prompt: Option<String>,
+    /// Fields added to the hook event, e.g. the error given to [HookTrigger::OnError] hooks.
+    pub event_input: Option<serde_json::Value>,
 }
 
 #[derive(Debug)]
@@ -339,6 +387,8 @@ struct ExecutingHook {
     cancel_token: CancellationToken,
     start_instant: Instant,
     start_time: DateTime<Utc>,
+    /// Files referenced by the hook command, with their modification time when the hook started.
+    referenced_files: Vec<(PathBuf, Option<SystemTime>)>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -491,6 +541,8 @@ pub struct Hook {
 struct CachedHook {
     result: HookResult,
     expiry: Option<Instant>,
+    /// The cached result is stale once any of these files is modified, created or deleted.
+    referenced_files: Vec<(PathBuf, Option<SystemTime>)>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -527,6 +579,14 @@ impl HookResult {
             _ => None,
         }
     }
+
+    /// Returns the error of a command hook that failed to run to completion, e.g. on timeout.
+    pub fn error(&self) -> Option<&str> {
+        match self {
+            HookResult::Command(Err(err)) => Some(err),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -564,33 +624,78 @@ impl From<(&ToolUseBlock, &Tool, &serde_json::Value)> for ToolContext {
     }
 }
 
+/// Working directory of a command hook, see [CommandHook::cwd].
+fn hook_cwd(config: &CommandHook, cwd: &str) -> PathBuf {
+    match &config.cwd {
+        Some(dir) => Path::new(cwd).join(expand_path(dir, &RealProvider).unwrap_or(dir.into()).as_ref()),
+        None => PathBuf::from(cwd),
+    }
+}
+
+/// Returns the files referenced by the words of a hook command, e.g. the script it runs or the
+/// files it reads, resolved against the working directory of the hook. Words that look like file
+/// paths are kept even if the file doesn't exist yet, so that creating it is noticed.
+pub fn referenced_files(command: &str, cwd: &Path) -> Vec<PathBuf> {
+    let words = shlex::split(command).unwrap_or_else(|| command.split_whitespace().map(str::to_string).collect());
+    words
+        .iter()
+        // Redirections and options may be attached to the path, e.g. `<input.txt` or `--file=a.txt`
+        .map(|word| word.trim_start_matches(['<', '>']))
+        .map(|word| word.split_once('=').map_or(word, |(_, value)| value))
+        .filter(|word| !word.is_empty())
+        .filter_map(|word| {
+            let path = cwd.join(expand_path(word, &RealProvider).ok()?.as_ref());
+            let looks_like_file = word.contains('/') || Path::new(word).extension().is_some();
+            (path.is_file() || (looks_like_file && !path.exists())).then_some(path)
+        })
+        .collect()
+}
+
+fn file_modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+fn file_modified_times(paths: Vec<PathBuf>) -> Vec<(PathBuf, Option<SystemTime>)> {
+    paths
+        .into_iter()
+        .map(|path| {
+            let modified = file_modified_time(&path);
+            (path, modified)
+        })
+        .collect()
+}
+
 async fn run_command_hook(
     trigger: HookTrigger,
     config: CommandHook,
     cwd: &str,
     prompt: Option<String>,
     tool_context: Option<ToolContext>,
+    event_input: Option<serde_json::Value>,
 ) -> (Result<CommandResult, String>, Duration) {
     let start_time = Instant::now();
+    let env = match resolve_hook_env(&config.env, &RealProvider).await {
+        Ok(env) => env,
+        Err(err) => return (Err(err), start_time.elapsed()),
+    };
 
     let command = &config.command;
 
     #[cfg(unix)]
-    let mut cmd = tokio::process::Command::new("bash");
-    #[cfg(unix)]
-    let cmd = cmd
-        .arg("-c")
-        .arg(command)
-        .stdin(Stdio::piped())
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped());
-
-    #[cfg(windows)]
-    let mut cmd = tokio::process::Command::new("cmd");
+    let (shell, shell_arg) = (config.shell.as_deref().unwrap_or("bash"), "-c");
     #[cfg(windows)]
+    let (shell, shell_arg) = match config.shell.as_deref() {
+        Some(shell) => (shell, "-c"),
+        None => ("cmd", "/C"),
+    };
+    let hook_cwd = hook_cwd(&config, cwd);
+
+    let mut cmd = tokio::process::Command::new(shell);
     let cmd = cmd
-        .arg("/C")
+        .arg(shell_arg)
         .arg(command)
+        .current_dir(hook_cwd)
+        .envs(env)
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
         .stderr(Stdio::piped());
@@ -619,6 +724,11 @@ async fn run_command_hook(
             hook_input["tool_response"] = response;
         }
     }
+    if let Some(serde_json::Value::Object(fields)) = event_input {
+        for (key, value) in fields {
+            hook_input[key] = value;
+        }
+    }
     let json_input = serde_json::to_string(&hook_input).unwrap_or_default();
 
     // Build a future for hook command w/ the JSON input passed in through STDIN
@@ -636,12 +746,20 @@ async fn run_command_hook(
     // Run with timeout
     let result = match tokio::time::timeout(timeout, command_future).await {
         Ok(Ok(output)) => {
-            let exit_code = output.status.code().unwrap_or(-1);
-            let raw_output = if exit_code == 0 {
+            let mut exit_code = output.status.code().unwrap_or(-1);
+            let mut raw_output = if exit_code == 0 {
                 output.stdout.to_str_lossy()
             } else {
                 output.stderr.to_str_lossy()
             };
+            // Structured output is handled like the exit codes, see [HookJsonOutput]
+            if let Some(json) = (exit_code == 0).then(|| HookJsonOutput::parse(&raw_output)).flatten() {
+                (exit_code, raw_output) = if json.is_block() {
+                    (2, json.reason.unwrap_or_default().into())
+                } else {
+                    (0, json.additional_context.unwrap_or_default().into())
+                };
+            }
             let formatted_output = format!(
                 "{}{}",
                 truncate_safe(&raw_output, config.opts.max_output_size),
@@ -689,6 +807,100 @@ mod tests {
         }
     }
 
+    /// Runs the hook through the executor, returning its result and whether it came from the cache.
+    async fn run_hook(executor: &mut TaskExecutor, id: HookExecutionId) -> (HookResult, bool) {
+        executor
+            .start_hook_execution(StartHookExecution {
+                id,
+                prompt: None,
+                event_input: None,
+            })
+            .await;
+        let mut event_buf = Vec::new();
+        tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                executor.recv_next(&mut event_buf).await;
+                for ev in event_buf.drain(..) {
+                    match ev {
+                        TaskExecutorEvent::CachedHookRun(ev) => return (ev.result, true),
+                        TaskExecutorEvent::HookExecutionEnd(HookExecutionEndEvent {
+                            result: HookExecutorResult::Completed { result, .. },
+                            ..
+                        }) => return (result, false),
+                        _ => (),
+                    }
+                }
+            }
+        })
+        .await
+        .expect("hook should finish")
+    }
+
+    #[tokio::test]
+    async fn test_hook_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("status.txt");
+        std::fs::write(&file, "green").unwrap();
+        let id = |cache_ttl_seconds: u64| HookExecutionId {
+            hook: Hook {
+                trigger: HookTrigger::UserPromptSubmit,
+                config: serde_json::from_value(serde_json::json!({
+                    "command": format!("cat {}", file.display()),
+                    "cache_ttl_seconds": cache_ttl_seconds,
+                }))
+                .unwrap(),
+            },
+            tool_context: None,
+        };
+        let mut executor = TaskExecutor::new();
+
+        // Not cached without a ttl
+        assert!(!run_hook(&mut executor, id(0)).await.1);
+        assert!(!run_hook(&mut executor, id(0)).await.1);
+
+        let (result, cached) = run_hook(&mut executor, id(60)).await;
+        assert_eq!((result.output(), cached), (Some("green"), false));
+        let (result, cached) = run_hook(&mut executor, id(60)).await;
+        assert_eq!((result.output(), cached), (Some("green"), true));
+
+        // Changing the file referenced by the command invalidates the cache
+        std::fs::write(&file, "red").unwrap();
+        let modified = std::fs::metadata(&file).unwrap().modified().unwrap() + Duration::from_secs(1);
+        std::fs::File::options()
+            .write(true)
+            .open(&file)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+        let (result, cached) = run_hook(&mut executor, id(60)).await;
+        assert_eq!((result.output(), cached), (Some("red"), false));
+    }
+
+    #[test]
+    fn test_referenced_files() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("scripts")).unwrap();
+        for file in ["scripts/status.sh", "input.txt", "config.toml"] {
+            std::fs::write(dir.path().join(file), "").unwrap();
+        }
+
+        let files = referenced_files(
+            "./scripts/status.sh --config=config.toml <input.txt | grep missing.txt",
+            dir.path(),
+        );
+        assert_eq!(files, vec![
+            dir.path().join("./scripts/status.sh"),
+            dir.path().join("config.toml"),
+            dir.path().join("input.txt"),
+            dir.path().join("missing.txt"),
+        ]);
+        assert!(referenced_files("git log --oneline -5", dir.path()).is_empty());
+        assert!(
+            referenced_files("ls scripts", dir.path()).is_empty(),
+            "directories aren't files"
+        );
+    }
+
     #[tokio::test]
     async fn test_hook_execution() {
         let mut executor = TaskExecutor::new();
@@ -703,6 +915,7 @@ mod tests {
                     tool_context: None,
                 },
                 prompt: None,
+                event_input: None,
             })
             .await;
 
@@ -129,16 +129,20 @@ impl AsRef<str> for ConversationSummary {
 pub struct AgentSettings {
     /// Timeout waiting for MCP servers to initialize during agent initialization.
     pub mcp_init_timeout: Duration,
+    /// Minimum duration of a user turn for the notification hooks to be told it completed.
+    pub notification_turn_threshold: Duration,
 }
 
 impl AgentSettings {
     const DEFAULT_MCP_INIT_TIMEOUT: Duration = Duration::from_secs(5);
+    const DEFAULT_NOTIFICATION_TURN_THRESHOLD: Duration = Duration::from_secs(30);
 }
 
 impl Default for AgentSettings {
     fn default() -> Self {
         Self {
             mcp_init_timeout: Self::DEFAULT_MCP_INIT_TIMEOUT,
+            notification_turn_threshold: Self::DEFAULT_NOTIFICATION_TURN_THRESHOLD,
         }
     }
 }
@@ -1,13 +1,37 @@
 mod common;
 
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
 use std::time::Duration;
 
+use agent::Agent;
 use agent::agent_config::definitions::AgentConfig;
+use agent::agent_loop::model::MockModel;
+use agent::mcp::McpManager;
+use agent::protocol::acp::{
+    NewSessionRequest,
+    SessionFactory,
+};
 use agent::protocol::{
     ApprovalResult,
     SendApprovalResultArgs,
 };
+use agent::types::AgentSnapshot;
+use agent::util::test::{
+    TestBase,
+    TestProvider,
+};
 use common::*;
+use serde_json::{
+    Value,
+    json,
+};
+use tokio::io::{
+    AsyncBufReadExt,
+    AsyncWriteExt,
+    BufReader,
+};
 
 #[tokio::test]
 async fn test_agent_defaults() {
@@ -71,3 +95,137 @@ async fn test_agent_defaults() {
         assert_contains(SUB_LOCAL_RULE_MD_CONTENT);
     }
 }
+
+struct MockSessionFactory {
+    model: MockModel,
+    provider: TestProvider,
+}
+
+impl SessionFactory for MockSessionFactory {
+    fn new_agent(&self, _: &NewSessionRequest) -> Pin<Box<dyn Future<Output = eyre::Result<Agent>> + Send + '_>> {
+        Box::pin(async move {
+            let snapshot = AgentSnapshot::new_empty(AgentConfig::default());
+            let mut agent = Agent::new(snapshot, Arc::new(self.model.clone()), McpManager::new().spawn()).await?;
+            agent.set_sys_provider(self.provider.clone());
+            Ok(agent)
+        })
+    }
+}
+
+#[tokio::test]
+async fn test_acp_session() {
+    let _ = tracing_subscriber::fmt::try_init();
+
+    let test_base = TestBase::new().await;
+    let mut model = MockModel::new();
+    for response in parse_response_streams(include_str!("./mock_responses/acp.jsonl"))
+        .await
+        .unwrap()
+    {
+        model = model.with_response(response);
+    }
+    let factory = MockSessionFactory {
+        model,
+        provider: test_base.provider().clone(),
+    };
+
+    let (client, server) = tokio::io::duplex(64 * 1024);
+    let (server_reader, server_writer) = tokio::io::split(server);
+    tokio::spawn(agent::protocol::acp::serve(factory, server_reader, server_writer));
+    let (client_reader, mut client_writer) = tokio::io::split(client);
+    let mut lines = BufReader::new(client_reader).lines();
+
+    // Sends a message, returning the messages received until the response to it
+    let mut exchange = async |message: Value| {
+        client_writer
+            .write_all(format!("{message}\n").as_bytes())
+            .await
+            .unwrap();
+        let mut received = Vec::new();
+        loop {
+            let line = tokio::time::timeout(Duration::from_secs(5), lines.next_line())
+                .await
+                .expect("timed out")
+                .unwrap()
+                .unwrap();
+            let received_message: Value = serde_json::from_str(&line).unwrap();
+            received.push(received_message.clone());
+            if received_message.get("method").is_none() && received_message["id"] == message["id"] {
+                return received;
+            }
+            if received_message["method"] == "session/request_permission" {
+                let rejection = json!({
+                    "jsonrpc": "2.0",
+                    "id": received_message["id"],
+                    "result": { "outcome": { "outcome": "selected", "optionId": "reject_once" } },
+                });
+                client_writer
+                    .write_all(format!("{rejection}\n").as_bytes())
+                    .await
+                    .unwrap();
+            }
+        }
+    };
+
+    let received = exchange(json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "initialize",
+        "params": { "protocolVersion": 1, "clientCapabilities": {} },
+    }))
+    .await;
+    assert_eq!(received.last().unwrap()["result"]["protocolVersion"], 1);
+
+    let received = exchange(json!({
+        "jsonrpc": "2.0",
+        "id": 2,
+        "method": "session/new",
+        "params": { "cwd": test_base.join(""), "mcpServers": [] },
+    }))
+    .await;
+    let session_id = received.last().unwrap()["result"]["sessionId"].clone();
+    assert!(session_id.is_string());
+
+    let received = exchange(json!({
+        "jsonrpc": "2.0",
+        "id": 3,
+        "method": "session/prompt",
+        "params": { "sessionId": session_id, "prompt": [{ "type": "text", "text": "create acp.txt" }] },
+    }))
+    .await;
+    assert_eq!(received.last().unwrap()["result"]["stopReason"], "end_turn");
+
+    let updates = received
+        .iter()
+        .filter(|message| message["method"] == "session/update")
+        .map(|message| &message["params"]["update"])
+        .collect::<Vec<_>>();
+    let has_update = |expected: Value| {
+        updates.iter().any(|update| {
+            expected
+                .as_object()
+                .unwrap()
+                .iter()
+                .all(|(key, value)| &update[key] == value)
+        })
+    };
+    assert!(has_update(
+        json!({ "sessionUpdate": "agent_message_chunk", "content": { "type": "text", "text": "I'll create the file." } })
+    ));
+    assert!(has_update(
+        json!({ "sessionUpdate": "tool_call", "toolCallId": "tooluse_acp", "kind": "edit", "status": "pending" })
+    ));
+    assert!(has_update(
+        json!({ "sessionUpdate": "tool_call_update", "toolCallId": "tooluse_acp", "status": "failed" })
+    ));
+    assert!(
+        received
+            .iter()
+            .any(|message| message["method"] == "session/request_permission"
+                && message["params"]["toolCall"]["toolCallId"] == "tooluse_acp")
+    );
+    assert!(
+        !test_base.join("acp.txt").exists(),
+        "the rejected tool use should not run"
+    );
+}
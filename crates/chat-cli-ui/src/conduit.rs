@@ -182,6 +182,11 @@ impl ViewEnd {
                         theme_source.reset(),
                     )?;
                 },
+                // The terminal shows delegated tasks with /agents
+                Event::SubagentSpawned(_)
+                | Event::SubagentStepFinished(_)
+                | Event::SubagentWaiting(_)
+                | Event::SubagentFinished(_) => {},
             }
         }
 
@@ -175,6 +175,56 @@ pub struct ToolCallRejection {
     pub reason: String,
 }
 
+// ============================================================================
+// Delegated Task Events (bespoke)
+// ============================================================================
+
+/// Signals that a task was delegated to an agent running in the background
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SubagentSpawned {
+    pub task_id: String,
+    pub agent: String,
+    /// Sub-agent of the agent config the task runs as, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subagent: Option<String>,
+    pub task: String,
+    /// "queued" or "running"
+    pub status: String,
+}
+
+/// Signals that a delegated task finished a step, i.e. one of its tool uses
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SubagentStepFinished {
+    pub task_id: String,
+    /// Number of the step in the task, from 1
+    pub step: usize,
+    pub description: String,
+}
+
+/// Signals that a delegated task waits for an answer, to one of its questions or to whether it
+/// carries on past one of its limits
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SubagentWaiting {
+    pub task_id: String,
+    pub question: String,
+}
+
+/// Signals that a delegated task ended
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SubagentFinished {
+    pub task_id: String,
+    /// "completed", "failed", "cancelled" or "interrupted"
+    pub status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
+}
+
 // ============================================================================
 // State Management Events
 // ============================================================================
@@ -360,6 +410,12 @@ pub enum Event {
     // bespoke variant
     ToolCallRejection(ToolCallRejection),
 
+    // Delegated Task Events (bespoke)
+    SubagentSpawned(SubagentSpawned),
+    SubagentStepFinished(SubagentStepFinished),
+    SubagentWaiting(SubagentWaiting),
+    SubagentFinished(SubagentFinished),
+
     // State Management Events
     StateSnapshot(StateSnapshot),
     StateDelta(StateDelta),
@@ -411,6 +467,12 @@ impl Event {
             Event::ToolCallResult(_) => "toolCallResult",
             Event::ToolCallRejection(_) => "toolCallRejection",
 
+            // Delegated Task Events
+            Event::SubagentSpawned(_) => "subagentSpawned",
+            Event::SubagentStepFinished(_) => "subagentStepFinished",
+            Event::SubagentWaiting(_) => "subagentWaiting",
+            Event::SubagentFinished(_) => "subagentFinished",
+
             // State Management Events
             Event::StateSnapshot(_) => "stateSnapshot",
             Event::StateDelta(_) => "stateDelta",
@@ -473,6 +535,17 @@ impl Event {
         )
     }
 
+    /// Check if this is an event of a delegated task
+    pub fn is_subagent_event(&self) -> bool {
+        matches!(
+            self,
+            Event::SubagentSpawned(_)
+                | Event::SubagentStepFinished(_)
+                | Event::SubagentWaiting(_)
+                | Event::SubagentFinished(_)
+        )
+    }
+
     /// Check if this is a state management event
     pub fn is_state_management_event(&self) -> bool {
         matches!(
@@ -0,0 +1,66 @@
+//! `q agent acp`, which runs the agent loop as an [Agent Client Protocol](https://agentclientprotocol.com)
+//! server over stdin and stdout so that editors speaking ACP can host it.
+
+use std::future::Future;
+use std::pin::Pin;
+
+use agent::Agent;
+use agent::agent_config::definitions::AgentConfig;
+use agent::agent_config::load_agents;
+use agent::mcp::McpManager;
+use agent::protocol::acp::{
+    NewSessionRequest,
+    SessionFactory,
+};
+use agent::types::AgentSnapshot;
+use eyre::{
+    Result,
+    bail,
+};
+use uuid::Uuid;
+
+use super::BackendArgs;
+use crate::os::Os;
+
+/// Creates the agents of ACP sessions, all running the same agent config on the same backend.
+struct AcpSessionFactory {
+    os: Os,
+    backend: BackendArgs,
+    model: Option<String>,
+    agent_config: AgentConfig,
+}
+
+impl SessionFactory for AcpSessionFactory {
+    fn new_agent(&self, _args: &NewSessionRequest) -> Pin<Box<dyn Future<Output = Result<Agent>> + Send + '_>> {
+        Box::pin(async move {
+            let model = self
+                .backend
+                .create_model(&self.os, Uuid::new_v4(), self.model.clone())
+                .await?;
+            let snapshot = AgentSnapshot::new_empty(self.agent_config.clone());
+            Agent::new(snapshot, model, McpManager::new().spawn()).await
+        })
+    }
+}
+
+/// Serves ACP sessions over stdin and stdout until the client disconnects.
+pub async fn serve_stdio(os: &Os, agent_name: Option<&str>, backend: BackendArgs, model: Option<String>) -> Result<()> {
+    let agent_config = match agent_name {
+        Some(name) => {
+            let (configs, _) = load_agents().await?;
+            match configs.into_iter().find(|c| c.name() == name) {
+                Some(config) => config.config().clone(),
+                None => bail!("unable to find agent with name: {}", name),
+            }
+        },
+        None => AgentConfig::default(),
+    };
+
+    let factory = AcpSessionFactory {
+        os: os.clone(),
+        backend,
+        model,
+        agent_config,
+    };
+    agent::protocol::acp::serve(factory, tokio::io::stdin(), tokio::io::stdout()).await
+}
@@ -0,0 +1,438 @@
+pub mod types;
+
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::{
+    Duration,
+    Instant,
+};
+
+use agent::agent_loop::model::Model;
+use agent::agent_loop::protocol::StreamResult;
+use agent::agent_loop::types::{
+    Message,
+    MetadataEvent,
+    MetadataMetrics,
+    MetadataService,
+    MetadataUsage,
+    StreamError,
+    StreamErrorKind,
+    StreamErrorSource,
+    StreamEvent,
+    ToolSpec,
+};
+use aws_sdk_bedrockruntime::Client;
+use aws_sdk_bedrockruntime::error::ProvideErrorMetadata;
+use aws_sdk_bedrockruntime::types::{
+    ConverseStreamOutput as BedrockStreamEvent,
+    SystemContentBlock,
+};
+use aws_types::request_id::RequestId;
+use chrono::{
+    DateTime,
+    Utc,
+};
+use eyre::{
+    Result,
+    bail,
+};
+use futures::Stream;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_util::sync::CancellationToken;
+use tracing::{
+    debug,
+    error,
+    info,
+    trace,
+    warn,
+};
+
+use crate::api_client::model::InferenceConfig;
+use crate::api_client::stalled_stream_protection_config;
+use crate::aws_common::{
+    UserAgentOverrideInterceptor,
+    app_name,
+    behavior_version,
+};
+
+/// A [Model] implementation using the Bedrock runtime Converse API.
+#[derive(Debug, Clone)]
+pub struct BedrockModel {
+    client: Client,
+    model_id: String,
+    inference_config: InferenceConfig,
+}
+
+impl BedrockModel {
+    pub fn new(client: Client, model_id: String) -> Self {
+        Self {
+            client,
+            model_id,
+            inference_config: InferenceConfig::default(),
+        }
+    }
+
+    /// Returns this model sending `config` as the sampling parameters of its requests.
+    pub fn with_inference_config(self, config: InferenceConfig) -> Self {
+        Self {
+            inference_config: config,
+            ..self
+        }
+    }
+
+    /// Creates a model using the region and credentials resolved from the environment, same as
+    /// the AWS CLI would.
+    pub async fn from_env(model_id: String) -> Result<Self> {
+        let sdk_config = aws_config::defaults(behavior_version()).load().await;
+        if sdk_config.region().is_none() {
+            bail!("No AWS region is configured for Bedrock, set AWS_REGION or a region in your AWS profile");
+        }
+
+        let client = Client::from_conf(
+            aws_sdk_bedrockruntime::config::Builder::from(&sdk_config)
+                .http_client(crate::aws_common::http_client::client())
+                .interceptor(UserAgentOverrideInterceptor::new())
+                .app_name(app_name())
+                .stalled_stream_protection(stalled_stream_protection_config())
+                .build(),
+        );
+
+        Ok(Self::new(client, model_id))
+    }
+
+    pub fn model_id(&self) -> &str {
+        &self.model_id
+    }
+
+    async fn converse_stream_bedrock(
+        self,
+        tx: mpsc::Sender<StreamResult>,
+        cancel_token: CancellationToken,
+        messages: Vec<Message>,
+        tool_specs: Option<Vec<ToolSpec>>,
+        system_prompt: Option<String>,
+    ) {
+        let cache_points = types::supports_prompt_caching(&self.model_id);
+        let request = messages
+            .into_iter()
+            .map(|message| types::to_bedrock_message(message, cache_points))
+            .collect::<Result<Vec<_>, _>>()
+            .and_then(|messages| {
+                let tool_config = tool_specs
+                    .filter(|specs| !specs.is_empty())
+                    .map(types::to_bedrock_tool_config)
+                    .transpose()?;
+                Ok((messages, tool_config))
+            });
+        let (messages, tool_config) = match request {
+            Ok(r) => r,
+            Err(msg) => {
+                error!(?msg, "failed to create bedrock request");
+                tx.send(StreamResult::Err(StreamError::new(StreamErrorKind::Validation {
+                    message: Some(msg),
+                })))
+                .await
+                .map_err(|err| error!(?err, "failed to send model event"))
+                .ok();
+                return;
+            },
+        };
+
+        let request_start_time = Instant::now();
+        let request_start_time_sys = Utc::now();
+        let token_clone = cancel_token.clone();
+        let result = tokio::select! {
+            _ = token_clone.cancelled() => {
+                warn!("bedrock request cancelled during send");
+                tx.send(StreamResult::Err(StreamError::new(StreamErrorKind::Interrupted)))
+                    .await
+                    .map_err(|err| error!(?err, "failed to send event"))
+                    .ok();
+                return;
+            },
+            result = self
+                .client
+                .converse_stream()
+                .model_id(&self.model_id)
+                .set_messages(Some(messages))
+                .set_system(system_prompt.map(|p| vec![SystemContentBlock::Text(p)]))
+                .set_tool_config(tool_config)
+                .set_inference_config(types::to_bedrock_inference_config(&self.inference_config))
+                .send() => {
+                result
+            }
+        };
+
+        match result {
+            Ok(output) => {
+                info!(request_duration = ?request_start_time.elapsed(), "bedrock request sent successfully");
+                let request_id = output.request_id().map(String::from);
+                ResponseParser {
+                    stream: output.stream,
+                    event_tx: tx,
+                    cancel_token,
+                    usage: None,
+                    request_id,
+                    request_start_time,
+                    request_start_time_sys,
+                    time_to_first_chunk: None,
+                    time_between_chunks: vec![],
+                    received_response_size: 0,
+                }
+                .consume_stream()
+                .await;
+            },
+            Err(err) => {
+                error!(?err, "failed to send bedrock request");
+                let kind = classify_error(err.code(), err.message());
+                let request_id = err.request_id().map(String::from);
+                let status_code = err.raw_response().map(|r| r.status().as_u16());
+                tx.send(StreamResult::Err(
+                    StreamError::new(kind)
+                        .set_original_request_id(request_id)
+                        .set_original_status_code(status_code)
+                        .with_source(Arc::new(BedrockError(Box::new(err)))),
+                ))
+                .await
+                .map_err(|err| error!(?err, "failed to send stream event"))
+                .ok();
+            },
+        }
+    }
+}
+
+impl Model for BedrockModel {
+    fn stream(
+        &self,
+        messages: Vec<Message>,
+        tool_specs: Option<Vec<ToolSpec>>,
+        system_prompt: Option<String>,
+        cancel_token: CancellationToken,
+    ) -> Pin<Box<dyn Stream<Item = StreamResult> + Send + 'static>> {
+        let (tx, rx) = mpsc::channel(16);
+
+        let self_clone = self.clone();
+        tokio::spawn(async move {
+            self_clone
+                .converse_stream_bedrock(tx, cancel_token, messages, tool_specs, system_prompt)
+                .await;
+        });
+
+        Box::pin(ReceiverStream::new(rx))
+    }
+}
+
+/// Maps the error code returned by Bedrock to the [StreamErrorKind] the agent loop acts on.
+fn classify_error(code: Option<&str>, message: Option<&str>) -> StreamErrorKind {
+    match code {
+        Some("ThrottlingException" | "ModelNotReadyException" | "ServiceQuotaExceededException") => {
+            StreamErrorKind::Throttling
+        },
+        Some("ValidationException")
+            if message.is_some_and(|m| {
+                let m = m.to_lowercase();
+                m.contains("too long") || m.contains("too many tokens") || m.contains("context length")
+            }) =>
+        {
+            StreamErrorKind::ContextWindowOverflow
+        },
+        Some("ValidationException") => StreamErrorKind::Validation {
+            message: message.map(String::from),
+        },
+        Some(
+            "InternalServerException"
+            | "ModelErrorException"
+            | "ModelTimeoutException"
+            | "ModelStreamErrorException"
+            | "ServiceUnavailableException",
+        ) => StreamErrorKind::ServiceFailure,
+        code => StreamErrorKind::Other(format!(
+            "Bedrock request failed: {}",
+            message.or(code).unwrap_or("unknown error")
+        )),
+    }
+}
+
+/// Wraps the SDK errors so they can be attached as the source of a [StreamError].
+#[derive(Debug, thiserror::Error)]
+#[error(transparent)]
+pub struct BedrockError(Box<dyn std::error::Error + Send + Sync>);
+
+impl StreamErrorSource for BedrockError {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+struct ResponseParser {
+    stream: aws_sdk_bedrockruntime::primitives::event_stream::EventReceiver<
+        BedrockStreamEvent,
+        aws_sdk_bedrockruntime::types::error::ConverseStreamOutputError,
+    >,
+    event_tx: mpsc::Sender<StreamResult>,
+    cancel_token: CancellationToken,
+
+    /// Token usage, as reported by the metadata event at the end of the stream.
+    usage: Option<MetadataUsage>,
+
+    // metadata fields
+    request_id: Option<String>,
+    /// Time immediately before sending the request.
+    request_start_time: Instant,
+    /// Time immediately before sending the request, as a [DateTime].
+    request_start_time_sys: DateTime<Utc>,
+    time_to_first_chunk: Option<Duration>,
+    time_between_chunks: Vec<Duration>,
+    /// Total size (in bytes) of the text and tool use input received so far.
+    received_response_size: usize,
+}
+
+impl ResponseParser {
+    /// Consumes the entire response stream, emitting [StreamEvent] and [StreamError], or exiting
+    /// early if [Self::cancel_token] is cancelled.
+    ///
+    /// In either case, metadata regarding the stream is emitted with a [StreamEvent::Metadata].
+    async fn consume_stream(mut self) {
+        loop {
+            let token = self.cancel_token.clone();
+            let start = Instant::now();
+            let result = tokio::select! {
+                _ = token.cancelled() => {
+                    debug!("bedrock response parser was cancelled");
+                    self.send(StreamResult::Ok(self.make_metadata())).await;
+                    self.send(StreamResult::Err(StreamError::new(StreamErrorKind::Interrupted))).await;
+                    return;
+                },
+                result = self.stream.recv() => result,
+            };
+
+            match result {
+                Ok(Some(ev)) => {
+                    trace!(?ev, "received new event");
+                    self.time_to_first_chunk
+                        .get_or_insert_with(|| self.request_start_time.elapsed());
+                    self.time_between_chunks.push(start.elapsed());
+                    self.record(&ev);
+                    if let Some(ev) = types::from_bedrock_stream_event(ev) {
+                        self.send(StreamResult::Ok(ev)).await;
+                    }
+                },
+                Ok(None) => {
+                    debug!("bedrock response stream has ended");
+                    self.send(StreamResult::Ok(self.make_metadata())).await;
+                    return;
+                },
+                Err(err) => {
+                    error!(?err, "failed to receive the next event");
+                    let kind = classify_error(err.code(), err.message());
+                    self.send(StreamResult::Ok(self.make_metadata())).await;
+                    self.send(StreamResult::Err(
+                        StreamError::new(kind)
+                            .set_original_request_id(self.request_id.clone())
+                            .with_source(Arc::new(BedrockError(Box::new(err)))),
+                    ))
+                    .await;
+                    return;
+                },
+            }
+        }
+    }
+
+    /// Tracks the size of the response and the token usage reported by the stream.
+    fn record(&mut self, ev: &BedrockStreamEvent) {
+        use aws_sdk_bedrockruntime::types::ContentBlockDelta;
+
+        match ev {
+            BedrockStreamEvent::ContentBlockDelta(delta) => match &delta.delta {
+                Some(ContentBlockDelta::Text(text)) => self.received_response_size += text.len(),
+                Some(ContentBlockDelta::ToolUse(tool_use)) => self.received_response_size += tool_use.input.len(),
+                _ => (),
+            },
+            BedrockStreamEvent::Metadata(metadata) => {
+                self.usage = metadata.usage.as_ref().map(|usage| MetadataUsage {
+                    input_tokens: Some(usage.input_tokens as u64),
+                    output_tokens: Some(usage.output_tokens as u64),
+                    cache_read_input_tokens: usage.cache_read_input_tokens.map(|v| v as u64),
+                    cache_write_input_tokens: usage.cache_write_input_tokens.map(|v| v as u64),
+                });
+            },
+            _ => (),
+        }
+    }
+
+    async fn send(&self, ev: StreamResult) {
+        self.event_tx
+            .send(ev)
+            .await
+            .map_err(|err| error!(?err, "failed to send event to channel"))
+            .ok();
+    }
+
+    fn make_metadata(&self) -> StreamEvent {
+        StreamEvent::Metadata(MetadataEvent {
+            metrics: Some(MetadataMetrics {
+                request_start_time: self.request_start_time_sys,
+                request_end_time: Utc::now(),
+                time_to_first_chunk: self.time_to_first_chunk,
+                time_between_chunks: if self.time_between_chunks.is_empty() {
+                    None
+                } else {
+                    Some(self.time_between_chunks.clone())
+                },
+                response_stream_len: self.received_response_size as u32,
+            }),
+            usage: self.usage.clone(),
+            service: Some(MetadataService {
+                request_id: self.request_id.clone(),
+                status_code: None,
+            }),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_error() {
+        assert!(matches!(
+            classify_error(Some("ThrottlingException"), Some("Too many requests")),
+            StreamErrorKind::Throttling
+        ));
+        assert!(matches!(
+            classify_error(Some("ModelNotReadyException"), None),
+            StreamErrorKind::Throttling
+        ));
+        assert!(matches!(
+            classify_error(
+                Some("ValidationException"),
+                Some("Input is too long for requested model.")
+            ),
+            StreamErrorKind::ContextWindowOverflow
+        ));
+        assert!(matches!(
+            classify_error(Some("ValidationException"), Some("The provided model identifier is invalid.")),
+            StreamErrorKind::Validation { message: Some(m) } if m == "The provided model identifier is invalid."
+        ));
+        assert!(matches!(
+            classify_error(Some("ModelStreamErrorException"), None),
+            StreamErrorKind::ServiceFailure
+        ));
+        assert!(matches!(
+            classify_error(Some("AccessDeniedException"), Some("You don't have access to the model")),
+            StreamErrorKind::Other(m) if m.contains("You don't have access to the model")
+        ));
+    }
+
+    #[test]
+    fn test_bedrock_err_downcasting() {
+        let err = StreamError::new(StreamErrorKind::ServiceFailure)
+            .with_source(Arc::new(BedrockError("model error".to_string().into())));
+        assert!(
+            err.as_concrete_error::<BedrockError>()
+                .is_some_and(|e| e.to_string() == "model error")
+        );
+    }
+}
@@ -0,0 +1,333 @@
+//! Conversions between the types of the agent loop and the Bedrock runtime API.
+
+use agent::agent_loop::types::*;
+use aws_sdk_bedrockruntime::types as bedrock;
+use aws_smithy_types::Blob;
+
+use crate::api_client::model::InferenceConfig;
+use crate::cli::chat::util::serde_value_to_document;
+
+/// Whether the model accepts cache points, see
+/// <https://docs.aws.amazon.com/bedrock/latest/userguide/prompt-caching.html>.
+pub fn supports_prompt_caching(model_id: &str) -> bool {
+    model_id.contains("anthropic.claude") || model_id.contains("amazon.nova")
+}
+
+/// Converts a message of the agent loop, dropping its cache points unless `cache_points` is set,
+/// since models without prompt caching reject them.
+pub fn to_bedrock_message(message: Message, cache_points: bool) -> Result<bedrock::Message, String> {
+    let role = match message.role {
+        Role::User => bedrock::ConversationRole::User,
+        Role::Assistant => bedrock::ConversationRole::Assistant,
+    };
+    let content = message
+        .content
+        .into_iter()
+        // Bedrock rejects blank text blocks
+        .filter(|block| !matches!(block, ContentBlock::Text(text) if text.trim().is_empty()))
+        .filter(|block| cache_points || !matches!(block, ContentBlock::CachePoint))
+        .map(to_bedrock_content_block)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    bedrock::Message::builder()
+        .role(role)
+        .set_content(Some(content))
+        .build()
+        .map_err(|err| err.to_string())
+}
+
+fn to_bedrock_content_block(block: ContentBlock) -> Result<bedrock::ContentBlock, String> {
+    Ok(match block {
+        ContentBlock::Text(text) => bedrock::ContentBlock::Text(text),
+        ContentBlock::ToolUse(tool_use) => bedrock::ContentBlock::ToolUse(
+            bedrock::ToolUseBlock::builder()
+                .tool_use_id(tool_use.tool_use_id)
+                .name(tool_use.name)
+                .input(serde_value_to_document(tool_use.input))
+                .build()
+                .map_err(|err| err.to_string())?,
+        ),
+        ContentBlock::ToolResult(tool_result) => bedrock::ContentBlock::ToolResult(
+            bedrock::ToolResultBlock::builder()
+                .tool_use_id(tool_result.tool_use_id)
+                .set_content(Some(
+                    tool_result
+                        .content
+                        .into_iter()
+                        .map(to_bedrock_tool_result_content_block)
+                        .collect::<Result<Vec<_>, _>>()?,
+                ))
+                .status(match tool_result.status {
+                    ToolResultStatus::Error => bedrock::ToolResultStatus::Error,
+                    ToolResultStatus::Success => bedrock::ToolResultStatus::Success,
+                })
+                .build()
+                .map_err(|err| err.to_string())?,
+        ),
+        ContentBlock::Image(image) => bedrock::ContentBlock::Image(to_bedrock_image_block(image)?),
+        ContentBlock::CachePoint => bedrock::ContentBlock::CachePoint(
+            bedrock::CachePointBlock::builder()
+                .r#type(bedrock::CachePointType::Default)
+                .build()
+                .map_err(|err| err.to_string())?,
+        ),
+    })
+}
+
+fn to_bedrock_tool_result_content_block(
+    block: ToolResultContentBlock,
+) -> Result<bedrock::ToolResultContentBlock, String> {
+    Ok(match block {
+        ToolResultContentBlock::Text(text) => bedrock::ToolResultContentBlock::Text(text),
+        ToolResultContentBlock::Json(value) => bedrock::ToolResultContentBlock::Json(serde_value_to_document(value)),
+        ToolResultContentBlock::Image(image) => bedrock::ToolResultContentBlock::Image(to_bedrock_image_block(image)?),
+    })
+}
+
+fn to_bedrock_image_block(image: ImageBlock) -> Result<bedrock::ImageBlock, String> {
+    let ImageSource::Bytes(bytes) = image.source;
+    bedrock::ImageBlock::builder()
+        .format(match image.format {
+            ImageFormat::Gif => bedrock::ImageFormat::Gif,
+            ImageFormat::Jpeg => bedrock::ImageFormat::Jpeg,
+            ImageFormat::Png => bedrock::ImageFormat::Png,
+            ImageFormat::Webp => bedrock::ImageFormat::Webp,
+        })
+        .source(bedrock::ImageSource::Bytes(Blob::new(bytes)))
+        .build()
+        .map_err(|err| err.to_string())
+}
+
+pub fn to_bedrock_tool_config(tool_specs: Vec<ToolSpec>) -> Result<bedrock::ToolConfiguration, String> {
+    let tools = tool_specs
+        .into_iter()
+        .map(|spec| {
+            bedrock::ToolSpecification::builder()
+                .name(spec.name)
+                .description(spec.description)
+                .input_schema(bedrock::ToolInputSchema::Json(serde_value_to_document(
+                    serde_json::Value::Object(spec.input_schema),
+                )))
+                .build()
+                .map(bedrock::Tool::ToolSpec)
+                .map_err(|err| err.to_string())
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    bedrock::ToolConfiguration::builder()
+        .set_tools(Some(tools))
+        .build()
+        .map_err(|err| err.to_string())
+}
+
+/// Converts the sampling parameters, [None] when none is set so that the model's defaults apply.
+pub fn to_bedrock_inference_config(config: &InferenceConfig) -> Option<bedrock::InferenceConfiguration> {
+    (!config.is_empty()).then(|| {
+        bedrock::InferenceConfiguration::builder()
+            .set_temperature(config.temperature)
+            .set_max_tokens(
+                config
+                    .max_output_tokens
+                    .map(|tokens| i32::try_from(tokens).unwrap_or(i32::MAX)),
+            )
+            .set_top_p(config.top_p)
+            .build()
+    })
+}
+
+/// Converts an event of the response stream. Returns [None] for the events the agent loop has no
+/// equivalent for, e.g. reasoning or citations, and for the metadata which is emitted separately
+/// once the stream ends.
+pub fn from_bedrock_stream_event(event: bedrock::ConverseStreamOutput) -> Option<StreamEvent> {
+    Some(match event {
+        bedrock::ConverseStreamOutput::MessageStart(event) => StreamEvent::MessageStart(MessageStartEvent {
+            role: match event.role {
+                bedrock::ConversationRole::User => Role::User,
+                _ => Role::Assistant,
+            },
+        }),
+        bedrock::ConverseStreamOutput::ContentBlockStart(event) => match event.start {
+            Some(bedrock::ContentBlockStart::ToolUse(start)) => {
+                StreamEvent::ContentBlockStart(ContentBlockStartEvent {
+                    content_block_start: Some(ContentBlockStart::ToolUse(ToolUseBlockStart {
+                        tool_use_id: start.tool_use_id,
+                        name: start.name,
+                    })),
+                    content_block_index: Some(event.content_block_index),
+                })
+            },
+            _ => StreamEvent::ContentBlockStart(ContentBlockStartEvent {
+                content_block_start: None,
+                content_block_index: Some(event.content_block_index),
+            }),
+        },
+        bedrock::ConverseStreamOutput::ContentBlockDelta(event) => {
+            let delta = match event.delta? {
+                bedrock::ContentBlockDelta::Text(text) => ContentBlockDelta::Text(text),
+                bedrock::ContentBlockDelta::ToolUse(delta) => {
+                    ContentBlockDelta::ToolUse(ToolUseBlockDelta { input: delta.input })
+                },
+                _ => return None,
+            };
+            StreamEvent::ContentBlockDelta(ContentBlockDeltaEvent {
+                delta,
+                content_block_index: Some(event.content_block_index),
+            })
+        },
+        bedrock::ConverseStreamOutput::ContentBlockStop(event) => {
+            StreamEvent::ContentBlockStop(ContentBlockStopEvent {
+                content_block_index: Some(event.content_block_index),
+            })
+        },
+        bedrock::ConverseStreamOutput::MessageStop(event) => StreamEvent::MessageStop(MessageStopEvent {
+            stop_reason: match event.stop_reason {
+                bedrock::StopReason::ToolUse => StopReason::ToolUse,
+                bedrock::StopReason::MaxTokens => StopReason::MaxTokens,
+                _ => StopReason::EndTurn,
+            },
+        }),
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_bedrock_message() {
+        let message = Message::new(
+            Role::User,
+            vec![
+                ContentBlock::Text(" ".to_string()),
+                ContentBlock::ToolResult(ToolResultBlock {
+                    tool_use_id: "tooluse_1".to_string(),
+                    content: vec![
+                        ToolResultContentBlock::Text("done".to_string()),
+                        ToolResultContentBlock::Json(serde_json::json!({ "exit_status": 0 })),
+                    ],
+                    status: ToolResultStatus::Success,
+                }),
+                ContentBlock::Image(ImageBlock {
+                    format: ImageFormat::Png,
+                    source: ImageSource::Bytes(vec![1, 2, 3]),
+                }),
+            ],
+            None,
+        );
+
+        let message = to_bedrock_message(message, false).unwrap();
+        assert_eq!(message.role, bedrock::ConversationRole::User);
+        // The blank text block is dropped
+        assert_eq!(message.content.len(), 2);
+        let bedrock::ContentBlock::ToolResult(tool_result) = &message.content[0] else {
+            panic!("expected a tool result, found {:?}", message.content[0]);
+        };
+        assert_eq!(tool_result.tool_use_id, "tooluse_1");
+        assert_eq!(tool_result.content.len(), 2);
+        assert_eq!(tool_result.status, Some(bedrock::ToolResultStatus::Success));
+        assert!(
+            matches!(&message.content[1], bedrock::ContentBlock::Image(image) if image.format == bedrock::ImageFormat::Png)
+        );
+    }
+
+    #[test]
+    fn test_to_bedrock_inference_config() {
+        assert!(to_bedrock_inference_config(&InferenceConfig::default()).is_none());
+        let config = to_bedrock_inference_config(&InferenceConfig {
+            temperature: Some(0.5),
+            max_output_tokens: Some(8000),
+            top_p: None,
+        })
+        .unwrap();
+        assert_eq!(config.temperature(), Some(0.5));
+        assert_eq!(config.max_tokens(), Some(8000));
+        assert_eq!(config.top_p(), None);
+    }
+
+    #[test]
+    fn test_to_bedrock_message_cache_point() {
+        let message = Message::new(
+            Role::Assistant,
+            vec![ContentBlock::Text("ok".to_string()), ContentBlock::CachePoint],
+            None,
+        );
+
+        assert!(supports_prompt_caching("us.anthropic.claude-sonnet-4-20250514-v1:0"));
+        let cached = to_bedrock_message(message.clone(), true).unwrap();
+        assert_eq!(cached.content.len(), 2);
+        assert!(matches!(&cached.content[1], bedrock::ContentBlock::CachePoint(_)));
+
+        assert!(!supports_prompt_caching("meta.llama3-70b-instruct-v1:0"));
+        let uncached = to_bedrock_message(message, false).unwrap();
+        assert_eq!(uncached.content.len(), 1);
+    }
+
+    #[test]
+    fn test_from_bedrock_stream_event() {
+        let start = bedrock::ConverseStreamOutput::ContentBlockStart(
+            bedrock::ContentBlockStartEvent::builder()
+                .start(bedrock::ContentBlockStart::ToolUse(
+                    bedrock::ToolUseBlockStart::builder()
+                        .tool_use_id("tooluse_1")
+                        .name("fs_read")
+                        .build()
+                        .unwrap(),
+                ))
+                .content_block_index(1)
+                .build()
+                .unwrap(),
+        );
+        assert!(matches!(
+            from_bedrock_stream_event(start),
+            Some(StreamEvent::ContentBlockStart(ContentBlockStartEvent {
+                content_block_start: Some(ContentBlockStart::ToolUse(ToolUseBlockStart { tool_use_id, name })),
+                content_block_index: Some(1),
+            })) if tool_use_id == "tooluse_1" && name == "fs_read"
+        ));
+
+        let delta = bedrock::ConverseStreamOutput::ContentBlockDelta(
+            bedrock::ContentBlockDeltaEvent::builder()
+                .delta(bedrock::ContentBlockDelta::ToolUse(
+                    bedrock::ToolUseBlockDelta::builder()
+                        .input("{\"path\":")
+                        .build()
+                        .unwrap(),
+                ))
+                .content_block_index(1)
+                .build()
+                .unwrap(),
+        );
+        assert!(matches!(
+            from_bedrock_stream_event(delta),
+            Some(StreamEvent::ContentBlockDelta(ContentBlockDeltaEvent {
+                delta: ContentBlockDelta::ToolUse(ToolUseBlockDelta { input }),
+                ..
+            })) if input == "{\"path\":"
+        ));
+
+        let stop = bedrock::ConverseStreamOutput::MessageStop(
+            bedrock::MessageStopEvent::builder()
+                .stop_reason(bedrock::StopReason::ToolUse)
+                .build()
+                .unwrap(),
+        );
+        assert!(matches!(
+            from_bedrock_stream_event(stop),
+            Some(StreamEvent::MessageStop(MessageStopEvent {
+                stop_reason: StopReason::ToolUse
+            }))
+        ));
+
+        let reasoning = bedrock::ConverseStreamOutput::ContentBlockDelta(
+            bedrock::ContentBlockDeltaEvent::builder()
+                .delta(bedrock::ContentBlockDelta::ReasoningContent(
+                    bedrock::ReasoningContentBlockDelta::Text("thinking".to_string()),
+                ))
+                .content_block_index(0)
+                .build()
+                .unwrap(),
+        );
+        assert!(from_bedrock_stream_event(reasoning).is_none());
+    }
+}
@@ -0,0 +1,543 @@
+//! Lets `q chat` send its conversations to the backends of the agent loop instead of the Q one.
+//!
+//! The conversation is converted to the [Message]s of the agent loop, and the events of the
+//! response back to the [ChatResponseStream] events `q chat` parses.
+
+use std::pin::Pin;
+
+use agent::agent_loop::model::Model;
+use agent::agent_loop::protocol::StreamResult;
+use agent::agent_loop::types::{
+    ContentBlock,
+    ContentBlockDelta,
+    ContentBlockDeltaEvent,
+    ContentBlockStart,
+    ContentBlockStartEvent,
+    ImageBlock,
+    ImageFormat,
+    ImageSource,
+    Message,
+    MetadataEvent,
+    Role,
+    StreamError,
+    StreamErrorKind,
+    StreamEvent,
+    ToolResultBlock,
+    ToolResultContentBlock,
+    ToolResultStatus,
+    ToolSpec,
+    ToolUseBlock,
+};
+use futures::{
+    Stream,
+    StreamExt,
+};
+use tokio_util::sync::{
+    CancellationToken,
+    DropGuard,
+};
+
+use super::bedrock::BedrockModel;
+use super::openai::OpenAiModel;
+use crate::api_client::error::{
+    ConverseStreamError,
+    ConverseStreamErrorKind,
+};
+use crate::api_client::model::{
+    self,
+    ChatMessage,
+    ChatResponseStream,
+    ConversationState,
+    InferenceConfig,
+};
+use crate::cli::chat::util::document_to_serde_value;
+
+/// A backend of the agent loop that `q chat` sends its requests to.
+#[derive(Debug, Clone)]
+pub enum ChatModel {
+    Bedrock(BedrockModel),
+    OpenAi(OpenAiModel),
+}
+
+impl ChatModel {
+    /// Id of the model, as given with `--model`.
+    pub fn model_id(&self) -> &str {
+        match self {
+            ChatModel::Bedrock(model) => model.model_id(),
+            ChatModel::OpenAi(model) => model.model_id(),
+        }
+    }
+
+    /// Returns this model sending `config` as the sampling parameters of its requests.
+    pub fn with_inference_config(self, config: InferenceConfig) -> Self {
+        match self {
+            ChatModel::Bedrock(model) => ChatModel::Bedrock(model.with_inference_config(config)),
+            ChatModel::OpenAi(model) => ChatModel::OpenAi(model.with_inference_config(config)),
+        }
+    }
+
+    /// Sends the conversation, returning once the first event of the response arrived so that
+    /// failed requests are reported as such rather than as a failed response stream.
+    pub async fn send(&self, conversation: ConversationState) -> Result<ChatModelOutput, ConverseStreamError> {
+        let model = self
+            .clone()
+            .with_inference_config(conversation.inference_config.unwrap_or_default());
+        let (messages, tool_specs) = to_messages(conversation);
+        let cancel_token = CancellationToken::new();
+        let mut output = ChatModelOutput {
+            stream: model.stream(messages, tool_specs, None, cancel_token.clone()),
+            tool_use: None,
+            first_event: None,
+            _cancel_on_drop: cancel_token.drop_guard(),
+        };
+        output.first_event = output.recv().await?;
+        Ok(output)
+    }
+}
+
+impl Model for ChatModel {
+    fn stream(
+        &self,
+        messages: Vec<Message>,
+        tool_specs: Option<Vec<ToolSpec>>,
+        system_prompt: Option<String>,
+        cancel_token: CancellationToken,
+    ) -> Pin<Box<dyn Stream<Item = StreamResult> + Send + 'static>> {
+        match self {
+            ChatModel::Bedrock(model) => model.stream(messages, tool_specs, system_prompt, cancel_token),
+            ChatModel::OpenAi(model) => model.stream(messages, tool_specs, system_prompt, cancel_token),
+        }
+    }
+}
+
+/// The response to a conversation sent with [ChatModel::send].
+pub struct ChatModelOutput {
+    stream: Pin<Box<dyn Stream<Item = StreamResult> + Send + 'static>>,
+    /// Id and name of the tool use whose input is being streamed
+    tool_use: Option<(String, String)>,
+    /// Event received by [ChatModel::send] before returning
+    first_event: Option<ChatResponseStream>,
+    /// Cancels the request once the response is dropped, e.g. when interrupted with ctrl+c
+    _cancel_on_drop: DropGuard,
+}
+
+impl std::fmt::Debug for ChatModelOutput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ChatModelOutput")
+            .field("tool_use", &self.tool_use)
+            .field("first_event", &self.first_event)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ChatModelOutput {
+    pub async fn recv(&mut self) -> Result<Option<ChatResponseStream>, ConverseStreamError> {
+        if let Some(event) = self.first_event.take() {
+            return Ok(Some(event));
+        }
+        while let Some(result) = self.stream.next().await {
+            match result {
+                StreamResult::Ok(event) => {
+                    if let Some(event) = self.map_event(event) {
+                        return Ok(Some(event));
+                    }
+                },
+                StreamResult::Err(err) => return Err(to_converse_stream_error(err)),
+            }
+        }
+        Ok(None)
+    }
+
+    /// Maps `event` to the [ChatResponseStream] event of the Q backend carrying the same content,
+    /// if any.
+    fn map_event(&mut self, event: StreamEvent) -> Option<ChatResponseStream> {
+        match event {
+            StreamEvent::ContentBlockStart(ContentBlockStartEvent {
+                content_block_start: Some(ContentBlockStart::ToolUse(start)),
+                ..
+            }) => {
+                self.tool_use = Some((start.tool_use_id.clone(), start.name.clone()));
+                Some(ChatResponseStream::ToolUseEvent {
+                    tool_use_id: start.tool_use_id,
+                    name: start.name,
+                    input: None,
+                    stop: None,
+                })
+            },
+            StreamEvent::ContentBlockDelta(ContentBlockDeltaEvent {
+                delta: ContentBlockDelta::Text(content),
+                ..
+            }) => Some(ChatResponseStream::AssistantResponseEvent { content }),
+            StreamEvent::ContentBlockDelta(ContentBlockDeltaEvent {
+                delta: ContentBlockDelta::ToolUse(delta),
+                ..
+            }) => {
+                let (tool_use_id, name) = self.tool_use.clone()?;
+                Some(ChatResponseStream::ToolUseEvent {
+                    tool_use_id,
+                    name,
+                    input: Some(delta.input),
+                    stop: None,
+                })
+            },
+            StreamEvent::ContentBlockStop(_) => {
+                let (tool_use_id, name) = self.tool_use.take()?;
+                Some(ChatResponseStream::ToolUseEvent {
+                    tool_use_id,
+                    name,
+                    input: None,
+                    stop: Some(true),
+                })
+            },
+            StreamEvent::Metadata(MetadataEvent { usage: Some(usage), .. }) => {
+                let cached_tokens = usage.cache_read_input_tokens.unwrap_or_default()
+                    + usage.cache_write_input_tokens.unwrap_or_default();
+                Some(ChatResponseStream::MetadataEvent {
+                    input_tokens: usage.input_tokens.map(|tokens| (tokens + cached_tokens) as usize),
+                    output_tokens: usage.output_tokens.map(|tokens| tokens as usize),
+                })
+            },
+            _ => None,
+        }
+    }
+}
+
+fn to_converse_stream_error(err: StreamError) -> ConverseStreamError {
+    let kind = match &err.kind {
+        StreamErrorKind::Throttling => ConverseStreamErrorKind::Throttling,
+        StreamErrorKind::ContextWindowOverflow => ConverseStreamErrorKind::ContextWindowOverflow,
+        StreamErrorKind::Validation { message: Some(message) } => ConverseStreamErrorKind::Unknown {
+            reason_code: message.clone(),
+        },
+        kind => ConverseStreamErrorKind::Unknown {
+            reason_code: kind.to_string(),
+        },
+    };
+    let request_id = err.original_request_id.clone();
+    let status_code = err.original_status_code;
+    ConverseStreamError::new(kind, Some(err))
+        .set_request_id(request_id)
+        .set_status_code(status_code)
+}
+
+/// Converts the conversation to the messages of the agent loop, along with the tools offered to
+/// the model.
+fn to_messages(conversation: ConversationState) -> (Vec<Message>, Option<Vec<ToolSpec>>) {
+    let tool_specs = conversation
+        .user_input_message
+        .user_input_message_context
+        .as_ref()
+        .and_then(|context| context.tools.clone())
+        .map(|tools| tools.into_iter().map(Into::into).collect());
+    let mut messages = conversation
+        .history
+        .unwrap_or_default()
+        .into_iter()
+        .map(|message| match message {
+            ChatMessage::UserInputMessage(message) => message.into(),
+            ChatMessage::AssistantResponseMessage(message) => message.into(),
+        })
+        .collect::<Vec<Message>>();
+    messages.push(conversation.user_input_message.into());
+    (messages, tool_specs)
+}
+
+impl From<model::UserInputMessage> for Message {
+    fn from(v: model::UserInputMessage) -> Self {
+        // Some models require the tool results to come first
+        let mut content = v
+            .user_input_message_context
+            .and_then(|context| context.tool_results)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|result| ContentBlock::ToolResult(result.into()))
+            .collect::<Vec<_>>();
+        if !v.content.is_empty() {
+            content.push(ContentBlock::Text(v.content));
+        }
+        content.extend(
+            v.images
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(to_image_block)
+                .map(ContentBlock::Image),
+        );
+        Message::new(Role::User, content, None)
+    }
+}
+
+impl From<model::AssistantResponseMessage> for Message {
+    fn from(v: model::AssistantResponseMessage) -> Self {
+        let mut content = Vec::new();
+        if !v.content.is_empty() {
+            content.push(ContentBlock::Text(v.content));
+        }
+        content.extend(v.tool_uses.unwrap_or_default().into_iter().map(|tool_use| {
+            ContentBlock::ToolUse(ToolUseBlock {
+                tool_use_id: tool_use.tool_use_id,
+                name: tool_use.name,
+                input: document_to_serde_value(tool_use.input.into()),
+            })
+        }));
+        if v.cache_point {
+            content.push(ContentBlock::CachePoint);
+        }
+        Message::new(Role::Assistant, content, None)
+    }
+}
+
+impl From<model::ToolResult> for ToolResultBlock {
+    fn from(v: model::ToolResult) -> Self {
+        Self {
+            tool_use_id: v.tool_use_id,
+            content: v
+                .content
+                .into_iter()
+                .map(|block| match block {
+                    model::ToolResultContentBlock::Json(document) => {
+                        ToolResultContentBlock::Json(document_to_serde_value(document))
+                    },
+                    model::ToolResultContentBlock::Text(text) => ToolResultContentBlock::Text(text),
+                })
+                .collect(),
+            status: match v.status {
+                model::ToolResultStatus::Error => ToolResultStatus::Error,
+                model::ToolResultStatus::Success => ToolResultStatus::Success,
+            },
+        }
+    }
+}
+
+impl From<model::Tool> for ToolSpec {
+    fn from(v: model::Tool) -> Self {
+        let model::Tool::ToolSpecification(spec) = v;
+        let input_schema = match spec.input_schema.json.map(|json| document_to_serde_value(json.into())) {
+            Some(serde_json::Value::Object(schema)) => schema,
+            _ => Default::default(),
+        };
+        Self {
+            name: spec.name,
+            description: spec.description,
+            input_schema,
+        }
+    }
+}
+
+fn to_image_block(v: model::ImageBlock) -> Option<ImageBlock> {
+    let model::ImageSource::Bytes(bytes) = v.source else {
+        return None;
+    };
+    Some(ImageBlock {
+        format: match v.format {
+            model::ImageFormat::Gif => ImageFormat::Gif,
+            model::ImageFormat::Jpeg => ImageFormat::Jpeg,
+            model::ImageFormat::Png => ImageFormat::Png,
+            model::ImageFormat::Webp => ImageFormat::Webp,
+        },
+        source: ImageSource::Bytes(bytes),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_client::model::{
+        AssistantResponseMessage,
+        ToolUse,
+        UserInputMessage,
+        UserInputMessageContext,
+    };
+    use crate::cli::chat::util::serde_value_to_document;
+    use crate::cli::{
+        Cli,
+        RootSubcommand,
+    };
+    use crate::os::Os;
+    use crate::util::CHAT_BINARY_NAME;
+
+    fn user_message(content: &str, tool_results: Option<Vec<model::ToolResult>>) -> UserInputMessage {
+        UserInputMessage {
+            content: content.to_string(),
+            user_input_message_context: Some(UserInputMessageContext {
+                env_state: None,
+                git_state: None,
+                tool_results,
+                tools: None,
+            }),
+            user_intent: None,
+            images: None,
+            model_id: None,
+        }
+    }
+
+    #[test]
+    fn test_to_messages() {
+        let conversation = ConversationState {
+            conversation_id: None,
+            user_input_message: user_message(
+                "",
+                Some(vec![model::ToolResult {
+                    tool_use_id: "tool-1".to_string(),
+                    content: vec![model::ToolResultContentBlock::Text("file content".to_string())],
+                    status: model::ToolResultStatus::Success,
+                }]),
+            ),
+            history: Some(vec![
+                ChatMessage::UserInputMessage(user_message("read the file", None)),
+                ChatMessage::AssistantResponseMessage(AssistantResponseMessage {
+                    message_id: None,
+                    content: "Reading it".to_string(),
+                    tool_uses: Some(vec![ToolUse {
+                        tool_use_id: "tool-1".to_string(),
+                        name: "fs_read".to_string(),
+                        input: serde_value_to_document(serde_json::json!({ "path": "a.txt" })).into(),
+                    }]),
+                    cache_point: false,
+                }),
+            ]),
+            inference_config: None,
+        };
+
+        let (messages, tool_specs) = to_messages(conversation);
+        assert!(tool_specs.is_none());
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[0].role, Role::User);
+        assert!(matches!(&messages[0].content[..], [ContentBlock::Text(text)] if text == "read the file"));
+        assert!(matches!(
+            &messages[1].content[..],
+            [ContentBlock::Text(_), ContentBlock::ToolUse(ToolUseBlock { input, .. })]
+                if *input == serde_json::json!({ "path": "a.txt" })
+        ));
+        // Empty text blocks are rejected by the backends
+        assert!(matches!(
+            &messages[2].content[..],
+            [ContentBlock::ToolResult(ToolResultBlock { tool_use_id, .. })] if tool_use_id == "tool-1"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_output_maps_events() {
+        let tool_use_start = StreamEvent::ContentBlockStart(ContentBlockStartEvent {
+            content_block_start: Some(ContentBlockStart::ToolUse(
+                agent::agent_loop::types::ToolUseBlockStart {
+                    tool_use_id: "tool-1".to_string(),
+                    name: "fs_read".to_string(),
+                },
+            )),
+            content_block_index: None,
+        });
+        let delta = |delta| {
+            StreamEvent::ContentBlockDelta(ContentBlockDeltaEvent {
+                delta,
+                content_block_index: None,
+            })
+        };
+        let stop = StreamEvent::ContentBlockStop(agent::agent_loop::types::ContentBlockStopEvent {
+            content_block_index: None,
+        });
+        let events = vec![
+            StreamResult::Ok(delta(ContentBlockDelta::Text("hi".to_string()))),
+            StreamResult::Ok(stop.clone()),
+            StreamResult::Ok(tool_use_start),
+            StreamResult::Ok(delta(ContentBlockDelta::ToolUse(
+                agent::agent_loop::types::ToolUseBlockDelta {
+                    input: "{}".to_string(),
+                },
+            ))),
+            StreamResult::Ok(stop),
+            StreamResult::Err(StreamError::new(StreamErrorKind::Throttling)),
+        ];
+        let mut output = ChatModelOutput {
+            stream: Box::pin(futures::stream::iter(events)),
+            tool_use: None,
+            first_event: None,
+            _cancel_on_drop: CancellationToken::new().drop_guard(),
+        };
+
+        let tool_use = |input: Option<&str>, stop| ChatResponseStream::ToolUseEvent {
+            tool_use_id: "tool-1".to_string(),
+            name: "fs_read".to_string(),
+            input: input.map(String::from),
+            stop,
+        };
+        assert_eq!(
+            output.recv().await.unwrap(),
+            Some(ChatResponseStream::AssistantResponseEvent {
+                content: "hi".to_string()
+            })
+        );
+        assert_eq!(output.recv().await.unwrap(), Some(tool_use(None, None)));
+        assert_eq!(output.recv().await.unwrap(), Some(tool_use(Some("{}"), None)));
+        assert_eq!(output.recv().await.unwrap(), Some(tool_use(None, Some(true))));
+        assert!(matches!(
+            output.recv().await.unwrap_err().kind,
+            ConverseStreamErrorKind::Throttling
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_chat_with_openai_backend() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/v1/chat/completions")
+            .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+                "model": "qwen3:8b",
+                "messages": [{ "role": "user", "content": "hi" }],
+                "temperature": 0.5,
+                "max_tokens": 100,
+            })))
+            .with_header("content-type", "text/event-stream")
+            .with_body(concat!(
+                r#"data: {"choices":[{"index":0,"delta":{"role":"assistant","content":"Hello"}}]}"#,
+                "\n\n",
+                r#"data: {"choices":[{"index":0,"delta":{},"finish_reason":"stop"}]}"#,
+                "\n\ndata: [DONE]\n\n",
+            ))
+            .create_async()
+            .await;
+
+        let endpoint = format!("{}/v1", server.url());
+        let cli = <Cli as clap::Parser>::parse_from([
+            CHAT_BINARY_NAME,
+            "chat",
+            "--backend",
+            "openai",
+            "--model",
+            "qwen3:8b",
+            "--endpoint",
+            &endpoint,
+            "--temperature",
+            "0.5",
+            "--max-output-tokens",
+            "100",
+        ]);
+        let Some(RootSubcommand::Chat(args)) = cli.subcommand else {
+            panic!("expected the chat subcommand, found {:?}", cli.subcommand);
+        };
+
+        let mut os = Os::new().await.unwrap();
+        let model = args.backend.create_chat_model(&os, args.model.clone()).await.unwrap();
+        assert!(matches!(&model, ChatModel::OpenAi(model) if model.model_id() == "qwen3:8b"));
+        os.client.set_chat_model(model);
+
+        let mut output = os
+            .client
+            .send_message(ConversationState {
+                conversation_id: None,
+                user_input_message: user_message("hi", None),
+                history: None,
+                inference_config: Some(args.model_params),
+            })
+            .await
+            .unwrap();
+        let mut events = Vec::new();
+        while let Some(event) = output.recv().await.unwrap() {
+            events.push(event);
+        }
+
+        mock.assert_async().await;
+        assert_eq!(events, vec![ChatResponseStream::AssistantResponseEvent {
+            content: "Hello".to_string()
+        }]);
+    }
+}
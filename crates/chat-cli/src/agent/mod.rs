@@ -1 +1,130 @@
+//! [Model] implementations for the agent loop.
+//!
+//! The backend is picked with [BackendArgs], which is meant to be flattened into the arguments of
+//! the command running the agent loop, or of `q chat`.
+
+pub mod acp;
+mod bedrock;
+pub mod chat;
+mod openai;
 mod rts;
+
+use std::sync::Arc;
+
+use agent::agent_loop::model::Model;
+pub use chat::ChatModel;
+use clap::{
+    Args,
+    ValueEnum,
+};
+use eyre::{
+    Result,
+    bail,
+};
+use uuid::Uuid;
+
+use crate::os::Os;
+
+/// The service the agent loop sends its requests to.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Backend {
+    /// The Amazon Q backend, authenticated with your Builder ID or IAM Identity Center login
+    #[default]
+    Rts,
+    /// Amazon Bedrock, authenticated with the AWS credentials of your environment
+    Bedrock,
+    /// An OpenAI-compatible chat completions API, e.g. a local model served by Ollama or LM Studio
+    #[value(name = "openai")]
+    OpenAi,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Args)]
+pub struct BackendArgs {
+    /// The backend to send requests to
+    #[arg(long, value_enum, default_value_t)]
+    pub backend: Backend,
+    /// Base URL of the chat completions API used by --backend openai. Defaults to Ollama's
+    /// http://localhost:11434/v1
+    #[arg(long)]
+    pub endpoint: Option<String>,
+}
+
+impl BackendArgs {
+    /// Creates the [Model] for the selected backend. `model` is the id of the model, required
+    /// with every backend but the Q one.
+    pub async fn create_model(&self, os: &Os, conversation_id: Uuid, model: Option<String>) -> Result<Arc<dyn Model>> {
+        Ok(match self.backend {
+            Backend::Rts => Arc::new(rts::RtsModel::new(os.client.clone(), conversation_id, model)),
+            Backend::Bedrock | Backend::OpenAi => Arc::new(self.create_chat_model(os, model).await?),
+        })
+    }
+
+    /// Creates the [ChatModel] `q chat` sends its requests to, for every backend but the Q one.
+    pub async fn create_chat_model(&self, os: &Os, model: Option<String>) -> Result<ChatModel> {
+        match self.backend {
+            Backend::Rts => bail!("q chat sends the requests of the Q backend itself"),
+            Backend::Bedrock => {
+                let Some(model_id) = model else {
+                    bail!("--model is required with --backend bedrock");
+                };
+                Ok(ChatModel::Bedrock(bedrock::BedrockModel::from_env(model_id).await?))
+            },
+            Backend::OpenAi => {
+                let Some(model_id) = model else {
+                    bail!("--model is required with --backend openai");
+                };
+                Ok(ChatModel::OpenAi(openai::OpenAiModel::new(
+                    crate::request::new_client()?,
+                    self.endpoint.clone().unwrap_or(openai::DEFAULT_ENDPOINT.to_string()),
+                    model_id,
+                    os.env.get("OPENAI_API_KEY").ok(),
+                )))
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+
+    #[derive(Debug, Parser)]
+    struct TestCli {
+        #[command(flatten)]
+        backend: BackendArgs,
+    }
+
+    #[test]
+    fn test_backend_args_parse() {
+        assert_eq!(TestCli::parse_from(["test"]).backend, BackendArgs::default());
+        assert_eq!(
+            TestCli::parse_from(["test", "--backend", "bedrock"]).backend,
+            BackendArgs {
+                backend: Backend::Bedrock,
+                endpoint: None,
+            }
+        );
+        assert_eq!(
+            TestCli::parse_from(["test", "--backend", "openai", "--endpoint", "http://localhost:1234/v1"]).backend,
+            BackendArgs {
+                backend: Backend::OpenAi,
+                endpoint: Some("http://localhost:1234/v1".to_string()),
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_backend_requires_model() {
+        let os = Os::new().await.unwrap();
+        for (backend, name) in [(Backend::Bedrock, "bedrock"), (Backend::OpenAi, "openai")] {
+            let args = BackendArgs {
+                backend,
+                ..Default::default()
+            };
+            let err = args.create_model(&os, Uuid::new_v4(), None).await.err().unwrap();
+            assert_eq!(err.to_string(), format!("--model is required with --backend {name}"));
+        }
+    }
+}
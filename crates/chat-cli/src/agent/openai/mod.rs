@@ -0,0 +1,709 @@
+pub mod types;
+
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::{
+    Duration,
+    Instant,
+};
+
+use agent::agent_loop::model::Model;
+use agent::agent_loop::protocol::StreamResult;
+use agent::agent_loop::types::{
+    ContentBlockDelta,
+    ContentBlockDeltaEvent,
+    ContentBlockStart,
+    ContentBlockStartEvent,
+    ContentBlockStopEvent,
+    Message,
+    MessageStartEvent,
+    MessageStopEvent,
+    MetadataEvent,
+    MetadataMetrics,
+    MetadataService,
+    MetadataUsage,
+    Role,
+    StopReason,
+    StreamError,
+    StreamErrorKind,
+    StreamErrorSource,
+    StreamEvent,
+    ToolSpec,
+    ToolUseBlockDelta,
+    ToolUseBlockStart,
+};
+use chrono::{
+    DateTime,
+    Utc,
+};
+use futures::{
+    Stream,
+    StreamExt,
+};
+use reqwest::StatusCode;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_util::sync::CancellationToken;
+use tracing::{
+    debug,
+    error,
+    info,
+    trace,
+    warn,
+};
+use types::{
+    ChatCompletionChunk,
+    ChatCompletionRequest,
+    StreamOptions,
+};
+
+use crate::api_client::model::InferenceConfig;
+
+/// The endpoint used when none is given, which is where Ollama serves its OpenAI-compatible API.
+/// LM Studio serves it at `http://localhost:1234/v1`.
+pub const DEFAULT_ENDPOINT: &str = "http://localhost:11434/v1";
+
+/// A [Model] implementation using an OpenAI-compatible chat completions API, e.g. a model served
+/// locally by Ollama or LM Studio.
+#[derive(Debug, Clone)]
+pub struct OpenAiModel {
+    client: reqwest::Client,
+    /// Base URL of the API, the request is sent to `{endpoint}/chat/completions`.
+    endpoint: String,
+    model_id: String,
+    api_key: Option<String>,
+    inference_config: InferenceConfig,
+}
+
+impl OpenAiModel {
+    pub fn new(client: reqwest::Client, endpoint: String, model_id: String, api_key: Option<String>) -> Self {
+        Self {
+            client,
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            model_id,
+            api_key,
+            inference_config: InferenceConfig::default(),
+        }
+    }
+
+    /// Returns this model sending `config` as the sampling parameters of its requests.
+    pub fn with_inference_config(self, config: InferenceConfig) -> Self {
+        Self {
+            inference_config: config,
+            ..self
+        }
+    }
+
+    pub fn model_id(&self) -> &str {
+        &self.model_id
+    }
+
+    async fn converse_stream_openai(
+        self,
+        tx: mpsc::Sender<StreamResult>,
+        cancel_token: CancellationToken,
+        messages: Vec<Message>,
+        tool_specs: Option<Vec<ToolSpec>>,
+        system_prompt: Option<String>,
+    ) {
+        let request = ChatCompletionRequest {
+            model: self.model_id.clone(),
+            messages: types::to_chat_messages(messages, system_prompt),
+            tools: types::to_tools(tool_specs.unwrap_or_default()),
+            stream: true,
+            stream_options: StreamOptions { include_usage: true },
+            temperature: self.inference_config.temperature,
+            max_tokens: self.inference_config.max_output_tokens,
+            top_p: self.inference_config.top_p,
+        };
+        debug!(?request, "sending chat completion request");
+
+        let mut builder = self
+            .client
+            .post(format!("{}/chat/completions", self.endpoint))
+            .json(&request);
+        if let Some(api_key) = &self.api_key {
+            builder = builder.bearer_auth(api_key);
+        }
+
+        let request_start_time = Instant::now();
+        let request_start_time_sys = Utc::now();
+        let token_clone = cancel_token.clone();
+        let result = tokio::select! {
+            _ = token_clone.cancelled() => {
+                warn!("chat completion request cancelled during send");
+                tx.send(StreamResult::Err(StreamError::new(StreamErrorKind::Interrupted)))
+                    .await
+                    .map_err(|err| error!(?err, "failed to send event"))
+                    .ok();
+                return;
+            },
+            result = builder.send() => result,
+        };
+
+        let response = match result {
+            Ok(response) if response.status().is_success() => response,
+            Ok(response) => {
+                let status = response.status();
+                let body = response.text().await.unwrap_or_default();
+                error!(?status, ?body, "chat completion request failed");
+                self.send_err(
+                    &tx,
+                    StreamError::new(classify_status(status, &body))
+                        .set_original_status_code(Some(status.as_u16()))
+                        .set_original_message(Some(body.clone()))
+                        .with_source(Arc::new(OpenAiError::Status { status, body })),
+                )
+                .await;
+                return;
+            },
+            Err(err) => {
+                error!(?err, "failed to send chat completion request");
+                let kind = if err.is_connect() {
+                    StreamErrorKind::Other(format!(
+                        "Could not connect to {}, is the model server running?",
+                        self.endpoint
+                    ))
+                } else if err.is_timeout() {
+                    StreamErrorKind::ServiceFailure
+                } else {
+                    StreamErrorKind::Other(err.to_string())
+                };
+                self.send_err(
+                    &tx,
+                    StreamError::new(kind).with_source(Arc::new(OpenAiError::Request(err))),
+                )
+                .await;
+                return;
+            },
+        };
+
+        info!(request_duration = ?request_start_time.elapsed(), "chat completion request sent successfully");
+        let request_id = response
+            .headers()
+            .get("x-request-id")
+            .and_then(|v| v.to_str().ok())
+            .map(String::from);
+        let mut parser = ChunkParser::new(request_id, request_start_time, request_start_time_sys);
+        let mut body = response.bytes_stream();
+        let mut buf = Vec::new();
+        loop {
+            let start = Instant::now();
+            let chunk = tokio::select! {
+                _ = cancel_token.cancelled() => {
+                    debug!("chat completion stream was cancelled");
+                    self.send_all(&tx, vec![StreamResult::Ok(parser.make_metadata())]).await;
+                    self.send_err(&tx, StreamError::new(StreamErrorKind::Interrupted)).await;
+                    return;
+                },
+                chunk = body.next() => chunk,
+            };
+
+            match chunk {
+                Some(Ok(bytes)) => {
+                    parser.record_chunk(start.elapsed(), bytes.len());
+                    buf.extend_from_slice(&bytes);
+                    // Server-sent events are separated by new lines, only parse the complete ones.
+                    while let Some(pos) = buf.iter().position(|b| *b == b'\n') {
+                        let line = buf.drain(..=pos).collect::<Vec<_>>();
+                        match parser.parse_line(&String::from_utf8_lossy(&line)) {
+                            Ok(events) => self.send_all(&tx, events).await,
+                            Err(err) => {
+                                self.send_all(&tx, vec![StreamResult::Ok(parser.make_metadata())]).await;
+                                self.send_err(&tx, err).await;
+                                return;
+                            },
+                        }
+                    }
+                },
+                Some(Err(err)) => {
+                    error!(?err, "failed to receive the next chunk");
+                    self.send_all(&tx, vec![StreamResult::Ok(parser.make_metadata())]).await;
+                    self.send_err(
+                        &tx,
+                        StreamError::new(StreamErrorKind::Other(format!(
+                            "An unexpected error occurred during the response stream: {}",
+                            err
+                        )))
+                        .with_source(Arc::new(OpenAiError::Request(err))),
+                    )
+                    .await;
+                    return;
+                },
+                None => {
+                    debug!("chat completion stream has ended");
+                    if let Ok(events) = parser.parse_line(&String::from_utf8_lossy(&buf)) {
+                        self.send_all(&tx, events).await;
+                    }
+                    self.send_all(&tx, parser.finish()).await;
+                    return;
+                },
+            }
+        }
+    }
+
+    async fn send_all(&self, tx: &mpsc::Sender<StreamResult>, events: Vec<StreamResult>) {
+        for ev in events {
+            tx.send(ev)
+                .await
+                .map_err(|err| error!(?err, "failed to send event to channel"))
+                .ok();
+        }
+    }
+
+    async fn send_err(&self, tx: &mpsc::Sender<StreamResult>, err: StreamError) {
+        self.send_all(tx, vec![StreamResult::Err(err)]).await;
+    }
+}
+
+impl Model for OpenAiModel {
+    fn stream(
+        &self,
+        messages: Vec<Message>,
+        tool_specs: Option<Vec<ToolSpec>>,
+        system_prompt: Option<String>,
+        cancel_token: CancellationToken,
+    ) -> Pin<Box<dyn Stream<Item = StreamResult> + Send + 'static>> {
+        let (tx, rx) = mpsc::channel(16);
+
+        let self_clone = self.clone();
+        tokio::spawn(async move {
+            self_clone
+                .converse_stream_openai(tx, cancel_token, messages, tool_specs, system_prompt)
+                .await;
+        });
+
+        Box::pin(ReceiverStream::new(rx))
+    }
+}
+
+/// Maps an unsuccessful response to the [StreamErrorKind] the agent loop acts on. Servers disagree
+/// on how they report an overflowing context, so the body is checked for the usual wording.
+fn classify_status(status: StatusCode, body: &str) -> StreamErrorKind {
+    let body_lower = body.to_lowercase();
+    match status {
+        StatusCode::TOO_MANY_REQUESTS => StreamErrorKind::Throttling,
+        StatusCode::BAD_REQUEST | StatusCode::PAYLOAD_TOO_LARGE
+            if body_lower.contains("context length")
+                || body_lower.contains("context window")
+                || body_lower.contains("maximum context") =>
+        {
+            StreamErrorKind::ContextWindowOverflow
+        },
+        StatusCode::BAD_REQUEST | StatusCode::NOT_FOUND | StatusCode::UNPROCESSABLE_ENTITY => {
+            StreamErrorKind::Validation {
+                message: Some(body.to_string()),
+            }
+        },
+        status if status.is_server_error() => StreamErrorKind::ServiceFailure,
+        status => StreamErrorKind::Other(format!("The chat completion request failed with {}: {}", status, body)),
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum OpenAiError {
+    #[error(transparent)]
+    Request(reqwest::Error),
+    #[error("The server responded with {status}: {body}")]
+    Status { status: StatusCode, body: String },
+    #[error("Received an invalid chunk: {0}")]
+    InvalidChunk(#[source] serde_json::Error),
+}
+
+impl StreamErrorSource for OpenAiError {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+/// Translates the server-sent events of a chat completion into a sequence of [StreamEvent].
+///
+/// Tool calls are streamed as deltas keyed by their index, with the id and name only present in
+/// the first delta of each call.
+#[derive(Debug)]
+struct ChunkParser {
+    message_start_pushed: bool,
+    message_stop_pushed: bool,
+    /// Index of the tool call currently being streamed.
+    parsing_tool_call: Option<usize>,
+    tool_use_seen: bool,
+    usage: Option<MetadataUsage>,
+
+    // metadata fields
+    request_id: Option<String>,
+    /// Time immediately before sending the request.
+    request_start_time: Instant,
+    /// Time immediately before sending the request, as a [DateTime].
+    request_start_time_sys: DateTime<Utc>,
+    time_to_first_chunk: Option<Duration>,
+    time_between_chunks: Vec<Duration>,
+    /// Total size (in bytes) of the response received so far.
+    received_response_size: usize,
+}
+
+impl ChunkParser {
+    fn new(request_id: Option<String>, request_start_time: Instant, request_start_time_sys: DateTime<Utc>) -> Self {
+        Self {
+            message_start_pushed: false,
+            message_stop_pushed: false,
+            parsing_tool_call: None,
+            tool_use_seen: false,
+            usage: None,
+            request_id,
+            request_start_time,
+            request_start_time_sys,
+            time_to_first_chunk: None,
+            time_between_chunks: vec![],
+            received_response_size: 0,
+        }
+    }
+
+    fn record_chunk(&mut self, duration: Duration, len: usize) {
+        self.time_to_first_chunk
+            .get_or_insert_with(|| self.request_start_time.elapsed());
+        self.time_between_chunks.push(duration);
+        self.received_response_size += len;
+    }
+
+    /// Parses a single line of the event stream, returning the events to emit.
+    fn parse_line(&mut self, line: &str) -> Result<Vec<StreamResult>, StreamError> {
+        let Some(data) = line.trim().strip_prefix("data:").map(str::trim) else {
+            // Blank lines separate events, and comments or other fields carry nothing we need.
+            return Ok(vec![]);
+        };
+        if data == "[DONE]" {
+            return Ok(vec![]);
+        }
+
+        let chunk = serde_json::from_str::<ChatCompletionChunk>(data).map_err(|err| {
+            error!(?err, ?data, "received an invalid chunk");
+            StreamError::new(StreamErrorKind::Other(format!("Received an invalid chunk: {}", err)))
+                .set_original_request_id(self.request_id.clone())
+                .with_source(Arc::new(OpenAiError::InvalidChunk(err)))
+        })?;
+        trace!(?chunk, "received new chunk");
+        if self.request_id.is_none() {
+            self.request_id = chunk.id.clone();
+        }
+
+        let mut events = Vec::new();
+        if !self.message_start_pushed {
+            events.push(StreamEvent::MessageStart(MessageStartEvent { role: Role::Assistant }));
+            self.message_start_pushed = true;
+        }
+
+        if let Some(usage) = chunk.usage {
+            self.usage = Some(MetadataUsage {
+                input_tokens: Some(usage.prompt_tokens),
+                output_tokens: Some(usage.completion_tokens),
+                cache_read_input_tokens: None,
+                cache_write_input_tokens: None,
+            });
+        }
+
+        for choice in chunk.choices {
+            if let Some(text) = choice.delta.content.filter(|t| !t.is_empty()) {
+                events.push(StreamEvent::ContentBlockDelta(ContentBlockDeltaEvent {
+                    delta: ContentBlockDelta::Text(text),
+                    content_block_index: None,
+                }));
+            }
+
+            for tool_call in choice.delta.tool_calls {
+                let function = tool_call.function.unwrap_or(types::FunctionCallDelta {
+                    name: None,
+                    arguments: None,
+                });
+                if self.parsing_tool_call != Some(tool_call.index) {
+                    if self.parsing_tool_call.take().is_some() {
+                        events.push(StreamEvent::ContentBlockStop(ContentBlockStopEvent {
+                            content_block_index: None,
+                        }));
+                    }
+                    self.parsing_tool_call = Some(tool_call.index);
+                    self.tool_use_seen = true;
+                    events.push(StreamEvent::ContentBlockStart(ContentBlockStartEvent {
+                        content_block_start: Some(ContentBlockStart::ToolUse(ToolUseBlockStart {
+                            // Some servers omit the id, which is only needed to pair the result.
+                            tool_use_id: tool_call.id.unwrap_or_else(|| format!("call_{}", tool_call.index)),
+                            name: function.name.unwrap_or_default(),
+                        })),
+                        content_block_index: None,
+                    }));
+                }
+                if let Some(input) = function.arguments.filter(|a| !a.is_empty()) {
+                    events.push(StreamEvent::ContentBlockDelta(ContentBlockDeltaEvent {
+                        delta: ContentBlockDelta::ToolUse(ToolUseBlockDelta { input }),
+                        content_block_index: None,
+                    }));
+                }
+            }
+
+            if let Some(reason) = choice.finish_reason {
+                events.extend(self.stop(match reason.as_str() {
+                    "length" => StopReason::MaxTokens,
+                    _ if self.tool_use_seen => StopReason::ToolUse,
+                    _ => StopReason::EndTurn,
+                }));
+            }
+        }
+
+        Ok(events.into_iter().map(StreamResult::Ok).collect())
+    }
+
+    /// Closes the tool call being streamed and emits the [MessageStopEvent], if not done already.
+    fn stop(&mut self, stop_reason: StopReason) -> Vec<StreamEvent> {
+        let mut events = Vec::new();
+        if self.parsing_tool_call.take().is_some() {
+            events.push(StreamEvent::ContentBlockStop(ContentBlockStopEvent {
+                content_block_index: None,
+            }));
+        }
+        if !self.message_stop_pushed {
+            events.push(StreamEvent::MessageStop(MessageStopEvent { stop_reason }));
+            self.message_stop_pushed = true;
+        }
+        events
+    }
+
+    /// Returns the events ending the stream.
+    fn finish(&mut self) -> Vec<StreamResult> {
+        let mut events = Vec::new();
+        if !self.message_start_pushed {
+            events.push(StreamEvent::MessageStart(MessageStartEvent { role: Role::Assistant }));
+            self.message_start_pushed = true;
+        }
+        let stop_reason = if self.tool_use_seen {
+            StopReason::ToolUse
+        } else {
+            StopReason::EndTurn
+        };
+        events.extend(self.stop(stop_reason));
+        events.push(self.make_metadata());
+        events.into_iter().map(StreamResult::Ok).collect()
+    }
+
+    fn make_metadata(&self) -> StreamEvent {
+        StreamEvent::Metadata(MetadataEvent {
+            metrics: Some(MetadataMetrics {
+                request_start_time: self.request_start_time_sys,
+                request_end_time: Utc::now(),
+                time_to_first_chunk: self.time_to_first_chunk,
+                time_between_chunks: if self.time_between_chunks.is_empty() {
+                    None
+                } else {
+                    Some(self.time_between_chunks.clone())
+                },
+                response_stream_len: self.received_response_size as u32,
+            }),
+            usage: self.usage.clone(),
+            service: Some(MetadataService {
+                request_id: self.request_id.clone(),
+                status_code: None,
+            }),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use agent::agent_loop::types::ContentBlock;
+
+    use super::*;
+
+    const TOOL_CALL_STREAM: &str = r#"data: {"id":"chatcmpl-1","choices":[{"index":0,"delta":{"role":"assistant","content":"Listing"},"finish_reason":null}]}
+
+data: {"id":"chatcmpl-1","choices":[{"index":0,"delta":{"tool_calls":[{"index":0,"id":"call_1","type":"function","function":{"name":"execute_bash","arguments":""}}]},"finish_reason":null}]}
+
+data: {"id":"chatcmpl-1","choices":[{"index":0,"delta":{"tool_calls":[{"index":0,"function":{"arguments":"{\"command\":"}}]},"finish_reason":null}]}
+
+data: {"id":"chatcmpl-1","choices":[{"index":0,"delta":{"tool_calls":[{"index":0,"function":{"arguments":"\"ls\"}"}}]},"finish_reason":null}]}
+
+data: {"id":"chatcmpl-1","choices":[{"index":0,"delta":{},"finish_reason":"tool_calls"}]}
+
+data: {"id":"chatcmpl-1","choices":[],"usage":{"prompt_tokens":120,"completion_tokens":18,"total_tokens":138}}
+
+data: [DONE]
+
+"#;
+
+    fn parse(body: &str) -> Vec<StreamEvent> {
+        let mut parser = ChunkParser::new(None, Instant::now(), Utc::now());
+        let mut events = Vec::new();
+        for line in body.lines() {
+            events.extend(parser.parse_line(line).unwrap());
+        }
+        events.extend(parser.finish());
+        events
+            .into_iter()
+            .map(|ev| match ev {
+                StreamResult::Ok(ev) => ev,
+                StreamResult::Err(err) => panic!("unexpected stream error: {:?}", err),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_tool_call_stream() {
+        let events = parse(TOOL_CALL_STREAM);
+        assert!(matches!(events[0], StreamEvent::MessageStart(_)));
+        assert!(matches!(
+            &events[1],
+            StreamEvent::ContentBlockDelta(ContentBlockDeltaEvent { delta: ContentBlockDelta::Text(t), .. }) if t == "Listing"
+        ));
+        assert!(matches!(
+            &events[2],
+            StreamEvent::ContentBlockStart(ContentBlockStartEvent {
+                content_block_start: Some(ContentBlockStart::ToolUse(ToolUseBlockStart { tool_use_id, name })),
+                ..
+            }) if tool_use_id == "call_1" && name == "execute_bash"
+        ));
+        let input = events
+            .iter()
+            .filter_map(|ev| match ev {
+                StreamEvent::ContentBlockDelta(ContentBlockDeltaEvent {
+                    delta: ContentBlockDelta::ToolUse(ToolUseBlockDelta { input }),
+                    ..
+                }) => Some(input.as_str()),
+                _ => None,
+            })
+            .collect::<String>();
+        assert_eq!(input, r#"{"command":"ls"}"#);
+        assert!(matches!(events[5], StreamEvent::ContentBlockStop(_)));
+        assert!(matches!(
+            events[6],
+            StreamEvent::MessageStop(MessageStopEvent {
+                stop_reason: StopReason::ToolUse
+            })
+        ));
+        let StreamEvent::Metadata(metadata) = &events[7] else {
+            panic!("expected metadata, found {:?}", events[7]);
+        };
+        let usage = metadata.usage.as_ref().unwrap();
+        assert_eq!((usage.input_tokens, usage.output_tokens), (Some(120), Some(18)));
+        assert_eq!(
+            metadata.service.as_ref().unwrap().request_id.as_deref(),
+            Some("chatcmpl-1")
+        );
+        assert_eq!(events.len(), 8);
+    }
+
+    #[test]
+    fn test_parse_multiple_tool_calls() {
+        let body = r#"data: {"choices":[{"delta":{"tool_calls":[{"index":0,"id":"call_a","function":{"name":"fs_read","arguments":"{}"}}]}}]}
+data: {"choices":[{"delta":{"tool_calls":[{"index":1,"id":"call_b","function":{"name":"fs_read","arguments":"{}"}}]}}]}
+data: {"choices":[{"delta":{},"finish_reason":"stop"}]}"#;
+        let events = parse(body);
+        let starts = events
+            .iter()
+            .filter(|ev| matches!(ev, StreamEvent::ContentBlockStart(_)))
+            .count();
+        let stops = events
+            .iter()
+            .filter(|ev| matches!(ev, StreamEvent::ContentBlockStop(_)))
+            .count();
+        assert_eq!((starts, stops), (2, 2));
+        // Some servers report "stop" even when calling tools
+        assert!(events.iter().any(|ev| matches!(
+            ev,
+            StreamEvent::MessageStop(MessageStopEvent {
+                stop_reason: StopReason::ToolUse
+            })
+        )));
+    }
+
+    #[test]
+    fn test_parse_invalid_chunk() {
+        let mut parser = ChunkParser::new(None, Instant::now(), Utc::now());
+        assert!(parser.parse_line(": keep-alive").unwrap().is_empty());
+        let err = parser.parse_line("data: {not json").unwrap_err();
+        assert!(err.as_concrete_error::<OpenAiError>().is_some());
+    }
+
+    #[test]
+    fn test_classify_status() {
+        assert!(matches!(
+            classify_status(StatusCode::TOO_MANY_REQUESTS, ""),
+            StreamErrorKind::Throttling
+        ));
+        assert!(matches!(
+            classify_status(
+                StatusCode::BAD_REQUEST,
+                "This model's maximum context length is 8192 tokens"
+            ),
+            StreamErrorKind::ContextWindowOverflow
+        ));
+        assert!(matches!(
+            classify_status(StatusCode::NOT_FOUND, "model \"llama\" not found"),
+            StreamErrorKind::Validation { .. }
+        ));
+        assert!(matches!(
+            classify_status(StatusCode::BAD_GATEWAY, ""),
+            StreamErrorKind::ServiceFailure
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_openai_model_stream() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/v1/chat/completions")
+            .match_header("authorization", "Bearer secret")
+            .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+                "model": "qwen3",
+                "stream": true,
+                "messages": [{ "role": "user", "content": "list the files" }],
+            })))
+            .with_header("content-type", "text/event-stream")
+            .with_body(TOOL_CALL_STREAM)
+            .create_async()
+            .await;
+
+        let model = OpenAiModel::new(
+            reqwest::Client::new(),
+            format!("{}/v1/", server.url()),
+            "qwen3".to_string(),
+            Some("secret".to_string()),
+        );
+        let events = model
+            .stream(
+                vec![Message::new(
+                    Role::User,
+                    vec![ContentBlock::Text("list the files".to_string())],
+                    None,
+                )],
+                None,
+                None,
+                CancellationToken::new(),
+            )
+            .collect::<Vec<_>>()
+            .await;
+
+        mock.assert_async().await;
+        assert_eq!(events.len(), 8);
+        assert!(events.iter().all(|ev| matches!(ev, StreamResult::Ok(_))));
+    }
+
+    #[tokio::test]
+    async fn test_openai_model_error_status() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/chat/completions")
+            .with_status(429)
+            .with_body("slow down")
+            .create_async()
+            .await;
+
+        let model = OpenAiModel::new(reqwest::Client::new(), server.url(), "qwen3".to_string(), None);
+        let events = model
+            .stream(vec![], None, None, CancellationToken::new())
+            .collect::<Vec<_>>()
+            .await;
+
+        let [StreamResult::Err(err)] = events.as_slice() else {
+            panic!("expected a single error, found {:?}", events);
+        };
+        assert!(matches!(err.kind, StreamErrorKind::Throttling));
+        assert_eq!(err.original_status_code, Some(429));
+    }
+}
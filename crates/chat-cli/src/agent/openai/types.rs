@@ -0,0 +1,329 @@
+//! Wire types of the OpenAI-compatible chat completions API, and conversions from the types of the
+//! agent loop.
+
+use agent::agent_loop::types::*;
+use base64::Engine as _;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ChatCompletionRequest {
+    pub model: String,
+    pub messages: Vec<ChatMessage>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tools: Vec<Tool>,
+    pub stream: bool,
+    pub stream_options: StreamOptions,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StreamOptions {
+    pub include_usage: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "role", rename_all = "lowercase")]
+pub enum ChatMessage {
+    System {
+        content: String,
+    },
+    User {
+        content: UserContent,
+    },
+    Assistant {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        content: Option<String>,
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        tool_calls: Vec<ToolCall>,
+    },
+    Tool {
+        tool_call_id: String,
+        content: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum UserContent {
+    Text(String),
+    Parts(Vec<ContentPart>),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContentPart {
+    Text { text: String },
+    ImageUrl { image_url: ImageUrl },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ImageUrl {
+    pub url: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ToolCall {
+    pub id: String,
+    pub r#type: &'static str,
+    pub function: FunctionCall,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FunctionCall {
+    pub name: String,
+    /// The arguments of the call, as a JSON encoded string.
+    pub arguments: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Tool {
+    pub r#type: &'static str,
+    pub function: FunctionDefinition,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FunctionDefinition {
+    pub name: String,
+    pub description: String,
+    pub parameters: serde_json::Map<String, serde_json::Value>,
+}
+
+/// A single server-sent event of a streamed chat completion.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ChatCompletionChunk {
+    #[serde(default)]
+    pub id: Option<String>,
+    #[serde(default)]
+    pub choices: Vec<ChunkChoice>,
+    #[serde(default)]
+    pub usage: Option<Usage>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ChunkChoice {
+    #[serde(default)]
+    pub delta: ChunkDelta,
+    #[serde(default)]
+    pub finish_reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ChunkDelta {
+    #[serde(default)]
+    pub content: Option<String>,
+    #[serde(default)]
+    pub tool_calls: Vec<ToolCallDelta>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ToolCallDelta {
+    pub index: usize,
+    #[serde(default)]
+    pub id: Option<String>,
+    #[serde(default)]
+    pub function: Option<FunctionCallDelta>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct FunctionCallDelta {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub arguments: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Usage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+/// Converts the conversation into chat completion messages.
+///
+/// Tool results are sent as `tool` messages preceding the rest of the user message, since the API
+/// doesn't allow mixing them with other content.
+pub fn to_chat_messages(messages: Vec<Message>, system_prompt: Option<String>) -> Vec<ChatMessage> {
+    let mut out = Vec::new();
+    if let Some(content) = system_prompt {
+        out.push(ChatMessage::System { content });
+    }
+
+    for message in messages {
+        match message.role {
+            Role::User => {
+                let mut parts = Vec::new();
+                for block in message.content {
+                    match block {
+                        ContentBlock::Text(text) => parts.push(ContentPart::Text { text }),
+                        ContentBlock::Image(image) => parts.push(to_image_part(image)),
+                        ContentBlock::ToolResult(result) => {
+                            let mut content = Vec::new();
+                            for c in result.content {
+                                match c {
+                                    ToolResultContentBlock::Text(text) => content.push(text),
+                                    ToolResultContentBlock::Json(value) => content.push(value.to_string()),
+                                    // Tool messages can only contain text
+                                    ToolResultContentBlock::Image(image) => parts.push(to_image_part(image)),
+                                }
+                            }
+                            if matches!(result.status, ToolResultStatus::Error) && content.is_empty() {
+                                content.push("The tool failed".to_string());
+                            }
+                            out.push(ChatMessage::Tool {
+                                tool_call_id: result.tool_use_id,
+                                content: content.join("\n"),
+                            });
+                        },
+                        // Prefix caching is automatic for the backends supporting it
+                        ContentBlock::ToolUse(_) | ContentBlock::CachePoint => (),
+                    }
+                }
+
+                let content = match parts.as_slice() {
+                    [] => continue,
+                    [ContentPart::Text { text }] => UserContent::Text(text.clone()),
+                    _ => UserContent::Parts(parts),
+                };
+                out.push(ChatMessage::User { content });
+            },
+            Role::Assistant => {
+                let content = message.text();
+                let tool_calls = message
+                    .content
+                    .into_iter()
+                    .filter_map(|block| match block {
+                        ContentBlock::ToolUse(tool_use) => Some(ToolCall {
+                            id: tool_use.tool_use_id,
+                            r#type: "function",
+                            function: FunctionCall {
+                                name: tool_use.name,
+                                arguments: tool_use.input.to_string(),
+                            },
+                        }),
+                        _ => None,
+                    })
+                    .collect();
+                out.push(ChatMessage::Assistant {
+                    content: (!content.is_empty()).then_some(content),
+                    tool_calls,
+                });
+            },
+        }
+    }
+
+    out
+}
+
+fn to_image_part(image: ImageBlock) -> ContentPart {
+    let ImageSource::Bytes(bytes) = image.source;
+    let mime = match image.format {
+        ImageFormat::Gif => "image/gif",
+        ImageFormat::Jpeg => "image/jpeg",
+        ImageFormat::Png => "image/png",
+        ImageFormat::Webp => "image/webp",
+    };
+    ContentPart::ImageUrl {
+        image_url: ImageUrl {
+            url: format!("data:{};base64,{}", mime, BASE64.encode(bytes)),
+        },
+    }
+}
+
+pub fn to_tools(tool_specs: Vec<ToolSpec>) -> Vec<Tool> {
+    tool_specs
+        .into_iter()
+        .map(|spec| Tool {
+            r#type: "function",
+            function: FunctionDefinition {
+                name: spec.name,
+                description: spec.description,
+                parameters: spec.input_schema,
+            },
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_to_chat_messages() {
+        let messages = vec![
+            Message::new(Role::User, vec![ContentBlock::Text("list the files".to_string())], None),
+            Message::new(
+                Role::Assistant,
+                vec![ContentBlock::ToolUse(ToolUseBlock {
+                    tool_use_id: "call_1".to_string(),
+                    name: "execute_bash".to_string(),
+                    input: json!({ "command": "ls" }),
+                })],
+                None,
+            ),
+            Message::new(
+                Role::User,
+                vec![ContentBlock::ToolResult(ToolResultBlock {
+                    tool_use_id: "call_1".to_string(),
+                    content: vec![ToolResultContentBlock::Text("Cargo.toml".to_string())],
+                    status: ToolResultStatus::Success,
+                })],
+                None,
+            ),
+        ];
+
+        let messages = to_chat_messages(messages, Some("be brief".to_string()));
+        assert_eq!(
+            serde_json::to_value(&messages).unwrap(),
+            json!([
+                { "role": "system", "content": "be brief" },
+                { "role": "user", "content": "list the files" },
+                {
+                    "role": "assistant",
+                    "tool_calls": [{
+                        "id": "call_1",
+                        "type": "function",
+                        "function": { "name": "execute_bash", "arguments": "{\"command\":\"ls\"}" }
+                    }]
+                },
+                { "role": "tool", "tool_call_id": "call_1", "content": "Cargo.toml" },
+            ])
+        );
+    }
+
+    #[test]
+    fn test_to_chat_messages_images() {
+        let messages = vec![Message::new(
+            Role::User,
+            vec![
+                ContentBlock::Text("what is this?".to_string()),
+                ContentBlock::Image(ImageBlock {
+                    format: ImageFormat::Png,
+                    source: ImageSource::Bytes(vec![1, 2, 3]),
+                }),
+            ],
+            None,
+        )];
+
+        assert_eq!(
+            serde_json::to_value(to_chat_messages(messages, None)).unwrap(),
+            json!([{
+                "role": "user",
+                "content": [
+                    { "type": "text", "text": "what is this?" },
+                    { "type": "image_url", "image_url": { "url": "data:image/png;base64,AQID" } },
+                ]
+            }])
+        );
+    }
+}
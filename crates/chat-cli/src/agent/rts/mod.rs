@@ -1,5 +1,3 @@
-#![allow(dead_code)]
-
 pub mod types;
 
 use std::pin::Pin;
@@ -93,14 +91,6 @@ impl RtsModel {
         }
     }
 
-    pub fn conversation_id(&self) -> &Uuid {
-        &self.conversation_id
-    }
-
-    pub fn model_id(&self) -> Option<&str> {
-        self.model_id.as_deref()
-    }
-
     async fn converse_stream_rts(
         self,
         tx: mpsc::Sender<StreamResult>,
@@ -181,7 +171,9 @@ impl RtsModel {
                     ConverseStreamErrorKind::MonthlyLimitReached => StreamErrorKind::Other(err.to_string()),
                     ConverseStreamErrorKind::ContextWindowOverflow => StreamErrorKind::ContextWindowOverflow,
                     ConverseStreamErrorKind::ModelOverloadedError => StreamErrorKind::Throttling,
-                    ConverseStreamErrorKind::Unknown { .. } => StreamErrorKind::Other(err.to_string()),
+                    ConverseStreamErrorKind::InferenceConfigUnsupported | ConverseStreamErrorKind::Unknown { .. } => {
+                        StreamErrorKind::Other(err.to_string())
+                    },
                 };
                 let request_id = err.request_id.clone();
                 tx.send(StreamResult::Err(
@@ -265,6 +257,7 @@ impl RtsModel {
                         message_id: m.id.clone(),
                         content: m.text(),
                         tool_uses: m.tool_uses().map(|v| v.into_iter().map(Into::into).collect()),
+                        cache_point: m.content.iter().any(|c| matches!(c, ContentBlock::CachePoint)),
                     };
                     rts::ChatMessage::AssistantResponseMessage(msg)
                 },
@@ -275,6 +268,7 @@ impl RtsModel {
             conversation_id: Some(self.conversation_id.to_string()),
             user_input_message,
             history: Some(history),
+            inference_config: None,
         })
     }
 }
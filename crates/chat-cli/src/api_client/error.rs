@@ -1,6 +1,7 @@
 use amzn_codewhisperer_client::operation::create_subscription_token::CreateSubscriptionTokenError;
 use amzn_codewhisperer_client::operation::generate_completions::GenerateCompletionsError;
 use amzn_codewhisperer_client::operation::get_profile::GetProfileError;
+use amzn_codewhisperer_client::operation::get_usage_limits::GetUsageLimitsError;
 use amzn_codewhisperer_client::operation::list_available_customizations::ListAvailableCustomizationsError;
 use amzn_codewhisperer_client::operation::list_available_models::ListAvailableModelsError;
 use amzn_codewhisperer_client::operation::list_available_profiles::ListAvailableProfilesError;
@@ -54,6 +55,9 @@ pub enum ApiClientError {
     #[error("{}", SdkErrorDisplay(.0))]
     CreateSubscriptionToken(#[from] SdkError<CreateSubscriptionTokenError, HttpResponse>),
 
+    #[error("{}", SdkErrorDisplay(.0))]
+    GetUsageLimits(#[from] SdkError<GetUsageLimitsError, HttpResponse>),
+
     #[error(transparent)]
     SmithyBuild(#[from] aws_smithy_types::error::operation::BuildError),
 
@@ -90,6 +94,7 @@ impl ApiClientError {
             Self::ListAvailableProfilesError(e) => sdk_status_code(e),
             Self::SendTelemetryEvent(e) => sdk_status_code(e),
             Self::CreateSubscriptionToken(e) => sdk_status_code(e),
+            Self::GetUsageLimits(e) => sdk_status_code(e),
             Self::SmithyBuild(_) => None,
             Self::AuthError(_) => None,
             Self::Credentials(_e) => None,
@@ -113,6 +118,7 @@ impl ReasonCode for ApiClientError {
             Self::ListAvailableProfilesError(e) => sdk_error_code(e),
             Self::SendTelemetryEvent(e) => sdk_error_code(e),
             Self::CreateSubscriptionToken(e) => sdk_error_code(e),
+            Self::GetUsageLimits(e) => sdk_error_code(e),
             Self::SmithyBuild(_) => "SmithyBuildError".to_string(),
             Self::AuthError(_) => "AuthError".to_string(),
             Self::Credentials(_) => "CredentialsError".to_string(),
@@ -152,6 +158,24 @@ impl ConverseStreamError {
         self.status_code = status_code;
         self
     }
+
+    /// Whether the request failed because the backend couldn't be reached at all, e.g. when the
+    /// network is down.
+    pub fn is_connectivity_error(&self) -> bool {
+        match &self.source {
+            Some(ConverseStreamSdkError::CodewhispererGenerateAssistantResponse(err)) => is_connectivity_sdk_error(err),
+            Some(ConverseStreamSdkError::QDeveloperSendMessage(err)) => is_connectivity_sdk_error(err),
+            _ => false,
+        }
+    }
+}
+
+fn is_connectivity_sdk_error<E, R>(err: &SdkError<E, R>) -> bool {
+    match err {
+        SdkError::DispatchFailure(failure) => failure.is_io() || failure.is_timeout(),
+        SdkError::TimeoutError(_) => true,
+        _ => false,
+    }
 }
 
 impl ReasonCode for ConverseStreamError {
@@ -162,6 +186,7 @@ impl ReasonCode for ConverseStreamError {
             ConverseStreamErrorKind::MonthlyLimitReached => "MonthlyLimitReached".to_string(),
             ConverseStreamErrorKind::ContextWindowOverflow => "ContextWindowOverflow".to_string(),
             ConverseStreamErrorKind::ModelOverloadedError => "ModelOverloadedError".to_string(),
+            ConverseStreamErrorKind::InferenceConfigUnsupported => "InferenceConfigUnsupported".to_string(),
             ConverseStreamErrorKind::Unknown { reason_code } => reason_code.clone(),
         }
     }
@@ -198,6 +223,11 @@ pub enum ConverseStreamErrorKind {
         "The model you've selected is temporarily unavailable. Please use '/model' to select a different model and try again."
     )]
     ModelOverloadedError,
+    /// Sampling parameters were set on the request, which neither streaming API accepts yet.
+    #[error(
+        "The Q Developer backend does not accept model parameters yet. Remove them with '/model params --reset' and try again."
+    )]
+    InferenceConfigUnsupported,
     #[error("An unknown error occurred: {}", .reason_code)]
     Unknown { reason_code: String },
 }
@@ -210,6 +240,9 @@ pub enum ConverseStreamSdkError {
     QDeveloperSendMessage(#[from] SdkError<QDeveloperSendMessageError, HttpResponse>),
     #[error(transparent)]
     SmithyBuild(#[from] aws_smithy_types::error::operation::BuildError),
+    /// Error of a backend other than the Q one, see [crate::agent::ChatModel]
+    #[error("{}", .0.kind)]
+    Model(#[from] agent::agent_loop::types::StreamError),
 }
 
 pub fn sdk_error_code<T: ProvideErrorMetadata, R>(e: &SdkError<T, R>) -> String {
@@ -226,6 +259,7 @@ fn sdk_status_code<E>(e: &SdkError<E, Response>) -> Option<u16> {
 mod tests {
     use std::error::Error as _;
 
+    use aws_smithy_runtime_api::client::result::ConnectorError;
     use aws_smithy_runtime_api::http::Response;
     use aws_smithy_types::body::SdkBody;
     use aws_smithy_types::event_stream::Message;
@@ -288,6 +322,10 @@ mod tests {
                 CreateSubscriptionTokenError::unhandled("<unhandled>"),
                 response(),
             )),
+            ApiClientError::GetUsageLimits(SdkError::service_error(
+                GetUsageLimitsError::unhandled("<unhandled>"),
+                response(),
+            )),
             ApiClientError::CodewhispererChatResponseStream(SdkError::service_error(
                 CodewhispererChatResponseStreamError::unhandled("<unhandled>"),
                 raw_message(),
@@ -300,6 +338,30 @@ mod tests {
         ]
     }
 
+    #[test]
+    fn test_is_connectivity_error() {
+        let dispatch_failure = ConverseStreamError::new(
+            ConverseStreamErrorKind::Unknown {
+                reason_code: "dispatch failure".to_string(),
+            },
+            Some(
+                SdkError::<GenerateAssistantResponseError, HttpResponse>::dispatch_failure(ConnectorError::io(
+                    std::io::Error::from(std::io::ErrorKind::ConnectionRefused).into(),
+                )),
+            ),
+        );
+        assert!(dispatch_failure.is_connectivity_error());
+
+        let service_error = ConverseStreamError::new(
+            ConverseStreamErrorKind::Throttling,
+            Some(SdkError::service_error(
+                GenerateAssistantResponseError::unhandled("<unhandled>"),
+                response(),
+            )),
+        );
+        assert!(!service_error.is_connectivity_error());
+    }
+
     #[test]
     fn test_errors() {
         for error in all_errors() {
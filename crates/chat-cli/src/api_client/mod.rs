@@ -7,12 +7,15 @@ pub mod model;
 mod opt_out;
 pub mod profile;
 mod retry_classifier;
+pub mod retry_policy;
 pub mod send_message_output;
+pub mod stream_timeouts;
 use std::sync::Arc;
 use std::time::Duration;
 
 use amzn_codewhisperer_client::Client as CodewhispererClient;
 use amzn_codewhisperer_client::operation::create_subscription_token::CreateSubscriptionTokenOutput;
+use amzn_codewhisperer_client::operation::get_usage_limits::GetUsageLimitsOutput;
 use amzn_codewhisperer_client::types::Origin::Cli;
 use amzn_codewhisperer_client::types::{
     Model,
@@ -25,7 +28,6 @@ use amzn_codewhisperer_client::types::{
 use amzn_codewhisperer_streaming_client::Client as CodewhispererStreamingClient;
 use amzn_qdeveloper_streaming_client::Client as QDeveloperStreamingClient;
 use amzn_qdeveloper_streaming_client::types::Origin;
-use aws_config::retry::RetryConfig;
 use aws_config::timeout::TimeoutConfig;
 use aws_credential_types::Credentials;
 use aws_credential_types::provider::ProvideCredentials;
@@ -47,6 +49,7 @@ use tracing::{
     error,
 };
 
+use crate::agent::ChatModel;
 use crate::api_client::credentials::CredentialsChain;
 use crate::api_client::delay_interceptor::DelayTrackingInterceptor;
 use crate::api_client::model::{
@@ -54,7 +57,9 @@ use crate::api_client::model::{
     ConversationState,
 };
 use crate::api_client::opt_out::OptOutInterceptor;
+use crate::api_client::retry_policy::RetryPolicy;
 use crate::api_client::send_message_output::SendMessageOutput;
+use crate::api_client::stream_timeouts::StreamTimeouts;
 use crate::auth::builder_id::BearerResolver;
 use crate::aws_common::{
     UserAgentOverrideInterceptor,
@@ -102,6 +107,10 @@ pub struct ApiClient {
     mock_client: Option<Arc<Mutex<std::vec::IntoIter<Vec<ChatResponseStream>>>>>,
     profile: Option<AuthProfile>,
     model_cache: ModelCache,
+    retry_policy: RetryPolicy,
+    stream_timeouts: StreamTimeouts,
+    /// Backend other than the Q one that chat requests are sent to, set with `q chat --backend`
+    chat_model: Option<ChatModel>,
 }
 
 impl ApiClient {
@@ -113,13 +122,15 @@ impl ApiClient {
         endpoint: Option<Endpoint>,
     ) -> Result<Self, ApiClientError> {
         let endpoint = endpoint.unwrap_or(Endpoint::configured_value(database));
+        let retry_policy = RetryPolicy::from_database(database);
+        let stream_timeouts = StreamTimeouts::from_database(database);
 
         let credentials = Credentials::new("xxx", "xxx", None, None, "xxx");
         let bearer_sdk_config = aws_config::defaults(behavior_version())
             .region(endpoint.region.clone())
             .credentials_provider(credentials)
             .timeout_config(timeout_config(database))
-            .retry_config(retry_config())
+            .retry_config(retry_policy.sdk_config())
             .load()
             .await;
 
@@ -142,6 +153,9 @@ impl ApiClient {
                 mock_client: None,
                 profile: None,
                 model_cache: Arc::new(RwLock::new(None)),
+                retry_policy,
+                stream_timeouts,
+                chat_model: None,
             };
 
             if let Some(json) = crate::util::env_var::get_mock_chat_response(env) {
@@ -167,7 +181,7 @@ impl ApiClient {
                             .region(endpoint.region.clone())
                             .credentials_provider(credentials_chain)
                             .timeout_config(timeout_config(database))
-                            .retry_config(retry_config())
+                            .retry_config(retry_policy.sdk_config())
                             .load()
                             .await,
                     )
@@ -221,6 +235,9 @@ impl ApiClient {
             mock_client: None,
             profile,
             model_cache: Arc::new(RwLock::new(None)),
+            retry_policy,
+            stream_timeouts,
+            chat_model: None,
         })
     }
 
@@ -381,19 +398,66 @@ impl ApiClient {
             .map_err(ApiClientError::CreateSubscriptionToken)
     }
 
+    /// Usage of the monthly allowance of the user and their subscription.
+    pub async fn get_usage_limits(&self) -> Result<GetUsageLimitsOutput, ApiClientError> {
+        if cfg!(test) {
+            return Ok(GetUsageLimitsOutput::builder().build());
+        }
+
+        self.client
+            .get_usage_limits()
+            .set_origin(Some(Cli))
+            .set_profile_arn(self.profile.as_ref().map(|p| p.arn.clone()))
+            .send()
+            .await
+            .map_err(ApiClientError::GetUsageLimits)
+    }
+
+    /// Policy used to retry the requests failing with transient errors.
+    pub fn retry_policy(&self) -> RetryPolicy {
+        self.retry_policy
+    }
+
+    /// Timeouts of the response streams.
+    pub fn stream_timeouts(&self) -> StreamTimeouts {
+        self.stream_timeouts
+    }
+
+    /// Sends the requests of [Self::send_message] to `model` instead of the Q backend.
+    pub fn set_chat_model(&mut self, model: ChatModel) {
+        self.chat_model = Some(model);
+    }
+
+    /// The backend set with [Self::set_chat_model], if any.
+    pub fn chat_model(&self) -> Option<&ChatModel> {
+        self.chat_model.as_ref()
+    }
+
     pub async fn send_message(
         &self,
         conversation: ConversationState,
     ) -> Result<SendMessageOutput, ConverseStreamError> {
         debug!("Sending conversation: {:#?}", conversation);
 
+        if let Some(model) = &self.chat_model {
+            return Ok(SendMessageOutput::Model(model.send(conversation).await?));
+        }
+
         let ConversationState {
             conversation_id,
             user_input_message,
             history,
+            inference_config,
         } = conversation;
 
         let model_id_opt: Option<String> = user_input_message.model_id.clone();
+        // Neither streaming API accepts sampling parameters yet
+        if inference_config.is_some() && self.mock_client.is_none() {
+            return Err(ConverseStreamError::new(
+                ConverseStreamErrorKind::InferenceConfigUnsupported,
+                None::<error::ConverseStreamSdkError>,
+            ));
+        }
 
         if let Some(client) = &self.streaming_client {
             let conversation_state = amzn_codewhisperer_streaming_client::types::ConversationState::builder()
@@ -581,12 +645,6 @@ fn timeout_config(database: &Database) -> TimeoutConfig {
         .build()
 }
 
-fn retry_config() -> RetryConfig {
-    RetryConfig::adaptive()
-        .with_max_attempts(3)
-        .with_max_backoff(MAX_RETRY_DELAY_DURATION)
-}
-
 pub fn stalled_stream_protection_config() -> StalledStreamProtectionConfig {
     StalledStreamProtectionConfig::enabled()
         .grace_period(Duration::from_secs(60 * 5))
@@ -699,6 +757,7 @@ mod tests {
                     model_id: Some("model".to_owned()),
                 },
                 history: None,
+                inference_config: None,
             })
             .await
             .unwrap();
@@ -4,6 +4,7 @@ use aws_smithy_types::{
     Blob,
     Document as AwsDocument,
 };
+use schemars::JsonSchema;
 use serde::de::{
     self,
     MapAccess,
@@ -97,6 +98,74 @@ pub struct ConversationState {
     pub conversation_id: Option<String>,
     pub user_input_message: UserInputMessage,
     pub history: Option<Vec<ChatMessage>>,
+    /// Sampling parameters requested for the response, if any were set.
+    pub inference_config: Option<InferenceConfig>,
+}
+
+/// Parameters controlling how the model samples its response. Unset parameters use the model's
+/// defaults.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, JsonSchema, clap::Args)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct InferenceConfig {
+    /// Randomness of the response, between 0 and 1. Lower values give more deterministic responses
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[arg(long)]
+    pub temperature: Option<f32>,
+    /// Maximum number of tokens generated in a response
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[arg(long)]
+    pub max_output_tokens: Option<u32>,
+    /// Nucleus sampling: only the most likely tokens whose probabilities add up to this value,
+    /// between 0 and 1, are considered
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[arg(long)]
+    pub top_p: Option<f32>,
+}
+
+impl InferenceConfig {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Returns this config with the parameters set in `overrides` replaced.
+    pub fn merge(self, overrides: Self) -> Self {
+        Self {
+            temperature: overrides.temperature.or(self.temperature),
+            max_output_tokens: overrides.max_output_tokens.or(self.max_output_tokens),
+            top_p: overrides.top_p.or(self.top_p),
+        }
+    }
+
+    /// Checks that every parameter set is within its allowed range.
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(temperature) = self.temperature {
+            if !(0.0..=1.0).contains(&temperature) {
+                return Err(format!("temperature must be between 0 and 1, got {temperature}"));
+            }
+        }
+        if self.max_output_tokens == Some(0) {
+            return Err("max output tokens must be greater than 0".to_string());
+        }
+        if let Some(top_p) = self.top_p {
+            if !(0.0..=1.0).contains(&top_p) {
+                return Err(format!("top-p must be between 0 and 1, got {top_p}"));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for InferenceConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let fmt_param = |param: Option<String>| param.unwrap_or_else(|| "model default".to_string());
+        write!(
+            f,
+            "temperature: {}, max output tokens: {}, top-p: {}",
+            fmt_param(self.temperature.map(|t| t.to_string())),
+            fmt_param(self.max_output_tokens.map(|t| t.to_string())),
+            fmt_param(self.top_p.map(|p| p.to_string())),
+        )
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -521,16 +590,29 @@ pub struct AssistantResponseMessage {
     pub content: String,
     /// ToolUse Request
     pub tool_uses: Option<Vec<ToolUse>>,
+    /// Whether the conversation up to and including this message is a stable prefix the backend
+    /// should cache.
+    pub cache_point: bool,
 }
 
 impl TryFrom<AssistantResponseMessage> for amzn_codewhisperer_streaming_client::types::AssistantResponseMessage {
     type Error = aws_smithy_types::error::operation::BuildError;
 
     fn try_from(value: AssistantResponseMessage) -> Result<Self, Self::Error> {
+        let cache_point = if value.cache_point {
+            Some(
+                amzn_codewhisperer_streaming_client::types::CachePoint::builder()
+                    .r#type(amzn_codewhisperer_streaming_client::types::CachePointType::Default)
+                    .build()?,
+            )
+        } else {
+            None
+        };
         Self::builder()
             .content(value.content)
             .set_message_id(value.message_id)
             .set_tool_uses(value.tool_uses.map(|uses| uses.into_iter().map(Into::into).collect()))
+            .set_cache_point(cache_point)
             .build()
     }
 }
@@ -539,10 +621,20 @@ impl TryFrom<AssistantResponseMessage> for amzn_qdeveloper_streaming_client::typ
     type Error = aws_smithy_types::error::operation::BuildError;
 
     fn try_from(value: AssistantResponseMessage) -> Result<Self, Self::Error> {
+        let cache_point = if value.cache_point {
+            Some(
+                amzn_qdeveloper_streaming_client::types::CachePoint::builder()
+                    .r#type(amzn_qdeveloper_streaming_client::types::CachePointType::Default)
+                    .build()?,
+            )
+        } else {
+            None
+        };
         Self::builder()
             .content(value.content)
             .set_message_id(value.message_id)
             .set_tool_uses(value.tool_uses.map(|uses| uses.into_iter().map(Into::into).collect()))
+            .set_cache_point(cache_point)
             .build()
     }
 }
@@ -569,6 +661,13 @@ pub enum ChatResponseStream {
         conversation_id: Option<String>,
         utterance_id: Option<String>,
     },
+    /// Usage reported once the response is complete.
+    MetadataEvent {
+        /// Number of input tokens of the request, cached or not.
+        input_tokens: Option<usize>,
+        /// Number of tokens generated for the response.
+        output_tokens: Option<usize>,
+    },
     SupplementaryWebLinksEvent(()),
     ToolUseEvent {
         tool_use_id: String,
@@ -596,6 +695,7 @@ impl ChatResponseStream {
             ChatResponseStream::IntentsEvent(_) => 0,
             ChatResponseStream::InvalidStateEvent { .. } => 0,
             ChatResponseStream::MessageMetadataEvent { .. } => 0,
+            ChatResponseStream::MetadataEvent { .. } => 0,
             ChatResponseStream::SupplementaryWebLinksEvent(_) => 0,
             ChatResponseStream::ToolUseEvent { input, .. } => input.as_ref().map(|s| s.len()).unwrap_or_default(),
             ChatResponseStream::Unknown => 0,
@@ -659,6 +759,17 @@ impl From<amzn_codewhisperer_streaming_client::types::ChatResponseStream> for Ch
             amzn_codewhisperer_streaming_client::types::ChatResponseStream::SupplementaryWebLinksEvent(_) => {
                 ChatResponseStream::SupplementaryWebLinksEvent(())
             },
+            amzn_codewhisperer_streaming_client::types::ChatResponseStream::MetadataEvent(
+                amzn_codewhisperer_streaming_client::types::MetadataEvent { token_usage, .. },
+            ) => ChatResponseStream::MetadataEvent {
+                input_tokens: token_usage.as_ref().map(|usage| {
+                    (usage.uncached_input_tokens
+                        + usage.cache_read_input_tokens.unwrap_or_default()
+                        + usage.cache_write_input_tokens.unwrap_or_default())
+                    .max(0) as usize
+                }),
+                output_tokens: token_usage.map(|usage| usage.output_tokens.max(0) as usize),
+            },
             _ => ChatResponseStream::Unknown,
         }
     }
@@ -715,6 +826,17 @@ impl From<amzn_qdeveloper_streaming_client::types::ChatResponseStream> for ChatR
             amzn_qdeveloper_streaming_client::types::ChatResponseStream::SupplementaryWebLinksEvent(_) => {
                 ChatResponseStream::SupplementaryWebLinksEvent(())
             },
+            amzn_qdeveloper_streaming_client::types::ChatResponseStream::MetadataEvent(
+                amzn_qdeveloper_streaming_client::types::MetadataEvent { token_usage, .. },
+            ) => ChatResponseStream::MetadataEvent {
+                input_tokens: token_usage.as_ref().map(|usage| {
+                    (usage.uncached_input_tokens
+                        + usage.cache_read_input_tokens.unwrap_or_default()
+                        + usage.cache_write_input_tokens.unwrap_or_default())
+                    .max(0) as usize
+                }),
+                output_tokens: token_usage.map(|usage| usage.output_tokens.max(0) as usize),
+            },
             _ => ChatResponseStream::Unknown,
         }
     }
@@ -1035,6 +1157,52 @@ mod tests {
         assert_eq!(format!("{codewhisper_minimal:?}"), format!("{qdeveloper_minimal:?}"));
     }
 
+    #[test]
+    fn test_inference_config() {
+        let config: InferenceConfig = serde_json::from_value(serde_json::json!({
+            "temperature": 0.2,
+            "maxOutputTokens": 8000
+        }))
+        .unwrap();
+        assert!(config.validate().is_ok());
+
+        let merged = config.merge(InferenceConfig {
+            temperature: Some(0.0),
+            top_p: Some(0.9),
+            ..Default::default()
+        });
+        assert_eq!(merged, InferenceConfig {
+            temperature: Some(0.0),
+            max_output_tokens: Some(8000),
+            top_p: Some(0.9),
+        });
+        assert_eq!(merged.merge(InferenceConfig::default()), merged);
+
+        let invalid = [
+            InferenceConfig {
+                temperature: Some(1.5),
+                ..Default::default()
+            },
+            InferenceConfig {
+                temperature: Some(f32::NAN),
+                ..Default::default()
+            },
+            InferenceConfig {
+                max_output_tokens: Some(0),
+                ..Default::default()
+            },
+            InferenceConfig {
+                top_p: Some(-0.1),
+                ..Default::default()
+            },
+        ];
+        for config in invalid {
+            assert!(config.validate().is_err(), "{config:?} should be invalid");
+        }
+
+        assert!(serde_json::from_value::<InferenceConfig>(serde_json::json!({ "topK": 5 })).is_err());
+    }
+
     #[test]
     fn build_assistant_response_message() {
         let message = AssistantResponseMessage {
@@ -1047,12 +1215,14 @@ mod tests {
                     [("key1".to_string(), AwsDocument::Null)].into_iter().collect(),
                 )),
             }]),
+            cache_point: true,
         };
         let codewhisper_input =
             amzn_codewhisperer_streaming_client::types::AssistantResponseMessage::try_from(message.clone()).unwrap();
         let qdeveloper_input =
             amzn_qdeveloper_streaming_client::types::AssistantResponseMessage::try_from(message).unwrap();
         assert_eq!(format!("{codewhisper_input:?}"), format!("{qdeveloper_input:?}"));
+        assert!(codewhisper_input.cache_point().is_some());
     }
 
     #[test]
@@ -1184,6 +1354,27 @@ mod tests {
             }
         );
 
+        let user_input_event = amzn_codewhisperer_streaming_client::types::ChatResponseStream::MetadataEvent(
+            amzn_codewhisperer_streaming_client::types::MetadataEvent::builder()
+                .token_usage(
+                    amzn_codewhisperer_streaming_client::types::TokenUsage::builder()
+                        .uncached_input_tokens(100)
+                        .output_tokens(20)
+                        .total_tokens(1120)
+                        .cache_read_input_tokens(1000)
+                        .build()
+                        .unwrap(),
+                )
+                .build(),
+        );
+        assert_eq!(
+            ChatResponseStream::from(user_input_event),
+            ChatResponseStream::MetadataEvent {
+                input_tokens: Some(1100),
+                output_tokens: Some(20),
+            }
+        );
+
         let user_input_event = amzn_codewhisperer_streaming_client::types::ChatResponseStream::MessageMetadataEvent(
             amzn_codewhisperer_streaming_client::types::MessageMetadataEvent::builder().build(),
         );
@@ -0,0 +1,200 @@
+use std::time::Duration;
+
+use aws_config::retry::RetryConfig;
+use aws_smithy_runtime_api::client::result::SdkError;
+use aws_smithy_types::error::metadata::ProvideErrorMetadata;
+use rand::Rng;
+
+use crate::api_client::MAX_RETRY_DELAY_DURATION;
+use crate::api_client::error::{
+    ApiClientError,
+    ConverseStreamErrorKind,
+};
+use crate::database::Database;
+use crate::database::settings::Setting;
+
+const DEFAULT_MAX_ATTEMPTS: u32 = 3;
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Error codes of the event stream exceptions worth retrying.
+const TRANSIENT_ERROR_CODES: &[&str] = &[
+    "ThrottlingException",
+    "InternalServerException",
+    "ServiceUnavailableException",
+];
+
+/// How requests to the backend are retried after transient failures, i.e. throttling, server
+/// errors and dropped connections.
+///
+/// The policy configures the retries of the SDK clients, and is also used to retry response
+/// streams failing before their first event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first one.
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            initial_backoff: INITIAL_BACKOFF,
+            max_backoff: MAX_RETRY_DELAY_DURATION,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Reads the policy from the `api.retry.maxAttempts` and `api.retry.maxBackoff` settings.
+    pub fn from_database(database: &Database) -> Self {
+        let default = Self::default();
+        let max_attempts = database
+            .settings
+            .get_int(Setting::ApiRetryMaxAttempts)
+            .and_then(|i| u32::try_from(i).ok())
+            .map_or(default.max_attempts, |i| i.max(1));
+        let max_backoff = database
+            .settings
+            .get_int(Setting::ApiRetryMaxBackoff)
+            .and_then(|i| i.try_into().ok())
+            .map_or(default.max_backoff, Duration::from_millis);
+
+        Self {
+            max_attempts,
+            initial_backoff: default.initial_backoff.min(max_backoff),
+            max_backoff,
+        }
+    }
+
+    pub fn sdk_config(&self) -> RetryConfig {
+        RetryConfig::adaptive()
+            .with_max_attempts(self.max_attempts)
+            .with_initial_backoff(self.initial_backoff)
+            .with_max_backoff(self.max_backoff)
+    }
+
+    /// Delay before the `retry`th retry, starting at 1: exponential backoff with full jitter.
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let exponential = self
+            .initial_backoff
+            .saturating_mul(2_u32.saturating_pow(retry.saturating_sub(1)))
+            .min(self.max_backoff);
+        exponential.mul_f64(rand::rng().random_range(0.0..=1.0))
+    }
+
+    /// The progress of the retries, shown to the user before waiting `delay` for the `retry`th
+    /// retry after `err`.
+    pub fn retry_notice(&self, retry: u32, delay: Duration, err: &ApiClientError) -> String {
+        format!(
+            "{err}. Retry #{retry} of {}, retrying in {:.1}s..",
+            self.max_attempts - 1,
+            delay.as_secs_f64()
+        )
+    }
+}
+
+/// Whether the error is likely to go away when retrying the request.
+pub fn is_transient(err: &ApiClientError) -> bool {
+    match err {
+        ApiClientError::ConverseStream(err) => match err.kind {
+            ConverseStreamErrorKind::Throttling => true,
+            // Handled by switching to the fallback models instead
+            ConverseStreamErrorKind::ModelOverloadedError
+            | ConverseStreamErrorKind::MonthlyLimitReached
+            | ConverseStreamErrorKind::ContextWindowOverflow
+            | ConverseStreamErrorKind::InferenceConfigUnsupported => false,
+            ConverseStreamErrorKind::Unknown { .. } => err.status_code.is_some_and(|status| status >= 500),
+        },
+        ApiClientError::CodewhispererChatResponseStream(err) => is_transient_sdk_error(err),
+        ApiClientError::QDeveloperChatResponseStream(err) => is_transient_sdk_error(err),
+        _ => false,
+    }
+}
+
+fn is_transient_sdk_error<E: ProvideErrorMetadata, R>(err: &SdkError<E, R>) -> bool {
+    match err {
+        SdkError::TimeoutError(_) | SdkError::DispatchFailure(_) | SdkError::ResponseError(_) => true,
+        SdkError::ServiceError(_) => err.code().is_some_and(|code| TRANSIENT_ERROR_CODES.contains(&code)),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_client::error::ConverseStreamError;
+
+    #[test]
+    fn test_backoff() {
+        let policy = RetryPolicy::default();
+        for retry in 1..10 {
+            let delay = policy.backoff(retry);
+            let cap = (INITIAL_BACKOFF * 2_u32.pow(retry - 1)).min(MAX_RETRY_DELAY_DURATION);
+            assert!(delay <= cap, "retry {retry}: {delay:?} > {cap:?}");
+        }
+        assert!(policy.backoff(u32::MAX) <= MAX_RETRY_DELAY_DURATION);
+    }
+
+    #[tokio::test]
+    async fn test_from_database() {
+        let mut database = Database::new().await.unwrap();
+        assert_eq!(RetryPolicy::from_database(&database), RetryPolicy::default());
+
+        database.settings.set(Setting::ApiRetryMaxAttempts, 5).await.unwrap();
+        database.settings.set(Setting::ApiRetryMaxBackoff, 500).await.unwrap();
+        assert_eq!(RetryPolicy::from_database(&database), RetryPolicy {
+            max_attempts: 5,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_millis(500),
+        });
+    }
+
+    #[test]
+    fn test_retry_notice() {
+        let policy = RetryPolicy::default();
+        let err = ApiClientError::ConverseStream(ConverseStreamError::new(
+            ConverseStreamErrorKind::Throttling,
+            None::<aws_smithy_types::error::operation::BuildError>,
+        ));
+        let notice = policy.retry_notice(1, Duration::from_millis(1500), &err);
+        assert!(notice.starts_with(&err.to_string()), "{notice}");
+        assert!(
+            notice.ends_with(&format!("Retry #1 of {}, retrying in 1.5s..", policy.max_attempts - 1)),
+            "{notice}"
+        );
+    }
+
+    #[test]
+    fn test_is_transient() {
+        let err = |kind, status_code| {
+            ApiClientError::ConverseStream(
+                ConverseStreamError::new(kind, None::<aws_smithy_types::error::operation::BuildError>)
+                    .set_status_code(status_code),
+            )
+        };
+        assert!(is_transient(&err(ConverseStreamErrorKind::Throttling, Some(429))));
+        assert!(is_transient(&err(
+            ConverseStreamErrorKind::Unknown {
+                reason_code: "InternalServerException".to_string()
+            },
+            Some(503)
+        )));
+        assert!(!is_transient(&err(
+            ConverseStreamErrorKind::Unknown {
+                reason_code: "ValidationException".to_string()
+            },
+            Some(400)
+        )));
+        assert!(!is_transient(&err(
+            ConverseStreamErrorKind::ModelOverloadedError,
+            Some(429)
+        )));
+        assert!(!is_transient(&err(
+            ConverseStreamErrorKind::MonthlyLimitReached,
+            Some(429)
+        )));
+        assert!(!is_transient(&ApiClientError::DefaultModelNotFound));
+    }
+}
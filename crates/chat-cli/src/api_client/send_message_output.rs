@@ -1,5 +1,6 @@
 use aws_types::request_id::RequestId;
 
+use crate::agent::chat::ChatModelOutput;
 use crate::api_client::ApiClientError;
 use crate::api_client::model::ChatResponseStream;
 
@@ -10,6 +11,7 @@ pub enum SendMessageOutput {
     ),
     QDeveloper(amzn_qdeveloper_streaming_client::operation::send_message::SendMessageOutput),
     Mock(Vec<ChatResponseStream>),
+    Model(ChatModelOutput),
 }
 
 impl SendMessageOutput {
@@ -18,6 +20,7 @@ impl SendMessageOutput {
             SendMessageOutput::Codewhisperer(output) => output.request_id(),
             SendMessageOutput::QDeveloper(output) => output.request_id(),
             SendMessageOutput::Mock(_) => None,
+            SendMessageOutput::Model(_) => None,
         }
     }
 
@@ -30,6 +33,7 @@ impl SendMessageOutput {
                 .map(|s| s.into())),
             SendMessageOutput::QDeveloper(output) => Ok(output.send_message_response.recv().await?.map(|s| s.into())),
             SendMessageOutput::Mock(vec) => Ok(vec.pop()),
+            SendMessageOutput::Model(output) => Ok(output.recv().await?),
         }
     }
 }
@@ -40,6 +44,7 @@ impl RequestId for SendMessageOutput {
             SendMessageOutput::Codewhisperer(output) => output.request_id(),
             SendMessageOutput::QDeveloper(output) => output.request_id(),
             SendMessageOutput::Mock(_) => Some("<mock-request-id>"),
+            SendMessageOutput::Model(_) => None,
         }
     }
 }
@@ -0,0 +1,105 @@
+use std::time::{
+    Duration,
+    Instant,
+};
+
+use crate::database::Database;
+use crate::database::settings::Setting;
+
+/// Same as the grace period of the SDK's stalled stream protection.
+const DEFAULT_STALL_TIMEOUT: Duration = Duration::from_secs(60 * 5);
+
+/// How long the response stream of a request may take before it's abandoned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StreamTimeouts {
+    /// Maximum time to receive the whole response, counted from sending the request. Unbounded
+    /// unless configured.
+    pub response: Option<Duration>,
+    /// Maximum time without receiving any event from the stream, including the first one.
+    pub stall: Duration,
+}
+
+impl Default for StreamTimeouts {
+    fn default() -> Self {
+        Self {
+            response: None,
+            stall: DEFAULT_STALL_TIMEOUT,
+        }
+    }
+}
+
+impl StreamTimeouts {
+    /// Reads the timeouts from the `api.responseTimeout` and `api.stallTimeout` settings, in
+    /// seconds.
+    pub fn from_database(database: &Database) -> Self {
+        let seconds = |setting| {
+            database
+                .settings
+                .get_int(setting)
+                .and_then(|i| u64::try_from(i).ok())
+                .filter(|i| *i > 0)
+                .map(Duration::from_secs)
+        };
+
+        Self {
+            response: seconds(Setting::ApiResponseTimeout),
+            stall: seconds(Setting::ApiStallTimeout).unwrap_or(DEFAULT_STALL_TIMEOUT),
+        }
+    }
+
+    /// How long to wait for the next event of a response requested at `request_start_time`.
+    /// [Duration::ZERO] if the response timeout already elapsed.
+    pub fn next_event_timeout(&self, request_start_time: Instant) -> Duration {
+        match self.response {
+            Some(response) => response.saturating_sub(request_start_time.elapsed()).min(self.stall),
+            None => self.stall,
+        }
+    }
+
+    /// Whether the response timeout elapsed for a response requested at `request_start_time`.
+    pub fn response_timed_out(&self, request_start_time: Instant) -> bool {
+        self.response
+            .is_some_and(|response| request_start_time.elapsed() >= response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_from_database() {
+        let mut database = Database::new().await.unwrap();
+        assert_eq!(StreamTimeouts::from_database(&database), StreamTimeouts::default());
+
+        database.settings.set(Setting::ApiResponseTimeout, 600).await.unwrap();
+        database.settings.set(Setting::ApiStallTimeout, 30).await.unwrap();
+        assert_eq!(StreamTimeouts::from_database(&database), StreamTimeouts {
+            response: Some(Duration::from_secs(600)),
+            stall: Duration::from_secs(30),
+        });
+    }
+
+    #[test]
+    fn test_next_event_timeout() {
+        let timeouts = StreamTimeouts {
+            response: Some(Duration::from_secs(100)),
+            stall: Duration::from_secs(30),
+        };
+        let now = Instant::now();
+        assert_eq!(timeouts.next_event_timeout(now), Duration::from_secs(30));
+        assert!(!timeouts.response_timed_out(now));
+
+        let started = now - Duration::from_secs(90);
+        assert!(timeouts.next_event_timeout(started) <= Duration::from_secs(10));
+
+        let started = now - Duration::from_secs(120);
+        assert_eq!(timeouts.next_event_timeout(started), Duration::ZERO);
+        assert!(timeouts.response_timed_out(started));
+
+        assert_eq!(
+            StreamTimeouts::default().next_event_timeout(started),
+            DEFAULT_STALL_TIMEOUT
+        );
+    }
+}
@@ -1,3 +1,4 @@
+use std::collections::BTreeMap;
 use std::fmt::Display;
 
 use schemars::JsonSchema;
@@ -23,6 +24,16 @@ pub enum HookTrigger {
     PostToolUse,
     /// Triggered when the assistant finishes responding
     Stop,
+    /// Triggered before the history is summarized. Output is added to the summarization
+    /// instructions
+    PreCompact,
+    /// Triggered when the chat session ends
+    SessionEnd,
+    /// Triggered when an error interrupts the current turn
+    OnError,
+    /// Triggered when a tool needs approval and when a long turn completes. Matchers match the
+    /// type of notification, `approval_needed` or `turn_complete`
+    Notification,
 }
 
 impl Display for HookTrigger {
@@ -33,6 +44,10 @@ impl Display for HookTrigger {
             HookTrigger::PreToolUse => write!(f, "preToolUse"),
             HookTrigger::PostToolUse => write!(f, "postToolUse"),
             HookTrigger::Stop => write!(f, "stop"),
+            HookTrigger::PreCompact => write!(f, "preCompact"),
+            HookTrigger::SessionEnd => write!(f, "sessionEnd"),
+            HookTrigger::OnError => write!(f, "onError"),
+            HookTrigger::Notification => write!(f, "notification"),
         }
     }
 }
@@ -71,6 +86,25 @@ pub struct Hook {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub matcher: Option<String>,
 
+    /// Whether to wait for the hook to finish. Non-blocking hooks run in the background, their
+    /// output is ignored and failures are only logged
+    #[serde(default = "Hook::default_blocking", skip_serializing_if = "Hook::is_blocking")]
+    pub blocking: bool,
+
+    /// Working directory of the hook, relative to the current directory. Defaults to the current
+    /// directory
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+
+    /// Environment variables added to the environment of the hook. Values may reference the
+    /// environment with `${env:VAR_NAME}` and the system keyring with `${keyring:NAME}`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+
+    /// Shell running the command with `-c`, e.g. `zsh`. Defaults to `bash`, or `cmd` on Windows
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shell: Option<String>,
+
     #[schemars(skip)]
     #[serde(default, skip_serializing)]
     pub source: Source,
@@ -84,6 +118,10 @@ impl Hook {
             max_output_size: Self::default_max_output_size(),
             cache_ttl_seconds: Self::default_cache_ttl_seconds(),
             matcher: None,
+            blocking: true,
+            cwd: None,
+            env: BTreeMap::new(),
+            shell: None,
             source,
         }
     }
@@ -99,4 +137,36 @@ impl Hook {
     fn default_cache_ttl_seconds() -> u64 {
         DEFAULT_CACHE_TTL_SECONDS
     }
+
+    fn default_blocking() -> bool {
+        true
+    }
+
+    fn is_blocking(blocking: &bool) -> bool {
+        *blocking
+    }
+}
+
+/// Policy applied to the blocking hooks matching a trigger, which all run concurrently
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Eq, PartialEq, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct HookPolicy {
+    /// How the results of preToolUse hooks decide whether the tool is blocked
+    #[serde(default)]
+    pub aggregation: HookAggregation,
+
+    /// Max time all the hooks of a trigger can run, in milliseconds. Hooks still running at the
+    /// deadline time out, as if their own timeout was reached
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deadline_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, Eq, PartialEq, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum HookAggregation {
+    /// The tool is blocked if any hook blocks it, with exit code 2 or a "block" decision
+    #[default]
+    AnyBlock,
+    /// The tool is blocked unless every hook succeeds
+    AllMustPass,
 }
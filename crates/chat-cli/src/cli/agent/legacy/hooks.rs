@@ -81,6 +81,10 @@ impl From<LegacyHook> for Option<Hook> {
             max_output_size: value.max_output_size,
             cache_ttl_seconds: value.cache_ttl_seconds,
             matcher: None,
+            blocking: true,
+            cwd: None,
+            env: Default::default(),
+            shell: None,
             source: Default::default(),
         })
     }
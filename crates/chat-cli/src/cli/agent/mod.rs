@@ -1,7 +1,11 @@
 pub mod hook;
 mod legacy;
 mod mcp_config;
+mod network_policy;
+mod permission_profile;
 mod root_command_args;
+mod subagent;
+mod validate;
 mod wrapper_types;
 
 use std::borrow::Borrow;
@@ -27,6 +31,8 @@ use crossterm::{
 };
 use eyre::bail;
 pub use mcp_config::McpServerConfig;
+pub use network_policy::NetworkPolicy;
+pub use permission_profile::PermissionProfile;
 pub use root_command_args::*;
 use schemars::{
     JsonSchema,
@@ -36,6 +42,7 @@ use serde::{
     Deserialize,
     Serialize,
 };
+pub use subagent::SubagentConfig;
 use thiserror::Error;
 use tokio::fs::ReadDir;
 use tracing::{
@@ -43,6 +50,10 @@ use tracing::{
     info,
     warn,
 };
+pub(crate) use validate::{
+    McpLaunch,
+    launch_mcp_server,
+};
 use wrapper_types::ResourcePath;
 pub use wrapper_types::{
     OriginalToolName,
@@ -53,11 +64,14 @@ pub use wrapper_types::{
 
 use super::chat::tools::{
     DEFAULT_APPROVE,
+    MUTATING_NATIVE_TOOLS,
     NATIVE_TOOLS,
     ToolOrigin,
 };
+use crate::api_client::model::InferenceConfig;
 use crate::cli::agent::hook::{
     Hook,
+    HookPolicy,
     HookTrigger,
 };
 use crate::database::settings::Setting;
@@ -126,7 +140,7 @@ pub enum AgentConfigError {
 ///
 /// Where agents are instantiated from their config, we would need to convert them from "cold" to
 /// "warm".
-#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq, JsonSchema)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, JsonSchema)]
 #[serde(rename_all = "camelCase", deny_unknown_fields)]
 #[schemars(description = "An Agent is a declarative way of configuring a given instance of q chat.")]
 pub struct Agent {
@@ -161,6 +175,9 @@ pub struct Agent {
     /// Commands to run when a chat session is created
     #[serde(default)]
     pub hooks: HashMap<HookTrigger, Vec<Hook>>,
+    /// How the results of the hooks matching a trigger are combined, and how long they may run
+    #[serde(default)]
+    pub hook_policy: HookPolicy,
     /// Settings for specific tools. These are mostly for native tools. The actual schema differs by
     /// tools and is documented in detail in our documentation
     #[serde(default)]
@@ -174,8 +191,40 @@ pub struct Agent {
     /// The model ID to use for this agent. If not specified, uses the default model.
     #[serde(default)]
     pub model: Option<String>,
+    /// Sampling parameters sent with each prompt. Unset parameters use the model's defaults
+    #[serde(default)]
+    pub model_parameters: InferenceConfig,
+    /// Models to switch to, in order, when the current model is overloaded or throttled
+    #[serde(default)]
+    pub model_fallbacks: Vec<String>,
+    /// Named sets of trust rules that can be switched between mid-session with
+    /// /permissions use <profile>. A profile's allowedTools and toolsSettings replace the ones
+    /// defined at the top level while it is in use
+    #[serde(default)]
+    pub permission_profiles: HashMap<String, PermissionProfile>,
+    /// Which hosts tools may connect to. Applies to tools from MCP servers using the http transport
+    /// and to execute_bash commands recognized as network clients
+    #[serde(default)]
+    pub network_policy: NetworkPolicy,
+    /// Instructions used when compacting the conversation, in place of the default ones. Use it to
+    /// tell what the summary must retain, e.g. open TODOs, file paths or decisions with their
+    /// rationale
+    #[serde(default)]
+    pub compaction_prompt: Option<String>,
+    /// Specialized agents, by name, that this agent can hand tasks to with the spawn_subagent tool.
+    /// Each has its own prompt, tools and model, its tools and allowed tools being narrowed down
+    /// to the ones of this agent
+    #[serde(default)]
+    pub subagents: HashMap<String, SubagentConfig>,
     #[serde(skip)]
     pub path: Option<PathBuf>,
+    /// Name of the permission profile currently in use, if any.
+    #[serde(skip)]
+    pub active_permission_profile: Option<String>,
+    /// The top level permissions of the agent, kept while a permission profile is in use so that
+    /// they can be restored (and written back to disk instead of the profile's).
+    #[serde(skip)]
+    pub base_permissions: Option<PermissionProfile>,
 }
 
 impl Default for Agent {
@@ -201,10 +250,19 @@ impl Default for Agent {
                 resources
             },
             hooks: Default::default(),
+            hook_policy: Default::default(),
             tools_settings: Default::default(),
             use_legacy_mcp_json: true,
             model: None,
+            model_parameters: Default::default(),
+            model_fallbacks: Vec::new(),
+            permission_profiles: Default::default(),
+            network_policy: Default::default(),
+            compaction_prompt: None,
+            subagents: Default::default(),
             path: None,
+            active_permission_profile: None,
+            base_permissions: None,
         }
     }
 }
@@ -214,6 +272,8 @@ impl Agent {
     /// Practically this means reverting some fields back to their original values as they were
     /// written in the config.
     fn freeze(&mut self) {
+        self.reset_permission_profile();
+
         let Self { mcp_servers, .. } = self;
 
         mcp_servers
@@ -268,6 +328,107 @@ impl Agent {
         Ok(())
     }
 
+    /// Replaces the trust rules of the agent with the ones from the permission profile named
+    /// `name`. The top level rules are kept so that they can be restored with
+    /// [Self::reset_permission_profile].
+    ///
+    /// Note that this discards any tool trust granted during the session.
+    pub fn use_permission_profile(&mut self, name: &str) -> eyre::Result<()> {
+        let Some(profile) = self.permission_profiles.get(name).cloned() else {
+            let mut available = self.permission_profiles.keys().cloned().collect::<Vec<_>>();
+            available.sort();
+            if available.is_empty() {
+                bail!("Agent {} does not define any permission profiles", self.name);
+            }
+            bail!(
+                "No permission profile with name {name} found. Available profiles: {}",
+                available.join(", ")
+            );
+        };
+
+        if self.base_permissions.is_none() {
+            self.base_permissions = Some(PermissionProfile {
+                description: None,
+                allowed_tools: std::mem::take(&mut self.allowed_tools),
+                tools_settings: std::mem::take(&mut self.tools_settings),
+            });
+        }
+
+        self.allowed_tools = profile.allowed_tools;
+        self.tools_settings = profile.tools_settings;
+        self.active_permission_profile = Some(name.to_string());
+
+        Ok(())
+    }
+
+    /// Restores the top level trust rules of the agent if a permission profile is in use.
+    ///
+    /// Returns whether or not a profile was in use.
+    pub fn reset_permission_profile(&mut self) -> bool {
+        self.active_permission_profile = None;
+        match self.base_permissions.take() {
+            Some(base) => {
+                self.allowed_tools = base.allowed_tools;
+                self.tools_settings = base.tools_settings;
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// The agent the sub-agent `name` runs as: its prompt, tools and model, with the tools and
+    /// allowed tools narrowed down to the ones of this agent. Sub-agents can't switch to a
+    /// permission profile or spawn sub-agents of their own.
+    pub fn subagent(&self, name: &str) -> eyre::Result<Agent> {
+        let Some(config) = self.subagents.get(name) else {
+            let mut available = self.subagents.keys().cloned().collect::<Vec<_>>();
+            available.sort();
+            if available.is_empty() {
+                bail!("Agent {} does not define any sub-agents", self.name);
+            }
+            bail!(
+                "No sub-agent with name {name} found. Available sub-agents: {}",
+                available.join(", ")
+            );
+        };
+
+        let mut agent = self.clone();
+        agent.reset_permission_profile();
+        agent.name = format!("{}/{name}", self.name);
+        agent.description = config.description.clone();
+        if config.prompt.is_some() {
+            agent.prompt = config.prompt.clone();
+            agent.prompt = agent.resolve_prompt()?;
+        }
+        if config.model.is_some() {
+            agent.model = config.model.clone();
+        }
+        if !config.tools.is_empty() {
+            agent.tools = config
+                .tools
+                .iter()
+                .filter(|tool| subagent::covers(&self.tools, tool))
+                .cloned()
+                .collect();
+        }
+        // Narrowed down from the top level rules, which the agent is loaded with
+        let trusted = agent.allowed_tools.iter().cloned().collect::<Vec<_>>();
+        agent.allowed_tools = config
+            .allowed_tools
+            .clone()
+            .unwrap_or_else(|| agent.allowed_tools.clone())
+            .iter()
+            .filter(|tool| subagent::covers(&trusted, tool) && subagent::covers(&agent.tools, tool))
+            .cloned()
+            .collect();
+        agent.permission_profiles.clear();
+        agent.subagents.clear();
+        // Trust granted during the session must not be written to the config of the agent
+        agent.path = None;
+
+        Ok(agent)
+    }
+
     pub fn print_overridden_permissions(&self, output: &mut impl Write) -> Result<(), AgentConfigError> {
         let execute_name = if cfg!(windows) { "execute_cmd" } else { "execute_bash" };
         for allowed_tool in &self.allowed_tools {
@@ -446,6 +607,9 @@ pub struct Agents {
     /// Agent name.
     pub active_idx: String,
     pub trust_all_tools: bool,
+    /// Whether tools that modify files or run commands with side effects are denied for the
+    /// session, regardless of their trust.
+    pub read_only: bool,
 }
 
 impl Agents {
@@ -485,6 +649,17 @@ impl Agents {
             .ok_or(eyre::eyre!("No agent with name {name} found"))
     }
 
+    /// Runs the session as the sub-agent `name` of the active agent, see [Agent::subagent].
+    pub fn use_subagent(&mut self, name: &str) -> eyre::Result<()> {
+        let agent = self
+            .get_active()
+            .ok_or(eyre::eyre!("No active agent to run the sub-agent {name} of"))?
+            .subagent(name)?;
+        self.active_idx = agent.name.clone();
+        self.agents.insert(agent.name.clone(), agent);
+        Ok(())
+    }
+
     /// This function does a number of things in the following order:
     /// 1. Migrates old profiles if applicable
     /// 2. Loads local agents
@@ -841,8 +1016,10 @@ impl Agents {
             is_tool_in_allowlist(&a.allowed_tools, tool_name, server_name)
         });
 
-        if tool_trusted || self.trust_all_tools {
-            format!("* {}", "trusted".dark_green().bold())
+        if self.read_only && matches!(origin, ToolOrigin::Native) && MUTATING_NATIVE_TOOLS.contains(&tool_name) {
+            format!("* {}", StyledText::error("denied (read-only mode)"))
+        } else if tool_trusted || self.trust_all_tools {
+            format!("* {}", StyledText::success("trusted").bold())
         } else {
             self.default_permission_label(tool_name)
         }
@@ -851,20 +1028,21 @@ impl Agents {
     /// Provide default permission labels for the built-in set of tools.
     // This "static" way avoids needing to construct a tool instance.
     fn default_permission_label(&self, tool_name: &str) -> String {
+        let trusted = |label: &str| StyledText::success(label).bold().to_string();
         let label = match tool_name {
-            "fs_read" => "trust working directory".dark_grey(),
-            "fs_write" => "not trusted".dark_grey(),
+            "fs_read" => StyledText::secondary("trust working directory"),
+            "fs_write" => StyledText::secondary("not trusted"),
             #[cfg(not(windows))]
-            "execute_bash" => "not trusted".dark_grey(),
+            "execute_bash" => StyledText::secondary("not trusted"),
             #[cfg(windows)]
-            "execute_cmd" => "not trusted".dark_grey(),
-            "use_aws" => "trust read-only commands".dark_grey(),
-            "report_issue" => "trusted".dark_green().bold(),
-            "introspect" => "trusted".dark_green().bold(),
-            "thinking" => "trusted (prerelease)".dark_green().bold(),
-            "todo_list" => "trusted".dark_green().bold(),
-            _ if self.trust_all_tools => "trusted".dark_grey().bold(),
-            _ => "not trusted".dark_grey(),
+            "execute_cmd" => StyledText::secondary("not trusted"),
+            "use_aws" => StyledText::secondary("trust read-only commands"),
+            "report_issue" => trusted("trusted"),
+            "introspect" => trusted("trusted"),
+            "thinking" => trusted("trusted (prerelease)"),
+            "todo_list" => trusted("trusted"),
+            _ if self.trust_all_tools => StyledText::secondary("trusted").bold().to_string(),
+            _ => StyledText::secondary("not trusted"),
         };
 
         format!("{} {label}", "*".reset())
@@ -1233,6 +1411,27 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_display_label_read_only() {
+        let agents = Agents {
+            trust_all_tools: true,
+            read_only: true,
+            ..Default::default()
+        };
+
+        let label = agents.display_label("fs_write", &ToolOrigin::Native);
+        assert!(
+            label.contains("read-only"),
+            "fs_write should be denied, instead found: {label}"
+        );
+
+        let label = agents.display_label("fs_read", &ToolOrigin::Native);
+        assert!(
+            !label.contains("read-only"),
+            "fs_read should not be denied, instead found: {label}"
+        );
+    }
+
     #[test]
     fn test_display_label_default_permissions() {
         let agents = Agents::default();
@@ -1290,9 +1489,18 @@ mod tests {
             tools_settings: Default::default(),
             resources: Vec::new(),
             hooks: Default::default(),
+            hook_policy: Default::default(),
             use_legacy_mcp_json: false,
             model: None,
+            model_parameters: Default::default(),
+            model_fallbacks: Vec::new(),
+            permission_profiles: Default::default(),
+            network_policy: Default::default(),
+            compaction_prompt: None,
+            subagents: Default::default(),
             path: None,
+            active_permission_profile: None,
+            base_permissions: None,
         };
 
         agents.agents.insert("test-agent".to_string(), agent);
@@ -1387,6 +1595,117 @@ mod tests {
         assert!(serialized.contains("\"model\":\"test-model\""));
     }
 
+    #[test]
+    fn test_permission_profiles() {
+        let agent_json = r#"{
+            "name": "test-agent",
+            "allowedTools": ["fs_read"],
+            "toolsSettings": {
+                "fs_write": { "allowedPaths": ["~/**"] }
+            },
+            "permissionProfiles": {
+                "explore": {
+                    "description": "Read around the codebase",
+                    "allowedTools": ["fs_read", "execute_bash"],
+                    "toolsSettings": {
+                        "execute_bash": { "allowedCommands": ["git status"] }
+                    }
+                },
+                "release": {
+                    "allowedTools": ["fs_write"]
+                }
+            }
+        }"#;
+
+        let mut agent: Agent = serde_json::from_str(agent_json).expect("Failed to deserialize agent");
+        assert_eq!(agent.permission_profiles.len(), 2);
+
+        agent.use_permission_profile("explore").unwrap();
+        assert_eq!(agent.active_permission_profile.as_deref(), Some("explore"));
+        assert!(agent.allowed_tools.contains("execute_bash"));
+        assert!(agent.tools_settings.contains_key("execute_bash"));
+        assert!(!agent.tools_settings.contains_key("fs_write"));
+
+        // Switching between profiles should keep the original top level rules around.
+        agent.use_permission_profile("release").unwrap();
+        assert_eq!(agent.allowed_tools, HashSet::from(["fs_write".to_string()]));
+        assert!(agent.tools_settings.is_empty());
+
+        // Writing the agent to disk should use the top level rules rather than the profile's.
+        let frozen: Agent = serde_json::from_str(&agent.to_str_pretty().unwrap()).unwrap();
+        assert_eq!(frozen.allowed_tools, HashSet::from(["fs_read".to_string()]));
+
+        assert!(agent.use_permission_profile("nonexistent").is_err());
+        assert_eq!(agent.active_permission_profile.as_deref(), Some("release"));
+
+        assert!(agent.reset_permission_profile());
+        assert_eq!(agent.active_permission_profile, None);
+        assert_eq!(agent.allowed_tools, HashSet::from(["fs_read".to_string()]));
+        assert!(agent.tools_settings.contains_key("fs_write"));
+        assert!(!agent.reset_permission_profile());
+    }
+
+    #[test]
+    fn test_subagent() {
+        let agent_json = r#"{
+            "name": "lead",
+            "prompt": "You lead the team",
+            "model": "claude-sonnet-4",
+            "tools": ["@builtin", "@git"],
+            "allowedTools": ["fs_read", "@git/git_status", "execute_bash"],
+            "permissionProfiles": {
+                "release": { "allowedTools": ["fs_write"] }
+            },
+            "subagents": {
+                "reviewer": {
+                    "description": "Reviews diffs",
+                    "prompt": "You review diffs",
+                    "tools": ["fs_read", "@git", "@github"],
+                    "allowedTools": ["fs_read", "@git/git_status", "@git/git_commit"],
+                    "model": "claude-haiku"
+                },
+                "helper": {}
+            }
+        }"#;
+        let mut agent: Agent = serde_json::from_str(agent_json).expect("Failed to deserialize agent");
+        agent.use_permission_profile("release").unwrap();
+
+        let reviewer = agent.subagent("reviewer").unwrap();
+        assert_eq!(reviewer.name, "lead/reviewer");
+        assert_eq!(reviewer.prompt.as_deref(), Some("You review diffs"));
+        assert_eq!(reviewer.model.as_deref(), Some("claude-haiku"));
+        // @github isn't one of the tools of the agent, and git_commit isn't trusted by it
+        assert_eq!(reviewer.tools, vec!["fs_read".to_string(), "@git".to_string()]);
+        assert_eq!(
+            reviewer.allowed_tools,
+            HashSet::from(["fs_read".to_string(), "@git/git_status".to_string()])
+        );
+        assert!(reviewer.permission_profiles.is_empty());
+        assert!(reviewer.subagents.is_empty());
+
+        // Defaults to the agent's, without the permission profile in use
+        let helper = agent.subagent("helper").unwrap();
+        assert_eq!(helper.prompt.as_deref(), Some("You lead the team"));
+        assert_eq!(helper.model.as_deref(), Some("claude-sonnet-4"));
+        assert_eq!(helper.tools, agent.tools);
+        assert_eq!(
+            helper.allowed_tools,
+            HashSet::from([
+                "fs_read".to_string(),
+                "@git/git_status".to_string(),
+                "execute_bash".to_string()
+            ])
+        );
+
+        assert!(agent.subagent("writer").is_err());
+
+        let mut agents = Agents::default();
+        agents.agents.insert("lead".to_string(), agent);
+        agents.switch("lead").unwrap();
+        agents.use_subagent("reviewer").unwrap();
+        assert_eq!(agents.get_active().unwrap().name, "lead/reviewer");
+    }
+
     #[test]
     fn test_agent_model_fallback_priority() {
         // Test that agent model is checked and falls back correctly
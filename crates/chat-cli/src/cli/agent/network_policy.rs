@@ -0,0 +1,101 @@
+use schemars::JsonSchema;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+/// Which hosts tools that reach out over the network may connect to.
+///
+/// This applies to tools from MCP servers using the HTTP transport and to `execute_bash` commands
+/// that are recognized as network clients (e.g. `curl`, `wget`, `ssh` or `git push`).
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Eq, PartialEq, JsonSchema)]
+#[serde(tag = "mode", rename_all = "camelCase", deny_unknown_fields)]
+pub enum NetworkPolicy {
+    /// Tools may connect to any host, subject to the usual permission checks
+    #[default]
+    Unrestricted,
+    /// Tools that reach out over the network are always denied
+    None,
+    /// Tools may only connect to the listed domains. A domain also matches its subdomains
+    Allowlist {
+        #[serde(default, rename = "allowedDomains")]
+        allowed_domains: Vec<String>,
+    },
+}
+
+impl NetworkPolicy {
+    pub fn is_unrestricted(&self) -> bool {
+        matches!(self, Self::Unrestricted)
+    }
+
+    pub fn is_host_allowed(&self, host: &str) -> bool {
+        match self {
+            Self::Unrestricted => true,
+            Self::None => false,
+            Self::Allowlist { allowed_domains } => {
+                let host = host.trim_end_matches('.').to_lowercase();
+                allowed_domains.iter().any(|domain| {
+                    let domain = domain.trim_start_matches("*.").trim_end_matches('.').to_lowercase();
+                    host == domain || host.ends_with(&format!(".{domain}"))
+                })
+            },
+        }
+    }
+
+    /// Returns the reasons for denying a tool use that connects to `hosts`, or [None] if the
+    /// policy permits it.
+    pub fn denied_reasons(&self, hosts: &[String]) -> Option<Vec<String>> {
+        match self {
+            Self::Unrestricted => None,
+            Self::None => Some(vec![
+                "network access is disabled by the agent's network policy".to_string(),
+            ]),
+            Self::Allowlist { .. } => {
+                let denied = hosts
+                    .iter()
+                    .filter(|host| !self.is_host_allowed(host))
+                    .map(|host| format!("{host} is not in the allowed domains of the agent's network policy"))
+                    .collect::<Vec<_>>();
+                (!denied.is_empty()).then_some(denied)
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_network_policy() {
+        let policy = serde_json::from_value::<NetworkPolicy>(serde_json::json!({
+            "mode": "allowlist",
+            "allowedDomains": ["github.com", "*.amazonaws.com"]
+        }))
+        .unwrap();
+
+        assert!(policy.is_host_allowed("github.com"));
+        assert!(policy.is_host_allowed("API.GitHub.com"));
+        assert!(policy.is_host_allowed("s3.us-east-1.amazonaws.com"));
+        assert!(!policy.is_host_allowed("notgithub.com"));
+        assert!(!policy.is_host_allowed("example.com"));
+
+        assert!(policy.denied_reasons(&["github.com".to_string()]).is_none());
+        assert_eq!(
+            policy
+                .denied_reasons(&["github.com".to_string(), "example.com".to_string()])
+                .unwrap()
+                .len(),
+            1
+        );
+
+        let policy = serde_json::from_value::<NetworkPolicy>(serde_json::json!({ "mode": "none" })).unwrap();
+        assert!(policy.denied_reasons(&[]).is_some());
+
+        assert!(
+            NetworkPolicy::default()
+                .denied_reasons(&["example.com".to_string()])
+                .is_none()
+        );
+    }
+}
@@ -0,0 +1,36 @@
+use std::collections::{
+    HashMap,
+    HashSet,
+};
+
+use schemars::JsonSchema;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use super::wrapper_types::{
+    ToolSettingTarget,
+    tool_settings_schema,
+};
+
+/// A named bundle of trust rules that can be swapped in mid-session with `/permissions use`,
+/// without having to change agents.
+///
+/// When a profile is in use, its `allowedTools` and `toolsSettings` replace the ones defined at the
+/// top level of the agent config.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Eq, PartialEq, JsonSchema)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct PermissionProfile {
+    /// Optional description of when this profile should be used
+    #[serde(default)]
+    pub description: Option<String>,
+    /// List of tools that are allowed to be used without prompting while this profile is active
+    #[serde(default)]
+    pub allowed_tools: HashSet<String>,
+    /// Settings for specific tools while this profile is active. Follows the same format as the
+    /// agent's toolsSettings
+    #[serde(default)]
+    #[schemars(schema_with = "tool_settings_schema")]
+    pub tools_settings: HashMap<ToolSettingTarget, serde_json::Value>,
+}
@@ -14,14 +14,16 @@ use eyre::{
     Result,
     bail,
 };
-use schemars::schema_for;
 
 use super::{
     Agent,
     Agents,
-    McpServerConfig,
     legacy,
+    validate,
 };
+use crate::agent::BackendArgs;
+use crate::cli::OutputFormat;
+use crate::cli::chat::tools::delegate::continue_task;
 use crate::database::settings::Setting;
 use crate::os::Os;
 use crate::theme::StyledText;
@@ -53,10 +55,22 @@ pub enum AgentSubcommands {
         #[arg(long, short)]
         name: String,
     },
-    /// Validate a config with the given path
+    /// Validate agent configs: their schema, that their MCP servers launch, that their resources
+    /// match files, and that their tool names and permission rules resolve. Exits with an error
+    /// if any config is invalid. All the workspace and global agents are validated without an
+    /// agent
     Validate {
-        #[arg(long, short)]
-        path: String,
+        /// Name of the agent, or path of its config
+        agent: Option<String>,
+        /// Path of the config, same as giving it as the agent
+        #[arg(long, short, conflicts_with = "agent", hide = true)]
+        path: Option<String>,
+        /// Don't launch the MCP servers of the agents
+        #[arg(long)]
+        skip_mcp: bool,
+        /// Format of the output
+        #[arg(long, short, value_enum, default_value_t)]
+        format: OutputFormat,
     },
     /// Migrate profiles to agent
     /// Note that doing this is potentially destructive to agents that are already in the global
@@ -70,6 +84,25 @@ pub enum AgentSubcommands {
         #[arg(long, short)]
         name: String,
     },
+    /// Serve the agent over the Agent Client Protocol (ACP) on stdin and stdout, for editors that
+    /// host ACP agents
+    Acp {
+        /// Name of the agent to run the sessions with
+        #[arg(long)]
+        agent: Option<String>,
+        #[command(flatten)]
+        backend: BackendArgs,
+        /// The model to use. Required with --backend bedrock and --backend openai, e.g.
+        /// us.anthropic.claude-sonnet-4-20250514-v1:0 or qwen3:8b
+        #[arg(long)]
+        model: Option<String>,
+    },
+    /// Continue a delegated task interrupted before it finished, e.g. by a restart, from its last
+    /// checkpoint. Must be invoked at the directory the task was delegated from
+    Resume {
+        /// Id of the task, e.g. rust-agent-1
+        id: String,
+    },
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Default, Args)]
@@ -166,71 +199,17 @@ impl AgentArgs {
                     path_with_file_name.display()
                 )?;
             },
-            Some(AgentSubcommands::Validate { path }) => {
-                let mut global_mcp_config = None::<McpServerConfig>;
-                let agent = Agent::load(os, path.as_str(), &mut global_mcp_config, mcp_enabled, &mut stderr).await;
-
-                'validate: {
-                    match agent {
-                        Ok(agent) => {
-                            let Ok(instance) = serde_json::to_value(&agent) else {
-                                queue!(
-                                    stderr,
-                                    StyledText::error_fg(),
-                                    style::Print("Error: "),
-                                    StyledText::reset(),
-                                    style::Print("failed to obtain value from agent provided. Aborting validation"),
-                                )?;
-                                break 'validate;
-                            };
-
-                            let schema = match serde_json::to_value(schema_for!(Agent)) {
-                                Ok(schema) => schema,
-                                Err(e) => {
-                                    queue!(
-                                        stderr,
-                                        StyledText::error_fg(),
-                                        style::Print("Error: "),
-                                        StyledText::reset(),
-                                        style::Print(format!("failed to obtain schema: {e}. Aborting validation"))
-                                    )?;
-                                    break 'validate;
-                                },
-                            };
-
-                            if let Err(e) = jsonschema::validate(&schema, &instance).map_err(|e| e.to_owned()) {
-                                let name = &agent.name;
-                                queue!(
-                                    stderr,
-                                    StyledText::warning_fg(),
-                                    style::Print("WARNING "),
-                                    StyledText::reset(),
-                                    style::Print("Agent config "),
-                                    StyledText::success_fg(),
-                                    style::Print(name),
-                                    StyledText::reset(),
-                                    style::Print(" is malformed at "),
-                                    StyledText::warning_fg(),
-                                    style::Print(&e.instance_path),
-                                    StyledText::reset(),
-                                    style::Print(format!(": {e}\n")),
-                                )?;
-                            }
-                        },
-                        Err(e) => {
-                            let _ = queue!(
-                                stderr,
-                                StyledText::error_fg(),
-                                style::Print("Error: "),
-                                StyledText::reset(),
-                                style::Print(e),
-                                style::Print("\n"),
-                            );
-                        },
-                    }
+            Some(AgentSubcommands::Validate {
+                agent,
+                path,
+                skip_mcp,
+                format,
+            }) => {
+                let reports = validate::validate_agents(os, agent.or(path).as_deref(), !skip_mcp, mcp_enabled).await?;
+                format.print(|| validate::format_reports(&reports), || &reports);
+                if reports.iter().any(|report| !report.valid) {
+                    return Ok(ExitCode::FAILURE);
                 }
-
-                stderr.flush()?;
             },
             Some(AgentSubcommands::Migrate { force }) => {
                 if !force {
@@ -314,6 +293,19 @@ impl AgentArgs {
                     },
                 }
             },
+            Some(AgentSubcommands::Acp { agent, backend, model }) => {
+                crate::agent::acp::serve_stdio(os, agent.as_deref(), backend, model).await?;
+            },
+            Some(AgentSubcommands::Resume { id }) => {
+                let execution = continue_task(os, &id).await?;
+                queue!(
+                    stderr,
+                    StyledText::success_fg(),
+                    style::Print(format!("✓ Resumed task '{}' in the background", execution.id)),
+                    StyledText::reset(),
+                    style::Print(". Follow it with /agents in q chat.\n"),
+                )?;
+            },
         }
 
         Ok(ExitCode::SUCCESS)
@@ -415,6 +407,32 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_agent_subcommand_validate() {
+        assert_parse!(
+            ["agent", "validate", "reviewer", "--skip-mcp", "--format", "json"],
+            RootSubcommand::Agent(AgentArgs {
+                cmd: Some(AgentSubcommands::Validate {
+                    agent: Some("reviewer".to_string()),
+                    path: None,
+                    skip_mcp: true,
+                    format: OutputFormat::Json,
+                })
+            })
+        );
+        assert_parse!(
+            ["agent", "validate", "--path", "agents/reviewer.json"],
+            RootSubcommand::Agent(AgentArgs {
+                cmd: Some(AgentSubcommands::Validate {
+                    agent: None,
+                    path: Some("agents/reviewer.json".to_string()),
+                    skip_mcp: false,
+                    format: OutputFormat::Plain,
+                })
+            })
+        );
+    }
+
     #[test]
     fn test_agent_subcommand_edit() {
         assert_parse!(
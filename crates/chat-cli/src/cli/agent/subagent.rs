@@ -0,0 +1,87 @@
+use std::collections::HashSet;
+
+use schemars::JsonSchema;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use crate::cli::chat::tools::delegate::limits::TaskLimits;
+
+/// A specialized agent the agent hands tasks to with the `spawn_subagent` tool. Sub-agents run in
+/// the background like delegated tasks.
+///
+/// Sub-agents never get more permissions than their agent: the tools they can see and are trusted
+/// with are narrowed down to the ones of the agent.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, JsonSchema)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct SubagentConfig {
+    /// What the sub-agent is for, shown to the model choosing which sub-agent to spawn
+    #[serde(default)]
+    pub description: Option<String>,
+    /// System prompt of the sub-agent, in place of the one of the agent. Supports file:// URIs
+    #[serde(default)]
+    pub prompt: Option<String>,
+    /// Tools the sub-agent can see, among the ones of the agent. Defaults to the tools of the agent
+    #[serde(default)]
+    pub tools: Vec<String>,
+    /// Tools the sub-agent is allowed to use without prompting, among the ones the agent is allowed
+    /// to use. Defaults to the allowed tools of the agent
+    #[serde(default)]
+    pub allowed_tools: Option<HashSet<String>>,
+    /// The model ID of the sub-agent. Defaults to the model of the agent
+    #[serde(default)]
+    pub model: Option<String>,
+    /// Caps on the turns, tool calls, tokens, estimated cost and duration of the tasks of the
+    /// sub-agent. A task reaching one pauses until the agent or the user tells it to continue or
+    /// stop
+    #[serde(default)]
+    pub limits: Option<TaskLimits>,
+}
+
+/// Whether `tool`, as written in `tools` or `allowedTools`, is one of `tools`. References to whole
+/// servers, e.g. `@git`, cover their tools, e.g. `@git/git_status`, and `@builtin` covers the
+/// native tools.
+pub fn covers(tools: &[String], tool: &str) -> bool {
+    let contains = |name: &str| tools.iter().any(|t| t == name);
+    if contains("*") || contains(tool) {
+        return true;
+    }
+    match tool.strip_prefix('@') {
+        Some("builtin") => false,
+        Some(reference) => match reference.split_once('/') {
+            Some(("builtin", name)) => contains("@builtin") || contains(name),
+            Some((server, _)) => contains(&format!("@{server}")),
+            None => false,
+        },
+        None => contains("@builtin") || contains(&format!("@builtin/{tool}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_covers() {
+        let tools = ["@builtin", "@git"].map(str::to_string);
+        assert!(covers(&tools, "fs_read"));
+        assert!(covers(&tools, "@builtin/fs_write"));
+        assert!(covers(&tools, "@builtin"));
+        assert!(covers(&tools, "@git/git_status"));
+        assert!(covers(&tools, "@git"));
+        assert!(!covers(&tools, "@github/create_issue"));
+        assert!(!covers(&tools, "@github"));
+
+        let tools = ["fs_read", "@builtin/execute_bash", "@git/git_status"].map(str::to_string);
+        assert!(covers(&tools, "fs_read"));
+        assert!(covers(&tools, "@builtin/fs_read"));
+        assert!(covers(&tools, "execute_bash"));
+        assert!(!covers(&tools, "fs_write"));
+        assert!(!covers(&tools, "@builtin"));
+        assert!(!covers(&tools, "@git"));
+        assert!(!covers(&tools, "@git/git_commit"));
+
+        assert!(covers(&["*".to_string()], "@github/create_issue"));
+    }
+}
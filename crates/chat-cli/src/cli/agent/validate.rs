@@ -0,0 +1,704 @@
+//! `q agent validate`: checks agent configs beyond their parsing, for catching mistakes before a
+//! chat session does, e.g. in CI:
+//! - the config matches the agent schema
+//! - its MCP servers launch, and list the tools referenced by the config
+//! - its resources match at least one file
+//! - the tool names of `tools`, `allowedTools`, `toolAliases` and `toolsSettings` resolve to
+//!   built-in tools or tools of its MCP servers
+//! - the tools trusted or configured, including by its permission profiles, are among its tools
+
+use std::collections::{
+    HashMap,
+    HashSet,
+};
+use std::fmt::Write as _;
+use std::path::{
+    Path,
+    PathBuf,
+};
+use std::time::Duration;
+
+use eyre::{
+    Result,
+    bail,
+};
+use glob::glob;
+use schemars::schema_for;
+use serde::Serialize;
+use tokio::task::JoinHandle;
+
+use super::{
+    Agent,
+    McpServerConfig,
+};
+use crate::cli::chat::consts::DUMMY_TOOL_NAME;
+use crate::cli::chat::server_messenger::{
+    ServerMessengerBuilder,
+    UpdateEventMessage,
+};
+use crate::cli::chat::tools::custom_tool::CustomToolConfig;
+use crate::mcp_client::{
+    InitializedMcpClient,
+    InnerService,
+    McpClientError,
+    McpClientService,
+    RunningService,
+};
+use crate::os::Os;
+use crate::theme::StyledText;
+use crate::util::MCP_SERVER_TOOL_DELIMITER;
+use crate::util::paths::PathResolver;
+use crate::util::pattern_matching::matches_any_pattern;
+use crate::util::tool_permission_checker::is_tool_in_allowlist;
+
+/// Maximum time for an MCP server to start and list its tools.
+const MCP_LAUNCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    /// The agent doesn't work as configured
+    Error,
+    /// Part of the config has no effect, or may not work everywhere
+    Warning,
+}
+
+/// What a finding is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Check {
+    Schema,
+    McpServer,
+    Resource,
+    ToolName,
+    Permission,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Finding {
+    pub severity: Severity,
+    pub check: Check,
+    pub message: String,
+}
+
+impl Finding {
+    fn error(check: Check, message: String) -> Self {
+        Self {
+            severity: Severity::Error,
+            check,
+            message,
+        }
+    }
+
+    fn warning(check: Check, message: String) -> Self {
+        Self {
+            severity: Severity::Warning,
+            check,
+            message,
+        }
+    }
+}
+
+/// The findings of the validation of an agent config.
+#[derive(Debug, Clone, Serialize)]
+pub struct AgentReport {
+    pub name: String,
+    pub path: PathBuf,
+    pub valid: bool,
+    pub findings: Vec<Finding>,
+}
+
+/// The result of launching an MCP server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum McpLaunch {
+    /// The server started, with the names of its tools
+    Launched(Vec<String>),
+    /// The server asked for the user to log in
+    NeedsLogin,
+    Failed(String),
+}
+
+/// The tools of the MCP servers of an agent, by server, or `None` for the servers that weren't
+/// launched.
+type ServerTools = HashMap<String, Option<Vec<String>>>;
+
+/// Validates the agent `target`, a name or the path of a config, or all the workspace and global
+/// agents without a target.
+pub async fn validate_agents(
+    os: &Os,
+    target: Option<&str>,
+    launch_mcp: bool,
+    mcp_enabled: bool,
+) -> Result<Vec<AgentReport>> {
+    let paths = match target {
+        Some(target) => vec![resolve_target(os, target)?],
+        None => {
+            let resolver = PathResolver::new(os);
+            let mut paths = Vec::new();
+            for dir in [resolver.workspace().agents_dir()?, resolver.global().agents_dir()?] {
+                let Ok(mut entries) = os.fs.read_dir(&dir).await else {
+                    continue;
+                };
+                while let Ok(Some(entry)) = entries.next_entry().await {
+                    let path = entry.path();
+                    if path.extension().is_some_and(|ext| ext == "json") {
+                        paths.push(path);
+                    }
+                }
+            }
+            paths.sort();
+            paths
+        },
+    };
+
+    let mut legacy_mcp_config = None::<McpServerConfig>;
+    let mut reports = Vec::new();
+    for path in paths {
+        reports.push(validate_agent(os, &path, &mut legacy_mcp_config, launch_mcp, mcp_enabled).await);
+    }
+    Ok(reports)
+}
+
+/// The path of the config of `target`, an agent name or a path.
+fn resolve_target(os: &Os, target: &str) -> Result<PathBuf> {
+    let path = os.env.current_dir()?.join(target);
+    if os.fs.exists(&path) {
+        return Ok(path);
+    }
+    if target.ends_with(".json") || target.contains(std::path::MAIN_SEPARATOR) {
+        bail!("{} does not exist", path.display());
+    }
+
+    let resolver = PathResolver::new(os);
+    for dir in [resolver.workspace().agents_dir()?, resolver.global().agents_dir()?] {
+        let path = dir.join(format!("{target}.json"));
+        if os.fs.exists(&path) {
+            return Ok(path);
+        }
+    }
+    bail!("Agent {target} does not exist")
+}
+
+async fn validate_agent(
+    os: &Os,
+    path: &Path,
+    legacy_mcp_config: &mut Option<McpServerConfig>,
+    launch_mcp: bool,
+    mcp_enabled: bool,
+) -> AgentReport {
+    let mut report = AgentReport {
+        name: path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        path: path.to_path_buf(),
+        valid: false,
+        findings: Vec::new(),
+    };
+
+    check_schema(os, path, &mut report.findings).await;
+    let mut stderr = std::io::stderr();
+    let agent = match Agent::load(os, path, legacy_mcp_config, mcp_enabled, &mut stderr).await {
+        Ok(agent) => agent,
+        Err(err) => {
+            if report.findings.is_empty() {
+                report.findings.push(Finding::error(Check::Schema, err.to_string()));
+            }
+            return report;
+        },
+    };
+    report.name = agent.name.clone();
+
+    // The servers are cleared when MCP is disabled
+    let mut servers = agent.mcp_servers.mcp_servers.iter().collect::<Vec<_>>();
+    servers.sort_by_key(|(name, _)| name.as_str());
+    let mut server_tools = ServerTools::new();
+    let mut launches = Vec::new();
+    for (name, config) in servers {
+        server_tools.insert(name.clone(), None);
+        if launch_mcp && !config.disabled {
+            launches.push(async move { (name, launch_mcp_server(os, name, config, MCP_LAUNCH_TIMEOUT).await) });
+        }
+    }
+    for (name, launch) in futures::future::join_all(launches).await {
+        match launch {
+            McpLaunch::Launched(tools) => {
+                server_tools.insert(name.clone(), Some(tools));
+            },
+            McpLaunch::NeedsLogin => report.findings.push(Finding::warning(
+                Check::McpServer,
+                format!("{name} needs you to log in, run q chat to do so"),
+            )),
+            McpLaunch::Failed(err) => report.findings.push(Finding::error(
+                Check::McpServer,
+                format!("{name} failed to launch: {err}"),
+            )),
+        }
+    }
+
+    check_resources(os, &agent, &mut report.findings);
+    check_tools(&agent, &server_tools, &mut report.findings);
+
+    report.valid = report.findings.iter().all(|f| f.severity != Severity::Error);
+    report
+}
+
+/// Validates the config at `path` as written against the agent schema.
+async fn check_schema(os: &Os, path: &Path, findings: &mut Vec<Finding>) {
+    let Ok(content) = os.fs.read(path).await else {
+        return;
+    };
+    let instance = match serde_json::from_slice::<serde_json::Value>(&content) {
+        Ok(instance) => instance,
+        Err(err) => {
+            findings.push(Finding::error(Check::Schema, format!("Invalid JSON: {err}")));
+            return;
+        },
+    };
+    let validator = match serde_json::to_value(schema_for!(Agent))
+        .map_err(|err| err.to_string())
+        .and_then(|schema| jsonschema::validator_for(&schema).map_err(|err| err.to_string()))
+    {
+        Ok(validator) => validator,
+        Err(err) => {
+            findings.push(Finding::error(
+                Check::Schema,
+                format!("Failed to obtain the schema: {err}"),
+            ));
+            return;
+        },
+    };
+    for err in validator.iter_errors(&instance) {
+        let location = match err.instance_path.to_string() {
+            location if location.is_empty() => "/".to_string(),
+            location => location,
+        };
+        findings.push(Finding::error(Check::Schema, format!("{location}: {err}")));
+    }
+}
+
+/// Launches the MCP server `name`, listing its tools, and stops it. Fails if that takes longer
+/// than `timeout`.
+pub(crate) async fn launch_mcp_server(os: &Os, name: &str, config: &CustomToolConfig, timeout: Duration) -> McpLaunch {
+    let (mut receiver, builder) = ServerMessengerBuilder::new(20);
+    let client = McpClientService::new(
+        name.to_string(),
+        config.clone(),
+        builder.build_with_name(name.to_string()),
+    );
+    let launch = async {
+        let service = match client.init(os).await {
+            Ok(InitializedMcpClient::Pending(handle)) => match wait_for_service(handle, &mut receiver).await {
+                Ok(service) => service,
+                Err(launch) => return launch,
+            },
+            Ok(InitializedMcpClient::Ready(service)) => service,
+            Err(err) => return McpLaunch::Failed(err.to_string()),
+        };
+
+        let has_tools = match &service.inner_service {
+            InnerService::Original(service) => service
+                .peer_info()
+                .is_some_and(|info| info.capabilities.tools.is_some()),
+            InnerService::Peer(peer) => peer.peer_info().is_some_and(|info| info.capabilities.tools.is_some()),
+        };
+        if !has_tools {
+            return McpLaunch::Launched(Vec::new());
+        }
+        while let Some(message) = receiver.recv().await {
+            if let UpdateEventMessage::ListToolsResult { result, .. } = message {
+                return match result {
+                    Ok(result) => {
+                        McpLaunch::Launched(result.tools.into_iter().map(|tool| tool.name.to_string()).collect())
+                    },
+                    Err(err) => McpLaunch::Failed(format!("failed to list its tools: {err}")),
+                };
+            }
+        }
+        McpLaunch::Failed("stopped before listing its tools".to_string())
+    };
+
+    match tokio::time::timeout(timeout, launch).await {
+        Ok(launch) => launch,
+        Err(_) => McpLaunch::Failed(format!("it didn't start within {} seconds", timeout.as_secs())),
+    }
+}
+
+/// Waits for the server initialized by `handle` to be running, or for it to ask for a login.
+async fn wait_for_service(
+    mut handle: JoinHandle<Result<RunningService, McpClientError>>,
+    receiver: &mut tokio::sync::mpsc::Receiver<UpdateEventMessage>,
+) -> Result<RunningService, McpLaunch> {
+    loop {
+        tokio::select! {
+            result = &mut handle => {
+                return match result {
+                    Ok(Ok(service)) => Ok(service),
+                    Ok(Err(err)) => Err(McpLaunch::Failed(err.to_string())),
+                    Err(err) => Err(McpLaunch::Failed(err.to_string())),
+                };
+            },
+            Some(message) = receiver.recv() => {
+                if let UpdateEventMessage::OauthLink { .. } = message {
+                    handle.abort();
+                    return Err(McpLaunch::NeedsLogin);
+                }
+            },
+        }
+    }
+}
+
+/// Warns about the resources of `agent` that match no file, resolving them as the context does.
+fn check_resources(os: &Os, agent: &Agent, findings: &mut Vec<Finding>) {
+    let Ok(cwd) = os.env.current_dir() else {
+        return;
+    };
+    for resource in &agent.resources {
+        let Some(path) = resource.strip_prefix("file://") else {
+            continue;
+        };
+        let path = match (path.strip_prefix("~/"), os.env.home()) {
+            (Some(rest), Some(home)) => home.join(rest),
+            _ => cwd.join(path),
+        };
+        let path = os.fs.chroot_path_str(&path);
+        let matched = if path.contains(['*', '?', '[']) {
+            match glob(&path) {
+                Ok(mut entries) => entries.any(|entry| entry.is_ok_and(|entry| entry.is_file())),
+                Err(err) => {
+                    findings.push(Finding::error(
+                        Check::Resource,
+                        format!("{} is an invalid glob pattern: {err}", resource.as_str()),
+                    ));
+                    continue;
+                },
+            }
+        } else {
+            Path::new(&path).exists()
+        };
+        if !matched {
+            findings.push(Finding::warning(
+                Check::Resource,
+                format!("{} matches no file", resource.as_str()),
+            ));
+        }
+    }
+}
+
+/// The names of the built-in tools.
+fn native_tool_names() -> Vec<String> {
+    let index =
+        serde_json::from_str::<HashMap<String, serde_json::Value>>(include_str!("../chat/tools/tool_index.json"))
+            .unwrap_or_default();
+    let mut names = index
+        .into_keys()
+        .filter(|name| name != DUMMY_TOOL_NAME)
+        .chain(["execute_cmd".to_string()])
+        .collect::<Vec<_>>();
+    names.sort();
+    names
+}
+
+fn matches(pattern: &str, text: &str) -> bool {
+    matches_any_pattern(&HashSet::from([pattern]), text)
+}
+
+/// Why the tool `reference` of a config resolves to no tool, if it doesn't. Patterns are only
+/// supported by the permission rules, `with_patterns`.
+fn unresolved(reference: &str, natives: &[String], server_tools: &ServerTools, with_patterns: bool) -> Option<String> {
+    let matches = |pattern: &str, text: &str| {
+        if with_patterns {
+            matches(pattern, text)
+        } else {
+            pattern == text
+        }
+    };
+    if reference == "*" {
+        return None;
+    }
+    let Some(rest) = reference.strip_prefix('@') else {
+        return (!natives.iter().any(|native| matches(reference, native)))
+            .then(|| format!("{reference} is not a built-in tool"));
+    };
+
+    let (server, tool) = match rest.split_once(MCP_SERVER_TOOL_DELIMITER) {
+        Some((server, tool)) => (server, Some(tool)),
+        None => (rest, None),
+    };
+    if server == "builtin" {
+        return tool
+            .filter(|tool| *tool != "*" && !natives.iter().any(|native| matches(tool, native)))
+            .map(|tool| format!("{reference}: {tool} is not a built-in tool"));
+    }
+    let servers = server_tools
+        .iter()
+        .filter(|(name, _)| matches(server, name))
+        .collect::<Vec<_>>();
+    if servers.is_empty() {
+        return Some(format!("{reference}: no MCP server named {server} is configured"));
+    }
+
+    // The tools are only known for the servers that were launched
+    let tool = tool?;
+    let known = servers
+        .iter()
+        .filter_map(|(_, tools)| tools.as_ref())
+        .collect::<Vec<_>>();
+    if known.len() == servers.len() && !known.iter().flat_map(|tools| tools.iter()).any(|t| matches(tool, t)) {
+        return Some(format!("{reference}: {server} has no tool named {tool}"));
+    }
+    None
+}
+
+/// Checks that the tool names of `agent` resolve, and that the tools its permission rules trust
+/// or configure are among its tools.
+fn check_tools(agent: &Agent, server_tools: &ServerTools, findings: &mut Vec<Finding>) {
+    let natives = native_tool_names();
+
+    for reference in &agent.tools {
+        if let Some(reason) = unresolved(reference, &natives, server_tools, false) {
+            findings.push(Finding::error(Check::ToolName, format!("tools: {reason}")));
+        }
+    }
+    for reference in agent.tool_aliases.keys() {
+        if let Some(reason) = unresolved(reference, &natives, server_tools, false) {
+            findings.push(Finding::error(Check::ToolName, format!("toolAliases: {reason}")));
+        }
+    }
+
+    let tools = agent.tools.iter().cloned().collect::<HashSet<_>>();
+    let available_native = |name: &str| {
+        tools.contains("*")
+            || tools.contains("@builtin")
+            || tools.contains(name)
+            || tools.contains(&format!("@builtin/{name}"))
+    };
+    let available_mcp =
+        |server: &str, tool: &str| tools.contains("*") || is_tool_in_allowlist(&tools, tool, Some(server));
+
+    let mut rules = Vec::new();
+    rules.extend(
+        agent
+            .allowed_tools
+            .iter()
+            .map(|rule| ("allowedTools".to_string(), rule.as_str())),
+    );
+    rules.extend(
+        agent
+            .tools_settings
+            .keys()
+            .map(|rule| ("toolsSettings".to_string(), rule.as_str())),
+    );
+    let mut profiles = agent.permission_profiles.iter().collect::<Vec<_>>();
+    profiles.sort_by_key(|(name, _)| name.as_str());
+    for (name, profile) in profiles {
+        let field = format!("permissionProfiles.{name}");
+        rules.extend(
+            profile
+                .allowed_tools
+                .iter()
+                .map(|rule| (format!("{field}.allowedTools"), rule.as_str())),
+        );
+        rules.extend(
+            profile
+                .tools_settings
+                .keys()
+                .map(|rule| (format!("{field}.toolsSettings"), rule.as_str())),
+        );
+    }
+    rules.sort();
+
+    for (field, rule) in rules {
+        if rule == "*" {
+            continue;
+        }
+        if let Some(reason) = unresolved(rule, &natives, server_tools, true) {
+            findings.push(Finding::error(Check::ToolName, format!("{field}: {reason}")));
+            continue;
+        }
+
+        let rule_set = HashSet::from([rule.to_string()]);
+        let satisfiable = natives
+            .iter()
+            .any(|native| is_tool_in_allowlist(&rule_set, native, None) && available_native(native))
+            || server_tools.iter().any(|(server, server_tools)| match server_tools {
+                Some(server_tools) => server_tools
+                    .iter()
+                    .any(|tool| is_tool_in_allowlist(&rule_set, tool, Some(server)) && available_mcp(server, tool)),
+                // The tools of the servers that weren't launched are unknown
+                None => rule.starts_with(&format!("@{server}")),
+            });
+        if !satisfiable {
+            findings.push(Finding::warning(
+                Check::Permission,
+                format!("{field}: {rule} applies to no tool among the tools of the agent"),
+            ));
+        }
+    }
+}
+
+/// The reports as text, the findings of each agent under its name.
+pub fn format_reports(reports: &[AgentReport]) -> String {
+    let mut text = String::new();
+    for report in reports {
+        let status = if report.valid {
+            StyledText::success("✓")
+        } else {
+            StyledText::error("✗")
+        };
+        let _ = writeln!(
+            text,
+            "{status} {} {}",
+            StyledText::emphasis(&report.name),
+            StyledText::secondary(&format!("({})", report.path.display()))
+        );
+        for finding in &report.findings {
+            let severity = match finding.severity {
+                Severity::Error => StyledText::error("error"),
+                Severity::Warning => StyledText::warning("warning"),
+            };
+            let check = serde_json::to_value(finding.check)
+                .ok()
+                .and_then(|check| check.as_str().map(str::to_string))
+                .unwrap_or_default();
+            let _ = writeln!(text, "  {severity} [{check}] {}", finding.message);
+        }
+    }
+    if reports.is_empty() {
+        text.push_str("No agent to validate\n");
+    }
+    text.trim_end().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn agent(json: serde_json::Value) -> Agent {
+        serde_json::from_value(json).unwrap()
+    }
+
+    fn messages(findings: &[Finding]) -> Vec<(Severity, &str)> {
+        findings.iter().map(|f| (f.severity, f.message.as_str())).collect()
+    }
+
+    #[test]
+    fn test_unresolved() {
+        let natives = native_tool_names();
+        let server_tools = ServerTools::from([
+            (
+                "git".to_string(),
+                Some(vec!["git_status".to_string(), "git_log".to_string()]),
+            ),
+            ("jira".to_string(), None),
+        ]);
+        let unresolved =
+            |reference: &str, with_patterns: bool| unresolved(reference, &natives, &server_tools, with_patterns);
+
+        for reference in [
+            "*",
+            "fs_read",
+            "@builtin",
+            "@builtin/fs_write",
+            "@git",
+            "@git/git_log",
+            "@jira/anything",
+        ] {
+            assert_eq!(unresolved(reference, false), None, "{reference}");
+        }
+        assert_eq!(unresolved("fs_*", true), None);
+        assert_eq!(unresolved("@git/git_*", true), None);
+
+        assert_eq!(
+            unresolved("fs_*", false),
+            Some("fs_* is not a built-in tool".to_string())
+        );
+        assert_eq!(
+            unresolved("@builtin/fs_red", false),
+            Some("@builtin/fs_red: fs_red is not a built-in tool".to_string())
+        );
+        assert_eq!(
+            unresolved("@github/issues", false),
+            Some("@github/issues: no MCP server named github is configured".to_string())
+        );
+        assert_eq!(
+            unresolved("@git/git_push", true),
+            Some("@git/git_push: git has no tool named git_push".to_string())
+        );
+    }
+
+    #[test]
+    fn test_check_tools() {
+        let agent = agent(serde_json::json!({
+            "name": "test",
+            "tools": ["fs_read", "fs_wirte", "@git/git_status"],
+            "allowedTools": ["fs_read", "execute_bash", "@git/git_log", "@git/git_*"],
+            "toolsSettings": { "fs_write": {} },
+            "permissionProfiles": {
+                "review": { "allowedTools": ["@git"] }
+            }
+        }));
+        let server_tools = ServerTools::from([(
+            "git".to_string(),
+            Some(vec!["git_status".to_string(), "git_log".to_string()]),
+        )]);
+        let mut findings = Vec::new();
+        check_tools(&agent, &server_tools, &mut findings);
+
+        assert_eq!(messages(&findings), vec![
+            (Severity::Error, "tools: fs_wirte is not a built-in tool"),
+            (
+                Severity::Warning,
+                "allowedTools: @git/git_log applies to no tool among the tools of the agent"
+            ),
+            (
+                Severity::Warning,
+                "allowedTools: execute_bash applies to no tool among the tools of the agent"
+            ),
+            (
+                Severity::Warning,
+                "toolsSettings: fs_write applies to no tool among the tools of the agent"
+            ),
+        ]);
+        assert!(findings.iter().all(|f| f.check != Check::Schema));
+    }
+
+    #[tokio::test]
+    async fn test_validate_agent() {
+        let os = Os::new().await.unwrap();
+        os.fs.create_dir_all("/project").await.unwrap();
+        os.fs.write("/project/notes.md", "notes").await.unwrap();
+        let config = serde_json::json!({
+            "name": "test",
+            "tools": ["fs_read"],
+            "resources": ["file:///project/*.md", "file:///project/*.rs"],
+            "useLegacyMcpJson": false,
+        });
+        os.fs.write("/project/test.json", config.to_string()).await.unwrap();
+
+        let reports = validate_agents(&os, Some("/project/test.json"), false, true)
+            .await
+            .unwrap();
+        assert_eq!(reports.len(), 1);
+        assert!(reports[0].valid);
+        assert_eq!(reports[0].findings, vec![Finding::warning(
+            Check::Resource,
+            "file:///project/*.rs matches no file".to_string()
+        )]);
+
+        os.fs
+            .write("/project/test.json", r#"{"name": "test", "tools": "fs_read"}"#)
+            .await
+            .unwrap();
+        let reports = validate_agents(&os, Some("/project/test.json"), false, true)
+            .await
+            .unwrap();
+        assert!(!reports[0].valid);
+        assert!(
+            reports[0]
+                .findings
+                .iter()
+                .all(|f| f.severity == Severity::Error && f.check == Check::Schema)
+        );
+    }
+}
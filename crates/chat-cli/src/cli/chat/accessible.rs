@@ -0,0 +1,163 @@
+//! Screen reader friendly output, enabled with `q chat --accessible`: spinners are replaced with
+//! plain status lines, output isn't animated, and box-drawing characters and emoji are replaced
+//! with text so that the narration stays coherent. The output of each tool is also surrounded
+//! with `BEGIN tool output` and `END tool output` lines.
+
+use std::io::Write;
+use std::sync::atomic::{
+    AtomicBool,
+    Ordering,
+};
+use std::sync::{
+    Arc,
+    Mutex,
+    mpsc,
+};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crossterm::{
+    queue,
+    style,
+    terminal,
+};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Enables the accessible mode for the rest of the process.
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+    #[cfg(test)]
+    if TEST_ENABLED.get() {
+        return true;
+    }
+    ENABLED.load(Ordering::Relaxed)
+}
+
+#[cfg(test)]
+thread_local! {
+    static TEST_ENABLED: std::cell::Cell<bool> = const { std::cell::Cell::new(false) };
+}
+
+/// Enables the accessible mode on the current test thread only, so that the other tests running
+/// in parallel keep the default output.
+#[cfg(test)]
+pub fn enable_for_test() {
+    TEST_ENABLED.set(true);
+}
+
+/// `symbol`, or its `plain` replacement in accessible mode.
+pub fn symbol(symbol: &'static str, plain: &'static str) -> &'static str {
+    if is_enabled() { plain } else { symbol }
+}
+
+/// The line before or after the output of `tool`, printed in accessible mode only.
+pub fn tool_output_marker(begin: bool, tool: &str) -> String {
+    format!("{} tool output: {tool}\n", if begin { "BEGIN" } else { "END" })
+}
+
+/// A spinner showing a status, or the status printed on its own line in accessible mode.
+pub enum StatusIndicator {
+    Spinner(Spinner),
+    Line,
+}
+
+impl StatusIndicator {
+    pub fn new(text: String) -> Self {
+        if is_enabled() {
+            let mut stderr = std::io::stderr();
+            let _ = writeln!(stderr, "{text}");
+            Self::Line
+        } else {
+            Self::Spinner(Spinner::new(text))
+        }
+    }
+
+    /// Replaces the status of the spinner, on its next frame. A status line of the accessible
+    /// mode is left as is.
+    pub fn set_text(&mut self, text: String) {
+        if let Self::Spinner(spinner) = self {
+            if let Ok(mut current) = spinner.text.lock() {
+                *current = text;
+            }
+        }
+    }
+
+    pub fn stop(&mut self) {
+        if let Self::Spinner(spinner) = self {
+            spinner.stop();
+        }
+    }
+}
+
+/// Frames of the spinner, the same as the `Dots` spinner of the `spinners` crate.
+const SPINNER_FRAMES: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+const SPINNER_INTERVAL: Duration = Duration::from_millis(80);
+
+/// A spinner on STDERR whose text can be changed while it spins, see [StatusIndicator::set_text].
+pub struct Spinner {
+    text: Arc<Mutex<String>>,
+    stop: mpsc::Sender<()>,
+    join: Option<JoinHandle<()>>,
+}
+
+impl Spinner {
+    fn new(text: String) -> Self {
+        let text = Arc::new(Mutex::new(text));
+        let (stop, stopped) = mpsc::channel();
+        let join = std::thread::spawn({
+            let text = Arc::clone(&text);
+            move || {
+                for frame in SPINNER_FRAMES.iter().cycle() {
+                    let line = text.lock().map(|text| text.clone()).unwrap_or_default();
+                    let mut stderr = std::io::stderr();
+                    let _ = queue!(
+                        stderr,
+                        style::Print(format!("\r{frame} {line}")),
+                        terminal::Clear(terminal::ClearType::UntilNewLine)
+                    );
+                    let _ = stderr.flush();
+                    if !matches!(
+                        stopped.recv_timeout(SPINNER_INTERVAL),
+                        Err(mpsc::RecvTimeoutError::Timeout)
+                    ) {
+                        break;
+                    }
+                }
+            }
+        });
+        Self {
+            text,
+            stop,
+            join: Some(join),
+        }
+    }
+
+    /// Stops the spinner, leaving its last frame on the line.
+    fn stop(&mut self) {
+        if let Some(join) = self.join.take() {
+            let _ = self.stop.send(());
+            let _ = join.join();
+        }
+    }
+}
+
+impl Drop for Spinner {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tool_output_marker() {
+        assert_eq!(tool_output_marker(true, "fs_read"), "BEGIN tool output: fs_read\n");
+        assert_eq!(tool_output_marker(false, "fs_read"), "END tool output: fs_read\n");
+    }
+}
@@ -0,0 +1,238 @@
+//! Allow and deny rules set with `--approval-policy`, evaluated for the tool uses that would
+//! otherwise need to be approved, so that headless runs can be granted narrowly scoped permissions
+//! without `--trust-all-tools`.
+//!
+//! Rules are evaluated in order and the first one matching the tool use decides. Tool uses no rule
+//! matches are approved as usual: by the user in interactive sessions, or by failing with
+//! [super::ChatExitCode::ToolApprovalRequired] with `--no-interactive`.
+
+use std::collections::BTreeMap;
+use std::path::{
+    Path,
+    PathBuf,
+};
+
+use globset::{
+    Glob,
+    GlobMatcher,
+};
+use serde::Deserialize;
+use serde_json::Value;
+use thiserror::Error;
+
+use crate::os::Os;
+
+#[derive(Debug, Error)]
+pub enum ApprovalPolicyError {
+    #[error("Failed to read the approval policy at {}: {error}", path.display())]
+    Io { path: PathBuf, error: std::io::Error },
+    #[error("The approval policy at {} is not valid: {error}", path.display())]
+    Invalid { path: PathBuf, error: String },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PolicyAction {
+    Allow,
+    Deny,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct PolicyFile {
+    rules: Vec<RuleEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct RuleEntry {
+    /// Glob of the tool name, e.g. `execute_bash` or `@git/*`
+    tool: String,
+    /// Globs the fields of the tool input must match, e.g. `command: "cargo test*"`
+    #[serde(default)]
+    input: BTreeMap<String, String>,
+    action: PolicyAction,
+}
+
+#[derive(Debug)]
+struct Rule {
+    tool: GlobMatcher,
+    input: Vec<(String, GlobMatcher)>,
+    action: PolicyAction,
+}
+
+/// Outcome of a rule matching a tool use
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PolicyDecision {
+    pub action: PolicyAction,
+    /// Describes the rule, e.g. `rule 2 of the approval policy (deny execute_bash)`
+    pub rule: String,
+}
+
+#[derive(Debug)]
+pub struct ApprovalPolicy {
+    rules: Vec<Rule>,
+}
+
+impl ApprovalPolicy {
+    pub async fn load(os: &Os, path: impl AsRef<Path>) -> Result<Self, ApprovalPolicyError> {
+        let path = path.as_ref().to_path_buf();
+        let content = match os.fs.read_to_string(&path).await {
+            Ok(content) => content,
+            Err(error) => return Err(ApprovalPolicyError::Io { path, error }),
+        };
+        Self::from_str(path, &content)
+    }
+
+    fn from_str(path: PathBuf, content: &str) -> Result<Self, ApprovalPolicyError> {
+        let invalid = |error: String| ApprovalPolicyError::Invalid {
+            path: path.clone(),
+            error,
+        };
+        let file: PolicyFile = if path.extension().is_some_and(|ext| ext == "json") {
+            serde_json::from_str(content).map_err(|err| err.to_string())
+        } else {
+            serde_yaml::from_str(content).map_err(|err| err.to_string())
+        }
+        .map_err(invalid)?;
+
+        let glob = |glob: &str| {
+            Glob::new(glob)
+                .map(|glob| glob.compile_matcher())
+                .map_err(|err| invalid(format!("invalid glob '{glob}': {err}")))
+        };
+        let mut rules = Vec::new();
+        for entry in file.rules {
+            rules.push(Rule {
+                tool: glob(&entry.tool)?,
+                input: entry
+                    .input
+                    .iter()
+                    .map(|(field, pattern)| Ok((field.clone(), glob(pattern)?)))
+                    .collect::<Result<_, ApprovalPolicyError>>()?,
+                action: entry.action,
+            });
+        }
+        Ok(Self { rules })
+    }
+
+    /// Finds the first rule matching the use of `tool_name`, MCP tools being named
+    /// `@server/tool`.
+    pub fn evaluate(&self, tool_name: &str, tool_input: &Value) -> Option<PolicyDecision> {
+        self.rules
+            .iter()
+            .enumerate()
+            .find(|(_, rule)| rule.matches(tool_name, tool_input))
+            .map(|(i, rule)| PolicyDecision {
+                action: rule.action,
+                rule: format!(
+                    "rule {} of the approval policy ({} {})",
+                    i + 1,
+                    match rule.action {
+                        PolicyAction::Allow => "allow",
+                        PolicyAction::Deny => "deny",
+                    },
+                    rule.tool.glob()
+                ),
+            })
+    }
+}
+
+impl Rule {
+    fn matches(&self, tool_name: &str, tool_input: &Value) -> bool {
+        self.tool.is_match(tool_name)
+            && self.input.iter().all(|(field, pattern)| match tool_input.get(field) {
+                Some(Value::String(value)) => pattern.is_match(value),
+                // Other values are matched as JSON, e.g. `true` or `["a","b"]`
+                Some(value) => pattern.is_match(value.to_string()),
+                None => false,
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    const POLICY: &str = r#"
+rules:
+  - tool: execute_bash
+    input:
+      command: "rm *"
+    action: deny
+  - tool: execute_bash
+    input:
+      command: "cargo test*"
+    action: allow
+  - tool: fs_write
+    input:
+      path: "src/**"
+    action: allow
+  - tool: "@git/*"
+    action: allow
+  - tool: "*"
+    action: deny
+"#;
+
+    #[test]
+    fn test_evaluate() {
+        let policy = ApprovalPolicy::from_str(PathBuf::from("policy.yaml"), POLICY).unwrap();
+        let action = |tool: &str, input: Value| policy.evaluate(tool, &input).map(|decision| decision.action);
+
+        assert_eq!(
+            action("execute_bash", json!({ "command": "cargo test -p chat_cli" })),
+            Some(PolicyAction::Allow)
+        );
+        assert_eq!(
+            action("execute_bash", json!({ "command": "rm -rf target" })),
+            Some(PolicyAction::Deny)
+        );
+        assert_eq!(
+            action("fs_write", json!({ "command": "create", "path": "src/cli/mod.rs" })),
+            Some(PolicyAction::Allow)
+        );
+        assert_eq!(
+            action("fs_write", json!({ "command": "create", "path": "Cargo.toml" })),
+            Some(PolicyAction::Deny)
+        );
+        assert_eq!(action("@git/git_status", json!({})), Some(PolicyAction::Allow));
+        assert_eq!(
+            policy.evaluate("use_aws", &json!({})),
+            Some(PolicyDecision {
+                action: PolicyAction::Deny,
+                rule: "rule 5 of the approval policy (deny *)".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_evaluate_no_match() {
+        let policy = ApprovalPolicy::from_str(
+            PathBuf::from("policy.json"),
+            r#"{"rules": [{"tool": "fs_write", "input": {"path": "docs/*"}, "action": "allow"}]}"#,
+        )
+        .unwrap();
+        assert_eq!(policy.evaluate("fs_write", &json!({ "path": "src/lib.rs" })), None);
+        assert_eq!(policy.evaluate("fs_write", &json!({})), None);
+        assert_eq!(policy.evaluate("execute_bash", &json!({ "command": "ls" })), None);
+    }
+
+    #[test]
+    fn test_invalid() {
+        for content in [
+            "rules:\n  - tool: fs_write\n    action: ask\n",
+            "rules:\n  - tool: fs_write\n    action: allow\n    paths: [src]\n",
+            "rules:\n  - tool: \"fs_[write\"\n    action: allow\n",
+        ] {
+            assert!(
+                matches!(
+                    ApprovalPolicy::from_str(PathBuf::from("policy.yaml"), content),
+                    Err(ApprovalPolicyError::Invalid { .. })
+                ),
+                "{content}"
+            );
+        }
+    }
+}
@@ -0,0 +1,174 @@
+//! Summarization of the older history in the background, started between turns once the
+//! conversation uses most of the context window, so that the context window rarely overflows.
+//!
+//! The summary covers the history as it was when the compaction started. Entries added since are
+//! kept as they are once the summary replaces the older history.
+
+use std::sync::Arc;
+use std::time::{
+    Duration,
+    Instant,
+};
+
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tracing::{
+    debug,
+    warn,
+};
+
+use super::ChatError;
+use super::cli::compact::{
+    CompactKind,
+    CompactStrategy,
+};
+use super::conversation::ConversationState;
+use super::parser::{
+    RequestMetadata,
+    ResponseEvent,
+    SendMessageStream,
+};
+use super::token_counter::TokenCounter;
+use crate::cli::chat::cli::model::context_window_tokens;
+use crate::database::settings::Setting;
+use crate::os::Os;
+use crate::telemetry::core::MessageMetaTag;
+
+/// Percentage of the context window used at which compaction starts, unless configured with
+/// `chat.backgroundCompactionThreshold`.
+pub const DEFAULT_BACKGROUND_COMPACTION_THRESHOLD: usize = 75;
+
+#[derive(Debug)]
+pub struct BackgroundCompaction {
+    /// Resolves to the summary, and the time it took to create it.
+    task: JoinHandle<Result<(String, RequestMetadata, Duration), ChatError>>,
+    /// Number of history entries summarized, counted from the oldest.
+    summarized: usize,
+    /// Fingerprint of the summarized entries, to tell whether the history was replaced since.
+    fingerprint: Option<u64>,
+}
+
+impl BackgroundCompaction {
+    /// Starts summarizing the older history if the conversation uses more of the context window
+    /// than the configured threshold. Returns [None] if it doesn't, or if there isn't enough
+    /// history to keep the most recent turns out of the summary.
+    pub async fn start_if_needed(os: &Os, conversation: &mut ConversationState) -> Option<Self> {
+        let settings = &os.database.settings;
+        let threshold = settings.get_int_or(
+            Setting::ChatBackgroundCompactionThreshold,
+            DEFAULT_BACKGROUND_COMPACTION_THRESHOLD,
+        );
+        if threshold == 0
+            || threshold >= 100
+            || settings.get_bool(Setting::ChatDisableAutoCompaction).unwrap_or(false)
+            || conversation.is_in_tangent_mode()
+        {
+            return None;
+        }
+
+        let used = *conversation.calculate_char_count(os).await.ok()?;
+        let max = TokenCounter::model_tokens_to_chars(
+            conversation.model_id(),
+            context_window_tokens(conversation.model_info.as_ref()),
+        );
+        if used * 100 < max * threshold {
+            return None;
+        }
+
+        let strategy = conversation.resolve_compact_strategy(CompactStrategy {
+            kind: CompactKind::Sliding,
+            ..Default::default()
+        });
+        let history_len = conversation.history().len();
+        if strategy.messages_to_exclude == 0 || strategy.messages_to_exclude >= history_len {
+            return None;
+        }
+        let summarized = history_len - strategy.messages_to_exclude;
+
+        let retention_instructions = conversation.run_pre_compact_hooks(os, &mut std::io::sink()).await;
+        let request = match conversation
+            .create_summary_request(os, retention_instructions, strategy)
+            .await
+        {
+            Ok(request) => request,
+            Err(e) => {
+                warn!(?e, "Failed to create the background compaction request");
+                return None;
+            },
+        };
+        debug!(summarized, history_len, "starting background compaction");
+
+        let client = os.client.clone();
+        let task = tokio::spawn(async move {
+            let started = Instant::now();
+            let mut stream = SendMessageStream::send_message(
+                &client,
+                request,
+                Arc::new(Mutex::new(None)),
+                Some(vec![MessageMetaTag::Compact]),
+            )
+            .await?;
+            loop {
+                match stream.recv().await {
+                    Some(Ok(ResponseEvent::EndStream {
+                        message,
+                        request_metadata,
+                    })) => break Ok((message.content().to_string(), request_metadata, started.elapsed())),
+                    Some(Ok(_)) => (),
+                    Some(Err(err)) => break Err(err.into()),
+                    None => break Err(ChatError::Custom("The summary response ended unexpectedly".into())),
+                }
+            }
+        });
+
+        Some(Self {
+            task,
+            summarized,
+            fingerprint: conversation.history_fingerprint(summarized),
+        })
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.task.is_finished()
+    }
+
+    /// Replaces the summarized history with the summary once it is ready. Returns the number of
+    /// history entries replaced along with the time the summary took, or [None] if the compaction
+    /// failed or the history changed in a way that makes the summary stale, e.g. with `/clear`.
+    pub async fn apply(mut self, conversation: &mut ConversationState) -> Option<(usize, Duration)> {
+        let (summary, request_metadata, duration) = match (&mut self.task).await {
+            Ok(Ok(res)) => res,
+            Ok(Err(e)) => {
+                warn!(?e, "Background compaction failed");
+                return None;
+            },
+            Err(e) => {
+                warn!(?e, "Background compaction task failed");
+                return None;
+            },
+        };
+
+        if conversation.history_fingerprint(self.summarized) != self.fingerprint {
+            debug!("discarding background compaction of a history that changed");
+            return None;
+        }
+
+        let kept = conversation.history().len() - self.summarized;
+        conversation.replace_history_with_summary(
+            summary,
+            CompactStrategy {
+                messages_to_exclude: kept,
+                ..Default::default()
+            },
+            request_metadata,
+        );
+        Some((self.summarized, duration))
+    }
+}
+
+impl Drop for BackgroundCompaction {
+    fn drop(&mut self) {
+        // Stops the request of a compaction that is no longer needed
+        self.task.abort();
+    }
+}
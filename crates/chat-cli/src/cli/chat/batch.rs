@@ -0,0 +1,484 @@
+//! `q chat batch`, which runs the prompts listed in a YAML or JSON file one after the other with
+//! `q chat --no-interactive`, for evaluation suites and bulk refactors.
+//!
+//! Each prompt runs in a process of its own, in a fresh conversation or, with `conversation:
+//! shared`, in the conversation of the previous prompts. The answer and the log of every prompt
+//! are written to the output directory, along with a `results.json` summary.
+
+use std::collections::HashSet;
+use std::path::{
+    Path,
+    PathBuf,
+};
+use std::process::Stdio;
+use std::time::Instant;
+
+use clap::{
+    Args,
+    ValueEnum,
+};
+use crossterm::{
+    execute,
+    style,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use thiserror::Error;
+use tokio::io::AsyncWriteExt;
+
+use super::ChatExitCode;
+use crate::agent::{
+    Backend,
+    BackendArgs,
+};
+use crate::os::Os;
+use crate::theme::StyledText;
+
+#[derive(Debug, Clone, PartialEq, Eq, Args)]
+pub struct BatchArgs {
+    /// YAML or JSON file listing the prompts to run
+    #[arg(value_name = "FILE")]
+    pub file: PathBuf,
+    /// Directory the answers and logs of the prompts are written to
+    #[arg(short, long, value_name = "DIR", default_value = "q-batch-results")]
+    pub output_dir: PathBuf,
+    /// Stops at the first prompt that fails instead of running the remaining ones
+    #[arg(long)]
+    pub fail_fast: bool,
+}
+
+#[derive(Debug, Error)]
+pub enum BatchError {
+    #[error("Failed to read the batch file at {}: {error}", path.display())]
+    Io { path: PathBuf, error: std::io::Error },
+    #[error("The batch file at {} is not valid: {error}", path.display())]
+    Invalid { path: PathBuf, error: String },
+}
+
+/// Whether the prompts of a batch share a conversation
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConversationMode {
+    /// Every prompt starts a new conversation
+    #[default]
+    Fresh,
+    /// Every prompt after the first one resumes the conversation of the previous ones
+    Shared,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct BatchFile {
+    #[serde(default)]
+    pub conversation: ConversationMode,
+    pub prompts: Vec<BatchPrompt>,
+}
+
+/// A prompt of the batch. Options left unset fall back to the options of `q chat` given before
+/// `batch`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(from = "PromptEntry")]
+pub struct BatchPrompt {
+    /// Names the files of the results, `prompt-<n>` by default
+    pub id: Option<String>,
+    pub prompt: String,
+    pub agent: Option<String>,
+    pub model: Option<String>,
+    pub trust_all_tools: Option<bool>,
+    pub trust_tools: Option<Vec<String>>,
+}
+
+/// Prompts can be given as a plain string when they use the default options.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PromptEntry {
+    Text(String),
+    #[serde(rename_all = "camelCase")]
+    Full {
+        #[serde(default)]
+        id: Option<String>,
+        prompt: String,
+        #[serde(default)]
+        agent: Option<String>,
+        #[serde(default)]
+        model: Option<String>,
+        #[serde(default)]
+        trust_all_tools: Option<bool>,
+        #[serde(default)]
+        trust_tools: Option<Vec<String>>,
+    },
+}
+
+impl From<PromptEntry> for BatchPrompt {
+    fn from(entry: PromptEntry) -> Self {
+        match entry {
+            PromptEntry::Text(prompt) => Self {
+                id: None,
+                prompt,
+                agent: None,
+                model: None,
+                trust_all_tools: None,
+                trust_tools: None,
+            },
+            PromptEntry::Full {
+                id,
+                prompt,
+                agent,
+                model,
+                trust_all_tools,
+                trust_tools,
+            } => Self {
+                id,
+                prompt,
+                agent,
+                model,
+                trust_all_tools,
+                trust_tools,
+            },
+        }
+    }
+}
+
+/// Options of `q chat` used by the prompts that don't set their own
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PromptDefaults {
+    pub agent: Option<String>,
+    pub model: Option<String>,
+    pub backend: BackendArgs,
+    pub trust_all_tools: bool,
+    pub trust_tools: Option<Vec<String>>,
+    pub read_only: bool,
+    pub approval_policy: Option<PathBuf>,
+}
+
+/// Outcome of a prompt, as written to `results.json`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PromptResult {
+    id: String,
+    prompt: String,
+    exit_code: Option<i32>,
+    duration_ms: u128,
+    output: PathBuf,
+    log: PathBuf,
+}
+
+impl BatchFile {
+    pub async fn load(os: &Os, path: impl AsRef<Path>) -> Result<Self, BatchError> {
+        let path = path.as_ref().to_path_buf();
+        let content = match os.fs.read_to_string(&path).await {
+            Ok(content) => content,
+            Err(error) => return Err(BatchError::Io { path, error }),
+        };
+        Self::from_str(path, &content)
+    }
+
+    fn from_str(path: PathBuf, content: &str) -> Result<Self, BatchError> {
+        let batch: Self = if path.extension().is_some_and(|ext| ext == "json") {
+            serde_json::from_str(content).map_err(|err| err.to_string())
+        } else {
+            serde_yaml::from_str(content).map_err(|err| err.to_string())
+        }
+        .map_err(|error| BatchError::Invalid {
+            path: path.clone(),
+            error,
+        })?;
+
+        if batch.prompts.is_empty() {
+            return Err(BatchError::Invalid {
+                path,
+                error: "it lists no prompts".to_string(),
+            });
+        }
+        let mut ids = HashSet::new();
+        for id in batch.ids() {
+            if id.is_empty()
+                || !id
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+            {
+                return Err(BatchError::Invalid {
+                    path,
+                    error: format!("the id '{id}' may only contain letters, digits, '-', '_' and '.'"),
+                });
+            }
+            if !ids.insert(id.clone()) {
+                return Err(BatchError::Invalid {
+                    path,
+                    error: format!("the id '{id}' is used by several prompts"),
+                });
+            }
+        }
+
+        Ok(batch)
+    }
+
+    /// Ids of the prompts, in order
+    fn ids(&self) -> Vec<String> {
+        self.prompts
+            .iter()
+            .enumerate()
+            .map(|(i, prompt)| prompt.id.clone().unwrap_or_else(|| format!("prompt-{}", i + 1)))
+            .collect()
+    }
+}
+
+impl BatchPrompt {
+    /// Arguments of the `q chat` process running the prompt, which is given through stdin.
+    fn command_args(&self, defaults: &PromptDefaults, resume: bool) -> Vec<String> {
+        PromptDefaults {
+            agent: self.agent.clone().or_else(|| defaults.agent.clone()),
+            model: self.model.clone().or_else(|| defaults.model.clone()),
+            backend: defaults.backend.clone(),
+            trust_all_tools: self.trust_all_tools.unwrap_or(defaults.trust_all_tools),
+            trust_tools: self.trust_tools.clone().or_else(|| defaults.trust_tools.clone()),
+            read_only: defaults.read_only,
+            approval_policy: defaults.approval_policy.clone(),
+        }
+        .command_args(resume)
+    }
+}
+
+impl PromptDefaults {
+    /// Arguments of a `q chat --no-interactive` process using these options, which is given its
+    /// prompt through stdin.
+    pub fn command_args(&self, resume: bool) -> Vec<String> {
+        let mut args = vec!["chat".to_string(), "--no-interactive".to_string()];
+        if resume {
+            args.push("--resume".to_string());
+        }
+        if let Some(agent) = &self.agent {
+            args.push(format!("--agent={agent}"));
+        }
+        if let Some(model) = &self.model {
+            args.push(format!("--model={model}"));
+        }
+        if let Some(backend) = self.backend.backend.to_possible_value() {
+            if self.backend.backend != Backend::default() {
+                args.push(format!("--backend={}", backend.get_name()));
+            }
+        }
+        if let Some(endpoint) = &self.backend.endpoint {
+            args.push(format!("--endpoint={endpoint}"));
+        }
+        if self.trust_all_tools {
+            args.push("--trust-all-tools".to_string());
+        } else if let Some(tools) = &self.trust_tools {
+            args.push(format!("--trust-tools={}", tools.join(",")));
+        }
+        if self.read_only {
+            args.push("--read-only".to_string());
+        }
+        if let Some(path) = &self.approval_policy {
+            args.push(format!("--approval-policy={}", path.display()));
+        }
+        args
+    }
+}
+
+impl BatchArgs {
+    /// Runs the prompts of the batch, returning [ChatExitCode::Failure] if any of them failed.
+    pub async fn execute(&self, os: &Os, defaults: &PromptDefaults) -> eyre::Result<ChatExitCode> {
+        let batch = BatchFile::load(os, &self.file).await?;
+        os.fs.create_dir_all(&self.output_dir).await?;
+        let exe = os.env.current_exe()?;
+        let mut stderr = std::io::stderr();
+
+        let total = batch.prompts.len();
+        let mut results = Vec::new();
+        for (i, (prompt, id)) in batch.prompts.iter().zip(batch.ids()).enumerate() {
+            execute!(
+                stderr,
+                StyledText::emphasis_fg(),
+                style::Print(format!("[{}/{total}] {id}", i + 1)),
+                StyledText::reset(),
+            )?;
+
+            let resume = batch.conversation == ConversationMode::Shared && i > 0;
+            let start = Instant::now();
+            let mut child = tokio::process::Command::new(&exe)
+                .args(prompt.command_args(defaults, resume))
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .spawn()?;
+            if let Some(mut stdin) = child.stdin.take() {
+                stdin.write_all(prompt.prompt.as_bytes()).await?;
+            }
+            let output = child.wait_with_output().await?;
+
+            let result = PromptResult {
+                id: id.clone(),
+                prompt: prompt.prompt.clone(),
+                exit_code: output.status.code(),
+                duration_ms: start.elapsed().as_millis(),
+                output: self.output_dir.join(format!("{id}.out")),
+                log: self.output_dir.join(format!("{id}.log")),
+            };
+            os.fs.write(&result.output, &output.stdout).await?;
+            os.fs.write(&result.log, &output.stderr).await?;
+
+            let succeeded = result.exit_code == Some(0);
+            execute!(
+                stderr,
+                if succeeded {
+                    StyledText::success_fg()
+                } else {
+                    StyledText::error_fg()
+                },
+                style::Print(match result.exit_code {
+                    Some(0) => " ✓\n".to_string(),
+                    Some(code) => format!(" ✗ exit code {code}, see {}\n", result.log.display()),
+                    None => format!(" ✗ terminated, see {}\n", result.log.display()),
+                }),
+                StyledText::reset(),
+            )?;
+
+            results.push(result);
+            // Written after every prompt so that the results of an interrupted batch are kept
+            os.fs
+                .write(
+                    self.output_dir.join("results.json"),
+                    serde_json::to_string_pretty(&results)?,
+                )
+                .await?;
+
+            if !succeeded && self.fail_fast {
+                break;
+            }
+        }
+
+        let failed = results.iter().filter(|r| r.exit_code != Some(0)).count();
+        execute!(
+            stderr,
+            StyledText::secondary_fg(),
+            style::Print(format!(
+                "\n{} of {total} prompts succeeded. Results are in {}\n",
+                results.len() - failed,
+                self.output_dir.display()
+            )),
+            StyledText::reset(),
+        )?;
+
+        Ok(if failed == 0 && results.len() == total {
+            ChatExitCode::Success
+        } else {
+            ChatExitCode::Failure
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(name: &str, content: &str) -> Result<BatchFile, BatchError> {
+        BatchFile::from_str(PathBuf::from(name), content)
+    }
+
+    #[test]
+    fn test_parse_yaml() {
+        let batch = parse(
+            "batch.yaml",
+            r#"
+conversation: shared
+prompts:
+  - Summarize the project
+  - id: fix-lints
+    prompt: Fix the clippy warnings
+    agent: dev
+    model: claude-sonnet-4
+    trustTools: [fs_read, fs_write]
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(batch.conversation, ConversationMode::Shared);
+        assert_eq!(batch.ids(), vec!["prompt-1", "fix-lints"]);
+        assert_eq!(batch.prompts[0].prompt, "Summarize the project");
+        assert_eq!(batch.prompts[1].agent.as_deref(), Some("dev"));
+        assert_eq!(
+            batch.prompts[1].trust_tools,
+            Some(vec!["fs_read".to_string(), "fs_write".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_parse_json() {
+        let batch = parse(
+            "batch.json",
+            r#"{"prompts": [{"prompt": "Add docs", "trustAllTools": true}, "Run the tests"]}"#,
+        )
+        .unwrap();
+
+        assert_eq!(batch.conversation, ConversationMode::Fresh);
+        assert_eq!(batch.prompts[0].trust_all_tools, Some(true));
+        assert_eq!(batch.ids(), vec!["prompt-1", "prompt-2"]);
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert!(matches!(
+            parse("batch.yaml", "prompts: []"),
+            Err(BatchError::Invalid { .. })
+        ));
+        assert!(matches!(
+            parse(
+                "batch.yaml",
+                "prompts:\n  - id: a\n    prompt: x\n  - id: a\n    prompt: y"
+            ),
+            Err(BatchError::Invalid { .. })
+        ));
+        assert!(matches!(
+            parse("batch.yaml", "prompts:\n  - id: ../a\n    prompt: x"),
+            Err(BatchError::Invalid { .. })
+        ));
+        assert!(matches!(
+            parse("batch.yaml", "conversation: forked\nprompts: [x]"),
+            Err(BatchError::Invalid { .. })
+        ));
+    }
+
+    #[test]
+    fn test_command_args() {
+        let defaults = PromptDefaults {
+            agent: Some("default-agent".to_string()),
+            trust_tools: Some(vec!["fs_read".to_string()]),
+            approval_policy: Some(PathBuf::from("policy.yaml")),
+            ..Default::default()
+        };
+        let prompt = BatchPrompt {
+            id: None,
+            prompt: "hi".to_string(),
+            agent: None,
+            model: Some("claude-sonnet-4".to_string()),
+            trust_all_tools: None,
+            trust_tools: None,
+        };
+        assert_eq!(prompt.command_args(&defaults, false), vec![
+            "chat",
+            "--no-interactive",
+            "--agent=default-agent",
+            "--model=claude-sonnet-4",
+            "--trust-tools=fs_read",
+            "--approval-policy=policy.yaml",
+        ]);
+
+        let prompt = BatchPrompt {
+            agent: Some("dev".to_string()),
+            trust_all_tools: Some(true),
+            ..prompt
+        };
+        assert_eq!(prompt.command_args(&defaults, true), vec![
+            "chat",
+            "--no-interactive",
+            "--resume",
+            "--agent=dev",
+            "--model=claude-sonnet-4",
+            "--trust-all-tools",
+            "--approval-policy=policy.yaml",
+        ]);
+    }
+}
@@ -31,6 +31,11 @@ use super::util::truncate_safe;
 use crate::cli::ConversationState;
 use crate::cli::chat::conversation::HistoryEntry;
 use crate::os::Os;
+use crate::theme::StyledText;
+use crate::util::ui::diff::{
+    DiffOptions,
+    print_diff,
+};
 
 /// Manages a shadow git repository for tracking and restoring workspace changes
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -285,20 +290,36 @@ impl CheckpointManager {
         Ok(stats)
     }
 
+    /// Files changed by a checkpoint since the state it was taken from
+    pub fn changed_files(&self, tag: &str) -> Result<Vec<PathBuf>> {
+        let output = run_git(&self.shadow_repo_path, None, &[
+            "diff",
+            "--name-only",
+            &format!("{tag}~1"),
+            tag,
+        ])?;
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(|path| self.work_tree_path.join(path))
+            .collect())
+    }
+
     /// Generate detailed diff between checkpoints
     pub fn diff(&self, from: &str, to: &str) -> Result<String> {
         let mut result = String::new();
 
         // Get file changes
         let output = run_git(&self.shadow_repo_path, None, &["diff", "--name-status", from, to])?;
+        let mut changes = Vec::new();
 
         for line in String::from_utf8_lossy(&output.stdout).lines() {
             if let Some((status, file)) = line.split_once('\t') {
+                changes.push((status.chars().next(), file.to_string()));
                 match status.chars().next() {
-                    Some('A') => result.push_str(&format!("  + {} (added)\n", file).green().to_string()),
-                    Some('M') => result.push_str(&format!("  ~ {} (modified)\n", file).yellow().to_string()),
-                    Some('D') => result.push_str(&format!("  - {} (deleted)\n", file).red().to_string()),
-                    Some('R' | 'C') => result.push_str(&format!("  ~ {} (renamed)\n", file).yellow().to_string()),
+                    Some('A') => result.push_str(&StyledText::success(&format!("  + {} (added)\n", file))),
+                    Some('M') => result.push_str(&StyledText::warning(&format!("  ~ {} (modified)\n", file))),
+                    Some('D') => result.push_str(&StyledText::error(&format!("  - {} (deleted)\n", file))),
+                    Some('R' | 'C') => result.push_str(&StyledText::warning(&format!("  ~ {} (renamed)\n", file))),
                     _ => {},
                 }
             }
@@ -318,9 +339,42 @@ impl CheckpointManager {
             result.push_str(&String::from_utf8_lossy(&stat_output.stdout));
         }
 
+        // Add the changed lines
+        for (status, file) in changes {
+            // Renamed and copied files are listed as `old\tnew`
+            let (old_path, new_path) = file.split_once('\t').unwrap_or((&file, &file));
+            let old = match status {
+                Some('A') => String::new(),
+                _ => self.file_at(from, old_path),
+            };
+            let new = match status {
+                Some('D') => String::new(),
+                _ => self.file_at(to, new_path),
+            };
+
+            result.push_str(&format!("\n{}\n", new_path.bold()));
+            if old.contains('\0') || new.contains('\0') {
+                result.push_str(&StyledText::secondary("  Binary file\n"));
+                continue;
+            }
+            let mut rendered = Vec::new();
+            print_diff(&mut rendered, new_path, &old, &new, DiffOptions {
+                context_lines: Some(DIFF_CONTEXT_LINES),
+                ..Default::default()
+            })?;
+            result.push_str(&String::from_utf8_lossy(&rendered));
+        }
+
         Ok(result)
     }
 
+    /// Returns the content of `path` at the checkpoint `tag`, empty if it didn't exist.
+    fn file_at(&self, tag: &str, path: &str) -> String {
+        run_git(&self.shadow_repo_path, None, &["show", &format!("{tag}:{path}")])
+            .map(|output| String::from_utf8_lossy(&output.stdout).into_owned())
+            .unwrap_or_default()
+    }
+
     /// Check for uncommitted changes
     pub fn has_changes(&self) -> Result<bool> {
         let output = run_git(&self.shadow_repo_path, Some(&self.work_tree_path), &[
@@ -381,6 +435,9 @@ pub fn truncate_message(s: &str, max_len: usize) -> String {
 
 pub const CHECKPOINT_MESSAGE_MAX_LENGTH: usize = 60;
 
+/// Unchanged lines shown around the changed lines of the diffs between checkpoints.
+const DIFF_CONTEXT_LINES: usize = 3;
+
 fn is_git_installed() -> bool {
     Command::new("git")
         .arg("--version")
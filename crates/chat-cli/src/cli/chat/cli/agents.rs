@@ -0,0 +1,626 @@
+//! `/agents`, a dashboard of the tasks delegated to background agents with the `delegate` tool,
+//! refreshed every second. The selected task can be attached to, following its output live,
+//! paused, resumed or cancelled. The changes of the tasks that ran in a git repository are
+//! reviewed and merged into the working tree with `/agents diff` and `/agents merge`.
+
+use std::io::Write;
+use std::time::Duration;
+
+use chrono::{
+    DateTime,
+    Utc,
+};
+use clap::{
+    Args,
+    Subcommand,
+};
+use crossterm::event::{
+    Event,
+    EventStream,
+    KeyCode,
+    KeyEvent,
+    KeyEventKind,
+    KeyModifiers,
+};
+use crossterm::style::{
+    Attribute,
+    Color,
+};
+use crossterm::{
+    cursor,
+    execute,
+    queue,
+    style,
+    terminal,
+};
+use futures::StreamExt;
+
+use crate::cli::chat::tools::delegate::staging::format_changes;
+use crate::cli::chat::tools::delegate::{
+    AgentExecution,
+    AgentStatus,
+    Delegate,
+    cancel_task,
+    current_step,
+    diff_task,
+    discard_task,
+    load_board,
+    load_task,
+    merge_task,
+    output_tail,
+    pause_task,
+    pending_questions,
+    read_output,
+    resume_task,
+    tell_task,
+};
+use crate::cli::chat::{
+    ChatError,
+    ChatSession,
+    ChatState,
+};
+use crate::os::Os;
+use crate::theme::{
+    StyledText,
+    theme,
+};
+use crate::util::format_duration;
+
+/// Time between two refreshes of the dashboard
+const REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+/// Time between two reads of the output of an attached task
+const ATTACH_POLL_INTERVAL: Duration = Duration::from_millis(250);
+/// Lines of output shown for the selected task
+const TAIL_LINES: usize = 8;
+const KEYS_HELP: &str = "↑/↓ select · a attach · p pause/resume · c cancel · q quit";
+
+/// Arguments to the `/agents` command.
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Args)]
+#[command(
+    before_long_help = "/agents shows the tasks delegated to background agents with their status, current step,
+elapsed time and latest output. Select a task with the arrow keys, then:
+• a attaches to it, following its output live until q or Esc is pressed
+• p pauses it, or resumes it when paused
+• c cancels it
+
+Tasks launched in a git repository run in a worktree of their own. Their changes only land in the
+working tree once reviewed and merged:
+• /agents diff <id> shows the changes of a finished task
+• /agents merge <id> applies them to the working tree
+• /agents discard <id> drops them
+
+/agents tell <id> <message> sends guidance to a running task, which receives it after its current
+step. Tasks can also ask questions, shown above the prompt, answered with /agents tell as well."
+)]
+pub struct AgentsArgs {
+    /// Review the changes of a finished task or message a running one instead of opening the
+    /// dashboard
+    #[command(subcommand)]
+    subcommand: Option<AgentsSubcommand>,
+}
+
+/// Subcommands of `/agents` reviewing the changes of a finished task or messaging a running one
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Subcommand)]
+pub enum AgentsSubcommand {
+    /// Show the changes of a finished task
+    Diff {
+        /// Id of the task, as listed by /agents
+        id: String,
+    },
+    /// Apply the changes of a finished task to the working tree
+    Merge {
+        /// Id of the task, as listed by /agents
+        id: String,
+    },
+    /// Drop the changes of a finished task
+    Discard {
+        /// Id of the task, as listed by /agents
+        id: String,
+    },
+    /// Send guidance to a running task, or the answer to its question
+    Tell {
+        /// Id of the task, as listed by /agents
+        id: String,
+        /// Message to send
+        #[arg(required = true, trailing_var_arg = true)]
+        message: Vec<String>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Action {
+    Up,
+    Down,
+    Attach,
+    TogglePause,
+    Cancel,
+    Quit,
+}
+
+impl AgentsArgs {
+    pub async fn execute(self, os: &mut Os, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        let message = if !Delegate::is_enabled(os) {
+            Some("The delegate tool is disabled. Enable it with /experiment\n")
+        } else if let Some(subcommand) = self.subcommand {
+            return subcommand.execute(os, session).await;
+        } else if load_board(os).await.map_err(custom)?.is_empty() {
+            Some("No tasks were delegated to background agents yet\n")
+        } else {
+            None
+        };
+        if let Some(message) = message {
+            execute!(
+                session.stderr,
+                StyledText::secondary_fg(),
+                style::Print(message),
+                StyledText::reset(),
+            )?;
+            return Ok(ChatState::PromptUser {
+                skip_printing_tools: true,
+            });
+        }
+
+        let _screen = Screen::enter(&mut session.stderr)?;
+        run_dashboard(os, &mut session.stderr).await?;
+
+        Ok(ChatState::PromptUser {
+            skip_printing_tools: true,
+        })
+    }
+}
+
+impl AgentsSubcommand {
+    async fn execute(self, os: &Os, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        let result = match &self {
+            Self::Diff { id } => diff_task(os, id).await.map(|diff| match diff.is_empty() {
+                true => format!("Task {id} didn't change any file\n"),
+                false => diff,
+            }),
+            Self::Merge { id } => merge_task(os, id).await.map(|changes| match changes.is_empty() {
+                true => format!("Task {id} didn't change any file\n"),
+                false => format!("Merged the changes of {id}:\n{}", format_changes(&changes)),
+            }),
+            Self::Discard { id } => discard_task(os, id)
+                .await
+                .map(|_| format!("Discarded the changes of {id}\n")),
+            Self::Tell { id, message } => tell_task(os, id, &message.join(" "))
+                .await
+                .map(|_| format!("Sent to {id}, which receives it after its current step\n")),
+        };
+        match result {
+            Ok(output) => execute!(session.stderr, style::Print(output))?,
+            Err(err) => execute!(
+                session.stderr,
+                StyledText::error_fg(),
+                style::Print(format!("{err}\n")),
+                StyledText::reset(),
+            )?,
+        }
+
+        Ok(ChatState::PromptUser {
+            skip_printing_tools: true,
+        })
+    }
+}
+
+/// Alternate screen in raw mode, restored when dropped, also when the command is interrupted
+pub(super) struct Screen;
+
+impl Screen {
+    pub(super) fn enter(output: &mut impl Write) -> Result<Self, ChatError> {
+        terminal::enable_raw_mode()?;
+        execute!(output, terminal::EnterAlternateScreen, cursor::Hide)?;
+        Ok(Self)
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        let _ = execute!(std::io::stderr(), cursor::Show, terminal::LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+async fn run_dashboard(os: &Os, output: &mut impl Write) -> Result<(), ChatError> {
+    let mut events = EventStream::new();
+    let mut refresh = tokio::time::interval(REFRESH_INTERVAL);
+    let mut selected = 0;
+    let mut message = None::<String>;
+
+    loop {
+        let mut board = load_board(os).await.map_err(custom)?;
+        board.sort_by_key(|execution| execution.launched_at);
+        selected = selected.min(board.len().saturating_sub(1));
+        draw_dashboard(os, output, &board, selected, message.as_deref()).await?;
+
+        let action = tokio::select! {
+            _ = refresh.tick() => continue,
+            event = events.next() => match event {
+                Some(Ok(Event::Key(key))) => action(key),
+                Some(Ok(_)) => continue,
+                Some(Err(err)) => return Err(err.into()),
+                None => return Ok(()),
+            },
+        };
+        let Some(action) = action else {
+            continue;
+        };
+        let task = board.get(selected);
+        message = match (action, task) {
+            (Action::Quit, _) => return Ok(()),
+            (Action::Up, _) => {
+                selected = selected.saturating_sub(1);
+                None
+            },
+            (Action::Down, _) => {
+                selected = (selected + 1).min(board.len().saturating_sub(1));
+                None
+            },
+            (Action::Attach, Some(task)) => {
+                attach(os, output, &mut events, task).await?;
+                None
+            },
+            (Action::TogglePause, Some(task)) => Some(
+                match task.status {
+                    AgentStatus::Paused => resume_task(os, &task.id).await.map(|_| format!("Resumed {}", task.id)),
+                    _ => pause_task(os, &task.id).await.map(|_| format!("Paused {}", task.id)),
+                }
+                .unwrap_or_else(|err| err.to_string()),
+            ),
+            (Action::Cancel, Some(task)) => Some(
+                cancel_task(os, &task.id)
+                    .await
+                    .map_or_else(|err| err.to_string(), |_| format!("Cancelled {}", task.id)),
+            ),
+            (_, None) => None,
+        };
+    }
+}
+
+fn action(key: KeyEvent) -> Option<Action> {
+    if key.kind != KeyEventKind::Press {
+        return None;
+    }
+    match key.code {
+        KeyCode::Up | KeyCode::Char('k') => Some(Action::Up),
+        KeyCode::Down | KeyCode::Char('j') => Some(Action::Down),
+        KeyCode::Enter | KeyCode::Char('a') => Some(Action::Attach),
+        KeyCode::Char('p') => Some(Action::TogglePause),
+        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => Some(Action::Quit),
+        KeyCode::Char('c') => Some(Action::Cancel),
+        KeyCode::Char('q') | KeyCode::Esc => Some(Action::Quit),
+        _ => None,
+    }
+}
+
+async fn draw_dashboard(
+    os: &Os,
+    output: &mut impl Write,
+    board: &[AgentExecution],
+    selected: usize,
+    message: Option<&str>,
+) -> Result<(), ChatError> {
+    let width = terminal::size().map_or(80, |(columns, _)| columns as usize);
+    let now = Utc::now();
+
+    queue!(
+        output,
+        terminal::Clear(terminal::ClearType::All),
+        cursor::MoveTo(0, 0),
+        StyledText::brand_fg(),
+        style::SetAttribute(Attribute::Bold),
+        style::Print("Delegated agents"),
+        StyledText::reset_attributes(),
+        StyledText::secondary_fg(),
+        style::Print(fit(&format!(" · {KEYS_HELP}"), width.saturating_sub(16))),
+        StyledText::reset(),
+        style::Print("\r\n\r\n"),
+        style::SetAttribute(Attribute::Dim),
+        style::Print(fit(
+            &format!("  {:<20} {:<11} {:<8} STEP", "TASK", "STATUS", "ELAPSED"),
+            width
+        )),
+        StyledText::reset_attributes(),
+        style::Print("\r\n"),
+    )?;
+
+    let questions = pending_questions(os).await.unwrap_or_default();
+    let mut selected_output = String::new();
+    for (i, execution) in board.iter().enumerate() {
+        let task_output = read_output(os, &execution.id).await;
+        let question = questions
+            .iter()
+            .find(|(id, _)| *id == execution.id)
+            .map(|(_, question)| question.text.as_str());
+        let row = fit(
+            &format_row(execution, &task_output, question, now),
+            width.saturating_sub(2),
+        );
+        queue!(
+            output,
+            style::Print(if i == selected { "> " } else { "  " }),
+            style::SetForegroundColor(status_color(execution.status)),
+        )?;
+        if i == selected {
+            queue!(output, style::SetAttribute(Attribute::Bold))?;
+            selected_output = task_output;
+        }
+        queue!(
+            output,
+            style::Print(row),
+            StyledText::reset_attributes(),
+            StyledText::reset(),
+            style::Print("\r\n"),
+        )?;
+    }
+
+    if let Some(execution) = board.get(selected) {
+        queue!(
+            output,
+            style::Print("\r\n"),
+            style::SetAttribute(Attribute::Bold),
+            style::Print(fit(
+                &format!("{} · {} · {}", execution.id, execution.cwd, execution.task),
+                width
+            )),
+            StyledText::reset_attributes(),
+            style::Print("\r\n"),
+        )?;
+        for line in output_tail(&selected_output, TAIL_LINES) {
+            queue!(
+                output,
+                StyledText::secondary_fg(),
+                style::Print("│ "),
+                StyledText::reset(),
+                style::Print(fit(line, width.saturating_sub(2))),
+                style::Print("\r\n"),
+            )?;
+        }
+    }
+
+    if let Some(message) = message {
+        queue!(
+            output,
+            style::Print("\r\n"),
+            StyledText::warning_fg(),
+            style::Print(fit(message, width)),
+            StyledText::reset(),
+        )?;
+    }
+    output.flush()?;
+    Ok(())
+}
+
+/// Follows the output of the task until q, Esc or ctrl+c is pressed.
+async fn attach(
+    os: &Os,
+    output: &mut impl Write,
+    events: &mut EventStream,
+    execution: &AgentExecution,
+) -> Result<(), ChatError> {
+    queue!(
+        output,
+        terminal::Clear(terminal::ClearType::All),
+        cursor::MoveTo(0, 0),
+        StyledText::brand_fg(),
+        style::Print(format!("Attached to {}", execution.id)),
+        StyledText::secondary_fg(),
+        style::Print(" · press q or Esc to detach\r\n\r\n"),
+        StyledText::reset(),
+    )?;
+    output.flush()?;
+
+    let mut shown = 0;
+    let mut finished = false;
+    loop {
+        let task_output = read_output(os, &execution.id).await;
+        // The end of the output read may be in the middle of a character being written
+        if let Some(new) = task_output.get(shown..) {
+            queue!(output, style::Print(new.replace('\n', "\r\n")))?;
+            shown = task_output.len();
+        }
+        if !finished {
+            if let Some(task) = load_task(os, &execution.id).await.map_err(custom)? {
+                if !matches!(
+                    task.status,
+                    AgentStatus::Queued | AgentStatus::Running | AgentStatus::Paused
+                ) {
+                    finished = true;
+                    queue!(
+                        output,
+                        style::Print("\r\n"),
+                        style::SetForegroundColor(status_color(task.status)),
+                        style::Print(format!("[{} {}]\r\n", task.id, task.status)),
+                        StyledText::reset(),
+                    )?;
+                }
+            }
+        }
+        output.flush()?;
+
+        tokio::select! {
+            _ = tokio::time::sleep(ATTACH_POLL_INTERVAL) => {},
+            event = events.next() => match event {
+                Some(Ok(Event::Key(key))) if action(key) == Some(Action::Quit) => return Ok(()),
+                Some(Ok(_)) => {},
+                Some(Err(err)) => return Err(err.into()),
+                None => return Ok(()),
+            },
+        }
+    }
+}
+
+/// Row of the task in the dashboard, without the colors
+fn format_row(execution: &AgentExecution, output: &str, question: Option<&str>, now: DateTime<Utc>) -> String {
+    let elapsed = match execution.status {
+        AgentStatus::Queued => "-".to_string(),
+        _ => format_duration(
+            (execution.completed_at.unwrap_or(now) - execution.launched_at)
+                .to_std()
+                .unwrap_or_default(),
+        ),
+    };
+    let step = match execution.status {
+        AgentStatus::Queued => "waiting for a running task to finish".to_string(),
+        AgentStatus::Running | AgentStatus::Paused => match question {
+            Some(question) => format!("asks: {question}"),
+            None => current_step(output).unwrap_or("starting").to_string(),
+        },
+        AgentStatus::Completed => "done".to_string(),
+        AgentStatus::Failed => format!("exit code {}", execution.exit_code.unwrap_or(-1)),
+        AgentStatus::Cancelled => "cancelled by the user".to_string(),
+        AgentStatus::Interrupted => format!("interrupted, continue it with q agent resume {}", execution.id),
+    };
+    let finished = !matches!(
+        execution.status,
+        AgentStatus::Queued | AgentStatus::Running | AgentStatus::Paused
+    );
+    let step = if finished && execution.has_pending_changes() {
+        format!("{step}, changes to review with /agents diff {}", execution.id)
+    } else {
+        step
+    };
+    format!(
+        "{:<20} {:<11} {:<8} {step}",
+        execution.id,
+        execution.status.to_string(),
+        elapsed
+    )
+}
+
+fn status_color(status: AgentStatus) -> Color {
+    match status {
+        AgentStatus::Running | AgentStatus::Completed => theme().status.success,
+        AgentStatus::Queued | AgentStatus::Paused | AgentStatus::Interrupted => theme().status.warning,
+        AgentStatus::Failed | AgentStatus::Cancelled => theme().status.error,
+    }
+}
+
+/// Truncates the line to the width of the terminal.
+pub(super) fn fit(line: &str, width: usize) -> String {
+    line.chars().take(width).collect()
+}
+
+fn custom(err: eyre::Report) -> ChatError {
+    ChatError::Custom(err.to_string().into())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+    use crate::cli::chat::tools::delegate::staging::{
+        StagedChanges,
+        StagingState,
+    };
+
+    #[test]
+    fn test_format_row() {
+        let start = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let now = start + Duration::seconds(192);
+        let execution = |status: AgentStatus| AgentExecution {
+            id: "rust-agent-1".to_string(),
+            status,
+            launched_at: start,
+            exit_code: Some(2),
+            ..Default::default()
+        };
+        let output =
+            "Reading the tests\n🛠️  Using tool: fs_read\nThe tests fail because\n🛠️  Using tool: fs_write (trusted)\n";
+
+        assert_eq!(
+            format_row(&execution(AgentStatus::Running), output, None, now),
+            "rust-agent-1         running     3m12s    Using tool: fs_write (trusted)"
+        );
+        assert_eq!(
+            format_row(&execution(AgentStatus::Running), "", None, now),
+            "rust-agent-1         running     3m12s    starting"
+        );
+        assert_eq!(
+            format_row(
+                &execution(AgentStatus::Running),
+                output,
+                Some("Can I rename the crate?"),
+                now
+            ),
+            "rust-agent-1         running     3m12s    asks: Can I rename the crate?"
+        );
+        assert_eq!(
+            format_row(&execution(AgentStatus::Queued), "", None, now),
+            "rust-agent-1         queued      -        waiting for a running task to finish"
+        );
+        assert_eq!(
+            format_row(
+                &AgentExecution {
+                    completed_at: Some(start + Duration::seconds(30)),
+                    ..execution(AgentStatus::Failed)
+                },
+                output,
+                None,
+                now
+            ),
+            "rust-agent-1         failed      30s      exit code 2"
+        );
+        assert_eq!(
+            format_row(
+                &AgentExecution {
+                    completed_at: Some(start + Duration::seconds(30)),
+                    ..execution(AgentStatus::Interrupted)
+                },
+                output,
+                None,
+                now
+            ),
+            "rust-agent-1         interrupted 30s      interrupted, continue it with q agent resume rust-agent-1"
+        );
+
+        let staged = StagedChanges {
+            repo_root: "/repo".into(),
+            worktree: "/repo/.amazonq/.subagents/worktrees/rust-agent-1".into(),
+            base: "HEAD".to_string(),
+            state: StagingState::Pending,
+        };
+        assert_eq!(
+            format_row(
+                &AgentExecution {
+                    completed_at: Some(start + Duration::seconds(30)),
+                    staged: Some(staged.clone()),
+                    ..execution(AgentStatus::Completed)
+                },
+                output,
+                None,
+                now
+            ),
+            "rust-agent-1         completed   30s      done, changes to review with /agents diff rust-agent-1"
+        );
+        assert_eq!(
+            format_row(
+                &AgentExecution {
+                    completed_at: Some(start + Duration::seconds(30)),
+                    staged: Some(StagedChanges {
+                        state: StagingState::Merged,
+                        ..staged
+                    }),
+                    ..execution(AgentStatus::Completed)
+                },
+                output,
+                None,
+                now
+            ),
+            "rust-agent-1         completed   30s      done"
+        );
+    }
+
+    #[test]
+    fn test_action() {
+        let key = |code: KeyCode, modifiers: KeyModifiers| action(KeyEvent::new(code, modifiers));
+        assert_eq!(key(KeyCode::Char('c'), KeyModifiers::NONE), Some(Action::Cancel));
+        assert_eq!(key(KeyCode::Char('c'), KeyModifiers::CONTROL), Some(Action::Quit));
+        assert_eq!(key(KeyCode::Enter, KeyModifiers::NONE), Some(Action::Attach));
+        assert_eq!(key(KeyCode::Char('j'), KeyModifiers::NONE), Some(Action::Down));
+        assert_eq!(key(KeyCode::Char('x'), KeyModifiers::NONE), None);
+    }
+}
@@ -0,0 +1,66 @@
+use clap::Args;
+use crossterm::execute;
+use crossterm::style::{
+    self,
+};
+
+use crate::cli::chat::{
+    ChatError,
+    ChatSession,
+    ChatState,
+    accessible,
+};
+use crate::theme::StyledText;
+
+#[derive(Debug, PartialEq, Args)]
+pub struct AttachArgs {
+    /// Paths of the images (jpg, jpeg, png, gif or webp) to attach to your next message
+    paths: Vec<String>,
+    /// Remove the images attached so far
+    #[arg(long)]
+    clear: bool,
+}
+
+impl AttachArgs {
+    pub async fn execute(self, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        if self.clear {
+            session.pending_images.clear();
+        }
+
+        for path in &self.paths {
+            if let Err(e) = session.attach_image(path) {
+                execute!(
+                    session.stderr,
+                    StyledText::error_fg(),
+                    style::Print(format!("\nFailed to attach {path}: {e}\n")),
+                    StyledText::reset(),
+                )?;
+            }
+        }
+
+        if session.pending_images.is_empty() {
+            execute!(
+                session.stderr,
+                StyledText::secondary_fg(),
+                style::Print("\nNo images attached. Use /attach <path> to attach an image to your next message.\n\n"),
+                StyledText::reset(),
+            )?;
+        } else {
+            execute!(session.stderr, style::Print("\nAttached to your next message:\n"))?;
+            for (_, metadata) in &session.pending_images {
+                execute!(
+                    session.stderr,
+                    style::Print(format!("  {}{} ", accessible::symbol("📎 ", ""), metadata.filename)),
+                    StyledText::secondary_fg(),
+                    style::Print(format!("({:.1} KB)\n", metadata.size as f64 / 1024.0)),
+                    StyledText::reset(),
+                )?;
+            }
+            execute!(session.stderr, style::Print("\n"))?;
+        }
+
+        Ok(ChatState::PromptUser {
+            skip_printing_tools: true,
+        })
+    }
+}
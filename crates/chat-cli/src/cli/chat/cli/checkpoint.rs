@@ -21,6 +21,7 @@ use crate::cli::chat::{
     ChatError,
     ChatSession,
     ChatState,
+    accessible,
 };
 use crate::cli::experiment::experiment_manager::{
     ExperimentManager,
@@ -104,8 +105,9 @@ impl CheckpointSubcommand {
             execute!(
                 session.stderr,
                 StyledText::warning_fg(),
+                style::Print(accessible::symbol("⚠️ ", "Warning: ")),
                 style::Print(
-                    "⚠️ Checkpoint is disabled while in tangent mode. Please exit tangent mode if you want to use checkpoint.\n\n"
+                    "Checkpoint is disabled while in tangent mode. Please exit tangent mode if you want to use checkpoint.\n\n"
                 ),
                 StyledText::reset(),
             )?;
@@ -176,7 +178,8 @@ impl CheckpointSubcommand {
             execute!(
                 session.stderr,
                 StyledText::warning_fg(),
-                style::Print("⚠️ Checkpoints not enabled. Use '/checkpoint init' to enable.\n"),
+                style::Print(accessible::symbol("⚠️ ", "Warning: ")),
+                style::Print("Checkpoints not enabled. Use '/checkpoint init' to enable.\n"),
                 StyledText::reset(),
             )?;
             return Ok(ChatState::PromptUser {
@@ -241,7 +244,8 @@ impl CheckpointSubcommand {
             execute!(
                 session.stderr,
                 StyledText::warning_fg(),
-                style::Print("⚠️ Checkpoints not enabled. Use '/checkpoint init' to enable.\n"),
+                style::Print(accessible::symbol("⚠️ ", "Warning: ")),
+                style::Print("Checkpoints not enabled. Use '/checkpoint init' to enable.\n"),
                 StyledText::reset(),
             )?;
             return Ok(ChatState::PromptUser {
@@ -262,7 +266,8 @@ impl CheckpointSubcommand {
             execute!(
                 session.stderr,
                 StyledText::warning_fg(),
-                style::Print("⚠️ ️Checkpoints not enabled.\n"),
+                style::Print(accessible::symbol("⚠️ ", "Warning: ")),
+                style::Print("️Checkpoints not enabled.\n"),
                 StyledText::reset(),
             )?;
             return Ok(ChatState::PromptUser {
@@ -301,7 +306,8 @@ impl CheckpointSubcommand {
             execute!(
                 session.stderr,
                 StyledText::warning_fg(),
-                style::Print("⚠️ ️Checkpoints not enabled. Use '/checkpoint init' to enable.\n"),
+                style::Print(accessible::symbol("⚠️ ", "Warning: ")),
+                style::Print("️Checkpoints not enabled. Use '/checkpoint init' to enable.\n"),
                 StyledText::reset(),
             )?;
             return Ok(ChatState::PromptUser {
@@ -322,7 +328,8 @@ impl CheckpointSubcommand {
             execute!(
                 session.stderr,
                 StyledText::warning_fg(),
-                style::Print("⚠️ Checkpoints not enabled. Use '/checkpoint init' to enable.\n"),
+                style::Print(accessible::symbol("⚠️ ", "Warning: ")),
+                style::Print("Checkpoints not enabled. Use '/checkpoint init' to enable.\n"),
                 StyledText::reset(),
             )?;
             return Ok(ChatState::PromptUser {
@@ -337,8 +344,9 @@ impl CheckpointSubcommand {
             execute!(
                 session.stderr,
                 StyledText::warning_fg(),
+                style::Print(accessible::symbol("⚠️ ", "Warning: ")),
                 style::Print(format!(
-                    "⚠️ Checkpoint '{}' not found! Use /checkpoint list to see available checkpoints\n",
+                    "Checkpoint '{}' not found! Use /checkpoint list to see available checkpoints\n",
                     tag1
                 )),
                 StyledText::reset(),
@@ -352,8 +360,9 @@ impl CheckpointSubcommand {
             execute!(
                 session.stderr,
                 StyledText::warning_fg(),
+                style::Print(accessible::symbol("⚠️ ", "Warning: ")),
                 style::Print(format!(
-                    "⚠️ Checkpoint '{}' not found! Use /checkpoint list to see available checkpoints\n",
+                    "Checkpoint '{}' not found! Use /checkpoint list to see available checkpoints\n",
                     tag2
                 )),
                 StyledText::reset(),
@@ -501,7 +510,8 @@ fn expand_checkpoint(manager: &CheckpointManager, output: &mut impl Write, tag:
         execute!(
             output,
             StyledText::warning_fg(),
-            style::Print(format!("⚠️ checkpoint '{}' not found\n", tag)),
+            style::Print(accessible::symbol("⚠️ ", "Warning: ")),
+            style::Print(format!("checkpoint '{}' not found\n", tag)),
             StyledText::reset(),
         )?;
         return Ok(());
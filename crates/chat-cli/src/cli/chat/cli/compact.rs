@@ -1,13 +1,30 @@
-use clap::Args;
+use std::fmt;
+use std::time::Duration;
+
+use clap::{
+    Args,
+    ValueEnum,
+};
+use crossterm::style::Attribute;
+use crossterm::{
+    queue,
+    style,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
 
 use crate::cli::chat::consts::MAX_USER_MESSAGE_SIZE;
 use crate::cli::chat::message::UserMessageContent;
+use crate::cli::chat::util::truncate_safe;
 use crate::cli::chat::{
     ChatError,
     ChatSession,
     ChatState,
 };
 use crate::os::Os;
+use crate::theme::StyledText;
 
 #[deny(missing_docs)]
 #[derive(Debug, PartialEq, Args)]
@@ -28,7 +45,28 @@ How it works
 • Clears the conversation history to free up space
 • The assistant will reference the summary context in future responses
 
-Compaction will be automatically performed whenever the context window overflows.
+Preview
+• /compact --preview shows the summary and how many tokens it would save without replacing the
+  history, then lets you use it as is, edit it in $EDITOR first, or cancel
+
+Prompts sent with /important are never summarized: they are carried forward verbatim alongside the
+summary.
+
+Summary checkpoints
+• Every summary is kept along with the messages it replaced
+• /compact history lists the summaries created in this conversation
+• /compact stats shows the tokens saved by the compactions of this conversation
+• /compact expand <number> restores the messages replaced by a summary, and by the summaries created
+  after it
+
+Strategies
+• full (default): summarizes the whole history
+• sliding: summarizes the oldest part of the history and keeps the most recent turns as they are
+
+Compaction will be automatically performed whenever the context window overflows, using the
+sliding strategy. Before that, once the conversation uses 75% of the context window, the older
+history is summarized in the background between turns. Change this percentage, or set it to 0 to
+turn this off, with: `q settings chat.backgroundCompactionThreshold <percentage>`
 To disable this behavior, run: `q settings chat.disableAutoCompaction true`"
 )]
 /// Arguments for the `/compact` command that summarizes conversation history to free up context
@@ -42,6 +80,12 @@ pub struct CompactArgs {
     prompt: Vec<String>,
     #[arg(long)]
     show_summary: bool,
+    /// Show the summary and the projected savings, then choose whether to use it, edit it or cancel
+    #[arg(long)]
+    preview: bool,
+    /// How much of the history to summarize
+    #[arg(long, value_enum, default_value_t)]
+    strategy: CompactKind,
     /// The number of user and assistant message pairs to exclude from the summarization.
     #[arg(long)]
     messages_to_exclude: Option<usize>,
@@ -56,6 +100,10 @@ pub struct CompactArgs {
 
 impl CompactArgs {
     pub async fn execute(self, os: &mut Os, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        if let Some(subcommand) = self.subcommand() {
+            return subcommand.execute(session);
+        }
+
         let default = CompactStrategy::default();
         let prompt = if self.prompt.is_empty() {
             None
@@ -69,6 +117,9 @@ impl CompactArgs {
 
         session
             .compact_history(os, prompt, self.show_summary, CompactStrategy {
+                kind: self.strategy,
+                preview: self.preview,
+                collapse_tool_results: default.collapse_tool_results,
                 messages_to_exclude: self.messages_to_exclude.unwrap_or(default.messages_to_exclude),
                 truncate_large_messages: self.truncate_large_messages.unwrap_or(default.truncate_large_messages),
                 max_message_length: self.max_message_length.map_or(default.max_message_length, |v| {
@@ -77,11 +128,298 @@ impl CompactArgs {
             })
             .await
     }
+
+    pub fn subcommand_name(&self) -> Option<&'static str> {
+        self.subcommand().map(|s| s.name())
+    }
+
+    /// `history`, `stats` and `expand <number>` are subcommands when given on their own. Anything
+    /// else is the prompt of the summary, e.g. `/compact history of the schema changes`.
+    fn subcommand(&self) -> Option<CompactSubcommand> {
+        match self.prompt.iter().map(String::as_str).collect::<Vec<_>>().as_slice() {
+            ["history"] => Some(CompactSubcommand::History),
+            ["stats"] => Some(CompactSubcommand::Stats),
+            ["expand", number] => number.parse().ok().map(|number| CompactSubcommand::Expand { number }),
+            _ => None,
+        }
+    }
+}
+
+/// Subcommands for the summaries kept by compaction
+#[derive(Debug, PartialEq)]
+pub enum CompactSubcommand {
+    /// List the summaries created in this conversation
+    History,
+    /// Show how much context space compaction saved during this session
+    Stats,
+    /// Restore the messages replaced by a summary, and by the summaries created after it
+    Expand {
+        /// Number of the summary, as listed by /compact history
+        number: usize,
+    },
+}
+
+impl CompactSubcommand {
+    fn execute(self, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        let checkpoints = session.conversation.summary_checkpoints();
+        match self {
+            Self::Stats => queue_compaction_stats(session)?,
+            _ if checkpoints.is_empty() => queue!(
+                session.stderr,
+                StyledText::secondary_fg(),
+                style::Print("\nThis conversation has not been compacted yet.\n\n"),
+                StyledText::reset(),
+            )?,
+            Self::History => {
+                queue!(
+                    session.stderr,
+                    style::Print("\n"),
+                    style::SetAttribute(Attribute::Bold),
+                    style::Print("Summaries of this conversation:\n"),
+                    StyledText::reset_attributes(),
+                )?;
+                for (i, checkpoint) in checkpoints.iter().enumerate() {
+                    let turns = match checkpoint.turns {
+                        (start, end) if end <= start + 1 => format!("turn {start}"),
+                        (start, end) => format!("turns {start}-{}", end - 1),
+                    };
+                    // First line of content, skipping the headings
+                    let preview = checkpoint
+                        .summary
+                        .lines()
+                        .map(|line| line.trim().trim_start_matches(['*', '-', '•']).trim())
+                        .find(|line| !line.is_empty() && !line.starts_with('#'))
+                        .unwrap_or_default();
+                    queue!(
+                        session.stderr,
+                        StyledText::brand_fg(),
+                        style::Print(format!("{:>3}. ", i + 1)),
+                        StyledText::reset(),
+                        style::Print(format!(
+                            "{} ({turns}, {} messages)\n",
+                            checkpoint.created_at.format("%Y-%m-%d %H:%M"),
+                            checkpoint.message_count()
+                        )),
+                        StyledText::secondary_fg(),
+                        style::Print(format!("     {}\n", truncate_safe(preview, 100))),
+                        StyledText::reset(),
+                    )?;
+                }
+                queue!(
+                    session.stderr,
+                    StyledText::secondary_fg(),
+                    style::Print("\nRun "),
+                    StyledText::success_fg(),
+                    style::Print("/compact expand <number>"),
+                    StyledText::secondary_fg(),
+                    style::Print(" to restore the messages replaced by a summary.\n\n"),
+                    StyledText::reset(),
+                )?;
+            },
+            Self::Expand { number } => {
+                let count = checkpoints.len();
+                let Some(restored) = number
+                    .checked_sub(1)
+                    .and_then(|index| session.conversation.expand_summary_checkpoint(index))
+                else {
+                    queue!(
+                        session.stderr,
+                        StyledText::error_fg(),
+                        style::Print(format!(
+                            "\nThere is no summary {number}. Pick a number between 1 and {count}.\n\n"
+                        )),
+                        StyledText::reset(),
+                    )?;
+                    return Ok(ChatState::PromptUser {
+                        skip_printing_tools: true,
+                    });
+                };
+
+                queue!(
+                    session.stderr,
+                    StyledText::success_fg(),
+                    style::Print(format!("\n✔ Restored {restored} messages in place of ")),
+                    style::Print(if number == count {
+                        format!("summary {number}")
+                    } else {
+                        format!("summaries {number} to {count}")
+                    }),
+                    style::Print(".\n\n"),
+                    StyledText::reset(),
+                )?;
+            },
+        }
+
+        Ok(ChatState::PromptUser {
+            skip_printing_tools: true,
+        })
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::History => "history",
+            Self::Stats => "stats",
+            Self::Expand { .. } => "expand",
+        }
+    }
+}
+
+fn queue_compaction_stats(session: &mut ChatSession) -> Result<(), ChatError> {
+    let compactions = session.conversation.compactions();
+    if compactions.is_empty() {
+        queue!(
+            session.stderr,
+            StyledText::secondary_fg(),
+            style::Print("\nThis conversation has not been compacted yet.\n\n"),
+            StyledText::reset(),
+        )?;
+        return Ok(());
+    }
+
+    queue!(
+        session.stderr,
+        style::Print("\n"),
+        style::SetAttribute(Attribute::Bold),
+        style::Print("Compactions of this conversation:\n"),
+        StyledText::reset_attributes(),
+    )?;
+    for trigger in CompactionTrigger::ALL {
+        let records = compactions
+            .iter()
+            .filter(|record| record.trigger == trigger)
+            .collect::<Vec<_>>();
+        if records.is_empty() {
+            continue;
+        }
+        let saved = records.iter().map(|record| record.tokens_saved()).sum::<usize>();
+        let folded = records.iter().map(|record| record.messages_folded).sum::<usize>();
+        let duration = records.iter().map(|record| record.duration).sum::<Duration>();
+        queue!(
+            session.stderr,
+            StyledText::brand_fg(),
+            style::Print(format!("  {trigger}")),
+            StyledText::reset(),
+            style::Print(format!(
+                ": {} times, ~{saved} tokens saved, {folded} messages folded, {:.1}s spent\n",
+                records.len(),
+                duration.as_secs_f64()
+            )),
+        )?;
+    }
+
+    let saved = compactions.iter().map(|record| record.tokens_saved()).sum::<usize>();
+    queue!(
+        session.stderr,
+        style::SetAttribute(Attribute::Bold),
+        style::Print(format!(
+            "  Total: ~{saved} tokens saved over {} compactions\n",
+            compactions.len()
+        )),
+        StyledText::reset_attributes(),
+        StyledText::secondary_fg(),
+        style::Print(
+            "\nAutomatic compactions can be turned off with `q settings chat.disableAutoCompaction true`, and background\nones tuned with `q settings chat.backgroundCompactionThreshold <percentage>`.\n\n"
+        ),
+        StyledText::reset(),
+    )?;
+    Ok(())
+}
+
+/// What triggered a compaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CompactionTrigger {
+    /// `/compact`
+    Manual,
+    /// The context window overflowed
+    Overflow,
+    /// The conversation reached `chat.backgroundCompactionThreshold`
+    Background,
+}
+
+impl CompactionTrigger {
+    const ALL: [Self; 3] = [Self::Manual, Self::Overflow, Self::Background];
+
+    /// Name of the trigger in telemetry.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Manual => "manual",
+            Self::Overflow => "overflow",
+            Self::Background => "background",
+        }
+    }
+}
+
+impl fmt::Display for CompactionTrigger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Manual => "Manual (/compact)",
+            Self::Overflow => "Context window overflow",
+            Self::Background => "Background",
+        })
+    }
+}
+
+/// A compaction of the conversation, reported after it completes, by `/compact stats` and to
+/// telemetry.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct CompactionRecord {
+    pub trigger: CompactionTrigger,
+    pub tokens_before: usize,
+    pub tokens_after: usize,
+    /// Number of history entries replaced by the summary.
+    pub messages_folded: usize,
+    pub duration: Duration,
+}
+
+impl CompactionRecord {
+    pub fn tokens_saved(&self) -> usize {
+        self.tokens_before.saturating_sub(self.tokens_after)
+    }
+}
+
+impl fmt::Display for CompactionRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "~{} → ~{} tokens (~{} saved), {} messages folded in {:.1}s",
+            self.tokens_before,
+            self.tokens_after,
+            self.tokens_saved(),
+            self.messages_folded,
+            self.duration.as_secs_f64()
+        )
+    }
+}
+
+/// Percentage of the history summarized by [CompactKind::Sliding], oldest messages first.
+pub const SLIDING_SUMMARIZED_PERCENT: usize = 60;
+
+/// Number of most recent turns [CompactKind::Sliding] always keeps as they are.
+pub const SLIDING_KEPT_TURNS: usize = 3;
+
+/// Which part of the history is summarized.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum CompactKind {
+    /// Summarize the whole history
+    #[default]
+    Full,
+    /// Summarize the oldest part of the history, keeping the most recent turns as they are
+    Sliding,
 }
 
 /// Parameters for performing the history compaction request.
 #[derive(Debug, Copy, Clone)]
 pub struct CompactStrategy {
+    /// Which part of the history is summarized. [CompactKind::Sliding] is resolved into
+    /// [Self::messages_to_exclude] right before the compaction request is created.
+    pub kind: CompactKind,
+    /// Whether to ask the user to accept, edit or discard the summary before it replaces the
+    /// history.
+    pub preview: bool,
+    /// Whether to first replace large tool results with digests, skipping the summary if that
+    /// frees up enough of the context window.
+    pub collapse_tool_results: bool,
     /// Number of user/assistant pairs to exclude from the history as part of compaction.
     pub messages_to_exclude: usize,
     /// Whether or not to truncate large messages in the history.
@@ -93,9 +431,80 @@ pub struct CompactStrategy {
 impl Default for CompactStrategy {
     fn default() -> Self {
         Self {
+            kind: Default::default(),
+            preview: Default::default(),
+            collapse_tool_results: Default::default(),
             messages_to_exclude: Default::default(),
             truncate_large_messages: Default::default(),
             max_message_length: MAX_USER_MESSAGE_SIZE,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+
+    #[derive(Parser, Debug)]
+    #[command(name = "test")]
+    struct TestCli {
+        #[command(flatten)]
+        compact: CompactArgs,
+    }
+
+    #[test]
+    fn test_compact_subcommand_parsing() {
+        let parse = |args: &[&str]| {
+            TestCli::try_parse_from(std::iter::once("test").chain(args.iter().copied()))
+                .unwrap()
+                .compact
+        };
+
+        assert_eq!(parse(&["history"]).subcommand(), Some(CompactSubcommand::History));
+        assert_eq!(parse(&["stats"]).subcommand(), Some(CompactSubcommand::Stats));
+        assert_eq!(
+            parse(&["expand", "2"]).subcommand(),
+            Some(CompactSubcommand::Expand { number: 2 })
+        );
+
+        // Anything else is the prompt of the summary
+        for prompt in [
+            &["focus", "on", "history"][..],
+            &["history", "of", "the", "schema", "changes"],
+            &["expand", "on", "the", "tests"],
+            &["stats", "only"],
+        ] {
+            let args = parse(prompt);
+            assert_eq!(args.subcommand(), None);
+            assert_eq!(args.prompt, prompt);
+        }
+
+        let args = parse(&["--preview", "history", "of", "the", "tests"]);
+        assert!(args.preview && args.subcommand().is_none());
+    }
+
+    #[test]
+    fn test_compaction_record() {
+        let record = CompactionRecord {
+            trigger: CompactionTrigger::Manual,
+            tokens_before: 120_000,
+            tokens_after: 8_000,
+            messages_folded: 42,
+            duration: Duration::from_millis(6_300),
+        };
+        assert_eq!(record.tokens_saved(), 112_000);
+        assert_eq!(
+            record.to_string(),
+            "~120000 → ~8000 tokens (~112000 saved), 42 messages folded in 6.3s"
+        );
+
+        // Compaction can grow the conversation, e.g. with a long summary of a short history
+        let record = CompactionRecord {
+            tokens_after: 130_000,
+            ..record
+        };
+        assert_eq!(record.tokens_saved(), 0);
+    }
+}
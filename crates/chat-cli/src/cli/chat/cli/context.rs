@@ -1,6 +1,9 @@
 use std::collections::HashSet;
 
-use clap::Subcommand;
+use clap::{
+    Subcommand,
+    ValueEnum,
+};
 use crossterm::style::Attribute;
 use crossterm::{
     execute,
@@ -10,14 +13,18 @@ use crossterm::{
 use crate::cli::chat::consts::AGENT_FORMAT_HOOKS_DOC_URL;
 use crate::cli::chat::context::{
     ContextFilePath,
+    ContextSnapshot,
     calc_max_context_files_size,
 };
+use crate::cli::chat::context_budget::ContextSource;
+use crate::cli::chat::symbol_context::SymbolContext;
 use crate::cli::chat::token_counter::TokenCounter;
-use crate::cli::chat::util::drop_matched_context_files;
+use crate::cli::chat::url_context::fetch_url;
 use crate::cli::chat::{
     ChatError,
     ChatSession,
     ChatState,
+    accessible,
 };
 use crate::constants::help_text::{
     CONTEXT_DESCRIPTION,
@@ -41,7 +48,7 @@ pub enum ContextSubcommand {
         #[arg(long)]
         expand: bool,
     },
-    /// Add context rules (filenames or glob patterns)
+    /// Add context rules (filenames, directories or glob patterns)
     Add {
         /// Include even if matched files exceed size limits
         #[arg(short, long)]
@@ -50,20 +57,89 @@ pub enum ContextSubcommand {
         /// Paths or glob patterns to remove from context rules
         paths: Vec<String>,
     },
+    /// Fetch a web page and add its readable content to context
+    AddUrl {
+        /// The http or https URL of the page
+        url: String,
+        /// Name to list the page under, defaults to the page title
+        #[arg(long)]
+        name: Option<String>,
+    },
+    /// Add the definition of a function, type or class to context rather than its whole file
+    AddSymbol {
+        /// The file and the symbol, e.g. src/main.rs::main, src/agent.rs::Agent::new or
+        /// app.py::Agent.run
+        symbol: String,
+    },
     /// Remove specified rules
     #[command(alias = "rm")]
     Remove {
-        /// Paths or glob patterns to remove from context rules
+        /// Paths or glob patterns to remove from context rules, the URLs or names of pages, or
+        /// <path>::<symbol> of symbols
         #[arg(required = true)]
         paths: Vec<String>,
     },
     /// Remove all rules
     Clear,
+    /// Export the context rules and pages added during this session to a file
+    Export {
+        /// Path of the file to write
+        path: String,
+        /// Overwrite the file if it already exists
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Add the context rules and pages from a file written with /context export
+    Import {
+        /// Path of the file to read
+        path: String,
+    },
+    /// Show or tune how the context size limit is split between agent resources, session files
+    /// and hook output
+    Budget {
+        /// Changes to make to the budget before showing it
+        #[command(subcommand)]
+        subcommand: Option<BudgetSubcommand>,
+    },
+    /// Show or change whether context the model hasn't used for a while is removed automatically
+    AutoEvict {
+        /// Turn automatic removal on or off
+        #[arg(value_enum)]
+        mode: Option<AutoEvictMode>,
+        /// Number of turns context may go unused before it is proposed for removal, or removed
+        #[arg(long)]
+        after: Option<usize>,
+    },
     #[command(hide = true)]
     /// Display information about agent format hooks (deprecated)
     Hooks,
 }
 
+/// Whether unused context is removed automatically
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum AutoEvictMode {
+    /// Remove unused context automatically
+    On,
+    /// Only propose to remove unused context
+    Off,
+}
+
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Subcommand)]
+/// Subcommands for tuning the context budget
+pub enum BudgetSubcommand {
+    /// Set the relative share of the context size limit given to a source of context
+    Set {
+        /// The source of context to set the share of
+        #[arg(value_enum)]
+        source: ContextSource,
+        /// The share given to the source, relative to the shares of the other sources
+        share: u32,
+    },
+    /// Go back to the default shares
+    Reset,
+}
+
 impl ContextSubcommand {
     pub async fn execute(self, os: &Os, session: &mut ChatSession) -> Result<ChatState, ChatError> {
         let Some(context_manager) = &mut session.conversation.context_manager else {
@@ -94,7 +170,11 @@ impl ContextSubcommand {
                     session.stderr,
                     style::SetAttribute(Attribute::Bold),
                     StyledText::emphasis_fg(),
-                    style::Print(format!("👤 Agent ({}):\n", context_manager.current_profile)),
+                    style::Print(format!(
+                        "{}Agent ({}):\n",
+                        accessible::symbol("👤 ", ""),
+                        context_manager.current_profile
+                    )),
                     StyledText::reset_attributes(),
                 )?;
 
@@ -108,10 +188,7 @@ impl ContextSubcommand {
                 } else {
                     for path in &agent_owned_list {
                         execute!(session.stderr, style::Print(format!("    {} ", path.get_path_as_str())))?;
-                        if let Ok(context_files) = context_manager
-                            .get_context_files_by_path(os, path.get_path_as_str())
-                            .await
-                        {
+                        if let Ok(context_files) = context_manager.get_context_files_by_path(os, path).await {
                             execute!(
                                 session.stderr,
                                 StyledText::success_fg(),
@@ -134,11 +211,16 @@ impl ContextSubcommand {
                     session.stderr,
                     style::SetAttribute(Attribute::Bold),
                     StyledText::emphasis_fg(),
-                    style::Print("💬 Session (temporary):\n"),
+                    style::Print(accessible::symbol("💬 ", "")),
+                    style::Print("Session (temporary):\n"),
                     StyledText::reset_attributes(),
                 )?;
 
-                if session_owned_list.is_empty() {
+                if session_owned_list.is_empty()
+                    && context_manager.urls.is_empty()
+                    && context_manager.symbols.is_empty()
+                    && context_manager.mcp_resources.is_empty()
+                {
                     execute!(
                         session.stderr,
                         StyledText::secondary_fg(),
@@ -148,10 +230,7 @@ impl ContextSubcommand {
                 } else {
                     for path in &session_owned_list {
                         execute!(session.stderr, style::Print(format!("    {} ", path.get_path_as_str())))?;
-                        if let Ok(context_files) = context_manager
-                            .get_context_files_by_path(os, path.get_path_as_str())
-                            .await
-                        {
+                        if let Ok(context_files) = context_manager.get_context_files_by_path(os, path).await {
                             execute!(
                                 session.stderr,
                                 StyledText::success_fg(),
@@ -167,6 +246,52 @@ impl ContextSubcommand {
                         }
                         execute!(session.stderr, style::Print("\n"))?;
                     }
+                    for url in &context_manager.urls {
+                        execute!(
+                            session.stderr,
+                            style::Print(format!("    {} ", url.url)),
+                            StyledText::secondary_fg(),
+                            style::Print(format!(
+                                "({}, fetched {})\n",
+                                url.name,
+                                url.fetched_at.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M")
+                            )),
+                            StyledText::reset(),
+                        )?;
+                        profile_context_files.insert((url.entry_name(), url.content.clone(), true));
+                    }
+                    for symbol in &context_manager.symbols {
+                        execute!(session.stderr, style::Print(format!("    {} ", symbol.spec())))?;
+                        match symbol.extract(os).await {
+                            Ok((name, content)) => {
+                                execute!(
+                                    session.stderr,
+                                    StyledText::success_fg(),
+                                    style::Print("(symbol)\n"),
+                                    StyledText::reset(),
+                                )?;
+                                profile_context_files.insert((name, content, true));
+                            },
+                            Err(e) => {
+                                execute!(
+                                    session.stderr,
+                                    StyledText::warning_fg(),
+                                    style::Print(format!("({e})\n")),
+                                    StyledText::reset(),
+                                )?;
+                            },
+                        }
+                    }
+                    for (uri, text) in &context_manager.mcp_resources {
+                        execute!(
+                            session.stderr,
+                            style::Print(format!("    {uri} ")),
+                            StyledText::secondary_fg(),
+                            style::Print("(MCP resource)\n"),
+                            StyledText::reset(),
+                        )?;
+                        profile_context_files.insert((uri.clone(), text.clone(), true));
+                    }
                     execute!(session.stderr, style::Print("\n"))?;
                 }
 
@@ -198,10 +323,14 @@ impl ContextSubcommand {
 
                     for (filename, content, is_temporary) in &profile_context_files {
                         let est_tokens = TokenCounter::count_tokens(content);
-                        let icon = if *is_temporary { "💬" } else { "👤" };
+                        let icon = if *is_temporary {
+                            accessible::symbol("💬 ", "Session: ")
+                        } else {
+                            accessible::symbol("👤 ", "Agent: ")
+                        };
                         execute!(
                             session.stderr,
-                            style::Print(format!("{} {} ", icon, filename)),
+                            style::Print(format!("{}{} ", icon, filename)),
                             StyledText::secondary_fg(),
                             style::Print(format!("(~{} tkns)\n", est_tokens)),
                             StyledText::reset(),
@@ -221,11 +350,13 @@ impl ContextSubcommand {
                     }
 
                     let context_files_max_size = calc_max_context_files_size(session.conversation.model_info.as_ref());
-                    let mut files_as_vec = profile_context_files
-                        .iter()
-                        .map(|(path, content, _)| (path.clone(), content.clone()))
-                        .collect::<Vec<_>>();
-                    let dropped_files = drop_matched_context_files(&mut files_as_vec, context_files_max_size).ok();
+                    // The hook output of the last request takes its share of the budget as well
+                    let mut hook_outputs = context_manager.hook_outputs.clone();
+                    let dropped_files = context_manager
+                        .collect_context_files_with_limit(os, &mut hook_outputs.iter_mut().collect::<Vec<_>>())
+                        .await
+                        .ok()
+                        .map(|(_, dropped, _)| dropped);
 
                     execute!(
                         session.stderr,
@@ -245,9 +376,7 @@ impl ContextSubcommand {
                             )?;
                             let total_files = dropped_files.len();
 
-                            let truncated_dropped_files = &dropped_files[..10];
-
-                            for (filename, content) in truncated_dropped_files {
+                            for (filename, content) in dropped_files.iter().take(10) {
                                 let est_tokens = TokenCounter::count_tokens(content);
                                 execute!(
                                     session.stderr,
@@ -311,6 +440,71 @@ impl ContextSubcommand {
                     )?;
                 },
             },
+            Self::AddUrl { url, name } => {
+                execute!(
+                    session.stderr,
+                    StyledText::secondary_fg(),
+                    style::Print(format!("\nFetching {url}...\n")),
+                    StyledText::reset(),
+                )?;
+                match fetch_url(&url, name).await {
+                    Ok(url_context) => {
+                        let tokens = TokenCounter::count_tokens(&url_context.content);
+                        let name = url_context.name.clone();
+                        let verb = match context_manager.add_url(url_context) {
+                            true => "Refreshed",
+                            false => "Added",
+                        };
+                        execute!(
+                            session.stderr,
+                            StyledText::success_fg(),
+                            style::Print(format!("{verb} '{name}' in context (~{tokens} tkns).\n")),
+                            style::Print("Note: Context modifications via slash command is temporary.\n\n"),
+                            StyledText::reset(),
+                        )?;
+                    },
+                    Err(e) => {
+                        execute!(
+                            session.stderr,
+                            StyledText::error_fg(),
+                            style::Print(format!("Error: Failed to fetch {url}: {e}\n\n")),
+                            StyledText::reset(),
+                        )?;
+                    },
+                }
+            },
+            Self::AddSymbol { symbol } => {
+                let result = match SymbolContext::parse(&symbol) {
+                    Ok(symbol) => match symbol.extract(os).await {
+                        Ok((name, content)) => context_manager.add_symbol(symbol).map(|_| (name, content)),
+                        Err(e) => Err(e),
+                    },
+                    Err(e) => Err(e),
+                };
+                match result {
+                    Ok((name, content)) => {
+                        execute!(
+                            session.stderr,
+                            StyledText::success_fg(),
+                            style::Print(format!(
+                                "\nAdded {} to context (~{} tkns).\n",
+                                name,
+                                TokenCounter::count_tokens(&content)
+                            )),
+                            style::Print("Note: Context modifications via slash command is temporary.\n\n"),
+                            StyledText::reset(),
+                        )?;
+                    },
+                    Err(e) => {
+                        execute!(
+                            session.stderr,
+                            StyledText::error_fg(),
+                            style::Print(format!("\nError: {}\n\n", e)),
+                            StyledText::reset(),
+                        )?;
+                    },
+                }
+            },
             Self::Remove { paths } => match context_manager.remove_paths(paths.clone()) {
                 Ok(_) => {
                     execute!(
@@ -340,6 +534,198 @@ impl ContextSubcommand {
                     StyledText::reset(),
                 )?;
             },
+            Self::Export { path, force } => {
+                let snapshot = context_manager.snapshot();
+                let result = match os.fs.exists(&path) && !force {
+                    true => Err(eyre::eyre!("File already exists. To overwrite, use -f or --force")),
+                    false => match serde_json::to_string_pretty(&snapshot) {
+                        Ok(contents) => os.fs.write(&path, contents).await.map_err(Into::into),
+                        Err(e) => Err(e.into()),
+                    },
+                };
+                match result {
+                    Ok(()) => {
+                        execute!(
+                            session.stderr,
+                            StyledText::success_fg(),
+                            style::Print(format!(
+                                "\nExported {} path(s), {} page(s) and {} symbol(s) to {}\n\n",
+                                snapshot.paths.len(),
+                                snapshot.urls.len(),
+                                snapshot.symbols.len(),
+                                path
+                            )),
+                            StyledText::reset(),
+                        )?;
+                    },
+                    Err(e) => {
+                        execute!(
+                            session.stderr,
+                            StyledText::error_fg(),
+                            style::Print(format!("\nError: Failed to export context to {}: {}\n\n", path, e)),
+                            StyledText::reset(),
+                        )?;
+                    },
+                }
+            },
+            Self::Import { path } => {
+                let snapshot = match os.fs.read_to_string(&path).await {
+                    Ok(contents) => serde_json::from_str::<ContextSnapshot>(&contents).map_err(eyre::Report::from),
+                    Err(e) => Err(e.into()),
+                };
+                match snapshot {
+                    Ok(snapshot) => {
+                        let added = context_manager.restore(snapshot);
+                        execute!(
+                            session.stderr,
+                            StyledText::success_fg(),
+                            style::Print(format!("\nImported {} context entries from {}.\n", added, path)),
+                            style::Print("Note: Context modifications via slash command is temporary.\n\n"),
+                            StyledText::reset(),
+                        )?;
+                    },
+                    Err(e) => {
+                        execute!(
+                            session.stderr,
+                            StyledText::error_fg(),
+                            style::Print(format!("\nError: Failed to import context from {}: {}\n\n", path, e)),
+                            StyledText::reset(),
+                        )?;
+                    },
+                }
+            },
+            Self::Budget { subcommand } => {
+                match subcommand {
+                    Some(BudgetSubcommand::Set { source, share }) => {
+                        context_manager.budget.set_share(source, share);
+                    },
+                    Some(BudgetSubcommand::Reset) => {
+                        context_manager.budget = Default::default();
+                    },
+                    None => {},
+                }
+
+                let (agent_files, session_files) = context_manager
+                    .get_context_files_by_source(os)
+                    .await
+                    .unwrap_or_default();
+                let size_of = |files: &[(String, String)]| files.iter().map(|(_, c)| c.len()).sum::<usize>();
+                let usage = [
+                    size_of(&agent_files),
+                    size_of(&session_files),
+                    size_of(&context_manager.mcp_resources),
+                    context_manager.hook_outputs.iter().map(String::len).sum(),
+                ];
+                let limit = calc_max_context_files_size(session.conversation.model_info.as_ref());
+                let allocated = context_manager
+                    .budget
+                    .allocate(TokenCounter::token_to_chars(limit), usage);
+
+                execute!(
+                    session.stderr,
+                    style::SetAttribute(Attribute::Bold),
+                    style::Print(format!("\nContext budget (~{limit} tokens in total):\n\n")),
+                    StyledText::reset_attributes(),
+                    StyledText::secondary_fg(),
+                    style::Print(format!(
+                        "  {:<18}{:>8}{:>18}{:>18}\n",
+                        "Source", "Share", "Allocated", "In use"
+                    )),
+                    StyledText::reset(),
+                )?;
+                for (i, source) in ContextSource::ALL.iter().enumerate() {
+                    execute!(
+                        session.stderr,
+                        style::Print(format!(
+                            "  {:<18}{:>7.0}%{:>18}",
+                            source.to_string(),
+                            context_manager.budget.percentage(*source),
+                            format!("~{} tkns", TokenCounter::count_tokens_char_count(allocated[i])),
+                        )),
+                    )?;
+                    let in_use = TokenCounter::count_tokens_char_count(usage[i]);
+                    if usage[i] > allocated[i] {
+                        execute!(session.stderr, StyledText::warning_fg())?;
+                    }
+                    execute!(
+                        session.stderr,
+                        style::Print(format!("{:>18}\n", format!("~{in_use} tkns"))),
+                        StyledText::reset(),
+                    )?;
+                }
+                execute!(
+                    session.stderr,
+                    StyledText::secondary_fg(),
+                    style::Print(
+                        "\nSources using less than their share leave the rest to the others. Sources over their allocation are\ntruncated, largest entries first. Use /context budget set <source> <share> to tune the shares.\n\n"
+                    ),
+                    StyledText::reset(),
+                )?;
+            },
+            Self::AutoEvict { mode, after } => {
+                let tracker = &mut context_manager.stale_context;
+                if let Some(mode) = mode {
+                    tracker.auto_evict = mode == AutoEvictMode::On;
+                }
+                if let Some(after) = after {
+                    tracker.evict_after_turns = after.max(1);
+                }
+
+                execute!(
+                    session.stderr,
+                    style::Print(format!(
+                        "\nAuto-evict is {}: context unused for {} turns is {}.\n",
+                        if tracker.auto_evict { "on" } else { "off" },
+                        tracker.evict_after_turns,
+                        if tracker.auto_evict {
+                            "removed automatically"
+                        } else {
+                            "proposed for removal"
+                        }
+                    )),
+                )?;
+
+                let mut entries = context_manager
+                    .paths
+                    .iter()
+                    .filter(|p| matches!(p, ContextFilePath::Session(_)))
+                    .map(|p| p.get_path_as_str().to_string())
+                    .collect::<Vec<_>>();
+                entries.extend(context_manager.urls.iter().map(|u| u.url.clone()));
+                entries.extend(context_manager.symbols.iter().map(|s| s.spec()));
+                if !entries.is_empty() {
+                    execute!(session.stderr, style::Print("\nSession context:\n"))?;
+                    for entry in entries {
+                        let unused = match context_manager.stale_context.turns_unused(&entry) {
+                            Some(0) => "used on the last turn".to_string(),
+                            Some(1) => "unused for 1 turn".to_string(),
+                            Some(turns) => format!("unused for {turns} turns"),
+                            None => "not sent yet".to_string(),
+                        };
+                        execute!(
+                            session.stderr,
+                            style::Print(format!("    {entry} ")),
+                            StyledText::secondary_fg(),
+                            style::Print(format!("({unused})\n")),
+                            StyledText::reset(),
+                        )?;
+                    }
+                }
+
+                let evicted = &context_manager.stale_context.evicted;
+                if !evicted.is_empty() {
+                    execute!(session.stderr, style::Print("\nRemoved automatically:\n"))?;
+                    for entry in evicted {
+                        execute!(
+                            session.stderr,
+                            StyledText::secondary_fg(),
+                            style::Print(format!("    {entry}\n")),
+                            StyledText::reset(),
+                        )?;
+                    }
+                }
+                execute!(session.stderr, style::Print("\n"))?;
+            },
             Self::Hooks => {
                 execute!(
                     session.stderr,
@@ -364,8 +750,14 @@ impl ContextSubcommand {
         match self {
             ContextSubcommand::Show { .. } => "show",
             ContextSubcommand::Add { .. } => "add",
+            ContextSubcommand::AddUrl { .. } => "add-url",
+            ContextSubcommand::AddSymbol { .. } => "add-symbol",
+            ContextSubcommand::AutoEvict { .. } => "auto-evict",
             ContextSubcommand::Remove { .. } => "remove",
             ContextSubcommand::Clear => "clear",
+            ContextSubcommand::Export { .. } => "export",
+            ContextSubcommand::Import { .. } => "import",
+            ContextSubcommand::Budget { .. } => "budget",
             ContextSubcommand::Hooks => "hooks",
         }
     }
@@ -0,0 +1,142 @@
+use clap::Subcommand;
+use crossterm::{
+    execute,
+    style,
+};
+
+use crate::cli::DEFAULT_AGENT_NAME;
+use crate::cli::chat::tools::delegate::{
+    Delegate,
+    launch_map,
+};
+use crate::cli::chat::{
+    ChatError,
+    ChatSession,
+    ChatState,
+};
+use crate::os::Os;
+use crate::theme::StyledText;
+
+/// Subcommands of `/delegate`, orchestrating several background tasks at once
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Subcommand)]
+#[command(
+    before_long_help = "/delegate map applies a task to every file matching a glob, splitting the files into shards
+each handled by a background task. At most chat.delegateMaxConcurrency tasks run at a time.
+Once they all finish, their changes are merged and another task validates the result as a whole.
+Follow the tasks with /agents.
+
+Example: /delegate map --glob \"src/**/*.rs\" add the Apache 2.0 license header"
+)]
+pub enum DelegateSubcommand {
+    /// Apply a task to every file matching a glob, split across background tasks
+    Map {
+        /// Files to apply the task to, relative to the current directory, e.g. "src/**/*.rs"
+        #[arg(long)]
+        glob: String,
+        /// Number of tasks to split the files into. Defaults to chat.delegateMaxConcurrency
+        #[arg(long, value_parser = clap::value_parser!(u16).range(1..))]
+        shards: Option<u16>,
+        /// Agent running the tasks. Defaults to the default agent, trusted with all tools
+        #[arg(long)]
+        agent: Option<String>,
+        /// Task applied to every file
+        #[arg(required = true, trailing_var_arg = true)]
+        prompt: Vec<String>,
+    },
+}
+
+impl DelegateSubcommand {
+    pub async fn execute(self, os: &mut Os, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        if !Delegate::is_enabled(os) {
+            execute!(
+                session.stderr,
+                StyledText::secondary_fg(),
+                style::Print("The delegate tool is disabled. Enable it with /experiment\n"),
+                StyledText::reset(),
+            )?;
+            return Ok(ChatState::PromptUser {
+                skip_printing_tools: true,
+            });
+        }
+
+        let result = match &self {
+            Self::Map {
+                glob,
+                shards,
+                agent,
+                prompt,
+            } => {
+                launch_map(
+                    os,
+                    agent.as_deref().unwrap_or(DEFAULT_AGENT_NAME),
+                    &session.conversation.agents,
+                    glob,
+                    &prompt.join(" "),
+                    shards.map(usize::from),
+                )
+                .await
+            },
+        };
+        match result {
+            Ok(output) => execute!(session.stderr, style::Print(format!("{output}\n")))?,
+            Err(err) => execute!(
+                session.stderr,
+                StyledText::error_fg(),
+                style::Print(format!("{err}\n")),
+                StyledText::reset(),
+            )?,
+        }
+
+        Ok(ChatState::PromptUser {
+            skip_printing_tools: true,
+        })
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Map { .. } => "map",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+
+    #[derive(Parser, Debug)]
+    #[command(name = "test")]
+    struct TestCli {
+        #[command(subcommand)]
+        delegate: DelegateSubcommand,
+    }
+
+    #[test]
+    fn test_map_parsing() {
+        let args = TestCli::try_parse_from([
+            "test",
+            "map",
+            "--glob",
+            "src/**/*.rs",
+            "--shards",
+            "4",
+            "add",
+            "the",
+            "license",
+            "header",
+        ])
+        .unwrap();
+        assert_eq!(args.delegate, DelegateSubcommand::Map {
+            glob: "src/**/*.rs".to_string(),
+            shards: Some(4),
+            agent: None,
+            prompt: ["add", "the", "license", "header"].map(str::to_string).to_vec(),
+        });
+
+        assert!(TestCli::try_parse_from(["test", "map", "add", "headers"]).is_err());
+        assert!(TestCli::try_parse_from(["test", "map", "--glob", "*.rs", "--shards", "0", "add"]).is_err());
+        assert!(TestCli::try_parse_from(["test", "map", "--glob", "*.rs"]).is_err());
+    }
+}
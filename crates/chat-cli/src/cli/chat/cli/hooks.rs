@@ -1,21 +1,33 @@
-use std::collections::HashMap;
+use std::collections::{
+    HashMap,
+    HashSet,
+};
 use std::io::Write;
+use std::path::{
+    Path,
+    PathBuf,
+};
 use std::process::Stdio;
 use std::time::{
     Duration,
     Instant,
+    SystemTime,
 };
 
+use agent::hooks::{
+    HookJsonOutput,
+    parse_hook_matcher,
+    resolve_hook_env,
+};
+use agent::task_executor;
+use agent::util::providers::RealProvider;
 use bstr::ByteSlice;
 use clap::Args;
-use crossterm::style::{
-    self,
-    Stylize,
-};
 use crossterm::{
     cursor,
     execute,
     queue,
+    style,
     terminal,
 };
 use eyre::{
@@ -26,9 +38,10 @@ use futures::stream::{
     FuturesUnordered,
     StreamExt,
 };
-use spinners::{
-    Spinner,
-    Spinners,
+use opentelemetry::KeyValue;
+use tracing::{
+    Instrument,
+    debug,
 };
 
 use crate::cli::agent::hook::{
@@ -36,50 +49,80 @@ use crate::cli::agent::hook::{
     HookTrigger,
 };
 use crate::cli::agent::is_mcp_tool_ref;
+use crate::cli::chat::accessible::StatusIndicator;
 use crate::cli::chat::consts::AGENT_FORMAT_HOOKS_DOC_URL;
 use crate::cli::chat::util::truncate_safe;
 use crate::cli::chat::{
     ChatError,
     ChatSession,
     ChatState,
+    accessible,
 };
 use crate::constants::help_text::hooks_long_help;
+use crate::telemetry::otel::{
+    self,
+    Operation,
+};
 use crate::theme::StyledText;
 use crate::util::MCP_SERVER_TOOL_DELIMITER;
 use crate::util::pattern_matching::matches_any_pattern;
 
+/// Exit code given to hooks that failed to execute, e.g. that timed out or could not be spawned
+pub const HOOK_ERROR_EXIT_CODE: i32 = -1;
+
 /// Hook execution result: (exit_code, output)
 /// Output is stdout if exit_code is 0, stderr otherwise.
 pub type HookOutput = (i32, String);
 
-/// Check if a hook matches a tool name based on its matcher pattern
-fn hook_matches_tool(hook: &Hook, tool_name: &str) -> bool {
+/// Types of the notifications given to [HookTrigger::Notification] hooks.
+pub const NOTIFICATION_APPROVAL_NEEDED: &str = "approval_needed";
+pub const NOTIFICATION_TURN_COMPLETE: &str = "turn_complete";
+
+/// Check if a [HookTrigger::Notification] hook matches a type of notification based on its
+/// matcher. Hooks without a matcher match all notifications.
+pub fn hook_matches_notification(hook: &Hook, notification_type: &str) -> bool {
+    hook.matcher
+        .as_deref()
+        .is_none_or(|matcher| matches_any_pattern(&HashSet::from([matcher]), notification_type))
+}
+
+/// Check if a hook matches a tool use based on its matcher.
+///
+/// The matcher is a tool name pattern, optionally followed by conditions on the tool input in
+/// brackets, e.g. `fs_write[path=**/migrations/**]`. See [parse_hook_matcher].
+pub fn hook_matches_tool(hook: &Hook, tool_name: &str, tool_input: &serde_json::Value) -> bool {
     match &hook.matcher {
         None => true, // No matcher means the hook runs for all tools
-        Some(pattern) => {
-            match pattern.as_str() {
-                "*" => true,                               // Wildcard matches all tools
-                "@builtin" => !is_mcp_tool_ref(tool_name), // Built-in tools are not MCP tools
-                _ => {
-                    // If tool_name is MCP, check server pattern first
-                    if is_mcp_tool_ref(tool_name) {
-                        if let Some(server_name) = tool_name
-                            .strip_prefix('@')
-                            .and_then(|s| s.split(MCP_SERVER_TOOL_DELIMITER).next())
-                        {
-                            let server_pattern = format!("@{}", server_name);
-                            if pattern == &server_pattern {
-                                return true;
-                            }
-                        }
-                    }
+        Some(matcher) => {
+            let (pattern, conditions) = parse_hook_matcher(matcher);
+            tool_name_matches(pattern, tool_name) && conditions.iter().all(|c| c.matches(tool_input))
+        },
+    }
+}
 
-                    // Use matches_any_pattern for both MCP and built-in tools
-                    let mut patterns = std::collections::HashSet::new();
-                    patterns.insert(pattern.as_str());
-                    matches_any_pattern(&patterns, tool_name)
-                },
+/// Check if a tool name matches the tool name pattern of a matcher
+fn tool_name_matches(pattern: &str, tool_name: &str) -> bool {
+    match pattern {
+        "*" => true,                               // Wildcard matches all tools
+        "@builtin" => !is_mcp_tool_ref(tool_name), // Built-in tools are not MCP tools
+        _ => {
+            // If tool_name is MCP, check server pattern first
+            if is_mcp_tool_ref(tool_name) {
+                if let Some(server_name) = tool_name
+                    .strip_prefix('@')
+                    .and_then(|s| s.split(MCP_SERVER_TOOL_DELIMITER).next())
+                {
+                    let server_pattern = format!("@{}", server_name);
+                    if pattern == server_pattern {
+                        return true;
+                    }
+                }
             }
+
+            // Use matches_any_pattern for both MCP and built-in tools
+            let mut patterns = std::collections::HashSet::new();
+            patterns.insert(pattern);
+            matches_any_pattern(&patterns, tool_name)
         },
     }
 }
@@ -91,10 +134,24 @@ pub struct ToolContext {
     pub tool_response: Option<serde_json::Value>,
 }
 
+/// Returns the context given by a successful hook: its `additionalContext` if it printed
+/// [HookJsonOutput], its STDOUT otherwise.
+pub fn hook_context(output: &str) -> Option<String> {
+    let context = match HookJsonOutput::parse(output) {
+        Some(json) if json.is_block() => None,
+        Some(json) => json.additional_context,
+        None => Some(output.to_string()),
+    };
+    context.filter(|c| !c.trim().is_empty())
+}
+
 #[derive(Debug, Clone)]
 pub struct CachedHook {
     output: String,
     expiry: Option<Instant>,
+    /// Files referenced by the hook command, with their modification time when the hook started.
+    /// The cached output is stale once any of them is modified, created or deleted.
+    referenced_files: Vec<(PathBuf, Option<SystemTime>)>,
 }
 
 /// Maps a hook name to a [`CachedHook`]
@@ -108,13 +165,16 @@ impl HookExecutor {
         Self { cache: HashMap::new() }
     }
 
-    /// Run and cache [`Hook`]s. Any hooks that are already cached will be returned without
-    /// executing. Hooks that fail to execute will not be returned. Returned hook order is
-    /// undefined.
+    /// Run and cache [`Hook`]s concurrently. Any hooks that are already cached will be returned
+    /// without executing. Hooks that fail to execute, e.g. on timeout, are returned with the exit
+    /// code [`HOOK_ERROR_EXIT_CODE`] and the error as output. Returned hook order is undefined.
     ///
     /// If `updates` is `Some`, progress on hook execution will be written to it.
     /// Errors encountered with write operations to `updates` are ignored.
     ///
+    /// The fields of `event_input`, if any, are added to the hook event, e.g. the error given to
+    /// [`HookTrigger::OnError`] hooks.
+    ///
     /// Note: [`HookTrigger::AgentSpawn`] hooks never leave the cache.
     pub async fn run_hooks(
         &mut self,
@@ -123,8 +183,10 @@ impl HookExecutor {
         cwd: &str,
         prompt: Option<&str>,
         tool_context: Option<ToolContext>,
+        event_input: Option<&serde_json::Value>,
     ) -> Result<Vec<((HookTrigger, Hook), HookOutput)>, ChatError> {
         let mut cached = vec![];
+        let mut referenced_files = HashMap::new();
         let mut futures = FuturesUnordered::new();
         for hook in hooks
             .into_iter()
@@ -132,17 +194,27 @@ impl HookExecutor {
         {
             // Filter hooks by tool matcher
             if let Some(tool_ctx) = &tool_context {
-                if !hook_matches_tool(&hook.1, &tool_ctx.tool_name) {
+                if !hook_matches_tool(&hook.1, &tool_ctx.tool_name, &tool_ctx.tool_input) {
                     continue; // Skip this hook - doesn't match tool
                 }
             }
 
+            if !hook.1.blocking {
+                Self::spawn_non_blocking_hook(hook, cwd, prompt, tool_context.clone(), event_input);
+                continue;
+            }
+
             if let Some(cache) = self.get_cache(&hook) {
                 // Note: we only cache successful hook run. hence always using 0 as exit code for cached hook
                 cached.push((hook.clone(), (0, cache)));
                 continue;
             }
-            futures.push(self.run_hook(hook, cwd, prompt, tool_context.clone()));
+            let files = file_modified_times(task_executor::referenced_files(
+                &hook.1.command,
+                &hook_cwd(&hook.1, cwd),
+            ));
+            referenced_files.insert(hook.clone(), files);
+            futures.push(Self::run_hook(hook, cwd, prompt, tool_context.clone(), event_input));
         }
 
         let mut complete = 0; // number of hooks that are run successfully with exit code 0
@@ -151,13 +223,13 @@ impl HookExecutor {
         let spinner_text = |complete: usize, total: usize| {
             format!(
                 "{} of {} hooks finished",
-                complete.to_string().blue(),
-                total.to_string().blue(),
+                StyledText::info(&complete.to_string()),
+                StyledText::info(&total.to_string()),
             )
         };
 
         if total != 0 {
-            spinner = Some(Spinner::new(Spinners::Dots12, spinner_text(complete, total)));
+            spinner = Some(StatusIndicator::new(spinner_text(complete, total)));
         }
 
         // Process results as they complete
@@ -177,55 +249,57 @@ impl HookExecutor {
                 )?;
             }
 
-            if let Err(err) = &result {
-                queue!(
-                    output,
-                    StyledText::error_fg(),
-                    style::Print("✗ "),
-                    StyledText::info_fg(),
-                    style::Print(&hook.1.command),
-                    StyledText::reset(),
-                    style::Print(" failed after "),
-                    StyledText::warning_fg(),
-                    style::Print(format!("{:.2} s", duration.as_secs_f32())),
-                    StyledText::reset(),
-                    style::Print(format!(": {}\n", err)),
-                )?;
-            }
-
             // Process results regardless of output enabled
-            if let Ok((exit_code, hook_output)) = &result {
-                // Print warning if exit code is not 0
-                if *exit_code != 0 {
+            match result {
+                Err(err) => {
                     queue!(
                         output,
                         StyledText::error_fg(),
-                        style::Print("✗ "),
-                        StyledText::reset(),
-                        style::Print(format!("{} \"", hook.0)),
+                        style::Print(accessible::symbol("✗ ", "Failed: ")),
+                        StyledText::info_fg(),
                         style::Print(&hook.1.command),
-                        style::Print("\""),
-                        StyledText::error_fg(),
-                        style::Print(format!(
-                            " failed with exit code: {}, stderr: {})\n",
-                            exit_code,
-                            hook_output.trim_end()
-                        )),
                         StyledText::reset(),
+                        style::Print(" failed after "),
+                        StyledText::warning_fg(),
+                        style::Print(format!("{:.2} s", duration.as_secs_f32())),
+                        StyledText::reset(),
+                        style::Print(format!(": {}\n", err)),
                     )?;
-                } else {
-                    complete += 1;
-                }
-                results.push((hook, result.unwrap()));
+                    results.push((hook, (HOOK_ERROR_EXIT_CODE, err.to_string())));
+                },
+                Ok((exit_code, hook_output)) => {
+                    // Print warning if exit code is not 0
+                    if exit_code != 0 {
+                        queue!(
+                            output,
+                            StyledText::error_fg(),
+                            style::Print(accessible::symbol("✗ ", "Failed: ")),
+                            StyledText::reset(),
+                            style::Print(format!("{} \"", hook.0)),
+                            style::Print(&hook.1.command),
+                            style::Print("\""),
+                            StyledText::error_fg(),
+                            style::Print(format!(
+                                " failed with exit code: {}, stderr: {})\n",
+                                exit_code,
+                                hook_output.trim_end()
+                            )),
+                            StyledText::reset(),
+                        )?;
+                    } else {
+                        complete += 1;
+                    }
+                    results.push((hook, (exit_code, hook_output)));
+                },
             }
 
             // Display ending summary or add a new spinner
             // The futures set size decreases each time we process one
             if futures.is_empty() {
                 let symbol = if total == complete {
-                    "✓".to_string().green()
+                    StyledText::success(accessible::symbol("✓", "Done:"))
                 } else {
-                    "✗".to_string().red()
+                    StyledText::error(accessible::symbol("✗", "Failed:"))
                 };
 
                 queue!(
@@ -237,7 +311,7 @@ impl HookExecutor {
                     StyledText::reset(),
                 )?;
             } else {
-                spinner = Some(Spinner::new(Spinners::Dots, spinner_text(complete, total)));
+                spinner = Some(StatusIndicator::new(spinner_text(complete, total)));
             }
         }
         drop(futures);
@@ -247,14 +321,20 @@ impl HookExecutor {
             if *exit_code != 0 {
                 continue; // Only cache successful hooks
             }
-            self.cache.insert((*trigger, hook.clone()), CachedHook {
+            let key = (*trigger, hook.clone());
+            self.cache.insert(key.clone(), CachedHook {
                 output: output.clone(),
+                referenced_files: referenced_files.remove(&key).unwrap_or_default(),
                 expiry: match trigger {
                     HookTrigger::AgentSpawn => None,
                     HookTrigger::UserPromptSubmit => Some(Instant::now() + Duration::from_secs(hook.cache_ttl_seconds)),
                     HookTrigger::PreToolUse => Some(Instant::now() + Duration::from_secs(hook.cache_ttl_seconds)),
                     HookTrigger::PostToolUse => Some(Instant::now() + Duration::from_secs(hook.cache_ttl_seconds)),
-                    HookTrigger::Stop => Some(Instant::now() + Duration::from_secs(hook.cache_ttl_seconds)),
+                    HookTrigger::Stop
+                    | HookTrigger::PreCompact
+                    | HookTrigger::SessionEnd
+                    | HookTrigger::OnError
+                    | HookTrigger::Notification => Some(Instant::now() + Duration::from_secs(hook.cache_ttl_seconds)),
                 },
             });
         }
@@ -264,61 +344,76 @@ impl HookExecutor {
         Ok(results)
     }
 
-    async fn run_hook(
-        &self,
+    /// Runs a hook in the background, as the ones with `blocking: false` are. Its output is
+    /// ignored and failures are only logged.
+    pub fn spawn_non_blocking_hook(
+        hook: (HookTrigger, Hook),
+        cwd: &str,
+        prompt: Option<&str>,
+        tool_context: Option<ToolContext>,
+        event_input: Option<&serde_json::Value>,
+    ) {
+        let cwd = cwd.to_string();
+        let prompt = prompt.map(str::to_string);
+        let event_input = event_input.cloned();
+        tokio::spawn(async move {
+            let ((trigger, hook), result, duration) =
+                Self::run_hook(hook, &cwd, prompt.as_deref(), tool_context, event_input.as_ref()).await;
+            match result {
+                Ok((0, _)) => debug!(%trigger, command = hook.command, ?duration, "non-blocking hook finished"),
+                Ok((exit_code, output)) => {
+                    debug!(%trigger, command = hook.command, exit_code, output, "non-blocking hook failed");
+                },
+                Err(err) => debug!(%trigger, command = hook.command, ?err, "non-blocking hook failed"),
+            }
+        });
+    }
+
+    /// Runs a single hook, returning its output and how long it ran.
+    pub async fn run_hook(
         hook: (HookTrigger, Hook),
         cwd: &str,
         prompt: Option<&str>,
         tool_context: Option<ToolContext>,
+        event_input: Option<&serde_json::Value>,
     ) -> ((HookTrigger, Hook), Result<HookOutput>, Duration) {
         let start_time = Instant::now();
+        let env = match resolve_hook_env(&hook.1.env, &RealProvider).await {
+            Ok(env) => env,
+            Err(err) => return (hook, Err(eyre!(err)), start_time.elapsed()),
+        };
 
         let command = &hook.1.command;
 
         #[cfg(unix)]
-        let mut cmd = tokio::process::Command::new("bash");
-        #[cfg(unix)]
-        let cmd = cmd
-            .arg("-c")
-            .arg(command)
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped());
-
-        #[cfg(windows)]
-        let mut cmd = tokio::process::Command::new("cmd");
+        let (shell, shell_arg) = (hook.1.shell.as_deref().unwrap_or("bash"), "-c");
         #[cfg(windows)]
+        let (shell, shell_arg) = match hook.1.shell.as_deref() {
+            Some(shell) => (shell, "-c"),
+            None => ("cmd", "/C"),
+        };
+        let hook_cwd = hook_cwd(&hook.1, cwd);
+
+        let mut cmd = tokio::process::Command::new(shell);
         let cmd = cmd
-            .arg("/C")
+            .arg(shell_arg)
             .arg(command)
+            .current_dir(hook_cwd)
+            .envs(env)
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
             .stderr(Stdio::piped());
 
         let timeout = Duration::from_millis(hook.1.timeout_ms);
 
-        // Generate hook command input in JSON format
-        let mut hook_input = serde_json::json!({
-            "hook_event_name": hook.0.to_string(),
-            "cwd": cwd
-        });
-
-        // Set USER_PROMPT environment variable and add to JSON input if provided
+        // Set USER_PROMPT environment variable if provided
         if let Some(prompt) = prompt {
             // Sanitize the prompt to avoid issues with special characters
             let sanitized_prompt = sanitize_user_prompt(prompt);
             cmd.env("USER_PROMPT", sanitized_prompt);
-            hook_input["prompt"] = serde_json::Value::String(prompt.to_string());
         }
 
-        // ToolUse specific input
-        if let Some(tool_ctx) = tool_context {
-            hook_input["tool_name"] = serde_json::Value::String(tool_ctx.tool_name);
-            hook_input["tool_input"] = tool_ctx.tool_input;
-            if let Some(response) = tool_ctx.tool_response {
-                hook_input["tool_response"] = response;
-            }
-        }
+        let hook_input = hook_event(hook.0, cwd, prompt, tool_context, event_input);
         let json_input = serde_json::to_string(&hook_input).unwrap_or_default();
 
         // Build a future for hook command w/ the JSON input passed in through STDIN
@@ -334,7 +429,13 @@ impl HookExecutor {
         };
 
         // Run with timeout
-        let result = match tokio::time::timeout(timeout, command_future).await {
+        let span = tracing::info_span!(
+            target: otel::SPAN_TARGET,
+            "hook",
+            trigger = %hook.0,
+            program = command_program(&hook.1.command)
+        );
+        let result = match tokio::time::timeout(timeout, command_future).instrument(span).await {
             Ok(Ok(output)) => {
                 let exit_code = output.status.code().unwrap_or(-1);
                 let raw_output = if exit_code == 0 {
@@ -356,6 +457,9 @@ impl HookExecutor {
             Ok(Err(err)) => Err(eyre!("failed to execute command: {}", err)),
             Err(_) => Err(eyre!("command timed out after {} ms", timeout.as_millis())),
         };
+        otel::record(Operation::Hook, start_time.elapsed(), matches!(result, Ok((0, _))), &[
+            KeyValue::new("trigger", hook.0.to_string()),
+        ]);
 
         (hook, result, start_time.elapsed())
     }
@@ -363,19 +467,71 @@ impl HookExecutor {
     /// Will return a cached hook's output if it exists and isn't expired.
     fn get_cache(&self, hook: &(HookTrigger, Hook)) -> Option<String> {
         self.cache.get(hook).and_then(|o| {
-            if let Some(expiry) = o.expiry {
-                if Instant::now() < expiry {
-                    Some(o.output.clone())
-                } else {
-                    None
-                }
-            } else {
-                Some(o.output.clone())
-            }
+            let expired = o.expiry.is_some_and(|expiry| Instant::now() >= expiry);
+            let files_changed = o
+                .referenced_files
+                .iter()
+                .any(|(path, modified)| file_modified_time(path) != *modified);
+            (!expired && !files_changed).then(|| o.output.clone())
         })
     }
 }
 
+/// Working directory of a hook: the `cwd` of the hook relative to the current directory.
+fn hook_cwd(hook: &Hook, cwd: &str) -> PathBuf {
+    match &hook.cwd {
+        Some(dir) => Path::new(cwd).join(shellexpand::tilde(dir).as_ref()),
+        None => PathBuf::from(cwd),
+    }
+}
+
+fn file_modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+fn file_modified_times(paths: Vec<PathBuf>) -> Vec<(PathBuf, Option<SystemTime>)> {
+    paths
+        .into_iter()
+        .map(|path| {
+            let modified = file_modified_time(&path);
+            (path, modified)
+        })
+        .collect()
+}
+
+/// Builds the hook event given to hooks in JSON format through STDIN.
+pub fn hook_event(
+    trigger: HookTrigger,
+    cwd: &str,
+    prompt: Option<&str>,
+    tool_context: Option<ToolContext>,
+    event_input: Option<&serde_json::Value>,
+) -> serde_json::Value {
+    let mut hook_input = serde_json::json!({
+        "hook_event_name": trigger.to_string(),
+        "cwd": cwd
+    });
+
+    if let Some(prompt) = prompt {
+        hook_input["prompt"] = serde_json::Value::String(prompt.to_string());
+    }
+
+    // ToolUse specific input
+    if let Some(tool_ctx) = tool_context {
+        hook_input["tool_name"] = serde_json::Value::String(tool_ctx.tool_name);
+        hook_input["tool_input"] = tool_ctx.tool_input;
+        if let Some(response) = tool_ctx.tool_response {
+            hook_input["tool_response"] = response;
+        }
+    }
+    if let Some(serde_json::Value::Object(fields)) = event_input {
+        for (key, value) in fields {
+            hook_input[key] = value.clone();
+        }
+    }
+    hook_input
+}
+
 /// Sanitizes a string value to be used as an environment variable
 fn sanitize_user_prompt(input: &str) -> String {
     let truncated = truncate_safe(input, 4096);
@@ -407,7 +563,10 @@ impl HooksArgs {
                 true => writeln!(&mut out, "<none>")?,
                 false => {
                     for hook in hooks {
-                        writeln!(&mut out, "  - {}", hook.command)?;
+                        match hook.blocking {
+                            true => writeln!(&mut out, "  - {}", hook.command)?,
+                            false => writeln!(&mut out, "  - {} (non-blocking)", hook.command)?,
+                        }
                     }
                 },
             }
@@ -434,6 +593,16 @@ impl HooksArgs {
     }
 }
 
+/// Name of the program a hook command runs, for the traces. The rest of the command, which may
+/// hold secrets in its arguments or environment variables, is left out.
+fn command_program(command: &str) -> &str {
+    command
+        .split_whitespace()
+        .find(|word| !word.contains('='))
+        .and_then(|program| program.rsplit(['/', '\\']).next())
+        .unwrap_or_default()
+}
+
 #[cfg(test)]
 mod tests {
     use std::collections::HashMap;
@@ -446,6 +615,17 @@ mod tests {
         HookTrigger,
     };
 
+    #[test]
+    fn test_command_program() {
+        assert_eq!(command_program("./scripts/lint.sh --fix"), "lint.sh");
+        assert_eq!(
+            command_program("TOKEN=secret curl -H 'x: y' https://example.com"),
+            "curl"
+        );
+        assert_eq!(command_program(r"C:\tools\check.exe"), "check.exe");
+        assert_eq!(command_program("  "), "");
+    }
+
     #[test]
     fn test_hook_matches_tool() {
         let hook_no_matcher = Hook {
@@ -454,6 +634,10 @@ mod tests {
             cache_ttl_seconds: 0,
             max_output_size: 1000,
             matcher: None,
+            blocking: true,
+            cwd: None,
+            env: Default::default(),
+            shell: None,
             source: crate::cli::agent::hook::Source::Session,
         };
 
@@ -463,6 +647,10 @@ mod tests {
             cache_ttl_seconds: 0,
             max_output_size: 1000,
             matcher: Some("fs_write".to_string()),
+            blocking: true,
+            cwd: None,
+            env: Default::default(),
+            shell: None,
             source: crate::cli::agent::hook::Source::Session,
         };
 
@@ -472,6 +660,10 @@ mod tests {
             cache_ttl_seconds: 0,
             max_output_size: 1000,
             matcher: Some("fs_*".to_string()),
+            blocking: true,
+            cwd: None,
+            env: Default::default(),
+            shell: None,
             source: crate::cli::agent::hook::Source::Session,
         };
 
@@ -481,6 +673,10 @@ mod tests {
             cache_ttl_seconds: 0,
             max_output_size: 1000,
             matcher: Some("*".to_string()),
+            blocking: true,
+            cwd: None,
+            env: Default::default(),
+            shell: None,
             source: crate::cli::agent::hook::Source::Session,
         };
 
@@ -490,6 +686,10 @@ mod tests {
             cache_ttl_seconds: 0,
             max_output_size: 1000,
             matcher: Some("@builtin".to_string()),
+            blocking: true,
+            cwd: None,
+            env: Default::default(),
+            shell: None,
             source: crate::cli::agent::hook::Source::Session,
         };
 
@@ -499,6 +699,10 @@ mod tests {
             cache_ttl_seconds: 0,
             max_output_size: 1000,
             matcher: Some("@git".to_string()),
+            blocking: true,
+            cwd: None,
+            env: Default::default(),
+            shell: None,
             source: crate::cli::agent::hook::Source::Session,
         };
 
@@ -508,42 +712,173 @@ mod tests {
             cache_ttl_seconds: 0,
             max_output_size: 1000,
             matcher: Some("@git/status".to_string()),
+            blocking: true,
+            cwd: None,
+            env: Default::default(),
+            shell: None,
             source: crate::cli::agent::hook::Source::Session,
         };
 
         // No matcher should match all tools
-        assert!(hook_matches_tool(&hook_no_matcher, "fs_write"));
-        assert!(hook_matches_tool(&hook_no_matcher, "execute_bash"));
-        assert!(hook_matches_tool(&hook_no_matcher, "@git/status"));
+        assert!(hook_matches_tool(
+            &hook_no_matcher,
+            "fs_write",
+            &serde_json::Value::Null
+        ));
+        assert!(hook_matches_tool(
+            &hook_no_matcher,
+            "execute_bash",
+            &serde_json::Value::Null
+        ));
+        assert!(hook_matches_tool(
+            &hook_no_matcher,
+            "@git/status",
+            &serde_json::Value::Null
+        ));
 
         // Exact matcher should only match exact tool
-        assert!(hook_matches_tool(&fs_write_hook, "fs_write"));
-        assert!(!hook_matches_tool(&fs_write_hook, "fs_read"));
+        assert!(hook_matches_tool(&fs_write_hook, "fs_write", &serde_json::Value::Null));
+        assert!(!hook_matches_tool(&fs_write_hook, "fs_read", &serde_json::Value::Null));
 
         // Wildcard matcher should match pattern
-        assert!(hook_matches_tool(&fs_wildcard_hook, "fs_write"));
-        assert!(hook_matches_tool(&fs_wildcard_hook, "fs_read"));
-        assert!(!hook_matches_tool(&fs_wildcard_hook, "execute_bash"));
+        assert!(hook_matches_tool(
+            &fs_wildcard_hook,
+            "fs_write",
+            &serde_json::Value::Null
+        ));
+        assert!(hook_matches_tool(
+            &fs_wildcard_hook,
+            "fs_read",
+            &serde_json::Value::Null
+        ));
+        assert!(!hook_matches_tool(
+            &fs_wildcard_hook,
+            "execute_bash",
+            &serde_json::Value::Null
+        ));
 
         // * should match all tools
-        assert!(hook_matches_tool(&all_tools_hook, "fs_write"));
-        assert!(hook_matches_tool(&all_tools_hook, "execute_bash"));
-        assert!(hook_matches_tool(&all_tools_hook, "@git/status"));
+        assert!(hook_matches_tool(&all_tools_hook, "fs_write", &serde_json::Value::Null));
+        assert!(hook_matches_tool(
+            &all_tools_hook,
+            "execute_bash",
+            &serde_json::Value::Null
+        ));
+        assert!(hook_matches_tool(
+            &all_tools_hook,
+            "@git/status",
+            &serde_json::Value::Null
+        ));
 
         // @builtin should match built-in tools only
-        assert!(hook_matches_tool(&builtin_hook, "fs_write"));
-        assert!(hook_matches_tool(&builtin_hook, "execute_bash"));
-        assert!(!hook_matches_tool(&builtin_hook, "@git/status"));
+        assert!(hook_matches_tool(&builtin_hook, "fs_write", &serde_json::Value::Null));
+        assert!(hook_matches_tool(
+            &builtin_hook,
+            "execute_bash",
+            &serde_json::Value::Null
+        ));
+        assert!(!hook_matches_tool(
+            &builtin_hook,
+            "@git/status",
+            &serde_json::Value::Null
+        ));
 
         // @git should match all git server tools
-        assert!(hook_matches_tool(&git_server_hook, "@git/status"));
-        assert!(!hook_matches_tool(&git_server_hook, "@other/tool"));
-        assert!(!hook_matches_tool(&git_server_hook, "fs_write"));
+        assert!(hook_matches_tool(
+            &git_server_hook,
+            "@git/status",
+            &serde_json::Value::Null
+        ));
+        assert!(!hook_matches_tool(
+            &git_server_hook,
+            "@other/tool",
+            &serde_json::Value::Null
+        ));
+        assert!(!hook_matches_tool(
+            &git_server_hook,
+            "fs_write",
+            &serde_json::Value::Null
+        ));
 
         // @git/status should match exact MCP tool
-        assert!(hook_matches_tool(&git_status_hook, "@git/status"));
-        assert!(!hook_matches_tool(&git_status_hook, "@git/commit"));
-        assert!(!hook_matches_tool(&git_status_hook, "fs_write"));
+        assert!(hook_matches_tool(
+            &git_status_hook,
+            "@git/status",
+            &serde_json::Value::Null
+        ));
+        assert!(!hook_matches_tool(
+            &git_status_hook,
+            "@git/commit",
+            &serde_json::Value::Null
+        ));
+        assert!(!hook_matches_tool(
+            &git_status_hook,
+            "fs_write",
+            &serde_json::Value::Null
+        ));
+    }
+
+    #[test]
+    fn test_hook_matches_tool_input() {
+        let hook = |matcher: &str| Hook {
+            matcher: Some(matcher.to_string()),
+            ..Hook::new("echo test".to_string(), crate::cli::agent::hook::Source::Session)
+        };
+        let write = serde_json::json!({ "command": "create", "path": "/repo/migrations/001_init.sql" });
+        let read = serde_json::json!({
+            "operations": [
+                { "mode": "Line", "path": "src/main.rs" },
+                { "mode": "Line", "path": ".env" }
+            ]
+        });
+
+        let migrations = hook("fs_write[path=**/migrations/**]");
+        assert!(hook_matches_tool(&migrations, "fs_write", &write));
+        assert!(!hook_matches_tool(&migrations, "fs_read", &write));
+        assert!(!hook_matches_tool(
+            &migrations,
+            "fs_write",
+            &serde_json::json!({ "path": "/repo/src/lib.rs" })
+        ));
+
+        // All conditions must match
+        assert!(hook_matches_tool(
+            &hook("fs_*[path=*.sql, command=create]"),
+            "fs_write",
+            &write
+        ));
+        assert!(!hook_matches_tool(
+            &hook("fs_*[path=*.sql, command=append]"),
+            "fs_write",
+            &write
+        ));
+        assert!(hook_matches_tool(
+            &hook("fs_write[command!=append]"),
+            "fs_write",
+            &write
+        ));
+        assert!(hook_matches_tool(&hook("fs_write[summary!=*]"), "fs_write", &write));
+
+        // Arrays match on any element, unless indexed
+        assert!(hook_matches_tool(
+            &hook("fs_read[operations.path=.env]"),
+            "fs_read",
+            &read
+        ));
+        assert!(!hook_matches_tool(
+            &hook("fs_read[operations.0.path=.env]"),
+            "fs_read",
+            &read
+        ));
+        assert!(hook_matches_tool(
+            &hook("fs_read[operations.path=*.{rs,toml}]"),
+            "fs_read",
+            &read
+        ));
+
+        // Conditions that can't be parsed match nothing
+        assert!(!hook_matches_tool(&hook("fs_write[path]"), "fs_write", &write));
+        assert!(!hook_matches_tool(&hook("fs_write[.path=x]"), "fs_write", &write));
     }
 
     #[tokio::test]
@@ -571,6 +906,10 @@ mod tests {
             cache_ttl_seconds: 0,
             max_output_size: 1000,
             matcher: Some("fs_write".to_string()),
+            blocking: true,
+            cwd: None,
+            env: Default::default(),
+            shell: None,
             source: crate::cli::agent::hook::Source::Session,
         };
 
@@ -588,7 +927,7 @@ mod tests {
 
         // Run the hook
         let result = executor
-            .run_hooks(hooks, &mut output, ".", None, Some(tool_context))
+            .run_hooks(hooks, &mut output, ".", None, Some(tool_context), None)
             .await;
 
         assert!(result.is_ok());
@@ -616,6 +955,10 @@ mod tests {
             cache_ttl_seconds: 0,
             max_output_size: 1000,
             matcher: Some("execute_bash".to_string()),
+            blocking: true,
+            cwd: None,
+            env: Default::default(),
+            shell: None,
             source: crate::cli::agent::hook::Source::Session,
         };
 
@@ -636,6 +979,7 @@ mod tests {
                 ".",  // cwd - using current directory for now
                 None, // prompt - no user prompt for this test
                 Some(tool_context),
+                None, // event_input
             )
             .await;
 
@@ -666,6 +1010,10 @@ mod tests {
             cache_ttl_seconds: 0,
             max_output_size: 1000,
             matcher: Some("fs_write".to_string()),
+            blocking: true,
+            cwd: None,
+            env: Default::default(),
+            shell: None,
             source: crate::cli::agent::hook::Source::Session,
         };
 
@@ -687,6 +1035,7 @@ mod tests {
                 ".",  // cwd
                 None, // prompt
                 Some(tool_context),
+                None, // event_input
             )
             .await
             .unwrap();
@@ -717,6 +1066,10 @@ mod tests {
             cache_ttl_seconds: 0,
             max_output_size: 1000,
             matcher: None, // Stop hooks don't use matchers
+            blocking: true,
+            cwd: None,
+            env: Default::default(),
+            shell: None,
             source: crate::cli::agent::hook::Source::Session,
         };
 
@@ -729,6 +1082,7 @@ mod tests {
                 ".",  // cwd
                 None, // prompt
                 None, // tool_context - Stop doesn't have tool context
+                None, // event_input
             )
             .await
             .unwrap();
@@ -742,6 +1096,207 @@ mod tests {
         assert!(hook_output.contains("Turn completed successfully"));
     }
 
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_on_error_hook_event_input() {
+        let mut executor = HookExecutor::new();
+        let mut output = Vec::new();
+
+        let hook = Hook {
+            command: "cat".to_string(),
+            timeout_ms: 5000,
+            cache_ttl_seconds: 0,
+            max_output_size: 1000,
+            matcher: None,
+            blocking: true,
+            cwd: None,
+            env: Default::default(),
+            shell: None,
+            source: crate::cli::agent::hook::Source::Session,
+        };
+        let hooks = HashMap::from([(HookTrigger::OnError, vec![hook])]);
+        let event_input = serde_json::json!({ "error": { "message": "something went wrong" } });
+
+        let results = executor
+            .run_hooks(hooks, &mut output, ".", None, None, Some(&event_input))
+            .await
+            .unwrap();
+
+        let (_, (exit_code, hook_output)) = &results[0];
+        assert_eq!(*exit_code, 0);
+        let event: serde_json::Value = serde_json::from_str(hook_output).unwrap();
+        assert_eq!(event["hook_event_name"], "onError");
+        assert_eq!(event["error"]["message"], "something went wrong");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_hook_cwd_env_and_shell() {
+        let mut executor = HookExecutor::new();
+        let mut output = Vec::new();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("scripts")).unwrap();
+
+        let hook = Hook {
+            shell: Some("sh".to_string()),
+            cwd: Some("scripts".to_string()),
+            env: [("GREETING".to_string(), "hello".to_string())].into(),
+            ..Hook::new(
+                "printf '%s %s' \"$GREETING\" \"$(basename \"$(pwd)\")\"".to_string(),
+                crate::cli::agent::hook::Source::Session,
+            )
+        };
+        let hooks = HashMap::from([(HookTrigger::Stop, vec![hook])]);
+
+        let results = executor
+            .run_hooks(hooks, &mut output, &dir.path().to_string_lossy(), None, None, None)
+            .await
+            .unwrap();
+
+        let (_, (exit_code, hook_output)) = &results[0];
+        assert_eq!(*exit_code, 0);
+        assert_eq!(hook_output, "hello scripts");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_non_blocking_hook() {
+        let mut executor = HookExecutor::new();
+        let mut output = Vec::new();
+        let dir = tempfile::tempdir().unwrap();
+        let marker = dir.path().join("ran");
+
+        let hook = Hook {
+            command: format!("sleep 1; touch {}", marker.display()),
+            timeout_ms: 5000,
+            cache_ttl_seconds: 0,
+            max_output_size: 1000,
+            matcher: None,
+            blocking: false,
+            cwd: None,
+            env: Default::default(),
+            shell: None,
+            source: crate::cli::agent::hook::Source::Session,
+        };
+        let hooks = HashMap::from([(HookTrigger::Stop, vec![hook])]);
+
+        let start = Instant::now();
+        let results = executor
+            .run_hooks(hooks, &mut output, ".", None, None, None)
+            .await
+            .unwrap();
+
+        // Returns without waiting for the hook, which still runs in the background
+        assert!(results.is_empty());
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert!(!marker.exists());
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while !marker.exists() {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        })
+        .await
+        .expect("non-blocking hook should run in the background");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_hooks_run_concurrently() {
+        let mut executor = HookExecutor::new();
+        let mut output = Vec::new();
+        let hook = |command: &str, timeout_ms: u64| Hook {
+            timeout_ms,
+            ..Hook::new(command.to_string(), crate::cli::agent::hook::Source::Session)
+        };
+        let hooks = HashMap::from([(HookTrigger::PreToolUse, vec![
+            hook("sleep 1; echo first", 5000),
+            hook("sleep 1; echo second", 5000),
+            hook("sleep 5", 200),
+        ])]);
+
+        let start = Instant::now();
+        let results = executor
+            .run_hooks(hooks, &mut output, ".", None, None, None)
+            .await
+            .unwrap();
+
+        assert!(start.elapsed() < Duration::from_secs(2));
+        assert_eq!(results.len(), 3);
+        // The hook that timed out is returned as failed
+        let (_, (exit_code, _)) = results.iter().find(|((_, hook), _)| hook.command == "sleep 5").unwrap();
+        assert_eq!(*exit_code, HOOK_ERROR_EXIT_CODE);
+        assert_eq!(results.iter().filter(|(_, (exit_code, _))| *exit_code == 0).count(), 2);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_hook_cache_invalidated_by_referenced_files() {
+        let mut executor = HookExecutor::new();
+        let mut output = Vec::new();
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("status.txt");
+        std::fs::write(&file, "green").unwrap();
+        let hook = Hook {
+            cache_ttl_seconds: 60,
+            ..Hook::new(
+                format!("cat {}", file.display()),
+                crate::cli::agent::hook::Source::Session,
+            )
+        };
+        let hooks = HashMap::from([(HookTrigger::UserPromptSubmit, vec![hook])]);
+
+        executor
+            .run_hooks(hooks.clone(), &mut output, ".", None, None, None)
+            .await
+            .unwrap();
+        std::fs::write(&file, "red").unwrap();
+        let modified = std::fs::metadata(&file).unwrap().modified().unwrap() + Duration::from_secs(1);
+        std::fs::File::options()
+            .write(true)
+            .open(&file)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+        let results = executor
+            .run_hooks(hooks, &mut output, ".", None, None, None)
+            .await
+            .unwrap();
+
+        let (_, (_, hook_output)) = &results[0];
+        assert_eq!(hook_output, "red");
+    }
+
+    #[test]
+    fn test_hook_matches_notification() {
+        let hook = |matcher: Option<&str>| Hook {
+            matcher: matcher.map(str::to_string),
+            ..Hook::new("notify-send".to_string(), crate::cli::agent::hook::Source::Session)
+        };
+
+        assert!(hook_matches_notification(&hook(None), NOTIFICATION_APPROVAL_NEEDED));
+        assert!(hook_matches_notification(&hook(None), NOTIFICATION_TURN_COMPLETE));
+        assert!(hook_matches_notification(&hook(Some("*")), NOTIFICATION_TURN_COMPLETE));
+        assert!(hook_matches_notification(
+            &hook(Some("approval_needed")),
+            NOTIFICATION_APPROVAL_NEEDED
+        ));
+        assert!(!hook_matches_notification(
+            &hook(Some("approval_needed")),
+            NOTIFICATION_TURN_COMPLETE
+        ));
+    }
+
+    #[test]
+    fn test_hook_context() {
+        assert_eq!(hook_context("all good").as_deref(), Some("all good"));
+        assert_eq!(
+            hook_context(r#"{"additionalContext": "use tabs"}"#).as_deref(),
+            Some("use tabs")
+        );
+        assert_eq!(hook_context(r#"{"decision": "allow", "reason": "safe"}"#), None);
+        assert_eq!(hook_context(r#"{"decision": "block", "additionalContext": "x"}"#), None);
+    }
+
     #[test]
     fn test_sanitize_user_prompt_cjk_characters() {
         // Test with CJK characters that would cause panic with naive byte slicing
@@ -0,0 +1,53 @@
+use clap::Args;
+use crossterm::execute;
+use crossterm::style::{
+    self,
+};
+
+use crate::cli::chat::{
+    ChatError,
+    ChatSession,
+    ChatState,
+};
+use crate::theme::StyledText;
+
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Args)]
+#[command(
+    before_long_help = "/important sends a prompt that is never summarized. When the conversation is compacted,
+important prompts are carried forward verbatim alongside the summary instead of being folded
+into it. Use it for requirements and constraints that must hold for the rest of the session.
+
+Example
+• /important Never modify the files under migrations/"
+)]
+/// Command-line arguments for sending a prompt that is kept verbatim when compacting
+pub struct ImportantArgs {
+    /// The prompt to send
+    #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+    pub prompt: Vec<String>,
+}
+
+impl ImportantArgs {
+    pub async fn execute(self, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        let prompt = self.prompt.join(" ");
+        let prompt = prompt.trim();
+        // Commands would be run instead of being sent as the important prompt
+        if prompt.starts_with('/') || prompt.starts_with('!') {
+            execute!(
+                session.stderr,
+                StyledText::error_fg(),
+                style::Print("\nOnly prompts can be marked as important, not commands.\n\n"),
+                StyledText::reset(),
+            )?;
+            return Ok(ChatState::PromptUser {
+                skip_printing_tools: true,
+            });
+        }
+
+        session.conversation.mark_next_prompt_important();
+        Ok(ChatState::HandleInput {
+            input: prompt.to_string(),
+        })
+    }
+}
@@ -13,6 +13,7 @@ use crate::cli::chat::{
     ChatError,
     ChatSession,
     ChatState,
+    accessible,
 };
 use crate::cli::experiment::experiment_manager::{
     ExperimentManager,
@@ -91,7 +92,8 @@ impl KnowledgeSubcommand {
             StyledText::error_fg(),
             style::Print("\nKnowledge tool is disabled. Enable it with: q settings chat.enableKnowledge true\n"),
             StyledText::warning_fg(),
-            style::Print("💡 Your knowledge base data is preserved and will be available when re-enabled.\n\n"),
+            style::Print(accessible::symbol("💡 ", "Tip: ")),
+            style::Print("Your knowledge base data is preserved and will be available when re-enabled.\n\n"),
             StyledText::reset(),
         )
     }
@@ -1,44 +1,62 @@
 use crate::theme::StyledText;
+pub mod agents;
+pub mod attach;
 pub mod changelog;
 pub mod checkpoint;
 pub mod clear;
 pub mod compact;
 pub mod context;
+pub mod delegate;
 pub mod editor;
 pub mod experiment;
 pub mod hooks;
+pub mod important;
 pub mod knowledge;
 pub mod logdump;
 pub mod mcp;
 pub mod model;
 pub mod paste;
+pub mod perf;
+pub mod permissions;
 pub mod persist;
+pub mod plan;
 pub mod profile;
 pub mod prompts;
+pub mod quota;
 pub mod reply;
+pub mod schema;
 pub mod subscribe;
 pub mod tangent;
 pub mod todos;
 pub mod tools;
 pub mod usage;
 
+use agents::AgentsArgs;
+use attach::AttachArgs;
 use changelog::ChangelogArgs;
 use clap::Parser;
 use clear::ClearArgs;
 use compact::CompactArgs;
 use context::ContextSubcommand;
+use delegate::DelegateSubcommand;
 use editor::EditorArgs;
 use experiment::ExperimentArgs;
 use hooks::HooksArgs;
+use important::ImportantArgs;
 use knowledge::KnowledgeSubcommand;
 use logdump::LogdumpArgs;
 use mcp::McpArgs;
 use model::ModelArgs;
 use paste::PasteArgs;
+use perf::PerfArgs;
+use permissions::PermissionsArgs;
 use persist::PersistSubcommand;
+use plan::PlanArgs;
 use profile::AgentSubcommand;
 use prompts::PromptsArgs;
+use quota::QuotaArgs;
 use reply::ReplyArgs;
+use schema::SchemaArgs;
 use tangent::TangentArgs;
 use todos::TodoSubcommand;
 use tools::ToolsArgs;
@@ -68,6 +86,12 @@ pub enum SlashCommand {
     /// Manage agents
     #[command(subcommand)]
     Agent(AgentSubcommand),
+    /// (Beta) View and control the tasks delegated to background agents. Requires the delegate
+    /// experiment
+    Agents(AgentsArgs),
+    /// (Beta) Orchestrate several background tasks at once. Requires the delegate experiment
+    #[command(subcommand)]
+    Delegate(DelegateSubcommand),
     #[command(hide = true)]
     Profile,
     /// Manage context files for the chat session
@@ -84,8 +108,12 @@ pub enum SlashCommand {
     Reply(ReplyArgs),
     /// Summarize the conversation to free up context space
     Compact(CompactArgs),
+    /// Send a prompt that is kept verbatim when the conversation is summarized
+    Important(ImportantArgs),
     /// View tools and permissions
     Tools(ToolsArgs),
+    /// Switch between the permission profiles of the active agent
+    Permissions(PermissionsArgs),
     /// Create a new Github issue or make a feature request
     Issue(issue::IssueArgs),
     /// Create a zip file with logs for support investigation
@@ -99,10 +127,18 @@ pub enum SlashCommand {
     Hooks(HooksArgs),
     /// Show current session's context window usage
     Usage(UsageArgs),
+    /// Show the usage of the monthly allowance of your subscription
+    Quota(QuotaArgs),
+    /// Show the latencies of the requests and tool uses of this session
+    Perf(PerfArgs),
     /// See mcp server loaded
     Mcp(McpArgs),
     /// Select a model for the current conversation session
     Model(ModelArgs),
+    /// Require final answers to match a JSON Schema
+    Schema(SchemaArgs),
+    /// Record tool uses with side effects into a plan for review instead of running them
+    Plan(PlanArgs),
     /// Toggle experimental features
     Experiment(ExperimentArgs),
     /// Upgrade to a Q Developer Pro subscription for increased query limits
@@ -125,8 +161,11 @@ pub enum SlashCommand {
     /// View, manage, and resume to-do lists
     #[command(subcommand)]
     Todos(TodoSubcommand),
-    /// Paste an image from clipboard
+    /// Attach an image from the clipboard to your next message
+    #[command(alias = "paste-image")]
     Paste(PasteArgs),
+    /// Attach images to your next message
+    Attach(AttachArgs),
 }
 
 impl SlashCommand {
@@ -135,6 +174,8 @@ impl SlashCommand {
             Self::Quit => Ok(ChatState::Exit),
             Self::Clear(args) => args.execute(session).await,
             Self::Agent(subcommand) => subcommand.execute(os, session).await,
+            Self::Agents(args) => args.execute(os, session).await,
+            Self::Delegate(subcommand) => subcommand.execute(os, session).await,
             Self::Profile => {
                 use crossterm::{
                     execute,
@@ -163,7 +204,9 @@ impl SlashCommand {
             Self::PromptEditor(args) => args.execute(session).await,
             Self::Reply(args) => args.execute(session).await,
             Self::Compact(args) => args.execute(os, session).await,
+            Self::Important(args) => args.execute(session).await,
             Self::Tools(args) => args.execute(session).await,
+            Self::Permissions(args) => args.execute(os, session).await,
             Self::Issue(args) => {
                 if let Err(err) = args.execute(os).await {
                     return Err(ChatError::Custom(err.to_string().into()));
@@ -178,8 +221,12 @@ impl SlashCommand {
             Self::Prompts(args) => args.execute(os, session).await,
             Self::Hooks(args) => args.execute(session).await,
             Self::Usage(args) => args.execute(os, session).await,
+            Self::Quota(args) => args.execute(os, session).await,
+            Self::Perf(args) => args.execute(session).await,
             Self::Mcp(args) => args.execute(session).await,
             Self::Model(args) => args.execute(os, session).await,
+            Self::Schema(args) => args.execute(os, session).await,
+            Self::Plan(args) => args.execute(os, session).await,
             Self::Experiment(args) => args.execute(os, session).await,
             Self::Subscribe(args) => args.execute(os, session).await,
             Self::Tangent(args) => args.execute(os, session).await,
@@ -195,7 +242,8 @@ impl SlashCommand {
             // },
             Self::Checkpoint(subcommand) => subcommand.execute(os, session).await,
             Self::Todos(subcommand) => subcommand.execute(os, session).await,
-            Self::Paste(args) => args.execute(os, session).await,
+            Self::Paste(args) => args.execute(session).await,
+            Self::Attach(args) => args.execute(session).await,
         }
     }
 
@@ -204,21 +252,29 @@ impl SlashCommand {
             Self::Quit => "quit",
             Self::Clear(_) => "clear",
             Self::Agent(_) => "agent",
+            Self::Agents(_) => "agents",
+            Self::Delegate(_) => "delegate",
             Self::Profile => "profile",
             Self::Context(_) => "context",
             Self::Knowledge(_) => "knowledge",
             Self::PromptEditor(_) => "editor",
             Self::Reply(_) => "reply",
             Self::Compact(_) => "compact",
+            Self::Important(_) => "important",
             Self::Tools(_) => "tools",
+            Self::Permissions(_) => "permissions",
             Self::Issue(_) => "issue",
             Self::Logdump(_) => "logdump",
             Self::Changelog(_) => "changelog",
             Self::Prompts(_) => "prompts",
             Self::Hooks(_) => "hooks",
             Self::Usage(_) => "usage",
+            Self::Quota(_) => "quota",
+            Self::Perf(_) => "perf",
             Self::Mcp(_) => "mcp",
             Self::Model(_) => "model",
+            Self::Schema(_) => "schema",
+            Self::Plan(_) => "plan",
             Self::Experiment(_) => "experiment",
             Self::Subscribe(_) => "subscribe",
             Self::Tangent(_) => "tangent",
@@ -229,6 +285,7 @@ impl SlashCommand {
             Self::Checkpoint(_) => "checkpoint",
             Self::Todos(_) => "todos",
             Self::Paste(_) => "paste",
+            Self::Attach(_) => "attach",
         }
     }
 
@@ -236,9 +293,15 @@ impl SlashCommand {
         match self {
             SlashCommand::Agent(sub) => Some(sub.name()),
             SlashCommand::Context(sub) => Some(sub.name()),
+            SlashCommand::Delegate(sub) => Some(sub.name()),
             SlashCommand::Knowledge(sub) => Some(sub.name()),
             SlashCommand::Tools(arg) => arg.subcommand_name(),
+            SlashCommand::Permissions(arg) => arg.subcommand_name(),
             SlashCommand::Prompts(arg) => arg.subcommand_name(),
+            SlashCommand::Compact(arg) => arg.subcommand_name(),
+            SlashCommand::Model(arg) => arg.subcommand_name(),
+            SlashCommand::Schema(arg) => arg.subcommand_name(),
+            SlashCommand::Plan(arg) => arg.subcommand_name(),
             _ => None,
         }
     }
@@ -1,5 +1,11 @@
-use amzn_codewhisperer_client::types::Model;
-use clap::Args;
+use amzn_codewhisperer_client::types::{
+    InputType,
+    Model,
+};
+use clap::{
+    Args,
+    Subcommand,
+};
 use crossterm::style::{
     self,
 };
@@ -14,6 +20,7 @@ use serde::{
 };
 
 use crate::api_client::Endpoint;
+use crate::api_client::model::InferenceConfig;
 use crate::cli::chat::{
     ChatError,
     ChatSession,
@@ -35,6 +42,9 @@ pub struct ModelInfo {
     /// Size of the model's context window, in tokens
     #[serde(default = "default_context_window")]
     pub context_window_tokens: usize,
+    /// What the model accepts, used to reject unsupported requests up front
+    #[serde(default)]
+    pub capabilities: ModelCapabilities,
 }
 
 impl ModelInfo {
@@ -43,21 +53,30 @@ impl ModelInfo {
             .token_limits()
             .and_then(|limits| limits.max_input_tokens())
             .map_or(default_context_window(), |tokens| tokens as usize);
+        let mut capabilities = ModelCapabilities::from_model_id(model.model_id());
+        // Models without input types listed are assumed to accept images, as they all did
+        if model.supported_input_types.is_some() {
+            capabilities.images = model.supported_input_types().contains(&InputType::Image);
+        }
+        capabilities.prompt_cache = model.supports_prompt_cache().unwrap_or_default();
         Self {
             model_id: model.model_id().to_string(),
             description: model.description.clone(),
             model_name: model.model_name().map(|s| s.to_string()),
             context_window_tokens,
+            capabilities,
         }
     }
 
     /// create a default model with only valid model_id（be compatoble with old stored model data）
     pub fn from_id(model_id: String) -> Self {
+        let capabilities = ModelCapabilities::from_model_id(&model_id);
         Self {
             model_id,
             description: None,
             model_name: None,
             context_window_tokens: 200_000,
+            capabilities,
         }
     }
 
@@ -72,16 +91,180 @@ impl ModelInfo {
     }
 }
 
+/// Features of a model, as listed by the service. The service doesn't list tool use and reasoning
+/// support, so these are derived from the model id.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ModelCapabilities {
+    pub images: bool,
+    pub tool_use: bool,
+    pub reasoning: bool,
+    pub prompt_cache: bool,
+}
+
+impl Default for ModelCapabilities {
+    fn default() -> Self {
+        Self {
+            images: true,
+            tool_use: true,
+            reasoning: false,
+            prompt_cache: false,
+        }
+    }
+}
+
+/// Model families without tool use support, matched against the normalized model id.
+const NO_TOOL_USE_MODELS: &[&str] = &["deepseek-r1"];
+
+/// Model families with extended thinking, matched against the normalized model id.
+const REASONING_MODELS: &[&str] = &[
+    "3-7-sonnet",
+    "sonnet-4",
+    "opus-4",
+    "haiku-4-5",
+    "gpt-oss",
+    "deepseek-r1",
+];
+
+impl ModelCapabilities {
+    pub fn from_model_id(model_id: &str) -> Self {
+        let normalized = model_id.to_lowercase().replace(['.', '_'], "-");
+        Self {
+            tool_use: !NO_TOOL_USE_MODELS.iter().any(|m| normalized.contains(m)),
+            reasoning: REASONING_MODELS.iter().any(|m| normalized.contains(m)),
+            ..Default::default()
+        }
+    }
+
+    /// Describes what the model doesn't support, to warn about when it's selected.
+    pub fn limitations(&self) -> Vec<&'static str> {
+        let mut limitations = Vec::new();
+        if !self.images {
+            limitations.push("Images can't be attached to prompts");
+        }
+        if !self.tool_use {
+            limitations.push("Tools aren't available, the model can only answer with text");
+        }
+        limitations
+    }
+}
+
+/// Error shown wherever sampling parameters are set with the Q Developer backend, which can't
+/// apply them yet.
+pub const INFERENCE_CONFIG_UNSUPPORTED: &str = "The Q Developer backend does not accept model parameters yet.";
+
 /// Command-line arguments for model selection operations
 #[deny(missing_docs)]
 #[derive(Debug, PartialEq, Args)]
-pub struct ModelArgs;
+#[command(before_long_help = "/model selects the model used for the rest of the session.
+
+/model params shows or adjusts the sampling parameters sent with each prompt, e.g. for more
+deterministic or longer responses. Parameters that aren't set use the model's defaults. They
+start from the agent's modelParameters and the --temperature, --max-output-tokens and --top-p
+arguments of q chat. Only the bedrock and openai backends accept them, so with the Q Developer
+backend new values are rejected.
+
+Examples
+• /model params --temperature 0
+• /model params --max-output-tokens 8000 --top-p 0.9
+• /model params --reset")]
+pub struct ModelArgs {
+    #[command(subcommand)]
+    subcommand: Option<ModelSubcommand>,
+}
+
 impl ModelArgs {
     pub async fn execute(self, os: &Os, session: &mut ChatSession) -> Result<ChatState, ChatError> {
-        Ok(select_model(os, session).await?.unwrap_or(ChatState::PromptUser {
-            skip_printing_tools: false,
-        }))
+        match self.subcommand {
+            Some(ModelSubcommand::Params { params, reset }) => {
+                set_model_params(os, session, params, reset)?;
+                Ok(ChatState::PromptUser {
+                    skip_printing_tools: true,
+                })
+            },
+            None if os.client.chat_model().is_some() => {
+                // Other backends have no list of models to pick from
+                queue!(
+                    session.stderr,
+                    StyledText::warning_fg(),
+                    style::Print("\nThe model of this backend is picked with --model when starting q chat.\n\n"),
+                    StyledText::reset(),
+                )?;
+                Ok(ChatState::PromptUser {
+                    skip_printing_tools: false,
+                })
+            },
+            None => Ok(select_model(os, session).await?.unwrap_or(ChatState::PromptUser {
+                skip_printing_tools: false,
+            })),
+        }
+    }
+
+    pub fn subcommand_name(&self) -> Option<&'static str> {
+        self.subcommand.as_ref().map(|subcommand| match subcommand {
+            ModelSubcommand::Params { .. } => "params",
+        })
+    }
+}
+
+/// Subcommands for the model used in the session
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Subcommand)]
+pub enum ModelSubcommand {
+    /// Show or adjust the sampling parameters sent with each prompt
+    Params {
+        /// Parameters to change. The others keep their current value
+        #[command(flatten)]
+        params: InferenceConfig,
+        /// Go back to the model's defaults for every parameter
+        #[arg(long, conflicts_with_all = ["temperature", "max_output_tokens", "top_p"])]
+        reset: bool,
+    },
+}
+
+fn set_model_params(os: &Os, session: &mut ChatSession, params: InferenceConfig, reset: bool) -> Result<(), ChatError> {
+    let updated = if reset {
+        InferenceConfig::default()
+    } else {
+        session.conversation.inference_config.merge(params)
+    };
+    if let Err(err) = updated.validate() {
+        queue!(
+            session.stderr,
+            StyledText::error_fg(),
+            style::Print(format!("\nInvalid model parameters: {err}\n\n")),
+            StyledText::reset(),
+        )?;
+        return Ok(());
     }
+    if !updated.is_empty() && os.client.chat_model().is_none() {
+        queue!(
+            session.stderr,
+            StyledText::error_fg(),
+            style::Print(format!(
+                "\n{INFERENCE_CONFIG_UNSUPPORTED} Start q chat with --backend bedrock or --backend openai to set them.\n\n"
+            )),
+            StyledText::reset(),
+        )?;
+        return Ok(());
+    }
+
+    let changed = updated != session.conversation.inference_config;
+    session.conversation.inference_config = updated;
+    queue!(
+        session.stderr,
+        style::Print("\n"),
+        StyledText::success_fg(),
+        style::Print(if changed {
+            "✔ Model parameters updated: "
+        } else {
+            "Model parameters: "
+        }),
+        StyledText::reset(),
+        style::Print(format!("{updated}\n")),
+    )?;
+    queue!(session.stderr, style::Print("\n"))?;
+    Ok(())
 }
 
 pub async fn select_model(os: &Os, session: &mut ChatSession) -> Result<Option<ChatState>, ChatError> {
@@ -151,6 +334,23 @@ pub async fn select_model(os: &Os, session: &mut ChatSession) -> Result<Option<C
             StyledText::reset(),
             StyledText::reset(),
         )?;
+        for limitation in selected.capabilities.limitations() {
+            queue!(
+                session.stderr,
+                StyledText::warning_fg(),
+                style::Print(format!(" {limitation} with this model\n")),
+                StyledText::reset(),
+            )?;
+        }
+        if !session.pending_images.is_empty() && !selected.capabilities.images {
+            session.pending_images.clear();
+            queue!(
+                session.stderr,
+                StyledText::warning_fg(),
+                style::Print(" Removed the attached images\n"),
+                StyledText::reset(),
+            )?;
+        }
     }
 
     execute!(session.stderr, StyledText::reset())?;
@@ -180,6 +380,10 @@ pub async fn get_available_models(os: &Os) -> Result<(Vec<ModelInfo>, ModelInfo)
             let default_model = ModelInfo::from_api_model(&api_res.default_model);
 
             tracing::debug!("Successfully fetched {} models from API", models.len());
+            let names = models.iter().map(|m| m.display_name().to_string()).collect::<Vec<_>>();
+            if let Err(err) = os.database.set_model_names(&names) {
+                tracing::warn!(?err, "Failed to save the model names for the shell completions");
+            }
             Ok((models, default_model))
         },
         // In case of API throttling or other errors, fall back to hardcoded models
@@ -204,6 +408,14 @@ fn default_context_window() -> usize {
     200_000
 }
 
+/// Names of the models completed by the shell completions before the backend listed them.
+pub fn fallback_model_names() -> Vec<String> {
+    get_fallback_models()
+        .iter()
+        .map(|m| m.display_name().to_string())
+        .collect()
+}
+
 fn get_fallback_models() -> Vec<ModelInfo> {
     vec![
         ModelInfo {
@@ -211,12 +423,14 @@ fn get_fallback_models() -> Vec<ModelInfo> {
             model_id: "claude-sonnet-4".to_string(),
             description: None,
             context_window_tokens: 200_000,
+            capabilities: ModelCapabilities::from_model_id("claude-sonnet-4"),
         },
         ModelInfo {
             model_name: Some("claude-3.7-sonnet".to_string()),
             model_id: "claude-3.7-sonnet".to_string(),
             description: None,
             context_window_tokens: 200_000,
+            capabilities: ModelCapabilities::from_model_id("claude-3.7-sonnet"),
         },
     ]
 }
@@ -229,6 +443,33 @@ pub fn normalize_model_name(name: &str) -> &str {
     }
 }
 
+/// Returns the model to switch to when `current` is overloaded or throttled: the one following it
+/// in the chain made of the agent's model and its `modelFallbacks`, or the start of the chain if
+/// `current` isn't part of it. Models that aren't available are skipped.
+pub fn next_fallback_model<'a>(
+    models: &'a [ModelInfo],
+    agent_model: Option<&str>,
+    fallbacks: &[String],
+    current: Option<&str>,
+) -> Option<&'a ModelInfo> {
+    if fallbacks.is_empty() {
+        return None;
+    }
+    let mut chain: Vec<&ModelInfo> = Vec::new();
+    for name in agent_model.into_iter().chain(fallbacks.iter().map(String::as_str)) {
+        if let Some(model) = find_model(models, name) {
+            if !chain.iter().any(|m| m.model_id == model.model_id) {
+                chain.push(model);
+            }
+        }
+    }
+
+    match chain.iter().position(|m| Some(m.model_id.as_str()) == current) {
+        Some(index) => chain.get(index + 1).copied(),
+        None => chain.first().copied(),
+    }
+}
+
 pub fn find_model<'a>(models: &'a [ModelInfo], name: &str) -> Option<&'a ModelInfo> {
     let normalized = normalize_model_name(name);
     models.iter().find(|m| {
@@ -238,3 +479,53 @@ pub fn find_model<'a>(models: &'a [ModelInfo], name: &str) -> Option<&'a ModelIn
             || m.model_id.eq_ignore_ascii_case(normalized)
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_model_capabilities() {
+        let sonnet = ModelCapabilities::from_model_id("CLAUDE_SONNET_4_20250514_V1_0");
+        assert!(sonnet.images && sonnet.tool_use && sonnet.reasoning);
+        assert!(!ModelCapabilities::from_model_id("claude-3.5-sonnet").reasoning);
+        assert!(!ModelCapabilities::from_model_id("deepseek-r1").tool_use);
+
+        let model = Model::builder()
+            .model_id("OPENAI_GPT_OSS_120B_1_0")
+            .supported_input_types(InputType::Text)
+            .supports_prompt_cache(true)
+            .build()
+            .unwrap();
+        let capabilities = ModelInfo::from_api_model(&model).capabilities;
+        assert!(!capabilities.images);
+        assert!(capabilities.reasoning);
+        assert!(capabilities.prompt_cache);
+        assert_eq!(capabilities.limitations(), vec!["Images can't be attached to prompts"]);
+
+        // Models stored before capabilities were tracked
+        let stored: ModelInfo = serde_json::from_str(r#"{"model_id":"claude-sonnet-4"}"#).unwrap();
+        assert_eq!(stored.capabilities, ModelCapabilities::default());
+    }
+
+    #[test]
+    fn test_next_fallback_model() {
+        let models = ["claude-sonnet-4", "claude-3.7-sonnet", "claude-3.5-sonnet"]
+            .map(|id| ModelInfo::from_id(id.to_string()))
+            .to_vec();
+        let fallbacks = ["claude-3.7-sonnet", "unavailable-model", "claude-3.5-sonnet"].map(String::from);
+        let next = |current: Option<&str>| {
+            next_fallback_model(&models, Some("claude-sonnet-4"), &fallbacks, current).map(|m| m.model_id.as_str())
+        };
+
+        assert_eq!(next(Some("claude-sonnet-4")), Some("claude-3.7-sonnet"));
+        // Unavailable models are skipped
+        assert_eq!(next(Some("claude-3.7-sonnet")), Some("claude-3.5-sonnet"));
+        assert_eq!(next(Some("claude-3.5-sonnet")), None);
+        // A model picked outside of the chain falls back to the start of it
+        assert_eq!(next(Some("other-model")), Some("claude-sonnet-4"));
+        assert_eq!(next(None), Some("claude-sonnet-4"));
+
+        assert!(next_fallback_model(&models, Some("claude-sonnet-4"), &[], Some("claude-sonnet-4")).is_none());
+    }
+}
@@ -1,8 +1,7 @@
 use clap::Args;
-use crossterm::execute;
-use crossterm::style::{
-    self,
-    Color,
+use crossterm::{
+    execute,
+    style,
 };
 
 use crate::cli::chat::util::clipboard::paste_image_from_clipboard;
@@ -10,31 +9,60 @@ use crate::cli::chat::{
     ChatError,
     ChatSession,
     ChatState,
+    accessible,
 };
-use crate::os::Os;
+use crate::theme::StyledText;
 
 #[derive(Debug, Args, PartialEq)]
 pub struct PasteArgs;
 
 impl PasteArgs {
-    pub async fn execute(self, _os: &mut Os, session: &mut ChatSession) -> Result<ChatState, ChatError> {
-        match paste_image_from_clipboard() {
-            Ok(path) => Ok(ChatState::HandleInput {
-                input: path.display().to_string(),
-            }),
+    pub async fn execute(self, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        let attached = paste_image_from_clipboard()
+            .map_err(|e| e.to_string())
+            .and_then(|path| {
+                let res = session.attach_image(&path.display().to_string());
+                // The image is read into memory when attached, the temp file is no longer needed
+                let _ = std::fs::remove_file(&path);
+                res.map_err(|e| e.to_string())
+            })
+            .and_then(|()| {
+                session
+                    .pending_images
+                    .last()
+                    .map(|(_, metadata)| metadata.size)
+                    .ok_or_else(|| "the image was not attached".to_string())
+            });
+
+        match attached {
+            Ok(size) => {
+                execute!(
+                    session.stderr,
+                    style::Print(format!(
+                        "\n{}Attached image from clipboard ({:.1} KB) to your next message ",
+                        accessible::symbol("📎 ", ""),
+                        size as f64 / 1024.0
+                    )),
+                    StyledText::secondary_fg(),
+                    style::Print(format!(
+                        "({} attached, /attach --clear to remove)\n\n",
+                        session.pending_images.len()
+                    )),
+                    StyledText::reset(),
+                )?;
+            },
             Err(e) => {
                 execute!(
                     session.stderr,
-                    style::SetForegroundColor(Color::Red),
-                    style::Print("❌ Failed to paste image: "),
-                    style::SetForegroundColor(Color::Reset),
-                    style::Print(format!("{}\n", e))
+                    StyledText::error_fg(),
+                    style::Print(format!("\nFailed to paste image: {e}\n\n")),
+                    StyledText::reset(),
                 )?;
-
-                Ok(ChatState::PromptUser {
-                    skip_printing_tools: false,
-                })
             },
         }
+
+        Ok(ChatState::PromptUser {
+            skip_printing_tools: true,
+        })
     }
 }
@@ -0,0 +1,136 @@
+use clap::Args;
+use crossterm::style::Attribute;
+use crossterm::{
+    execute,
+    queue,
+    style,
+};
+
+use crate::cli::chat::perf::{
+    Latencies,
+    SLOW_MCP_SERVER,
+    format_latency,
+    is_slow_mcp_server,
+};
+use crate::cli::chat::{
+    ChatError,
+    ChatSession,
+    ChatState,
+};
+use crate::theme::StyledText;
+
+/// Arguments to the `/perf` command.
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Args)]
+#[command(
+    before_long_help = "/perf shows how long the requests and the tool uses of this session took: their count and
+their median (p50), 95th percentile (p95) and maximum durations.
+
+MCP servers whose tools took 5 seconds or more at the 95th percentile are highlighted."
+)]
+pub struct PerfArgs {}
+
+impl PerfArgs {
+    pub async fn execute(self, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        let perf = &session.perf;
+        if perf.requests.count() == 0 && perf.tools.is_empty() {
+            execute!(
+                session.stderr,
+                StyledText::secondary_fg(),
+                style::Print("\nNothing was measured yet in this session.\n\n"),
+                StyledText::reset(),
+            )?;
+            return Ok(ChatState::PromptUser {
+                skip_printing_tools: true,
+            });
+        }
+
+        let name_width = perf
+            .tools
+            .keys()
+            .chain(perf.mcp_servers.keys())
+            .map(|name| name.len() + 2)
+            .chain([20])
+            .max()
+            .unwrap_or_default();
+        let row = |name: &str, latencies: &Latencies| {
+            let latency = |percentile: Option<std::time::Duration>| percentile.map(format_latency).unwrap_or_default();
+            format!(
+                "{name:<name_width$}  {:>6}  {:>8}  {:>8}  {:>8}\n",
+                latencies.count(),
+                latency(latencies.percentile(50.0)),
+                latency(latencies.percentile(95.0)),
+                latency(latencies.max()),
+            )
+        };
+        let heading = |title: &str| {
+            format!(
+                "{title:<name_width$}  {:>6}  {:>8}  {:>8}  {:>8}\n",
+                "count", "p50", "p95", "max"
+            )
+        };
+
+        queue!(
+            session.stderr,
+            style::Print("\n"),
+            style::SetAttribute(Attribute::Bold),
+            style::Print(heading("Requests")),
+            StyledText::reset_attributes(),
+            style::Print(row("  Response", &perf.requests)),
+            style::Print(row("  First chunk", &perf.first_chunks)),
+        )?;
+
+        if !perf.tools.is_empty() {
+            queue!(
+                session.stderr,
+                style::Print("\n"),
+                style::SetAttribute(Attribute::Bold),
+                style::Print(heading("Tools")),
+                StyledText::reset_attributes(),
+            )?;
+            for (name, latencies) in &perf.tools {
+                queue!(session.stderr, style::Print(row(&format!("  {name}"), latencies)))?;
+            }
+        }
+
+        if !perf.mcp_servers.is_empty() {
+            queue!(
+                session.stderr,
+                style::Print("\n"),
+                style::SetAttribute(Attribute::Bold),
+                style::Print(heading("MCP servers")),
+                StyledText::reset_attributes(),
+            )?;
+            let mut slow = false;
+            for (name, latencies) in &perf.mcp_servers {
+                if is_slow_mcp_server(latencies) {
+                    slow = true;
+                    queue!(
+                        session.stderr,
+                        StyledText::warning_fg(),
+                        style::Print(row(&format!("  {name}"), latencies)),
+                        StyledText::reset(),
+                    )?;
+                } else {
+                    queue!(session.stderr, style::Print(row(&format!("  {name}"), latencies)))?;
+                }
+            }
+            if slow {
+                queue!(
+                    session.stderr,
+                    StyledText::warning_fg(),
+                    style::Print(format!(
+                        "\nHighlighted servers took {} or more at p95. Check them with /mcp\n",
+                        format_latency(SLOW_MCP_SERVER)
+                    )),
+                    StyledText::reset(),
+                )?;
+            }
+        }
+        execute!(session.stderr, style::Print("\n"))?;
+
+        Ok(ChatState::PromptUser {
+            skip_printing_tools: true,
+        })
+    }
+}
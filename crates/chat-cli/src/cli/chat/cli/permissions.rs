@@ -0,0 +1,724 @@
+use std::io::Write;
+
+use clap::{
+    Args,
+    Subcommand,
+};
+use crossterm::event::{
+    Event,
+    EventStream,
+    KeyCode,
+    KeyEventKind,
+    KeyModifiers,
+};
+use crossterm::style::Attribute;
+use crossterm::{
+    cursor,
+    execute,
+    queue,
+    style,
+    terminal,
+};
+use futures::StreamExt;
+use serde_json::Value;
+
+use crate::cli::agent::{
+    Agent,
+    PermissionEvalResult,
+    ToolSettingTarget,
+};
+use crate::cli::chat::cli::agents::{
+    Screen,
+    fit,
+};
+use crate::cli::chat::tools::execute::ExecuteCommand;
+use crate::cli::chat::tools::fs_read::{
+    FsLine,
+    FsRead,
+    FsReadOperation,
+};
+use crate::cli::chat::tools::fs_write::FsWrite;
+use crate::cli::chat::tools::use_aws::UseAws;
+use crate::cli::chat::tools::{
+    NATIVE_TOOLS,
+    Tool,
+};
+use crate::cli::chat::{
+    ChatError,
+    ChatSession,
+    ChatState,
+};
+use crate::constants::help_text::permissions_long_help;
+use crate::os::Os;
+use crate::theme::StyledText;
+
+/// Command-line arguments for managing the permission profiles of the active agent
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Args)]
+pub struct PermissionsArgs {
+    #[command(subcommand)]
+    subcommand: Option<PermissionsSubcommand>,
+}
+
+impl PermissionsArgs {
+    pub async fn execute(self, os: &Os, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        self.subcommand
+            .unwrap_or(PermissionsSubcommand::List)
+            .execute(os, session)
+            .await
+    }
+
+    pub fn subcommand_name(&self) -> Option<&'static str> {
+        self.subcommand.as_ref().map(|s| s.name())
+    }
+}
+
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Subcommand)]
+#[command(
+    before_long_help = permissions_long_help()
+)]
+/// Subcommands for switching between permission profiles
+pub enum PermissionsSubcommand {
+    /// List the permission profiles defined by the active agent
+    List,
+    /// Switch to a permission profile for the rest of the session
+    Use {
+        /// Name of the permission profile to use
+        name: String,
+    },
+    /// Go back to the permissions defined at the top level of the agent config
+    Reset,
+    /// Interactively edit the allowedTools and toolsSettings of the active agent
+    Edit,
+}
+
+impl PermissionsSubcommand {
+    pub async fn execute(self, os: &Os, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        let Some(agent) = session.conversation.agents.get_active_mut() else {
+            queue!(
+                session.stderr,
+                StyledText::error_fg(),
+                style::Print("\nThere is no active agent.\n\n"),
+                StyledText::reset(),
+            )?;
+            return Ok(ChatState::PromptUser {
+                skip_printing_tools: true,
+            });
+        };
+
+        match self {
+            Self::Edit => return edit_permissions(os, session).await,
+            Self::List => {
+                if agent.permission_profiles.is_empty() {
+                    queue!(
+                        session.stderr,
+                        style::Print("\nAgent "),
+                        StyledText::brand_fg(),
+                        style::Print(&agent.name),
+                        StyledText::reset(),
+                        style::Print(" does not define any permission profiles. Add them under "),
+                        StyledText::success_fg(),
+                        style::Print("permissionProfiles"),
+                        StyledText::reset(),
+                        style::Print(" in the agent config.\n\n"),
+                    )?;
+                    return Ok(ChatState::PromptUser {
+                        skip_printing_tools: true,
+                    });
+                }
+
+                let mut names = agent.permission_profiles.keys().collect::<Vec<_>>();
+                names.sort();
+
+                queue!(
+                    session.stderr,
+                    style::Print("\n"),
+                    style::SetAttribute(Attribute::Bold),
+                    style::Print(format!("Permission profiles for {}:\n", agent.name)),
+                    StyledText::reset_attributes(),
+                )?;
+                for name in names {
+                    let profile = &agent.permission_profiles[name];
+                    let is_active = agent.active_permission_profile.as_ref() == Some(name);
+                    if is_active {
+                        queue!(
+                            session.stderr,
+                            StyledText::success_fg(),
+                            style::Print(format!("* {name}"))
+                        )?;
+                    } else {
+                        queue!(session.stderr, style::Print(format!("  {name}")))?;
+                    }
+                    queue!(
+                        session.stderr,
+                        StyledText::reset(),
+                        StyledText::secondary_fg(),
+                        style::Print(format!(" ({} allowed tools)", profile.allowed_tools.len())),
+                    )?;
+                    if let Some(description) = &profile.description {
+                        queue!(session.stderr, style::Print(format!(" - {description}")))?;
+                    }
+                    queue!(session.stderr, StyledText::reset(), style::Print("\n"))?;
+                }
+                if agent.active_permission_profile.is_none() {
+                    queue!(
+                        session.stderr,
+                        StyledText::secondary_fg(),
+                        style::Print(
+                            "\nNo profile is in use. Permissions are taken from the top level of the agent config.\n"
+                        ),
+                        StyledText::reset(),
+                    )?;
+                }
+                queue!(session.stderr, style::Print("\n"))?;
+
+                return Ok(ChatState::PromptUser {
+                    skip_printing_tools: true,
+                });
+            },
+            Self::Use { name } => {
+                if let Err(err) = agent.use_permission_profile(&name) {
+                    queue!(
+                        session.stderr,
+                        StyledText::error_fg(),
+                        style::Print(format!("\n{err}\n\n")),
+                        StyledText::reset(),
+                    )?;
+                    return Ok(ChatState::PromptUser {
+                        skip_printing_tools: true,
+                    });
+                }
+
+                queue!(
+                    session.stderr,
+                    StyledText::success_fg(),
+                    style::Print("\nNow using permission profile "),
+                    StyledText::brand_fg(),
+                    style::Print(&name),
+                    StyledText::success_fg(),
+                    style::Print(". Tools trusted during this session have been reset.\n\n"),
+                    StyledText::reset(),
+                )?;
+            },
+            Self::Reset => {
+                let was_in_use = agent.reset_permission_profile();
+                queue!(
+                    session.stderr,
+                    StyledText::success_fg(),
+                    style::Print(if was_in_use {
+                        "\nRestored the permissions defined by the agent.\n\n"
+                    } else {
+                        "\nNo permission profile is in use.\n\n"
+                    }),
+                    StyledText::reset(),
+                )?;
+            },
+        }
+
+        if let Some(agent) = session.conversation.agents.get_active() {
+            agent
+                .print_overridden_permissions(&mut session.stderr)
+                .map_err(|_e| ChatError::Custom("Failed to validate agent tool settings".into()))?;
+        }
+
+        // Tools awaiting approval need their trust re-evaluated against the new rules.
+        if session.pending_tool_index.is_some() {
+            return Ok(ChatState::ExecuteTools);
+        }
+
+        Ok(ChatState::PromptUser {
+            skip_printing_tools: true,
+        })
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::List => "list",
+            Self::Use { .. } => "use",
+            Self::Reset => "reset",
+            Self::Edit => "edit",
+        }
+    }
+}
+
+/// Native tools whose behavior can be configured through `toolsSettings`.
+const CONFIGURABLE_TOOLS: [&str; 4] = [
+    "fs_read",
+    "fs_write",
+    #[cfg(windows)]
+    "execute_cmd",
+    #[cfg(not(windows))]
+    "execute_bash",
+    "use_aws",
+];
+
+const KEYS_HELP: &str = "↑/↓ select · a allow a tool · enter edit · d remove · s save · q discard";
+
+/// Line of the editor that can be selected
+#[derive(Debug, Clone, PartialEq)]
+enum Row {
+    /// An entry of `allowedTools`
+    Allowed(String),
+    /// The `toolsSettings` of a tool
+    Settings(String),
+}
+
+/// What the line typed at the bottom of the editor is for
+#[derive(Debug, Clone, PartialEq)]
+enum Prompt {
+    AllowTool,
+    Settings(String),
+}
+
+enum Outcome {
+    Save,
+    Discard,
+}
+
+/// Interactive editor over the `allowedTools` and `toolsSettings` of the active agent. Edits are
+/// made on a copy of the agent, below which a preview of how a few sample tool uses would be
+/// evaluated is kept up to date. Saving writes the rules back to the agent's config file.
+async fn edit_permissions(os: &Os, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+    let Some(agent) = session.conversation.agents.get_active() else {
+        return Err(ChatError::Custom("There is no active agent".into()));
+    };
+    if let Some(profile) = &agent.active_permission_profile {
+        return Err(ChatError::Custom(
+            format!(
+                "Permission profile {profile} is in use. Run /permissions reset before editing the agent's permissions"
+            )
+            .into(),
+        ));
+    }
+    let Some(path) = agent.path.clone() else {
+        return Err(ChatError::Custom(
+            format!(
+                "Agent {} does not have a config file to write changes to. Create one with /agent create",
+                agent.name
+            )
+            .into(),
+        ));
+    };
+
+    let mut draft = agent.clone();
+    let outcome = {
+        let _screen = Screen::enter(&mut session.stderr)?;
+        run_editor(os, &mut session.stderr, &mut draft).await?
+    };
+    if let Outcome::Discard = outcome {
+        execute!(
+            session.stderr,
+            StyledText::secondary_fg(),
+            style::Print("\nDiscarded changes to the agent's permissions.\n\n"),
+            StyledText::reset(),
+        )?;
+        return Ok(ChatState::PromptUser {
+            skip_printing_tools: true,
+        });
+    }
+
+    write_permissions(os, &path, &draft)
+        .await
+        .map_err(|e| ChatError::Custom(format!("Failed to save agent config: {e}").into()))?;
+    if let Some(agent) = session.conversation.agents.get_active_mut() {
+        agent.allowed_tools = draft.allowed_tools;
+        agent.tools_settings = draft.tools_settings;
+    }
+
+    execute!(
+        session.stderr,
+        StyledText::success_fg(),
+        style::Print(format!("\nSaved the agent's permissions to {}\n\n", path.display())),
+        StyledText::reset(),
+    )?;
+
+    Ok(ChatState::PromptUser {
+        skip_printing_tools: true,
+    })
+}
+
+async fn run_editor(os: &Os, output: &mut impl Write, draft: &mut Agent) -> Result<Outcome, ChatError> {
+    let mut events = EventStream::new();
+    let mut selected = 0;
+    let mut input = None::<(Prompt, String)>;
+    let mut message = None::<String>;
+
+    loop {
+        let rows = rows(draft);
+        selected = selected.min(rows.len().saturating_sub(1));
+        draw_editor(os, output, draft, &rows, selected, input.as_ref(), message.as_deref())?;
+
+        let key = match events.next().await {
+            Some(Ok(Event::Key(key))) if key.kind == KeyEventKind::Press => key,
+            Some(Ok(_)) => continue,
+            Some(Err(err)) => return Err(err.into()),
+            None => return Ok(Outcome::Discard),
+        };
+        if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+            return Ok(Outcome::Discard);
+        }
+
+        if let Some((prompt, mut line)) = input.take() {
+            match key.code {
+                KeyCode::Enter => message = apply_input(draft, &prompt, &line).err(),
+                KeyCode::Esc => (),
+                code => {
+                    match code {
+                        KeyCode::Char(c) => line.push(c),
+                        KeyCode::Backspace => {
+                            line.pop();
+                        },
+                        _ => (),
+                    }
+                    input = Some((prompt, line));
+                },
+            }
+            continue;
+        }
+
+        message = None;
+        match (key.code, rows.get(selected)) {
+            (KeyCode::Up | KeyCode::Char('k'), _) => selected = selected.saturating_sub(1),
+            (KeyCode::Down | KeyCode::Char('j'), _) => selected = (selected + 1).min(rows.len().saturating_sub(1)),
+            (KeyCode::Char('a'), _) => input = Some((Prompt::AllowTool, String::new())),
+            (KeyCode::Enter | KeyCode::Char('e'), Some(Row::Settings(tool))) => {
+                let current = draft
+                    .tools_settings
+                    .get(tool.as_str())
+                    .map(|settings| settings.to_string())
+                    .unwrap_or_default();
+                input = Some((Prompt::Settings(tool.clone()), current));
+            },
+            (KeyCode::Delete | KeyCode::Char('d'), Some(Row::Allowed(tool))) => {
+                draft.allowed_tools.remove(tool);
+            },
+            (KeyCode::Delete | KeyCode::Char('d'), Some(Row::Settings(tool))) => {
+                draft.tools_settings.remove(tool.as_str());
+            },
+            (KeyCode::Char('s'), _) => return Ok(Outcome::Save),
+            (KeyCode::Char('q') | KeyCode::Esc, _) => return Ok(Outcome::Discard),
+            _ => (),
+        }
+    }
+}
+
+/// The entries of `allowedTools`, then the tools that can be configured through `toolsSettings`.
+fn rows(agent: &Agent) -> Vec<Row> {
+    let mut allowed = agent.allowed_tools.iter().cloned().collect::<Vec<_>>();
+    allowed.sort();
+    let mut targets = CONFIGURABLE_TOOLS
+        .iter()
+        .copied()
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+    targets.extend(agent.tools_settings.keys().map(|k| k.0.clone()));
+    targets.sort();
+    targets.dedup();
+
+    allowed
+        .into_iter()
+        .map(Row::Allowed)
+        .chain(targets.into_iter().map(Row::Settings))
+        .collect()
+}
+
+/// Applies the line typed for `prompt` to the draft, or tells why it can't be.
+fn apply_input(draft: &mut Agent, prompt: &Prompt, line: &str) -> Result<(), String> {
+    match prompt {
+        Prompt::AllowTool => {
+            let name = line.trim();
+            validate_tool_name(name)?;
+            draft.allowed_tools.insert(name.to_string());
+        },
+        Prompt::Settings(tool) => match parse_tool_settings(line)? {
+            Some(settings) => {
+                draft.tools_settings.insert(ToolSettingTarget(tool.clone()), settings);
+            },
+            None => {
+                draft.tools_settings.remove(tool.as_str());
+            },
+        },
+    }
+    Ok(())
+}
+
+fn draw_editor(
+    os: &Os,
+    output: &mut impl Write,
+    agent: &Agent,
+    rows: &[Row],
+    selected: usize,
+    input: Option<&(Prompt, String)>,
+    message: Option<&str>,
+) -> Result<(), ChatError> {
+    let width = terminal::size().map_or(80, |(columns, _)| columns as usize);
+    let title = format!("Permissions of {}", agent.name);
+
+    queue!(
+        output,
+        terminal::Clear(terminal::ClearType::All),
+        cursor::MoveTo(0, 0),
+        StyledText::brand_fg(),
+        style::SetAttribute(Attribute::Bold),
+        style::Print(&title),
+        StyledText::reset_attributes(),
+        StyledText::secondary_fg(),
+        style::Print(fit(&format!(" · {KEYS_HELP}"), width.saturating_sub(title.len()))),
+        StyledText::reset(),
+        style::Print("\r\n\r\n"),
+        style::SetAttribute(Attribute::Bold),
+        style::Print("allowedTools\r\n"),
+        StyledText::reset_attributes(),
+    )?;
+    if !matches!(rows.first(), Some(Row::Allowed(_))) {
+        queue!(
+            output,
+            StyledText::secondary_fg(),
+            style::Print("  (none)\r\n"),
+            StyledText::reset()
+        )?;
+    }
+
+    let mut previous = None::<&Row>;
+    for (i, row) in rows.iter().enumerate() {
+        let line = match row {
+            Row::Allowed(tool) => tool.clone(),
+            Row::Settings(tool) => {
+                if !matches!(previous, Some(Row::Settings(_))) {
+                    queue!(
+                        output,
+                        style::Print("\r\n"),
+                        style::SetAttribute(Attribute::Bold),
+                        style::Print("toolsSettings\r\n"),
+                        StyledText::reset_attributes(),
+                    )?;
+                }
+                match agent.tools_settings.get(tool.as_str()) {
+                    Some(settings) => format!("{tool}: {settings}"),
+                    None => format!("{tool}: (default)"),
+                }
+            },
+        };
+        queue!(output, style::Print(if i == selected { "> " } else { "  " }))?;
+        if i == selected {
+            queue!(output, style::SetAttribute(Attribute::Bold))?;
+        }
+        queue!(
+            output,
+            style::Print(fit(&line, width.saturating_sub(2))),
+            StyledText::reset_attributes(),
+            style::Print("\r\n"),
+        )?;
+        previous = Some(row);
+    }
+
+    queue_evaluation_preview(os, agent, output)?;
+
+    if let Some(message) = message {
+        queue!(
+            output,
+            StyledText::error_fg(),
+            style::Print(fit(message, width)),
+            StyledText::reset(),
+            style::Print("\r\n"),
+        )?;
+    }
+    match input {
+        Some((prompt, line)) => {
+            let label = match prompt {
+                Prompt::AllowTool => "Tool to allow (e.g. fs_write, @server or @server/tool): ".to_string(),
+                Prompt::Settings(tool) => format!("Settings of {tool} as JSON, empty to remove: "),
+            };
+            queue!(
+                output,
+                StyledText::info_fg(),
+                style::Print(label),
+                StyledText::reset(),
+                style::Print(line),
+                cursor::Show,
+            )?;
+        },
+        None => queue!(output, cursor::Hide)?,
+    }
+    output.flush()?;
+
+    Ok(())
+}
+
+/// Checks that `name` can be used as an entry of `allowedTools`.
+fn validate_tool_name(name: &str) -> Result<(), String> {
+    if name.is_empty() {
+        return Err("Tool name must not be empty".to_string());
+    }
+    if name.chars().any(char::is_whitespace) {
+        return Err(format!("Tool name '{name}' must not contain whitespace"));
+    }
+    // MCP tools and wildcard patterns are matched against tools that may not be loaded yet.
+    if name.starts_with('@') || name.contains('*') || name.contains('?') {
+        return Ok(());
+    }
+    if NATIVE_TOOLS.contains(&name) || matches!(name, "report_issue" | "introspect") {
+        return Ok(());
+    }
+
+    Err(format!(
+        "Unknown tool '{name}'. Use one of {} or @server_name for MCP tools",
+        NATIVE_TOOLS.join(", ")
+    ))
+}
+
+/// Parses the settings written in the editor. An empty document or object removes the settings.
+fn parse_tool_settings(content: &str) -> Result<Option<Value>, String> {
+    if content.trim().is_empty() {
+        return Ok(None);
+    }
+    match serde_json::from_str::<Value>(content) {
+        Ok(Value::Object(map)) if map.is_empty() => Ok(None),
+        Ok(value @ Value::Object(_)) => Ok(Some(value)),
+        Ok(_) => Err("Tool settings must be a JSON object".to_string()),
+        Err(e) => Err(format!("Invalid JSON, the settings were not changed: {e}")),
+    }
+}
+
+/// Tool uses used to preview the effect of the rules being edited.
+fn sample_tool_uses() -> Vec<(&'static str, Tool)> {
+    let fs_read = |path: &str| {
+        Tool::FsRead(FsRead {
+            operations: vec![FsReadOperation::Line(FsLine {
+                path: path.to_string(),
+                start_line: None,
+                end_line: None,
+            })],
+            summary: None,
+        })
+    };
+    let execute = |command: &str| {
+        Tool::ExecuteCommand(ExecuteCommand {
+            command: command.to_string(),
+            summary: None,
+        })
+    };
+    let use_aws = |service_name: &str, operation_name: &str| {
+        Tool::UseAws(UseAws {
+            service_name: service_name.to_string(),
+            operation_name: operation_name.to_string(),
+            parameters: None,
+            region: "us-east-1".to_string(),
+            profile_name: None,
+            label: None,
+        })
+    };
+
+    vec![
+        ("read README.md", fs_read("README.md")),
+        ("read /etc/hosts", fs_read("/etc/hosts")),
+        (
+            "write src/main.rs",
+            Tool::FsWrite(FsWrite::Create {
+                path: "src/main.rs".to_string(),
+                file_text: Some(String::new()),
+                new_str: None,
+                summary: None,
+            }),
+        ),
+        ("run `ls -la`", execute("ls -la")),
+        ("run `git push`", execute("git push")),
+        ("run `rm -rf target`", execute("rm -rf target")),
+        ("aws s3 list-buckets", use_aws("s3", "list-buckets")),
+        ("aws ec2 terminate-instances", use_aws("ec2", "terminate-instances")),
+    ]
+}
+
+fn queue_evaluation_preview(os: &Os, agent: &Agent, output: &mut impl Write) -> Result<(), ChatError> {
+    queue!(
+        output,
+        style::Print("\r\n"),
+        style::SetAttribute(Attribute::Bold),
+        style::Print("How sample tool uses would be handled\r\n"),
+        StyledText::reset_attributes(),
+    )?;
+
+    for (description, tool) in sample_tool_uses() {
+        queue!(output, style::Print(format!("  {description:<30}")))?;
+        match tool.requires_acceptance(os, agent) {
+            PermissionEvalResult::Allow => queue!(output, StyledText::success_fg(), style::Print("trusted"))?,
+            PermissionEvalResult::Ask => queue!(output, StyledText::warning_fg(), style::Print("ask"))?,
+            PermissionEvalResult::Deny(reasons) => queue!(
+                output,
+                StyledText::error_fg(),
+                style::Print(format!("denied ({})", reasons.join(", ")))
+            )?,
+        }
+        queue!(output, StyledText::reset(), style::Print("\r\n"))?;
+    }
+    queue!(output, style::Print("\r\n"))?;
+
+    Ok(())
+}
+
+/// Writes the permission rules of `agent` to the config file at `path`, leaving the rest of the
+/// file as is.
+async fn write_permissions(os: &Os, path: &std::path::Path, agent: &Agent) -> eyre::Result<()> {
+    let content = os.fs.read(path).await?;
+    let mut config = serde_json::from_slice::<serde_json::Map<String, Value>>(&content)?;
+
+    let mut allowed = agent.allowed_tools.iter().cloned().collect::<Vec<_>>();
+    allowed.sort();
+    config.insert("allowedTools".to_string(), serde_json::to_value(allowed)?);
+    config.insert(
+        "toolsSettings".to_string(),
+        serde_json::to_value(&agent.tools_settings)?,
+    );
+
+    os.fs.write(path, serde_json::to_string_pretty(&config)?).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_tool_name() {
+        assert!(validate_tool_name("fs_write").is_ok());
+        assert!(validate_tool_name("report_issue").is_ok());
+        assert!(validate_tool_name("@git").is_ok());
+        assert!(validate_tool_name("@git/git_status").is_ok());
+        assert!(validate_tool_name("@git/*").is_ok());
+
+        assert!(validate_tool_name("").is_err());
+        assert!(validate_tool_name("fs write").is_err());
+        assert!(validate_tool_name("not_a_tool").is_err());
+    }
+
+    #[test]
+    fn test_apply_input() {
+        let mut draft = Agent::default();
+        assert!(apply_input(&mut draft, &Prompt::AllowTool, " @git ").is_ok());
+        assert!(apply_input(&mut draft, &Prompt::AllowTool, "not_a_tool").is_err());
+        assert!(draft.allowed_tools.contains("@git"));
+        assert!(!draft.allowed_tools.contains("not_a_tool"));
+
+        let settings = Prompt::Settings("fs_write".to_string());
+        assert!(apply_input(&mut draft, &settings, r#"{"allowedPaths": ["src"]}"#).is_ok());
+        assert!(rows(&draft).contains(&Row::Allowed("@git".to_string())));
+        assert!(draft.tools_settings.contains_key("fs_write"));
+        assert!(apply_input(&mut draft, &settings, "{").is_err());
+        assert!(draft.tools_settings.contains_key("fs_write"));
+        assert!(apply_input(&mut draft, &settings, "").is_ok());
+        assert!(!draft.tools_settings.contains_key("fs_write"));
+    }
+
+    #[test]
+    fn test_parse_tool_settings() {
+        assert_eq!(parse_tool_settings(""), Ok(None));
+        assert_eq!(parse_tool_settings("{}"), Ok(None));
+        assert_eq!(
+            parse_tool_settings(r#"{ "allowedPaths": ["~/projects"] }"#),
+            Ok(Some(serde_json::json!({ "allowedPaths": ["~/projects"] })))
+        );
+        assert!(parse_tool_settings("[]").is_err());
+        assert!(parse_tool_settings("{ allowedPaths }").is_err());
+    }
+}
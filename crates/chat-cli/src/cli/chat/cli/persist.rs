@@ -116,6 +116,12 @@ impl PersistSubcommand {
                                     .push(ContextFilePath::Session(incoming_path.get_path_as_str().to_string()));
                             }
                         }
+                        for url in &cm.urls {
+                            existing_cm.add_url(url.clone());
+                        }
+                        for symbol in &cm.symbols {
+                            let _ = existing_cm.add_symbol(symbol.clone());
+                        }
                     }
                 }
                 std::mem::swap(
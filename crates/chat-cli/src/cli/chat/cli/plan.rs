@@ -0,0 +1,251 @@
+use std::io::Write;
+
+use clap::{
+    Args,
+    Subcommand,
+};
+use crossterm::{
+    execute,
+    style,
+};
+
+use crate::cli::agent::PermissionEvalResult;
+use crate::cli::chat::util::truncate_safe;
+use crate::cli::chat::{
+    ChatError,
+    ChatSession,
+    ChatState,
+};
+use crate::os::Os;
+use crate::theme::StyledText;
+
+/// Maximum size of the output of a step sent back to the model after applying the plan
+const MAX_STEP_OUTPUT_BYTES: usize = 2_000;
+
+/// Command-line arguments for plan mode
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Args)]
+#[command(
+    before_long_help = "In plan mode, tool uses that can modify files or run commands with side effects are
+recorded into a plan instead of being run. Read-only tools run as usual. The plan is shown for
+review once the model is done, and /plan apply runs its steps in order, stopping at the first
+one that fails. Steps that were not run stay in the plan.
+
+Applying the plan turns plan mode off and sends the results of the steps to the model."
+)]
+pub struct PlanArgs {
+    #[command(subcommand)]
+    subcommand: Option<PlanSubcommand>,
+}
+
+impl PlanArgs {
+    pub async fn execute(self, os: &Os, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        self.subcommand
+            .unwrap_or(PlanSubcommand::Show)
+            .execute(os, session)
+            .await
+    }
+
+    pub fn subcommand_name(&self) -> Option<&'static str> {
+        self.subcommand.as_ref().map(|s| s.name())
+    }
+}
+
+/// Subcommands for plan mode
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Subcommand)]
+pub enum PlanSubcommand {
+    /// Show the plan
+    Show,
+    /// Record tool uses with side effects into the plan instead of running them
+    On,
+    /// Run tool uses as usual again, keeping the plan
+    Off,
+    /// Run the steps of the plan and turn plan mode off
+    Apply,
+    /// Discard the plan
+    Clear,
+}
+
+impl PlanSubcommand {
+    pub async fn execute(self, os: &Os, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        match self {
+            Self::Show => {
+                if !session.plan.enabled && session.plan.is_empty() {
+                    execute!(
+                        session.stderr,
+                        StyledText::secondary_fg(),
+                        style::Print("\nPlan mode is off. Use /plan on to turn it on.\n\n"),
+                        StyledText::reset(),
+                    )?;
+                } else {
+                    session.plan.show(&mut session.stderr)?;
+                    session.stderr.flush()?;
+                }
+            },
+            Self::On => {
+                session.plan.enabled = true;
+                execute!(
+                    session.stderr,
+                    StyledText::success_fg(),
+                    style::Print("\nPlan mode is on. Tool uses with side effects will be recorded into the plan.\n\n"),
+                    StyledText::reset(),
+                )?;
+            },
+            Self::Off => {
+                session.plan.enabled = false;
+                execute!(
+                    session.stderr,
+                    StyledText::success_fg(),
+                    style::Print("\nPlan mode is off."),
+                    style::Print(if session.plan.is_empty() {
+                        "\n\n"
+                    } else {
+                        " The plan is kept until /plan apply or /plan clear.\n\n"
+                    }),
+                    StyledText::reset(),
+                )?;
+            },
+            Self::Apply => return apply(os, session).await,
+            Self::Clear => {
+                session.plan.clear();
+                execute!(
+                    session.stderr,
+                    StyledText::success_fg(),
+                    style::Print("\nPlan cleared\n\n"),
+                    StyledText::reset(),
+                )?;
+            },
+        }
+
+        Ok(ChatState::PromptUser {
+            skip_printing_tools: true,
+        })
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Show => "show",
+            Self::On => "on",
+            Self::Off => "off",
+            Self::Apply => "apply",
+            Self::Clear => "clear",
+        }
+    }
+}
+
+/// Runs the steps of the plan in order, stopping at the first one that is denied or fails, and
+/// sends their results to the model.
+async fn apply(os: &Os, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+    if session.plan.is_empty() {
+        execute!(
+            session.stderr,
+            StyledText::secondary_fg(),
+            style::Print("\nThe plan has no steps to apply.\n\n"),
+            StyledText::reset(),
+        )?;
+        return Ok(ChatState::PromptUser {
+            skip_printing_tools: true,
+        });
+    }
+    if session.conversation.agents.read_only {
+        execute!(
+            session.stderr,
+            StyledText::error_fg(),
+            style::Print("\nThe plan can't be applied while the session is in read-only mode.\n\n"),
+            StyledText::reset(),
+        )?;
+        return Ok(ChatState::PromptUser {
+            skip_printing_tools: true,
+        });
+    }
+
+    session.plan.enabled = false;
+    let mut steps = session.plan.take_steps().into_iter();
+    let mut results = Vec::new();
+    let mut step_number = 0;
+    for step in steps.by_ref() {
+        step_number += 1;
+        execute!(
+            session.stderr,
+            StyledText::emphasis_fg(),
+            style::Print(format!("\nStep {step_number}: {}\n", step.action)),
+            StyledText::reset(),
+        )?;
+
+        let denied = session.conversation.agents.get_active().and_then(|agent| {
+            match step.tool.tool.requires_acceptance(os, agent) {
+                PermissionEvalResult::Deny(rules) => Some(rules),
+                PermissionEvalResult::Allow | PermissionEvalResult::Ask => None,
+            }
+        });
+        let outcome: Result<String, String> = match denied {
+            Some(rules) => Err(format!("denied by the rules: {}", rules.join(", "))),
+            None => step
+                .tool
+                .tool
+                .invoke(
+                    os,
+                    &mut session.stdout,
+                    &mut session.conversation.file_line_tracker,
+                    &session.conversation.agents,
+                )
+                .await
+                .map(|output| output.as_str().into_owned())
+                .map_err(|err| err.to_string()),
+        };
+
+        match outcome {
+            Ok(output) => {
+                execute!(
+                    session.stderr,
+                    StyledText::success_fg(),
+                    style::Print(" ● Completed\n"),
+                    StyledText::reset(),
+                )?;
+                results.push(format!(
+                    "Step {step_number} ({}) succeeded:\n{}",
+                    step.action,
+                    truncate_safe(&output, MAX_STEP_OUTPUT_BYTES)
+                ));
+            },
+            Err(err) => {
+                execute!(
+                    session.stderr,
+                    StyledText::error_fg(),
+                    style::Print(format!(" ● Failed: {err}\n")),
+                    StyledText::reset(),
+                )?;
+                results.push(format!(
+                    "Step {step_number} ({}) failed:\n{}",
+                    step.action,
+                    truncate_safe(&err, MAX_STEP_OUTPUT_BYTES)
+                ));
+                session.plan.restore_steps(std::iter::once(step));
+                break;
+            },
+        }
+    }
+    session.plan.restore_steps(steps);
+
+    let remaining = session.plan.steps().len();
+    execute!(
+        session.stderr,
+        StyledText::secondary_fg(),
+        style::Print(if remaining == 0 {
+            "\nThe plan was applied. Plan mode is off.\n\n".to_string()
+        } else {
+            format!("\n{remaining} step(s) were not applied and remain in the plan. Plan mode is off.\n\n")
+        }),
+        StyledText::reset(),
+    )?;
+
+    let mut input = String::from("I applied the plan. Here are the results of its steps:\n\n");
+    input.push_str(&results.join("\n\n"));
+    if remaining > 0 {
+        input.push_str(&format!(
+            "\n\nThe remaining {remaining} step(s) were not run. Explain what went wrong before continuing."
+        ));
+    }
+    Ok(ChatState::HandleInput { input })
+}
@@ -1,5 +1,8 @@
 use std::borrow::Cow;
-use std::collections::HashMap;
+use std::collections::{
+    BTreeSet,
+    HashMap,
+};
 use std::io::Write;
 
 use clap::Subcommand;
@@ -26,12 +29,14 @@ use syntect::util::{
     as_24_bit_terminal_escaped,
 };
 
+use crate::api_client::model::Tool;
 use crate::cli::agent::{
     Agent,
     Agents,
     McpServerConfig,
     create_agent,
 };
+use crate::cli::chat::consts::DUMMY_TOOL_NAME;
 use crate::cli::chat::conversation::McpServerInfo;
 use crate::cli::chat::{
     ChatError,
@@ -435,18 +440,17 @@ impl AgentSubcommand {
                 },
             },
             Self::Swap { name } => {
-                if let Some(name) = name {
-                    session.conversation.swap_agent(os, &mut session.stderr, &name).await?;
-                } else {
-                    let labels = session
-                        .conversation
-                        .agents
-                        .agents
-                        .keys()
-                        .map(|name| name.as_str())
-                        .collect::<Vec<_>>();
-
-                    let name = {
+                let name = match name {
+                    Some(name) => Some(name),
+                    None => {
+                        let labels = session
+                            .conversation
+                            .agents
+                            .agents
+                            .keys()
+                            .map(|name| name.as_str())
+                            .collect::<Vec<_>>();
+
                         let idx = match Select::with_theme(&crate::util::dialoguer_theme())
                             .with_prompt("Choose one of the following agents")
                             .items(&labels)
@@ -467,11 +471,11 @@ impl AgentSubcommand {
                         };
 
                         idx.and_then(|idx| labels.get(idx).cloned().map(str::to_string))
-                    };
+                    },
+                };
 
-                    if let Some(name) = name {
-                        session.conversation.swap_agent(os, &mut session.stderr, &name).await?;
-                    }
+                if let Some(name) = name {
+                    return swap_agent(os, session, &name).await;
                 }
             },
         }
@@ -496,6 +500,149 @@ impl AgentSubcommand {
     }
 }
 
+/// What the active agent contributes to the conversation, compared before and after a swap.
+#[derive(Debug, Default, PartialEq)]
+struct AgentSnapshot {
+    context: BTreeSet<String>,
+    tools: BTreeSet<String>,
+    permissions: BTreeSet<String>,
+}
+
+impl AgentSnapshot {
+    fn capture(session: &ChatSession) -> Self {
+        let conversation = &session.conversation;
+        let context = conversation
+            .context_manager
+            .as_ref()
+            .map(|cm| {
+                cm.paths
+                    .iter()
+                    .map(|p| p.get_path_as_str().to_string())
+                    .chain(cm.urls.iter().map(|u| u.entry_name()))
+                    .chain(cm.symbols.iter().map(|s| s.spec()))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let tools = conversation
+            .tools
+            .values()
+            .flatten()
+            .map(|tool| match tool {
+                Tool::ToolSpecification(spec) => spec.name.clone(),
+            })
+            .filter(|name| name != DUMMY_TOOL_NAME)
+            .collect();
+        let permissions = conversation
+            .agents
+            .get_active()
+            .map(|agent| {
+                agent
+                    .allowed_tools
+                    .iter()
+                    .map(|tool| format!("allow {tool}"))
+                    .chain(
+                        agent
+                            .tools_settings
+                            .iter()
+                            .map(|(target, settings)| format!("{} settings: {settings}", target.as_str())),
+                    )
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Self {
+            context,
+            tools,
+            permissions,
+        }
+    }
+
+    /// Returns the entries added and removed from `self` to `other` for each category.
+    fn diff<'a>(&'a self, other: &'a Self) -> Vec<(&'static str, Vec<&'a String>, Vec<&'a String>)> {
+        [
+            ("Context", &self.context, &other.context),
+            ("Tools", &self.tools, &other.tools),
+            ("Permissions", &self.permissions, &other.permissions),
+        ]
+        .into_iter()
+        .map(|(category, before, after)| {
+            (
+                category,
+                after.difference(before).collect::<Vec<_>>(),
+                before.difference(after).collect::<Vec<_>>(),
+            )
+        })
+        .filter(|(_, added, removed)| !added.is_empty() || !removed.is_empty())
+        .collect()
+    }
+}
+
+/// Swaps to the agent `name`, shows what changed in the context, tools and permissions, and
+/// checks the tool uses waiting for approval against the permissions of the new agent.
+async fn swap_agent(os: &mut Os, session: &mut ChatSession, name: &str) -> Result<ChatState, ChatError> {
+    let before = AgentSnapshot::capture(session);
+    session.conversation.swap_agent(os, &mut session.stderr, name).await?;
+    let after = AgentSnapshot::capture(session);
+
+    let changes = before.diff(&after);
+    if changes.is_empty() {
+        execute!(
+            session.stderr,
+            StyledText::secondary_fg(),
+            style::Print("\nThe context, tools and permissions are unchanged.\n"),
+            StyledText::reset(),
+        )?;
+    }
+    for (category, added, removed) in changes {
+        queue!(session.stderr, style::Print(format!("\n{category}:\n")))?;
+        for entry in added {
+            queue!(
+                session.stderr,
+                StyledText::success_fg(),
+                style::Print(format!("  + {entry}\n")),
+                StyledText::reset(),
+            )?;
+        }
+        for entry in removed {
+            queue!(
+                session.stderr,
+                StyledText::error_fg(),
+                style::Print(format!("  - {entry}\n")),
+                StyledText::reset(),
+            )?;
+        }
+    }
+    execute!(session.stderr, style::Print("\n"))?;
+
+    if session.pending_tool_index.is_none() {
+        return Ok(ChatState::PromptUser {
+            skip_printing_tools: true,
+        });
+    }
+
+    // Tool uses waiting for approval were evaluated against the previous agent
+    if let Some(tool_use) = session.tool_uses.iter().find(|t| !after.tools.contains(&t.name)) {
+        return Ok(ChatState::HandleInput {
+            input: format!(
+                "Tool use with {} was rejected because the tool is not available to the {name} agent",
+                tool_use.name
+            ),
+        });
+    }
+    for tool_use in &mut session.tool_uses {
+        tool_use.accepted = false;
+    }
+    session.pending_tool_index = None;
+    execute!(
+        session.stderr,
+        StyledText::secondary_fg(),
+        style::Print("Checking the pending tool uses against the permissions of the new agent\n"),
+        StyledText::reset(),
+    )?;
+
+    Ok(ChatState::ExecuteTools)
+}
+
 fn highlight_json(output: &mut impl Write, json_str: &str) -> eyre::Result<()> {
     let ps = SyntaxSet::load_defaults_newlines();
     let ts = ThemeSet::load_defaults();
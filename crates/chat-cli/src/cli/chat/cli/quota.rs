@@ -0,0 +1,289 @@
+use amzn_codewhisperer_client::operation::get_usage_limits::GetUsageLimitsOutput;
+use clap::Args;
+use crossterm::style::Attribute;
+use crossterm::{
+    execute,
+    queue,
+    style,
+};
+
+use crate::api_client::ApiClientError;
+use crate::cli::chat::{
+    ActualSubscriptionStatus,
+    ChatError,
+    ChatSession,
+    ChatState,
+    get_subscription_status,
+    with_spinner,
+};
+use crate::database::Database;
+use crate::database::settings::Setting;
+use crate::os::Os;
+use crate::theme::StyledText;
+
+/// Percentages of the monthly allowance at which to warn, unless configured with
+/// `chat.quotaWarningThresholds`.
+const DEFAULT_QUOTA_WARNING_THRESHOLDS: [f64; 2] = [80.0, 95.0];
+
+/// Width of the usage bars
+const QUOTA_BAR_WIDTH: usize = 30;
+
+/// Arguments to the `/quota` command.
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Args)]
+#[command(
+    before_long_help = "/quota shows how much of the monthly allowance of your subscription has been used.
+
+A warning is also shown after a response once the usage reaches one of the percentages set with
+chat.quotaWarningThresholds (80 and 95 by default), e.g.
+• q settings chat.quotaWarningThresholds '[50, 90]'"
+)]
+pub struct QuotaArgs {}
+
+impl QuotaArgs {
+    pub async fn execute(self, os: &mut Os, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        let quota = match with_spinner(&mut session.stderr, "Checking usage limits...", || fetch_quota(os)).await {
+            Ok(quota) => quota,
+            Err(err) => {
+                execute!(
+                    session.stderr,
+                    StyledText::error_fg(),
+                    style::Print(format!("\nFailed to get usage limits: {err}\n\n")),
+                    StyledText::reset(),
+                )?;
+                return Ok(ChatState::PromptUser {
+                    skip_printing_tools: true,
+                });
+            },
+        };
+
+        let subscription = match &quota.subscription {
+            Some(title) => title.clone(),
+            None => match get_subscription_status(os).await {
+                Ok(ActualSubscriptionStatus::Active | ActualSubscriptionStatus::Expiring) => {
+                    "Q Developer Pro".to_string()
+                },
+                Ok(ActualSubscriptionStatus::None) => "Q Developer Free".to_string(),
+                Err(_) => "Unknown".to_string(),
+            },
+        };
+        queue!(
+            session.stderr,
+            style::Print("\n"),
+            style::SetAttribute(Attribute::Bold),
+            style::Print("Subscription: "),
+            StyledText::reset_attributes(),
+            StyledText::brand_fg(),
+            style::Print(format!("{subscription}\n")),
+            StyledText::reset(),
+        )?;
+
+        if quota.resources.is_empty() {
+            queue!(
+                session.stderr,
+                StyledText::secondary_fg(),
+                style::Print("No usage limits apply to this subscription.\n"),
+                StyledText::reset(),
+            )?;
+        }
+        let warn_at = quota_warning_thresholds(&os.database).first().copied().unwrap_or(100.0);
+        let name_width = quota.resources.iter().map(|r| r.name.len()).max().unwrap_or_default();
+        for resource in &quota.resources {
+            let percent = resource.percent_used();
+            let width = ((percent / 100.0 * QUOTA_BAR_WIDTH as f64).round() as usize).min(QUOTA_BAR_WIDTH);
+            let color = match percent {
+                p if p >= 100.0 => StyledText::error_fg(),
+                p if p >= warn_at => StyledText::warning_fg(),
+                _ => StyledText::brand_fg(),
+            };
+            queue!(
+                session.stderr,
+                style::Print(format!("  {:<name_width$}  ", resource.name)),
+                color,
+                style::Print("█".repeat(width)),
+                StyledText::secondary_fg(),
+                style::Print("░".repeat(QUOTA_BAR_WIDTH - width)),
+                StyledText::reset(),
+                style::Print(format!(
+                    "  {} of {} used ({percent:.0}%)\n",
+                    format_amount(resource.used),
+                    format_amount(resource.limit)
+                )),
+            )?;
+        }
+        if let Some(days) = quota.days_until_reset {
+            queue!(
+                session.stderr,
+                StyledText::secondary_fg(),
+                style::Print(format!("\nResets in {days} day{}\n", if days == 1 { "" } else { "s" })),
+                StyledText::reset(),
+            )?;
+        }
+        execute!(session.stderr, style::Print("\n"))?;
+
+        Ok(ChatState::PromptUser {
+            skip_printing_tools: true,
+        })
+    }
+}
+
+/// Usage of a resource limited each month, e.g. requests.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResourceUsage {
+    pub name: String,
+    pub used: f64,
+    pub limit: f64,
+}
+
+impl ResourceUsage {
+    pub fn percent_used(&self) -> f64 {
+        if self.limit > 0.0 {
+            self.used / self.limit * 100.0
+        } else {
+            0.0
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QuotaUsage {
+    /// Title of the subscription, if returned with the usage.
+    pub subscription: Option<String>,
+    pub resources: Vec<ResourceUsage>,
+    pub days_until_reset: Option<i32>,
+}
+
+impl QuotaUsage {
+    fn from_output(output: &GetUsageLimitsOutput) -> Self {
+        let breakdowns = match output.usage_breakdown_list() {
+            [] => output.usage_breakdown().into_iter().collect(),
+            list => list.iter().collect::<Vec<_>>(),
+        };
+        let mut resources = breakdowns
+            .into_iter()
+            .map(|b| ResourceUsage {
+                name: b
+                    .display_name_plural()
+                    .or(b.display_name())
+                    .or(b.resource_type().map(|t| t.as_str()))
+                    .unwrap_or("Requests")
+                    .to_string(),
+                used: b.current_usage_with_precision().unwrap_or(b.current_usage() as f64),
+                limit: b.usage_limit_with_precision().unwrap_or(b.usage_limit() as f64),
+            })
+            .collect::<Vec<_>>();
+        // Older responses only have the totals
+        if resources.is_empty() {
+            resources = output
+                .limits()
+                .iter()
+                .map(|l| ResourceUsage {
+                    name: l.r#type().as_str().to_string(),
+                    used: l.current_usage() as f64,
+                    limit: l.total_usage_limit() as f64,
+                })
+                .collect();
+        }
+
+        Self {
+            subscription: output.subscription_info().map(|s| s.subscription_title().to_string()),
+            resources,
+            days_until_reset: output.days_until_reset(),
+        }
+    }
+
+    /// Percentage used of the resource closest to its limit.
+    pub fn percent_used(&self) -> Option<f64> {
+        self.resources.iter().map(ResourceUsage::percent_used).reduce(f64::max)
+    }
+}
+
+pub async fn fetch_quota(os: &Os) -> Result<QuotaUsage, ApiClientError> {
+    Ok(QuotaUsage::from_output(&os.client.get_usage_limits().await?))
+}
+
+/// Percentages of the monthly allowance at which to warn, in increasing order.
+pub fn quota_warning_thresholds(database: &Database) -> Vec<f64> {
+    let mut thresholds = match database.settings.get(Setting::ChatQuotaWarningThresholds) {
+        Some(serde_json::Value::Array(values)) => values.iter().filter_map(|v| v.as_f64()).collect(),
+        Some(value) => value.as_f64().into_iter().collect(),
+        None => DEFAULT_QUOTA_WARNING_THRESHOLDS.to_vec(),
+    };
+    thresholds.sort_by(f64::total_cmp);
+    thresholds
+}
+
+/// Returns the highest of `thresholds` reached by `percent`, unless it was already warned about.
+pub fn threshold_to_warn(thresholds: &[f64], percent: f64, warned: Option<f64>) -> Option<f64> {
+    thresholds
+        .iter()
+        .copied()
+        .filter(|threshold| percent >= *threshold)
+        .reduce(f64::max)
+        .filter(|threshold| warned.is_none_or(|warned| *threshold > warned))
+}
+
+fn format_amount(amount: f64) -> String {
+    if amount.fract() == 0.0 {
+        format!("{amount:.0}")
+    } else {
+        format!("{amount:.2}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_threshold_to_warn() {
+        let thresholds = [80.0, 95.0];
+        assert_eq!(threshold_to_warn(&thresholds, 50.0, None), None);
+        assert_eq!(threshold_to_warn(&thresholds, 82.0, None), Some(80.0));
+        assert_eq!(threshold_to_warn(&thresholds, 85.0, Some(80.0)), None);
+        assert_eq!(threshold_to_warn(&thresholds, 96.0, Some(80.0)), Some(95.0));
+        assert_eq!(threshold_to_warn(&thresholds, 100.0, Some(95.0)), None);
+        assert_eq!(threshold_to_warn(&[], 100.0, None), None);
+    }
+
+    #[tokio::test]
+    async fn test_quota_warning_thresholds() {
+        let mut database = Database::new().await.unwrap();
+        assert_eq!(quota_warning_thresholds(&database), vec![80.0, 95.0]);
+
+        database
+            .settings
+            .set(Setting::ChatQuotaWarningThresholds, serde_json::json!([90, 50]))
+            .await
+            .unwrap();
+        assert_eq!(quota_warning_thresholds(&database), vec![50.0, 90.0]);
+
+        database
+            .settings
+            .set(Setting::ChatQuotaWarningThresholds, 75)
+            .await
+            .unwrap();
+        assert_eq!(quota_warning_thresholds(&database), vec![75.0]);
+    }
+
+    #[test]
+    fn test_percent_used() {
+        let quota = QuotaUsage {
+            resources: vec![
+                ResourceUsage {
+                    name: "Requests".to_string(),
+                    used: 50.0,
+                    limit: 200.0,
+                },
+                ResourceUsage {
+                    name: "Credits".to_string(),
+                    used: 9.5,
+                    limit: 10.0,
+                },
+            ],
+            ..Default::default()
+        };
+        assert_eq!(quota.percent_used(), Some(95.0));
+        assert_eq!(QuotaUsage::default().percent_used(), None);
+    }
+}
@@ -0,0 +1,124 @@
+use clap::{
+    Args,
+    Subcommand,
+};
+use crossterm::{
+    execute,
+    style,
+};
+
+use crate::cli::chat::response_schema::ResponseSchema;
+use crate::cli::chat::{
+    ChatError,
+    ChatSession,
+    ChatState,
+};
+use crate::os::Os;
+use crate::theme::StyledText;
+
+/// Command-line arguments for managing the JSON Schema that final answers must match
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Args)]
+#[command(
+    before_long_help = "While a schema is set, the model is asked to give its final answers as a JSON document
+matching it. Answers that don't match are requested again along with the mismatches, up to
+chat.responseSchemaRetries times (2 by default).
+
+Example
+• /schema set ./answer.schema.json"
+)]
+pub struct SchemaArgs {
+    #[command(subcommand)]
+    subcommand: Option<SchemaSubcommand>,
+}
+
+impl SchemaArgs {
+    pub async fn execute(self, os: &Os, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        self.subcommand
+            .unwrap_or(SchemaSubcommand::Show)
+            .execute(os, session)
+            .await
+    }
+
+    pub fn subcommand_name(&self) -> Option<&'static str> {
+        self.subcommand.as_ref().map(|s| s.name())
+    }
+}
+
+/// Subcommands for managing the response schema
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Subcommand)]
+pub enum SchemaSubcommand {
+    /// Show the schema in use
+    Show,
+    /// Require final answers to match the JSON Schema in this file
+    Set {
+        /// Path of the JSON Schema file
+        path: String,
+    },
+    /// Stop requiring final answers to match a schema
+    Clear,
+}
+
+impl SchemaSubcommand {
+    pub async fn execute(self, os: &Os, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        match self {
+            Self::Show => match &session.response_schema {
+                Some(schema) => execute!(
+                    session.stderr,
+                    style::Print("\nFinal answers must match the schema at "),
+                    StyledText::brand_fg(),
+                    style::Print(schema.path().display()),
+                    StyledText::reset(),
+                    style::Print("\n\n"),
+                )?,
+                None => execute!(
+                    session.stderr,
+                    StyledText::secondary_fg(),
+                    style::Print("\nNo response schema is set. Use /schema set <path> to set one.\n\n"),
+                    StyledText::reset(),
+                )?,
+            },
+            Self::Set { path } => match ResponseSchema::load(os, &path).await {
+                Ok(schema) => {
+                    session.response_schema = Some(schema);
+                    session.response_schema_retries = 0;
+                    execute!(
+                        session.stderr,
+                        StyledText::success_fg(),
+                        style::Print(format!("\nFinal answers must now match the schema at {path}\n\n")),
+                        StyledText::reset(),
+                    )?;
+                },
+                Err(err) => execute!(
+                    session.stderr,
+                    StyledText::error_fg(),
+                    style::Print(format!("\n{err}\n\n")),
+                    StyledText::reset(),
+                )?,
+            },
+            Self::Clear => {
+                session.response_schema = None;
+                session.response_schema_retries = 0;
+                execute!(
+                    session.stderr,
+                    StyledText::success_fg(),
+                    style::Print("\nResponse schema cleared\n\n"),
+                    StyledText::reset(),
+                )?;
+            },
+        }
+
+        Ok(ChatState::PromptUser {
+            skip_printing_tools: true,
+        })
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Show => "show",
+            Self::Set { .. } => "set",
+            Self::Clear => "clear",
+        }
+    }
+}
@@ -1,5 +1,4 @@
 use clap::Args;
-use crossterm::style::Stylize;
 use crossterm::{
     cursor,
     execute,
@@ -87,7 +86,10 @@ impl SubscribeArgs {
             if is_remote() || crate::util::open::open_url_async(&url).await.is_err() {
                 execute!(
                     session.stderr,
-                    style::Print(format!("Open this URL to manage your subscription: {}\n\n", url.blue())),
+                    style::Print(format!(
+                        "Open this URL to manage your subscription: {}\n\n",
+                        StyledText::info(&url)
+                    )),
                     StyledText::reset(),
                     StyledText::reset(),
                 )?;
@@ -141,11 +143,11 @@ async fn upgrade_to_pro(os: &mut Os, session: &mut ChatSession) -> Result<(), Ch
 
     let prompt = format!(
         "{}{}{}{}{}",
-        "Would you like to open the AWS console to upgrade? [".dark_grey(),
-        "y".green(),
-        "/".dark_grey(),
-        "n".green(),
-        "]: ".dark_grey(),
+        StyledText::secondary("Would you like to open the AWS console to upgrade? ["),
+        StyledText::command("y"),
+        StyledText::secondary("/"),
+        StyledText::command("n"),
+        StyledText::secondary("]: "),
     );
 
     let user_input = session.read_user_input(&prompt, true);
@@ -177,8 +179,8 @@ async fn upgrade_to_pro(os: &mut Os, session: &mut ChatSession) -> Result<(), Ch
             StyledText::secondary_fg(),
             style::Print(format!(
                 "{} Having issues opening the AWS console? Try copy and pasting the URL > {}\n\n",
-                "?".magenta(),
-                url.blue()
+                StyledText::emphasis("?"),
+                StyledText::info(&url)
             )),
             StyledText::reset(),
         )?;
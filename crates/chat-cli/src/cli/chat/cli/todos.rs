@@ -51,12 +51,12 @@ pub struct TodoDisplayEntry {
 impl std::fmt::Display for TodoDisplayEntry {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         if self.num_completed == self.num_tasks {
-            write!(f, "{} {}", "✓".green().bold(), self.description.clone(),)
+            write!(f, "{} {}", StyledText::success("✓").bold(), self.description.clone(),)
         } else {
             write!(
                 f,
                 "{} {} ({}/{})",
-                "✗".red().bold(),
+                StyledText::error("✗").bold(),
                 self.description.clone(),
                 self.num_completed,
                 self.num_tasks
@@ -103,7 +103,7 @@ impl TodoSubcommand {
                 if cleared_one {
                     execute!(
                         session.stderr,
-                        style::Print("✔ Cleared finished to-do lists!\n".green())
+                        style::Print(StyledText::success("✔ Cleared finished to-do lists!\n"))
                     )?;
                 } else {
                     execute!(session.stderr, style::Print("No finished to-do lists to clear!\n"))?;
@@ -111,7 +111,10 @@ impl TodoSubcommand {
                 if !errors.is_empty() {
                     execute!(
                         session.stderr,
-                        style::Print(format!("* Failed to get {} todo list(s)\n", errors.len()).dark_grey())
+                        style::Print(StyledText::secondary(&format!(
+                            "* Failed to get {} todo list(s)\n",
+                            errors.len()
+                        )))
                     )?;
                 }
             },
@@ -125,7 +128,7 @@ impl TodoSubcommand {
                                 session.stderr,
                                 style::Print(format!(
                                     "{} {}",
-                                    "⟳ Resuming:".magenta(),
+                                    StyledText::emphasis("⟳ Resuming:"),
                                     entries[index].description.clone()
                                 ))
                             )?;
@@ -148,7 +151,7 @@ impl TodoSubcommand {
                                 session.stderr,
                                 style::Print(format!(
                                     "{} {}\n\n",
-                                    "Viewing:".magenta(),
+                                    StyledText::emphasis("Viewing:"),
                                     entries[index].description.clone()
                                 ))
                             )?;
@@ -171,7 +174,10 @@ impl TodoSubcommand {
                                 .await
                                 .map_err(|_e| ChatError::Custom("Could not delete all to-do lists".into()))?;
                         }
-                        execute!(session.stderr, style::Print("✔ Deleted all to-do lists!\n".green()),)?;
+                        execute!(
+                            session.stderr,
+                            style::Print(StyledText::success("✔ Deleted all to-do lists!\n")),
+                        )?;
                     } else if let Some(index) = fuzzy_select_todos(&entries, "Select a to-do list to delete:") {
                         if index < entries.len() {
                             delete_todo(os, &entries[index].id).await.map_err(|e| {
@@ -179,8 +185,8 @@ impl TodoSubcommand {
                             })?;
                             execute!(
                                 session.stderr,
-                                style::Print("✔ Deleted to-do list: ".green()),
-                                style::Print(format!("{}\n", entries[index].description.clone().dark_grey()))
+                                style::Print(StyledText::success("✔ Deleted to-do list: ")),
+                                style::Print(format!("{}\n", StyledText::secondary(&entries[index].description)))
                             )?;
                         }
                     }
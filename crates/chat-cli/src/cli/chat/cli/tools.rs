@@ -371,7 +371,17 @@ impl ToolsSubcommand {
                 session.conversation.agents.trust_all_tools = false;
 
                 let active_agent_path = session.conversation.agents.get_active().and_then(|a| a.path.clone());
-                if let Some(path) = active_agent_path {
+                let active_profile = session
+                    .conversation
+                    .agents
+                    .get_active()
+                    .and_then(|a| a.active_permission_profile.clone());
+                if let Some(profile) = active_profile {
+                    // Reset to the permission levels of the profile in use rather than the agent's
+                    if let Some(active_agent) = session.conversation.agents.get_active_mut() {
+                        let _ = active_agent.use_permission_profile(&profile);
+                    }
+                } else if let Some(path) = active_agent_path {
                     let result = async {
                         let content = tokio::fs::read(&path).await?;
                         let orig_agent = serde_json::from_slice::<Agent>(&content)?;
@@ -21,6 +21,8 @@ pub struct DetailedUsageData {
     pub tools_tokens: TokenCount,
     pub context_window_size: usize,
     pub dropped_context_files: Vec<(String, String)>,
+    /// Tokens used by each category of content sent to the model, largest first
+    pub breakdown: Vec<(&'static str, TokenCount)>,
 }
 
 /// Arguments for the usage command that displays token usage statistics and context window
@@ -15,6 +15,8 @@ pub(super) async fn get_detailed_usage_data(
     os: &Os,
 ) -> Result<super::DetailedUsageData, ChatError> {
     let context_window_size = context_window_tokens(session.conversation.model_info.as_ref());
+    let model_id = session.conversation.model_id().map(str::to_string);
+    let model_id = model_id.as_deref();
 
     let state = session
         .conversation
@@ -29,17 +31,40 @@ pub(super) async fn get_detailed_usage_data(
         .collect::<Vec<String>>()
         .join("");
     let tools_char_count: CharCount = tool_specs_json.len().into();
-    let total_tokens: TokenCount =
-        (data.context_messages + data.user_messages + data.assistant_messages + tools_char_count).into();
+    let total_tokens = TokenCount::for_model(
+        model_id,
+        data.context_messages + data.user_messages + data.assistant_messages + tools_char_count,
+    );
+
+    let context = state.context_breakdown;
+    let mut breakdown: Vec<(&'static str, TokenCount)> = [
+        ("Instructions", context.instructions),
+        ("Conversation summary", context.summary),
+        ("Agent resources", context.agent_resources),
+        ("Session context", context.session_context),
+        ("MCP resources", context.mcp_resources),
+        ("Hooks", context.hooks),
+        ("Git context", context.git),
+        ("Workspace index", context.workspace_index),
+        ("Delegate handoffs", context.handoffs),
+        ("Conversation history", *data.user_messages + *data.assistant_messages),
+        ("Tool specs", *tools_char_count),
+    ]
+    .into_iter()
+    .filter(|(_, chars)| *chars > 0)
+    .map(|(name, chars)| (name, TokenCount::for_model(model_id, chars.into())))
+    .collect();
+    breakdown.sort_by(|a, b| b.1.value().cmp(&a.1.value()));
 
     Ok(super::DetailedUsageData {
         total_tokens,
-        context_tokens: data.context_messages.into(),
-        assistant_tokens: data.assistant_messages.into(),
-        user_tokens: data.user_messages.into(),
-        tools_tokens: tools_char_count.into(),
+        context_tokens: TokenCount::for_model(model_id, data.context_messages),
+        assistant_tokens: TokenCount::for_model(model_id, data.assistant_messages),
+        user_tokens: TokenCount::for_model(model_id, data.user_messages),
+        tools_tokens: TokenCount::for_model(model_id, tools_char_count),
         context_window_size,
         dropped_context_files: state.dropped_context_files,
+        breakdown,
     })
 }
 
@@ -5,10 +5,12 @@ use crossterm::{
     style,
 };
 
+use crate::cli::chat::cost::Cost;
 use crate::cli::chat::token_counter::TokenCount;
 use crate::cli::chat::{
     ChatError,
     ChatSession,
+    accessible,
 };
 use crate::theme::StyledText;
 
@@ -17,6 +19,99 @@ fn calculate_usage_percentage(tokens: TokenCount, context_window_size: usize) ->
     (tokens.value() as f32 / context_window_size as f32) * 100.0
 }
 
+/// Width of the bars of the breakdown chart
+const BREAKDOWN_BAR_WIDTH: usize = 30;
+
+/// Render the tokens used by each category of content as a bar chart, with bars relative to the
+/// total usage
+fn render_breakdown(usage_data: &super::DetailedUsageData, session: &mut ChatSession) -> Result<(), ChatError> {
+    if usage_data.breakdown.is_empty() {
+        return Ok(());
+    }
+
+    queue!(
+        session.stderr,
+        style::SetAttribute(Attribute::Bold),
+        style::Print("Breakdown:\n"),
+        StyledText::reset_attributes(),
+    )?;
+    let name_width = usage_data
+        .breakdown
+        .iter()
+        .map(|(name, _)| name.len())
+        .max()
+        .unwrap_or_default();
+    let total = usage_data.total_tokens.value().max(1);
+    for (name, tokens) in &usage_data.breakdown {
+        let width = (tokens.value() * BREAKDOWN_BAR_WIDTH)
+            .div_ceil(total)
+            .min(BREAKDOWN_BAR_WIDTH);
+        queue!(
+            session.stderr,
+            style::Print(format!("  {name:<name_width$}  ")),
+            StyledText::brand_fg(),
+            style::Print("█".repeat(width)),
+            StyledText::secondary_fg(),
+            style::Print("░".repeat(BREAKDOWN_BAR_WIDTH - width)),
+            StyledText::reset(),
+            style::Print(format!(
+                "  ~{} tokens ({:.2}%)\n",
+                tokens,
+                calculate_usage_percentage(*tokens, usage_data.context_window_size)
+            )),
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Render the tokens used by the requests sent in this session and their estimated cost
+fn render_session_cost(session: &mut ChatSession) -> Result<(), ChatError> {
+    let cost = &session.cost;
+    if cost.requests == 0 {
+        return Ok(());
+    }
+
+    let estimated = if cost.estimated { " (partly estimated)" } else { "" };
+    let mut lines = vec![format!(
+        "  {} requests, {} input and {} output tokens{estimated}\n",
+        cost.requests, cost.input_tokens, cost.output_tokens
+    )];
+    if cost.is_priced() {
+        lines.push(format!(
+            "  Last turn: ~{}, session: ~{}\n",
+            Cost(cost.turn_cost),
+            Cost(cost.total_cost)
+        ));
+    }
+    if cost.unpriced_requests > 0 {
+        lines.push(format!(
+            "  {} requests were sent to models without known pricing and aren't included\n",
+            cost.unpriced_requests
+        ));
+    }
+
+    queue!(
+        session.stderr,
+        style::SetAttribute(Attribute::Bold),
+        style::Print("\nSession cost:\n"),
+        StyledText::reset_attributes(),
+    )?;
+    for line in lines {
+        queue!(session.stderr, style::Print(line))?;
+    }
+    if let Some(limit) = session.max_cost {
+        queue!(
+            session.stderr,
+            StyledText::secondary_fg(),
+            style::Print(format!("  Limited to {} with --max-cost\n", Cost(limit.0))),
+            StyledText::reset(),
+        )?;
+    }
+
+    Ok(())
+}
+
 /// Render context window information section
 pub async fn render_context_window(
     usage_data: &super::DetailedUsageData,
@@ -164,10 +259,13 @@ pub async fn render_context_window(
         )),
     )?;
 
+    render_breakdown(usage_data, session)?;
+    render_session_cost(session)?;
+
     queue!(
         session.stderr,
         style::SetAttribute(Attribute::Bold),
-        style::Print("\n💡 Pro Tips:\n"),
+        style::Print(format!("\n{}Pro Tips:\n", accessible::symbol("💡 ", ""))),
         StyledText::reset_attributes(),
         StyledText::secondary_fg(),
         style::Print("Run "),
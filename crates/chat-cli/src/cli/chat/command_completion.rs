@@ -0,0 +1,175 @@
+//! Completion of the subcommands, flags and arguments of slash commands, derived from the clap
+//! definition of [SlashCommand] so that it follows the commands as they change.
+
+use clap::{
+    Arg,
+    Command,
+    CommandFactory,
+};
+
+use super::cli::SlashCommand;
+
+/// Arguments of slash commands completed with names only known while chatting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DynamicArgument {
+    ToolName,
+    PromptName,
+}
+
+/// What the word being typed in a slash command completes to.
+#[derive(Debug, PartialEq, Eq)]
+pub enum CommandCompletion {
+    /// Subcommands, flags or values of the command
+    Words(Vec<String>),
+    Dynamic(DynamicArgument),
+}
+
+/// Whether the positional argument at `index` of the command at `path` is completed with
+/// [DynamicArgument] names.
+fn dynamic_argument(path: &[&str], index: usize) -> Option<DynamicArgument> {
+    match (path, index) {
+        (["tools", "trust" | "untrust"], _) => Some(DynamicArgument::ToolName),
+        (["prompts", "get" | "details"], 0) => Some(DynamicArgument::PromptName),
+        _ => None,
+    }
+}
+
+pub struct CommandCompleter {
+    command: Command,
+}
+
+impl CommandCompleter {
+    pub fn new() -> Self {
+        Self {
+            command: SlashCommand::command(),
+        }
+    }
+
+    /// Completes `word`, typed after the `preceding` words of a slash command, [None] if it's a
+    /// free-form argument such as a path.
+    pub fn complete(&self, preceding: &[&str], word: &str) -> Option<CommandCompletion> {
+        let (name, args) = preceding.split_first()?;
+        let mut command = self.command.find_subcommand(name.strip_prefix('/')?)?;
+        let mut path = vec![command.get_name()];
+        let mut positionals = 0;
+        let mut value_of = None;
+        for arg in args {
+            if value_of.take().is_some() {
+                continue;
+            }
+            if arg.starts_with('-') {
+                value_of = (!arg.contains('=')).then(|| find_flag(command, arg)).flatten();
+                continue;
+            }
+            match command.find_subcommand(arg) {
+                Some(subcommand) if positionals == 0 => {
+                    command = subcommand;
+                    path.push(command.get_name());
+                },
+                _ => positionals += 1,
+            }
+        }
+
+        let words: Vec<String> = if let Some(arg) = value_of {
+            possible_values(arg)
+        } else if word.starts_with('-') {
+            command
+                .get_arguments()
+                .filter(|arg| !arg.is_positional() && !arg.is_hide_set())
+                .flat_map(|arg| {
+                    let long = arg.get_long().map(|long| format!("--{long}"));
+                    let short = arg.get_short().map(|short| format!("-{short}"));
+                    long.into_iter().chain(short)
+                })
+                .collect()
+        } else if positionals == 0 && command.has_subcommands() {
+            command
+                .get_subcommands()
+                .filter(|subcommand| !subcommand.is_hide_set())
+                .map(|subcommand| subcommand.get_name().to_string())
+                .collect()
+        } else if let Some(dynamic) = dynamic_argument(&path, positionals) {
+            return Some(CommandCompletion::Dynamic(dynamic));
+        } else {
+            let mut args = command.get_positionals();
+            let arg = args.nth(positionals).or_else(|| {
+                command
+                    .get_positionals()
+                    .last()
+                    .filter(|arg| arg.get_num_args().is_some_and(|range| range.max_values() > 1))
+            });
+            arg.map(possible_values).unwrap_or_default()
+        };
+
+        let words: Vec<String> = words.into_iter().filter(|w| w.starts_with(word)).collect();
+        (!words.is_empty()).then_some(CommandCompletion::Words(words))
+    }
+}
+
+/// The flag `name` of `command`, if it takes a value.
+fn find_flag<'a>(command: &'a Command, name: &str) -> Option<&'a Arg> {
+    command
+        .get_arguments()
+        .find(|arg| match name.strip_prefix("--") {
+            Some(long) => arg.get_long() == Some(long),
+            None => name.strip_prefix('-').and_then(|short| short.chars().next()) == arg.get_short(),
+        })
+        .filter(|arg| arg.get_action().takes_values())
+}
+
+fn possible_values(arg: &Arg) -> Vec<String> {
+    arg.get_possible_values()
+        .iter()
+        .filter(|value| !value.is_hide_set())
+        .map(|value| value.get_name().to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(preceding: &[&str], word: &str) -> Vec<String> {
+        match CommandCompleter::new().complete(preceding, word) {
+            Some(CommandCompletion::Words(words)) => words,
+            other => panic!("expected words, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_complete_subcommands() {
+        assert_eq!(words(&["/tools"], "tr"), vec!["trust", "trust-all"]);
+        assert!(words(&["/context"], "").contains(&"add".to_string()));
+        assert_eq!(words(&["/prompts"], "ge"), vec!["get"]);
+        assert!(CommandCompleter::new().complete(&["/unknown"], "").is_none());
+    }
+
+    #[test]
+    fn test_complete_flags() {
+        assert_eq!(words(&["/context", "show"], "--e"), vec!["--expand"]);
+        assert!(words(&["/prompts", "create"], "-").contains(&"--global".to_string()));
+        // Hidden flags aren't completed
+        assert_eq!(CommandCompleter::new().complete(&["/prompts", "get"], "--"), None);
+    }
+
+    #[test]
+    fn test_complete_dynamic_arguments() {
+        let completer = CommandCompleter::new();
+        assert_eq!(
+            completer.complete(&["/tools", "trust"], ""),
+            Some(CommandCompletion::Dynamic(DynamicArgument::ToolName))
+        );
+        assert_eq!(
+            completer.complete(&["/tools", "untrust", "fs_read"], "fs"),
+            Some(CommandCompletion::Dynamic(DynamicArgument::ToolName))
+        );
+        assert_eq!(
+            completer.complete(&["/prompts", "get"], "re"),
+            Some(CommandCompletion::Dynamic(DynamicArgument::PromptName))
+        );
+        // Only the name of the prompt, not its arguments
+        assert_eq!(completer.complete(&["/prompts", "get", "review"], "ma"), None);
+        // Paths are left to the path completion
+        assert_eq!(completer.complete(&["/context", "add"], "src"), None);
+    }
+}
@@ -18,6 +18,9 @@ pub const MAX_NUMBER_OF_IMAGES_PER_REQUEST: usize = 10;
 /// In bytes - 10 MB
 pub const MAX_IMAGE_SIZE: usize = 10 * 1024 * 1024;
 
+/// In pixels - images attached to a message are downscaled so that neither side exceeds this
+pub const MAX_IMAGE_DIMENSION: u32 = 8000;
+
 pub const AGENT_FORMAT_HOOKS_DOC_URL: &str =
     "https://github.com/aws/amazon-q-developer-cli/blob/main/docs/agent-format.md#hooks-field";
 
@@ -16,15 +16,29 @@ use serde::{
 
 use super::cli::hooks::HookOutput;
 use super::cli::model::context_window_tokens;
-use super::util::drop_matched_context_files;
+use super::context_budget::{
+    ContextBudget,
+    fit_to_budget,
+};
+use super::directory_summary::summarize_directory;
+use super::stale_context::StaleContextTracker;
+use super::symbol_context::SymbolContext;
+use super::template_vars::interpolate;
+use super::token_counter::TokenCounter;
+use super::url_context::UrlContext;
 use crate::cli::agent::Agent;
 use crate::cli::agent::hook::{
     Hook,
+    HookPolicy,
     HookTrigger,
 };
 use crate::cli::chat::ChatError;
-use crate::cli::chat::cli::hooks::HookExecutor;
+use crate::cli::chat::cli::hooks::{
+    HookExecutor,
+    hook_matches_notification,
+};
 use crate::cli::chat::cli::model::ModelInfo;
+use crate::mcp_client::substitute_env_vars;
 use crate::os::Os;
 
 #[derive(Debug, Clone)]
@@ -35,14 +49,25 @@ pub enum ContextFilePath {
     Session(String),
 }
 
+/// Agent paths are serialized as plain strings, which is also how conversations saved before
+/// session paths were told apart are read. Session paths are serialized as `{"session": path}`.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum SerializedContextFilePath {
+    Agent(String),
+    Session { session: String },
+}
+
 impl Serialize for ContextFilePath {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
         match self {
-            ContextFilePath::Agent(path) | ContextFilePath::Session(path) => path.serialize(serializer),
+            ContextFilePath::Agent(path) => SerializedContextFilePath::Agent(path.clone()),
+            ContextFilePath::Session(path) => SerializedContextFilePath::Session { session: path.clone() },
         }
+        .serialize(serializer)
     }
 }
 
@@ -51,8 +76,10 @@ impl<'de> Deserialize<'de> for ContextFilePath {
     where
         D: Deserializer<'de>,
     {
-        let path = String::deserialize(deserializer)?;
-        Ok(ContextFilePath::Agent(path))
+        Ok(match SerializedContextFilePath::deserialize(deserializer)? {
+            SerializedContextFilePath::Agent(path) => ContextFilePath::Agent(path),
+            SerializedContextFilePath::Session { session } => ContextFilePath::Session(session),
+        })
     }
 }
 
@@ -89,6 +116,23 @@ impl PartialEq<ContextFilePath> for String {
     }
 }
 
+/// The context entries added during a session, as opposed to the ones coming from the agent.
+///
+/// Snapshots are carried over when a conversation is resumed, and can be shared between sessions
+/// with /context export and /context import.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ContextSnapshot {
+    /// Paths or glob patterns added with /context add.
+    pub paths: Vec<String>,
+    /// Web pages added with /context add-url.
+    pub urls: Vec<UrlContext>,
+    /// Symbols added with /context add-symbol.
+    pub symbols: Vec<SymbolContext>,
+    /// How the context size limit is split between the sources of context.
+    pub budget: ContextBudget,
+}
+
 /// Manager for context files and profiles.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ContextManager {
@@ -99,8 +143,36 @@ pub struct ContextManager {
     pub paths: Vec<ContextFilePath>,
     /// Map of Hook Name to [`Hook`]. The hook name serves as the hook's ID.
     pub hooks: HashMap<HookTrigger, Vec<Hook>>,
+    /// How the results of the hooks matching a trigger are combined, and how long they may run.
+    #[serde(default)]
+    pub hook_policy: HookPolicy,
     #[serde(skip)]
     pub hook_executor: HookExecutor,
+    /// Web pages added with /context add-url.
+    #[serde(default)]
+    pub urls: Vec<UrlContext>,
+    /// Symbols added with /context add-symbol.
+    #[serde(default)]
+    pub symbols: Vec<SymbolContext>,
+    /// Tracks which of the entries added during the session the model still uses.
+    #[serde(default)]
+    pub stale_context: StaleContextTracker,
+    /// Text of the resources embedded in the MCP prompts used during the session, by uri.
+    #[serde(default)]
+    pub mcp_resources: Vec<(String, String)>,
+    /// How the context size limit is split between the sources of context.
+    #[serde(default)]
+    pub budget: ContextBudget,
+    /// Hook output sent along with the last request, before it was fit to its budget.
+    #[serde(skip)]
+    pub hook_outputs: Vec<String>,
+    /// Content of the context files as they were last sent to the model, used to point out the
+    /// files that changed in between turns. [None] until context has been sent at least once.
+    #[serde(skip)]
+    sent_files: Option<HashMap<String, String>>,
+    /// Content of the context files included in the request currently being built.
+    #[serde(skip)]
+    pending_files: HashMap<String, String>,
 }
 
 impl ContextManager {
@@ -117,10 +189,35 @@ impl ContextManager {
             current_profile: agent.name.clone(),
             paths,
             hooks: agent.hooks.clone(),
+            hook_policy: agent.hook_policy.clone(),
             hook_executor: HookExecutor::new(),
+            urls: Vec::new(),
+            symbols: Vec::new(),
+            stale_context: StaleContextTracker::default(),
+            mcp_resources: Vec::new(),
+            budget: ContextBudget::default(),
+            hook_outputs: Vec::new(),
+            sent_files: None,
+            pending_files: HashMap::new(),
         })
     }
 
+    /// Records `files` as the context files of the request being built, and returns a description
+    /// of how they changed since context files were last sent to the model, if they did.
+    ///
+    /// The files are only considered sent once [Self::mark_context_files_sent] is called.
+    pub fn track_context_file_changes(&mut self, files: &[(String, String)]) -> Option<String> {
+        self.pending_files = files.iter().cloned().collect();
+        self.sent_files
+            .as_ref()
+            .and_then(|sent_files| describe_context_file_changes(sent_files, files))
+    }
+
+    /// Marks the context files of the last built request as seen by the model.
+    pub fn mark_context_files_sent(&mut self) {
+        self.sent_files = Some(std::mem::take(&mut self.pending_files));
+    }
+
     /// Add paths to the context configuration.
     ///
     /// # Arguments
@@ -138,7 +235,7 @@ impl ContextManager {
             for path in &paths {
                 // We're using a temporary context_files vector just for validation
                 // Pass is_validation=true to ensure we error if glob patterns don't match any files
-                match process_path(os, path, &mut context_files, true).await {
+                match process_path(os, path, &mut context_files, true, true).await {
                     Ok(_) => {}, // Path is valid
                     Err(e) => return Err(eyre!("Invalid path '{}': {}. Use --force to add anyway.", path, e)),
                 }
@@ -167,11 +264,14 @@ impl ContextManager {
     /// A Result indicating success or an error
     pub fn remove_paths(&mut self, paths: Vec<String>) -> Result<()> {
         // Remove each path if it exists
-        let old_path_num = self.paths.len();
+        let entry_count = |cm: &Self| cm.paths.len() + cm.urls.len() + cm.symbols.len();
+        let old_path_num = entry_count(self);
         self.paths
             .retain(|p| !paths.iter().any(|path| path.as_str() == p.get_path_as_str()));
+        self.urls.retain(|u| !paths.iter().any(|path| u.matches(path)));
+        self.symbols.retain(|s| !paths.iter().any(|path| s.matches(path)));
 
-        if old_path_num == self.paths.len() {
+        if old_path_num == entry_count(self) {
             return Err(eyre!("None of the specified paths were found in the context"));
         }
 
@@ -181,6 +281,153 @@ impl ContextManager {
     /// Clear all paths from the context configuration.
     pub fn clear(&mut self) {
         self.paths.clear();
+        self.urls.clear();
+        self.symbols.clear();
+        self.mcp_resources.clear();
+    }
+
+    /// Adds the text of an MCP resource to the context, replacing the one previously added from
+    /// the same uri.
+    pub fn add_mcp_resource(&mut self, uri: String, text: String) {
+        match self.mcp_resources.iter_mut().find(|(u, _)| *u == uri) {
+            Some((_, existing)) => *existing = text,
+            None => self.mcp_resources.push((uri, text)),
+        }
+    }
+
+    /// Returns the context entries added during the session.
+    pub fn snapshot(&self) -> ContextSnapshot {
+        ContextSnapshot {
+            paths: self
+                .paths
+                .iter()
+                .filter(|p| matches!(p, ContextFilePath::Session(_)))
+                .map(|p| p.get_path_as_str().to_string())
+                .collect(),
+            urls: self.urls.clone(),
+            symbols: self.symbols.clone(),
+            budget: self.budget,
+        }
+    }
+
+    /// Adds the entries of `snapshot` to the context, skipping paths that are already present.
+    ///
+    /// # Returns
+    /// The number of paths, pages and symbols added
+    pub fn restore(&mut self, snapshot: ContextSnapshot) -> usize {
+        let mut added = 0;
+        for path in snapshot.paths {
+            if !self.paths.iter().any(|p| p == path.as_str()) {
+                self.paths.push(ContextFilePath::Session(path));
+                added += 1;
+            }
+        }
+        for url in snapshot.urls {
+            if !self.add_url(url) {
+                added += 1;
+            }
+        }
+        for symbol in snapshot.symbols {
+            if self.add_symbol(symbol).is_ok() {
+                added += 1;
+            }
+        }
+        self.budget = snapshot.budget;
+        added
+    }
+
+    /// Adds a web page to the context, replacing the page previously added from the same url.
+    ///
+    /// # Returns
+    /// Whether a previously added page was replaced
+    pub fn add_url(&mut self, url_context: UrlContext) -> bool {
+        match self.urls.iter_mut().find(|u| u.url == url_context.url) {
+            Some(existing) => {
+                *existing = url_context;
+                true
+            },
+            None => {
+                self.urls.push(url_context);
+                false
+            },
+        }
+    }
+
+    /// Adds a symbol to the context.
+    pub fn add_symbol(&mut self, symbol: SymbolContext) -> Result<()> {
+        if self.symbols.contains(&symbol) {
+            return Err(eyre!("Symbol '{}' already exists.", symbol.spec()));
+        }
+        self.symbols.push(symbol);
+        Ok(())
+    }
+
+    /// Extracts the definitions of the symbols added to the context. Symbols that can no longer
+    /// be extracted, e.g. because they were renamed, are listed with the reason instead.
+    pub async fn get_symbol_files(&self, os: &Os) -> Vec<(String, String)> {
+        let mut symbol_files = Vec::new();
+        for symbol in &self.symbols {
+            symbol_files.push(match symbol.extract(os).await {
+                Ok(file) => file,
+                Err(e) => (symbol.spec(), format!("Could not extract {}: {}", symbol.spec(), e)),
+            });
+        }
+        symbol_files
+    }
+
+    /// Returns the entries added during the session, keyed by the name they are removed with,
+    /// along with the strings whose presence in the model output means the entry was used: the
+    /// names of the files it matches, relative to the current directory and absolute, or the url
+    /// and name of a page.
+    async fn session_entry_references(&self, os: &Os) -> Vec<(String, Vec<String>)> {
+        let cwd = os.env.current_dir().ok().map(|cwd| os.fs.chroot_path(cwd));
+        let mut entries = Vec::new();
+
+        for path in self.paths.iter().filter(|p| matches!(p, ContextFilePath::Session(_))) {
+            let mut references = vec![path.get_path_as_str().to_string()];
+            for (name, _) in self.get_context_files_by_path(os, path).await.unwrap_or_default() {
+                if let Some(relative) = cwd
+                    .as_ref()
+                    .and_then(|cwd| std::path::Path::new(&name).strip_prefix(cwd).ok())
+                {
+                    references.push(relative.to_string_lossy().to_string());
+                }
+                references.push(name);
+            }
+            entries.push((path.get_path_as_str().to_string(), references));
+        }
+        for url in &self.urls {
+            entries.push((url.url.clone(), vec![url.url.clone(), url.name.clone()]));
+        }
+        for symbol in &self.symbols {
+            entries.push((symbol.spec(), vec![
+                symbol.spec(),
+                symbol.path.clone(),
+                symbol.symbol.clone(),
+            ]));
+        }
+        entries
+    }
+
+    /// Starts a new turn of [Self::stale_context], removing the entries the model hasn't used for
+    /// a while if auto eviction is on.
+    ///
+    /// # Returns
+    /// The entries that were removed, and the entries that should be proposed for removal
+    pub async fn evict_stale_entries(&mut self, os: &Os) -> (Vec<String>, Vec<String>) {
+        let entries = self.session_entry_references(os).await;
+        let stale = self.stale_context.next_turn(&entries);
+        if stale.is_empty() {
+            return (Vec::new(), Vec::new());
+        }
+
+        if self.stale_context.auto_evict {
+            let _ = self.remove_paths(stale.clone());
+            self.stale_context.record_evicted(&stale);
+            (stale, Vec::new())
+        } else {
+            (Vec::new(), self.stale_context.take_new_proposals(stale))
+        }
     }
 
     /// Get all context files (global + profile-specific).
@@ -201,30 +448,97 @@ impl ContextManager {
 
         context_files.sort_by(|a, b| a.0.cmp(&b.0));
         context_files.dedup_by(|a, b| a.0 == b.0);
+        context_files.extend(self.urls.iter().map(|u| (u.entry_name(), u.content.clone())));
+        context_files.extend(self.get_symbol_files(os).await);
 
         Ok(context_files)
     }
 
-    pub async fn get_context_files_by_path(&self, os: &Os, path: &str) -> Result<Vec<(String, String)>> {
+    pub async fn get_context_files_by_path(&self, os: &Os, path: &ContextFilePath) -> Result<Vec<(String, String)>> {
         let mut context_files = Vec::new();
-        process_path(os, path, &mut context_files, true).await?;
+        let summarize_dirs = matches!(path, ContextFilePath::Session(_));
+        process_path(os, path.get_path_as_str(), &mut context_files, true, summarize_dirs).await?;
         Ok(context_files)
     }
 
-    /// Collects context files and optionally drops files if the total size exceeds the limit.
-    /// Returns (files_to_use, dropped_files)
+    /// Returns the context files matched by the agent's resources and the ones matched by paths
+    /// added during the session, in that order. Files matched by both only appear in the former.
+    pub async fn get_context_files_by_source(&self, os: &Os) -> Result<(Vec<(String, String)>, Vec<(String, String)>)> {
+        let (agent_paths, session_paths) = self
+            .paths
+            .iter()
+            .cloned()
+            .partition::<Vec<_>, _>(|p| matches!(p, ContextFilePath::Agent(_)));
+
+        let mut agent_files = Vec::new();
+        self.collect_context_files(os, &agent_paths, &mut agent_files).await?;
+        agent_files.sort_by(|a, b| a.0.cmp(&b.0));
+        agent_files.dedup_by(|a, b| a.0 == b.0);
+
+        let mut session_files = Vec::new();
+        self.collect_context_files(os, &session_paths, &mut session_files)
+            .await?;
+        session_files.sort_by(|a, b| a.0.cmp(&b.0));
+        session_files.dedup_by(|a, b| a.0 == b.0);
+        session_files.retain(|(name, _)| !agent_files.iter().any(|(agent_name, _)| agent_name == name));
+        session_files.extend(self.urls.iter().map(|u| (u.entry_name(), u.content.clone())));
+        session_files.extend(self.get_symbol_files(os).await);
+
+        Ok((agent_files, session_files))
+    }
+
+    /// Collects context files, keeping each source of context within its share of the size limit
+    /// as configured by [Self::budget]. Files are truncated to fit, or dropped if too little of
+    /// them would be left. `hook_outputs` is the hook output sent along with the files, and is
+    /// truncated in place.
+    ///
+    /// Returns (files_to_use, dropped_files, size_by_source), where the sizes of the files and
+    /// hook output kept for each source are indexed like `ContextSource::ALL`.
     pub async fn collect_context_files_with_limit(
         &self,
         os: &Os,
-    ) -> Result<(Vec<(String, String)>, Vec<(String, String)>)> {
-        let mut files = self.get_context_files(os).await?;
-
-        let dropped_files = drop_matched_context_files(&mut files, self.max_context_files_size).unwrap_or_default();
-
-        // remove dropped files from files
-        files.retain(|file| !dropped_files.iter().any(|dropped| dropped.0 == file.0));
+        hook_outputs: &mut [&mut String],
+    ) -> Result<(Vec<(String, String)>, Vec<(String, String)>, [usize; 4])> {
+        let (mut agent_files, mut session_files) = self.get_context_files_by_source(os).await?;
+        let mut mcp_resources = self.mcp_resources.clone();
+
+        let size_of = |files: &[(String, String)]| files.iter().map(|(_, content)| content.len()).sum::<usize>();
+        let usage = [
+            size_of(&agent_files),
+            size_of(&session_files),
+            size_of(&mcp_resources),
+            hook_outputs.iter().map(|output| output.len()).sum(),
+        ];
+        let allocated = self
+            .budget
+            .allocate(TokenCounter::token_to_chars(self.max_context_files_size), usage);
+
+        let mut dropped_files = fit_to_budget(&mut agent_files, allocated[0], true);
+        dropped_files.extend(fit_to_budget(&mut session_files, allocated[1], true));
+        dropped_files.extend(fit_to_budget(&mut mcp_resources, allocated[2], true));
+
+        let mut hooks = hook_outputs
+            .iter()
+            .enumerate()
+            .map(|(i, output)| (i.to_string(), (*output).clone()))
+            .collect::<Vec<_>>();
+        fit_to_budget(&mut hooks, allocated[3], false);
+        for (output, (_, truncated)) in hook_outputs.iter_mut().zip(hooks) {
+            **output = truncated;
+        }
 
-        Ok((files, dropped_files))
+        let size_by_source = [
+            size_of(&agent_files),
+            size_of(&session_files),
+            size_of(&mcp_resources),
+            hook_outputs.iter().map(|output| output.len()).sum(),
+        ];
+        let mut files = agent_files;
+        files.extend(session_files);
+        files.extend(mcp_resources);
+        files.sort_by(|a, b| a.0.cmp(&b.0));
+
+        Ok((files, dropped_files, size_by_source))
     }
 
     async fn collect_context_files(
@@ -234,8 +548,14 @@ impl ContextManager {
         context_files: &mut Vec<(String, String)>,
     ) -> Result<()> {
         for path in paths {
-            // Use is_validation=false to handle non-matching globs gracefully
-            process_path(os, path.get_path_as_str(), context_files, false).await?;
+            // Use is_validation=false to handle non-matching globs gracefully. Directories added
+            // during the session are summarized rather than inlined.
+            let summarize_dirs = matches!(path, ContextFilePath::Session(_));
+            let path = match path {
+                ContextFilePath::Agent(path) => interpolate(os, path).await,
+                ContextFilePath::Session(path) => path.clone(),
+            };
+            process_path(os, &path, context_files, false, summarize_dirs).await?;
         }
         Ok(())
     }
@@ -251,13 +571,115 @@ impl ContextManager {
         prompt: Option<&str>,
         tool_context: Option<crate::cli::chat::cli::hooks::ToolContext>,
     ) -> Result<Vec<((HookTrigger, Hook), HookOutput)>, ChatError> {
-        let mut hooks = self.hooks.clone();
-        hooks.retain(|t, _| *t == trigger);
+        self.run_hooks_with_input(trigger, output, os, prompt, tool_context, None)
+            .await
+    }
+
+    /// Same as [Self::run_hooks], adding the fields of `event_input` to the hook event.
+    pub async fn run_hooks_with_input(
+        &mut self,
+        trigger: HookTrigger,
+        output: &mut impl Write,
+        os: &crate::os::Os,
+        prompt: Option<&str>,
+        tool_context: Option<crate::cli::chat::cli::hooks::ToolContext>,
+        event_input: Option<&serde_json::Value>,
+    ) -> Result<Vec<((HookTrigger, Hook), HookOutput)>, ChatError> {
+        let hooks = self.hooks_for(trigger, os, event_input);
         let cwd = os.env.current_dir()?.to_string_lossy().to_string();
         self.hook_executor
-            .run_hooks(hooks, output, &cwd, prompt, tool_context)
+            .run_hooks(hooks, output, &cwd, prompt, tool_context, event_input)
             .await
     }
+
+    /// Starts the hooks of `trigger` in the background without waiting for them, whether they
+    /// are blocking or not, for the events the session shouldn't wait on.
+    pub fn spawn_hooks_with_input(
+        &self,
+        trigger: HookTrigger,
+        os: &crate::os::Os,
+        event_input: &serde_json::Value,
+    ) -> Result<(), ChatError> {
+        let cwd = os.env.current_dir()?.to_string_lossy().to_string();
+        for (trigger, hooks) in self.hooks_for(trigger, os, Some(event_input)) {
+            for hook in hooks {
+                HookExecutor::spawn_non_blocking_hook((trigger, hook), &cwd, None, None, Some(event_input));
+            }
+        }
+        Ok(())
+    }
+
+    /// The hooks of `trigger` matching `event_input`, with their environment substituted.
+    fn hooks_for(
+        &self,
+        trigger: HookTrigger,
+        os: &crate::os::Os,
+        event_input: Option<&serde_json::Value>,
+    ) -> HashMap<HookTrigger, Vec<Hook>> {
+        let mut hooks = self.hooks.clone();
+        hooks.retain(|t, _| *t == trigger);
+        if let Some(notification_type) = event_input.and_then(|input| input["notification_type"].as_str()) {
+            for hooks in hooks.values_mut() {
+                hooks.retain(|hook| hook_matches_notification(hook, notification_type));
+            }
+        }
+        for hook in hooks.values_mut().flatten() {
+            for value in hook.env.values_mut() {
+                *value = substitute_env_vars(value, &os.env);
+            }
+            // Blocking hooks all start together, so the deadline bounds each of their timeouts.
+            if let Some(deadline_ms) = self.hook_policy.deadline_ms.filter(|_| hook.blocking) {
+                hook.timeout_ms = hook.timeout_ms.min(deadline_ms);
+            }
+        }
+        hooks
+    }
+}
+
+/// Maximum size of the diff included for a single changed context file. Larger changes are only
+/// pointed out, the new content being included in full anyway.
+const MAX_CONTEXT_FILE_DIFF_SIZE: usize = 4_000;
+
+/// Describes which context files were modified, added or removed between `previous` and `current`,
+/// including a diff for the modified ones.
+fn describe_context_file_changes(previous: &HashMap<String, String>, current: &[(String, String)]) -> Option<String> {
+    let mut changes = Vec::new();
+    for (filename, content) in current {
+        match previous.get(filename) {
+            Some(old_content) if old_content == content => {},
+            Some(old_content) => {
+                let diff = similar::TextDiff::from_lines(old_content, content)
+                    .unified_diff()
+                    .context_radius(2)
+                    .to_string();
+                if diff.len() > MAX_CONTEXT_FILE_DIFF_SIZE {
+                    changes.push(format!("[{filename}] was modified (the diff is too large to show)"));
+                } else {
+                    changes.push(format!("[{filename}] was modified:\n```diff\n{diff}```"));
+                }
+            },
+            None => changes.push(format!("[{filename}] was added")),
+        }
+    }
+    let mut removed = previous
+        .keys()
+        .filter(|filename| !current.iter().any(|(f, _)| f == *filename))
+        .collect::<Vec<_>>();
+    removed.sort();
+    changes.extend(
+        removed
+            .into_iter()
+            .map(|filename| format!("[{filename}] is no longer part of the context")),
+    );
+
+    if changes.is_empty() {
+        return None;
+    }
+
+    Some(format!(
+        "The following context files changed since the previous turn. The content above is up to date, prefer it over any earlier version of these files:\n{}\n",
+        changes.join("\n")
+    ))
 }
 
 /// Calculates the maximum context files size to use for the given model id.
@@ -279,6 +701,8 @@ pub fn calc_max_context_files_size(model: Option<&ModelInfo>) -> usize {
 /// * `path` - The path to process
 /// * `context_files` - The collection to add files to
 /// * `is_validation` - If true, error when glob patterns don't match; if false, silently skip
+/// * `summarize_dirs` - If true, a directory is added as a summary of its tree instead of the
+///   content of the files it directly contains
 ///
 /// # Returns
 /// A Result indicating success or an error
@@ -287,6 +711,7 @@ async fn process_path(
     path: &str,
     context_files: &mut Vec<(String, String)>,
     is_validation: bool,
+    summarize_dirs: bool,
 ) -> Result<()> {
     // Expand ~ to home directory
     let expanded_path = if path.starts_with('~') {
@@ -343,6 +768,9 @@ async fn process_path(
         if path.exists() {
             if path.is_file() {
                 add_file_to_context(os, path, context_files).await?;
+            } else if path.is_dir() && summarize_dirs {
+                let summary = summarize_directory(os, path).await?;
+                context_files.push((path.to_string_lossy().to_string(), summary));
             } else if path.is_dir() {
                 // For directories, add all files in the directory (non-recursive)
                 let mut read_dir = os.fs.read_dir(path).await?;
@@ -396,7 +824,7 @@ mod tests {
         os.fs.write("test/to-drop.md", "long content that exceed limit").await?;
         manager.add_paths(&os, vec!["test/*.md".to_string()], false).await?;
 
-        let (used, dropped) = manager.collect_context_files_with_limit(&os).await.unwrap();
+        let (used, dropped, _) = manager.collect_context_files_with_limit(&os, &mut []).await.unwrap();
 
         assert!(used.len() + dropped.len() == 2);
         assert!(used.len() == 1);
@@ -437,6 +865,41 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_context_file_path_serde() {
+        let paths = vec![
+            ContextFilePath::Agent("README.md".to_string()),
+            ContextFilePath::Session("src/**/*.rs".to_string()),
+        ];
+        let json = serde_json::to_value(&paths).unwrap();
+        assert_eq!(json, serde_json::json!(["README.md", { "session": "src/**/*.rs" }]));
+
+        let deserialized: Vec<ContextFilePath> = serde_json::from_value(json).unwrap();
+        assert!(matches!(&deserialized[0], ContextFilePath::Agent(p) if p == "README.md"));
+        assert!(matches!(&deserialized[1], ContextFilePath::Session(p) if p == "src/**/*.rs"));
+    }
+
+    #[test]
+    fn test_snapshot_restore() {
+        let mut manager = create_test_context_manager(None).unwrap();
+        manager.paths.push(ContextFilePath::Agent("agent.md".to_string()));
+        manager.paths.push(ContextFilePath::Session("notes.md".to_string()));
+        manager.budget.hooks = 0;
+
+        let snapshot = manager.snapshot();
+        assert_eq!(snapshot.paths, vec!["notes.md".to_string()]);
+
+        let mut other = create_test_context_manager(None).unwrap();
+        other.paths.push(ContextFilePath::Agent("agent.md".to_string()));
+        assert_eq!(other.restore(snapshot.clone()), 1);
+        assert_eq!(other.snapshot(), snapshot);
+
+        // Restoring twice doesn't duplicate entries
+        let path_count = other.paths.len();
+        assert_eq!(other.restore(snapshot), 0);
+        assert_eq!(other.paths.len(), path_count);
+    }
+
     #[test]
     fn test_calc_max_context_files_size() {
         assert_eq!(
@@ -445,6 +908,7 @@ mod tests {
                 description: None,
                 model_name: Some("Claude".to_string()),
                 context_window_tokens: 200_000,
+                capabilities: Default::default(),
             })),
             150_000
         );
@@ -454,8 +918,54 @@ mod tests {
                 description: None,
                 model_name: Some("GPT".to_string()),
                 context_window_tokens: 128_000,
+                capabilities: Default::default(),
             })),
             96_000
         );
     }
+
+    #[test]
+    fn test_describe_context_file_changes() {
+        let previous = HashMap::from([
+            ("a.md".to_string(), "one\ntwo\nthree\n".to_string()),
+            ("b.md".to_string(), "unchanged\n".to_string()),
+            ("c.md".to_string(), "removed\n".to_string()),
+        ]);
+
+        let unchanged = vec![
+            ("a.md".to_string(), "one\ntwo\nthree\n".to_string()),
+            ("b.md".to_string(), "unchanged\n".to_string()),
+            ("c.md".to_string(), "removed\n".to_string()),
+        ];
+        assert!(describe_context_file_changes(&previous, &unchanged).is_none());
+
+        let current = vec![
+            ("a.md".to_string(), "one\n2\nthree\n".to_string()),
+            ("b.md".to_string(), "unchanged\n".to_string()),
+            ("d.md".to_string(), "new\n".to_string()),
+        ];
+        let changes = describe_context_file_changes(&previous, &current).unwrap();
+        assert!(changes.contains("[a.md] was modified"));
+        assert!(changes.contains("-two\n+2\n"));
+        assert!(!changes.contains("b.md"));
+        assert!(changes.contains("[c.md] is no longer part of the context"));
+        assert!(changes.contains("[d.md] was added"));
+    }
+
+    #[tokio::test]
+    async fn test_track_context_file_changes() {
+        let mut manager = ContextManager::from_agent(&Agent::default(), 1000).unwrap();
+        let v1 = vec![("a.md".to_string(), "v1\n".to_string())];
+        let v2 = vec![("a.md".to_string(), "v2\n".to_string())];
+
+        // Nothing to compare against before context is sent for the first time
+        assert!(manager.track_context_file_changes(&v1).is_none());
+        manager.mark_context_files_sent();
+
+        assert!(manager.track_context_file_changes(&v2).is_some());
+        // Not sent yet, so the change is still reported
+        assert!(manager.track_context_file_changes(&v2).is_some());
+        manager.mark_context_files_sent();
+        assert!(manager.track_context_file_changes(&v2).is_none());
+    }
 }
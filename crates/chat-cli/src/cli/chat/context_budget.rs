@@ -0,0 +1,253 @@
+use std::fmt;
+
+use clap::ValueEnum;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use super::token_counter::TokenCounter;
+use super::util::truncate_safe_in_place;
+
+/// Appended to context entries that were cut short to fit within their budget.
+const TRUNCATION_MARKER: &str = "\n... (truncated to fit the context budget, see /context budget)";
+
+/// Context files that would be truncated to fewer characters than this are dropped instead, since
+/// a stub of a file is more likely to mislead the model than to help it.
+const MIN_TRUNCATED_FILE_CHARS: usize = TokenCounter::token_to_chars(64);
+
+/// The sources of context that is sent along with every request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, ValueEnum)]
+pub enum ContextSource {
+    /// Files matched by the resources of the agent
+    AgentResources,
+    /// Files added during the session with /context add
+    SessionFiles,
+    /// Resources embedded in the MCP prompts used during the session
+    McpResources,
+    /// Output of agentSpawn and userPromptSubmit hooks
+    Hooks,
+}
+
+impl ContextSource {
+    pub const ALL: [ContextSource; 4] = [
+        Self::AgentResources,
+        Self::SessionFiles,
+        Self::McpResources,
+        Self::Hooks,
+    ];
+
+    fn index(self) -> usize {
+        match self {
+            Self::AgentResources => 0,
+            Self::SessionFiles => 1,
+            Self::McpResources => 2,
+            Self::Hooks => 3,
+        }
+    }
+}
+
+impl fmt::Display for ContextSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::AgentResources => write!(f, "agent-resources"),
+            Self::SessionFiles => write!(f, "session-files"),
+            Self::McpResources => write!(f, "mcp-resources"),
+            Self::Hooks => write!(f, "hooks"),
+        }
+    }
+}
+
+/// Relative shares of the context size limit given to each [ContextSource].
+///
+/// A source that needs less than its share leaves the rest to the other sources, in proportion to
+/// their own shares. Sources needing more than they are given are truncated, largest entries
+/// first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ContextBudget {
+    pub agent_resources: u32,
+    pub session_files: u32,
+    pub mcp_resources: u32,
+    pub hooks: u32,
+}
+
+impl Default for ContextBudget {
+    fn default() -> Self {
+        Self {
+            agent_resources: 45,
+            session_files: 25,
+            mcp_resources: 10,
+            hooks: 20,
+        }
+    }
+}
+
+impl ContextBudget {
+    pub fn share(&self, source: ContextSource) -> u32 {
+        match source {
+            ContextSource::AgentResources => self.agent_resources,
+            ContextSource::SessionFiles => self.session_files,
+            ContextSource::McpResources => self.mcp_resources,
+            ContextSource::Hooks => self.hooks,
+        }
+    }
+
+    pub fn set_share(&mut self, source: ContextSource, share: u32) {
+        match source {
+            ContextSource::AgentResources => self.agent_resources = share,
+            ContextSource::SessionFiles => self.session_files = share,
+            ContextSource::McpResources => self.mcp_resources = share,
+            ContextSource::Hooks => self.hooks = share,
+        }
+    }
+
+    /// The share of `source` as a percentage of all shares.
+    pub fn percentage(&self, source: ContextSource) -> f64 {
+        let total = ContextSource::ALL.iter().map(|s| self.share(*s)).sum::<u32>();
+        match total {
+            0 => 100.0 / ContextSource::ALL.len() as f64,
+            total => self.share(source) as f64 * 100.0 / total as f64,
+        }
+    }
+
+    /// Splits `limit` between the sources, given the amount each of them would use. Returns the
+    /// amount allocated to each source, indexed as [ContextSource::ALL].
+    pub fn allocate(&self, limit: usize, usage: [usize; 4]) -> [usize; 4] {
+        let mut allocated = [0; 4];
+        let mut remaining = limit;
+        let mut pending = ContextSource::ALL
+            .iter()
+            .copied()
+            .filter(|s| usage[s.index()] > 0)
+            .collect::<Vec<_>>();
+
+        while !pending.is_empty() {
+            let shares = pending.iter().map(|s| self.share(*s) as usize).collect::<Vec<_>>();
+            let total_shares = shares.iter().sum::<usize>();
+            let budget_of = |i: usize| match total_shares {
+                0 => remaining / pending.len(),
+                total => remaining * shares[i] / total,
+            };
+
+            // Sources fitting within their budget get what they need, and the rest is split again
+            // between the others.
+            let (fitting, over) =
+                (0..pending.len()).partition::<Vec<_>, _>(|i| usage[pending[*i].index()] <= budget_of(*i));
+            if fitting.is_empty() {
+                for (i, source) in pending.iter().enumerate() {
+                    allocated[source.index()] = budget_of(i);
+                }
+                break;
+            }
+
+            for i in &fitting {
+                let source = pending[*i];
+                allocated[source.index()] = usage[source.index()];
+                remaining -= usage[source.index()];
+            }
+            pending = over.into_iter().map(|i| pending[i]).collect();
+        }
+
+        allocated
+    }
+}
+
+/// Truncates the content of `entries` so that their total size fits within `budget` characters.
+/// The largest entries are truncated first so that small ones are kept whole where possible.
+///
+/// Returns the entries that would be truncated too much to be useful, which are removed from
+/// `entries` when `drop_stubs` is set.
+pub fn fit_to_budget(entries: &mut Vec<(String, String)>, budget: usize, drop_stubs: bool) -> Vec<(String, String)> {
+    let total = entries.iter().map(|(_, content)| content.len()).sum::<usize>();
+    if total <= budget {
+        return Vec::new();
+    }
+
+    // Find the largest size each entry may keep, such that the total fits in the budget.
+    let mut sizes = entries.iter().map(|(_, content)| content.len()).collect::<Vec<_>>();
+    sizes.sort_unstable();
+    let mut remaining = budget;
+    let mut cap = 0;
+    for (i, size) in sizes.iter().enumerate() {
+        let even_split = remaining / (sizes.len() - i);
+        if *size > even_split {
+            cap = even_split;
+            break;
+        }
+        remaining -= size;
+    }
+
+    let mut dropped = Vec::new();
+    entries.retain_mut(|(name, content)| {
+        if content.len() <= cap {
+            return true;
+        }
+        if drop_stubs && cap < MIN_TRUNCATED_FILE_CHARS {
+            dropped.push((name.clone(), std::mem::take(content)));
+            return false;
+        }
+        if cap <= TRUNCATION_MARKER.len() {
+            content.clear();
+        } else {
+            truncate_safe_in_place(content, cap, TRUNCATION_MARKER);
+        }
+        true
+    });
+
+    dropped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allocate() {
+        let budget = ContextBudget::default();
+
+        // Everything fits
+        assert_eq!(budget.allocate(1000, [100, 100, 100, 100]), [100, 100, 100, 100]);
+
+        // Each source over its share gets exactly its share
+        assert_eq!(budget.allocate(1000, [1000, 1000, 1000, 1000]), [450, 250, 100, 200]);
+
+        // Unused budget is given to the other sources in proportion to their shares
+        assert_eq!(budget.allocate(1000, [1000, 1000, 0, 0]), [642, 357, 0, 0]);
+        assert_eq!(budget.allocate(1000, [1000, 100, 0, 50]), [850, 100, 0, 50]);
+
+        let mut budget = ContextBudget::default();
+        budget.set_share(ContextSource::Hooks, 0);
+        budget.set_share(ContextSource::McpResources, 0);
+        assert_eq!(budget.allocate(1000, [1000, 1000, 1000, 1000]), [642, 357, 0, 0]);
+        assert!((budget.percentage(ContextSource::AgentResources) - 64.28).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_fit_to_budget() {
+        let small = ("small".to_string(), "a".repeat(50));
+        let large = ("large".to_string(), "b".repeat(1000));
+
+        // Small entries are kept whole and the large ones are truncated
+        let mut entries = vec![small.clone(), large.clone()];
+        let dropped = fit_to_budget(&mut entries, 600, true);
+        assert!(dropped.is_empty());
+        assert_eq!(entries[0], small);
+        assert_eq!(entries[1].1.len(), 550);
+        assert!(entries[1].1.ends_with(TRUNCATION_MARKER));
+
+        // Entries that can't be meaningfully truncated are dropped
+        let mut entries = vec![small.clone(), large.clone()];
+        let dropped = fit_to_budget(&mut entries, 150, true);
+        assert_eq!(entries, vec![small.clone()]);
+        assert_eq!(dropped[0].0, "large");
+
+        // Unless dropping is not allowed
+        let mut entries = vec![small.clone(), large];
+        let dropped = fit_to_budget(&mut entries, 150, false);
+        assert!(dropped.is_empty());
+        assert_eq!(entries[0], small);
+        assert_eq!(entries[1].1.len(), 100);
+        assert!(entries[1].1.ends_with(TRUNCATION_MARKER));
+    }
+}
@@ -1,12 +1,22 @@
+use std::borrow::Cow;
 use std::collections::{
     HashMap,
     HashSet,
     VecDeque,
 };
+use std::hash::{
+    DefaultHasher,
+    Hash,
+    Hasher,
+};
 use std::io::Write;
 use std::sync::atomic::Ordering;
 
-use chrono::Local;
+use chrono::{
+    DateTime,
+    FixedOffset,
+    Local,
+};
 use crossterm::{
     execute,
     style,
@@ -27,8 +37,17 @@ use tracing::{
     warn,
 };
 
-use super::cli::compact::CompactStrategy;
-use super::cli::hooks::HookOutput;
+use super::cli::compact::{
+    CompactKind,
+    CompactStrategy,
+    CompactionRecord,
+    SLIDING_KEPT_TURNS,
+    SLIDING_SUMMARIZED_PERCENT,
+};
+use super::cli::hooks::{
+    HookOutput,
+    hook_context,
+};
 use super::cli::model::context_window_tokens;
 use super::consts::{
     DUMMY_TOOL_NAME,
@@ -38,19 +57,31 @@ use super::context::{
     ContextManager,
     calc_max_context_files_size,
 };
+use super::git_context::git_context;
+use super::inline_commands::expand_agent_prompt;
 use super::line_tracker::FileLineTracker;
 use super::message::{
     AssistantMessage,
     ToolUseResult,
+    ToolUseResultBlock,
     UserMessage,
 };
 use super::parser::RequestMetadata;
+use super::template_vars::interpolate;
 use super::token_counter::{
     CharCount,
     CharCounter,
     TokenCounter,
 };
 use super::tool_manager::ToolManager;
+use super::tool_result_digest::{
+    MAX_LOCALLY_COMPACTED_TOOL_RESULT_SIZE,
+    MAX_UNDIGESTED_TOOL_RESULT_SIZE,
+    digest_tool_result,
+    is_digest,
+    tool_result_size,
+};
+use super::tools::delegate::snapshot;
 use super::tools::{
     InputSchema,
     QueuedTool,
@@ -58,10 +89,15 @@ use super::tools::{
     ToolSpec,
 };
 use super::util::serde_value_to_document;
+use super::workspace_index::{
+    load_index,
+    relevant_slice,
+};
 use crate::api_client::model::{
     ChatMessage,
     ConversationState as FigConversationState,
     ImageBlock,
+    InferenceConfig,
     Tool,
     ToolInputSchema,
     ToolSpecification,
@@ -82,6 +118,10 @@ use crate::cli::chat::cli::model::{
     get_model_info,
 };
 use crate::cli::chat::tools::custom_tool::CustomToolConfig;
+use crate::cli::experiment::experiment_manager::{
+    ExperimentManager,
+    ExperimentName,
+};
 use crate::os::Os;
 use crate::theme::StyledText;
 
@@ -96,6 +136,43 @@ pub struct HistoryEntry {
     request_metadata: Option<RequestMetadata>,
 }
 
+/// A summary created by compacting the history, kept along with the history entries it replaced so
+/// that they can be restored with `/compact expand`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SummaryCheckpoint {
+    pub summary: String,
+    /// Range of the user turns summarized, counted from the start of the conversation (start
+    /// inclusive, end exclusive).
+    pub turns: (usize, usize),
+    pub created_at: DateTime<FixedOffset>,
+    history: VecDeque<HistoryEntry>,
+    request_metadata: RequestMetadata,
+}
+
+impl SummaryCheckpoint {
+    /// Number of history entries replaced by the summary.
+    pub fn message_count(&self) -> usize {
+        self.history.len()
+    }
+}
+
+/// What [ConversationState::compact_history_locally] changed in the history.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LocalCompaction {
+    /// Number of tool results removed as duplicates of a more recent one.
+    pub deduplicated: usize,
+    /// Number of tool results collapsed into digests.
+    pub collapsed: usize,
+    /// Number of intermediate assistant messages whose text was dropped.
+    pub reasoning_dropped: usize,
+}
+
+impl LocalCompaction {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct McpServerInfo {
     pub name: String,
@@ -128,6 +205,13 @@ pub struct ConversationState {
     context_message_length: Option<usize>,
     /// Stores the latest conversation summary created by /compact
     latest_summary: Option<(String, RequestMetadata)>,
+    /// Every summary created in this conversation, oldest first. The last one matches
+    /// [Self::latest_summary].
+    #[serde(default)]
+    summary_checkpoints: Vec<SummaryCheckpoint>,
+    /// Savings of every compaction of this conversation, oldest first, for `/compact stats`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    compactions: Vec<CompactionRecord>,
     #[serde(skip)]
     pub agents: Agents,
     /// Unused, kept only to maintain deserialization backwards compatibility with <=v1.13.3
@@ -137,6 +221,10 @@ pub struct ConversationState {
     /// Model explicitly selected by the user in this conversation state via `/model`.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub model_info: Option<ModelInfo>,
+    /// Sampling parameters sent with each prompt, from the chat arguments, the agent's
+    /// `modelParameters` or `/model params`. Summary requests always use the model's defaults.
+    #[serde(default)]
+    pub inference_config: InferenceConfig,
     /// Used to track agent vs user updates to file modifications.
     ///
     /// Maps from a file path to [FileLineTracker]
@@ -149,6 +237,22 @@ pub struct ConversationState {
     /// Tangent mode checkpoint - stores main conversation when in tangent mode
     #[serde(default, skip_serializing_if = "Option::is_none")]
     tangent_state: Option<ConversationCheckpoint>,
+    /// Character count of the last request created with [Self::as_sendable_conversation_state],
+    /// compared with the input tokens reported for it to calibrate the token estimates.
+    #[serde(skip)]
+    sent_char_count: Option<usize>,
+    /// Whether the next prompt was marked with `/important`.
+    #[serde(skip)]
+    next_prompt_important: bool,
+    /// Prompt of the active agent with its inline commands expanded. Cleared when a new prompt is
+    /// submitted or the agent changes, so that the commands run once per prompt rather than for
+    /// every request and context size estimate.
+    #[serde(skip)]
+    expanded_agent_prompt: Option<String>,
+    /// Handoff documents of the tasks delegated by earlier sessions, given to the conversation
+    /// when it started
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    handoffs: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -161,6 +265,9 @@ struct ConversationCheckpoint {
     main_transcript: VecDeque<String>,
     /// Main conversation summary
     main_latest_summary: Option<(String, RequestMetadata)>,
+    /// Main conversation summary checkpoints
+    #[serde(default)]
+    main_summary_checkpoints: Vec<SummaryCheckpoint>,
     /// Timestamp when tangent mode was entered (milliseconds since epoch)
     #[serde(default = "time::OffsetDateTime::now_utc")]
     tangent_start_time: time::OffsetDateTime,
@@ -205,13 +312,20 @@ impl ConversationState {
             tool_manager,
             context_message_length: None,
             latest_summary: None,
+            summary_checkpoints: Vec::new(),
+            compactions: Vec::new(),
             agents,
             model: None,
             model_info: model,
+            inference_config: Default::default(),
             file_line_tracker: HashMap::new(),
             checkpoint_manager: None,
             mcp_enabled,
             tangent_state: None,
+            sent_char_count: None,
+            next_prompt_important: false,
+            expanded_agent_prompt: None,
+            handoffs: Vec::new(),
         }
     }
 
@@ -219,15 +333,47 @@ impl ConversationState {
         self.latest_summary.as_ref().map(|(s, _)| s.as_str())
     }
 
+    pub fn summary_checkpoints(&self) -> &[SummaryCheckpoint] {
+        &self.summary_checkpoints
+    }
+
+    pub fn compactions(&self) -> &[CompactionRecord] {
+        &self.compactions
+    }
+
+    pub fn push_compaction(&mut self, record: CompactionRecord) {
+        self.compactions.push(record);
+    }
+
     pub fn history(&self) -> &VecDeque<HistoryEntry> {
         &self.history
     }
 
+    /// Metadata of the requests sent in the conversation, including the ones of the history
+    /// replaced by summaries and the summary requests themselves.
+    pub fn request_metadata(&self) -> impl Iterator<Item = &RequestMetadata> {
+        let main_summary_checkpoints = self
+            .tangent_state
+            .iter()
+            .flat_map(|state| &state.main_summary_checkpoints);
+        main_summary_checkpoints
+            .chain(&self.summary_checkpoints)
+            .flat_map(|checkpoint| {
+                checkpoint
+                    .history
+                    .iter()
+                    .filter_map(|entry| entry.request_metadata.as_ref())
+                    .chain(std::iter::once(&checkpoint.request_metadata))
+            })
+            .chain(self.history.iter().filter_map(|entry| entry.request_metadata.as_ref()))
+    }
+
     /// Clears the conversation history and summary.
     pub fn clear(&mut self) {
         self.next_message = None;
         self.history.clear();
         self.latest_summary = None;
+        self.summary_checkpoints.clear();
     }
 
     /// Check if currently in tangent mode
@@ -235,13 +381,15 @@ impl ConversationState {
         self.tangent_state.is_some()
     }
 
-    /// Create a checkpoint of current conversation state
-    fn create_checkpoint(&self) -> ConversationCheckpoint {
+    /// Create a checkpoint of current conversation state. The summary checkpoints are moved into it
+    /// rather than copied, as they keep every history entry replaced by a summary.
+    fn create_checkpoint(&mut self) -> ConversationCheckpoint {
         ConversationCheckpoint {
             main_history: self.history.clone(),
             main_next_message: self.next_message.clone(),
             main_transcript: self.transcript.clone(),
             main_latest_summary: self.latest_summary.clone(),
+            main_summary_checkpoints: std::mem::take(&mut self.summary_checkpoints),
             tangent_start_time: time::OffsetDateTime::now_utc(),
         }
     }
@@ -252,6 +400,7 @@ impl ConversationState {
         self.next_message = checkpoint.main_next_message;
         self.transcript = checkpoint.main_transcript;
         self.latest_summary = checkpoint.main_latest_summary;
+        self.summary_checkpoints = checkpoint.main_summary_checkpoints;
         self.valid_history_range = (0, self.history.len());
         if let Some(manager) = self.checkpoint_manager.as_mut() {
             manager.message_locked = false;
@@ -262,7 +411,8 @@ impl ConversationState {
     /// Enter tangent mode - creates checkpoint of current state
     pub fn enter_tangent_mode(&mut self) {
         if self.tangent_state.is_none() {
-            self.tangent_state = Some(self.create_checkpoint());
+            let checkpoint = self.create_checkpoint();
+            self.tangent_state = Some(checkpoint);
         }
     }
 
@@ -304,34 +454,43 @@ impl ConversationState {
 
     /// Appends a collection prompts into history and returns the last message in the collection.
     /// It asserts that the collection ends with a prompt that assumes the role of user.
+    ///
+    /// The text resources embedded in the prompts are added to the context, where they share the
+    /// budget of [ContextSource::McpResources](super::context_budget::ContextSource).
     pub fn append_prompts(&mut self, mut prompts: VecDeque<PromptMessage>) -> Option<String> {
-        fn stringify_prompt_message_content(prompt_msg_content: PromptMessageContent) -> String {
+        let has_context = self.context_manager.is_some();
+        let mut resources = Vec::new();
+        let mut stringify_prompt_message_content = |prompt_msg_content: PromptMessageContent| -> String {
             match prompt_msg_content {
                 PromptMessageContent::Text { text } => text,
                 PromptMessageContent::Image { image } => image.raw.data,
-                PromptMessageContent::Resource { resource } => {
-                    // TODO: add support for resources for prompt
-                    match resource.raw.resource {
-                        ResourceContents::TextResourceContents {
-                            uri, mime_type, text, ..
-                        } => {
-                            let mime_type = mime_type.as_deref().unwrap_or("unknown");
+                PromptMessageContent::Resource { resource } => match resource.raw.resource {
+                    ResourceContents::TextResourceContents {
+                        uri, mime_type, text, ..
+                    } => {
+                        let mime_type = mime_type.as_deref().unwrap_or("unknown");
+                        if has_context {
+                            let message =
+                                format!("Text resource of uri: {uri}, mime_type: {mime_type}, added to the context");
+                            resources.push((uri, text));
+                            message
+                        } else {
                             format!("Text resource of uri: {uri}, mime_type: {mime_type}, text: {text}")
-                        },
-                        ResourceContents::BlobResourceContents {
-                            uri, mime_type, blob, ..
-                        } => {
-                            let mime_type = mime_type.as_deref().unwrap_or("unknown");
-                            format!("Blob resource of uri: {uri}, mime_type: {mime_type}, blob: {blob}")
-                        },
-                    }
+                        }
+                    },
+                    ResourceContents::BlobResourceContents {
+                        uri, mime_type, blob, ..
+                    } => {
+                        let mime_type = mime_type.as_deref().unwrap_or("unknown");
+                        format!("Blob resource of uri: {uri}, mime_type: {mime_type}, blob: {blob}")
+                    },
                 },
                 PromptMessageContent::ResourceLink { link } => serde_json::to_string(&link.raw).unwrap_or(format!(
                     "Resource link with uri: {}, name: {}",
                     link.raw.uri, link.raw.name
                 )),
             }
-        }
+        };
 
         debug_assert!(self.next_message.is_none(), "next_message should not exist");
         debug_assert!(prompts.back().is_some_and(|p| p.role == PromptMessageRole::User));
@@ -367,7 +526,13 @@ impl ConversationState {
             }
         }
 
-        Some(stringify_prompt_message_content(last_msg.content))
+        let last_msg = stringify_prompt_message_content(last_msg.content);
+        if let Some(context_manager) = self.context_manager.as_mut() {
+            for (uri, text) in resources {
+                context_manager.add_mcp_resource(uri, text);
+            }
+        }
+        Some(last_msg)
     }
 
     pub fn next_user_message(&self) -> Option<&UserMessage> {
@@ -397,7 +562,49 @@ impl ConversationState {
 
         let mut msg = UserMessage::new_prompt(input, Some(Local::now().fixed_offset()));
         msg.additional_context = additional_context;
+        msg.important = std::mem::take(&mut self.next_prompt_important);
         self.next_message = Some(msg);
+        self.expanded_agent_prompt = None;
+    }
+
+    /// Adds context to the message sent next, e.g. guidance for the model sent along with tool
+    /// results.
+    pub fn add_next_message_context(&mut self, context: &str) {
+        if let Some(next_message) = self.next_message.as_mut() {
+            if !next_message.additional_context.is_empty() {
+                next_message.additional_context.push('\n');
+            }
+            next_message.additional_context.push_str(context);
+        }
+    }
+
+    /// Adds the handoff documents of delegated tasks to the context of the conversation.
+    pub fn add_handoffs(&mut self, handoffs: Vec<String>) {
+        self.handoffs.extend(handoffs);
+    }
+
+    /// Marks the next prompt as important, so that it is kept verbatim when the history is
+    /// summarized.
+    pub fn mark_next_prompt_important(&mut self) {
+        self.next_prompt_important = true;
+    }
+
+    /// Prompts marked as important that were replaced by summaries, oldest first. They are sent
+    /// verbatim along with the latest summary.
+    pub fn important_messages(&self) -> Vec<&str> {
+        self.summary_checkpoints
+            .iter()
+            .flat_map(|checkpoint| &checkpoint.history)
+            .filter(|entry| entry.user.important)
+            .filter_map(|entry| entry.user.prompt())
+            .collect()
+    }
+
+    /// Attaches images to [Self::next_message].
+    pub fn attach_images_to_next_message(&mut self, images: Vec<ImageBlock>) {
+        if let Some(next_message) = self.next_message.as_mut() {
+            next_message.images.get_or_insert_default().extend(images);
+        }
     }
 
     /// Sets the response message according to the currently set [Self::next_message].
@@ -411,6 +618,14 @@ impl ConversationState {
         let next_user_message = self.next_message.take().expect("next user message should exist");
 
         self.append_assistant_transcript(&message);
+        if let Some(context_manager) = self.context_manager.as_mut() {
+            context_manager.stale_context.record_model_output(message.content());
+            for tool_use in message.tool_uses().unwrap_or_default() {
+                context_manager
+                    .stale_context
+                    .record_model_output(&tool_use.args.to_string());
+            }
+        }
         self.history.push_back(HistoryEntry {
             user: next_user_message,
             assistant: message,
@@ -420,6 +635,7 @@ impl ConversationState {
         if let Ok(cwd) = std::env::current_dir() {
             os.database.set_conversation_by_path(cwd, self).ok();
         }
+        snapshot::save(os, self);
     }
 
     /// Returns the conversation id.
@@ -494,9 +710,25 @@ impl ConversationState {
 
     /// Sets the next user message with "cancelled" tool results.
     pub fn abandon_tool_use(&mut self, tools_to_be_abandoned: &[QueuedTool], deny_input: String) {
+        self.abandon_tool_use_ids(tools_to_be_abandoned.iter().map(|t| t.id.as_str()), deny_input);
+    }
+
+    /// Same as [Self::abandon_tool_use], for tool uses that weren't queued yet.
+    pub fn abandon_tool_use_ids<'a>(&mut self, tool_use_ids: impl Iterator<Item = &'a str>, deny_input: String) {
         self.next_message = Some(UserMessage::new_cancelled_tool_uses(
             Some(deny_input),
-            tools_to_be_abandoned.iter().map(|t| t.id.as_str()),
+            tool_use_ids,
+            Some(Local::now().fixed_offset()),
+        ));
+    }
+
+    /// Same as [Self::abandon_tool_use], except the tool results sent back to the model contain
+    /// the reason the user gave for denying the tool uses.
+    pub fn deny_tool_use(&mut self, tools_to_be_denied: &[QueuedTool], deny_input: String, reason: &str) {
+        self.next_message = Some(UserMessage::new_denied_tool_uses(
+            Some(deny_input),
+            tools_to_be_denied.iter().map(|t| t.id.as_str()),
+            reason,
             Some(Local::now().fixed_offset()),
         ));
     }
@@ -531,9 +763,28 @@ impl ConversationState {
             .ok();
         }
 
-        Ok(context
+        // The context messages, which stand in for a system prompt, the history and the new message
+        let char_count = *context.char_count() + context.next_user_message.map_or(0, |msg| *msg.char_count());
+        let mut state = context
             .into_fig_conversation_state()
-            .expect("unable to construct conversation state"))
+            .expect("unable to construct conversation state");
+        state.inference_config = Some(self.inference_config).filter(|config| !config.is_empty());
+        self.sent_char_count = Some(char_count + tool_specs_char_count(&state.user_input_message));
+        if let Some(cm) = self.context_manager.as_mut() {
+            cm.mark_context_files_sent();
+        }
+
+        Ok(state)
+    }
+
+    /// Returns the character count of the last request sent, see [Self::sent_char_count].
+    pub fn take_sent_char_count(&mut self) -> Option<usize> {
+        self.sent_char_count.take()
+    }
+
+    /// Id of the model used in this conversation, if selected.
+    pub fn model_id(&self) -> Option<&str> {
+        self.model_info.as_ref().map(|m| m.model_id.as_str())
     }
 
     pub async fn update_state(&mut self, force_update: bool) {
@@ -607,7 +858,8 @@ impl ConversationState {
             }
         }
 
-        let (context_messages, dropped_context_files) = self.context_messages(os, agent_spawn_context).await;
+        let (context_messages, dropped_context_files, context_breakdown) =
+            self.context_messages(os, agent_spawn_context).await;
 
         Ok(BackendConversationState {
             conversation_id: self.conversation_id.as_str(),
@@ -617,11 +869,121 @@ impl ConversationState {
                 .range(self.valid_history_range.0..self.valid_history_range.1),
             context_messages,
             dropped_context_files,
+            context_breakdown,
             tools: &self.tools,
             model_id: self.model_info.as_ref().map(|m| m.model_id.as_str()),
+            supports_tool_use: self.model_info.as_ref().is_none_or(|m| m.capabilities.tool_use),
         })
     }
 
+    /// Replaces the tool results of the history larger than [MAX_UNDIGESTED_TOOL_RESULT_SIZE]
+    /// with digests. Returns the number of tool results replaced.
+    pub fn collapse_large_tool_results(&mut self) -> usize {
+        collapse_large_tool_results(&mut self.history, MAX_UNDIGESTED_TOOL_RESULT_SIZE)
+    }
+
+    /// Shrinks the history without a model call, used when the summary can't be created because
+    /// the history is too large even for the summary request. By rule:
+    /// 1. Tool results identical to a more recent one, e.g. a file read twice, are removed.
+    /// 2. Tool results are collapsed into digests with a lower size limit than
+    ///    [Self::collapse_large_tool_results].
+    /// 3. The text accompanying tool uses before the final response of each turn, i.e. the
+    ///    intermediate reasoning, is dropped. The most recent history entry is kept as is.
+    pub fn compact_history_locally(&mut self) -> LocalCompaction {
+        let deduplicated = deduplicate_tool_results(&mut self.history);
+        let collapsed = collapse_large_tool_results(&mut self.history, MAX_LOCALLY_COMPACTED_TOOL_RESULT_SIZE);
+        let len = self.history.len();
+        let reasoning_dropped = self
+            .history
+            .iter_mut()
+            .take(len.saturating_sub(1))
+            .map(|entry| entry.assistant.clear_tool_use_content())
+            .filter(|cleared| *cleared)
+            .count();
+
+        LocalCompaction {
+            deduplicated,
+            collapsed,
+            reasoning_dropped,
+        }
+    }
+
+    /// Returns a fingerprint of the `len` oldest history entries, used to tell whether they
+    /// changed, or [None] if the history is shorter.
+    pub fn history_fingerprint(&self, len: usize) -> Option<u64> {
+        if self.history.len() < len {
+            return None;
+        }
+        let mut hasher = DefaultHasher::new();
+        for entry in self.history.iter().take(len) {
+            serde_json::to_string(entry).unwrap_or_default().hash(&mut hasher);
+        }
+        Some(hasher.finish())
+    }
+
+    /// Resolves [CompactKind::Sliding] into the number of most recent history entries kept out of
+    /// the summary. The kept entries start at a user prompt, so that a tool use is never separated
+    /// from its result. Falls back to summarizing the whole history when it is too short to keep
+    /// anything.
+    pub fn resolve_compact_strategy(&self, strategy: CompactStrategy) -> CompactStrategy {
+        if strategy.kind != CompactKind::Sliding {
+            return strategy;
+        }
+
+        let len = self.history.len();
+        let summarized = (len * SLIDING_SUMMARIZED_PERCENT).div_ceil(100);
+        let mut boundary = len - summarized;
+        // Start of the most recent turns, which are always kept
+        if let Some(start) = self
+            .history
+            .iter()
+            .enumerate()
+            .rev()
+            .filter(|(_, entry)| entry.user.prompt().is_some())
+            .nth(SLIDING_KEPT_TURNS - 1)
+            .map(|(i, _)| i)
+        {
+            boundary = boundary.min(start);
+        }
+        while boundary > 0 && self.history[boundary].user.prompt().is_none() {
+            boundary -= 1;
+        }
+
+        CompactStrategy {
+            kind: CompactKind::Full,
+            messages_to_exclude: match boundary {
+                0 => strategy.messages_to_exclude,
+                boundary => (len - boundary).max(strategy.messages_to_exclude),
+            },
+            ..strategy
+        }
+    }
+
+    /// Runs the `preCompact` hooks before the history is summarized, returning the output of the
+    /// successful ones to be added to the summarization instructions.
+    pub async fn run_pre_compact_hooks(&mut self, os: &Os, output: &mut impl Write) -> Option<String> {
+        let history_length = self.history.len();
+        let cm = self.context_manager.as_mut()?;
+        let event_input = serde_json::json!({ "history_length": history_length });
+        let results = match cm
+            .run_hooks_with_input(HookTrigger::PreCompact, output, os, None, None, Some(&event_input))
+            .await
+        {
+            Ok(results) => results,
+            Err(err) => {
+                warn!(?err, "failed to run the preCompact hooks");
+                return None;
+            },
+        };
+        let instructions = results
+            .iter()
+            .filter(|(_, (exit_code, _))| *exit_code == 0)
+            .filter_map(|(_, (_, output))| hook_context(output))
+            .map(|context| context.trim().to_string())
+            .collect::<Vec<_>>();
+        (!instructions.is_empty()).then(|| instructions.join("\n"))
+    }
+
     /// Returns a [FigConversationState] capable of replacing the history of the current
     /// conversation with a summary generated by the model.
     ///
@@ -633,8 +995,25 @@ impl ConversationState {
         custom_prompt: Option<impl AsRef<str>>,
         strategy: CompactStrategy,
     ) -> Result<FigConversationState, ChatError> {
-        let mut summary_content = match custom_prompt {
-            Some(custom_prompt) => {
+        let agent_prompt = self
+            .agents
+            .get_active()
+            .and_then(|agent| agent.compaction_prompt.clone());
+        let mut summary_content = match (agent_prompt, custom_prompt) {
+            (Some(agent_prompt), custom_prompt) => {
+                // The agent's instructions replace the default ones, still followed by the custom
+                // instruction given to /compact
+                let mut content = format!(
+                    "[SYSTEM NOTE: This is an automated summarization request, not from the user]\n\n\
+                    DO NOT respond conversationally. DO NOT address the user directly.\n\n{}",
+                    expand_agent_prompt(os, &self.agents, &interpolate(os, &agent_prompt).await).await
+                );
+                if let Some(custom_prompt) = custom_prompt {
+                    content.push_str(&format!("\n\nIMPORTANT CUSTOM INSTRUCTION: {}", custom_prompt.as_ref()));
+                }
+                content
+            },
+            (None, Some(custom_prompt)) => {
                 // Make the custom instructions much more prominent and directive
                 format!(
                     "[SYSTEM NOTE: This is an automated summarization request, not from the user]\n\n\
@@ -659,7 +1038,7 @@ impl ConversationState {
                     custom_prompt.as_ref()
                 )
             },
-            None => {
+            (None, None) => {
                 // Default prompt
                 "[SYSTEM NOTE: This is an automated summarization request, not from the user]\n\n\
                         FORMAT REQUIREMENTS: Create a structured, concise summary in bullet-point format. DO NOT respond conversationally. DO NOT address the user directly.\n\n\
@@ -690,6 +1069,27 @@ impl ConversationState {
             summary_content.push('\n');
             summary_content.push_str(CONTEXT_ENTRY_END_HEADER);
         }
+        let summarized_len = self.history.len().saturating_sub(strategy.messages_to_exclude);
+        let important_messages = self
+            .important_messages()
+            .into_iter()
+            .chain(
+                self.history
+                    .iter()
+                    .take(summarized_len)
+                    .filter(|entry| entry.user.important)
+                    .filter_map(|entry| entry.user.prompt()),
+            )
+            .collect::<Vec<_>>();
+        if !important_messages.is_empty() {
+            summary_content.push_str("\n\n");
+            summary_content.push_str(CONTEXT_ENTRY_START_HEADER);
+            summary_content.push_str("The user marked these messages as important. They are carried forward verbatim alongside the summary, so take them into account but DO NOT repeat them in the summary.\n\n");
+            for message in important_messages {
+                summary_content.push_str(&format!("- {message}\n"));
+            }
+            summary_content.push_str(CONTEXT_ENTRY_END_HEADER);
+        }
 
         let conv_state = self.backend_conversation_state(os, false, &mut vec![]).await?;
         let mut summary_message = Some(UserMessage::new_prompt(summary_content.clone(), None));
@@ -697,24 +1097,16 @@ impl ConversationState {
         // Create the history according to the passed compact strategy.
         let mut history = conv_state.history.cloned().collect::<VecDeque<_>>();
         history.drain((history.len().saturating_sub(strategy.messages_to_exclude))..);
+        collapse_large_tool_results(&mut history, MAX_UNDIGESTED_TOOL_RESULT_SIZE);
         if strategy.truncate_large_messages {
-            for HistoryEntry { user, .. } in &mut history {
+            for HistoryEntry { user, .. } in history.iter_mut().filter(|entry| !entry.user.important) {
                 user.truncate_safe(strategy.max_message_length);
             }
         }
 
         // Only send the dummy tool spec in order to prevent the model from ever attempting a tool
         // use.
-        let mut tools = self.tools.clone();
-        tools.retain(|k, v| match k {
-            ToolOrigin::Native => {
-                v.retain(|tool| match tool {
-                    Tool::ToolSpecification(tool_spec) => tool_spec.name == DUMMY_TOOL_NAME,
-                });
-                true
-            },
-            ToolOrigin::McpServer(_) => false,
-        });
+        let tools = dummy_tool_only(&self.tools);
 
         enforce_conversation_invariants(&mut history, &mut summary_message, &tools);
 
@@ -724,6 +1116,7 @@ impl ConversationState {
                 .unwrap_or(UserMessage::new_prompt(summary_content, None)) // should not happen
                 .into_user_input_message(self.model_info.as_ref().map(|m| m.model_id.clone()), &tools),
             history: Some(flatten_history(history.iter())),
+            inference_config: None,
         })
     }
 
@@ -735,11 +1128,62 @@ impl ConversationState {
         strategy: CompactStrategy,
         request_metadata: RequestMetadata,
     ) {
-        self.history
-            .drain(..(self.history.len().saturating_sub(strategy.messages_to_exclude)));
+        let history = self
+            .history
+            .drain(..(self.history.len().saturating_sub(strategy.messages_to_exclude)))
+            .collect::<VecDeque<_>>();
+        let first_turn = self.summary_checkpoints.last().map_or(1, |c| c.turns.1);
+        let turn_count = history.iter().filter(|entry| entry.user.prompt().is_some()).count();
+        self.summary_checkpoints.push(SummaryCheckpoint {
+            summary: summary.clone(),
+            turns: (first_turn, first_turn + turn_count),
+            created_at: Local::now().fixed_offset(),
+            history,
+            request_metadata: request_metadata.clone(),
+        });
         self.latest_summary = Some((summary, request_metadata));
     }
 
+    /// Number of characters replaced by a summary created with `strategy`: the summarized history
+    /// entries, and the summary they already replace.
+    pub fn summarized_char_count(&self, strategy: CompactStrategy) -> usize {
+        let len = self.history.len().saturating_sub(strategy.messages_to_exclude);
+        let history = self
+            .history
+            .iter()
+            .take(len)
+            .map(|HistoryEntry { user, assistant, .. }| *user.char_count() + *assistant.char_count())
+            .sum::<usize>();
+        history + self.latest_summary.as_ref().map_or(0, |(summary, _)| summary.len())
+    }
+
+    /// Restores the history entries replaced by the summary checkpoint at `index`, and by every
+    /// checkpoint created after it, in place of their summaries. The summary preceding that
+    /// checkpoint becomes the latest again. Returns the number of history entries restored, or
+    /// [None] if there is no such checkpoint.
+    pub fn expand_summary_checkpoint(&mut self, index: usize) -> Option<usize> {
+        if index >= self.summary_checkpoints.len() {
+            return None;
+        }
+
+        let mut history = self
+            .summary_checkpoints
+            .split_off(index)
+            .into_iter()
+            .flat_map(|checkpoint| checkpoint.history)
+            .collect::<VecDeque<_>>();
+        let restored = history.len();
+        history.append(&mut self.history);
+        self.history = history;
+        self.valid_history_range = (0, self.history.len());
+        self.latest_summary = self
+            .summary_checkpoints
+            .last()
+            .map(|checkpoint| (checkpoint.summary.clone(), checkpoint.request_metadata.clone()));
+
+        Some(restored)
+    }
+
     pub async fn create_agent_generation_request(
         &mut self,
         agent_name: &str,
@@ -767,21 +1211,13 @@ Return only the JSON configuration, no additional text.",
         let history = VecDeque::new();
 
         // Only send the dummy tool spec to prevent the model from attempting tool use during generation
-        let mut tools = self.tools.clone();
-        tools.retain(|k, v| match k {
-            ToolOrigin::Native => {
-                v.retain(|tool| match tool {
-                    Tool::ToolSpecification(tool_spec) => tool_spec.name == DUMMY_TOOL_NAME,
-                });
-                true
-            },
-            ToolOrigin::McpServer(_) => false,
-        });
+        let tools = dummy_tool_only(&self.tools);
 
         Ok(FigConversationState {
             conversation_id: Some(self.conversation_id.clone()),
             user_input_message: generation_message.into_user_input_message(self.model.clone(), &tools),
             history: Some(flatten_history(history.iter())),
+            inference_config: None,
         })
     }
 
@@ -805,9 +1241,10 @@ Return only the JSON configuration, no additional text.",
         &mut self,
         os: &Os,
         additional_context: Option<String>,
-    ) -> (Option<Vec<HistoryEntry>>, Vec<(String, String)>) {
+    ) -> (Option<Vec<HistoryEntry>>, Vec<(String, String)>, ContextBreakdown) {
         let mut context_content = String::new();
         let mut dropped_context_files = Vec::new();
+        let mut breakdown = ContextBreakdown::default();
         if let Some((summary, _)) = &self.latest_summary {
             context_content.push_str(CONTEXT_ENTRY_START_HEADER);
             context_content.push_str("This summary contains ALL relevant information from our previous conversation including tool uses, results, code analysis, and file operations. YOU MUST reference this information when answering questions and explicitly acknowledge specific details from the summary when they're relevant to the current question.\n\n");
@@ -815,21 +1252,67 @@ Return only the JSON configuration, no additional text.",
             context_content.push_str(summary);
             context_content.push('\n');
             context_content.push_str(CONTEXT_ENTRY_END_HEADER);
+            breakdown.summary = summary.len();
+        }
+        let important_messages = self.important_messages();
+        if !important_messages.is_empty() {
+            let start = context_content.len();
+            context_content.push_str(CONTEXT_ENTRY_START_HEADER);
+            context_content.push_str("The user marked these messages from the previous conversation as important. They are kept verbatim, follow them as if they were just sent.\n\n");
+            for message in important_messages {
+                context_content.push_str(&format!("- {message}\n"));
+            }
+            context_content.push_str(CONTEXT_ENTRY_END_HEADER);
+            breakdown.summary += context_content.len() - start;
+        }
+        if !self.handoffs.is_empty() {
+            let start = context_content.len();
+            context_content.push_str(CONTEXT_ENTRY_START_HEADER);
+            context_content.push_str("Handoffs of the tasks delegated in earlier sessions in this directory, describing what they did and left to do. Build on them rather than redoing the work.\n\n");
+            for handoff in &self.handoffs {
+                context_content.push_str(handoff);
+                context_content.push('\n');
+            }
+            context_content.push_str(CONTEXT_ENTRY_END_HEADER);
+            breakdown.handoffs = context_content.len() - start;
         }
 
         // Add context files if available
+        let mut additional_context = additional_context;
         if let Some(context_manager) = self.context_manager.as_mut() {
-            match context_manager.collect_context_files_with_limit(os).await {
-                Ok((files_to_use, files_dropped)) => {
+            // Hook output shares the context budget with the context files
+            let mut hook_outputs = Vec::new();
+            if let Some(context) = additional_context.as_mut() {
+                hook_outputs.push(context);
+            }
+            if let Some(next_message) = self.next_message.as_mut() {
+                if !next_message.additional_context.is_empty() {
+                    hook_outputs.push(&mut next_message.additional_context);
+                }
+            }
+            context_manager.hook_outputs = hook_outputs.iter().map(|output| (**output).clone()).collect();
+
+            match context_manager
+                .collect_context_files_with_limit(os, &mut hook_outputs)
+                .await
+            {
+                Ok((files_to_use, files_dropped, size_by_source)) => {
                     if !files_dropped.is_empty() {
                         dropped_context_files.extend(files_dropped);
                     }
+                    breakdown.agent_resources = size_by_source[0];
+                    breakdown.session_context = size_by_source[1];
+                    breakdown.mcp_resources = size_by_source[2];
 
-                    if !files_to_use.is_empty() {
+                    let changes = context_manager.track_context_file_changes(&files_to_use);
+                    if !files_to_use.is_empty() || changes.is_some() {
                         context_content.push_str(CONTEXT_ENTRY_START_HEADER);
                         for (filename, content) in files_to_use {
                             context_content.push_str(&format!("[{}]\n{}\n", filename, content));
                         }
+                        if let Some(changes) = changes {
+                            context_content.push_str(&changes);
+                        }
                         context_content.push_str(CONTEXT_ENTRY_END_HEADER);
                     }
                 },
@@ -839,11 +1322,43 @@ Return only the JSON configuration, no additional text.",
             }
         }
 
+        if ExperimentManager::is_enabled(os, ExperimentName::GitContext) {
+            if let Some(git_context) = git_context(os).await {
+                context_content.push_str(CONTEXT_ENTRY_START_HEADER);
+                context_content.push_str(&git_context);
+                context_content.push_str(CONTEXT_ENTRY_END_HEADER);
+                breakdown.git = git_context.len();
+            }
+        }
+
+        if let Some(index) = load_index(os).await {
+            // Tool results carry no prompt, so the slice follows the latest prompt of the user
+            let prompt = self
+                .next_message
+                .as_ref()
+                .and_then(|m| m.prompt())
+                .or_else(|| self.history.iter().rev().find_map(|entry| entry.user.prompt()));
+            if let Some(slice) = prompt.and_then(|prompt| relevant_slice(&index, prompt)) {
+                context_content.push_str(CONTEXT_ENTRY_START_HEADER);
+                context_content.push_str(&slice);
+                context_content.push_str(CONTEXT_ENTRY_END_HEADER);
+                breakdown.workspace_index = slice.len();
+            }
+        }
+
         if let Some(context) = additional_context {
             context_content.push_str(&context);
+            breakdown.hooks = context.len();
         }
 
-        if let Some(agent_prompt) = self.agents.get_active().and_then(|a| a.prompt.as_ref()) {
+        if let Some(agent_prompt) = self.agents.get_active().and_then(|a| a.prompt.clone()) {
+            let agent_prompt = match &self.expanded_agent_prompt {
+                Some(expanded) => expanded.clone(),
+                None => {
+                    let expanded = expand_agent_prompt(os, &self.agents, &interpolate(os, &agent_prompt).await).await;
+                    self.expanded_agent_prompt.insert(expanded).clone()
+                },
+            };
             context_content.push_str(&format!("Follow this instruction: {}", agent_prompt));
         }
 
@@ -851,6 +1366,17 @@ Return only the JSON configuration, no additional text.",
             self.context_message_length = Some(context_content.len());
             let user = UserMessage::new_prompt(context_content, None);
             let assistant = AssistantMessage::new_response(None, "I will fully incorporate this information when generating my responses, and explicitly acknowledge relevant parts of the summary when answering questions.".into());
+            // Everything else in the context messages is instructions to the model
+            breakdown.instructions = (*user.char_count() + *assistant.char_count()).saturating_sub(
+                breakdown.summary
+                    + breakdown.agent_resources
+                    + breakdown.session_context
+                    + breakdown.mcp_resources
+                    + breakdown.hooks
+                    + breakdown.git
+                    + breakdown.workspace_index
+                    + breakdown.handoffs,
+            );
             (
                 Some(vec![HistoryEntry {
                     user,
@@ -858,9 +1384,10 @@ Return only the JSON configuration, no additional text.",
                     request_metadata: None,
                 }]),
                 dropped_context_files,
+                breakdown,
             )
         } else {
-            (None, dropped_context_files)
+            (None, dropped_context_files, breakdown)
         }
     }
 
@@ -880,7 +1407,8 @@ Return only the JSON configuration, no additional text.",
     /// Get the current token warning level
     pub async fn get_token_warning_level(&mut self, os: &Os) -> Result<TokenWarningLevel, ChatError> {
         let total_chars = self.calculate_char_count(os).await?;
-        let max_chars = TokenCounter::token_to_chars(context_window_tokens(self.model_info.as_ref()));
+        let max_chars =
+            TokenCounter::model_tokens_to_chars(self.model_id(), context_window_tokens(self.model_info.as_ref()));
 
         Ok(if *total_chars >= max_chars {
             TokenWarningLevel::Critical
@@ -936,6 +1464,25 @@ Return only the JSON configuration, no additional text.",
         Ok(())
     }
 
+    /// Rebuilds the context manager from the active agent, carrying over the context entries added
+    /// during the session. Used when resuming a conversation, since the resources and hooks of the
+    /// agent may have changed since it was saved.
+    pub fn reload_context_manager(&mut self) -> Result<(), ChatError> {
+        let Some(agent) = self.agents.get_active() else {
+            return Ok(());
+        };
+
+        let mut context_manager =
+            ContextManager::from_agent(agent, calc_max_context_files_size(self.model_info.as_ref()))
+                .map_err(|e| ChatError::Custom(format!("Context manager has failed to instantiate: {e}").into()))?;
+        if let Some(previous) = self.context_manager.as_ref() {
+            context_manager.restore(previous.snapshot());
+        }
+        self.context_manager = Some(context_manager);
+
+        Ok(())
+    }
+
     /// Swapping agent involves the following:
     /// - Reinstantiate the context manager
     /// - Swap agent on tool manager
@@ -955,6 +1502,7 @@ Return only the JSON configuration, no additional text.",
             .swap_agent(os, output, agent)
             .await
             .map_err(ChatError::AgentSwapError)?;
+        self.expanded_agent_prompt = None;
 
         self.update_state(true).await;
 
@@ -962,6 +1510,15 @@ Return only the JSON configuration, no additional text.",
     }
 }
 
+/// Characters of the specs of the tools sent with a message.
+fn tool_specs_char_count(message: &UserInputMessage) -> usize {
+    message
+        .user_input_message_context
+        .as_ref()
+        .and_then(|context| context.tools.as_ref())
+        .map_or(0, |tools| serde_json::to_string(tools).map_or(0, |json| json.len()))
+}
+
 pub fn format_tool_spec(tool_spec: HashMap<String, ToolSpec>) -> HashMap<ToolOrigin, Vec<Tool>> {
     tool_spec
         .into_values()
@@ -995,23 +1552,43 @@ pub struct BackendConversationStateImpl<'a, T, U> {
     pub history: T,
     pub context_messages: U,
     pub dropped_context_files: Vec<(String, String)>,
+    pub context_breakdown: ContextBreakdown,
     pub tools: &'a HashMap<ToolOrigin, Vec<Tool>>,
     pub model_id: Option<&'a str>,
+    /// Whether the tools are sent, only the dummy tool is otherwise
+    pub supports_tool_use: bool,
 }
 
 impl BackendConversationStateImpl<'_, std::collections::vec_deque::Iter<'_, HistoryEntry>, Option<Vec<HistoryEntry>>> {
     fn into_fig_conversation_state(self) -> eyre::Result<FigConversationState> {
-        let history = flatten_history(self.context_messages.unwrap_or_default().iter().chain(self.history));
+        let context_messages = self.context_messages.unwrap_or_default();
+        let mut history = flatten_history(context_messages.iter().chain(self.history));
+        // The context messages only change along with the agent's resources, so ask the backend
+        // to cache them.
+        if let Some(ChatMessage::AssistantResponseMessage(last_context_message)) = (context_messages.len() * 2)
+            .checked_sub(1)
+            .and_then(|i| history.get_mut(i))
+        {
+            last_context_message.cache_point = true;
+        }
         let user_input_message: UserInputMessage = self
             .next_user_message
             .cloned()
-            .map(|msg| msg.into_user_input_message(self.model_id.map(str::to_string), self.tools))
+            .map(|msg| {
+                let tools = if self.supports_tool_use {
+                    Cow::Borrowed(self.tools)
+                } else {
+                    Cow::Owned(dummy_tool_only(self.tools))
+                };
+                msg.into_user_input_message(self.model_id.map(str::to_string), &tools)
+            })
             .ok_or(eyre::eyre!("next user message is not set"))?;
 
         Ok(FigConversationState {
             conversation_id: Some(self.conversation_id.to_string()),
             user_input_message,
             history: Some(history),
+            inference_config: None,
         })
     }
 
@@ -1055,6 +1632,93 @@ pub struct ConversationSize {
     pub assistant_messages: CharCount,
 }
 
+/// Characters of the context messages taken by each kind of context.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ContextBreakdown {
+    /// The agent prompt, along with the framing of the context entries
+    pub instructions: usize,
+    /// The conversation summary created by /compact
+    pub summary: usize,
+    pub agent_resources: usize,
+    /// Files, pages and symbols added during the session
+    pub session_context: usize,
+    /// Resources embedded in the MCP prompts used during the session
+    pub mcp_resources: usize,
+    /// Output of agentSpawn hooks
+    pub hooks: usize,
+    pub git: usize,
+    /// Entries of the workspace index related to the prompt
+    pub workspace_index: usize,
+    /// Handoffs of the tasks delegated in earlier sessions
+    pub handoffs: usize,
+}
+
+/// Replaces the tool results of `history` larger than [MAX_UNDIGESTED_TOOL_RESULT_SIZE] with
+/// digests. Returns the number of tool results replaced.
+fn collapse_large_tool_results(history: &mut VecDeque<HistoryEntry>, max_size: usize) -> usize {
+    let tool_uses = history
+        .iter()
+        .filter_map(|entry| entry.assistant.tool_uses())
+        .flatten()
+        .map(|tool_use| (tool_use.id.clone(), tool_use.clone()))
+        .collect::<HashMap<_, _>>();
+
+    let mut collapsed = 0;
+    for entry in history.iter_mut() {
+        for result in entry.user.tool_use_results_mut().into_iter().flatten() {
+            if tool_result_size(result) > max_size && !is_digest(result) {
+                let digest = digest_tool_result(result, tool_uses.get(&result.tool_use_id));
+                result.content = vec![ToolUseResultBlock::Text(digest)];
+                collapsed += 1;
+            }
+        }
+    }
+    collapsed
+}
+
+/// Tool results smaller than this aren't worth deduplicating.
+const MIN_DEDUPLICATED_TOOL_RESULT_SIZE: usize = 500;
+
+/// Replaces the tool results identical to a more recent one with a note, returning how many were
+/// replaced.
+fn deduplicate_tool_results(history: &mut VecDeque<HistoryEntry>) -> usize {
+    let mut seen = HashSet::new();
+    let mut deduplicated = 0;
+    for entry in history.iter_mut().rev() {
+        for result in entry.user.tool_use_results_mut().into_iter().flatten().rev() {
+            if tool_result_size(result) < MIN_DEDUPLICATED_TOOL_RESULT_SIZE {
+                continue;
+            }
+            let mut hasher = DefaultHasher::new();
+            serde_json::to_string(&result.content)
+                .unwrap_or_default()
+                .hash(&mut hasher);
+            if !seen.insert(hasher.finish()) {
+                result.content = vec![ToolUseResultBlock::Text(
+                    "[Removed during compaction: same output as a later use of this tool]".to_string(),
+                )];
+                deduplicated += 1;
+            }
+        }
+    }
+    deduplicated
+}
+
+/// Keeps only the dummy tool spec, which prevents the model from ever attempting a tool use.
+fn dummy_tool_only(tools: &HashMap<ToolOrigin, Vec<Tool>>) -> HashMap<ToolOrigin, Vec<Tool>> {
+    let mut tools = tools.clone();
+    tools.retain(|k, v| match k {
+        ToolOrigin::Native => {
+            v.retain(|tool| match tool {
+                Tool::ToolSpecification(tool_spec) => tool_spec.name == DUMMY_TOOL_NAME,
+            });
+            true
+        },
+        ToolOrigin::McpServer(_) => false,
+    });
+    tools
+}
+
 /// Converts a list of user/assistant message pairs into a flattened list of ChatMessage.
 fn flatten_history<'a, T>(history: T) -> Vec<ChatMessage>
 where
@@ -1092,10 +1756,12 @@ impl From<InputSchema> for ToolInputSchema {
 /// [Option::None]
 fn format_hook_context(hook_results: &[((HookTrigger, Hook), HookOutput)], trigger: HookTrigger) -> Option<String> {
     // Note: only format context when hook command exit code is 0
-    if hook_results
+    let contexts = hook_results
         .iter()
-        .all(|(_, (exit_code, content))| *exit_code != 0 || content.is_empty())
-    {
+        .filter(|((h_trigger, _), (exit_code, _))| *h_trigger == trigger && *exit_code == 0)
+        .filter_map(|(_, (_, output))| hook_context(output))
+        .collect::<Vec<_>>();
+    if contexts.is_empty() {
         return None;
     }
 
@@ -1108,10 +1774,7 @@ fn format_hook_context(hook_results: &[((HookTrigger, Hook), HookOutput)], trigg
     }
     context_content.push_str("\n\n");
 
-    for (_, (_, output)) in hook_results
-        .iter()
-        .filter(|((h_trigger, _), (exit_code, _))| *h_trigger == trigger && *exit_code == 0)
-    {
+    for output in contexts {
         context_content.push_str(&format!("{output}\n\n"));
     }
     context_content.push_str(CONTEXT_ENTRY_END_HEADER);
@@ -1405,6 +2068,329 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_sent_char_count() {
+        let mut os = Os::new().await.unwrap();
+        let mut output = vec![];
+        let mut tool_manager = ToolManager::default();
+        let mut conversation = ConversationState::new(
+            "fake_conv_id",
+            Agents::default(),
+            tool_manager.load_tools(&mut os, &mut output).await.unwrap(),
+            tool_manager,
+            None,
+            &os,
+            false,
+        )
+        .await;
+
+        let prompt = "a".repeat(1_000);
+        conversation.set_next_user_message(prompt.clone()).await;
+        let state = conversation
+            .as_sendable_conversation_state(&os, &mut vec![], false)
+            .await
+            .unwrap();
+        let tools = tool_specs_char_count(&state.user_input_message);
+        assert!(tools > 0);
+        // The new message and the tool specs are sent along with the history
+        assert!(conversation.take_sent_char_count().unwrap() >= prompt.len() + tools);
+        assert!(conversation.take_sent_char_count().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_resolve_sliding_compact_strategy() {
+        let mut os = Os::new().await.unwrap();
+        let mut tool_manager = ToolManager::default();
+        let mut conversation = ConversationState::new(
+            "fake_conv_id",
+            Agents::default(),
+            tool_manager.load_tools(&mut os, &mut vec![]).await.unwrap(),
+            tool_manager,
+            None,
+            &os,
+            false,
+        )
+        .await;
+        let sliding = CompactStrategy {
+            kind: CompactKind::Sliding,
+            ..Default::default()
+        };
+
+        // Each turn is a prompt answered with a tool use, then the tool result and the response.
+        for i in 0..9 {
+            conversation.set_next_user_message(i.to_string()).await;
+            conversation.push_assistant_message(
+                &mut os,
+                AssistantMessage::new_tool_use(None, i.to_string(), vec![AssistantToolUse {
+                    id: "tool_id".to_string(),
+                    name: "tool name".to_string(),
+                    args: serde_json::Value::Null,
+                    ..Default::default()
+                }]),
+                None,
+            );
+            conversation.add_tool_results(vec![ToolUseResult {
+                tool_use_id: "tool_id".to_string(),
+                content: vec![],
+                status: ToolResultStatus::Success,
+            }]);
+            conversation.push_assistant_message(&mut os, AssistantMessage::new_response(None, i.to_string()), None);
+
+            if i == 0 {
+                // Too short to keep anything
+                let strategy = conversation.resolve_compact_strategy(sliding);
+                assert_eq!(strategy.kind, CompactKind::Full);
+                assert_eq!(strategy.messages_to_exclude, 0);
+            }
+        }
+
+        // 11 of the 18 entries are summarized, moved back to the start of the turn
+        let strategy = conversation.resolve_compact_strategy(sliding);
+        assert_eq!(strategy.kind, CompactKind::Full);
+        assert_eq!(strategy.messages_to_exclude, 12);
+        let kept = &conversation.history()[18 - 12];
+        assert_eq!(kept.user.prompt(), Some("3"));
+
+        // The fingerprint of background compactions changes once the history is replaced
+        let fingerprint = conversation.history_fingerprint(6);
+        assert!(fingerprint.is_some());
+        assert_eq!(conversation.history_fingerprint(19), None);
+        conversation.replace_history_with_summary("summary".to_string(), strategy, RequestMetadata::default());
+        assert_eq!(conversation.history().len(), 12);
+        assert_ne!(conversation.history_fingerprint(6), fingerprint);
+    }
+
+    #[tokio::test]
+    async fn test_compact_history_locally() {
+        let mut os = Os::new().await.unwrap();
+        let mut tool_manager = ToolManager::default();
+        let mut conversation = ConversationState::new(
+            "fake_conv_id",
+            Agents::default(),
+            tool_manager.load_tools(&mut os, &mut vec![]).await.unwrap(),
+            tool_manager,
+            None,
+            &os,
+            false,
+        )
+        .await;
+
+        // The same file is read twice in a turn
+        let file = "fn main() {}\n".repeat(300);
+        conversation.set_next_user_message("read main.rs".to_string()).await;
+        for (id, reasoning) in [("1", "Let me read the file"), ("2", "Reading it again")] {
+            conversation.push_assistant_message(
+                &mut os,
+                AssistantMessage::new_tool_use(None, reasoning.to_string(), vec![AssistantToolUse {
+                    id: id.to_string(),
+                    name: "fs_read".to_string(),
+                    args: serde_json::json!({ "operations": [{ "mode": "Line", "path": "src/main.rs" }] }),
+                    ..Default::default()
+                }]),
+                None,
+            );
+            conversation.add_tool_results(vec![ToolUseResult {
+                tool_use_id: id.to_string(),
+                content: vec![ToolUseResultBlock::Text(file.clone())],
+                status: ToolResultStatus::Success,
+            }]);
+        }
+        conversation.push_assistant_message(&mut os, AssistantMessage::new_response(None, "done".into()), None);
+
+        assert_eq!(conversation.compact_history_locally(), LocalCompaction {
+            deduplicated: 1,
+            collapsed: 1,
+            reasoning_dropped: 2,
+        });
+        let history = conversation.history();
+        assert!(history.iter().take(2).all(|entry| entry.assistant.content().is_empty()));
+        assert_eq!(history[2].assistant.content(), "done");
+        assert!(tool_result_size(&history[1].user.tool_use_results().unwrap()[0]) < 100);
+        assert!(is_digest(&history[2].user.tool_use_results().unwrap()[0]));
+
+        // Nothing is left to compact
+        assert!(conversation.compact_history_locally().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_summary_checkpoints() {
+        let mut os = Os::new().await.unwrap();
+        let mut tool_manager = ToolManager::default();
+        let mut conversation = ConversationState::new(
+            "fake_conv_id",
+            Agents::default(),
+            tool_manager.load_tools(&mut os, &mut vec![]).await.unwrap(),
+            tool_manager,
+            None,
+            &os,
+            false,
+        )
+        .await;
+        let mut push_turns = async |conversation: &mut ConversationState, turns: std::ops::Range<usize>| {
+            for i in turns {
+                conversation.set_next_user_message(i.to_string()).await;
+                conversation.push_assistant_message(&mut os, AssistantMessage::new_response(None, i.to_string()), None);
+            }
+        };
+
+        push_turns(&mut conversation, 0..5).await;
+        let keep_two = CompactStrategy {
+            messages_to_exclude: 2,
+            ..Default::default()
+        };
+        // Three prompts and responses of one character each
+        assert_eq!(conversation.summarized_char_count(keep_two), 6);
+        conversation.replace_history_with_summary("first".to_string(), keep_two, RequestMetadata::default());
+        push_turns(&mut conversation, 5..7).await;
+        assert_eq!(
+            conversation.summarized_char_count(CompactStrategy::default()),
+            4 + 4 + "first".len()
+        );
+        conversation.replace_history_with_summary(
+            "second".to_string(),
+            CompactStrategy::default(),
+            RequestMetadata::default(),
+        );
+        push_turns(&mut conversation, 7..8).await;
+
+        let checkpoints = conversation.summary_checkpoints();
+        assert_eq!(checkpoints.len(), 2);
+        assert_eq!((checkpoints[0].turns, checkpoints[0].message_count()), ((1, 4), 3));
+        assert_eq!((checkpoints[1].turns, checkpoints[1].message_count()), ((4, 8), 4));
+        assert_eq!(conversation.latest_summary(), Some("second"));
+        assert_eq!(conversation.history().len(), 1);
+
+        // Tangent mode keeps the summary checkpoints of the main conversation aside, without copying
+        conversation.enter_tangent_mode();
+        assert!(conversation.summary_checkpoints().is_empty());
+        assert_eq!(conversation.latest_summary(), Some("second"));
+        conversation.exit_tangent_mode();
+        assert_eq!(conversation.summary_checkpoints().len(), 2);
+        // Only the summary requests have metadata here
+        assert_eq!(conversation.request_metadata().count(), 2);
+
+        assert_eq!(conversation.expand_summary_checkpoint(2), None);
+        assert_eq!(conversation.expand_summary_checkpoint(1), Some(4));
+        assert_eq!(conversation.latest_summary(), Some("first"));
+        assert_eq!(conversation.summary_checkpoints().len(), 1);
+        let prompts = conversation
+            .history()
+            .iter()
+            .filter_map(|entry| entry.user.prompt())
+            .collect::<Vec<_>>();
+        assert_eq!(prompts, ["3", "4", "5", "6", "7"]);
+
+        assert_eq!(conversation.expand_summary_checkpoint(0), Some(3));
+        assert_eq!(conversation.latest_summary(), None);
+        assert_eq!(conversation.history().len(), 8);
+    }
+
+    #[tokio::test]
+    async fn test_important_messages() {
+        let mut os = Os::new().await.unwrap();
+        let mut tool_manager = ToolManager::default();
+        let mut conversation = ConversationState::new(
+            "fake_conv_id",
+            Agents::default(),
+            tool_manager.load_tools(&mut os, &mut vec![]).await.unwrap(),
+            tool_manager,
+            None,
+            &os,
+            false,
+        )
+        .await;
+
+        conversation.mark_next_prompt_important();
+        conversation
+            .set_next_user_message("Never modify the migrations".to_string())
+            .await;
+        conversation.push_assistant_message(&mut os, AssistantMessage::new_response(None, "ok".into()), None);
+        conversation.set_next_user_message("fix the tests".to_string()).await;
+        assert!(!conversation.next_user_message().unwrap().important);
+        conversation.push_assistant_message(&mut os, AssistantMessage::new_response(None, "done".into()), None);
+
+        let request = conversation
+            .create_summary_request(&os, None::<String>, CompactStrategy::default())
+            .await
+            .unwrap();
+        assert!(
+            request
+                .user_input_message
+                .content
+                .contains("- Never modify the migrations\n")
+        );
+
+        conversation.replace_history_with_summary(
+            "summary".to_string(),
+            CompactStrategy::default(),
+            RequestMetadata::default(),
+        );
+        assert_eq!(conversation.important_messages(), ["Never modify the migrations"]);
+
+        // Sent verbatim along with the summary
+        conversation.set_next_user_message("next".to_string()).await;
+        let state = conversation
+            .as_sendable_conversation_state(&os, &mut vec![], false)
+            .await
+            .unwrap();
+        let ChatMessage::UserInputMessage(context) = &state.history.as_ref().unwrap()[0] else {
+            panic!("expected the context message first");
+        };
+        assert!(context.content.contains("SUMMARY CONTENT:\nsummary"));
+        assert!(context.content.contains("- Never modify the migrations\n"));
+    }
+
+    #[tokio::test]
+    async fn test_collapse_large_tool_results() {
+        let mut os = Os::new().await.unwrap();
+        let mut tool_manager = ToolManager::default();
+        let mut conversation = ConversationState::new(
+            "fake_conv_id",
+            Agents::default(),
+            tool_manager.load_tools(&mut os, &mut vec![]).await.unwrap(),
+            tool_manager,
+            None,
+            &os,
+            false,
+        )
+        .await;
+
+        conversation.set_next_user_message("read the logs".to_string()).await;
+        for (id, output) in [("1", "x\n".repeat(10_000)), ("2", "small".to_string())] {
+            conversation.push_assistant_message(
+                &mut os,
+                AssistantMessage::new_tool_use(None, String::new(), vec![AssistantToolUse {
+                    id: id.to_string(),
+                    name: "fs_read".to_string(),
+                    args: serde_json::json!({ "operations": [{ "mode": "Line", "path": "logs/app.log" }] }),
+                    ..Default::default()
+                }]),
+                None,
+            );
+            conversation.add_tool_results(vec![ToolUseResult {
+                tool_use_id: id.to_string(),
+                content: vec![ToolUseResultBlock::Text(output)],
+                status: ToolResultStatus::Success,
+            }]);
+        }
+        conversation.push_assistant_message(&mut os, AssistantMessage::new_response(None, "done".into()), None);
+
+        assert_eq!(conversation.collapse_large_tool_results(), 1);
+        let results = conversation.history()[1].user.tool_use_results().unwrap();
+        assert!(is_digest(&results[0]));
+        assert!(tool_result_size(&results[0]) < 1000);
+        let ToolUseResultBlock::Text(digest) = &results[0].content[0] else {
+            panic!("expected a text digest");
+        };
+        assert!(digest.contains("Paths: logs/app.log\n"));
+        assert!(!is_digest(
+            &conversation.history()[2].user.tool_use_results().unwrap()[0]
+        ));
+
+        // Digests aren't collapsed again
+        assert_eq!(conversation.collapse_large_tool_results(), 0);
+    }
+
     #[tokio::test]
     async fn test_conversation_state_history_handling_with_tool_results() {
         let mut os = Os::new().await.unwrap();
@@ -1531,15 +2517,22 @@ mod tests {
             let user = &hist[0];
             let assistant = &hist[1];
             match (user, assistant) {
-                (ChatMessage::UserInputMessage(user), ChatMessage::AssistantResponseMessage(_)) => {
+                (ChatMessage::UserInputMessage(user), ChatMessage::AssistantResponseMessage(assistant)) => {
                     assert!(
                         user.content.contains("test context"),
                         "expected context message to contain context file, instead found: {}",
                         user.content
                     );
+                    assert!(assistant.cache_point, "expected the context messages to be cached");
                 },
                 _ => panic!("Expected the first two messages to be from the user and the assistant"),
             }
+            assert_eq!(
+                hist.iter()
+                    .filter(|m| matches!(m, ChatMessage::AssistantResponseMessage(m) if m.cache_point))
+                    .count(),
+                1
+            );
 
             assert_conversation_state_invariants(s, i);
 
@@ -1548,6 +2541,99 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_mcp_prompt_resources_in_context() {
+        let mut os = Os::new().await.unwrap();
+        let agents = {
+            let mut agents = Agents::default();
+            agents.agents.insert("TestAgent".to_string(), Agent::default());
+            agents.switch("TestAgent").expect("Agent switch failed");
+            agents
+        };
+        let mut output = vec![];
+        let mut tool_manager = ToolManager::default();
+        let mut conversation = ConversationState::new(
+            "fake_conv_id",
+            agents,
+            tool_manager.load_tools(&mut os, &mut output).await.unwrap(),
+            tool_manager,
+            None,
+            &os,
+            false,
+        )
+        .await;
+
+        let resource = |text: &str| {
+            PromptMessage::new_resource(
+                PromptMessageRole::User,
+                "file:///schema.sql".to_string(),
+                None,
+                Some(text.to_string()),
+                None,
+                None,
+                None,
+            )
+        };
+        let last = conversation
+            .append_prompts(VecDeque::from([
+                resource("create table old"),
+                resource("create table users"),
+            ]))
+            .unwrap();
+        assert!(last.ends_with("added to the context"));
+        assert_eq!(conversation.context_manager.as_ref().unwrap().mcp_resources, vec![(
+            "file:///schema.sql".to_string(),
+            "create table users".to_string()
+        )]);
+
+        conversation.set_next_user_message(last).await;
+        let state = conversation
+            .as_sendable_conversation_state(&os, &mut vec![], true)
+            .await
+            .unwrap();
+        let ChatMessage::UserInputMessage(context) = &state.history.as_ref().unwrap()[0] else {
+            panic!("expected the context message first");
+        };
+        assert!(context.content.contains("create table users"));
+    }
+
+    #[tokio::test]
+    async fn test_agent_compaction_prompt() {
+        let mut os = Os::new().await.unwrap();
+        let agents = {
+            let mut agents = Agents::default();
+            let agent = Agent {
+                compaction_prompt: Some("Always preserve the open TODOs on {{os}}".to_string()),
+                ..Default::default()
+            };
+            agents.agents.insert("TestAgent".to_string(), agent);
+            agents.switch("TestAgent").expect("Agent switch failed");
+            agents
+        };
+        let mut tool_manager = ToolManager::default();
+        let mut conversation = ConversationState::new(
+            "fake_conv_id",
+            agents,
+            tool_manager.load_tools(&mut os, &mut vec![]).await.unwrap(),
+            tool_manager,
+            None,
+            &os,
+            false,
+        )
+        .await;
+        conversation.set_next_user_message("hello".to_string()).await;
+        conversation.push_assistant_message(&mut os, AssistantMessage::new_response(None, "hi".to_string()), None);
+
+        let request = conversation
+            .create_summary_request(&os, Some("mention the tests"), CompactStrategy::default())
+            .await
+            .unwrap();
+        let content = &request.user_input_message.content;
+        assert!(content.contains(&format!("Always preserve the open TODOs on {}", std::env::consts::OS)));
+        assert!(content.contains("IMPORTANT CUSTOM INSTRUCTION: mention the tests"));
+        assert!(!content.contains("A bullet-point list of key topics"));
+    }
+
     #[tokio::test]
     async fn test_tangent_mode() {
         let mut os = Os::new().await.unwrap();
@@ -0,0 +1,257 @@
+//! Estimates the cost of the requests sent in a session from the tokens they used.
+
+use std::fmt::Display;
+use std::str::FromStr;
+
+use serde::Serialize;
+
+/// On-demand price of a model, in US dollars per million tokens.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ModelPricing {
+    pub input: f64,
+    pub output: f64,
+}
+
+impl ModelPricing {
+    const fn new(input: f64, output: f64) -> Self {
+        Self { input, output }
+    }
+
+    pub fn cost(&self, input_tokens: usize, output_tokens: usize) -> f64 {
+        (input_tokens as f64 * self.input + output_tokens as f64 * self.output) / 1_000_000.0
+    }
+}
+
+/// Prices of the known model families, matched in order against the normalized model id, so more
+/// specific patterns come first.
+const PRICING: &[(&str, ModelPricing)] = &[
+    ("opus-4-5", ModelPricing::new(5.0, 25.0)),
+    ("opus", ModelPricing::new(15.0, 75.0)),
+    ("haiku-4-5", ModelPricing::new(1.0, 5.0)),
+    ("3-5-haiku", ModelPricing::new(0.8, 4.0)),
+    ("haiku", ModelPricing::new(0.25, 1.25)),
+    ("sonnet", ModelPricing::new(3.0, 15.0)),
+    ("nova-premier", ModelPricing::new(2.5, 12.5)),
+    ("nova-pro", ModelPricing::new(0.8, 3.2)),
+    ("nova-lite", ModelPricing::new(0.06, 0.24)),
+    ("nova-micro", ModelPricing::new(0.035, 0.14)),
+];
+
+/// Returns the pricing of `model_id`, e.g. `claude-sonnet-4` or
+/// `us.anthropic.claude-3-7-sonnet-20250219-v1:0`, if known.
+pub fn pricing_for(model_id: &str) -> Option<ModelPricing> {
+    let normalized = model_id.to_lowercase().replace('.', "-");
+    PRICING
+        .iter()
+        .find(|(pattern, _)| normalized.contains(pattern))
+        .map(|(_, pricing)| *pricing)
+}
+
+/// Tokens used by a single request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RequestUsage {
+    pub input_tokens: usize,
+    pub output_tokens: usize,
+    /// Whether the tokens were estimated from the size of the request rather than reported by
+    /// the backend.
+    pub estimated: bool,
+}
+
+/// Running totals of the tokens used and their estimated cost over a session.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CostTracker {
+    pub requests: usize,
+    pub input_tokens: usize,
+    pub output_tokens: usize,
+    /// Estimated cost of all the requests of the session.
+    pub total_cost: f64,
+    /// Estimated cost of the requests of the current turn.
+    pub turn_cost: f64,
+    /// Number of requests sent to a model without known pricing, which aren't part of the cost.
+    pub unpriced_requests: usize,
+    /// Whether any of the token counts were estimated.
+    pub estimated: bool,
+}
+
+impl CostTracker {
+    /// Records a request sent to `model_id`, returning its estimated cost if the model's pricing
+    /// is known.
+    pub fn record(&mut self, model_id: Option<&str>, usage: RequestUsage) -> Option<f64> {
+        self.requests += 1;
+        self.input_tokens += usage.input_tokens;
+        self.output_tokens += usage.output_tokens;
+        self.estimated |= usage.estimated;
+
+        match model_id.and_then(pricing_for) {
+            Some(pricing) => {
+                let cost = pricing.cost(usage.input_tokens, usage.output_tokens);
+                self.total_cost += cost;
+                self.turn_cost += cost;
+                Some(cost)
+            },
+            None => {
+                self.unpriced_requests += 1;
+                None
+            },
+        }
+    }
+
+    pub fn start_turn(&mut self) {
+        self.turn_cost = 0.0;
+    }
+
+    /// Whether at least one request was sent to a model with known pricing, i.e. whether the
+    /// costs mean anything.
+    pub fn is_priced(&self) -> bool {
+        self.requests > self.unpriced_requests
+    }
+
+    pub fn report(&self) -> CostReport {
+        let priced = self.is_priced();
+        CostReport {
+            requests: self.requests,
+            input_tokens: self.input_tokens,
+            output_tokens: self.output_tokens,
+            turn_cost_usd: priced.then_some(self.turn_cost),
+            total_cost_usd: priced.then_some(self.total_cost),
+            estimated: self.estimated,
+        }
+    }
+}
+
+/// Usage and cost of a session, as reported at the end of each turn.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CostReport {
+    pub requests: usize,
+    pub input_tokens: usize,
+    pub output_tokens: usize,
+    /// [None] if none of the requests were sent to a model with known pricing.
+    pub turn_cost_usd: Option<f64>,
+    pub total_cost_usd: Option<f64>,
+    pub estimated: bool,
+}
+
+/// Maximum estimated cost of a session in US dollars, given with `--max-cost`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CostLimit(pub f64);
+
+// Parsing rejects NaN
+impl Eq for CostLimit {}
+
+impl FromStr for CostLimit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let amount = s
+            .trim()
+            .trim_start_matches('$')
+            .parse::<f64>()
+            .map_err(|err| format!("invalid amount '{s}': {err}, expected a number of US dollars, e.g. 2.50"))?;
+        if !amount.is_finite() || amount <= 0.0 {
+            return Err(format!("the maximum cost must be a positive amount, got {s}"));
+        }
+        Ok(Self(amount))
+    }
+}
+
+/// Formats a cost in US dollars, with more precision for amounts below a dollar.
+pub struct Cost(pub f64);
+
+impl Display for Cost {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.0 >= 1.0 {
+            write!(f, "${:.2}", self.0)
+        } else {
+            write!(f, "${:.4}", self.0)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pricing_for() {
+        assert_eq!(pricing_for("claude-sonnet-4"), Some(ModelPricing::new(3.0, 15.0)));
+        assert_eq!(pricing_for("claude-3.7-sonnet"), Some(ModelPricing::new(3.0, 15.0)));
+        assert_eq!(
+            pricing_for("us.anthropic.claude-3-5-haiku-20241022-v1:0"),
+            Some(ModelPricing::new(0.8, 4.0))
+        );
+        assert_eq!(pricing_for("claude-haiku-4.5"), Some(ModelPricing::new(1.0, 5.0)));
+        assert_eq!(pricing_for("claude-opus-4.1"), Some(ModelPricing::new(15.0, 75.0)));
+        assert_eq!(pricing_for("claude-opus-4.5"), Some(ModelPricing::new(5.0, 25.0)));
+        assert_eq!(pricing_for("qwen3:8b"), None);
+    }
+
+    #[test]
+    fn test_cost_tracker() {
+        let mut tracker = CostTracker::default();
+        let usage = RequestUsage {
+            input_tokens: 100_000,
+            output_tokens: 2_000,
+            estimated: false,
+        };
+
+        let cost = tracker.record(Some("claude-sonnet-4"), usage).unwrap();
+        assert!((cost - 0.33).abs() < 1e-9);
+        assert_eq!(tracker.record(Some("qwen3:8b"), usage), None);
+        assert_eq!(tracker.record(None, usage), None);
+        assert_eq!(tracker.requests, 3);
+        assert_eq!(tracker.unpriced_requests, 2);
+        assert_eq!(tracker.input_tokens, 300_000);
+        assert!(!tracker.estimated);
+
+        tracker.start_turn();
+        tracker.record(Some("claude-sonnet-4"), RequestUsage {
+            estimated: true,
+            ..usage
+        });
+        assert!((tracker.turn_cost - 0.33).abs() < 1e-9);
+        assert!((tracker.total_cost - 0.66).abs() < 1e-9);
+        assert!(tracker.estimated);
+    }
+
+    #[test]
+    fn test_cost_report() {
+        let mut tracker = CostTracker::default();
+        let usage = RequestUsage {
+            input_tokens: 1_000,
+            output_tokens: 100,
+            estimated: false,
+        };
+        tracker.record(Some("qwen3:8b"), usage);
+        assert_eq!(
+            serde_json::to_value(tracker.report()).unwrap(),
+            serde_json::json!({
+                "requests": 1,
+                "inputTokens": 1000,
+                "outputTokens": 100,
+                "turnCostUsd": null,
+                "totalCostUsd": null,
+                "estimated": false,
+            })
+        );
+
+        tracker.record(Some("claude-sonnet-4"), usage);
+        assert_eq!(tracker.report().total_cost_usd, Some(0.0045));
+    }
+
+    #[test]
+    fn test_cost_limit_parse() {
+        assert_eq!("2.5".parse::<CostLimit>(), Ok(CostLimit(2.5)));
+        assert_eq!("$10".parse::<CostLimit>(), Ok(CostLimit(10.0)));
+        assert!("0".parse::<CostLimit>().is_err());
+        assert!("-1".parse::<CostLimit>().is_err());
+        assert!("NaN".parse::<CostLimit>().is_err());
+        assert!("ten".parse::<CostLimit>().is_err());
+    }
+
+    #[test]
+    fn test_cost_display() {
+        assert_eq!(Cost(0.0123456).to_string(), "$0.0123");
+        assert_eq!(Cost(12.345).to_string(), "$12.35");
+    }
+}
@@ -0,0 +1,246 @@
+//! Slash commands defined by the user in markdown files, `.amazonq/commands/<name>.md` in the
+//! workspace or `~/.aws/amazonq/commands/<name>.md` for all workspaces. Running `/<name> args`
+//! sends the body of the file as a prompt, with `$ARGUMENTS` replaced with all the arguments and
+//! `$1` to `$9` with each of them.
+//!
+//! The file can start with a YAML frontmatter:
+//! ```markdown
+//! ---
+//! description: Review the staged changes
+//! allowed-tools: [fs_read, execute_bash]
+//! model: claude-sonnet-4
+//! ---
+//! Review the staged changes, focusing on $ARGUMENTS.
+//! ```
+//! The allowed tools are trusted and the model is used until the command's turn ends.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::LazyLock;
+
+use regex::{
+    Captures,
+    Regex,
+};
+use serde::Deserialize;
+use thiserror::Error;
+use tracing::warn;
+
+use super::cli::model::ModelInfo;
+use crate::os::Os;
+use crate::util::paths::PathResolver;
+
+static PLACEHOLDER: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\$(ARGUMENTS|[1-9])").unwrap());
+
+#[derive(Debug, Error)]
+pub enum CustomCommandError {
+    #[error("the frontmatter isn't closed with ---")]
+    UnclosedFrontmatter,
+    #[error("invalid frontmatter: {0}")]
+    Frontmatter(#[from] serde_yaml::Error),
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct Frontmatter {
+    description: Option<String>,
+    #[serde(default)]
+    allowed_tools: Vec<String>,
+    model: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CustomCommand {
+    pub name: String,
+    pub description: Option<String>,
+    /// Tools trusted during the command's turn, named as in the `allowedTools` of agents
+    pub allowed_tools: Vec<String>,
+    /// Model used during the command's turn
+    pub model: Option<String>,
+    template: String,
+}
+
+impl CustomCommand {
+    pub fn parse(name: &str, content: &str) -> Result<Self, CustomCommandError> {
+        let (frontmatter, template) = match content.strip_prefix("---\n").or(content.strip_prefix("---\r\n")) {
+            Some(rest) => {
+                let (yaml, body) = rest
+                    .split_once("\n---\n")
+                    .or(rest.split_once("\n---\r\n"))
+                    .or(rest.strip_suffix("\n---").map(|yaml| (yaml, "")))
+                    .ok_or(CustomCommandError::UnclosedFrontmatter)?;
+                let frontmatter = match yaml.trim() {
+                    "" => Frontmatter::default(),
+                    yaml => serde_yaml::from_str(yaml)?,
+                };
+                (frontmatter, body)
+            },
+            None => (Frontmatter::default(), content),
+        };
+
+        Ok(Self {
+            name: name.to_string(),
+            description: frontmatter.description,
+            allowed_tools: frontmatter.allowed_tools,
+            model: frontmatter.model,
+            template: template.trim().to_string(),
+        })
+    }
+
+    /// The prompt sent for `/<name> <arguments>`.
+    pub fn expand(&self, arguments: &str) -> String {
+        let arguments = arguments.trim();
+        let split = shlex::split(arguments).unwrap_or_else(|| arguments.split_whitespace().map(String::from).collect());
+        PLACEHOLDER
+            .replace_all(&self.template, |captures: &Captures<'_>| match &captures[1] {
+                "ARGUMENTS" => arguments.to_string(),
+                n => n
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|n| split.get(n - 1).cloned())
+                    .unwrap_or_default(),
+            })
+            .to_string()
+    }
+}
+
+/// What a custom command changed for its turn, undone once the turn ends.
+#[derive(Debug, Default)]
+pub struct CommandTurn {
+    /// Whether the model of the command replaced [Self::previous_model]
+    pub switched_model: bool,
+    pub previous_model: Option<ModelInfo>,
+    /// The allowed tools of the command that weren't trusted already
+    pub trusted_tools: Vec<String>,
+}
+
+/// Whether `name` can name a custom command, which is also a valid file name.
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Loads the custom commands, those of the workspace overriding the global ones of the same name.
+pub async fn load(os: &Os) -> Vec<CustomCommand> {
+    let resolver = PathResolver::new(os);
+    let mut commands = BTreeMap::new();
+    for dir in [resolver.global().commands_dir(), resolver.workspace().commands_dir()]
+        .into_iter()
+        .flatten()
+    {
+        load_dir(os, &dir, &mut commands).await;
+    }
+    commands.into_values().collect()
+}
+
+/// The custom command `name`, if any.
+pub async fn find(os: &Os, name: &str) -> Option<CustomCommand> {
+    if !is_valid_name(name) {
+        return None;
+    }
+    load(os).await.into_iter().find(|command| command.name == name)
+}
+
+async fn load_dir(os: &Os, dir: &Path, commands: &mut BTreeMap<String, CustomCommand>) {
+    let Ok(mut entries) = os.fs.read_dir(dir).await else {
+        return;
+    };
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let path = entry.path();
+        if path.extension().is_none_or(|ext| ext != "md") {
+            continue;
+        }
+        let Some(name) = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .filter(|name| is_valid_name(name))
+        else {
+            warn!(
+                ?path,
+                "Ignoring the custom command, its name can only have letters, digits, - and _"
+            );
+            continue;
+        };
+        let content = match os.fs.read_to_string(&path).await {
+            Ok(content) => content,
+            Err(err) => {
+                warn!(?path, ?err, "Failed to read the custom command");
+                continue;
+            },
+        };
+        match CustomCommand::parse(name, &content) {
+            Ok(command) => {
+                commands.insert(command.name.clone(), command);
+            },
+            Err(err) => warn!(?path, %err, "Ignoring the invalid custom command"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let command = CustomCommand::parse(
+            "review",
+            "---\ndescription: Review the changes\nallowed-tools: [fs_read, \"@git/diff\"]\nmodel: claude-sonnet-4\n---\nReview $ARGUMENTS\n",
+        )
+        .unwrap();
+        assert_eq!(command.description.as_deref(), Some("Review the changes"));
+        assert_eq!(command.allowed_tools, vec!["fs_read", "@git/diff"]);
+        assert_eq!(command.model.as_deref(), Some("claude-sonnet-4"));
+        assert_eq!(command.template, "Review $ARGUMENTS");
+
+        let command = CustomCommand::parse("plain", "Explain this code\n").unwrap();
+        assert_eq!(command.description, None);
+        assert!(command.allowed_tools.is_empty());
+        assert_eq!(command.template, "Explain this code");
+
+        assert!(matches!(
+            CustomCommand::parse("open", "---\nmodel: x\nBody"),
+            Err(CustomCommandError::UnclosedFrontmatter)
+        ));
+        assert!(matches!(
+            CustomCommand::parse("typo", "---\nallowed_tools: [fs_read]\n---\nBody"),
+            Err(CustomCommandError::Frontmatter(_))
+        ));
+    }
+
+    #[test]
+    fn test_expand() {
+        let command = CustomCommand::parse("fix", "Fix issue $1 in $2 ($ARGUMENTS), not $3").unwrap();
+        assert_eq!(
+            command.expand(" 42 \"src/main.rs\" "),
+            "Fix issue 42 in src/main.rs (42 \"src/main.rs\"), not "
+        );
+        assert_eq!(command.expand(""), "Fix issue  in  (), not ");
+    }
+
+    #[tokio::test]
+    async fn test_load() {
+        let os = Os::new().await.unwrap();
+        let resolver = PathResolver::new(&os);
+        let global = resolver.global().commands_dir().unwrap();
+        let workspace = resolver.workspace().commands_dir().unwrap();
+        os.fs.create_dir_all(&global).await.unwrap();
+        os.fs.create_dir_all(&workspace).await.unwrap();
+        os.fs.write(global.join("review.md"), "Global review").await.unwrap();
+        os.fs.write(global.join("explain.md"), "Explain $1").await.unwrap();
+        os.fs
+            .write(workspace.join("review.md"), "Workspace review")
+            .await
+            .unwrap();
+        os.fs.write(workspace.join("notes.txt"), "Not a command").await.unwrap();
+        os.fs
+            .write(workspace.join("bad name.md"), "Invalid name")
+            .await
+            .unwrap();
+
+        let commands = load(&os).await;
+        let names = commands.iter().map(|command| command.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["explain", "review"]);
+        assert_eq!(find(&os, "review").await.unwrap().expand(""), "Workspace review");
+        assert!(find(&os, "missing").await.is_none());
+    }
+}
@@ -0,0 +1,263 @@
+//! Compact summaries of directories added to the context.
+//!
+//! Rather than inlining every file of a directory, a summary lists the directory tree along with a
+//! short synopsis of each file: its size, line count and top-level symbols. The model can then
+//! read the files it actually needs with its tools.
+
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::path::{
+    Path,
+    PathBuf,
+};
+use std::sync::LazyLock;
+
+use eyre::Result;
+use regex::Regex;
+
+use crate::os::Os;
+
+/// Directories that are skipped on top of hidden ones, since they hold generated or vendored files.
+pub(super) const SKIPPED_DIRECTORIES: &[&str] = &["node_modules", "target", "__pycache__", "venv"];
+
+/// How deep below the summarized directory entries are listed.
+const MAX_DEPTH: usize = 8;
+
+/// Maximum number of files and directories listed in a summary.
+const MAX_ENTRIES: usize = 400;
+
+/// Files larger than this are listed without looking for symbols.
+pub(super) const MAX_PARSED_FILE_SIZE: u64 = 512 * 1024;
+
+/// Maximum number of symbols listed for a single file.
+const MAX_SYMBOLS_PER_FILE: usize = 12;
+
+/// File extensions, and a pattern matching the top-level symbols of files with these extensions.
+/// Patterns capture the kind of symbol and its name.
+static SYMBOL_PATTERNS: LazyLock<Vec<(&[&str], Regex)>> = LazyLock::new(|| {
+    vec![
+        (
+            &["rs"],
+            Regex::new(
+                r#"(?m)^(?:pub(?:\([^)]*\))?\s+)?(?:(?:async|const|unsafe|extern\s+"[^"]*")\s+)*(fn|struct|enum|trait|mod|type|union|macro_rules!)\s*([A-Za-z_][A-Za-z0-9_]*)"#,
+            )
+            .unwrap(),
+        ),
+        (
+            &["py"],
+            Regex::new(r"(?m)^(?:async\s+)?(def|class)\s+([A-Za-z_][A-Za-z0-9_]*)").unwrap(),
+        ),
+        (
+            &["js", "jsx", "mjs", "cjs", "ts", "tsx"],
+            Regex::new(
+                r"(?m)^(?:export\s+)?(?:default\s+)?(?:declare\s+)?(?:async\s+)?(function\*?|class|interface|type|enum|const|let|var)\s+([A-Za-z_$][A-Za-z0-9_$]*)",
+            )
+            .unwrap(),
+        ),
+        (
+            &["go"],
+            Regex::new(r"(?m)^(func|type)\s+(?:\([^)]*\)\s*)?([A-Za-z_][A-Za-z0-9_]*)").unwrap(),
+        ),
+        (
+            &["java", "kt", "cs", "swift", "scala"],
+            Regex::new(
+                r"(?m)^(?:(?:public|private|protected|internal|abstract|final|sealed|static|data|open)\s+)*(class|interface|enum|record|object|struct|protocol|fun|func)\s+([A-Za-z_][A-Za-z0-9_]*)",
+            )
+            .unwrap(),
+        ),
+        (&["md"], Regex::new(r"(?m)^(#{1,2})\s+(.+?)\s*$").unwrap()),
+    ]
+});
+
+/// Returns the top-level symbols found in `content`, formatted as `<kind> <name>`.
+pub(super) fn top_level_symbols(extension: &str, content: &str) -> Vec<String> {
+    let Some((_, pattern)) = SYMBOL_PATTERNS.iter().find(|(exts, _)| exts.contains(&extension)) else {
+        return Vec::new();
+    };
+
+    pattern
+        .captures_iter(content)
+        .map(|c| format!("{} {}", &c[1], &c[2]))
+        .collect()
+}
+
+fn format_size(size: u64) -> String {
+    match size {
+        s if s < 1024 => format!("{s} B"),
+        s if s < 1024 * 1024 => format!("{:.1} KB", s as f64 / 1024.0),
+        s => format!("{:.1} MB", s as f64 / (1024.0 * 1024.0)),
+    }
+}
+
+/// Returns a one line synopsis of the file at `path`, e.g. `4.2 KB, 120 lines: fn main, struct
+/// Args`.
+async fn file_synopsis(os: &Os, path: &Path, size: u64) -> String {
+    let mut synopsis = format_size(size);
+    if size > MAX_PARSED_FILE_SIZE {
+        return synopsis;
+    }
+
+    let Ok(content) = os.fs.read(path).await else {
+        return synopsis;
+    };
+    let Ok(content) = String::from_utf8(content) else {
+        synopsis.push_str(", binary");
+        return synopsis;
+    };
+
+    let lines = content.lines().count();
+    let _ = write!(synopsis, ", {} line{}", lines, if lines == 1 { "" } else { "s" });
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or_default();
+    let symbols = top_level_symbols(extension, &content);
+    if !symbols.is_empty() {
+        let _ = write!(
+            synopsis,
+            ": {}",
+            symbols
+                .iter()
+                .take(MAX_SYMBOLS_PER_FILE)
+                .cloned()
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        if symbols.len() > MAX_SYMBOLS_PER_FILE {
+            let _ = write!(synopsis, ", and {} more", symbols.len() - MAX_SYMBOLS_PER_FILE);
+        }
+    }
+    synopsis
+}
+
+/// Summarizes the directory at `path` as a tree of its files and directories, with a synopsis of
+/// each file. Hidden entries and common build and dependency directories are skipped.
+pub async fn summarize_directory(os: &Os, path: &Path) -> Result<String> {
+    // (relative path, size for files or None for directories)
+    let mut entries: Vec<(PathBuf, Option<u64>)> = Vec::new();
+    let mut truncated = false;
+    let mut dir_queue = VecDeque::from([(path.to_path_buf(), 0)]);
+
+    'outer: while let Some((dir, depth)) = dir_queue.pop_front() {
+        let mut read_dir = os.fs.read_dir(&dir).await?;
+        while let Some(entry) = read_dir.next_entry().await? {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name.starts_with('.') {
+                continue;
+            }
+            let Ok(metadata) = entry.metadata().await else {
+                continue;
+            };
+            if entries.len() >= MAX_ENTRIES {
+                truncated = true;
+                break 'outer;
+            }
+
+            let relative = entry.path().strip_prefix(path).map(Path::to_path_buf)?;
+            if metadata.is_dir() {
+                if SKIPPED_DIRECTORIES.contains(&name.as_str()) {
+                    continue;
+                }
+                entries.push((relative, None));
+                if depth + 1 < MAX_DEPTH {
+                    dir_queue.push_back((entry.path(), depth + 1));
+                }
+            } else if metadata.is_file() {
+                entries.push((relative, Some(metadata.len())));
+            }
+        }
+    }
+
+    // Sorting by path lists the content of every directory right after it.
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let file_count = entries.iter().filter(|(_, size)| size.is_some()).count();
+    let total_size = entries.iter().filter_map(|(_, size)| *size).sum::<u64>();
+    let mut summary = format!(
+        "Summary of the directory {} ({} files, {} in total). File contents are not included, read the files that are relevant to the task.\n\n",
+        path.display(),
+        file_count,
+        format_size(total_size)
+    );
+
+    for (relative, size) in &entries {
+        let indent = "  ".repeat(relative.components().count() - 1);
+        let name = relative.file_name().unwrap_or_default().to_string_lossy();
+        match size {
+            Some(size) => {
+                let synopsis = file_synopsis(os, &path.join(relative), *size).await;
+                let _ = writeln!(summary, "{indent}{name} ({synopsis})");
+            },
+            None => {
+                let _ = writeln!(summary, "{indent}{name}/");
+            },
+        }
+    }
+
+    if truncated {
+        let _ = writeln!(
+            summary,
+            "... only the first {MAX_ENTRIES} entries are listed, add subdirectories separately for more"
+        );
+    }
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_top_level_symbols() {
+        let rust = "use std::io;\n\npub struct Agent {\n    name: String,\n}\n\npub(crate) async fn load() {}\nfn helper() {\n    fn nested() {}\n}\nmacro_rules! m { () => {} }\n";
+        assert_eq!(top_level_symbols("rs", rust), vec![
+            "struct Agent",
+            "fn load",
+            "fn helper",
+            "macro_rules! m"
+        ]);
+
+        let python = "import os\n\nclass Agent:\n    def method(self):\n        pass\n\nasync def main():\n    pass\n";
+        assert_eq!(top_level_symbols("py", python), vec!["class Agent", "def main"]);
+
+        let typescript = "export default class App {}\nexport const handler = () => {};\ninterface Props {}\n";
+        assert_eq!(top_level_symbols("ts", typescript), vec![
+            "class App",
+            "const handler",
+            "interface Props"
+        ]);
+
+        assert!(top_level_symbols("txt", "fn main() {}").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_summarize_directory() -> Result<()> {
+        let os = Os::new().await.unwrap();
+        os.fs.create_dir_all("project/src/cli").await?;
+        os.fs.create_dir_all("project/node_modules/dep").await?;
+        os.fs.write("project/README.md", "# Project\n\nSome text\n").await?;
+        os.fs.write("project/src/main.rs", "fn main() {}\n").await?;
+        os.fs
+            .write("project/src/cli/mod.rs", "pub mod chat;\npub struct Cli;\n")
+            .await?;
+        os.fs
+            .write("project/node_modules/dep/index.js", "module.exports = {}\n")
+            .await?;
+        os.fs.write("project/.env", "SECRET=1\n").await?;
+        os.fs
+            .write("project/logo.png", [0x89, 0x50, 0x4e, 0x47, 0xff, 0xfe])
+            .await?;
+
+        let path = PathBuf::from(os.fs.chroot_path_str("project"));
+        let summary = summarize_directory(&os, &path).await?;
+
+        assert!(summary.contains("(4 files, "));
+        assert!(summary.contains("\nREADME.md (21 B, 3 lines: # Project)\n"));
+        assert!(summary.contains("\nlogo.png (6 B, binary)\n"));
+        assert!(summary.contains(
+            "\nsrc/\n  cli/\n    mod.rs (30 B, 2 lines: mod chat, struct Cli)\n  main.rs (13 B, 1 line: fn main)\n"
+        ));
+        assert!(!summary.contains("node_modules"));
+        assert!(!summary.contains(".env"));
+
+        Ok(())
+    }
+}
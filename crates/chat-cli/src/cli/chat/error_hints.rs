@@ -0,0 +1,227 @@
+//! Clusters of the errors hit in chat, by reason code and status code. Errors that are known
+//! issues get a remediation hint once they repeat, and the clusters are kept in the database for
+//! `q diagnostic`.
+
+use std::collections::{
+    HashMap,
+    HashSet,
+};
+
+use chrono::{
+    DateTime,
+    Utc,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use crate::database::{
+    Database,
+    DatabaseError,
+};
+
+/// Number of times an error is hit in a session before its hint is shown.
+const REPEATS_BEFORE_HINT: usize = 2;
+
+/// Number of clusters kept in the database, the least recently seen ones being dropped first.
+const MAX_CLUSTERS: usize = 20;
+
+/// Maximum length of the reason codes kept, as some reason codes are whole error messages.
+const MAX_TEXT_LEN: usize = 200;
+
+/// A cause of errors with a known remediation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum KnownIssue {
+    /// The credentials expired or were revoked.
+    Auth,
+    /// Requests are throttled.
+    Throttled,
+    /// The model is overloaded or unavailable.
+    ModelUnavailable,
+    /// The conversation doesn't fit in the context window.
+    ContextOverflow,
+    /// The service can't be reached or stops responding.
+    Connectivity,
+}
+
+impl KnownIssue {
+    /// Classifies an error from its reason code and status code.
+    pub fn classify(reason_code: &str, status_code: Option<u16>) -> Option<Self> {
+        let lowercase = reason_code.to_lowercase();
+        match (reason_code, status_code) {
+            (
+                "AuthError"
+                | "CredentialsError"
+                | "ExpiredTokenException"
+                | "UnauthorizedException"
+                | "AccessDeniedException",
+                _,
+            )
+            | (_, Some(401 | 403)) => Some(Self::Auth),
+            ("QuotaBreachError" | "ThrottlingException", _) | (_, Some(429)) => Some(Self::Throttled),
+            ("ModelOverloadedError" | "ServiceUnavailableException", _) | (_, Some(503)) => {
+                Some(Self::ModelUnavailable)
+            },
+            ("ContextWindowOverflow" | "CompactHistoryFailure", _) => Some(Self::ContextOverflow),
+            ("RecvErrorStreamTimeout" | "RecvErrorStalled" | "RecvErrorResponseTimeout", _) => Some(Self::Connectivity),
+            _ if lowercase.contains("dispatch failure") || lowercase.contains("timed out") => Some(Self::Connectivity),
+            _ => None,
+        }
+    }
+
+    /// What to do about the issue.
+    pub fn hint(&self) -> &'static str {
+        match self {
+            Self::Auth => "Your credentials keep being rejected, log in again with q login",
+            Self::Throttled => "Requests keep being throttled, wait a moment or switch models with /model",
+            Self::ModelUnavailable => "The model keeps being unavailable, choose another one with /model",
+            Self::ContextOverflow => "The conversation keeps overflowing the context window, run /compact or /clear",
+            Self::Connectivity => "The service keeps being unreachable, check your network and proxy settings",
+        }
+    }
+}
+
+/// Errors with the same reason code and status code.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ErrorCluster {
+    pub reason_code: String,
+    pub status_code: Option<u16>,
+    pub issue: Option<KnownIssue>,
+    pub count: usize,
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+}
+
+impl ErrorCluster {
+    pub fn hint(&self) -> Option<&'static str> {
+        self.issue.as_ref().map(KnownIssue::hint)
+    }
+}
+
+/// Adds an error to its cluster in `clusters`.
+fn add_to_clusters(clusters: &mut Vec<ErrorCluster>, reason_code: &str, status_code: Option<u16>, now: DateTime<Utc>) {
+    let reason_code = truncate(reason_code);
+    match clusters
+        .iter_mut()
+        .find(|cluster| cluster.reason_code == reason_code && cluster.status_code == status_code)
+    {
+        Some(cluster) => {
+            cluster.count += 1;
+            cluster.last_seen = now;
+        },
+        None => clusters.push(ErrorCluster {
+            issue: KnownIssue::classify(&reason_code, status_code),
+            reason_code,
+            status_code,
+            count: 1,
+            first_seen: now,
+            last_seen: now,
+        }),
+    }
+    clusters.sort_by(|a, b| b.last_seen.cmp(&a.last_seen));
+    clusters.truncate(MAX_CLUSTERS);
+}
+
+fn truncate(text: &str) -> String {
+    match text.char_indices().nth(MAX_TEXT_LEN) {
+        Some((end, _)) => format!("{}...", &text[..end]),
+        None => text.to_string(),
+    }
+}
+
+/// Adds an error to the clusters kept in the database.
+pub fn record_error(database: &Database, reason_code: &str, status_code: Option<u16>) -> Result<(), DatabaseError> {
+    let mut clusters = database.get_error_clusters()?.unwrap_or_default();
+    add_to_clusters(&mut clusters, reason_code, status_code, Utc::now());
+    database.set_error_clusters(&clusters)
+}
+
+/// Errors hit in the current session, to hint at the remediation of those that repeat.
+#[derive(Debug, Default)]
+pub struct ErrorHints {
+    counts: HashMap<(String, Option<u16>), usize>,
+    hinted: HashSet<KnownIssue>,
+}
+
+impl ErrorHints {
+    /// Records an error, returning the issue to hint at if the error is a known issue that just
+    /// repeated and wasn't hinted at yet in this session.
+    pub fn record(&mut self, reason_code: &str, status_code: Option<u16>) -> Option<KnownIssue> {
+        let count = self.counts.entry((reason_code.to_string(), status_code)).or_default();
+        *count += 1;
+        if *count < REPEATS_BEFORE_HINT {
+            return None;
+        }
+        KnownIssue::classify(reason_code, status_code).filter(|issue| self.hinted.insert(*issue))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(KnownIssue::classify("AuthError", None), Some(KnownIssue::Auth));
+        assert_eq!(KnownIssue::classify("SomeException", Some(403)), Some(KnownIssue::Auth));
+        assert_eq!(
+            KnownIssue::classify("QuotaBreachError", None),
+            Some(KnownIssue::Throttled)
+        );
+        assert_eq!(
+            KnownIssue::classify("ModelOverloadedError", Some(500)),
+            Some(KnownIssue::ModelUnavailable)
+        );
+        assert_eq!(
+            KnownIssue::classify("ContextWindowOverflow", Some(400)),
+            Some(KnownIssue::ContextOverflow)
+        );
+        assert_eq!(
+            KnownIssue::classify("dispatch failure: io error", None),
+            Some(KnownIssue::Connectivity)
+        );
+        assert_eq!(KnownIssue::classify("StdIoError", None), None);
+    }
+
+    #[test]
+    fn test_hint_once_repeated() {
+        let mut hints = ErrorHints::default();
+        assert_eq!(hints.record("QuotaBreachError", Some(429)), None);
+        assert_eq!(hints.record("StdIoError", None), None);
+        assert_eq!(hints.record("StdIoError", None), None);
+        assert_eq!(hints.record("QuotaBreachError", Some(429)), Some(KnownIssue::Throttled));
+        assert_eq!(hints.record("QuotaBreachError", Some(429)), None);
+        // Another error of an issue already hinted at
+        assert_eq!(hints.record("ThrottlingException", Some(429)), None);
+        assert_eq!(hints.record("ThrottlingException", Some(429)), None);
+    }
+
+    #[test]
+    fn test_add_to_clusters() {
+        let mut clusters = Vec::new();
+        let start = Utc::now();
+        add_to_clusters(&mut clusters, "RecvErrorStalled", None, start);
+        add_to_clusters(&mut clusters, "StdIoError", None, start);
+        add_to_clusters(
+            &mut clusters,
+            "RecvErrorStalled",
+            None,
+            start + chrono::TimeDelta::minutes(1),
+        );
+
+        assert_eq!(clusters.len(), 2);
+        assert_eq!(clusters[0].reason_code, "RecvErrorStalled");
+        assert_eq!(clusters[0].count, 2);
+        assert_eq!(clusters[0].issue, Some(KnownIssue::Connectivity));
+        assert_eq!(clusters[1].hint(), None);
+
+        for i in 0..MAX_CLUSTERS {
+            add_to_clusters(&mut clusters, &format!("Error{i}"), None, start);
+        }
+        assert_eq!(clusters.len(), MAX_CLUSTERS);
+        assert_eq!(clusters[0].reason_code, "RecvErrorStalled");
+    }
+}
@@ -0,0 +1,106 @@
+//! Exit codes of `q chat`, telling scripts running it with `--no-interactive` why it failed without
+//! parsing its output. 2 is left out as clap exits with it on invalid arguments.
+
+use std::process::ExitCode;
+
+use super::ChatError;
+use crate::api_client::ApiClientError;
+use crate::api_client::error::ConverseStreamErrorKind;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ChatExitCode {
+    #[default]
+    Success              = 0,
+    /// Any failure without a more specific exit code
+    Failure              = 1,
+    /// A limit set with `--max-turns`, `--max-tool-calls`, `--max-cost` or `--timeout` was reached
+    LimitReached         = 3,
+    /// The model could not be reached or did not give a valid response
+    ModelError           = 4,
+    /// A tool use needed approval, which can't be given without user input
+    ToolApprovalRequired = 5,
+    /// The user is not logged in, or their credentials were rejected
+    AuthFailure          = 6,
+    /// The conversation doesn't fit in the context window of the model
+    ContextOverflow      = 7,
+}
+
+impl From<&ChatError> for ChatExitCode {
+    fn from(err: &ChatError) -> Self {
+        match err {
+            ChatError::LimitReached(_) => Self::LimitReached,
+            ChatError::NonInteractiveToolApproval => Self::ToolApprovalRequired,
+            ChatError::Auth(_) => Self::AuthFailure,
+            ChatError::Client(err) => match **err {
+                ApiClientError::AuthError(_) | ApiClientError::Credentials(_) => Self::AuthFailure,
+                _ if is_auth_status(err.status_code()) => Self::AuthFailure,
+                _ => Self::ModelError,
+            },
+            ChatError::SendMessage(err) => match err.source.kind {
+                ConverseStreamErrorKind::ContextWindowOverflow => Self::ContextOverflow,
+                _ if is_auth_status(err.status_code()) => Self::AuthFailure,
+                _ => Self::ModelError,
+            },
+            ChatError::CompactHistoryFailure => Self::ContextOverflow,
+            ChatError::ResponseStream(_) | ChatError::ResponseSchemaMismatch { .. } => Self::ModelError,
+            ChatError::Std(_)
+            | ChatError::Readline(_)
+            | ChatError::Custom(_)
+            | ChatError::Interrupted { .. }
+            | ChatError::GetPromptError(_)
+            | ChatError::AgentSwapError(_)
+            | ChatError::Conduit(_) => Self::Failure,
+        }
+    }
+}
+
+impl From<ChatExitCode> for ExitCode {
+    fn from(code: ChatExitCode) -> Self {
+        ExitCode::from(code as u8)
+    }
+}
+
+fn is_auth_status(status_code: Option<u16>) -> bool {
+    matches!(status_code, Some(401 | 403))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::AuthError;
+    use crate::cli::chat::run_limits::RunLimit;
+
+    #[test]
+    fn test_exit_code_of_error() {
+        assert_eq!(
+            ChatExitCode::from(&ChatError::LimitReached(RunLimit::Turns(3))),
+            ChatExitCode::LimitReached
+        );
+        assert_eq!(
+            ChatExitCode::from(&ChatError::NonInteractiveToolApproval),
+            ChatExitCode::ToolApprovalRequired
+        );
+        assert_eq!(
+            ChatExitCode::from(&ChatError::Auth(AuthError::NoToken)),
+            ChatExitCode::AuthFailure
+        );
+        assert_eq!(
+            ChatExitCode::from(&ChatError::Client(Box::new(ApiClientError::AuthError(
+                AuthError::NoToken
+            )))),
+            ChatExitCode::AuthFailure
+        );
+        assert_eq!(
+            ChatExitCode::from(&ChatError::Client(Box::new(ApiClientError::DefaultModelNotFound))),
+            ChatExitCode::ModelError
+        );
+        assert_eq!(
+            ChatExitCode::from(&ChatError::CompactHistoryFailure),
+            ChatExitCode::ContextOverflow
+        );
+        assert_eq!(
+            ChatExitCode::from(&ChatError::Custom("oops".into())),
+            ChatExitCode::Failure
+        );
+    }
+}
@@ -0,0 +1,144 @@
+//! Context entry describing the state of the git repository the session runs in, so that prompts
+//! such as "review my change" work without pasting the diff by hand. Enabled with the Git Context
+//! experiment.
+
+use std::path::Path;
+
+use tokio::process::Command;
+use tracing::debug;
+
+use super::token_counter::TokenCounter;
+use super::util::truncate_safe_in_place;
+use crate::os::Os;
+
+/// Maximum size of the staged and unstaged diffs combined.
+const MAX_DIFF_CHARS: usize = TokenCounter::token_to_chars(8000);
+
+/// Maximum number of `git status` entries listed.
+const MAX_STATUS_LINES: usize = 50;
+
+const DIFF_TRUNCATION_MARKER: &str = "\n... (diff truncated)";
+
+/// Runs git in `cwd`, returning its stdout if it succeeded.
+async fn git(cwd: &Path, args: &[&str]) -> Option<String> {
+    let output = Command::new("git")
+        .current_dir(cwd)
+        .args(args)
+        // Keeps `git status` from taking the index lock, which could get in the way of the user's
+        // own git commands.
+        .env("GIT_OPTIONAL_LOCKS", "0")
+        .output()
+        .await
+        .ok()?;
+
+    if !output.status.success() {
+        debug!(?args, "git exited with {}", output.status);
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Returns the name of the branch checked out in `cwd`, or [None] outside of a git repository or
+/// with a detached HEAD.
+pub async fn current_branch(cwd: &Path) -> Option<String> {
+    let branch = git(cwd, &["branch", "--show-current"]).await?;
+    Some(branch.trim().to_string()).filter(|b| !b.is_empty())
+}
+
+/// Returns a description of the current branch, status and diff of the git repository containing
+/// the current directory, or [None] if it isn't in a git repository.
+pub async fn git_context(os: &Os) -> Option<String> {
+    let cwd = os.env.current_dir().ok()?;
+    git(&cwd, &["rev-parse", "--is-inside-work-tree"]).await?;
+
+    let branch = git(&cwd, &["branch", "--show-current"]).await.unwrap_or_default();
+    let status = git(&cwd, &["status", "--short"]).await.unwrap_or_default();
+    let staged = git(&cwd, &["diff", "--cached", "--no-color", "--no-ext-diff"])
+        .await
+        .unwrap_or_default();
+    let unstaged = git(&cwd, &["diff", "--no-color", "--no-ext-diff"])
+        .await
+        .unwrap_or_default();
+
+    Some(format_git_context(&cwd, branch.trim(), &status, staged, unstaged))
+}
+
+fn format_git_context(cwd: &Path, branch: &str, status: &str, mut staged: String, mut unstaged: String) -> String {
+    let mut res = format!("Current state of the git repository at {}:\n", cwd.display());
+    match branch {
+        "" => res.push_str("Branch: (detached HEAD)\n"),
+        branch => res.push_str(&format!("Branch: {branch}\n")),
+    }
+
+    let status = status.lines().collect::<Vec<_>>();
+    if status.is_empty() {
+        res.push_str("\nThe working tree is clean.\n");
+        return res;
+    }
+    res.push_str("\nStatus (git status --short):\n");
+    for line in status.iter().take(MAX_STATUS_LINES) {
+        res.push_str(line);
+        res.push('\n');
+    }
+    if status.len() > MAX_STATUS_LINES {
+        res.push_str(&format!("... and {} more\n", status.len() - MAX_STATUS_LINES));
+    }
+
+    // Each diff gets half of the limit, plus whatever the other one doesn't use.
+    let staged_limit = (MAX_DIFF_CHARS / 2).max(MAX_DIFF_CHARS.saturating_sub(unstaged.len()));
+    truncate_safe_in_place(&mut staged, staged_limit, DIFF_TRUNCATION_MARKER);
+    truncate_safe_in_place(
+        &mut unstaged,
+        MAX_DIFF_CHARS.saturating_sub(staged.len()),
+        DIFF_TRUNCATION_MARKER,
+    );
+
+    for (title, diff) in [
+        ("Staged changes (git diff --cached)", staged),
+        ("Unstaged changes (git diff)", unstaged),
+    ] {
+        if !diff.trim().is_empty() {
+            res.push_str(&format!("\n{title}:\n```diff\n{}\n```\n", diff.trim_end()));
+        }
+    }
+
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_git_context() {
+        let cwd = Path::new("/repo");
+
+        let clean = format_git_context(cwd, "main", "", String::new(), String::new());
+        assert_eq!(
+            clean,
+            "Current state of the git repository at /repo:\nBranch: main\n\nThe working tree is clean.\n"
+        );
+
+        let dirty = format_git_context(
+            cwd,
+            "",
+            "M  src/lib.rs\n?? notes.md\n",
+            "-old\n+new\n".to_string(),
+            String::new(),
+        );
+        assert!(dirty.contains("Branch: (detached HEAD)\n"));
+        assert!(dirty.contains("\nM  src/lib.rs\n?? notes.md\n"));
+        assert!(dirty.contains("Staged changes (git diff --cached):\n```diff\n-old\n+new\n```\n"));
+        assert!(!dirty.contains("Unstaged changes"));
+
+        let large = format_git_context(
+            cwd,
+            "main",
+            " M big.txt\n",
+            "a".repeat(MAX_DIFF_CHARS),
+            "b".repeat(MAX_DIFF_CHARS),
+        );
+        assert!(large.len() < MAX_DIFF_CHARS + 500);
+        assert_eq!(large.matches(DIFF_TRUNCATION_MARKER).count(), 2);
+    }
+}
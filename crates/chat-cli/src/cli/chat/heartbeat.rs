@@ -0,0 +1,283 @@
+//! Heartbeats of the responses and tool uses making no visible progress for a while, to tell
+//! progress from a hang. Each heartbeat is sent as a `heartbeat` meta event, which `q chat serve`
+//! forwards to its client, and updates a status line in interactive sessions.
+
+use std::io::Write;
+use std::sync::atomic::{
+    AtomicBool,
+    Ordering,
+};
+use std::time::{
+    Duration,
+    Instant,
+};
+
+use chat_cli_ui::protocol::{
+    Event,
+    MetaEvent,
+};
+use crossterm::{
+    cursor,
+    queue,
+    terminal,
+};
+use serde::Serialize;
+
+use crate::database::settings::{
+    Setting,
+    Settings,
+};
+
+/// Seconds without progress after which heartbeats are reported, if not set.
+const DEFAULT_HEARTBEAT_SECONDS: usize = 10;
+
+/// The interval of heartbeats of the `chat.heartbeatSeconds` setting, [None] if disabled.
+pub fn interval(settings: &Settings) -> Option<Duration> {
+    match settings.get_int_or(Setting::ChatHeartbeatSeconds, DEFAULT_HEARTBEAT_SECONDS) {
+        0 => None,
+        seconds => Some(Duration::from_secs(seconds as u64)),
+    }
+}
+
+/// What the turn is doing.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase", tag = "phase")]
+pub enum Phase {
+    /// Waiting for the first event of the response
+    WaitingForResponse,
+    /// Receiving the response, or the input of `tool` when the model is writing a tool use
+    ReceivingResponse {
+        tool: Option<String>,
+    },
+    RunningTool {
+        tool: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Heartbeat {
+    #[serde(flatten)]
+    pub phase: Phase,
+    /// Since the request was sent or the tool started
+    pub elapsed_ms: u64,
+    /// Size of the response received so far, [None] for tools
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bytes_received: Option<usize>,
+}
+
+impl Heartbeat {
+    pub fn new(phase: Phase, elapsed: Duration, bytes_received: Option<usize>) -> Self {
+        Self {
+            phase,
+            elapsed_ms: elapsed.as_millis() as u64,
+            bytes_received,
+        }
+    }
+
+    pub fn event(&self) -> Event {
+        Event::MetaEvent(MetaEvent {
+            meta_type: "heartbeat".to_string(),
+            payload: serde_json::to_value(self).unwrap_or_default(),
+        })
+    }
+
+    /// E.g. `Receiving fs_write (12.3 KB, 42s)...`.
+    pub fn status_line(&self) -> String {
+        let elapsed = format!("{}s", self.elapsed_ms / 1000);
+        let progress = match self.bytes_received {
+            Some(bytes) => format!("{}, {elapsed}", format_size(bytes)),
+            None => elapsed,
+        };
+        match &self.phase {
+            Phase::WaitingForResponse => format!("Waiting for the response ({progress})..."),
+            Phase::ReceivingResponse { tool: Some(tool) } => format!("Receiving {tool} ({progress})..."),
+            Phase::ReceivingResponse { tool: None } => format!("Receiving the response ({progress})..."),
+            Phase::RunningTool { tool } => format!("Running {tool} ({progress})..."),
+        }
+    }
+}
+
+fn format_size(size: usize) -> String {
+    match size {
+        s if s < 1024 => format!("{s} B"),
+        s if s < 1024 * 1024 => format!("{:.1} KB", s as f64 / 1024.0),
+        s => format!("{:.1} MB", s as f64 / (1024.0 * 1024.0)),
+    }
+}
+
+/// Tells when heartbeats are due: once `interval` passed without progress, then every
+/// `interval` until there is progress again.
+#[derive(Debug)]
+pub struct HeartbeatTimer {
+    interval: Duration,
+    last: Instant,
+}
+
+impl HeartbeatTimer {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            last: Instant::now(),
+        }
+    }
+
+    pub fn progressed(&mut self) {
+        self.last = Instant::now();
+    }
+
+    pub fn is_due(&mut self) -> bool {
+        self.is_due_at(Instant::now())
+    }
+
+    fn is_due_at(&mut self, now: Instant) -> bool {
+        if now.duration_since(self.last) < self.interval {
+            return false;
+        }
+        self.last = now;
+        true
+    }
+}
+
+/// Whether a tool wrote output, and whether the status line of its heartbeats is shown.
+#[derive(Debug, Default)]
+pub struct ToolActivity {
+    wrote: AtomicBool,
+    status_shown: AtomicBool,
+}
+
+impl ToolActivity {
+    /// Whether the tool wrote output since the last call.
+    pub fn take_output(&self) -> bool {
+        self.wrote.swap(false, Ordering::Relaxed)
+    }
+
+    /// Shows `status` in place of the previous status line.
+    pub fn show_status(&self, mut output: impl Write, status: &str) -> std::io::Result<()> {
+        queue!(
+            output,
+            terminal::Clear(terminal::ClearType::CurrentLine),
+            cursor::MoveToColumn(0),
+            crossterm::style::Print(status),
+        )?;
+        self.status_shown.store(true, Ordering::Relaxed);
+        output.flush()
+    }
+
+    /// Clears the status line, if shown.
+    pub fn clear_status(&self, mut output: impl Write) -> std::io::Result<()> {
+        if self.status_shown.swap(false, Ordering::Relaxed) {
+            queue!(
+                output,
+                terminal::Clear(terminal::ClearType::CurrentLine),
+                cursor::MoveToColumn(0),
+            )?;
+            output.flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// The output of a tool, clearing the status line of its heartbeats before writing to it.
+pub struct ToolOutput<'a, W> {
+    inner: &'a mut W,
+    activity: &'a ToolActivity,
+}
+
+impl<'a, W: Write> ToolOutput<'a, W> {
+    pub fn new(inner: &'a mut W, activity: &'a ToolActivity) -> Self {
+        Self { inner, activity }
+    }
+}
+
+impl<W: Write> Write for ToolOutput<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.activity.wrote.store(true, Ordering::Relaxed);
+        self.activity.clear_status(&mut *self.inner)?;
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event() {
+        let heartbeat = Heartbeat::new(
+            Phase::ReceivingResponse {
+                tool: Some("fs_write".to_string()),
+            },
+            Duration::from_millis(42_500),
+            Some(12_600),
+        );
+        let Event::MetaEvent(meta) = heartbeat.event() else {
+            unreachable!()
+        };
+        assert_eq!(meta.meta_type, "heartbeat");
+        assert_eq!(
+            meta.payload,
+            serde_json::json!({
+                "phase": "receivingResponse",
+                "tool": "fs_write",
+                "elapsedMs": 42_500,
+                "bytesReceived": 12_600,
+            })
+        );
+        assert_eq!(heartbeat.status_line(), "Receiving fs_write (12.3 KB, 42s)...");
+
+        let heartbeat = Heartbeat::new(
+            Phase::RunningTool {
+                tool: "execute_bash".to_string(),
+            },
+            Duration::from_secs(75),
+            None,
+        );
+        assert_eq!(
+            serde_json::to_value(&heartbeat).unwrap(),
+            serde_json::json!({ "phase": "runningTool", "tool": "execute_bash", "elapsedMs": 75_000 })
+        );
+        assert_eq!(heartbeat.status_line(), "Running execute_bash (75s)...");
+    }
+
+    #[test]
+    fn test_timer() {
+        let mut timer = HeartbeatTimer::new(Duration::from_secs(10));
+        let start = timer.last;
+        assert!(!timer.is_due_at(start + Duration::from_secs(9)));
+        assert!(timer.is_due_at(start + Duration::from_secs(10)));
+        assert!(!timer.is_due_at(start + Duration::from_secs(15)));
+        assert!(timer.is_due_at(start + Duration::from_secs(20)));
+    }
+
+    #[test]
+    fn test_tool_output_clears_status() {
+        let activity = ToolActivity::default();
+        let mut buf = Vec::new();
+        activity.show_status(&mut buf, "Running slow_tool (10s)...").unwrap();
+        let status_len = buf.len();
+
+        ToolOutput::new(&mut buf, &activity).write_all(b"done").unwrap();
+        assert!(activity.take_output());
+        assert!(!activity.take_output());
+        let written = String::from_utf8(buf[status_len..].to_vec()).unwrap();
+        assert!(written.starts_with('\x1b') && written.ends_with("done"));
+
+        // Only the first write after the status line clears it
+        let mut buf = Vec::new();
+        ToolOutput::new(&mut buf, &activity).write_all(b"more").unwrap();
+        assert_eq!(buf, b"more");
+    }
+
+    #[tokio::test]
+    async fn test_interval() {
+        let mut settings = Settings::new().await.unwrap();
+        assert_eq!(interval(&settings), Some(Duration::from_secs(10)));
+        settings.set(Setting::ChatHeartbeatSeconds, 0).await.unwrap();
+        assert_eq!(interval(&settings), None);
+    }
+}
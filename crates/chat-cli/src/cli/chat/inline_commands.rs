@@ -0,0 +1,238 @@
+//! Shell commands inlined in prompts, written `!{command}`, e.g. `Fix this test: !{cargo test 2>&1
+//! | tail -n 20}`. Each command runs when the prompt is submitted and is replaced with its output,
+//! capped to [MAX_OUTPUT_CHARS]. They work in user prompts, including saved prompts, and in the
+//! prompt of agents, where they run once for each prompt submitted. `!{` directly following a word,
+//! as in `json!{...}`, isn't a command.
+//!
+//! Commands go through the permissions of the `execute_bash` tool of the active agent: commands
+//! the tool would run without asking run as is, denied commands never run, and the others need the
+//! user's confirmation. Agent prompts are sent without the user typing anything, so commands of
+//! agent prompts that need confirmation are skipped.
+
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use regex::{
+    Captures,
+    Regex,
+};
+use tracing::warn;
+
+use super::token_counter::TokenCounter;
+use super::tools::execute::{
+    ExecuteCommand,
+    run_command,
+};
+use crate::cli::agent::{
+    Agents,
+    PermissionEvalResult,
+};
+use crate::os::Os;
+
+static INLINE_COMMAND: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\B!\{([^}]+)\}").unwrap());
+
+/// Maximum size of the output substituted for one command.
+pub const MAX_OUTPUT_CHARS: usize = TokenCounter::token_to_chars(2000);
+
+/// Time after which a command is abandoned, so that a hanging command doesn't block the prompt.
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Returns the distinct commands inlined in `text`, in order of appearance.
+pub fn commands(text: &str) -> Vec<String> {
+    let mut commands: Vec<String> = Vec::new();
+    for captures in INLINE_COMMAND.captures_iter(text) {
+        let command = captures[1].trim().to_string();
+        if !command.is_empty() && !commands.contains(&command) {
+            commands.push(command);
+        }
+    }
+    commands
+}
+
+/// Evaluates whether `command` may run, as the `execute_bash` tool of the active agent would.
+pub fn permission(os: &Os, agents: &Agents, command: &str) -> PermissionEvalResult {
+    let execute = ExecuteCommand {
+        command: command.to_string(),
+        summary: None,
+    };
+    if agents.read_only && execute.requires_acceptance(None, true) {
+        return PermissionEvalResult::Deny(vec!["read-only mode is enabled for this session".to_string()]);
+    }
+    match agents.get_active().map(|agent| execute.eval_perm(os, agent)) {
+        Some(PermissionEvalResult::Deny(rules)) => PermissionEvalResult::Deny(rules),
+        _ if agents.trust_all_tools => PermissionEvalResult::Allow,
+        Some(result) => result,
+        None => PermissionEvalResult::Ask,
+    }
+}
+
+/// Runs `command`, returning the output to substitute for it. Failures are described in the
+/// output rather than returned, so that the prompt is still sent.
+pub async fn run(os: &Os, command: &str) -> String {
+    let result = tokio::time::timeout(
+        COMMAND_TIMEOUT,
+        run_command(os, command, MAX_OUTPUT_CHARS, None::<std::io::Stderr>),
+    )
+    .await;
+    match result {
+        Ok(Ok(result)) if result.exit_status == Some(0) => result.stdout.trim_end().to_string(),
+        Ok(Ok(result)) => {
+            let status = result
+                .exit_status
+                .map_or("was terminated".to_string(), |code| format!("exited with {code}"));
+            let output = format!("{}\n{}", result.stdout.trim_end(), result.stderr.trim_end());
+            format!("{}\n(`{command}` {status})", output.trim())
+                .trim_start()
+                .to_string()
+        },
+        Ok(Err(err)) => {
+            warn!(?err, "failed to run inline command {command}");
+            format!("(`{command}` failed to run: {err})")
+        },
+        Err(_) => format!("(`{command}` timed out after {} s)", COMMAND_TIMEOUT.as_secs()),
+    }
+}
+
+/// Replaces the commands of `text` with their output, given as `(command, output)` pairs. Commands
+/// without an output are left as is.
+pub fn substitute(text: &str, outputs: &[(String, String)]) -> String {
+    INLINE_COMMAND
+        .replace_all(text, |captures: &Captures<'_>| {
+            let command = captures[1].trim();
+            match outputs.iter().find(|(c, _)| c == command) {
+                Some((_, output)) => output.clone(),
+                None => captures[0].to_string(),
+            }
+        })
+        .to_string()
+}
+
+/// Replaces the commands of an agent prompt with their output, running only the commands allowed
+/// without confirmation. The others are replaced with a note saying why they didn't run.
+pub async fn expand_agent_prompt(os: &Os, agents: &Agents, prompt: &str) -> String {
+    let mut outputs = Vec::new();
+    for command in commands(prompt) {
+        let output = match permission(os, agents, &command) {
+            PermissionEvalResult::Allow => run(os, &command).await,
+            PermissionEvalResult::Ask => {
+                warn!("skipping inline command {command} of the agent prompt as it needs approval");
+                format!("(`{command}` was not run as it needs approval)")
+            },
+            PermissionEvalResult::Deny(rules) => {
+                format!("(`{command}` was not run as it is denied: {})", rules.join(", "))
+            },
+        };
+        outputs.push((command, output));
+    }
+    substitute(prompt, &outputs)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::cli::agent::{
+        Agent,
+        ToolSettingTarget,
+    };
+
+    #[test]
+    fn test_commands() {
+        assert_eq!(
+            commands("Status: !{git status} diff: !{ git diff } again !{git status} !{ } {not} !{}"),
+            vec!["git status", "git diff"]
+        );
+        assert!(commands("No commands {here} or !here").is_empty());
+        assert!(commands("Build it with `json!{\"a\": 1}` and vec!{1}").is_empty());
+        assert_eq!(commands("(!{date})!{whoami}"), vec!["date", "whoami"]);
+    }
+
+    #[test]
+    fn test_substitute() {
+        assert_eq!(
+            substitute("a !{one} b !{ two } c !{three}", &[
+                ("one".to_string(), "1".to_string()),
+                ("two".to_string(), "2".to_string()),
+            ]),
+            "a 1 b 2 c !{three}"
+        );
+    }
+
+    /// Agents whose active agent allows `echo` and denies `git push`.
+    fn test_agents() -> Agents {
+        let tool_name = if cfg!(windows) { "execute_cmd" } else { "execute_bash" };
+        let agent = Agent {
+            tools_settings: HashMap::from([(
+                ToolSettingTarget(tool_name.to_string()),
+                serde_json::json!({
+                    "allowedCommands": ["echo .*"],
+                    "deniedCommands": ["git push.*"]
+                }),
+            )]),
+            ..Default::default()
+        };
+        Agents {
+            active_idx: agent.name.clone(),
+            agents: HashMap::from([(agent.name.clone(), agent)]),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_permission() {
+        let os = Os::new().await.unwrap();
+        let mut agents = test_agents();
+
+        assert_eq!(permission(&os, &agents, "echo hi"), PermissionEvalResult::Allow);
+        assert_eq!(permission(&os, &agents, "rm -rf target"), PermissionEvalResult::Ask);
+        assert!(matches!(
+            permission(&os, &agents, "git push"),
+            PermissionEvalResult::Deny(_)
+        ));
+
+        agents.trust_all_tools = true;
+        assert_eq!(permission(&os, &agents, "rm -rf target"), PermissionEvalResult::Allow);
+        assert!(matches!(
+            permission(&os, &agents, "git push"),
+            PermissionEvalResult::Deny(_)
+        ));
+
+        agents.read_only = true;
+        assert!(matches!(
+            permission(&os, &agents, "rm -rf target"),
+            PermissionEvalResult::Deny(_)
+        ));
+        assert_eq!(permission(&os, &agents, "ls"), PermissionEvalResult::Allow);
+    }
+
+    #[cfg(not(windows))]
+    #[tokio::test]
+    async fn test_run() {
+        let os = Os::new().await.unwrap();
+        assert_eq!(run(&os, "echo hello").await, "hello");
+        assert_eq!(
+            run(&os, "echo oops >&2; exit 3").await,
+            "oops\n(`echo oops >&2; exit 3` exited with 3)"
+        );
+
+        let output = run(&os, &format!("head -c {} /dev/zero | tr '\\0' a", MAX_OUTPUT_CHARS * 2)).await;
+        assert!(output.len() < MAX_OUTPUT_CHARS + 20);
+        assert!(output.ends_with("truncated"));
+    }
+
+    #[cfg(not(windows))]
+    #[tokio::test]
+    async fn test_expand_agent_prompt() {
+        let os = Os::new().await.unwrap();
+        assert_eq!(
+            expand_agent_prompt(
+                &os,
+                &test_agents(),
+                "Greeting: !{echo hi}. Cleanup: !{rm -rf target}. Push: !{git push}"
+            )
+            .await,
+            "Greeting: hi. Cleanup: (`rm -rf target` was not run as it needs approval). Push: (`git push` was not run as it is denied: \\Agit push.*\\z)"
+        );
+    }
+}
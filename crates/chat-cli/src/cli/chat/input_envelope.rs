@@ -0,0 +1,177 @@
+//! Structured input of `q chat --no-interactive --input-format json`, given through stdin as a
+//! JSON object listing the prompt along with the files and images to attach to it, so that
+//! wrappers don't have to fit everything into a single argument:
+//!
+//! ```json
+//! { "prompt": "Why does this test fail?", "attachments": [{ "path": "tests/api.rs" }, { "path": "ci.png" }] }
+//! ```
+//!
+//! Images are attached like with `--attach`. The content of other files is added to the prompt.
+
+use std::path::PathBuf;
+
+use clap::ValueEnum;
+use serde::Deserialize;
+use thiserror::Error;
+
+use super::token_counter::TokenCounter;
+use super::util::images::is_supported_image_type;
+use crate::os::Os;
+
+/// Maximum size of the content added to the prompt for one file.
+const MAX_FILE_CHARS: usize = TokenCounter::token_to_chars(25_000);
+
+/// Format of the input of `q chat --no-interactive` given through stdin
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum InputFormat {
+    /// The prompt as is
+    #[default]
+    Text,
+    /// A JSON object with the `prompt` and the `attachments` to attach to it by `path`
+    Json,
+}
+
+#[derive(Debug, Error)]
+pub enum InputEnvelopeError {
+    #[error("The input is not a valid JSON envelope: {0}")]
+    Invalid(#[from] serde_json::Error),
+    #[error("Failed to read the attachment at {}: {error}", path.display())]
+    Io { path: PathBuf, error: std::io::Error },
+    #[error("The attachment at {} is neither a text file nor a supported image", path.display())]
+    Binary { path: PathBuf },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct InputEnvelope {
+    pub prompt: String,
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct Attachment {
+    pub path: PathBuf,
+}
+
+/// The prompt of an envelope with its files added, and the images to attach to it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnvelopeInput {
+    pub prompt: String,
+    pub images: Vec<String>,
+}
+
+impl InputEnvelope {
+    pub fn parse(input: &str) -> Result<Self, InputEnvelopeError> {
+        Ok(serde_json::from_str(input)?)
+    }
+
+    /// Reads the attached files, adding their content to the prompt after it.
+    pub async fn into_input(self, os: &Os) -> Result<EnvelopeInput, InputEnvelopeError> {
+        let mut prompt = self.prompt;
+        let mut images = Vec::new();
+        let mut files = String::new();
+        for Attachment { path } in self.attachments {
+            if is_supported_image_type(&path.to_string_lossy()) {
+                images.push(path.to_string_lossy().to_string());
+                continue;
+            }
+
+            let content = match os.fs.read(&path).await {
+                Ok(content) => content,
+                Err(error) => return Err(InputEnvelopeError::Io { path, error }),
+            };
+            let Ok(content) = String::from_utf8(content) else {
+                return Err(InputEnvelopeError::Binary { path });
+            };
+            let truncated = match content.char_indices().nth(MAX_FILE_CHARS) {
+                Some((end, _)) => &content[..end],
+                None => content.as_str(),
+            };
+            files.push_str(&format!("[{}]\n{}\n", path.display(), truncated));
+            if truncated.len() < content.len() {
+                files.push_str("... (truncated)\n");
+            }
+        }
+
+        if !files.is_empty() {
+            prompt.push_str("\n\nAttached files:\n");
+            prompt.push_str(&files);
+        }
+        Ok(EnvelopeInput { prompt, images })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_into_input() {
+        let os = Os::new().await.unwrap();
+        os.fs.create_dir_all("/src").await.unwrap();
+        os.fs
+            .write("/src/lib.rs", "pub fn answer() -> u32 { 42 }")
+            .await
+            .unwrap();
+
+        let envelope = InputEnvelope::parse(
+            r#"{"prompt": "Why?", "attachments": [{"path": "/src/lib.rs"}, {"path": "/shots/ci.PNG"}]}"#,
+        )
+        .unwrap();
+        assert_eq!(envelope.into_input(&os).await.unwrap(), EnvelopeInput {
+            prompt: "Why?\n\nAttached files:\n[/src/lib.rs]\npub fn answer() -> u32 { 42 }\n".to_string(),
+            images: vec!["/shots/ci.PNG".to_string()],
+        });
+
+        // Truncated by characters rather than bytes
+        os.fs.write("/big.txt", "é".repeat(MAX_FILE_CHARS + 1)).await.unwrap();
+        let envelope = InputEnvelope::parse(r#"{"prompt": "Why?", "attachments": [{"path": "/big.txt"}]}"#).unwrap();
+        assert_eq!(
+            envelope.into_input(&os).await.unwrap().prompt,
+            format!(
+                "Why?\n\nAttached files:\n[/big.txt]\n{}\n... (truncated)\n",
+                "é".repeat(MAX_FILE_CHARS)
+            )
+        );
+
+        let envelope = InputEnvelope::parse(r#"{"prompt": "Hi"}"#).unwrap();
+        assert_eq!(envelope.into_input(&os).await.unwrap(), EnvelopeInput {
+            prompt: "Hi".to_string(),
+            images: vec![],
+        });
+    }
+
+    #[tokio::test]
+    async fn test_into_input_errors() {
+        let os = Os::new().await.unwrap();
+        os.fs.write("/data.bin", [0xff, 0xfe, 0x00]).await.unwrap();
+
+        let envelope = InputEnvelope::parse(r#"{"prompt": "x", "attachments": [{"path": "/missing.rs"}]}"#).unwrap();
+        assert!(matches!(
+            envelope.into_input(&os).await,
+            Err(InputEnvelopeError::Io { .. })
+        ));
+        let envelope = InputEnvelope::parse(r#"{"prompt": "x", "attachments": [{"path": "/data.bin"}]}"#).unwrap();
+        assert!(matches!(
+            envelope.into_input(&os).await,
+            Err(InputEnvelopeError::Binary { .. })
+        ));
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        for input in [
+            "Fix the tests",
+            r#"{"attachments": []}"#,
+            r#"{"prompt": "x", "files": []}"#,
+            r#"{"prompt": "x", "attachments": ["src/lib.rs"]}"#,
+        ] {
+            assert!(
+                matches!(InputEnvelope::parse(input), Err(InputEnvelopeError::Invalid(_))),
+                "{input}"
+            );
+        }
+    }
+}
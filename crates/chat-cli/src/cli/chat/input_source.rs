@@ -1,5 +1,12 @@
+use std::sync::Arc;
+use std::sync::atomic::{
+    AtomicBool,
+    Ordering,
+};
+
 use eyre::Result;
 use rustyline::error::ReadlineError;
+use tracing::warn;
 
 use super::prompt::{
     PasteState,
@@ -9,15 +16,21 @@ use super::prompt::{
 };
 #[cfg(unix)]
 use super::skim_integration::SkimCommandSelector;
+use crate::database::Database;
 use crate::os::Os;
 
 #[derive(Debug)]
 pub struct InputSource {
     inner: inner::Inner,
     paste_state: PasteState,
+    /// Where the prompts read from the terminal are recorded for searching them in later sessions
+    prompt_history: Option<Database>,
 }
 
 mod inner {
+    use std::sync::Arc;
+    use std::sync::atomic::AtomicBool;
+
     use rustyline::Editor;
     use rustyline::history::FileHistory;
 
@@ -27,6 +40,12 @@ mod inner {
     #[derive(Debug)]
     pub enum Inner {
         Readline(Editor<ChatHelper, FileHistory>),
+        /// Lines sent by a client of `q chat serve`
+        Channel {
+            receiver: std::sync::mpsc::Receiver<String>,
+            /// Whether a tool use waits for the client to approve it
+            awaiting_approval: Arc<AtomicBool>,
+        },
         #[allow(dead_code)]
         Mock {
             index: usize,
@@ -46,6 +65,7 @@ impl InputSource {
         Ok(Self {
             inner: inner::Inner::Readline(rl(os, sender, receiver, paste_state.clone())?),
             paste_state,
+            prompt_history: Some(os.database.clone()),
         })
     }
 
@@ -96,11 +116,64 @@ impl InputSource {
         }
     }
 
+    /// Sets the names of the tools completed after `/tools trust`.
+    pub fn set_tool_names(&mut self, tool_names: Vec<String>) {
+        if let inner::Inner::Readline(rl) = &mut self.inner {
+            if let Some(helper) = rl.helper_mut() {
+                helper.set_tool_names(tool_names);
+            }
+        }
+    }
+
+    /// Sets the names of the custom commands completed after `/`.
+    pub fn set_custom_commands(&mut self, names: Vec<String>) {
+        if let inner::Inner::Readline(rl) = &mut self.inner {
+            if let Some(helper) = rl.helper_mut() {
+                helper.set_custom_commands(names);
+            }
+        }
+    }
+
+    /// Sets the number of images attached to the next prompt, counted in its tokens.
+    pub fn set_attached_images(&mut self, count: usize) {
+        if let inner::Inner::Readline(rl) = &mut self.inner {
+            if let Some(helper) = rl.helper_mut() {
+                helper.set_attached_images(count);
+            }
+        }
+    }
+
+    /// Tells the sender of the lines whether the next one answers a tool approval prompt.
+    pub fn set_awaiting_approval(&mut self, awaiting: bool) {
+        if let inner::Inner::Channel { awaiting_approval, .. } = &self.inner {
+            awaiting_approval.store(awaiting, Ordering::SeqCst);
+        }
+    }
+
+    /// Reads the lines sent through `receiver`, ending the input once all its senders are dropped.
+    /// `awaiting_approval` is set while a tool use waits for approval.
+    pub fn new_channel(receiver: std::sync::mpsc::Receiver<String>, awaiting_approval: Arc<AtomicBool>) -> Self {
+        Self {
+            inner: inner::Inner::Channel {
+                receiver,
+                awaiting_approval,
+            },
+            paste_state: PasteState::new(),
+            prompt_history: None,
+        }
+    }
+
+    /// Whether the input is read from the terminal, where the user can also answer other prompts.
+    pub fn is_terminal(&self) -> bool {
+        matches!(self.inner, inner::Inner::Readline(_))
+    }
+
     #[allow(dead_code)]
     pub fn new_mock(lines: Vec<String>) -> Self {
         Self {
             inner: inner::Inner::Mock { index: 0, lines },
             paste_state: PasteState::new(),
+            prompt_history: None,
         }
     }
 
@@ -108,11 +181,19 @@ impl InputSource {
         match &mut self.inner {
             inner::Inner::Readline(rl) => {
                 let prompt = prompt.unwrap_or_default();
+                if let Some(helper) = rl.helper() {
+                    helper.block_input().reset();
+                }
                 let curr_line = rl.readline(prompt);
                 match curr_line {
                     Ok(line) => {
                         if Self::should_append_history(&line) {
                             let _ = rl.add_history_entry(line.as_str());
+                            if let Some(database) = &self.prompt_history {
+                                if let Err(err) = database.add_prompt_history(&line) {
+                                    warn!(?err, "Failed to record the prompt history");
+                                }
+                            }
                         }
                         Ok(Some(line))
                     },
@@ -120,6 +201,7 @@ impl InputSource {
                     Err(err) => Err(err),
                 }
             },
+            inner::Inner::Channel { receiver, .. } => Ok(receiver.recv().ok()),
             inner::Inner::Mock { index, lines } => {
                 *index += 1;
                 Ok(lines.get(*index - 1).cloned())
@@ -175,4 +257,21 @@ mod tests {
         assert_eq!(input.read_line(None).unwrap().unwrap(), l3);
         assert!(input.read_line(None).unwrap().is_none());
     }
+
+    #[test]
+    fn test_channel_input_source() {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let awaiting_approval = Arc::new(AtomicBool::new(false));
+        let mut input = InputSource::new_channel(receiver, awaiting_approval.clone());
+        assert!(!input.is_terminal());
+        input.set_awaiting_approval(true);
+        assert!(awaiting_approval.load(Ordering::SeqCst));
+
+        sender.send("Hello".to_string()).unwrap();
+        sender.send("y".to_string()).unwrap();
+        drop(sender);
+        assert_eq!(input.read_line(None).unwrap().unwrap(), "Hello");
+        assert_eq!(input.read_line(None).unwrap().unwrap(), "y");
+        assert!(input.read_line(None).unwrap().is_none());
+    }
 }
@@ -57,6 +57,10 @@ pub struct UserMessage {
     pub content: UserMessageContent,
     pub timestamp: Option<DateTime<FixedOffset>>,
     pub images: Option<Vec<ImageBlock>>,
+    /// Whether the user marked this message with `/important`. Important messages are kept
+    /// verbatim when the history is summarized.
+    #[serde(default)]
+    pub important: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -111,6 +115,7 @@ impl UserMessage {
     pub fn new_prompt(prompt: String, timestamp: Option<DateTime<FixedOffset>>) -> Self {
         Self {
             images: None,
+            important: false,
             timestamp,
             additional_context: String::new(),
             env_context: UserEnvContext::generate_new(),
@@ -122,9 +127,40 @@ impl UserMessage {
         prompt: Option<String>,
         tool_use_ids: impl Iterator<Item = &'a str>,
         timestamp: Option<DateTime<FixedOffset>>,
+    ) -> Self {
+        Self::new_cancelled_tool_uses_with_result(
+            prompt,
+            tool_use_ids,
+            "Tool use was cancelled by the user".to_string(),
+            timestamp,
+        )
+    }
+
+    /// Creates a new [UserMessageContent::CancelledToolUses] where each tool use result is
+    /// populated with a reason provided by the user for denying the tool use.
+    pub fn new_denied_tool_uses<'a>(
+        prompt: Option<String>,
+        tool_use_ids: impl Iterator<Item = &'a str>,
+        reason: &str,
+        timestamp: Option<DateTime<FixedOffset>>,
+    ) -> Self {
+        Self::new_cancelled_tool_uses_with_result(
+            prompt,
+            tool_use_ids,
+            format!("Tool use was denied by the user. Reason: {reason}"),
+            timestamp,
+        )
+    }
+
+    fn new_cancelled_tool_uses_with_result<'a>(
+        prompt: Option<String>,
+        tool_use_ids: impl Iterator<Item = &'a str>,
+        result_text: String,
+        timestamp: Option<DateTime<FixedOffset>>,
     ) -> Self {
         Self {
             images: None,
+            important: false,
             timestamp,
             additional_context: String::new(),
             env_context: UserEnvContext::generate_new(),
@@ -133,9 +169,7 @@ impl UserMessage {
                 tool_use_results: tool_use_ids
                     .map(|id| ToolUseResult {
                         tool_use_id: id.to_string(),
-                        content: vec![ToolUseResultBlock::Text(
-                            "Tool use was cancelled by the user".to_string(),
-                        )],
+                        content: vec![ToolUseResultBlock::Text(result_text.clone())],
                         status: ToolResultStatus::Error,
                     })
                     .collect(),
@@ -152,6 +186,7 @@ impl UserMessage {
                 tool_use_results: results,
             },
             images: None,
+            important: false,
         }
     }
 
@@ -168,6 +203,7 @@ impl UserMessage {
                 tool_use_results: results,
             },
             images: Some(images),
+            important: false,
         }
     }
 
@@ -218,7 +254,11 @@ impl UserMessage {
                 tools: if tools.is_empty() {
                     None
                 } else {
-                    Some(tools.values().flatten().cloned().collect::<Vec<_>>())
+                    // Sorted so that the request stays the same between turns, as the order of
+                    // the map isn't stable
+                    let mut tools = tools.values().flatten().cloned().collect::<Vec<_>>();
+                    tools.sort_by(|Tool::ToolSpecification(a), Tool::ToolSpecification(b)| a.name.cmp(&b.name));
+                    Some(tools)
                 },
                 ..Default::default()
             }),
@@ -242,6 +282,14 @@ impl UserMessage {
         }
     }
 
+    pub fn tool_use_results_mut(&mut self) -> Option<&mut [ToolUseResult]> {
+        match &mut self.content {
+            UserMessageContent::Prompt { .. } => None,
+            UserMessageContent::CancelledToolUses { tool_use_results, .. } => Some(tool_use_results.as_mut_slice()),
+            UserMessageContent::ToolUseResults { tool_use_results } => Some(tool_use_results.as_mut_slice()),
+        }
+    }
+
     pub fn additional_context(&self) -> &str {
         &self.additional_context
     }
@@ -480,6 +528,18 @@ impl AssistantMessage {
             AssistantMessage::Response { .. } => None,
         }
     }
+
+    /// Removes the text of a message containing tool uses, returning whether there was any. Used
+    /// to drop the intermediate reasoning of a turn when compacting the history.
+    pub fn clear_tool_use_content(&mut self) -> bool {
+        match self {
+            AssistantMessage::ToolUse { content, .. } if !content.is_empty() => {
+                content.clear();
+                true
+            },
+            _ => false,
+        }
+    }
 }
 
 impl From<AssistantMessage> for AssistantResponseMessage {
@@ -500,6 +560,7 @@ impl From<AssistantMessage> for AssistantResponseMessage {
             message_id,
             content,
             tool_uses,
+            cache_point: false,
         }
     }
 }
@@ -626,4 +687,19 @@ mod tests {
             assert!(!m.content.contains(USER_ENTRY_END_HEADER.trim()));
         }
     }
+
+    #[test]
+    fn test_denied_tool_uses_include_reason() {
+        let msg = UserMessage::new_denied_tool_uses(None, ["1", "2"].into_iter(), "wrong directory", None);
+        let UserMessageContent::CancelledToolUses { tool_use_results, .. } = msg.content else {
+            panic!("expected cancelled tool uses");
+        };
+        assert_eq!(tool_use_results.len(), 2);
+        for result in tool_use_results {
+            assert!(matches!(result.status, ToolResultStatus::Error));
+            assert!(
+                matches!(&result.content[0], ToolUseResultBlock::Text(text) if text.contains("Reason: wrong directory"))
+            );
+        }
+    }
 }
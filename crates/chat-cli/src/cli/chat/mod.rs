@@ -1,38 +1,67 @@
 use std::path::PathBuf;
 
-use spinners::{
-    Spinner,
-    Spinners,
-};
-
 use crate::api_client::error::ConverseStreamErrorKind;
 use crate::theme::StyledText;
 use crate::util::ui::should_send_structured_message;
+mod accessible;
+mod approval_policy;
+mod background_compaction;
 pub mod cli;
-mod consts;
+mod command_completion;
+pub mod consts;
 pub mod context;
+mod context_budget;
 mod conversation;
+pub mod cost;
+mod custom_commands;
+mod directory_summary;
+pub mod error_hints;
+mod exit_code;
+mod git_context;
+mod heartbeat;
+mod inline_commands;
+mod input_envelope;
 mod input_source;
 mod message;
+mod notifier;
+mod offline_queue;
 mod parse;
 use std::path::MAIN_SEPARATOR;
+mod batch;
 pub mod checkpoint;
 mod line_tracker;
 mod parser;
+mod perf;
+mod plan;
+mod progress;
 mod prompt;
+mod prompt_history;
 mod prompt_parser;
+mod response_schema;
+mod run_limits;
+mod serve;
 pub mod server_messenger;
+mod session_summary;
 use crate::cli::chat::checkpoint::CHECKPOINT_MESSAGE_MAX_LENGTH;
 use crate::constants::ui_text;
 #[cfg(unix)]
 mod skim_integration;
+mod stale_context;
+mod status_bar;
+mod symbol_context;
+mod template_vars;
 mod token_counter;
 pub mod tool_manager;
+mod tool_result_digest;
 pub mod tools;
+mod url_context;
 pub mod util;
+mod watch;
+pub mod workspace_index;
 use std::borrow::Cow;
 use std::collections::{
     HashMap,
+    HashSet,
     VecDeque,
 };
 use std::io::{
@@ -47,17 +76,28 @@ use std::time::{
     Instant,
 };
 
+use accessible::StatusIndicator;
+use agent::hooks::HookJsonOutput;
 use amzn_codewhisperer_client::types::SubscriptionStatus;
+use approval_policy::{
+    ApprovalPolicy,
+    PolicyAction,
+};
+use background_compaction::BackgroundCompaction;
+pub use batch::BatchArgs;
+use batch::PromptDefaults;
 use chat_cli_ui::conduit::{
     ConduitError,
     ControlEnd,
     DestinationStderr,
     DestinationStdout,
+    ViewEnd,
     get_legacy_conduits,
 };
 use chat_cli_ui::protocol::{
     Event,
     MessageRole,
+    RunFinished,
     TextMessageContent,
     TextMessageEnd,
     TextMessageStart,
@@ -68,17 +108,42 @@ use clap::{
     Args,
     CommandFactory,
     Parser,
+    Subcommand,
     ValueEnum,
 };
-use cli::compact::CompactStrategy;
-use cli::hooks::ToolContext;
+use cli::compact::{
+    CompactKind,
+    CompactStrategy,
+    CompactionRecord,
+    CompactionTrigger,
+};
+use cli::hooks::{
+    NOTIFICATION_APPROVAL_NEEDED,
+    NOTIFICATION_TURN_COMPLETE,
+    ToolContext,
+};
 use cli::model::{
+    INFERENCE_CONFIG_UNSUPPORTED,
+    context_window_tokens,
     find_model,
     get_available_models,
+    next_fallback_model,
     select_model,
 };
+use cli::quota::{
+    fetch_quota,
+    quota_warning_thresholds,
+    threshold_to_warn,
+};
+use consts::MAX_NUMBER_OF_IMAGES_PER_REQUEST;
 pub use conversation::ConversationState;
 use conversation::TokenWarningLevel;
+use cost::{
+    Cost,
+    CostLimit,
+    CostTracker,
+    RequestUsage,
+};
 use crossterm::style::{
     Attribute,
     Stylize,
@@ -90,19 +155,39 @@ use crossterm::{
     style,
     terminal,
 };
+use custom_commands::CommandTurn;
+use error_hints::ErrorHints;
+pub use exit_code::ChatExitCode;
 use eyre::{
     Report,
     Result,
     bail,
     eyre,
 };
+use heartbeat::{
+    Heartbeat,
+    HeartbeatTimer,
+    Phase,
+    ToolActivity,
+    ToolOutput,
+};
+use input_envelope::InputEnvelope;
+pub use input_envelope::InputFormat;
 use input_source::InputSource;
 use message::{
     AssistantMessage,
     AssistantToolUse,
     ToolUseResult,
     ToolUseResultBlock,
+    UserMessageContent,
 };
+use notifier::{
+    Notification,
+    NotificationKind,
+    Notifier,
+};
+use offline_queue::OfflineQueue;
+use opentelemetry::KeyValue;
 use parse::{
     ParseState,
     interpret_markdown,
@@ -112,8 +197,28 @@ use parser::{
     RequestMetadata,
     SendMessageStream,
 };
+use perf::PerfStats;
+use plan::Plan;
+use progress::{
+    Step,
+    TurnProgress,
+};
 use regex::Regex;
+use response_schema::{
+    DEFAULT_RESPONSE_SCHEMA_RETRIES,
+    ResponseSchema,
+};
 use rmcp::model::PromptMessage;
+use run_limits::{
+    RunLimit,
+    RunLimits,
+};
+pub use serve::ServeArgs;
+use session_summary::SessionSummary;
+use status_bar::{
+    Status,
+    StatusBar,
+};
 use thiserror::Error;
 use time::OffsetDateTime;
 use token_counter::TokenCounter;
@@ -128,8 +233,18 @@ use tool_manager::{
     ToolManager,
     ToolManagerBuilder,
 };
+use tools::delegate::limits::{
+    Decision,
+    TaskBudget,
+    TaskLimit,
+};
+use tools::delegate::staging::format_changes;
 use tools::delegate::{
     AgentExecution,
+    AgentStatus,
+    load_board,
+    pending_questions,
+    recover,
     save_agent_execution,
     status_all_agents,
 };
@@ -142,18 +257,31 @@ use tools::{
     ToolSpec,
 };
 use tracing::{
+    Instrument,
     debug,
     error,
     info,
     trace,
     warn,
 };
-use util::images::RichImageBlock;
+use util::image_display::{
+    self,
+    ImageProtocol,
+    MAX_REFERENCED_IMAGES,
+    referenced_image_paths,
+};
+use util::images::{
+    RichImageBlock,
+    RichImageBlocks,
+    detect_image_paths,
+    load_image_attachment,
+};
 use util::ui::draw_box;
 use util::{
     animate_output,
-    play_notification_bell,
+    truncate_safe,
 };
+pub use watch::WatchFilesArgs;
 use winnow::Partial;
 use winnow::stream::Offset;
 
@@ -162,7 +290,14 @@ use super::agent::{
     DEFAULT_AGENT_NAME,
     PermissionEvalResult,
 };
-use crate::api_client::model::ToolResultStatus;
+use crate::agent::{
+    Backend,
+    BackendArgs,
+};
+use crate::api_client::model::{
+    InferenceConfig,
+    ToolResultStatus,
+};
 use crate::api_client::{
     self,
     ApiClientError,
@@ -171,6 +306,7 @@ use crate::auth::AuthError;
 use crate::auth::builder_id::is_idc_user;
 use crate::cli::TodoListState;
 use crate::cli::agent::Agents;
+use crate::cli::agent::hook::HookAggregation;
 use crate::cli::chat::checkpoint::{
     CheckpointManager,
     truncate_message,
@@ -197,18 +333,25 @@ use crate::telemetry::core::{
     AgentConfigInitArgs,
     ChatAddedMessageParams,
     ChatConversationType,
+    HistoryCompactedArgs,
     MessageMetaTag,
     RecordUserTurnCompletionArgs,
     ToolUseEventBuilder,
 };
+use crate::telemetry::otel::{
+    self,
+    Operation,
+};
 use crate::telemetry::{
     ReasonCode,
     TelemetryResult,
     get_error_reason,
 };
+use crate::util::consts::env_var::Q_SUBAGENT;
 use crate::util::paths::PathResolver;
 use crate::util::{
     MCP_SERVER_TOOL_DELIMITER,
+    choose,
     ui,
 };
 
@@ -226,7 +369,7 @@ fn get_shadow_repo_dir(os: &Os, conversation_id: String) -> Result<PathBuf, crat
     Ok(PathResolver::new(os).global().shadow_repo_dir()?.join(conversation_id))
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Default, Args)]
+#[derive(Debug, Clone, PartialEq, Default, Args)]
 pub struct ChatArgs {
     /// Resumes the previous conversation from this directory.
     #[arg(short, long)]
@@ -234,9 +377,12 @@ pub struct ChatArgs {
     /// Context profile to use
     #[arg(long = "agent", alias = "profile")]
     pub agent: Option<String>,
-    /// Current model to use
+    /// Current model to use. With --backend bedrock or openai, the id of the model on that
+    /// backend, e.g. us.anthropic.claude-sonnet-4-20250514-v1:0 or qwen3:8b
     #[arg(long = "model")]
     pub model: Option<String>,
+    #[command(flatten)]
+    pub backend: BackendArgs,
     /// Allows the model to use any tool to run commands without asking for confirmation.
     #[arg(short = 'a', long)]
     pub trust_all_tools: bool,
@@ -244,20 +390,120 @@ pub struct ChatArgs {
     /// '--trust-tools=fs_read,fs_write', trust no tools: '--trust-tools='
     #[arg(long, value_delimiter = ',', value_name = "TOOL_NAMES")]
     pub trust_tools: Option<Vec<String>>,
+    /// Denies every tool use that can modify files or run commands with side effects for the
+    /// whole session. Read-only tools keep their usual permissions.
+    #[arg(long)]
+    pub read_only: bool,
+    /// Starts in plan mode: tool uses that can modify files or run commands with side effects
+    /// are recorded into a plan for review instead of being run. Apply it with /plan apply
+    #[arg(long)]
+    pub plan: bool,
     /// Whether the command should run without expecting user input
     #[arg(long, alias = "non-interactive")]
     pub no_interactive: bool,
     /// The first question to ask
     pub input: Option<String>,
+    /// Format of the input given through stdin with --no-interactive. With json, stdin is a JSON
+    /// object with the prompt and the files and images to attach to it
+    #[arg(
+        long,
+        value_enum,
+        default_value_t,
+        requires = "no_interactive",
+        conflicts_with = "input"
+    )]
+    pub input_format: InputFormat,
     /// Control line wrapping behavior (default: auto-detect)
     #[arg(short = 'w', long, value_enum)]
     pub wrap: Option<WrapMode>,
+    /// Screen reader friendly output, without spinners, animations, box-drawing characters or
+    /// emoji
+    #[arg(long)]
+    pub accessible: bool,
+    /// Attach an image to the first message. Can be given several times
+    #[arg(long, value_name = "IMAGE_PATH")]
+    pub attach: Vec<String>,
+    /// Sampling parameters for the model's responses. Take precedence over the agent's
+    /// modelParameters
+    #[command(flatten)]
+    pub model_params: InferenceConfig,
+    /// Stops the turn once the estimated cost of the session exceeds this amount of US dollars
+    #[arg(long, value_name = "USD")]
+    pub max_cost: Option<CostLimit>,
+    /// Stops the session with exit code 3 once the model responded this many times and requests
+    /// more tool uses
+    #[arg(long, value_name = "N", requires = "no_interactive")]
+    pub max_turns: Option<usize>,
+    /// Stops the session with exit code 3 before running more than this many tool uses
+    #[arg(long, value_name = "N", requires = "no_interactive")]
+    pub max_tool_calls: Option<usize>,
+    /// Stops the session with exit code 3 once it ran for this many seconds, cancelling the
+    /// response, tool uses and MCP calls in progress
+    #[arg(long, value_name = "SECS", requires = "no_interactive")]
+    pub timeout: Option<u64>,
+    /// JSON Schema file that final answers must match. Answers that don't are requested again
+    /// with the mismatches, up to chat.responseSchemaRetries times
+    #[arg(long, value_name = "SCHEMA_PATH")]
+    pub response_schema: Option<PathBuf>,
+    /// YAML or JSON file of allow and deny rules deciding on the tool uses that would otherwise
+    /// need to be approved, e.g. in headless runs
+    #[arg(long, value_name = "POLICY_PATH")]
+    pub approval_policy: Option<PathBuf>,
+    #[command(subcommand)]
+    pub subcommand: Option<ChatSubcommand>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Subcommand)]
+pub enum ChatSubcommand {
+    /// Runs the prompts listed in a YAML or JSON file one after the other, writing their answers
+    /// to a directory. Options of `q chat` given before `batch` apply to every prompt
+    Batch(BatchArgs),
+    /// Keeps a session running behind a unix socket, taking prompts and tool approvals as
+    /// JSON-RPC requests and streaming its events back
+    Serve(ServeArgs),
+    /// Runs a prompt whenever files matching globs change. Options of `q chat` given before
+    /// `watch-files` apply to every run
+    WatchFiles(WatchFilesArgs),
 }
 
 impl ChatArgs {
+    /// Options of `q chat` applying to the prompts run by `batch` and `watch-files`
+    fn prompt_defaults(&self) -> PromptDefaults {
+        PromptDefaults {
+            agent: self.agent.clone(),
+            model: self.model.clone(),
+            backend: self.backend.clone(),
+            trust_all_tools: self.trust_all_tools,
+            trust_tools: self.trust_tools.clone(),
+            read_only: self.read_only,
+            approval_policy: self.approval_policy.clone(),
+        }
+    }
+
     pub async fn execute(mut self, os: &mut Os) -> Result<ExitCode> {
+        let start = Instant::now();
+        // Without a socket, `q chat serve` sends "serve" as a prompt, as it did before serving existed
+        if matches!(
+            &self.subcommand,
+            Some(ChatSubcommand::Serve(ServeArgs { socket: None }))
+        ) {
+            self.subcommand = None;
+            self.input = Some("serve".to_string());
+        }
+        match &self.subcommand {
+            Some(ChatSubcommand::Batch(args)) => return Ok(args.execute(os, &self.prompt_defaults()).await?.into()),
+            Some(ChatSubcommand::WatchFiles(args)) => {
+                return Ok(args.execute(os, &self.prompt_defaults()).await?.into());
+            },
+            _ => (),
+        }
+
         let mut input = self.input;
 
+        if self.accessible {
+            accessible::enable();
+        }
+
         if self.no_interactive && input.is_none() {
             if !std::io::stdin().is_terminal() {
                 let mut buffer = String::new();
@@ -278,6 +524,14 @@ impl ChatArgs {
             }
         }
 
+        if self.input_format == InputFormat::Json {
+            if let Some(envelope) = input.take() {
+                let envelope = InputEnvelope::parse(&envelope)?.into_input(os).await?;
+                input = Some(envelope.prompt);
+                self.attach.extend(envelope.images);
+            }
+        }
+
         let mut stderr = std::io::stderr();
 
         let args: Vec<String> = std::env::args().collect();
@@ -315,6 +569,12 @@ impl ChatArgs {
             let (mut agents, md) =
                 Agents::load(os, self.agent.as_deref(), skip_migration, &mut stderr, mcp_enabled).await;
             agents.trust_all_tools = self.trust_all_tools;
+            agents.read_only = self.read_only;
+            // Tasks spawned with spawn_subagent run as a sub-agent of the agent, with its narrower
+            // permissions
+            if let Ok(subagent) = os.env.get(Q_SUBAGENT) {
+                agents.use_subagent(&subagent)?;
+            }
 
             os.telemetry
                 .send_agent_config_init(&os.database, conversation_id.clone(), AgentConfigInitArgs {
@@ -376,53 +636,88 @@ impl ChatArgs {
             agents
         };
 
-        // If modelId is specified, verify it exists before starting the chat
-        // Otherwise, CLI will use a default model when starting chat
-        let (models, default_model_opt) = get_available_models(os).await?;
-        // Fallback logic: try user's saved default, then system default
-        let fallback_model_id = || {
-            if let Some(saved) = os.database.settings.get_string(Setting::ChatDefaultModel) {
-                find_model(&models, &saved)
-                    .map(|m| m.model_id.clone())
-                    .or(Some(default_model_opt.model_id.clone()))
+        let model_id: Option<String> = if self.backend.backend != Backend::Rts {
+            // Other backends have no list of models to pick from, so the id is sent as given
+            let model = self.backend.create_chat_model(os, self.model.clone()).await?;
+            let model_id = model.model_id().to_string();
+            os.client.set_chat_model(model);
+            Some(model_id)
+        } else {
+            // If modelId is specified, verify it exists before starting the chat
+            // Otherwise, CLI will use a default model when starting chat
+            let (models, default_model_opt) = get_available_models(os).await?;
+            // Fallback logic: try user's saved default, then system default
+            let fallback_model_id = || {
+                if let Some(saved) = os.database.settings.get_string(Setting::ChatDefaultModel) {
+                    find_model(&models, &saved)
+                        .map(|m| m.model_id.clone())
+                        .or(Some(default_model_opt.model_id.clone()))
+                } else {
+                    Some(default_model_opt.model_id.clone())
+                }
+            };
+
+            if let Some(requested) = self.model.as_ref() {
+                // CLI argument takes highest priority
+                if let Some(m) = find_model(&models, requested) {
+                    Some(m.model_id.clone())
+                } else {
+                    let available = models
+                        .iter()
+                        .map(|m| m.model_name.as_deref().unwrap_or(&m.model_id))
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    bail!("Model '{}' does not exist. Available models: {}", requested, available);
+                }
+            } else if let Some(agent_model) = agents.get_active().and_then(|a| a.model.as_ref()) {
+                // Agent model takes second priority
+                if let Some(m) = find_model(&models, agent_model) {
+                    Some(m.model_id.clone())
+                } else {
+                    let _ = execute!(
+                        stderr,
+                        StyledText::warning_fg(),
+                        style::Print("WARNING: "),
+                        StyledText::reset(),
+                        style::Print("Agent specifies model '"),
+                        StyledText::brand_fg(),
+                        style::Print(agent_model),
+                        StyledText::reset(),
+                        style::Print("' which is not available. Falling back to configured defaults.\n"),
+                    );
+                    fallback_model_id()
+                }
             } else {
-                Some(default_model_opt.model_id.clone())
+                fallback_model_id()
             }
         };
 
-        let model_id: Option<String> = if let Some(requested) = self.model.as_ref() {
-            // CLI argument takes highest priority
-            if let Some(m) = find_model(&models, requested) {
-                Some(m.model_id.clone())
-            } else {
-                let available = models
-                    .iter()
-                    .map(|m| m.model_name.as_deref().unwrap_or(&m.model_id))
-                    .collect::<Vec<_>>()
-                    .join(", ");
-                bail!("Model '{}' does not exist. Available models: {}", requested, available);
-            }
-        } else if let Some(agent_model) = agents.get_active().and_then(|a| a.model.as_ref()) {
-            // Agent model takes second priority
-            if let Some(m) = find_model(&models, agent_model) {
-                Some(m.model_id.clone())
-            } else {
-                let _ = execute!(
-                    stderr,
-                    StyledText::warning_fg(),
-                    style::Print("WARNING: "),
-                    StyledText::reset(),
-                    style::Print("Agent specifies model '"),
-                    StyledText::brand_fg(),
-                    style::Print(agent_model),
-                    StyledText::reset(),
-                    style::Print("' which is not available. Falling back to configured defaults.\n"),
+        let mut model_params = agents
+            .get_active()
+            .map(|a| a.model_parameters)
+            .unwrap_or_default()
+            .merge(self.model_params);
+        if let Err(err) = model_params.validate() {
+            bail!("Invalid model parameters: {err}");
+        }
+        if self.backend.backend == Backend::Rts && !model_params.is_empty() {
+            if !self.model_params.is_empty() {
+                bail!(
+                    "{INFERENCE_CONFIG_UNSUPPORTED} Remove the --temperature, --max-output-tokens and --top-p arguments, or send the requests to another backend with --backend bedrock or --backend openai."
                 );
-                fallback_model_id()
             }
-        } else {
-            fallback_model_id()
-        };
+            // The agent may be meant for the other backends, so it still runs with the model defaults
+            let _ = execute!(
+                stderr,
+                StyledText::warning_fg(),
+                style::Print("WARNING: "),
+                StyledText::reset(),
+                style::Print(format!(
+                    "{INFERENCE_CONFIG_UNSUPPORTED} The modelParameters of the agent are ignored.\n"
+                )),
+            );
+            model_params = InferenceConfig::default();
+        }
 
         let (prompt_request_sender, prompt_request_receiver) = tokio::sync::broadcast::channel::<PromptQuery>(5);
         let (prompt_response_sender, prompt_response_receiver) =
@@ -438,12 +733,26 @@ impl ChatArgs {
             .await?;
         let tool_config = tool_manager.load_tools(os, &mut stderr).await?;
 
-        ChatSession::new(
+        let (server, input_source) = match &self.subcommand {
+            Some(ChatSubcommand::Serve(args @ ServeArgs { socket: Some(socket) })) => {
+                let (server, input_source) = args.listen()?;
+                execute!(
+                    stderr,
+                    style::Print(format!("Serving the session at {}\n", socket.display()))
+                )?;
+                (Some(server), input_source)
+            },
+            _ => (
+                None,
+                InputSource::new(os, prompt_request_sender, prompt_response_receiver)?,
+            ),
+        };
+        let mut session = ChatSession::new(
             os,
             &conversation_id,
             agents,
             input,
-            InputSource::new(os, prompt_request_sender, prompt_response_receiver)?,
+            input_source,
             self.resume,
             || terminal::window_size().map(|s| s.columns.into()).ok(),
             tool_manager,
@@ -453,10 +762,76 @@ impl ChatArgs {
             mcp_enabled,
             self.wrap,
         )
-        .await?
-        .spawn(os)
-        .await
-        .map(|_| ExitCode::SUCCESS)
+        .await?;
+        if let Some(server) = &server {
+            session.set_view(server.view());
+        }
+        session.max_cost = self.max_cost;
+        session.run_limits = RunLimits::new(self.max_turns, self.max_tool_calls).with_timeout(self.timeout, start);
+        session.task_budget = TaskBudget::from_env(os, start);
+        session.plan.enabled = self.plan;
+        session.conversation.inference_config = model_params;
+        if let Some(path) = &self.response_schema {
+            session.response_schema = Some(ResponseSchema::load(os, path).await?);
+        }
+        if let Some(path) = &self.approval_policy {
+            session.approval_policy = Some(ApprovalPolicy::load(os, path).await?);
+        }
+        for path in &self.attach {
+            session
+                .attach_image(path)
+                .map_err(|e| eyre!("Failed to attach {path}: {e}"))?;
+        }
+
+        // Tasks delegated by an earlier session carry on, the ones left queued starting now
+        if !self.no_interactive && ExperimentManager::is_enabled(os, ExperimentName::Delegate) {
+            match recover(os).await {
+                Ok(interrupted) => execute!(stderr, style::Print(format_interrupted_notification(&interrupted)))?,
+                Err(err) => warn!(?err, "Failed to recover the delegated tasks"),
+            }
+            // New conversations pick up where the tasks delegated by earlier sessions left off
+            if session.conversation.history().is_empty() && !tools::delegate::snapshot::is_enabled(os) {
+                match tools::delegate::handoff::take_pending(os).await {
+                    Ok(handoffs) if !handoffs.is_empty() => {
+                        execute!(
+                            stderr,
+                            StyledText::secondary_fg(),
+                            style::Print(format!(
+                                "Added the handoffs of {} delegated tasks finished since the last session to the context.\n",
+                                handoffs.len()
+                            )),
+                            StyledText::reset(),
+                        )?;
+                        session.conversation.add_handoffs(handoffs);
+                    },
+                    Ok(_) => {},
+                    Err(err) => warn!(?err, "Failed to read the handoffs of the delegated tasks"),
+                }
+            }
+        }
+
+        // Clients of the structured output follow the delegated tasks through their events
+        if session.stdout.should_send_structured_event && ExperimentManager::is_enabled(os, ExperimentName::Delegate) {
+            tokio::spawn(tools::delegate::events::watch(os.clone(), session.stdout.clone()));
+        }
+
+        let result = session.spawn(os).await;
+        tools::delegate::snapshot::record_exit(os, match &result {
+            Ok(()) => session.exit_code as i32,
+            Err(err) => err
+                .downcast_ref::<ChatError>()
+                .map_or(ChatExitCode::Failure, ChatExitCode::from) as i32,
+        });
+        match result {
+            Ok(()) => Ok(session.exit_code.into()),
+            Err(err) => match err.downcast_ref::<ChatError>().map(ChatExitCode::from) {
+                Some(exit_code) if exit_code != ChatExitCode::Failure => {
+                    eprintln!("{} {err}", StyledText::error("error:"));
+                    Ok(exit_code.into())
+                },
+                _ => Err(err),
+            },
+        }
     }
 }
 
@@ -465,11 +840,104 @@ const CHANGELOG_MAX_SHOW_COUNT: i64 = 2;
 
 const GREETING_BREAK_POINT: usize = 80;
 
+/// After the context window overflows, collapsing large tool outputs is enough if the conversation
+/// then uses less than this percentage of the context window. Otherwise the history is summarized.
+const COLLAPSED_TOOL_RESULTS_MAX_USAGE_PERCENT: usize = 80;
+
 const RESPONSE_TIMEOUT_CONTENT: &str = "Response timed out - message took too long to generate";
+
+/// How long to wait for the first token of a response before showing the time waited.
+const FIRST_TOKEN_WAIT_NOTICE: Duration = Duration::from_secs(3);
+
+/// How long a tool can run without writing anything before the step in progress is shown.
+const TOOL_PROGRESS_NOTICE: Duration = Duration::from_secs(3);
+
+/// Minimum time between two checks of the monthly allowance for the quota warnings.
+const QUOTA_CHECK_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Longest prompt, in characters, sent to the model set with `chat.raceModel` as well.
+const RACE_MAX_PROMPT_CHARS: usize = 500;
+
+/// Minimum duration of a turn, in seconds, for the `notification` hooks to be told it completed,
+/// unless set with `chat.notificationTurnSeconds`.
+const DEFAULT_NOTIFICATION_TURN_SECONDS: usize = 30;
+
+/// Longest response, in bytes, given as summary to the `notification` hooks.
+const NOTIFICATION_SUMMARY_MAX_LEN: usize = 500;
+
 fn trust_all_text() -> String {
     ui_text::trust_all_warning()
 }
 
+/// Line shown above the prompt when delegated tasks finished, which are detailed in `/agents`
+fn format_task_notification(executions: &[AgentExecution]) -> String {
+    let tasks = executions
+        .iter()
+        .map(|execution| match execution.status {
+            tools::delegate::AgentStatus::Completed => format!("{}{}", accessible::symbol("✓ ", ""), execution.id),
+            _ => format!("{}{}", accessible::symbol("✗ ", "failed: "), execution.id),
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    match executions.len() {
+        1 => format!("1 background task finished: {tasks} · /agents for details"),
+        count => format!("{count} background tasks finished: {tasks} · /agents for details"),
+    }
+}
+
+/// Tasks found interrupted when the session started, e.g. after the computer restarted
+fn format_interrupted_notification(executions: &[AgentExecution]) -> String {
+    executions
+        .iter()
+        .map(|execution| {
+            format!(
+                "{} was interrupted before it finished: {}\n{}\n",
+                StyledText::warning(&execution.id).bold(),
+                execution.task,
+                StyledText::secondary(&format!("  q agent resume {}", execution.id))
+            )
+        })
+        .collect()
+}
+
+/// Questions the delegated tasks asked the user, answered with `/agents tell`
+fn format_question_notification(questions: &[(String, tools::delegate::mailbox::Message)]) -> String {
+    questions
+        .iter()
+        .map(|(id, question)| {
+            format!(
+                "{} {}\n{}\n",
+                StyledText::warning(&format!("{id} asks:")).bold(),
+                question.text,
+                StyledText::secondary(&format!("  /agents tell {id} <answer>"))
+            )
+        })
+        .collect()
+}
+
+/// Changes of the finished delegated tasks waiting to be merged, listed like `/checkpoint expand`
+async fn format_review_notification(executions: &[AgentExecution]) -> String {
+    let mut notification = String::new();
+    for execution in executions.iter().filter(|execution| execution.has_pending_changes()) {
+        let Some(Ok(changes)) = (match &execution.staged {
+            Some(staged) => Some(staged.changes().await),
+            None => None,
+        }) else {
+            continue;
+        };
+        if changes.is_empty() {
+            continue;
+        }
+        let id = &execution.id;
+        notification.push_str(&format!("Changes of {id} to review:\n{}", format_changes(&changes)));
+        notification.push_str(&StyledText::secondary(&format!(
+            "  /agents diff {id} · /agents merge {id} · /agents discard {id}\n"
+        )));
+    }
+    notification
+}
+
+/// Summaries of the finished delegated tasks, given to the model along with the next prompt
 fn format_rich_notification(executions: &[AgentExecution]) -> String {
     let count = executions.len();
     let header = if count == 1 {
@@ -483,9 +951,14 @@ fn format_rich_notification(executions: &[AgentExecution]) -> String {
 
     for (i, execution) in executions.iter().enumerate() {
         let status_icon = match execution.status {
-            tools::delegate::AgentStatus::Completed => "✓ SUCCESS",
-            tools::delegate::AgentStatus::Failed => "✗ FAILED",
-            tools::delegate::AgentStatus::Running => "⏳ RUNNING", // shouldn't happen but just in case
+            tools::delegate::AgentStatus::Completed => accessible::symbol("✓ SUCCESS", "SUCCESS"),
+            tools::delegate::AgentStatus::Failed => accessible::symbol("✗ FAILED", "FAILED"),
+            tools::delegate::AgentStatus::Cancelled => accessible::symbol("✗ CANCELLED", "CANCELLED"),
+            // shouldn't happen but just in case
+            tools::delegate::AgentStatus::Queued => accessible::symbol("⏳ QUEUED", "QUEUED"),
+            tools::delegate::AgentStatus::Running => accessible::symbol("⏳ RUNNING", "RUNNING"),
+            tools::delegate::AgentStatus::Paused => accessible::symbol("⏸ PAUSED", "PAUSED"),
+            tools::delegate::AgentStatus::Interrupted => accessible::symbol("⏸ INTERRUPTED", "INTERRUPTED"),
         };
 
         let time_ago = if let Some(completed_at) = execution.completed_at {
@@ -513,15 +986,22 @@ fn format_rich_notification(executions: &[AgentExecution]) -> String {
         let summary = execution.summary.as_deref().unwrap_or("No summary available");
 
         notification.push_str(&format!(
-            "[{}] {} · {} · {} · {}\n\nTask: {}\n\n{}\n\n",
+            "[{}] {} ({}) · {} · {} · {}\n\nTask: {}\n\n{}\n\n",
             i + 1,
             execution.agent,
+            execution.id,
             shortened_cwd,
             status_icon,
             time_ago,
             execution.task,
             summary
         ));
+        if execution.has_pending_changes() {
+            notification.push_str(&format!(
+                "The changes of this task are not in the working tree yet. The user reviews them, then merges them with /agents merge {}.\n\n",
+                execution.id
+            ));
+        }
     }
 
     // Add footer with instructions
@@ -530,11 +1010,36 @@ fn format_rich_notification(executions: &[AgentExecution]) -> String {
     notification
 }
 
-const TOOL_BULLET: &str = " ● ";
-const CONTINUATION_LINE: &str = " ⋮ ";
-const PURPOSE_ARROW: &str = " ↳ ";
-const SUCCESS_TICK: &str = " ✓ ";
-const ERROR_EXCLAMATION: &str = " ❗ ";
+/// The notice printed when large tool results were collapsed instead of compacting the conversation
+fn format_collapsed_notice(collapsed: usize) -> String {
+    format!(
+        "{}\n",
+        StyledText::success(&format!(
+            "{}Collapsed {collapsed} large tool outputs to free up context space",
+            accessible::symbol("✔ ", "")
+        ))
+    )
+}
+
+fn tool_bullet() -> &'static str {
+    accessible::symbol(" ● ", " - ")
+}
+
+fn continuation_line() -> &'static str {
+    accessible::symbol(" ⋮ ", "   ")
+}
+
+fn purpose_arrow() -> &'static str {
+    accessible::symbol(" ↳ ", "   ")
+}
+
+fn success_tick() -> &'static str {
+    accessible::symbol(" ✓ ", " Done: ")
+}
+
+fn error_exclamation() -> &'static str {
+    accessible::symbol(" ❗ ", " Error: ")
+}
 
 /// Enum used to denote the origin of a tool use event
 enum ToolUseStatus {
@@ -568,7 +1073,7 @@ pub enum ChatError {
     #[error(transparent)]
     GetPromptError(#[from] GetPromptError),
     #[error(
-        "Tool approval required but --no-interactive was specified. Use --trust-all-tools to automatically approve tools."
+        "Tool approval required but --no-interactive was specified. Use --trust-all-tools or --approval-policy to automatically approve tools."
     )]
     NonInteractiveToolApproval,
     #[error("The conversation history is too large to compact")]
@@ -577,6 +1082,10 @@ pub enum ChatError {
     AgentSwapError(eyre::Report),
     #[error(transparent)]
     Conduit(#[from] ConduitError),
+    #[error("The response does not match the schema at {}:\n{}", path.display(), errors.join("\n"))]
+    ResponseSchemaMismatch { path: PathBuf, errors: Vec<String> },
+    #[error("Stopped the session after reaching {0}")]
+    LimitReached(RunLimit),
 }
 
 impl ChatError {
@@ -595,6 +1104,8 @@ impl ChatError {
             ChatError::CompactHistoryFailure => None,
             ChatError::AgentSwapError(_) => None,
             ChatError::Conduit(_) => None,
+            ChatError::ResponseSchemaMismatch { .. } => None,
+            ChatError::LimitReached(_) => None,
         }
     }
 }
@@ -615,6 +1126,8 @@ impl ReasonCode for ChatError {
             ChatError::CompactHistoryFailure => "CompactHistoryFailure".to_string(),
             ChatError::AgentSwapError(_) => "AgentSwapError".to_string(),
             ChatError::Conduit(_) => "ConduitError".to_string(),
+            ChatError::ResponseSchemaMismatch { .. } => "ResponseSchemaMismatch".to_string(),
+            ChatError::LimitReached(_) => "LimitReached".to_string(),
         }
     }
 }
@@ -648,7 +1161,7 @@ pub struct ChatSession {
     input_source: InputSource,
     /// Width of the terminal, required for [ParseState].
     terminal_width_provider: fn() -> Option<usize>,
-    spinner: Option<Spinner>,
+    spinner: Option<StatusIndicator>,
     /// [ConversationState].
     conversation: ConversationState,
     /// Tool uses requested by the model that are actively being handled.
@@ -677,6 +1190,55 @@ pub struct ChatSession {
     prompt_ack_rx: std::sync::mpsc::Receiver<()>,
     /// Additional context to be added to the next user message (e.g., delegate task summaries)
     pending_additional_context: Option<String>,
+    /// Questions of the delegated tasks already shown above the prompt
+    shown_task_questions: HashSet<(String, chrono::DateTime<chrono::Utc>)>,
+    /// Images to be attached to the next user message, added with /attach
+    pending_images: RichImageBlocks,
+    /// Summarization of the older history running in the background
+    background_compaction: Option<BackgroundCompaction>,
+    /// Tokens used and estimated cost of the requests sent in this session
+    cost: CostTracker,
+    /// Set with `--max-cost`
+    max_cost: Option<CostLimit>,
+    /// What happened in the session, printed when it ends with `chat.sessionSummary`
+    session_summary: SessionSummary,
+    /// Latencies of the requests and tool uses, shown by `/perf`.
+    perf: PerfStats,
+    /// Errors hit in the session, to hint at the remediation of those that repeat.
+    error_hints: ErrorHints,
+    /// Limits set with `--max-turns` and `--max-tool-calls`
+    run_limits: RunLimits,
+    /// Limits of the delegated task this session runs, if any
+    task_budget: Option<TaskBudget>,
+    /// Exit code of the session, set from the error that ended a non-interactive session
+    exit_code: ChatExitCode,
+    /// Prompts composed while the backend couldn't be reached
+    offline_queue: OfflineQueue,
+    /// Set with `--response-schema` or `/schema set`
+    response_schema: Option<ResponseSchema>,
+    /// Number of times the current answer was requested again for not matching
+    /// [Self::response_schema]
+    response_schema_retries: usize,
+    /// Set with `--plan` or `/plan on`
+    plan: Plan,
+    /// Set with `--approval-policy`
+    approval_policy: Option<ApprovalPolicy>,
+    /// Highest percentage of the monthly allowance already warned about
+    quota_warned_threshold: Option<f64>,
+    /// Check of the monthly allowance running in the background, returning the percentage used
+    quota_check: Option<tokio::task::JoinHandle<Option<f64>>>,
+    /// When [Self::quota_check] last started
+    quota_checked_at: Option<Instant>,
+    /// Set with `chat.statusBar` in interactive sessions
+    status_bar: Option<StatusBar>,
+    /// Percentage of the context window used, shown in the status bar. Estimated when prompting
+    /// the user and taken from the tokens of each response, so that refreshing the status bar
+    /// while a turn runs doesn't build the request again.
+    context_usage: Option<f32>,
+    /// Changes of the custom command running in the current turn
+    custom_command_turn: Option<CommandTurn>,
+    /// Steps of the current turn, shown by the spinner
+    progress: TurnProgress,
 }
 
 impl ChatSession {
@@ -699,6 +1261,12 @@ impl ChatSession {
         // Only load prior conversation if we need to resume
         let mut existing_conversation = false;
 
+        match os.database.get_token_calibration() {
+            Ok(Some(calibration)) => TokenCounter::set_calibration(calibration),
+            Ok(None) => (),
+            Err(err) => warn!(?err, "Failed to load the token calibration"),
+        }
+
         let should_send_structured_msg = should_send_structured_message(os);
         let (view_end, _byte_receiver, mut control_end_stderr, control_end_stdout) =
             get_legacy_conduits(should_send_structured_msg);
@@ -714,10 +1282,15 @@ impl ChatSession {
 
         let conversation = match resume_conversation {
             true => {
-                let previous_conversation = std::env::current_dir()
-                    .ok()
-                    .and_then(|cwd| os.database.get_conversation_by_path(cwd).ok())
-                    .flatten();
+                // Delegated tasks share their directory with other sessions, so they resume from
+                // their own checkpoint
+                let previous_conversation = match tools::delegate::snapshot::is_enabled(os) {
+                    true => tools::delegate::snapshot::load(os),
+                    false => std::env::current_dir()
+                        .ok()
+                        .and_then(|cwd| os.database.get_conversation_by_path(cwd).ok())
+                        .flatten(),
+                };
 
                 // Only restore conversations where there were actual messages
                 // Prevents edge case where user clears conversation then exits without chatting.
@@ -742,6 +1315,7 @@ impl ChatSession {
                         }
                         cs.agents = agents;
                         cs.mcp_enabled = mcp_enabled;
+                        cs.reload_context_manager()?;
                         cs.update_state(true).await;
                         cs.enforce_tool_use_history_invariants();
                         cs
@@ -791,6 +1365,13 @@ impl ChatSession {
             }
         });
 
+        let status_bar = (interactive
+            && !control_end_stdout.should_send_structured_event
+            && !accessible::is_enabled()
+            && std::io::stdout().is_terminal()
+            && os.database.settings.get_bool(Setting::ChatStatusBar).unwrap_or(false))
+        .then(StatusBar::default);
+
         Ok(Self {
             stdout: control_end_stdout,
             stderr: control_end_stderr,
@@ -814,12 +1395,60 @@ impl ChatSession {
             wrap,
             prompt_ack_rx,
             pending_additional_context: None,
+            shown_task_questions: HashSet::new(),
+            pending_images: Vec::new(),
+            background_compaction: None,
+            cost: CostTracker::default(),
+            max_cost: None,
+            session_summary: SessionSummary::new(Instant::now()),
+            perf: PerfStats::default(),
+            error_hints: ErrorHints::default(),
+            run_limits: RunLimits::default(),
+            task_budget: None,
+            exit_code: ChatExitCode::Success,
+            offline_queue: OfflineQueue::default(),
+            response_schema: None,
+            response_schema_retries: 0,
+            plan: Plan::default(),
+            approval_policy: None,
+            quota_warned_threshold: None,
+            quota_check: None,
+            quota_checked_at: None,
+            status_bar,
+            context_usage: None,
+            custom_command_turn: None,
+            progress: TurnProgress::default(),
         })
     }
 
+    /// Attaches the image at `path` to the next user message.
+    pub fn attach_image(&mut self, path: &str) -> Result<()> {
+        let path = shellexpand::tilde(path).to_string();
+        if self
+            .pending_images
+            .iter()
+            .any(|(_, metadata)| metadata.filepath == path)
+        {
+            return Ok(());
+        }
+        if let Some(model) = self.conversation.model_info.as_ref().filter(|m| !m.capabilities.images) {
+            bail!(
+                "{} doesn't accept images, use /model to switch to a model that does",
+                model.display_name()
+            );
+        }
+        if self.pending_images.len() >= MAX_NUMBER_OF_IMAGES_PER_REQUEST {
+            bail!("at most {MAX_NUMBER_OF_IMAGES_PER_REQUEST} images can be attached to a message");
+        }
+
+        self.pending_images.push(load_image_attachment(&path)?);
+        Ok(())
+    }
+
     pub async fn next(&mut self, os: &mut Os) -> Result<(), ChatError> {
         // Update conversation state with new tool information
         self.conversation.update_state(false).await;
+        crate::util::crash::set_conversation(self.conversation.conversation_id(), self.conversation.model_id());
 
         let mut ctrl_c_stream = self.ctrlc_rx.resubscribe();
         let result = match self.inner.take().expect("state must always be Some") {
@@ -901,7 +1530,16 @@ impl ChatSession {
 
         // We encountered an error. Handle it.
         error!(?err, "An error occurred processing the current state");
+        let exit_code = ChatExitCode::from(&err);
         let (reason, reason_desc) = get_error_reason(&err);
+        let mut hint = None;
+        if !matches!(err, ChatError::Interrupted { .. }) {
+            self.run_error_hooks(os, &err, &reason, &reason_desc).await;
+            if let Err(db_err) = error_hints::record_error(&os.database, &reason, err.status_code()) {
+                warn!(?db_err, "Failed to record the error cluster");
+            }
+            hint = self.error_hints.record(&reason, err.status_code());
+        }
         self.send_error_telemetry(os, reason, Some(reason_desc), err.status_code())
             .await;
 
@@ -915,6 +1553,8 @@ impl ChatSession {
         }
 
         let (context, report, display_err_message) = match err {
+            // Only raised without user input, fails the invocation
+            ChatError::ResponseSchemaMismatch { .. } | ChatError::LimitReached(_) => return Err(err),
             ChatError::Auth(AuthError::NoToken) => {
                 execute!(
                     self.stderr,
@@ -972,16 +1612,26 @@ impl ChatSession {
                     StyledText::reset(),
                     style::Print(format!(
                         "• Run {} to compact your conversation. See {} for compaction options\n",
-                        "/compact".green(),
-                        "/compact --help".green()
+                        StyledText::command("/compact"),
+                        StyledText::command("/compact --help")
+                    )),
+                    style::Print(format!(
+                        "• Run {} to analyze your context usage\n",
+                        StyledText::command("/usage")
+                    )),
+                    style::Print(format!(
+                        "• Run {} to reset your conversation state\n",
+                        StyledText::command("/clear")
                     )),
-                    style::Print(format!("• Run {} to analyze your context usage\n", "/usage".green())),
-                    style::Print(format!("• Run {} to reset your conversation state\n", "/clear".green())),
                     StyledText::reset_attributes(),
                     style::Print("\n\n"),
                 )?;
                 ("Unable to compact the conversation history", eyre!(err), true)
             },
+            ChatError::SendMessage(ref send_err) if self.interactive && send_err.source.is_connectivity_error() => {
+                self.queue_unsent_prompt()?;
+                ("Unable to reach the service", Report::from(err), false)
+            },
             ChatError::SendMessage(err) => match &err.source.kind {
                 // Errors from attempting to send too large of a conversation history. In
                 // this case, attempt to automatically compact the history for the user.
@@ -997,7 +1647,10 @@ impl ChatSession {
                             StyledText::error_fg(),
                             style::Print("The conversation history has overflowed.\n"),
                             StyledText::reset(),
-                            style::Print(format!("• Run {} to compact your conversation\n", "/compact".green())),
+                            style::Print(format!(
+                                "• Run {} to compact your conversation\n",
+                                StyledText::command("/compact")
+                            )),
                             StyledText::reset_attributes(),
                             style::Print("\n\n"),
                         )?;
@@ -1007,6 +1660,8 @@ impl ChatSession {
                             prompt: None,
                             show_summary: false,
                             strategy: CompactStrategy {
+                                kind: CompactKind::Sliding,
+                                collapse_tool_results: true,
                                 truncate_large_messages: self.conversation.history().len() <= 2,
                                 max_message_length: if self.conversation.history().len() <= 2 {
                                     25_000
@@ -1029,6 +1684,11 @@ impl ChatSession {
                     }
                 },
                 ConverseStreamErrorKind::Throttling => {
+                    if let Some(state) = self.retry_with_fallback_model(os, "throttled").await? {
+                        self.inner = Some(state);
+                        return Ok(());
+                    }
+
                     let err = "Request quota exceeded. Please wait a moment and try again.".to_string();
                     self.conversation.append_transcript(err.clone());
                     execute!(
@@ -1044,6 +1704,11 @@ impl ChatSession {
                     (error_messages::TROUBLE_RESPONDING, eyre!(err), false)
                 },
                 ConverseStreamErrorKind::ModelOverloadedError => {
+                    if let Some(state) = self.retry_with_fallback_model(os, "temporarily unavailable").await? {
+                        self.inner = Some(state);
+                        return Ok(());
+                    }
+
                     if self.interactive {
                         execute!(
                             self.stderr,
@@ -1137,6 +1802,9 @@ impl ChatSession {
                         )?;
                     }
 
+                    if !self.interactive {
+                        self.exit_code = exit_code;
+                    }
                     self.inner = Some(ChatState::PromptUser {
                         skip_printing_tools: false,
                     });
@@ -1165,6 +1833,14 @@ impl ChatSession {
 
             execute!(self.stderr, StyledText::reset_attributes(), StyledText::reset(),)?;
         }
+        if let Some(issue) = hint {
+            execute!(
+                self.stderr,
+                StyledText::warning_fg(),
+                style::Print(format!("Hint: {}\n\n", issue.hint())),
+                StyledText::reset(),
+            )?;
+        }
 
         self.conversation.enforce_conversation_invariants();
         self.conversation.reset_next_user_message();
@@ -1172,6 +1848,10 @@ impl ChatSession {
         self.tool_turn_start_time = None;
         self.reset_user_turn();
 
+        // Without user input, the session ends on the next prompt
+        if !self.interactive {
+            self.exit_code = exit_code;
+        }
         self.inner = Some(ChatState::PromptUser {
             skip_printing_tools: false,
         });
@@ -1286,9 +1966,28 @@ impl ChatSession {
         request_metadata_lock: Arc<Mutex<Option<RequestMetadata>>>,
         message_meta_tags: Option<Vec<MessageMetaTag>>,
     ) -> Result<SendMessageStream, ChatError> {
-        match SendMessageStream::send_message(&os.client, conversation_state, request_metadata_lock, message_meta_tags)
-            .await
-        {
+        let res = match self.race_model(os).await {
+            Some(race_model_id) => {
+                SendMessageStream::race(
+                    &os.client,
+                    conversation_state,
+                    race_model_id,
+                    request_metadata_lock,
+                    message_meta_tags,
+                )
+                .await
+            },
+            None => {
+                SendMessageStream::send_message(
+                    &os.client,
+                    conversation_state,
+                    request_metadata_lock,
+                    message_meta_tags,
+                )
+                .await
+            },
+        };
+        match res {
             Ok(res) => Ok(res),
             Err(err) => {
                 let (reason, reason_desc) = get_error_reason(&err);
@@ -1306,6 +2005,83 @@ impl ChatSession {
         }
     }
 
+    /// Runs the custom command of `input`, the line after its `/`, if it names one and no built-in
+    /// command, see [custom_commands].
+    async fn run_custom_command(&mut self, os: &Os, input: &str) -> Result<Option<ChatState>, ChatError> {
+        let (name, arguments) = input.split_once(char::is_whitespace).unwrap_or((input, ""));
+        if name == "help" || SlashCommand::command().find_subcommand(name).is_some() {
+            return Ok(None);
+        }
+        let Some(command) = custom_commands::find(os, name).await else {
+            return Ok(None);
+        };
+
+        if let Some(description) = &command.description {
+            execute!(
+                self.stderr,
+                style::Print(StyledText::secondary(&format!("/{name}: {description}\n\n")))
+            )?;
+        }
+        self.end_custom_command_turn();
+        let mut turn = CommandTurn::default();
+        if let Some(model) = &command.model {
+            let (models, _) = get_available_models(os).await?;
+            match find_model(&models, model) {
+                Some(model) => {
+                    turn.switched_model = true;
+                    turn.previous_model = self.conversation.model_info.replace(model.clone());
+                },
+                None => execute!(
+                    self.stderr,
+                    StyledText::warning_fg(),
+                    style::Print(format!(
+                        "The model {model} of /{name} isn't available, using the current model\n\n"
+                    )),
+                    StyledText::reset(),
+                )?,
+            }
+        }
+        let trusted = self.conversation.agents.get_active().map(|agent| &agent.allowed_tools);
+        turn.trusted_tools = command
+            .allowed_tools
+            .iter()
+            .filter(|tool| !trusted.is_some_and(|trusted| trusted.contains(*tool)))
+            .cloned()
+            .collect();
+        self.conversation.agents.trust_tools(turn.trusted_tools.clone());
+        self.custom_command_turn = Some(turn);
+
+        Ok(Some(ChatState::HandleInput {
+            input: command.expand(arguments),
+        }))
+    }
+
+    /// Undoes the changes of the custom command of the turn that ended.
+    fn end_custom_command_turn(&mut self) {
+        let Some(turn) = self.custom_command_turn.take() else {
+            return;
+        };
+        if turn.switched_model {
+            self.conversation.model_info = turn.previous_model;
+        }
+        self.conversation.agents.untrust_tools(&turn.trusted_tools);
+    }
+
+    /// Returns the id of the model set with `chat.raceModel` if the next request should be raced
+    /// against it, see [is_raceable].
+    async fn race_model(&self, os: &Os) -> Option<String> {
+        let name = os.database.settings.get_string(Setting::ChatRaceModel)?;
+        if !self.conversation.next_user_message().is_some_and(is_raceable) {
+            return None;
+        }
+        let (models, _) = get_available_models(os).await.ok()?;
+        let Some(model) = find_model(&models, &name) else {
+            warn!(%name, "the model set with chat.raceModel isn't available");
+            return None;
+        };
+        (Some(model.model_id.as_str()) != self.conversation.model_id()).then(|| model.model_id.clone())
+    }
+
     async fn spawn(&mut self, os: &mut Os) -> Result<()> {
         let is_small_screen = self.terminal_width() < GREETING_BREAK_POINT;
         if os
@@ -1326,11 +2102,11 @@ impl ChatSession {
 
             let rotating_tips = tips::get_rotating_tips();
             let tip = &rotating_tips[usize::try_from(rand::random::<u32>()).unwrap_or(0) % rotating_tips.len()];
-            if is_small_screen {
+            if is_small_screen || accessible::is_enabled() {
                 // If the screen is small, print the tip in a single line
                 execute!(
                     self.stderr,
-                    style::Print("💡 ".to_string()),
+                    style::Print(accessible::symbol("💡 ", "Tip: ")),
                     style::Print(tip),
                     style::Print("\n")
                 )?;
@@ -1352,11 +2128,13 @@ impl ChatSession {
                     false => ui_text::popular_shortcuts(),
                 }),
                 style::Print("\n"),
-                style::Print(
-                    "━"
-                        .repeat(if is_small_screen { 0 } else { GREETING_BREAK_POINT })
-                        .dark_grey()
-                )
+                style::Print(StyledText::secondary(&"━".repeat(
+                    if is_small_screen || accessible::is_enabled() {
+                        0
+                    } else {
+                        GREETING_BREAK_POINT
+                    }
+                )))
             )?;
             execute!(self.stderr, style::Print("\n"), StyledText::reset())?;
         }
@@ -1375,23 +2153,51 @@ impl ChatSession {
             )?;
         }
 
+        if self.conversation.agents.read_only {
+            queue!(
+                self.stderr,
+                style::Print(format!("{}\n\n", ui_text::read_only_notice()))
+            )?;
+        }
+
+        if self.plan.enabled {
+            queue!(
+                self.stderr,
+                style::Print(format!("{}\n\n", ui_text::plan_mode_notice()))
+            )?;
+        }
+
         if let Some(agent) = self.conversation.agents.get_active() {
             agent.print_overridden_permissions(&mut self.stderr)?;
         }
 
         self.stderr.flush()?;
 
-        if let Some(ref model_info) = self.conversation.model_info {
+        if let Some(ref mut model_info) = self.conversation.model_info {
             let (models, _default_model) = get_available_models(os).await?;
             if let Some(model_option) = models.iter().find(|option| option.model_id == model_info.model_id) {
+                // Models stored with a resumed conversation may predate the current capabilities
+                model_info.capabilities = model_option.capabilities;
                 let display_name = model_option.model_name.as_deref().unwrap_or(&model_option.model_id);
                 execute!(
                     self.stderr,
                     StyledText::brand_fg(),
-                    style::Print(format!("🤖 You are chatting with {}\n", display_name)),
+                    style::Print(format!(
+                        "{}You are chatting with {}\n",
+                        accessible::symbol("🤖 ", ""),
+                        display_name
+                    )),
                     StyledText::reset(),
-                    style::Print("\n")
                 )?;
+                for limitation in model_option.capabilities.limitations() {
+                    execute!(
+                        self.stderr,
+                        StyledText::warning_fg(),
+                        style::Print(format!("{limitation} with this model\n")),
+                        StyledText::reset(),
+                    )?;
+                }
+                execute!(self.stderr, style::Print("\n"))?;
             }
         }
 
@@ -1404,18 +2210,18 @@ impl ChatSession {
                     execute!(
                         self.stderr,
                         style::Print(
-                            format!(
-                                "📷 Checkpoints are enabled! (took {:.2}s)\n\n",
+                            StyledText::info(&format!(
+                                "{}Checkpoints are enabled! (took {:.2}s)\n\n",
+                                accessible::symbol("📷 ", ""),
                                 start.elapsed().as_secs_f32()
-                            )
-                            .blue()
+                            ))
                             .bold()
                         )
                     )?;
                     Some(manager)
                 },
                 Err(e) => {
-                    execute!(self.stderr, style::Print(format!("{e}\n\n").blue()))?;
+                    execute!(self.stderr, style::Print(StyledText::info(&format!("{e}\n\n"))))?;
                     None
                 },
             };
@@ -1426,19 +2232,66 @@ impl ChatSession {
             self.inner = Some(ChatState::HandleInput { input: user_input });
         }
 
-        while !matches!(self.inner, Some(ChatState::Exit)) {
-            self.next(os).await?;
-        }
+        let result = loop {
+            if matches!(self.inner, Some(ChatState::Exit)) {
+                break Ok(());
+            }
+            let next = match self.run_limits.deadline() {
+                Some((deadline, limit)) => match tokio::time::timeout_at(deadline.into(), self.next(os)).await {
+                    Ok(next) => next,
+                    Err(_) => Err(self.stop_session_on_timeout(os, limit).await),
+                },
+                None => self.next(os).await,
+            };
+            if let Err(err) = next {
+                break Err(err);
+            }
+        };
 
-        Ok(())
-    }
+        if let Some(status_bar) = self.status_bar.as_mut() {
+            status_bar.clear(&mut self.stderr)?;
+        }
 
-    /// Compacts the conversation history using the strategy specified by [CompactStrategy],
-    /// replacing the history with a summary generated by the model.
-    ///
-    /// If the compact request itself fails, it will be retried depending on [CompactStrategy]
-    ///
-    /// If [CompactStrategy::messages_to_exclude] is greater than 0, and
+        if os
+            .database
+            .settings
+            .get_bool(Setting::ChatSessionSummary)
+            .unwrap_or(false)
+            && !self.session_summary.is_empty()
+        {
+            execute!(
+                self.stderr,
+                style::Print("\n"),
+                style::Print(self.session_summary.format(&self.cost, Instant::now()))
+            )?;
+        }
+
+        let event_input = serde_json::json!({
+            "conversation_id": self.conversation.conversation_id(),
+            "reason": if result.is_ok() { "exit" } else { "error" },
+        });
+        if let Some(cm) = self.conversation.context_manager.as_mut() {
+            let _ = cm
+                .run_hooks_with_input(
+                    crate::cli::agent::hook::HookTrigger::SessionEnd,
+                    &mut self.stderr,
+                    os,
+                    None,
+                    None,
+                    Some(&event_input),
+                )
+                .await;
+        }
+
+        Ok(result?)
+    }
+
+    /// Compacts the conversation history using the strategy specified by [CompactStrategy],
+    /// replacing the history with a summary generated by the model.
+    ///
+    /// If the compact request itself fails, it will be retried depending on [CompactStrategy]
+    ///
+    /// If [CompactStrategy::messages_to_exclude] is greater than 0, and
     /// [CompactStrategy::truncate_large_messages] is true, then compaction will not be retried and
     /// will fail with [ChatError::CompactHistoryFailure].
     async fn compact_history(
@@ -1484,6 +2337,7 @@ impl ChatSession {
         strategy: CompactStrategy,
         request_metadata_lock: Arc<Mutex<Option<RequestMetadata>>>,
     ) -> Result<ChatState, ChatError> {
+        let strategy = self.conversation.resolve_compact_strategy(strategy);
         let hist = self.conversation.history();
         debug!(?strategy, ?hist, "compacting history");
 
@@ -1500,6 +2354,40 @@ impl ChatSession {
             });
         }
 
+        let started = Instant::now();
+        let chars_before = *self.conversation.calculate_char_count(os).await?;
+        let history_len_before = self.conversation.history().len();
+
+        // Tool outputs usually take up most of the context window. Collapsing them keeps the rest of
+        // the history as is, so the summary is only created if that isn't enough.
+        if strategy.collapse_tool_results && self.conversation.next_user_message().is_some() {
+            let collapsed = self.conversation.collapse_large_tool_results();
+            let max_chars = TokenCounter::model_tokens_to_chars(
+                self.conversation.model_id(),
+                context_window_tokens(self.conversation.model_info.as_ref()),
+            );
+            if collapsed > 0
+                && *self.conversation.calculate_char_count(os).await? * 100
+                    < max_chars * COLLAPSED_TOOL_RESULTS_MAX_USAGE_PERCENT
+            {
+                let record = self
+                    .record_compaction(os, CompactionTrigger::Overflow, started.elapsed(), chars_before, 0)
+                    .await?;
+                execute!(
+                    self.stderr,
+                    style::Print(format_collapsed_notice(collapsed)),
+                    StyledText::secondary_fg(),
+                    style::Print(format!("  {record}\n\n")),
+                    StyledText::reset(),
+                )?;
+                return Ok(ChatState::HandleResponseStream(
+                    self.conversation
+                        .as_sendable_conversation_state(os, &mut self.stderr, false)
+                        .await?,
+                ));
+            }
+        }
+
         if strategy.truncate_large_messages {
             info!("truncating large messages");
             execute!(
@@ -1513,13 +2401,18 @@ impl ChatSession {
             )?;
         }
 
+        let retention_instructions = self.conversation.run_pre_compact_hooks(os, &mut self.stderr).await;
+        let custom_prompt = match (custom_prompt, retention_instructions) {
+            (Some(prompt), Some(instructions)) => Some(format!("{prompt}\n{instructions}")),
+            (prompt, instructions) => prompt.or(instructions),
+        };
         let summary_state = self
             .conversation
             .create_summary_request(os, custom_prompt.as_ref(), strategy)
             .await?;
 
         if self.interactive {
-            self.spinner = Some(Spinner::new(Spinners::Dots, "Creating summary...".to_string()));
+            self.spinner = Some(StatusIndicator::new("Creating summary...".to_string()));
         }
 
         let mut response = match self
@@ -1560,6 +2453,9 @@ impl ChatSession {
                                 prompt: custom_prompt,
                                 show_summary,
                                 strategy: CompactStrategy {
+                                    kind: CompactKind::Full,
+                                    preview: strategy.preview,
+                                    collapse_tool_results: false,
                                     truncate_large_messages: true,
                                     max_message_length: 25_000,
                                     messages_to_exclude: 0,
@@ -1589,7 +2485,7 @@ impl ChatSession {
                                 },
                             });
                         } else {
-                            return Err(ChatError::CompactHistoryFailure);
+                            return self.compact_history_locally(os, started, chars_before).await;
                         }
                     },
                     err => return Err(err),
@@ -1597,7 +2493,7 @@ impl ChatSession {
             },
         };
 
-        let (summary, request_metadata) = {
+        let (mut summary, request_metadata) = {
             loop {
                 match response.recv().await {
                     Some(Ok(parser::ResponseEvent::EndStream {
@@ -1607,6 +2503,9 @@ impl ChatSession {
                         self.user_turn_request_metadata.push(request_metadata.clone());
                         break (message.content().to_string(), request_metadata);
                     },
+                    Some(Ok(parser::ResponseEvent::Retry(notice))) => {
+                        self.show_retry_notice(&notice, Some("Creating summary...".to_string()))?;
+                    },
                     Some(Ok(_)) => (),
                     Some(Err(err)) => {
                         if let Some(request_id) = &err.request_metadata.request_id {
@@ -1645,12 +2544,41 @@ impl ChatSession {
             )?;
         }
 
+        if strategy.preview {
+            match self.preview_summary(os, summary, strategy).await? {
+                Some(accepted) => summary = accepted,
+                None => {
+                    self.send_chat_telemetry(os, TelemetryResult::Cancelled, None, None, None, true)
+                        .await;
+                    execute!(
+                        self.stderr,
+                        StyledText::secondary_fg(),
+                        style::Print("\nCompaction cancelled. The conversation history was left as is.\n\n"),
+                        StyledText::reset(),
+                    )?;
+                    return Ok(ChatState::PromptUser {
+                        skip_printing_tools: true,
+                    });
+                },
+            }
+        }
+
         self.conversation
             .replace_history_with_summary(summary.clone(), strategy, request_metadata);
 
         // If a next message is set, then retry the request.
         let should_retry = self.conversation.next_user_message().is_some();
 
+        let messages_folded = history_len_before.saturating_sub(self.conversation.history().len());
+        let trigger = if should_retry {
+            CompactionTrigger::Overflow
+        } else {
+            CompactionTrigger::Manual
+        };
+        let record = self
+            .record_compaction(os, trigger, started.elapsed(), chars_before, messages_folded)
+            .await?;
+
         // If we retry, then don't end the current turn.
         self.send_chat_telemetry(os, TelemetryResult::Succeeded, None, None, None, !should_retry)
             .await;
@@ -1660,8 +2588,10 @@ impl ChatSession {
             execute!(
                 self.stderr,
                 StyledText::success_fg(),
-                style::Print("✔ Conversation history has been compacted successfully!\n\n"),
-                StyledText::secondary_fg()
+                style::Print(accessible::symbol("✔ ", "")),
+                style::Print("Conversation history has been compacted successfully!\n"),
+                StyledText::secondary_fg(),
+                style::Print(format!("  {record}\n\n")),
             )?;
 
             let mut output = Vec::new();
@@ -1725,6 +2655,452 @@ impl ChatSession {
         }
     }
 
+    /// Fallback for when the summary can't be created because the history is too large even for
+    /// the summary request: shrinks the history by rule instead, see
+    /// [ConversationState::compact_history_locally].
+    async fn compact_history_locally(
+        &mut self,
+        os: &mut Os,
+        started: Instant,
+        chars_before: usize,
+    ) -> Result<ChatState, ChatError> {
+        let compacted = self.conversation.compact_history_locally();
+        if compacted.is_empty() {
+            return Err(ChatError::CompactHistoryFailure);
+        }
+        info!(?compacted, "compacted history locally");
+        let record = self
+            .record_compaction(os, CompactionTrigger::Overflow, started.elapsed(), chars_before, 0)
+            .await?;
+
+        let mut changes = Vec::new();
+        if compacted.deduplicated > 0 {
+            changes.push(format!("removed {} duplicate tool outputs", compacted.deduplicated));
+        }
+        if compacted.collapsed > 0 {
+            changes.push(format!("collapsed {} tool outputs", compacted.collapsed));
+        }
+        if compacted.reasoning_dropped > 0 {
+            changes.push(format!(
+                "dropped the text of {} intermediate responses",
+                compacted.reasoning_dropped
+            ));
+        }
+        execute!(
+            self.stderr,
+            StyledText::warning_fg(),
+            style::Print("The conversation is too large to be summarized. "),
+            StyledText::success_fg(),
+            style::Print(format!(
+                "{}Compacted it locally instead: {}\n",
+                accessible::symbol("✔ ", ""),
+                changes.join(", ")
+            )),
+            StyledText::secondary_fg(),
+            style::Print(format!("  {record}\n\n")),
+            StyledText::reset(),
+        )?;
+
+        if self.conversation.next_user_message().is_some() {
+            Ok(ChatState::HandleResponseStream(
+                self.conversation
+                    .as_sendable_conversation_state(os, &mut self.stderr, false)
+                    .await?,
+            ))
+        } else {
+            Ok(ChatState::PromptUser {
+                skip_printing_tools: true,
+            })
+        }
+    }
+
+    /// Records a compaction that shrank the conversation from `chars_before`, for `/compact stats`,
+    /// and sends it to telemetry.
+    async fn record_compaction(
+        &mut self,
+        os: &Os,
+        trigger: CompactionTrigger,
+        duration: Duration,
+        chars_before: usize,
+        messages_folded: usize,
+    ) -> Result<CompactionRecord, ChatError> {
+        let chars_after = *self.conversation.calculate_char_count(os).await?;
+        let model_id = self.conversation.model_id();
+        let record = CompactionRecord {
+            trigger,
+            tokens_before: TokenCounter::count_model_tokens(model_id, chars_before),
+            tokens_after: TokenCounter::count_model_tokens(model_id, chars_after),
+            messages_folded,
+            duration,
+        };
+        info!(?record, "compacted history");
+        self.conversation.push_compaction(record);
+
+        if let Err(err) = os
+            .telemetry
+            .send_history_compacted(
+                &os.database,
+                self.conversation.conversation_id().to_string(),
+                HistoryCompactedArgs {
+                    trigger: record.trigger.name().to_string(),
+                    tokens_before: record.tokens_before as i64,
+                    tokens_after: record.tokens_after as i64,
+                    messages_folded: record.messages_folded as i64,
+                    duration_ms: record.duration.as_millis() as i64,
+                },
+            )
+            .await
+        {
+            warn!(?err, "Failed to send the history compaction telemetry");
+        }
+        Ok(record)
+    }
+
+    /// Calibrates the token estimates of the model with the input tokens reported for the last
+    /// request sent, keeping the calibration for later sessions.
+    fn calibrate_token_counter(os: &Os, request_metadata: &RequestMetadata, char_count: Option<usize>) {
+        let (Some(model_id), Some(char_count), Some(input_tokens)) =
+            (&request_metadata.model_id, char_count, request_metadata.input_tokens)
+        else {
+            return;
+        };
+        if TokenCounter::calibrate(model_id, char_count, input_tokens) {
+            debug!(
+                model_id,
+                chars_per_token = TokenCounter::chars_per_token(Some(model_id)),
+                "calibrated token counter"
+            );
+            if let Err(err) = os.database.set_token_calibration(&TokenCounter::calibration()) {
+                warn!(?err, "Failed to save the token calibration");
+            }
+        }
+    }
+
+    /// Adds the request to the cost of the session. Token counts the backend didn't report are
+    /// estimated from the size of the request and of the response.
+    fn record_request_cost(&mut self, request_metadata: &RequestMetadata, char_count: Option<usize>) {
+        let model_id = request_metadata.model_id.as_deref();
+        let mut estimated = false;
+        let mut estimate = |chars: usize| {
+            estimated = true;
+            TokenCounter::count_model_tokens(model_id, chars)
+        };
+        let input_tokens = match request_metadata.input_tokens {
+            Some(tokens) => tokens,
+            None => estimate(char_count.unwrap_or_default()),
+        };
+        let output_tokens = match request_metadata.output_tokens {
+            Some(tokens) => tokens,
+            None => estimate(request_metadata.response_size),
+        };
+        let usage = RequestUsage {
+            input_tokens,
+            output_tokens,
+            estimated,
+        };
+        let cost = self.cost.record(model_id, usage);
+        debug!(?model_id, ?usage, ?cost, "recorded request cost");
+    }
+
+    /// Updates the context usage shown in the status bar with the tokens of the request and of its
+    /// response, estimated from their size when the backend didn't report them.
+    fn record_context_usage(&mut self, request_metadata: &RequestMetadata, char_count: Option<usize>) {
+        let model_id = request_metadata.model_id.as_deref();
+        let Some(input_tokens) = request_metadata
+            .input_tokens
+            .or_else(|| char_count.map(|chars| TokenCounter::count_model_tokens(model_id, chars)))
+        else {
+            return;
+        };
+        let output_tokens = request_metadata
+            .output_tokens
+            .unwrap_or_else(|| TokenCounter::count_model_tokens(model_id, request_metadata.response_size));
+        let context_window = context_window_tokens(self.conversation.model_info.as_ref());
+        self.context_usage = Some((input_tokens + output_tokens) as f32 / context_window as f32 * 100.0);
+    }
+
+    /// Whether the estimated cost of the session went over the limit set with `--max-cost`.
+    fn over_cost_limit(&self) -> Option<CostLimit> {
+        self.max_cost.filter(|limit| self.cost.total_cost > limit.0)
+    }
+
+    /// Ends the turn because the session went over its cost limit, abandoning the tool uses the
+    /// model requested in its last response.
+    async fn stop_turn_over_cost_limit(
+        &mut self,
+        os: &mut Os,
+        limit: CostLimit,
+        tool_uses: &[AssistantToolUse],
+    ) -> Result<ChatState, ChatError> {
+        execute!(
+            self.stderr,
+            StyledText::error_fg(),
+            style::Print(format!(
+                "\nThe estimated cost of this session, {}, exceeds the limit of {} set with --max-cost. Stopping the turn.\n\n",
+                Cost(self.cost.total_cost),
+                Cost(limit.0)
+            )),
+            StyledText::reset(),
+        )?;
+        self.abandon_turn(
+            os,
+            tool_uses,
+            "the session exceeded its cost limit",
+            serde_json::json!({ "reason": "maxCost" }),
+        )
+        .await?;
+        if !self.interactive {
+            self.exit_code = ChatExitCode::LimitReached;
+        }
+
+        Ok(ChatState::PromptUser {
+            skip_printing_tools: true,
+        })
+    }
+
+    /// Pauses the delegated task this session runs, which reached one of its limits, until the
+    /// session that delegated it decides whether it carries on with the tool uses the model
+    /// requested in its last response.
+    async fn pause_over_task_limit(
+        &mut self,
+        os: &mut Os,
+        limit: TaskLimit,
+        tool_uses: Vec<AssistantToolUse>,
+    ) -> Result<ChatState, ChatError> {
+        execute!(
+            self.stderr,
+            StyledText::warning_fg(),
+            style::Print(format!(
+                "\nPaused as the task reached {limit}, waiting for the decision of the session that delegated it.\n"
+            )),
+            StyledText::reset(),
+        )?;
+        let decision = tools::delegate::limits::decide(os, &limit)
+            .await
+            .map_err(|e| ChatError::Custom(format!("Failed to ask whether the task carries on: {e}").into()))?;
+        match decision {
+            Decision::Continue => {
+                if let Some(budget) = self.task_budget.as_mut() {
+                    let tokens = self.cost.input_tokens + self.cost.output_tokens;
+                    budget.renew(tokens, self.cost.total_cost, Instant::now());
+                }
+                execute!(self.stderr, style::Print("Carrying on with the same limits again.\n\n"))?;
+                Ok(ChatState::ValidateTools { tool_uses })
+            },
+            Decision::Stop => {
+                execute!(
+                    self.stderr,
+                    StyledText::error_fg(),
+                    style::Print(format!("Stopping the task as it reached {limit}.\n\n")),
+                    StyledText::reset(),
+                )?;
+                self.abandon_turn(
+                    os,
+                    &tool_uses,
+                    &format!("the task reached {limit}"),
+                    serde_json::json!({ "reason": "taskLimit", "limit": limit.to_string() }),
+                )
+                .await?;
+                self.exit_code = ChatExitCode::LimitReached;
+                Ok(ChatState::PromptUser {
+                    skip_printing_tools: true,
+                })
+            },
+        }
+    }
+
+    /// Ends the session because it reached one of its [RunLimits], abandoning the tool uses the
+    /// model requested in its last response.
+    async fn stop_session_over_run_limit(
+        &mut self,
+        os: &mut Os,
+        limit: RunLimit,
+        tool_uses: &[AssistantToolUse],
+    ) -> Result<ChatState, ChatError> {
+        self.abandon_turn(
+            os,
+            tool_uses,
+            &format!("the session reached {limit}"),
+            serde_json::json!({ "reason": limit.reason(), "limit": limit.value() }),
+        )
+        .await?;
+        Err(ChatError::LimitReached(limit))
+    }
+
+    /// Ends the session because it ran out of time, once the state it was in was cancelled: the
+    /// tool uses in progress are abandoned and the conversation so far is saved, so that it can be
+    /// resumed.
+    async fn stop_session_on_timeout(&mut self, os: &mut Os, limit: RunLimit) -> ChatError {
+        if let Some(spinner) = self.spinner.take() {
+            drop(spinner);
+            let _ = queue!(
+                self.stderr,
+                terminal::Clear(terminal::ClearType::CurrentLine),
+                cursor::MoveToColumn(0),
+                cursor::Show
+            );
+        }
+        let _ = execute!(
+            self.stderr,
+            StyledText::error_fg(),
+            style::Print(format!("\n\nStopping the session as it reached {limit}.\n")),
+            StyledText::reset(),
+        );
+
+        let tool_uses = std::mem::take(&mut self.tool_uses);
+        let note = if !tool_uses.is_empty() {
+            self.conversation.abandon_tool_use(
+                &tool_uses,
+                format!("Tool uses were stopped because the session reached {limit}."),
+            );
+            Some("Tool uses were stopped because the session timed out.")
+        } else if self.conversation.next_user_message().is_some() {
+            Some("The session timed out before the response was complete.")
+        } else {
+            None
+        };
+        if let Some(note) = note {
+            if self
+                .conversation
+                .as_sendable_conversation_state(os, &mut self.stderr, false)
+                .await
+                .is_ok()
+            {
+                self.conversation.push_assistant_message(
+                    os,
+                    AssistantMessage::new_response(None, note.to_string()),
+                    None,
+                );
+            }
+        }
+        self.pending_tool_index = None;
+        self.tool_turn_start_time = None;
+        let _ = self.stdout.flush();
+
+        self.send_chat_telemetry(os, TelemetryResult::Cancelled, None, None, None, true)
+            .await;
+        let interrupt = serde_json::json!({ "reason": limit.reason(), "limit": limit.value() });
+        if let Err(err) = self.send_run_finished("interrupt", Some(interrupt)) {
+            return err;
+        }
+        ChatError::LimitReached(limit)
+    }
+
+    /// Abandons the tool uses the model requested in its last response for `reason`, and reports
+    /// the turn as interrupted with `interrupt`.
+    async fn abandon_turn(
+        &mut self,
+        os: &mut Os,
+        tool_uses: &[AssistantToolUse],
+        reason: &str,
+        interrupt: serde_json::Value,
+    ) -> Result<(), ChatError> {
+        if !tool_uses.is_empty() {
+            self.conversation.abandon_tool_use_ids(
+                tool_uses.iter().map(|tool_use| tool_use.id.as_str()),
+                format!("Tool uses were stopped because {reason}."),
+            );
+            let _ = self
+                .conversation
+                .as_sendable_conversation_state(os, &mut self.stderr, false)
+                .await?;
+            self.conversation.push_assistant_message(
+                os,
+                AssistantMessage::new_response(
+                    None,
+                    format!("Tool uses were stopped because {reason}. Waiting for the next user prompt."),
+                ),
+                None,
+            );
+        }
+        self.tool_uses.clear();
+        self.pending_tool_index = None;
+        self.tool_turn_start_time = None;
+
+        self.send_chat_telemetry(os, TelemetryResult::Cancelled, None, None, None, true)
+            .await;
+        self.send_run_finished("interrupt", Some(interrupt))?;
+        Ok(())
+    }
+
+    /// Reports the usage and cost of the session at the end of a turn.
+    fn send_run_finished(&mut self, outcome: &str, interrupt: Option<serde_json::Value>) -> Result<(), ChatError> {
+        if !self.stdout.should_send_structured_event {
+            return Ok(());
+        }
+        let result = serde_json::to_value(self.cost.report()).ok();
+        self.stdout.send(Event::RunFinished(RunFinished {
+            thread_id: self.conversation.conversation_id().to_string(),
+            run_id: self.conversation.message_id().unwrap_or_default().to_string(),
+            result,
+            outcome: Some(outcome.to_string()),
+            interrupt,
+        }))?;
+        Ok(())
+    }
+
+    /// Shows the summary created by `/compact --preview` along with the projected savings, and lets
+    /// the user use it, edit it in $EDITOR first, or cancel. Returns [None] if cancelled.
+    async fn preview_summary(
+        &mut self,
+        os: &Os,
+        mut summary: String,
+        strategy: CompactStrategy,
+    ) -> Result<Option<String>, ChatError> {
+        const ACTIONS: [&str; 3] = ["Use this summary", "Edit it in $EDITOR", "Cancel"];
+
+        let before = *self.conversation.calculate_char_count(os).await?;
+        let replaced = self.conversation.summarized_char_count(strategy);
+        loop {
+            let after = before.saturating_sub(replaced) + summary.len();
+            let border = "═".repeat(self.terminal_width().min(80));
+            execute!(
+                self.stderr,
+                style::Print("\n"),
+                StyledText::brand_fg(),
+                style::Print(&border),
+                style::Print("\n"),
+                style::SetAttribute(Attribute::Bold),
+                style::Print("                       PROPOSED SUMMARY\n"),
+                style::Print(&border),
+                StyledText::reset_attributes(),
+                StyledText::reset(),
+                style::Print("\n\n"),
+                style::Print(&summary),
+                style::Print("\n\n"),
+                StyledText::brand_fg(),
+                style::Print(&border),
+                StyledText::reset(),
+                style::Print(format!(
+                    "\nContext usage: ~{} tokens now, ~{} tokens with this summary (~{} saved)\n\n",
+                    TokenCounter::count_model_tokens(self.conversation.model_id(), before),
+                    TokenCounter::count_model_tokens(self.conversation.model_id(), after),
+                    TokenCounter::count_model_tokens(self.conversation.model_id(), before.saturating_sub(after)),
+                )),
+            )?;
+
+            match choose("What would you like to do?", &ACTIONS)
+                .map_err(|e| ChatError::Custom(format!("Failed to get input: {e}").into()))?
+            {
+                Some(0) => return Ok(Some(summary)),
+                Some(1) => {
+                    let edited = open_editor(Some(summary.clone()))?;
+                    if edited.trim().is_empty() {
+                        execute!(
+                            self.stderr,
+                            StyledText::warning_fg(),
+                            style::Print("\nThe edited summary is empty, keeping the previous one.\n"),
+                            StyledText::reset(),
+                        )?;
+                    } else {
+                        summary = edited.trim().to_string();
+                    }
+                },
+                _ => return Ok(None),
+            }
+        }
+    }
+
     /// Generates a custom agent configuration (system prompt and tool config) based on user input.
     /// Uses an LLM to create the agent specifications from the provided name and description.
     async fn generate_agent_config(
@@ -1813,10 +3189,10 @@ impl ChatSession {
 
         if self.interactive {
             execute!(self.stderr, cursor::Hide, style::Print("\n"))?;
-            self.spinner = Some(Spinner::new(
-                Spinners::Dots,
-                format!("Generating agent config for '{}'...", agent_name),
-            ));
+            self.spinner = Some(StatusIndicator::new(format!(
+                "Generating agent config for '{}'...",
+                agent_name
+            )));
         }
 
         let mut response = match self
@@ -1853,6 +3229,12 @@ impl ChatSession {
                         self.user_turn_request_metadata.push(request_metadata.clone());
                         break (message.content().to_string(), request_metadata);
                     },
+                    Some(Ok(parser::ResponseEvent::Retry(notice))) => {
+                        self.show_retry_notice(
+                            &notice,
+                            Some(format!("Generating agent config for '{}'...", agent_name)),
+                        )?;
+                    },
                     Some(Ok(_)) => (),
                     Some(Err(err)) => {
                         if let Some(request_id) = &err.request_metadata.request_id {
@@ -1957,8 +3339,20 @@ impl ChatSession {
     async fn prompt_user(&mut self, os: &Os, skip_printing_tools: bool) -> Result<ChatState, ChatError> {
         execute!(self.stderr, cursor::Show)?;
 
+        if self.pending_tool_index.is_none() {
+            self.end_custom_command_turn();
+            if let Some(state) = self.next_queued_prompt(os).await? {
+                return Ok(state);
+            }
+        }
+
         // Check token usage and display warnings if needed
         if self.pending_tool_index.is_none() {
+            self.apply_background_compaction(os).await?;
+            if self.background_compaction.is_none() {
+                self.background_compaction = BackgroundCompaction::start_if_needed(os, &mut self.conversation).await;
+            }
+
             // Only display warnings when not waiting for tool approval
             if let Err(err) = self.display_char_warnings(os).await {
                 warn!("Failed to display character limit warnings: {}", err);
@@ -1986,11 +3380,31 @@ impl ChatSession {
                 StyledText::success_fg(),
                 style::Print("t"),
                 StyledText::secondary_fg(),
-                style::Print("]:\n\n"),
+                style::Print("]. Use '"),
+                StyledText::success_fg(),
+                style::Print("n <reason>"),
+                StyledText::secondary_fg(),
+                style::Print("' to deny with a reason for the model:\n\n"),
                 StyledText::reset(),
             )?;
         }
 
+        let tool_names = self
+            .conversation
+            .tools
+            .values()
+            .flatten()
+            .map(|api_client::model::Tool::ToolSpecification(spec)| spec.name.clone())
+            .filter(|name| name != consts::DUMMY_TOOL_NAME)
+            .collect();
+        self.input_source.set_tool_names(tool_names);
+        let custom_commands = custom_commands::load(os).await;
+        self.input_source
+            .set_custom_commands(custom_commands.into_iter().map(|command| command.name).collect());
+        self.input_source.set_attached_images(self.pending_images.len());
+        self.input_source
+            .set_awaiting_approval(self.pending_tool_index.is_some());
+
         // Do this here so that the skim integration sees an updated view of the context *during the current
         // q session*. (e.g., if I add files to context, that won't show up for skim for the current
         // q session unless we do this in prompt_user... unless you can find a better way)
@@ -2013,6 +3427,11 @@ impl ChatSession {
         }
 
         execute!(self.stderr, StyledText::reset(), StyledText::reset_attributes())?;
+        if self.status_bar.is_some() {
+            use crate::cli::chat::cli::usage::usage_data_provider::get_total_usage_percentage;
+            self.context_usage = get_total_usage_percentage(self, os).await.ok();
+        }
+        self.update_status_bar(os).await?;
         let prompt = self.generate_tool_trust_prompt(os).await;
 
         // Here we are signaling to the ui layer that the event loop wants to prompt user
@@ -2072,6 +3491,12 @@ impl ChatSession {
         if let Some(chat_state) = does_input_reference_file(input) {
             return Ok(chat_state);
         }
+        if let Some(state) = match input.strip_prefix("/") {
+            Some(command) => self.run_custom_command(os, command).await?,
+            None => None,
+        } {
+            return Ok(state);
+        }
         if let Some(mut args) = input.strip_prefix("/").and_then(shlex::split) {
             // Required for printing errors correctly.
             let orig_args = args.clone();
@@ -2104,6 +3529,9 @@ impl ChatSession {
                                 // from manually running /compact, without impacting behavior of
                                 // other slash commands.
                                 || matches!(chat_state, ChatState::CompactHistory { .. })
+                                // Commands that change permissions re-evaluate tools that are
+                                // pending approval.
+                                || matches!(chat_state, ChatState::ExecuteTools)
                             {
                                 return Ok(chat_state);
                             }
@@ -2216,6 +3644,34 @@ impl ChatSession {
                 skip_printing_tools: false,
             })
         } else {
+            // Queue the prompt rather than failing to send it while offline
+            if self.pending_tool_index.is_none() && self.offline_queue.is_offline() {
+                if !offline_queue::probe(os).await {
+                    self.offline_queue.push(user_input);
+                    execute!(
+                        self.stderr,
+                        StyledText::warning_fg(),
+                        style::Print(format!(
+                            "Still offline, the prompt was queued ({} queued). Queued prompts are offered for sending once the connection is back.\n\n",
+                            self.offline_queue.len()
+                        )),
+                        StyledText::reset(),
+                    )?;
+                    return Ok(ChatState::PromptUser {
+                        skip_printing_tools: false,
+                    });
+                }
+
+                self.offline_queue.reconnected();
+                // The earlier prompts go first
+                if !self.offline_queue.is_empty() {
+                    self.offline_queue.push(user_input);
+                    return Ok(ChatState::PromptUser {
+                        skip_printing_tools: false,
+                    });
+                }
+            }
+
             // Track the message for checkpoint descriptions, but only if not already set
             // This prevents tool approval responses (y/n/t) from overwriting the original message
             if ExperimentManager::is_enabled(os, ExperimentName::Checkpoint) && !self.conversation.is_in_tangent_mode()
@@ -2270,27 +3726,104 @@ impl ChatSession {
             // Otherwise continue with normal chat on 'n' or other responses
             self.tool_use_status = ToolUseStatus::Idle;
 
+            if let Some(context_manager) = self.conversation.context_manager.as_mut() {
+                let (evicted, stale) = context_manager.evict_stale_entries(os).await;
+                let turns = context_manager.stale_context.evict_after_turns;
+                if !evicted.is_empty() {
+                    queue!(
+                        self.stderr,
+                        StyledText::secondary_fg(),
+                        style::Print(format!(
+                            "Removed context unused for the last {turns} turns: {}\n",
+                            evicted.join(", ")
+                        )),
+                        StyledText::reset(),
+                    )?;
+                }
+                if !stale.is_empty() {
+                    queue!(
+                        self.stderr,
+                        StyledText::secondary_fg(),
+                        style::Print(format!(
+                            "Context unused for the last {turns} turns: {}. Remove it with /context rm, or automatically with /context auto-evict on\n",
+                            stale.join(", ")
+                        )),
+                        StyledText::reset(),
+                    )?;
+                }
+            }
+
             if self.pending_tool_index.is_some() {
                 // If the user just enters "n", replace the message we send to the model with
                 // something more substantial.
                 // TODO: Update this flow to something that does *not* require two requests just to
                 // get a meaningful response from the user - this is a short term solution before
                 // we decide on a better flow.
-                let user_input = if ["n", "N"].contains(&user_input.trim()) {
-                    "I deny this tool request. Ask a follow up question clarifying the expected action".to_string()
-                } else {
-                    user_input
-                };
-                self.conversation.abandon_tool_use(&self.tool_uses, user_input);
+                match parse_denial_reason(&user_input) {
+                    Some(reason) => {
+                        let user_input = format!(
+                            "I deny this tool request because: {reason}. Take this into account before attempting the action again, or ask a follow up question clarifying the expected action"
+                        );
+                        self.conversation.deny_tool_use(&self.tool_uses, user_input, &reason);
+                    },
+                    None => {
+                        let user_input = if ["n", "N"].contains(&user_input.trim()) {
+                            "I deny this tool request. Ask a follow up question clarifying the expected action"
+                                .to_string()
+                        } else {
+                            user_input
+                        };
+                        self.conversation.abandon_tool_use(&self.tool_uses, user_input);
+                    },
+                }
             } else {
+                // Images dragged onto the terminal show up as their paths in the prompt
+                for path in detect_image_paths(&user_input) {
+                    match self.attach_image(&path) {
+                        Ok(()) => queue!(
+                            self.stderr,
+                            StyledText::secondary_fg(),
+                            style::Print(format!("Attached image {path}\n")),
+                            StyledText::reset(),
+                        )?,
+                        Err(e) => queue!(
+                            self.stderr,
+                            StyledText::warning_fg(),
+                            style::Print(format!("Could not attach image {path}: {e}\n")),
+                            StyledText::reset(),
+                        )?,
+                    }
+                }
+                let images = std::mem::take(&mut self.pending_images);
+                let user_input = self.expand_inline_commands(os, user_input).await?;
+
+                self.apply_background_compaction(os).await?;
+
                 // Add additional context if available (e.g., delegate summaries)
-                let context = self.pending_additional_context.take().unwrap_or_default();
+                let mut context = self.pending_additional_context.take().unwrap_or_default();
+                if let Some(schema) = &self.response_schema {
+                    if !context.is_empty() {
+                        context.push_str("\n\n");
+                    }
+                    context.push_str(&schema.instructions());
+                }
+                if self.plan.enabled {
+                    if !context.is_empty() {
+                        context.push_str("\n\n");
+                    }
+                    context.push_str(plan::PLAN_MODE_INSTRUCTIONS);
+                }
                 self.conversation
                     .set_next_user_message_with_context(user_input, context)
                     .await;
+                if !images.is_empty() {
+                    self.conversation
+                        .attach_images_to_next_message(images.into_iter().map(|(block, _)| block).collect());
+                }
             }
 
             self.reset_user_turn();
+            self.session_summary.record_turn();
 
             let conv_state = self
                 .conversation
@@ -2302,8 +3835,9 @@ impl ChatSession {
             queue!(self.stderr, StyledText::reset())?;
             queue!(self.stderr, cursor::Hide)?;
 
+            self.progress.start(Step::Response);
             if self.interactive {
-                self.spinner = Some(Spinner::new(Spinners::Dots, "Thinking...".to_owned()));
+                self.spinner = Some(StatusIndicator::new(self.progress.terminal_line()));
             }
 
             Ok(ChatState::HandleResponseStream(conv_state))
@@ -2337,19 +3871,42 @@ impl ChatSession {
             }
 
             let mut denied_match_set = None::<Vec<String>>;
-            let allowed =
-                self.conversation
-                    .agents
-                    .get_active()
-                    .is_some_and(|a| match tool.tool.requires_acceptance(os, a) {
+            let denied_by_read_only = self.conversation.agents.read_only && !tool.tool.is_read_only();
+            if denied_by_read_only {
+                denied_match_set.replace(vec!["read-only mode is enabled for this session".to_string()]);
+            }
+            let allowed = !denied_by_read_only
+                && (self.conversation.agents.get_active().is_some_and(|a| {
+                    match tool.tool.requires_acceptance(os, a) {
                         PermissionEvalResult::Allow => true,
                         PermissionEvalResult::Ask => false,
                         PermissionEvalResult::Deny(matches) => {
                             denied_match_set.replace(matches);
                             false
                         },
-                    })
-                    || self.conversation.agents.trust_all_tools;
+                    }
+                }) || self.conversation.agents.trust_all_tools);
+
+            // The approval policy decides where the user would otherwise be asked. Tool uses recorded
+            // into the plan are not run, so there is nothing to decide on yet.
+            let mut allowed = allowed;
+            let mut denied_by_policy = false;
+            if let Some(policy) = &self.approval_policy {
+                if !allowed && denied_match_set.is_none() && !(self.plan.enabled && plan::is_planned(&tool.tool)) {
+                    let tool_name = match &tool.tool {
+                        Tool::Custom(custom_tool) => custom_tool.namespaced_tool_name(),
+                        _ => tool.name.clone(),
+                    };
+                    match policy.evaluate(&tool_name, &tool.tool_input) {
+                        Some(decision) if decision.action == PolicyAction::Allow => allowed = true,
+                        Some(decision) => {
+                            denied_by_policy = true;
+                            denied_match_set.replace(vec![decision.rule]);
+                        },
+                        None => (),
+                    }
+                }
+            }
 
             if let Some(match_set) = denied_match_set {
                 let formatted_set = match_set.into_iter().fold(String::new(), |mut acc, rule| {
@@ -2379,22 +3936,27 @@ impl ChatSession {
                     self.stderr.send(Event::ToolCallRejection(event))?;
                 }
 
-                return Ok(ChatState::HandleInput {
-                    input: format!(
+                let input = if denied_by_read_only {
+                    format!(
+                        "Tool use with {} was rejected because the session is in read-only mode. Only use tools that do not modify files or run commands with side effects",
+                        tool.name
+                    )
+                } else if denied_by_policy {
+                    format!(
+                        "Tool use with {} was rejected by the approval policy of the session. Do not retry it with the same arguments",
+                        tool.name
+                    )
+                } else {
+                    format!(
                         "Tool use with {} was rejected because the arguments supplied were forbidden",
                         tool.name
-                    ),
-                });
+                    )
+                };
+                return Ok(ChatState::HandleInput { input });
             }
 
-            if os
-                .database
-                .settings
-                .get_bool(Setting::ChatEnableNotifications)
-                .unwrap_or(false)
-            {
-                play_notification_bell(!allowed);
-            }
+            // Tool uses recorded into the plan are not run, so there is nothing to approve yet.
+            let allowed = allowed || (self.plan.enabled && plan::is_planned(&tool.tool));
 
             // TODO: Control flow is hacky here because of borrow rules
             let _ = tool;
@@ -2412,6 +3974,21 @@ impl ChatSession {
                 continue;
             }
 
+            let tool_name = match &tool.tool {
+                Tool::Custom(custom_tool) => custom_tool.namespaced_tool_name(),
+                _ => tool.name.clone(),
+            };
+            let message = format!("{tool_name} needs your approval");
+            Notifier::from_settings(&os.database.settings)
+                .notify(&Notification::new(NotificationKind::ApprovalNeeded, message.clone()));
+            let notification = serde_json::json!({
+                "notification_type": NOTIFICATION_APPROVAL_NEEDED,
+                "message": message,
+                "tool_name": tool_name,
+                "tool_input": tool.tool_input,
+            });
+            self.run_notification_hooks(os, notification);
+
             self.pending_tool_index = Some(i);
 
             return Ok(ChatState::PromptUser {
@@ -2423,9 +4000,42 @@ impl ChatSession {
         // Execute the requested tools.
         let mut tool_results = vec![];
         let mut image_blocks: Vec<RichImageBlock> = Vec::new();
+        let tools_to_run = self
+            .tool_uses
+            .iter()
+            .filter(|tool| !(self.plan.enabled && plan::is_planned(&tool.tool)))
+            .count();
+        let mut tool_index = 0;
+
+        for tool in &self.tool_uses {
+            if self.plan.enabled && plan::is_planned(&tool.tool) {
+                let step = self.plan.record(os, tool);
+                execute!(
+                    self.stdout,
+                    style::Print(continuation_line()),
+                    style::Print("\n"),
+                    StyledText::info_fg(),
+                    style::SetAttribute(Attribute::Bold),
+                    style::Print(format!("{}Recorded as step {step} of the plan\n\n", tool_bullet())),
+                    StyledText::reset(),
+                    StyledText::reset_attributes(),
+                )?;
+                tool_results.push(ToolUseResult {
+                    tool_use_id: tool.id.clone(),
+                    content: vec![ToolUseResultBlock::Text(plan::recorded_result(step))],
+                    status: ToolResultStatus::Success,
+                });
+                continue;
+            }
 
-        for tool in &self.tool_uses {
             let tool_start = std::time::Instant::now();
+            tool_index += 1;
+            self.progress.start(Step::RunningTool {
+                index: tool_index,
+                total: tools_to_run,
+                name: tool.name.clone(),
+            });
+            self.session_summary.record_tool_use(&tool.name);
             let mut tool_telemetry = self.tool_use_telemetry_events.entry(tool.id.clone());
             tool_telemetry = tool_telemetry.and_modify(|ev| {
                 ev.is_accepted = true;
@@ -2443,15 +4053,74 @@ impl ChatSession {
                 }
             }
 
-            let invoke_result = tool
-                .tool
-                .invoke(
-                    os,
-                    &mut self.stdout,
-                    &mut self.conversation.file_line_tracker,
-                    &self.conversation.agents,
-                )
-                .await;
+            let span = tracing::info_span!(target: otel::SPAN_TARGET, "tool_execution", tool = tool.name);
+            // Status lines overwritten in place don't make sense to screen readers
+            let show_status =
+                self.interactive && !self.stderr.should_send_structured_event && !accessible::is_enabled();
+            let mut heartbeat = heartbeat::interval(&os.database.settings).map(HeartbeatTimer::new);
+            let activity = ToolActivity::default();
+            if accessible::is_enabled() {
+                execute!(
+                    self.stdout,
+                    style::Print(accessible::tool_output_marker(true, &tool.name))
+                )?;
+            }
+            let invoke_result = {
+                let mut tool_output = ToolOutput::new(&mut self.stdout, &activity);
+                let invoke = tool
+                    .tool
+                    .invoke(
+                        os,
+                        &mut tool_output,
+                        &mut self.conversation.file_line_tracker,
+                        &self.conversation.agents,
+                    )
+                    .instrument(span);
+                tokio::pin!(invoke);
+                let mut heartbeat_ticker = tokio::time::interval(Duration::from_secs(1));
+                let mut quiet_since = Instant::now();
+                loop {
+                    tokio::select! {
+                        result = &mut invoke => break result,
+                        _ = heartbeat_ticker.tick() => {
+                            let wrote = activity.take_output();
+                            if wrote {
+                                quiet_since = Instant::now();
+                            }
+                            let heartbeat_due = heartbeat.as_mut().is_some_and(|timer| {
+                                if wrote {
+                                    timer.progressed();
+                                }
+                                timer.is_due()
+                            });
+                            if !heartbeat_due {
+                                // The step in progress, once the tool stopped writing for a while
+                                if show_status && self.spinner.is_none() && quiet_since.elapsed() >= TOOL_PROGRESS_NOTICE {
+                                    activity.show_status(&mut self.stderr, &self.progress.terminal_line())?;
+                                }
+                                continue;
+                            }
+                            let phase = Phase::RunningTool { tool: tool.name.clone() };
+                            let beat = Heartbeat::new(phase, tool_start.elapsed(), None);
+                            self.stderr.send(beat.event())?;
+                            if show_status && self.spinner.is_none() {
+                                activity.show_status(&mut self.stderr, &beat.status_line())?;
+                            }
+                        },
+                    }
+                }
+            };
+            activity.clear_status(&mut self.stderr)?;
+            self.progress.finish();
+            if accessible::is_enabled() {
+                execute!(
+                    self.stdout,
+                    style::Print(accessible::tool_output_marker(false, &tool.name))
+                )?;
+            }
+            otel::record(Operation::Tool, tool_start.elapsed(), invoke_result.is_ok(), &[
+                KeyValue::new("tool", tool.name.clone()),
+            ]);
 
             if let Some(spinner) = self.spinner.take() {
                 drop(spinner);
@@ -2515,6 +4184,14 @@ impl ChatSession {
                         } else {
                             manager.tools_in_turn += 1;
 
+                            // Any tool writing files counts for the conflicts of the task board, not only fs_write
+                            match manager.changed_files(&tool_tag) {
+                                Ok(paths) => paths
+                                    .iter()
+                                    .for_each(|path| tools::delegate::record_touched_file(os, path)),
+                                Err(e) => debug!("Failed to list the files changed by the tool: {}", e),
+                            }
+
                             // Also update/create the turn checkpoint to point to latest state
                             // This is important so that we create turn-checkpoints even when tools are aborted
                             let turn_tag = format!("{}", manager.current_turn + 1);
@@ -2546,6 +4223,11 @@ impl ChatSession {
 
             let tool_end_time = Instant::now();
             let tool_time = tool_end_time.duration_since(tool_start);
+            let mcp_server = match &tool.tool {
+                Tool::Custom(ct) => Some(ct.server_name.as_str()),
+                _ => None,
+            };
+            self.perf.record_tool(&tool.name, mcp_server, tool_time);
             tool_telemetry = tool_telemetry.and_modify(|ev| {
                 ev.execution_duration = Some(tool_time);
                 ev.turn_duration = self.tool_turn_start_time.map(|t| tool_end_time.duration_since(t));
@@ -2580,11 +4262,11 @@ impl ChatSession {
                     debug!("tool result output: {:#?}", result);
                     execute!(
                         self.stdout,
-                        style::Print(CONTINUATION_LINE),
+                        style::Print(continuation_line()),
                         style::Print("\n"),
                         StyledText::success_fg(),
                         style::SetAttribute(Attribute::Bold),
-                        style::Print(format!(" ● Completed in {}s", tool_time)),
+                        style::Print(format!("{}Completed in {}s", tool_bullet(), tool_time)),
                         StyledText::reset(),
                     )?;
                     if let Some(tag) = checkpoint_tag {
@@ -2598,6 +4280,12 @@ impl ChatSession {
                         )?;
                     }
                     execute!(self.stdout, style::Print("\n\n"))?;
+                    if let OutputKind::Images(images) | OutputKind::Mixed { images, .. } = &result.output {
+                        if self.interactive {
+                            let protocol = image_protocol(os, &self.stdout);
+                            display_images(&mut self.stdout, images, protocol, true)?;
+                        }
+                    }
 
                     tool_telemetry = tool_telemetry.and_modify(|ev| ev.is_success = Some(true));
                     if let Tool::Custom(_) = &tool.tool {
@@ -2607,6 +4295,9 @@ impl ChatSession {
 
                     // Send telemetry for agent contribution
                     if let Tool::FsWrite(w) = &tool.tool {
+                        tools::delegate::record_touched_file(os, &w.path(os));
+                        self.session_summary
+                            .record_file_modified(w.path(os).to_string_lossy().to_string());
                         let sanitized_path_str = w.path(os).to_string_lossy().to_string();
                         let conversation_id = self.conversation.conversation_id().to_string();
                         let message_id = self.conversation.message_id().map(|s| s.to_string());
@@ -2641,11 +4332,11 @@ impl ChatSession {
                     error!(?err, "An error occurred processing the tool");
                     execute!(
                         self.stderr,
-                        style::Print(CONTINUATION_LINE),
+                        style::Print(continuation_line()),
                         style::Print("\n"),
                         style::SetAttribute(Attribute::Bold),
                         StyledText::error_fg(),
-                        style::Print(format!(" ● Execution failed after {}s:\n", tool_time)),
+                        style::Print(format!("{}Execution failed after {}s:\n", tool_bullet(), tool_time)),
                         StyledText::reset_attributes(),
                         StyledText::error_fg(),
                         style::Print(&err),
@@ -2707,6 +4398,9 @@ impl ChatSession {
                     // Exit code is 0: nothing. stdout is not shown to user. We don't support processing the PostToolUse
                     // hook output yet. Exit code is non-zero: display an error to user (already
                     // taken care of by the ContextManager.run_hooks)
+                    self.progress.start(Step::RunningHooks {
+                        trigger: crate::cli::agent::hook::HookTrigger::PostToolUse.to_string(),
+                    });
                     let _ = cm
                         .run_hooks(
                             crate::cli::agent::hook::HookTrigger::PostToolUse,
@@ -2716,10 +4410,24 @@ impl ChatSession {
                             Some(tool_context),
                         )
                         .await;
+                    self.progress.finish();
                 }
             }
         }
 
+        for result in &mut tool_results {
+            if let Some(context) = self
+                .tool_uses
+                .iter()
+                .find(|t| t.id == result.tool_use_id)
+                .and_then(|t| t.hook_context.as_ref())
+            {
+                result.content.push(ToolUseResultBlock::Text(format!(
+                    "Additional context from preToolUse hooks:\n{context}"
+                )));
+            }
+        }
+
         if !image_blocks.is_empty() {
             let images = image_blocks.into_iter().map(|(block, _)| block).collect();
             self.conversation.add_tool_results_with_images(tool_results, images);
@@ -2732,11 +4440,18 @@ impl ChatSession {
         } else {
             self.conversation.add_tool_results(tool_results);
         }
+        // Guidance sent with /agents tell when this session runs a delegated task, given as a
+        // message of the user rather than as the output of a tool
+        if let Some(guidance) = tools::delegate::mailbox::format_guidance(&tools::delegate::mailbox::receive(os).await)
+        {
+            self.conversation.add_next_message_context(&guidance);
+        }
 
         execute!(self.stderr, cursor::Hide)?;
         execute!(self.stderr, style::Print("\n"), StyledText::reset_attributes())?;
+        self.progress.start(Step::Response);
         if self.interactive {
-            self.spinner = Some(Spinner::new(Spinners::Dots, "Thinking...".to_string()));
+            self.spinner = Some(StatusIndicator::new(self.progress.terminal_line()));
         }
 
         self.send_chat_telemetry(os, TelemetryResult::Succeeded, None, None, None, false)
@@ -2762,6 +4477,7 @@ impl ChatSession {
         state: crate::api_client::model::ConversationState,
         request_metadata_lock: Arc<Mutex<Option<RequestMetadata>>>,
     ) -> Result<ChatState, ChatError> {
+        let request_start = Instant::now();
         let mut rx = self.send_message(os, state, request_metadata_lock, None).await?;
 
         let request_id = rx.request_id().map(String::from);
@@ -2783,13 +4499,19 @@ impl ChatSession {
 
         let mut state = ParseState::new(
             terminal_width,
-            os.database.settings.get_bool(Setting::ChatDisableMarkdownRendering),
+            // Answers matching a schema are printed as is to be parsed
+            self.response_schema
+                .is_some()
+                .then_some(true)
+                .or(os.database.settings.get_bool(Setting::ChatDisableMarkdownRendering)),
         );
         let mut response_prefix_printed = false;
+        let mut answer = None;
 
         let mut tool_uses = Vec::new();
         let mut tool_name_being_recvd: Option<String> = None;
 
+        self.update_status_bar(os).await?;
         if self.spinner.is_some() {
             drop(self.spinner.take());
             queue!(
@@ -2799,12 +4521,74 @@ impl ChatSession {
             )?;
         }
 
+        // Show how long the first token is taking, to tell a slow model from a hung connection
+        let show_status = self.interactive && !self.stdout.should_send_structured_event;
+        let mut waiting_for_first_event = show_status;
+        let mut received_first_event = false;
+        let mut heartbeat = heartbeat::interval(&os.database.settings).map(HeartbeatTimer::new);
+        let mut wait_ticker = tokio::time::interval(Duration::from_secs(1));
         loop {
-            match rx.recv().await {
+            let event = tokio::select! {
+                event = rx.recv() => event,
+                _ = wait_ticker.tick() => {
+                    if let Some(status_bar) = self.status_bar.as_mut() {
+                        status_bar.redraw_if_resized(&mut self.stderr)?;
+                    }
+                    if waiting_for_first_event {
+                        self.show_first_token_wait(os, request_start.elapsed())?;
+                    }
+                    if heartbeat.as_mut().is_some_and(|timer| timer.is_due()) {
+                        let phase = if received_first_event {
+                            Phase::ReceivingResponse { tool: tool_name_being_recvd.clone() }
+                        } else {
+                            Phase::WaitingForResponse
+                        };
+                        let beat = Heartbeat::new(phase, request_start.elapsed(), Some(rx.received_response_size()));
+                        self.stderr.send(beat.event())?;
+                        // The wait for the first token is already shown
+                        if show_status && received_first_event && self.spinner.is_some() {
+                            drop(self.spinner.take());
+                            queue!(
+                                self.stderr,
+                                terminal::Clear(terminal::ClearType::CurrentLine),
+                                cursor::MoveToColumn(0),
+                            )?;
+                            self.spinner = Some(StatusIndicator::new(beat.status_line()));
+                        }
+                    } else if !waiting_for_first_event {
+                        // For the elapsed times
+                        self.redraw_progress(false)?;
+                    }
+                    continue;
+                },
+            };
+            // Not an event of the response, which is yet to start again
+            if let Some(Ok(parser::ResponseEvent::Retry(notice))) = &event {
+                self.show_retry_notice(notice, None)?;
+                continue;
+            }
+            received_first_event = true;
+            if let Some(timer) = heartbeat.as_mut() {
+                timer.progressed();
+            }
+            if waiting_for_first_event {
+                waiting_for_first_event = false;
+                if self.spinner.is_some() {
+                    drop(self.spinner.take());
+                    queue!(
+                        self.stderr,
+                        terminal::Clear(terminal::ClearType::CurrentLine),
+                        cursor::MoveToColumn(0),
+                    )?;
+                }
+            }
+
+            match event {
                 Some(Ok(msg_event)) => {
                     trace!("Consumed: {:?}", msg_event);
 
                     match msg_event {
+                        parser::ResponseEvent::Retry(_) => (),
                         parser::ResponseEvent::ToolUseStart { name } => {
                             // We need to flush the buffer here, otherwise text will not be
                             // printed while we are receiving tool use events.
@@ -2847,6 +4631,7 @@ impl ChatSession {
                             }
                             tool_uses.push(tool_use);
                             tool_name_being_recvd = None;
+                            self.progress.finish();
                         },
                         parser::ResponseEvent::EndStream {
                             message,
@@ -2857,8 +4642,19 @@ impl ChatSession {
                             if message.content() == RESPONSE_TIMEOUT_CONTENT {
                                 error!(?request_id, ?message, "Encountered an unexpected model response");
                             }
+                            let char_count = self.conversation.take_sent_char_count();
+                            Self::calibrate_token_counter(os, &rm, char_count);
+                            self.record_request_cost(&rm, char_count);
+                            // The request raced against this one was sent with the same history
+                            if let Some(losing_request) = rx.take_losing_request() {
+                                self.record_request_cost(&losing_request, char_count);
+                            }
+                            self.record_context_usage(&rm, char_count);
+                            self.perf.record_request(&rm);
+                            answer = Some(message.content().to_string());
                             self.conversation.push_assistant_message(os, message, Some(rm.clone()));
                             self.user_turn_request_metadata.push(rm);
+                            self.update_status_bar(os).await?;
                             ended = true;
                         },
                     }
@@ -2893,7 +4689,7 @@ impl ChatSession {
                             );
 
                             execute!(self.stderr, cursor::Hide)?;
-                            self.spinner = Some(Spinner::new(Spinners::Dots, "Dividing up the work...".to_string()));
+                            self.spinner = Some(StatusIndicator::new("Dividing up the work...".to_string()));
 
                             // For stream timeouts, we'll tell the model to try and split its response into
                             // smaller chunks.
@@ -3029,6 +4825,7 @@ impl ChatSession {
             if ended {
                 buf.push('\n');
             }
+            state.ended = ended;
 
             if tool_name_being_recvd.is_none() && !buf.is_empty() && self.spinner.is_some() {
                 drop(self.spinner.take());
@@ -3088,23 +4885,21 @@ impl ChatSession {
             }
 
             // Set spinner after showing all of the assistant text content so far.
-            if tool_name_being_recvd.is_some() {
+            if let Some(name) = &tool_name_being_recvd {
                 queue!(self.stderr, cursor::Hide)?;
-                if self.interactive {
-                    self.spinner = Some(Spinner::new(Spinners::Dots, "Thinking...".to_string()));
+                let step = Step::ReceivingTool { name: name.clone() };
+                if !self.progress.is_current(&step) || self.spinner.is_none() {
+                    self.progress.start(step);
+                    if self.interactive {
+                        self.redraw_progress(true)?;
+                    }
                 }
             }
 
             if ended {
-                if os
-                    .database
-                    .settings
-                    .get_bool(Setting::ChatEnableNotifications)
-                    .unwrap_or(false)
-                {
-                    // For final responses (no tools suggested), always play the bell
-                    play_notification_bell(tool_uses.is_empty());
-                }
+                self.progress.finish();
+                // For final responses (no tools suggested), always play the bell
+                Notifier::from_settings(&os.database.settings).notify_response_end(tool_uses.is_empty());
 
                 if self.stderr.should_send_structured_event {
                     self.stderr.send(Event::TextMessageEnd(TextMessageEnd {
@@ -3127,10 +4922,34 @@ impl ChatSession {
                     )?;
                 }
 
+                // The paths are in the response already, so the images are only loaded when they
+                // can be shown inline
+                if let Some(protocol) = self.interactive.then(|| image_protocol(os, &self.stdout)).flatten() {
+                    let images = referenced_image_paths(&buf)
+                        .iter()
+                        .take(MAX_REFERENCED_IMAGES)
+                        .filter_map(|path| load_image_attachment(path).ok())
+                        .collect::<Vec<_>>();
+                    display_images(&mut self.stdout, &images, Some(protocol), false)?;
+                }
+
                 break;
             }
         }
 
+        if let Some(limit) = self.over_cost_limit() {
+            return self.stop_turn_over_cost_limit(os, limit, &tool_uses).await;
+        }
+        if let Some(limit) = self.run_limits.record_response(tool_uses.len()) {
+            return self.stop_session_over_run_limit(os, limit, &tool_uses).await;
+        }
+        if let Some(budget) = self.task_budget.as_mut() {
+            let tokens = self.cost.input_tokens + self.cost.output_tokens;
+            if let Some(limit) = budget.record_response(tool_uses.len(), tokens, self.cost.total_cost, Instant::now()) {
+                return self.pause_over_task_limit(os, limit, tool_uses).await;
+            }
+        }
+
         if !tool_uses.is_empty() {
             Ok(ChatState::ValidateTools { tool_uses })
         } else {
@@ -3194,6 +5013,18 @@ impl ChatSession {
             self.send_chat_telemetry(os, TelemetryResult::Succeeded, None, None, None, true)
                 .await;
 
+            if let Some(answer) = &answer {
+                if let Some(state) = self.check_response_schema(os, answer)? {
+                    return Ok(state);
+                }
+            }
+            if self.plan.has_unshown_steps() {
+                self.plan.show(&mut self.stdout)?;
+                self.stdout.flush()?;
+            }
+            self.warn_quota_threshold(os).await;
+            self.notify_turn_complete(os, answer.as_deref()).await;
+
             // Run Stop hooks when the assistant finishes responding
             if let Some(cm) = self.conversation.context_manager.as_mut() {
                 let _ = cm
@@ -3207,12 +5038,198 @@ impl ChatSession {
                     .await;
             }
 
+            self.send_run_finished("success", None)?;
+
             Ok(ChatState::PromptUser {
                 skip_printing_tools: false,
             })
         }
     }
 
+    /// Runs the `onError` hooks with the error interrupting the current turn.
+    async fn run_error_hooks(&mut self, os: &Os, err: &ChatError, reason: &str, reason_desc: &str) {
+        let Some(cm) = self.conversation.context_manager.as_mut() else {
+            return;
+        };
+        let event_input = serde_json::json!({
+            "error": {
+                "message": err.to_string(),
+                "reason": reason,
+                "reason_desc": reason_desc,
+                "status_code": err.status_code(),
+            }
+        });
+        if let Err(err) = cm
+            .run_hooks_with_input(
+                crate::cli::agent::hook::HookTrigger::OnError,
+                &mut self.stderr,
+                os,
+                None,
+                None,
+                Some(&event_input),
+            )
+            .await
+        {
+            warn!(?err, "failed to run the onError hooks");
+        }
+    }
+
+    /// Starts the `notification` hooks in the background, e.g. to forward the notification to the
+    /// desktop or to a pager while the session runs unattended.
+    fn run_notification_hooks(&self, os: &Os, notification: serde_json::Value) {
+        let Some(cm) = self.conversation.context_manager.as_ref() else {
+            return;
+        };
+        if let Err(err) =
+            cm.spawn_hooks_with_input(crate::cli::agent::hook::HookTrigger::Notification, os, &notification)
+        {
+            warn!(?err, "failed to run the notification hooks");
+        }
+    }
+
+    /// Tells the notifiers and the `notification` hooks that the current turn completed, with a
+    /// summary of it, if it lasted at least [Setting::ChatNotificationTurnSeconds].
+    async fn notify_turn_complete(&mut self, os: &Os, answer: Option<&str>) {
+        let mds = &self.user_turn_request_metadata;
+        let (Some(first), Some(last)) = (mds.first(), mds.last()) else {
+            return;
+        };
+        let duration_seconds = last
+            .stream_end_timestamp_ms
+            .saturating_sub(first.request_start_timestamp_ms)
+            / 1000;
+        let min_seconds = os
+            .database
+            .settings
+            .get_int_or(Setting::ChatNotificationTurnSeconds, DEFAULT_NOTIFICATION_TURN_SECONDS);
+        if duration_seconds < min_seconds as u64 {
+            return;
+        }
+
+        let tool_uses = mds.iter().map(|md| md.tool_use_ids_and_names.len()).sum::<usize>();
+        let message = format!("Turn completed in {duration_seconds} s with {tool_uses} tool uses");
+        Notifier::from_settings(&os.database.settings)
+            .notify(&Notification::new(NotificationKind::TurnComplete, message.clone()));
+        let notification = serde_json::json!({
+            "notification_type": NOTIFICATION_TURN_COMPLETE,
+            "message": message,
+            "duration_seconds": duration_seconds,
+            "tool_uses": tool_uses,
+            "summary": answer.map(|answer| truncate_safe(answer, NOTIFICATION_SUMMARY_MAX_LEN)),
+        });
+        self.run_notification_hooks(os, notification);
+    }
+
+    /// Warns once the usage of the monthly allowance reaches one of the configured thresholds,
+    /// rather than only when the limit is reached. The usage is fetched in the background at most
+    /// every [QUOTA_CHECK_INTERVAL], and the warning is shown at the end of the turn following the
+    /// check.
+    async fn warn_quota_threshold(&mut self, os: &Os) {
+        let thresholds = quota_warning_thresholds(&os.database);
+        let Some(highest) = thresholds.last() else {
+            return;
+        };
+        if self.quota_warned_threshold.is_some_and(|warned| warned >= *highest) {
+            return;
+        }
+
+        if let Some(check) = self.quota_check.take_if(|check| check.is_finished()) {
+            let percent = check.await.unwrap_or_else(|err| {
+                warn!(?err, "quota check failed");
+                None
+            });
+            let threshold =
+                percent.and_then(|percent| threshold_to_warn(&thresholds, percent, self.quota_warned_threshold));
+            if let (Some(percent), Some(threshold)) = (percent, threshold) {
+                self.quota_warned_threshold = Some(threshold);
+                if let Err(err) = execute!(
+                    self.stderr,
+                    StyledText::warning_fg(),
+                    style::Print(format!(
+                        "\nYou have used {percent:.0}% of your monthly allowance. Run /quota for details.\n"
+                    )),
+                    StyledText::reset(),
+                ) {
+                    warn!(?err, "failed to show the quota warning");
+                }
+            }
+        }
+
+        if self.quota_check.is_none()
+            && self
+                .quota_checked_at
+                .is_none_or(|checked_at| checked_at.elapsed() >= QUOTA_CHECK_INTERVAL)
+        {
+            let os = os.clone();
+            self.quota_checked_at = Some(Instant::now());
+            self.quota_check = Some(tokio::spawn(async move {
+                match fetch_quota(&os).await {
+                    Ok(quota) => quota.percent_used(),
+                    Err(err) => {
+                        debug!(?err, "failed to get the usage limits");
+                        None
+                    },
+                }
+            }));
+        }
+    }
+
+    /// Checks a final answer against [Self::response_schema], returning the state requesting it
+    /// again if it doesn't match and retries are left.
+    fn check_response_schema(&mut self, os: &Os, answer: &str) -> Result<Option<ChatState>, ChatError> {
+        let Some(schema) = &self.response_schema else {
+            return Ok(None);
+        };
+        let errors = match schema.validate(answer) {
+            Ok(_) => {
+                self.response_schema_retries = 0;
+                return Ok(None);
+            },
+            Err(errors) => errors,
+        };
+
+        let max_retries = os
+            .database
+            .settings
+            .get_int(Setting::ChatResponseSchemaRetries)
+            .and_then(|i| usize::try_from(i).ok())
+            .unwrap_or(DEFAULT_RESPONSE_SCHEMA_RETRIES);
+        if self.response_schema_retries < max_retries {
+            self.response_schema_retries += 1;
+            execute!(
+                self.stderr,
+                StyledText::warning_fg(),
+                style::Print(format!(
+                    "\nThe response does not match the schema, requesting it again ({}/{max_retries})\n\n",
+                    self.response_schema_retries
+                )),
+                StyledText::reset(),
+            )?;
+            return Ok(Some(ChatState::HandleInput {
+                input: response_schema::retry_prompt(&errors),
+            }));
+        }
+
+        self.response_schema_retries = 0;
+        if !self.interactive {
+            return Err(ChatError::ResponseSchemaMismatch {
+                path: schema.path().to_path_buf(),
+                errors,
+            });
+        }
+        execute!(
+            self.stderr,
+            StyledText::warning_fg(),
+            style::Print(format!(
+                "\nThe response does not match the schema at {}:\n{}\n\n",
+                schema.path().display(),
+                errors.join("\n")
+            )),
+            StyledText::reset(),
+        )?;
+        Ok(None)
+    }
+
     // Validate the tool use request from LLM, including basic checks like fs_read file should exist, as
     // well as user-defined preToolUse hook check.
     async fn validate_tools(&mut self, os: &Os, tool_uses: Vec<AssistantToolUse>) -> Result<ChatState, ChatError> {
@@ -3232,7 +5249,8 @@ impl ChatSession {
             )
             .set_tool_use_id(tool_use_id.clone())
             .set_tool_name(tool_use.name.clone())
-            .utterance_id(self.conversation.message_id().map(|s| s.to_string()));
+            .utterance_id(self.conversation.message_id().map(|s| s.to_string()))
+            .subagent_name(os.env.get(Q_SUBAGENT).ok());
             match self.conversation.tool_manager.get_tool_from_tool_use(tool_use).await {
                 Ok(mut tool) => {
                     // Apply non-Q-generated context to tools
@@ -3247,6 +5265,7 @@ impl ChatSession {
                                 tool,
                                 accepted: false,
                                 tool_input,
+                                hook_context: None,
                             });
                         },
                         Err(err) => {
@@ -3322,7 +5341,7 @@ impl ChatSession {
         // The mental model is preToolHook is like validate tools, but its behavior can be customized by
         // user Note that after preTookUse hook, user can still reject the took run
         if let Some(cm) = self.conversation.context_manager.as_mut() {
-            for tool in &queued_tools {
+            for tool in &mut queued_tools {
                 let tool_context = ToolContext {
                     tool_name: match &tool.tool {
                         Tool::Custom(custom_tool) => custom_tool.namespaced_tool_name(), // for MCP tool, pass MCP
@@ -3333,6 +5352,9 @@ impl ChatSession {
                     tool_response: None,
                 };
 
+                self.progress.start(Step::RunningHooks {
+                    trigger: crate::cli::agent::hook::HookTrigger::PreToolUse.to_string(),
+                });
                 let hook_results = cm
                     .run_hooks(
                         crate::cli::agent::hook::HookTrigger::PreToolUse,
@@ -3341,59 +5363,306 @@ impl ChatSession {
                         None, // prompt
                         Some(tool_context),
                     )
-                    .await?;
+                    .await;
+                self.progress.finish();
+                let hook_results = hook_results?;
 
                 // Here is how we handle the preToolUse hook output:
-                // Exit code is 0: nothing. stdout is not shown to user.
+                // Exit code is 0: stdout is not shown to user, unless it is a HookJsonOutput. Its
+                // "block" decision is handled like exit code 2, its reason is shown to the user
+                // otherwise and its additional context is added to the tool result.
                 // Exit code is 2: block the tool use. return stderr to LLM. show warning to user
-                // Other error: show warning to user.
+                // Other error: show warning to user, and block the tool use if the agent's hook
+                // policy requires all hooks to pass.
+
+                // Check for blocking hooks and add to tool_results
+                let all_must_pass = cm.hook_policy.aggregation == HookAggregation::AllMustPass;
+                let mut contexts = Vec::new();
+                for ((_, hook), (exit_code, output)) in &hook_results {
+                    let json = match *exit_code {
+                        0 => HookJsonOutput::parse(output),
+                        _ => None,
+                    };
+                    let blocked_reason = match &json {
+                        _ if *exit_code == 2 => Some(output.clone()),
+                        _ if *exit_code != 0 && all_must_pass => Some(format!(
+                            "\"{}\" failed and all hooks must pass: {}",
+                            hook.command,
+                            output.trim()
+                        )),
+                        Some(json) if json.is_block() => Some(json.reason.clone().unwrap_or_default()),
+                        _ => None,
+                    };
+                    if let Some(reason) = blocked_reason {
+                        tool_results.push(ToolUseResult {
+                            tool_use_id: tool.id.clone(),
+                            content: vec![ToolUseResultBlock::Text(format!(
+                                "PreToolHook blocked the tool execution: {}",
+                                reason
+                            ))],
+                            status: ToolResultStatus::Error,
+                        });
+                        // A single result per tool use, even if several hooks block it
+                        break;
+                    }
+
+                    let Some(json) = json else { continue };
+                    if let Some(reason) = json.reason.filter(|r| !r.trim().is_empty()) {
+                        queue!(
+                            self.stderr,
+                            StyledText::info_fg(),
+                            style::Print(format!(
+                                "ℹ {} \"{}\": ",
+                                crate::cli::agent::hook::HookTrigger::PreToolUse,
+                                hook.command
+                            )),
+                            StyledText::reset(),
+                            style::Print(format!("{}\n", reason.trim())),
+                        )?;
+                    }
+                    contexts.extend(json.additional_context.filter(|c| !c.trim().is_empty()));
+                }
+                tool.hook_context = (!contexts.is_empty()).then(|| contexts.join("\n"));
+            }
+        }
+
+        // If we have any hook validation errors, return them immediately to the model
+        if !tool_results.is_empty() {
+            debug!(?tool_results, "Error found in PreToolUse hooks");
+            for tool_result in &tool_results {
+                for block in &tool_result.content {
+                    if let ToolUseResultBlock::Text(content) = block {
+                        queue!(
+                            self.stderr,
+                            style::Print("\n"),
+                            StyledText::error_fg(),
+                            style::Print(format!("{}\n", content)),
+                            StyledText::reset(),
+                        )?;
+                    }
+                }
+            }
+
+            self.conversation.add_tool_results(tool_results);
+            return Ok(ChatState::HandleResponseStream(
+                self.conversation
+                    .as_sendable_conversation_state(os, &mut self.stderr, false)
+                    .await?,
+            ));
+        }
+
+        self.tool_uses = queued_tools;
+        self.pending_tool_index = Some(0);
+        self.tool_turn_start_time = Some(Instant::now());
+
+        Ok(ChatState::ExecuteTools)
+    }
+
+    /// Shows the progress of the turn in the spinner again, to update its elapsed times, or to
+    /// show a new step if `show` even if no spinner was shown.
+    fn redraw_progress(&mut self, show: bool) -> Result<(), ChatError> {
+        if !self.interactive {
+            return Ok(());
+        }
+        let line = self.progress.terminal_line();
+        match &mut self.spinner {
+            // Each redraw would be another line in accessible mode, so the line is only printed
+            // again when shown
+            Some(spinner) if !show || !accessible::is_enabled() => spinner.set_text(line),
+            Some(_) => self.spinner = Some(StatusIndicator::new(line)),
+            None if show => self.spinner = Some(StatusIndicator::new(line)),
+            None => (),
+        }
+        Ok(())
+    }
+
+    /// Shows the notice of a request sent again after a transient failure, see
+    /// [parser::ResponseEvent::Retry], in place of the spinner, which shows `status` again if any.
+    fn show_retry_notice(&mut self, notice: &str, status: Option<String>) -> Result<(), ChatError> {
+        let had_spinner = self.spinner.take().is_some();
+        if had_spinner {
+            queue!(
+                self.stderr,
+                terminal::Clear(terminal::ClearType::CurrentLine),
+                cursor::MoveToColumn(0),
+            )?;
+        }
+        execute!(
+            self.stderr,
+            StyledText::warning_fg(),
+            style::Print("WARNING: "),
+            StyledText::reset(),
+            style::Print(format!("{notice}\n")),
+        )?;
+        if let Some(status) = status.filter(|_| had_spinner) {
+            self.spinner = Some(StatusIndicator::new(status));
+        }
+        Ok(())
+    }
+
+    /// Replaces the spinner with how long the response has been waited for, once it's noticeable.
+    fn show_first_token_wait(&mut self, os: &Os, waited: Duration) -> Result<(), ChatError> {
+        // Shown once in accessible mode, rather than a line per second
+        if waited < FIRST_TOKEN_WAIT_NOTICE || (accessible::is_enabled() && self.spinner.is_some()) {
+            return Ok(());
+        }
+        if self.spinner.is_some() {
+            drop(self.spinner.take());
+            queue!(
+                self.stderr,
+                terminal::Clear(terminal::ClearType::CurrentLine),
+                cursor::MoveToColumn(0),
+            )?;
+        }
+        self.spinner = Some(StatusIndicator::new(format!(
+            "Waiting {}s for the first token (gives up after {}s without a response)...",
+            waited.as_secs(),
+            os.client.stream_timeouts().stall.as_secs()
+        )));
+        Ok(())
+    }
+
+    /// Queues the prompt of the request that just failed because the backend couldn't be reached,
+    /// so that it can be sent once the connection is back.
+    fn queue_unsent_prompt(&mut self) -> Result<(), ChatError> {
+        let prompt = match self.conversation.next_user_message().map(|m| m.content()) {
+            Some(UserMessageContent::Prompt { prompt }) => Some(prompt.clone()),
+            _ => None,
+        };
+        let queued = prompt.is_some();
+        self.offline_queue.request_failed(prompt);
+
+        execute!(
+            self.stderr,
+            StyledText::warning_fg(),
+            style::Print("Unable to reach the service, you appear to be offline. "),
+            style::Print(if queued {
+                format!(
+                    "The prompt was queued ({} queued) and will be offered for sending once the connection is back.\n\n",
+                    self.offline_queue.len()
+                )
+            } else {
+                "The next prompts will be queued until the connection is back.\n\n".to_string()
+            }),
+            StyledText::reset(),
+        )?;
+        Ok(())
+    }
+
+    /// Returns the next prompt queued while offline to send, once the backend can be reached
+    /// again and the user agreed to send them.
+    async fn next_queued_prompt(&mut self, os: &Os) -> Result<Option<ChatState>, ChatError> {
+        if self.offline_queue.is_empty() || (self.offline_queue.is_offline() && !offline_queue::probe(os).await) {
+            return Ok(None);
+        }
+        self.offline_queue.reconnected();
+
+        if !self.offline_queue.is_sending() {
+            execute!(
+                self.stderr,
+                StyledText::success_fg(),
+                style::Print(format!(
+                    "\nThe connection is back. {} prompts were queued while offline:\n",
+                    self.offline_queue.len()
+                )),
+                StyledText::reset(),
+            )?;
+            for prompt in self.offline_queue.prompts() {
+                queue!(
+                    self.stderr,
+                    style::Print(format!("  • {}\n", truncate_safe(prompt, 80)))
+                )?;
+            }
+            execute!(
+                self.stderr,
+                StyledText::secondary_fg(),
+                style::Print("\nSend them in order? ["),
+                StyledText::success_fg(),
+                style::Print("y"),
+                StyledText::secondary_fg(),
+                style::Print("/"),
+                StyledText::success_fg(),
+                style::Print("n"),
+                StyledText::secondary_fg(),
+                style::Print("]:\n\n"),
+                StyledText::reset(),
+            )?;
 
-                // Check for exit code 2 and add to tool_results
-                for (_, (exit_code, output)) in &hook_results {
-                    if *exit_code == 2 {
-                        tool_results.push(ToolUseResult {
-                            tool_use_id: tool.id.clone(),
-                            content: vec![ToolUseResultBlock::Text(format!(
-                                "PreToolHook blocked the tool execution: {}",
-                                output
-                            ))],
-                            status: ToolResultStatus::Error,
-                        });
-                    }
-                }
+            let user_input = self
+                .read_user_input(&StyledText::warning("> "), true)
+                .unwrap_or_default();
+            if !["y", "Y"].contains(&user_input.trim()) {
+                self.offline_queue.clear();
+                execute!(
+                    self.stderr,
+                    StyledText::secondary_fg(),
+                    style::Print("\nDiscarded the queued prompts.\n\n"),
+                    StyledText::reset(),
+                )?;
+                return Ok(None);
             }
+            self.offline_queue.start_sending();
         }
 
-        // If we have any hook validation errors, return them immediately to the model
-        if !tool_results.is_empty() {
-            debug!(?tool_results, "Error found in PreToolUse hooks");
-            for tool_result in &tool_results {
-                for block in &tool_result.content {
-                    if let ToolUseResultBlock::Text(content) = block {
-                        queue!(
-                            self.stderr,
-                            style::Print("\n"),
-                            StyledText::error_fg(),
-                            style::Print(format!("{}\n", content)),
-                            StyledText::reset(),
-                        )?;
-                    }
-                }
-            }
+        let Some(prompt) = self.offline_queue.next_to_send() else {
+            return Ok(None);
+        };
+        execute!(
+            self.stderr,
+            StyledText::secondary_fg(),
+            style::Print(format!("\n> {prompt}\n")),
+            StyledText::reset(),
+        )?;
+        self.conversation.append_user_transcript(&prompt);
+        Ok(Some(ChatState::HandleInput { input: prompt }))
+    }
 
-            self.conversation.add_tool_results(tool_results);
-            return Ok(ChatState::HandleResponseStream(
-                self.conversation
-                    .as_sendable_conversation_state(os, &mut self.stderr, false)
-                    .await?,
-            ));
+    /// Switches to the next model of the active agent's `modelFallbacks` and retries the request
+    /// with it. Returns [None] if there is no fallback model left to try.
+    async fn retry_with_fallback_model(&mut self, os: &mut Os, reason: &str) -> Result<Option<ChatState>, ChatError> {
+        let Some(agent) = self.conversation.agents.get_active() else {
+            return Ok(None);
+        };
+        if agent.model_fallbacks.is_empty() {
+            return Ok(None);
         }
+        let (models, _) = get_available_models(os).await?;
+        let Some(next) = next_fallback_model(
+            &models,
+            agent.model.as_deref(),
+            &agent.model_fallbacks,
+            self.conversation.model_id(),
+        )
+        .cloned() else {
+            return Ok(None);
+        };
 
-        self.tool_uses = queued_tools;
-        self.pending_tool_index = Some(0);
-        self.tool_turn_start_time = Some(Instant::now());
+        let current = self
+            .conversation
+            .model_info
+            .as_ref()
+            .map_or("The current model", |m| m.display_name())
+            .to_string();
+        let message = format!("{current} is {reason}, switching to {}", next.display_name());
+        info!(model_id = next.model_id, "{message}");
+        self.conversation.append_transcript(message.clone());
+        execute!(
+            self.stderr,
+            StyledText::warning_fg(),
+            style::Print(format!("\n{message}...\n\n")),
+            StyledText::reset(),
+        )?;
+        self.conversation.model_info = Some(next);
 
-        Ok(ChatState::ExecuteTools)
+        self.progress.start(Step::Response);
+        if self.interactive {
+            self.spinner = Some(StatusIndicator::new(self.progress.terminal_line()));
+        }
+        Ok(Some(ChatState::HandleResponseStream(
+            self.conversation
+                .as_sendable_conversation_state(os, &mut self.stderr, true)
+                .await?,
+        )))
     }
 
     async fn retry_model_overload(&mut self, os: &mut Os) -> Result<ChatState, ChatError> {
@@ -3413,8 +5682,9 @@ impl ChatSession {
             Err(err) => return Err(err),
         }
 
+        self.progress.start(Step::Response);
         if self.interactive {
-            self.spinner = Some(Spinner::new(Spinners::Dots, "Thinking...".to_owned()));
+            self.spinner = Some(StatusIndicator::new(self.progress.terminal_line()));
         }
 
         Ok(ChatState::HandleResponseStream(
@@ -3469,9 +5739,14 @@ impl ChatSession {
                 self.stdout,
                 StyledText::emphasis_fg(),
                 style::Print(format!(
-                    "🛠️  Using tool: {}{}",
+                    "{}Using tool: {}{}",
+                    accessible::symbol("🛠️  ", ""),
                     tool_use.tool.display_name(),
-                    if trusted { " (trusted)".dark_green() } else { "".reset() }
+                    if trusted {
+                        StyledText::success(" (trusted)")
+                    } else {
+                        String::new()
+                    }
                 )),
                 StyledText::reset(),
             )?;
@@ -3489,15 +5764,15 @@ impl ChatSession {
             execute!(
                 self.stdout,
                 style::Print("\n"),
-                style::Print(CONTINUATION_LINE),
+                style::Print(continuation_line()),
                 style::Print("\n"),
-                style::Print(TOOL_BULLET)
+                style::Print(tool_bullet())
             )?;
         }
 
         tool_use
             .tool
-            .queue_description(os, &mut self.stdout)
+            .queue_description(os, self.conversation.agents.get_active(), &mut self.stdout)
             .await
             .map_err(|e| ChatError::Custom(format!("failed to print tool, `{}`: {}", tool_use.name, e).into()))?;
 
@@ -3525,7 +5800,7 @@ impl ChatSession {
                         self.stderr,
                         style::Print(format!(
                             "\n(To exit the CLI, press Ctrl+C or Ctrl+D again or type {})\n\n",
-                            "/quit".green()
+                            StyledText::command("/quit")
                         ))
                     )
                     .unwrap_or_default();
@@ -3551,17 +5826,33 @@ impl ChatSession {
             None
         };
 
+        let cost = (os.database.settings.get_bool(Setting::ChatShowCost).unwrap_or(false) && self.cost.is_priced())
+            .then_some(self.cost.total_cost);
+
         let mut generated_prompt =
-            prompt::generate_prompt(profile.as_deref(), all_trusted, tangent_mode, usage_percentage);
+            prompt::generate_prompt(profile.as_deref(), all_trusted, tangent_mode, usage_percentage, cost);
 
         if ExperimentManager::is_enabled(os, ExperimentName::Delegate) {
+            if let Ok(questions) = pending_questions(os).await {
+                let new_questions = questions
+                    .into_iter()
+                    .filter(|(id, question)| self.shown_task_questions.insert((id.clone(), question.sent_at)))
+                    .collect::<Vec<_>>();
+                let _ = execute!(self.stderr, style::Print(format_question_notification(&new_questions)));
+            }
             if let Ok(mut executions) = status_all_agents(os).await {
                 if !executions.is_empty() {
-                    let rich_notification = format_rich_notification(&executions);
-                    generated_prompt = format!("{}\n{}", rich_notification, generated_prompt);
-
-                    // Use the notification text as context for the model (it's already plain text)
-                    self.pending_additional_context = Some(rich_notification.clone());
+                    generated_prompt = format!("{}\n{}", format_task_notification(&executions), generated_prompt);
+                    let review = format_review_notification(&executions).await;
+                    let _ = execute!(self.stderr, style::Print(review));
+                    let plural = if executions.len() == 1 { "" } else { "s" };
+                    Notifier::from_settings(&os.database.settings).notify(&Notification::new(
+                        NotificationKind::DelegateFinished,
+                        format!("{} background task{plural} finished", executions.len()),
+                    ));
+
+                    // Give the summaries to the model, the user can read them in /agents
+                    self.pending_additional_context = Some(format_rich_notification(&executions));
 
                     // Mark all shown tasks as user_notified
                     for execution in &mut executions {
@@ -3589,6 +5880,45 @@ impl ChatSession {
         }
     }
 
+    /// Shows the agent, model, context usage, background agents and cost in the status bar, if
+    /// enabled.
+    async fn update_status_bar(&mut self, os: &Os) -> Result<(), ChatError> {
+        if self.status_bar.is_none() {
+            return Ok(());
+        }
+
+        let background_agents = if ExperimentManager::is_enabled(os, ExperimentName::Delegate) {
+            load_board(os)
+                .await
+                .unwrap_or_default()
+                .iter()
+                .filter(|execution| {
+                    matches!(
+                        execution.status,
+                        AgentStatus::Queued | AgentStatus::Running | AgentStatus::Paused
+                    )
+                })
+                .count()
+        } else {
+            0
+        };
+        let status = Status {
+            agent: self.conversation.current_profile().map(str::to_string),
+            model: self
+                .conversation
+                .model_info
+                .as_ref()
+                .map(|model| model.model_name.clone().unwrap_or_else(|| model.model_id.clone())),
+            context_percentage: self.context_usage,
+            background_agents,
+            cost: self.cost.is_priced().then_some(self.cost.total_cost),
+        };
+        if let Some(status_bar) = self.status_bar.as_mut() {
+            status_bar.update(&mut self.stderr, status)?;
+        }
+        Ok(())
+    }
+
     fn terminal_width(&self) -> usize {
         (self.terminal_width_provider)().unwrap_or(80)
     }
@@ -3624,6 +5954,91 @@ impl ChatSession {
         Ok(())
     }
 
+    /// Replaces the older history with the summary created in the background, if it is ready.
+    async fn apply_background_compaction(&mut self, os: &Os) -> Result<(), ChatError> {
+        if !self.background_compaction.as_ref().is_some_and(|c| c.is_finished()) {
+            return Ok(());
+        }
+        let Some(compaction) = self.background_compaction.take() else {
+            return Ok(());
+        };
+
+        let chars_before = *self.conversation.calculate_char_count(os).await?;
+        if let Some((summarized, duration)) = compaction.apply(&mut self.conversation).await {
+            let record = self
+                .record_compaction(os, CompactionTrigger::Background, duration, chars_before, summarized)
+                .await?;
+            execute!(
+                self.stderr,
+                StyledText::secondary_fg(),
+                style::Print(format!(
+                    "Summarized the {summarized} oldest messages in the background to free up context space: {record}\n"
+                )),
+                StyledText::reset(),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Replaces the `!{command}` of a prompt with the output of the commands. Commands the
+    /// execute_bash tool would ask about need the user's approval, and are not run without it.
+    async fn expand_inline_commands(&mut self, os: &Os, prompt: String) -> Result<String, ChatError> {
+        let commands = inline_commands::commands(&prompt);
+        if commands.is_empty() {
+            return Ok(prompt);
+        }
+
+        let mut outputs = Vec::new();
+        for command in commands {
+            let output = match inline_commands::permission(os, &self.conversation.agents, &command) {
+                PermissionEvalResult::Deny(rules) => {
+                    execute!(
+                        self.stderr,
+                        StyledText::warning_fg(),
+                        style::Print(format!(
+                            "Not running `{command}` as it is denied: {}\n",
+                            rules.join(", ")
+                        )),
+                        StyledText::reset(),
+                    )?;
+                    format!("(`{command}` was not run as it is denied)")
+                },
+                PermissionEvalResult::Ask
+                    if !self.interactive
+                        || !self.input_source.is_terminal()
+                        || !crate::util::input(&format!("Run `{command}`? (y/n)"), None)
+                            .is_ok_and(|answer| ["y", "Y"].contains(&answer.trim())) =>
+                {
+                    format!("(`{command}` was not run as it was not approved)")
+                },
+                PermissionEvalResult::Allow | PermissionEvalResult::Ask => {
+                    execute!(
+                        self.stderr,
+                        StyledText::secondary_fg(),
+                        style::Print(format!("Running `{command}`\n")),
+                        StyledText::reset(),
+                    )?;
+                    inline_commands::run(os, &command).await
+                },
+            };
+            outputs.push((command, output));
+        }
+
+        Ok(inline_commands::substitute(&prompt, &outputs))
+    }
+
+    /// Replaces the terminal as the view of the session, e.g. to serve it over a socket. `view` is
+    /// given the structured events of the session, and must acknowledge the `prompt_user` timing
+    /// events before the session reads the next input.
+    fn set_view(&mut self, view: impl FnOnce(ViewEnd, std::sync::mpsc::Sender<()>) + Send + 'static) {
+        let (view_end, _byte_receiver, stderr, stdout) = get_legacy_conduits(true);
+        let (prompt_ack_tx, prompt_ack_rx) = std::sync::mpsc::channel::<()>();
+        tokio::task::spawn_blocking(move || view(view_end, prompt_ack_tx));
+        self.stdout = stdout;
+        self.stderr = stderr;
+        self.prompt_ack_rx = prompt_ack_rx;
+    }
+
     /// Resets state associated with the active user turn.
     ///
     /// This should *always* be called whenever a new user prompt is sent to the backend. Note
@@ -3631,6 +6046,8 @@ impl ChatSession {
     fn reset_user_turn(&mut self) {
         info!(?self.user_turn_request_metadata, "Resetting the current user turn");
         self.user_turn_request_metadata.clear();
+        self.cost.start_turn();
+        self.progress.start_turn();
     }
 
     /// Sends an "codewhispererterminal_addChatMessage" telemetry event.
@@ -3875,7 +6292,7 @@ where
     Fut: std::future::Future<Output = Result<T, E>>,
 {
     queue!(output, cursor::Hide,).ok();
-    let spinner = Spinner::new(Spinners::Dots, spinner_text.to_owned());
+    let spinner = StatusIndicator::new(spinner_text.to_owned());
 
     let result = f().await;
 
@@ -3908,6 +6325,31 @@ fn does_input_reference_file(input: &str) -> Option<ChatState> {
     None
 }
 
+/// Parses a tool denial of the form `n <reason>` or `n: <reason>`, returning the reason if one
+/// was given.
+///
+/// A bare `n` returns [None] so that the generic denial message is used instead.
+fn parse_denial_reason(input: &str) -> Option<String> {
+    let input = input.trim();
+    let rest = input.strip_prefix(['n', 'N'])?;
+    if !rest.starts_with([' ', '\t', ':']) {
+        return None;
+    }
+
+    let reason = rest.trim_start_matches(':').trim();
+    (!reason.is_empty()).then(|| reason.to_string())
+}
+
+/// Whether `message` is a short prompt without images, for which the latency of the response
+/// matters more than the cost of requesting it twice. Tool results are never raced, so that
+/// tool uses aren't requested by two models.
+fn is_raceable(message: &UserMessage) -> bool {
+    let UserMessageContent::Prompt { prompt } = &message.content else {
+        return false;
+    };
+    message.images.as_ref().is_none_or(|images| images.is_empty()) && prompt.chars().count() <= RACE_MAX_PROMPT_CHARS
+}
+
 // Helper method to save the agent config to file
 async fn save_agent_config(os: &mut Os, config: &Agent, agent_name: &str, is_global: bool) -> Result<(), ChatError> {
     let resolver = PathResolver::new(os);
@@ -3938,6 +6380,34 @@ async fn save_agent_config(os: &mut Os, config: &Agent, agent_name: &str, is_glo
     Ok(())
 }
 
+/// The protocol images are shown inline with, if the terminal supports one.
+fn image_protocol(os: &Os, stdout: &ControlEnd<DestinationStdout>) -> Option<ImageProtocol> {
+    match !stdout.should_send_structured_event && std::io::stdout().is_terminal() {
+        true => ImageProtocol::load(&os.database.settings, &os.env),
+        false => None,
+    }
+}
+
+/// Shows `images` inline with `protocol`, or else their paths if `paths_as_fallback`.
+fn display_images(
+    stdout: &mut ControlEnd<DestinationStdout>,
+    images: &[RichImageBlock],
+    protocol: Option<ImageProtocol>,
+    paths_as_fallback: bool,
+) -> Result<(), ChatError> {
+    if images.is_empty() || stdout.should_send_structured_event {
+        return Ok(());
+    }
+    if protocol.is_none() && !paths_as_fallback {
+        return Ok(());
+    }
+    for image in images {
+        image_display::display_image(stdout, image, protocol)?;
+    }
+    execute!(stdout, style::Print("\n"))?;
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use std::path::PathBuf;
@@ -4338,6 +6808,120 @@ mod tests {
         assert!(!os.fs.exists("/file2.txt"));
     }
 
+    #[tokio::test]
+    async fn test_flow_read_only() {
+        let mut os = Os::new().await.unwrap();
+        os.client.set_mock_output(serde_json::json!([
+            [
+                "Sure, I'll create a file for you",
+                {
+                    "tool_use_id": "1",
+                    "name": "fs_write",
+                    "args": {
+                        "command": "create",
+                        "file_text": "Hello, world!",
+                        "path": "/file1.txt",
+                    }
+                }
+            ],
+            [
+                "I can't create files in read-only mode.",
+            ],
+        ]));
+
+        let mut agents = get_test_agents(&os).await;
+        agents.trust_all_tools = true;
+        agents.read_only = true;
+        let tool_manager = ToolManager::default();
+        let tool_config = serde_json::from_str::<HashMap<String, ToolSpec>>(include_str!("tools/tool_index.json"))
+            .expect("Tools failed to load");
+        ChatSession::new(
+            &mut os,
+            "fake_conv_id",
+            agents,
+            None,
+            InputSource::new_mock(vec!["create a new file".to_string(), "exit".to_string()]),
+            false,
+            || Some(80),
+            tool_manager,
+            None,
+            tool_config,
+            true,
+            false,
+            None,
+        )
+        .await
+        .unwrap()
+        .spawn(&mut os)
+        .await
+        .unwrap();
+
+        assert!(!os.fs.exists("/file1.txt"));
+    }
+
+    #[tokio::test]
+    async fn test_flow_plan() {
+        for apply in [false, true] {
+            let mut os = Os::new().await.unwrap();
+            os.client.set_mock_output(serde_json::json!([
+                [
+                    "Sure, I'll create a file for you",
+                    {
+                        "tool_use_id": "1",
+                        "name": "fs_write",
+                        "args": {
+                            "command": "create",
+                            "file_text": "Hello, world!",
+                            "path": "/file1.txt",
+                        }
+                    }
+                ],
+                [
+                    "The plan creates /file1.txt.",
+                ],
+                [
+                    "The file was created.",
+                ],
+            ]));
+
+            let mut inputs = vec!["create a new file".to_string()];
+            if apply {
+                inputs.push("/plan apply".to_string());
+            }
+            inputs.push("exit".to_string());
+
+            let agents = get_test_agents(&os).await;
+            let tool_manager = ToolManager::default();
+            let tool_config = serde_json::from_str::<HashMap<String, ToolSpec>>(include_str!("tools/tool_index.json"))
+                .expect("Tools failed to load");
+            let mut session = ChatSession::new(
+                &mut os,
+                "fake_conv_id",
+                agents,
+                None,
+                InputSource::new_mock(inputs),
+                false,
+                || Some(80),
+                tool_manager,
+                None,
+                tool_config,
+                true,
+                false,
+                None,
+            )
+            .await
+            .unwrap();
+            session.plan.enabled = true;
+            session.spawn(&mut os).await.unwrap();
+
+            if apply {
+                assert_eq!(os.fs.read_to_string("/file1.txt").await.unwrap(), "Hello, world!\n");
+            } else {
+                assert!(!os.fs.exists("/file1.txt"));
+            }
+        }
+    }
+
     #[test]
     fn test_editor_content_processing() {
         // Since we no longer have template replacement, this test is simplified
@@ -4441,6 +7025,10 @@ mod tests {
             max_output_size: 1024,
             cache_ttl_seconds: 0,
             matcher: Some("fs_*".to_string()), // Match fs_read, fs_write, etc.
+            blocking: true,
+            cwd: None,
+            env: Default::default(),
+            shell: None,
             source: crate::cli::agent::hook::Source::Agent,
         }]);
 
@@ -4450,6 +7038,10 @@ mod tests {
             max_output_size: 1024,
             cache_ttl_seconds: 0,
             matcher: Some("fs_*".to_string()), // Match fs_read, fs_write, etc.
+            blocking: true,
+            cwd: None,
+            env: Default::default(),
+            shell: None,
             source: crate::cli::agent::hook::Source::Agent,
         }]);
 
@@ -4584,6 +7176,10 @@ mod tests {
             max_output_size: 1024,
             cache_ttl_seconds: 0,
             matcher: Some("fs_read".to_string()),
+            blocking: true,
+            cwd: None,
+            env: Default::default(),
+            shell: None,
             source: crate::cli::agent::hook::Source::Agent,
         }]);
 
@@ -4627,6 +7223,126 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_denial_reason() {
+        assert_eq!(parse_denial_reason("n"), None);
+        assert_eq!(parse_denial_reason("N"), None);
+        assert_eq!(parse_denial_reason("n   "), None);
+        assert_eq!(parse_denial_reason("n:"), None);
+        assert_eq!(parse_denial_reason("no thanks"), None);
+        assert_eq!(parse_denial_reason("nope"), None);
+        assert_eq!(parse_denial_reason("y"), None);
+        assert_eq!(
+            parse_denial_reason("n wrong directory"),
+            Some("wrong directory".to_string())
+        );
+        assert_eq!(
+            parse_denial_reason("N: use the src folder"),
+            Some("use the src folder".to_string())
+        );
+        assert_eq!(
+            parse_denial_reason("  n:do not touch tests  "),
+            Some("do not touch tests".to_string())
+        );
+    }
+
+    #[test]
+    fn test_is_raceable() {
+        assert!(is_raceable(&UserMessage::new_prompt(
+            "what is a monad?".to_string(),
+            None
+        )));
+        assert!(!is_raceable(&UserMessage::new_prompt(
+            "a".repeat(RACE_MAX_PROMPT_CHARS + 1),
+            None
+        )));
+        assert!(!is_raceable(&UserMessage::new_tool_use_results(vec![])));
+    }
+
+    #[test]
+    fn test_format_task_notification() {
+        let execution = |id: &str, status: tools::delegate::AgentStatus| AgentExecution {
+            id: id.to_string(),
+            status,
+            ..Default::default()
+        };
+        assert_eq!(
+            format_task_notification(&[execution("rust-1", tools::delegate::AgentStatus::Completed)]),
+            "1 background task finished: ✓ rust-1 · /agents for details"
+        );
+        assert_eq!(
+            format_task_notification(&[
+                execution("rust-1", tools::delegate::AgentStatus::Completed),
+                execution("docs-2", tools::delegate::AgentStatus::Failed),
+            ]),
+            "2 background tasks finished: ✓ rust-1, ✗ docs-2 · /agents for details"
+        );
+    }
+
+    #[test]
+    fn test_format_question_notification() {
+        let question =
+            tools::delegate::mailbox::Message::new(tools::delegate::mailbox::Sender::Task, "Can I rename the crate?");
+        assert_eq!(
+            strip_ansi_escapes::strip_str(format_question_notification(&[("rust-1".to_string(), question)])),
+            "rust-1 asks: Can I rename the crate?\n  /agents tell rust-1 <answer>\n"
+        );
+        assert_eq!(format_question_notification(&[]), "");
+    }
+
+    #[test]
+    fn test_format_interrupted_notification() {
+        let execution = AgentExecution {
+            id: "rust-1".to_string(),
+            task: "Fix the tests".to_string(),
+            status: tools::delegate::AgentStatus::Interrupted,
+            ..Default::default()
+        };
+        assert_eq!(
+            strip_ansi_escapes::strip_str(format_interrupted_notification(&[execution])),
+            "rust-1 was interrupted before it finished: Fix the tests\n  q agent resume rust-1\n"
+        );
+        assert_eq!(format_interrupted_notification(&[]), "");
+    }
+
+    #[test]
+    fn test_task_notifications_accessible() {
+        accessible::enable_for_test();
+        let execution = |id: &str, status: tools::delegate::AgentStatus| AgentExecution {
+            id: id.to_string(),
+            status,
+            ..Default::default()
+        };
+        let executions = [
+            execution("rust-1", tools::delegate::AgentStatus::Completed),
+            execution("docs-2", tools::delegate::AgentStatus::Failed),
+            execution("web-3", tools::delegate::AgentStatus::Running),
+            execution("api-4", tools::delegate::AgentStatus::Paused),
+        ];
+        assert_eq!(
+            format_task_notification(&executions[..2]),
+            "2 background tasks finished: rust-1, failed: docs-2 · /agents for details"
+        );
+        let notification = format_rich_notification(&executions);
+        for status in ["SUCCESS", "FAILED", "RUNNING", "PAUSED"] {
+            assert!(notification.contains(&format!(" · {status} · ")), "{notification}");
+        }
+        assert!(!notification.contains(['✓', '✗', '⏳', '⏸']), "{notification}");
+    }
+
+    #[test]
+    fn test_format_collapsed_notice() {
+        assert_eq!(
+            strip_ansi_escapes::strip_str(format_collapsed_notice(3)),
+            "✔ Collapsed 3 large tool outputs to free up context space\n"
+        );
+        accessible::enable_for_test();
+        assert_eq!(
+            strip_ansi_escapes::strip_str(format_collapsed_notice(3)),
+            "Collapsed 3 large tool outputs to free up context space\n"
+        );
+    }
+
     #[test]
     fn test_does_input_reference_file() {
         let tests = &[
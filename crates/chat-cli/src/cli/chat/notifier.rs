@@ -0,0 +1,332 @@
+//! Notifications of the events a user may be waiting for, sent through the notifiers selected by
+//! `chat.notifiers`:
+//! - `bell`: the terminal bell
+//! - `desktop`: a desktop notification, with `notify-send` on Linux, `osascript` on macOS and a
+//!   toast on Windows
+//! - `webhook`: a JSON POST to the URL of `chat.notificationWebhook`
+//!
+//! Without `chat.notifiers`, only the bell is used, if `chat.enableNotifications` is set: it rings
+//! at the end of every final response rather than for the turns lasting long enough.
+
+use std::time::Duration;
+
+use serde::Serialize;
+use serde_json::Value;
+use tracing::{
+    debug,
+    warn,
+};
+
+use super::cli::hooks::{
+    NOTIFICATION_APPROVAL_NEEDED,
+    NOTIFICATION_TURN_COMPLETE,
+};
+use super::util::play_notification_bell;
+use crate::database::settings::{
+    Setting,
+    Settings,
+};
+
+pub const NOTIFICATION_DELEGATE_FINISHED: &str = "delegate_finished";
+
+/// Maximum time to deliver a notification to the webhook.
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Name of the application showing the desktop notifications.
+const APP_NAME: &str = "Amazon Q";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationKind {
+    /// A tool use waits for the user's approval
+    ApprovalNeeded,
+    /// The response to the user's prompt is complete
+    TurnComplete,
+    /// Delegated tasks running in the background finished
+    DelegateFinished,
+}
+
+impl NotificationKind {
+    const ALL: [Self; 3] = [Self::ApprovalNeeded, Self::TurnComplete, Self::DelegateFinished];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::ApprovalNeeded => NOTIFICATION_APPROVAL_NEEDED,
+            Self::TurnComplete => NOTIFICATION_TURN_COMPLETE,
+            Self::DelegateFinished => NOTIFICATION_DELEGATE_FINISHED,
+        }
+    }
+
+    /// Title of the desktop notifications.
+    fn title(&self) -> &'static str {
+        match self {
+            Self::ApprovalNeeded => "Approval needed",
+            Self::TurnComplete => "Response complete",
+            Self::DelegateFinished => "Background tasks finished",
+        }
+    }
+
+    fn from_str(kind: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|k| k.as_str() == kind)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Notification {
+    #[serde(rename = "notification_type")]
+    pub kind: NotificationKind,
+    pub message: String,
+}
+
+impl Notification {
+    pub fn new(kind: NotificationKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Channel {
+    Bell,
+    Desktop,
+    Webhook(String),
+}
+
+/// Sends the notifications of the events selected in the settings.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Notifier {
+    channels: Vec<Channel>,
+    kinds: Vec<NotificationKind>,
+    /// Whether the bell rings at the end of every final response, without `chat.notifiers`
+    rings_every_response: bool,
+}
+
+impl Notifier {
+    pub fn from_settings(settings: &Settings) -> Self {
+        let webhook = settings.get_string(Setting::ChatNotificationWebhook);
+        let rings_every_response = !matches!(settings.get(Setting::ChatNotifiers), Some(Value::Array(_)))
+            && settings.get_bool(Setting::ChatEnableNotifications).unwrap_or(false);
+        let channels = match settings.get(Setting::ChatNotifiers) {
+            Some(Value::Array(names)) => names
+                .iter()
+                .filter_map(|name| match name.as_str() {
+                    Some("bell") => Some(Channel::Bell),
+                    Some("desktop") => Some(Channel::Desktop),
+                    Some("webhook") => match &webhook {
+                        Some(url) => Some(Channel::Webhook(url.clone())),
+                        None => {
+                            warn!(
+                                "The webhook notifier needs {}, ignoring it",
+                                Setting::ChatNotificationWebhook
+                            );
+                            None
+                        },
+                    },
+                    _ => {
+                        warn!(%name, "Unknown notifier of {}, ignoring it", Setting::ChatNotifiers);
+                        None
+                    },
+                })
+                .collect(),
+            _ if rings_every_response => vec![Channel::Bell],
+            _ => Vec::new(),
+        };
+        let mut kinds = match settings.get(Setting::ChatNotificationEvents) {
+            Some(Value::Array(kinds)) => kinds
+                .iter()
+                .filter_map(|kind| kind.as_str().and_then(NotificationKind::from_str))
+                .collect(),
+            _ => NotificationKind::ALL.to_vec(),
+        };
+        if rings_every_response {
+            // The bell already rang at the end of the response
+            kinds.retain(|kind| *kind != NotificationKind::TurnComplete);
+        }
+        Self {
+            channels,
+            kinds,
+            rings_every_response,
+        }
+    }
+
+    /// Rings the bell at the end of a response of the model, if it is final and the notifier is
+    /// the default one.
+    pub fn notify_response_end(&self, is_final: bool) {
+        if self.rings_every_response {
+            play_notification_bell(is_final);
+        }
+    }
+
+    /// Sends `notification` through the notifiers, unless its kind isn't notified. The desktop
+    /// notifications and the webhook are sent in the background.
+    pub fn notify(&self, notification: &Notification) {
+        if !self.kinds.contains(&notification.kind) {
+            return;
+        }
+        for channel in &self.channels {
+            match channel {
+                Channel::Bell => play_notification_bell(true),
+                Channel::Desktop => {
+                    let Some((program, args)) =
+                        desktop_command(std::env::consts::OS, notification.kind.title(), &notification.message)
+                    else {
+                        debug!("Desktop notifications aren't supported on this platform");
+                        continue;
+                    };
+                    tokio::spawn(async move {
+                        match tokio::process::Command::new(program).args(&args).output().await {
+                            Ok(output) if !output.status.success() => {
+                                warn!(program, status = %output.status, "Failed to show the desktop notification");
+                            },
+                            Err(err) => warn!(program, ?err, "Failed to show the desktop notification"),
+                            Ok(_) => (),
+                        }
+                    });
+                },
+                Channel::Webhook(url) => {
+                    let url = url.clone();
+                    let notification = notification.clone();
+                    tokio::spawn(async move {
+                        if let Err(err) = post_webhook(&url, &notification).await {
+                            warn!(?err, "Failed to send the notification to the webhook");
+                        }
+                    });
+                },
+            }
+        }
+    }
+}
+
+/// Posts `notification` to the webhook. Errors leave out the URL, which may hold a token.
+async fn post_webhook(url: &str, notification: &Notification) -> eyre::Result<()> {
+    crate::request::new_client()?
+        .post(url)
+        .timeout(WEBHOOK_TIMEOUT)
+        .json(notification)
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .map_err(reqwest::Error::without_url)?;
+    Ok(())
+}
+
+/// The program and arguments showing a desktop notification on `os`, as in
+/// [std::env::consts::OS].
+fn desktop_command(os: &str, title: &str, message: &str) -> Option<(&'static str, Vec<String>)> {
+    match os {
+        "linux" | "freebsd" | "openbsd" | "netbsd" => Some(("notify-send", vec![
+            "--app-name".to_string(),
+            APP_NAME.to_string(),
+            title.to_string(),
+            message.to_string(),
+        ])),
+        "macos" => {
+            let quote = |s: &str| format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""));
+            Some(("osascript", vec![
+                "-e".to_string(),
+                format!("display notification {} with title {}", quote(message), quote(title)),
+            ]))
+        },
+        "windows" => {
+            let quote = |s: &str| format!("'{}'", s.replace('\'', "''"));
+            let script = format!(
+                "[Windows.UI.Notifications.ToastNotificationManager, Windows.UI.Notifications, ContentType = WindowsRuntime] > $null; \
+                 $toast = [Windows.UI.Notifications.ToastNotificationManager]::GetTemplateContent([Windows.UI.Notifications.ToastTemplateType]::ToastText02); \
+                 $texts = $toast.GetElementsByTagName('text'); \
+                 $texts.Item(0).AppendChild($toast.CreateTextNode({})) > $null; \
+                 $texts.Item(1).AppendChild($toast.CreateTextNode({})) > $null; \
+                 [Windows.UI.Notifications.ToastNotificationManager]::CreateToastNotifier({}).Show([Windows.UI.Notifications.ToastNotification]::new($toast))",
+                quote(title),
+                quote(message),
+                quote(APP_NAME),
+            );
+            Some(("powershell", vec![
+                "-NoProfile".to_string(),
+                "-NonInteractive".to_string(),
+                "-Command".to_string(),
+                script,
+            ]))
+        },
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_from_settings() {
+        let mut settings = Settings::new().await.unwrap();
+        assert_eq!(Notifier::from_settings(&settings), Notifier {
+            channels: vec![],
+            kinds: NotificationKind::ALL.to_vec(),
+            rings_every_response: false,
+        });
+
+        settings.set(Setting::ChatEnableNotifications, true).await.unwrap();
+        assert_eq!(Notifier::from_settings(&settings), Notifier {
+            channels: vec![Channel::Bell],
+            kinds: vec![NotificationKind::ApprovalNeeded, NotificationKind::DelegateFinished],
+            rings_every_response: true,
+        });
+
+        settings
+            .set(
+                Setting::ChatNotifiers,
+                serde_json::json!(["desktop", "webhook", "pager"]),
+            )
+            .await
+            .unwrap();
+        settings
+            .set(Setting::ChatNotificationEvents, serde_json::json!(["approval_needed"]))
+            .await
+            .unwrap();
+        // The webhook needs its URL
+        assert_eq!(Notifier::from_settings(&settings), Notifier {
+            channels: vec![Channel::Desktop],
+            kinds: vec![NotificationKind::ApprovalNeeded],
+            rings_every_response: false,
+        });
+
+        settings
+            .set(Setting::ChatNotificationWebhook, "https://example.com/hook")
+            .await
+            .unwrap();
+        assert_eq!(Notifier::from_settings(&settings).channels, vec![
+            Channel::Desktop,
+            Channel::Webhook("https://example.com/hook".to_string())
+        ]);
+    }
+
+    #[test]
+    fn test_payload() {
+        let notification = Notification::new(NotificationKind::DelegateFinished, "2 background tasks finished");
+        assert_eq!(
+            serde_json::to_value(&notification).unwrap(),
+            serde_json::json!({
+                "notification_type": "delegate_finished",
+                "message": "2 background tasks finished",
+            })
+        );
+    }
+
+    #[test]
+    fn test_desktop_command() {
+        let (program, args) = desktop_command("linux", "Title", "Done").unwrap();
+        assert_eq!(program, "notify-send");
+        assert_eq!(args, vec!["--app-name", APP_NAME, "Title", "Done"]);
+
+        let (program, args) = desktop_command("macos", "Title", "Say \"hi\"").unwrap();
+        assert_eq!(program, "osascript");
+        assert_eq!(args[1], "display notification \"Say \\\"hi\\\"\" with title \"Title\"");
+
+        let (program, args) = desktop_command("windows", "Title", "It's done").unwrap();
+        assert_eq!(program, "powershell");
+        assert!(args[3].contains("CreateTextNode('It''s done')"));
+
+        assert!(desktop_command("wasi", "Title", "Done").is_none());
+    }
+}
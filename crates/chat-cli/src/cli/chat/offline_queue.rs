@@ -0,0 +1,142 @@
+//! Prompts composed while the backend can't be reached, kept to be sent in order once the
+//! connection is back rather than failing each of them.
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use tracing::debug;
+
+use crate::api_client::Endpoint;
+use crate::os::Os;
+use crate::request::new_client;
+
+/// How long the health probe waits for the backend to respond.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+#[derive(Debug, Default)]
+pub struct OfflineQueue {
+    /// Whether the last request failed because the backend couldn't be reached.
+    offline: bool,
+    prompts: VecDeque<String>,
+    /// Whether the user agreed to send the queued prompts, which are then sent one per turn.
+    sending: bool,
+}
+
+impl OfflineQueue {
+    pub fn is_offline(&self) -> bool {
+        self.offline
+    }
+
+    pub fn len(&self) -> usize {
+        self.prompts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.prompts.is_empty()
+    }
+
+    pub fn is_sending(&self) -> bool {
+        self.sending
+    }
+
+    pub fn prompts(&self) -> impl Iterator<Item = &str> {
+        self.prompts.iter().map(String::as_str)
+    }
+
+    /// Queues a prompt composed while offline.
+    pub fn push(&mut self, prompt: String) {
+        self.prompts.push_back(prompt);
+    }
+
+    /// Marks the backend unreachable after a request failed, queuing its `prompt` if any.
+    ///
+    /// A prompt failing while the queue is being sent is the oldest one, so it's put back first.
+    pub fn request_failed(&mut self, prompt: Option<String>) {
+        self.offline = true;
+        if let Some(prompt) = prompt {
+            if self.sending {
+                self.prompts.push_front(prompt);
+            } else {
+                self.prompts.push_back(prompt);
+            }
+        }
+        self.sending = false;
+    }
+
+    pub fn reconnected(&mut self) {
+        self.offline = false;
+    }
+
+    /// Starts sending the queued prompts, once the user agreed to.
+    pub fn start_sending(&mut self) {
+        self.sending = !self.prompts.is_empty();
+    }
+
+    /// Returns the next prompt to send, if the queue is being sent.
+    pub fn next_to_send(&mut self) -> Option<String> {
+        if !self.sending {
+            return None;
+        }
+        let prompt = self.prompts.pop_front();
+        self.sending = !self.prompts.is_empty();
+        prompt
+    }
+
+    pub fn clear(&mut self) {
+        self.prompts.clear();
+        self.sending = false;
+    }
+}
+
+/// Checks whether the backend is reachable again with a HEAD request to its endpoint. Any response
+/// counts, since the probe isn't authenticated.
+pub async fn probe(os: &Os) -> bool {
+    let client = match new_client() {
+        Ok(client) => client,
+        Err(err) => {
+            debug!(?err, "failed to create the client of the health probe");
+            return false;
+        },
+    };
+    let endpoint = Endpoint::configured_value(&os.database);
+    match client.head(endpoint.url()).timeout(PROBE_TIMEOUT).send().await {
+        Ok(_) => true,
+        Err(err) => {
+            debug!(?err, "the backend is still unreachable");
+            false
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_offline_queue_order() {
+        let mut queue = OfflineQueue::default();
+        queue.request_failed(Some("first".to_string()));
+        assert!(queue.is_offline());
+        queue.push("second".to_string());
+        queue.push("third".to_string());
+
+        queue.reconnected();
+        assert!(!queue.is_offline());
+        assert_eq!(queue.next_to_send(), None);
+        queue.start_sending();
+        assert_eq!(queue.next_to_send().as_deref(), Some("first"));
+
+        // The connection dropped again while sending "second"
+        let second = queue.next_to_send();
+        queue.request_failed(second);
+        assert!(!queue.is_sending());
+        assert_eq!(queue.prompts().collect::<Vec<_>>(), vec!["second", "third"]);
+
+        queue.reconnected();
+        queue.start_sending();
+        assert_eq!(queue.next_to_send().as_deref(), Some("second"));
+        assert_eq!(queue.next_to_send().as_deref(), Some("third"));
+        assert!(!queue.is_sending());
+        assert!(queue.is_empty());
+    }
+}
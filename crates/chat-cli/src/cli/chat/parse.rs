@@ -23,6 +23,7 @@ use winnow::ascii::{
 use winnow::combinator::{
     alt,
     delimited,
+    opt,
     preceded,
     repeat,
     terminated,
@@ -30,6 +31,7 @@ use winnow::combinator::{
 use winnow::error::{
     ErrMode,
     ErrorKind,
+    Needed,
     ParserError,
 };
 use winnow::prelude::*;
@@ -44,10 +46,14 @@ use winnow::token::{
     take_while,
 };
 
+use super::accessible;
 use crate::theme::StyledText;
 
 const DEFAULT_RULE_WIDTH: usize = 40;
 
+/// Narrowest width table columns are shrunk to, to fit the terminal.
+const MIN_COLUMN_WIDTH: usize = 3;
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error<'a> {
     #[error(transparent)]
@@ -87,6 +93,9 @@ pub struct ParseState {
     pub set_newline: bool,
     pub newline: bool,
     pub citations: Vec<(String, String)>,
+    /// Whether the whole input was received, ending the blocks that wait for the line after them,
+    /// like tables.
+    pub ended: bool,
 }
 
 impl ParseState {
@@ -103,6 +112,7 @@ impl ParseState {
             set_newline: false,
             newline: true,
             citations: vec![],
+            ended: false,
         }
     }
 }
@@ -146,6 +156,7 @@ pub fn interpret_markdown<'a, 'b>(
                 text,
                 // multiline patterns
                 blockquote,
+                table,
                 // linted_codeblock,
                 codeblock_begin,
                 // single line patterns
@@ -239,10 +250,27 @@ fn bulleted_item<'a, 'b>(
         }
 
         let ws = (space0, alt(("-", "*")), space1).parse_next(i)?.0;
-        let print = format!("{ws}• ");
-
-        queue_newline_or_advance(&mut o, state, print.width())?;
-        queue(&mut o, style::Print(print))
+        let task = opt(terminated(alt(("[ ]", "[x]", "[X]")), space1)).parse_next(i)?;
+
+        match task {
+            Some(checkbox) => {
+                let (checkbox, color) = match checkbox {
+                    "[ ]" => (accessible::symbol("☐", "[ ]"), StyledText::secondary_fg()),
+                    _ => (accessible::symbol("☑", "[x]"), StyledText::success_fg()),
+                };
+                queue_newline_or_advance(&mut o, state, ws.width() + checkbox.width() + 1)?;
+                queue(&mut o, style::Print(ws))?;
+                queue(&mut o, color)?;
+                queue(&mut o, style::Print(checkbox))?;
+                queue(&mut o, StyledText::reset())?;
+                queue(&mut o, style::Print(' '))
+            },
+            None => {
+                let print = format!("{ws}• ");
+                queue_newline_or_advance(&mut o, state, print.width())?;
+                queue(&mut o, style::Print(print))
+            },
+        }
     }
 }
 
@@ -282,7 +310,10 @@ fn horizontal_rule<'a, 'b>(
         state.set_newline = true;
 
         let rule_width = state.terminal_width.unwrap_or(DEFAULT_RULE_WIDTH);
-        queue(&mut o, style::Print(format!("{}\n", "━".repeat(rule_width))))
+        queue(
+            &mut o,
+            style::Print(format!("{}\n", accessible::symbol("━", "-").repeat(rule_width))),
+        )
     }
 }
 
@@ -314,7 +345,7 @@ fn blockquote<'a, 'b>(
         let level = repeat::<_, _, Vec<&'_ str>, _, _>(1.., terminated(">", space0))
             .parse_next(i)?
             .len();
-        let print = "│ ".repeat(level);
+        let print = accessible::symbol("│ ", "> ").repeat(level);
 
         queue(&mut o, StyledText::secondary_fg())?;
         queue_newline_or_advance(&mut o, state, print.width())?;
@@ -322,6 +353,238 @@ fn blockquote<'a, 'b>(
     }
 }
 
+/// A GitHub-style table, rendered once all its rows are received.
+fn table<'a, 'b>(
+    mut o: impl Write + 'b,
+    state: &'b mut ParseState,
+) -> impl FnMut(&mut Partial<&'a str>) -> PResult<(), Error<'a>> + 'b {
+    move |i| {
+        if !state.newline {
+            return Err(ErrMode::from_error_kind(i, ErrorKind::Fail));
+        }
+
+        let input: &'a str = **i;
+        let mut rows = Vec::new();
+        let mut consumed = 0;
+        loop {
+            let rest = &input[consumed..];
+            let Some(end) = rest.find('\n') else {
+                // The line after the table tells it ended, unless it couldn't be a row anyway
+                let may_be_row = is_table_row(rest) || (!rows.is_empty() && rest.trim_start().is_empty());
+                if may_be_row && !state.ended {
+                    return Err(ErrMode::Incomplete(Needed::Unknown));
+                }
+                break;
+            };
+            let line = rest[..end].trim_end_matches('\r');
+            if !is_table_row(line) || (rows.len() == 1 && !is_delimiter_row(line)) {
+                break;
+            }
+            rows.push(line);
+            consumed += end + 1;
+        }
+        if rows.len() < 2 {
+            return Err(ErrMode::from_error_kind(i, ErrorKind::Fail));
+        }
+        i.next_slice(consumed);
+
+        let alignments = table_cells(rows[1])
+            .iter()
+            .map(|cell| Alignment::from_delimiter(cell))
+            .collect();
+        let rows = [&rows[..1], &rows[2..]]
+            .concat()
+            .into_iter()
+            .map(|row| table_cells(row).iter().map(|cell| inline_text(cell)).collect())
+            .collect();
+        queue_table(&mut o, state.terminal_width, &Table::new(rows, alignments))?;
+
+        state.column = 0;
+        state.set_newline = true;
+        Ok(())
+    }
+}
+
+fn is_table_row(line: &str) -> bool {
+    line.trim_start().starts_with('|')
+}
+
+/// E.g. `|:---|:---:|---:|`.
+fn is_delimiter_row(line: &str) -> bool {
+    let cells = table_cells(line);
+    !cells.is_empty()
+        && cells.iter().all(|cell| {
+            let dashes = cell.trim_start_matches(':').trim_end_matches(':');
+            !dashes.is_empty() && dashes.chars().all(|c| c == '-')
+        })
+}
+
+/// Splits a row into its trimmed cells, leaving escaped pipes in cells.
+fn table_cells(row: &str) -> Vec<String> {
+    let row = row.trim();
+    let row = row.strip_prefix('|').unwrap_or(row);
+    let row = row.strip_suffix('|').filter(|row| !row.ends_with('\\')).unwrap_or(row);
+
+    let mut cells = vec![String::new()];
+    let mut chars = row.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&'|') => {
+                cells.last_mut().unwrap().push('|');
+                chars.next();
+            },
+            '|' => cells.push(String::new()),
+            c => cells.last_mut().unwrap().push(c),
+        }
+    }
+    cells.iter().map(|cell| cell.trim().to_string()).collect()
+}
+
+/// The text of a cell without its inline markdown, which would throw the columns off.
+fn inline_text(cell: &str) -> String {
+    cell.replace("**", "")
+        .replace("__", "")
+        .replace('`', "")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&amp;", "&")
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Alignment {
+    Left,
+    Center,
+    Right,
+}
+
+impl Alignment {
+    fn from_delimiter(cell: &str) -> Self {
+        match (cell.starts_with(':'), cell.ends_with(':')) {
+            (true, true) => Self::Center,
+            (false, true) => Self::Right,
+            _ => Self::Left,
+        }
+    }
+
+    fn pad(self, text: &str, width: usize) -> String {
+        let padding = width.saturating_sub(text.width());
+        match self {
+            Self::Left => format!("{text}{}", " ".repeat(padding)),
+            Self::Right => format!("{}{text}", " ".repeat(padding)),
+            Self::Center => format!("{}{text}{}", " ".repeat(padding / 2), " ".repeat(padding - padding / 2)),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Table {
+    /// The header then the body, all with as many cells as there are columns
+    rows: Vec<Vec<String>>,
+    alignments: Vec<Alignment>,
+}
+
+impl Table {
+    fn new(mut rows: Vec<Vec<String>>, mut alignments: Vec<Alignment>) -> Self {
+        let columns = rows.iter().map(Vec::len).max().unwrap_or(0).max(alignments.len());
+        for row in &mut rows {
+            row.resize(columns, String::new());
+        }
+        alignments.resize(columns, Alignment::Left);
+        Self { rows, alignments }
+    }
+
+    /// Widths of the columns, shrinking the widest ones to fit `terminal_width` if any.
+    fn column_widths(&self, terminal_width: Option<usize>) -> Vec<usize> {
+        let mut widths = vec![0; self.alignments.len()];
+        for row in &self.rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.width());
+            }
+        }
+
+        // Each column takes its content, a space on each side and a border.
+        let Some(available) = terminal_width.map(|width| width.saturating_sub(widths.len() * 3 + 1)) else {
+            return widths;
+        };
+        while widths.iter().sum::<usize>() > available {
+            let Some(widest) = widths.iter_mut().filter(|width| **width > MIN_COLUMN_WIDTH).max() else {
+                break;
+            };
+            *widest -= 1;
+        }
+        widths
+    }
+}
+
+fn queue_table<'a>(mut o: impl Write, terminal_width: Option<usize>, table: &Table) -> Result<(), ErrMode<Error<'a>>> {
+    let widths = table.column_widths(terminal_width);
+    let border = |left: &str, middle: &str, right: &str| {
+        let lines = widths
+            .iter()
+            .map(|width| accessible::symbol("─", "-").repeat(width + 2))
+            .collect::<Vec<_>>();
+        format!("{left}{}{right}\n", lines.join(middle))
+    };
+
+    queue(&mut o, StyledText::secondary_fg())?;
+    let vertical = accessible::symbol("│", "|");
+    let corner = |symbol| accessible::symbol(symbol, "+");
+    queue(&mut o, style::Print(border(corner("┌"), corner("┬"), corner("┐"))))?;
+    for (index, row) in table.rows.iter().enumerate() {
+        let cells = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| wrap_cell(cell, *width))
+            .collect::<Vec<_>>();
+        let height = cells.iter().map(Vec::len).max().unwrap_or(1);
+        for line in 0..height {
+            for ((cell, width), alignment) in cells.iter().zip(&widths).zip(&table.alignments) {
+                let text = cell.get(line).map_or("", String::as_str);
+                queue(&mut o, style::Print(format!("{vertical} ")))?;
+                queue(&mut o, StyledText::reset())?;
+                if index == 0 {
+                    queue(&mut o, style::SetAttribute(Attribute::Bold))?;
+                }
+                queue(&mut o, style::Print(alignment.pad(text, *width)))?;
+                queue(&mut o, StyledText::reset_attributes())?;
+                queue(&mut o, StyledText::secondary_fg())?;
+                queue(&mut o, style::Print(' '))?;
+            }
+            queue(&mut o, style::Print(format!("{vertical}\n")))?;
+        }
+        if index == 0 {
+            queue(&mut o, style::Print(border(corner("├"), corner("┼"), corner("┤"))))?;
+        }
+    }
+    queue(&mut o, style::Print(border(corner("└"), corner("┴"), corner("┘"))))?;
+    queue(&mut o, StyledText::reset())
+}
+
+/// Wraps `text` at whitespace to lines of at most `width` columns, breaking longer words.
+fn wrap_cell(text: &str, width: usize) -> Vec<String> {
+    let mut lines = vec![String::new()];
+    for word in text.split_whitespace() {
+        let line = lines.last_mut().unwrap();
+        if !line.is_empty() && line.width() + 1 + word.width() <= width {
+            line.push(' ');
+            line.push_str(word);
+            continue;
+        }
+        if !line.is_empty() {
+            lines.push(String::new());
+        }
+        for c in word.chars() {
+            let line = lines.last_mut().unwrap();
+            if !line.is_empty() && line.width() + c.width().unwrap_or(0) > width {
+                lines.push(String::new());
+            }
+            lines.last_mut().unwrap().push(c);
+        }
+    }
+    lines
+}
+
 fn bold<'a, 'b>(
     mut o: impl Write + 'b,
     state: &'b mut ParseState,
@@ -658,11 +921,15 @@ mod tests {
     use super::*;
 
     macro_rules! validate {
-        ($test:ident, $input:literal, [$($commands:expr),+ $(,)?], $markdown_enabled:expr) => {
+        ($test:ident, $input:literal, [$($commands:expr),+ $(,)?], $markdown_enabled:expr, $accessible:expr) => {
             #[test]
             fn $test() -> eyre::Result<()> {
                 use crossterm::ExecutableCommand;
 
+                if $accessible {
+                    accessible::enable_for_test();
+                }
+
                 let mut input = $input.trim().to_owned();
                 input.push(' ');
                 input.push(' ');
@@ -699,8 +966,12 @@ mod tests {
             }
         };
 
+        ($test:ident, $input:literal, [$($commands:expr),+ $(,)?], $markdown_enabled:expr) => {
+            validate!($test, $input, [$($commands),+], $markdown_enabled, false);
+        };
+
         ($test:ident, $input:literal, [$($commands:expr),+ $(,)?]) => {
-            validate!($test, $input, [$($commands),+], false);
+            validate!($test, $input, [$($commands),+], false, false);
         };
     }
 
@@ -759,6 +1030,57 @@ mod tests {
     validate!(bulleted_item_1, "- bullet", [style::Print("• bullet")]);
     validate!(bulleted_item_2, "* bullet", [style::Print("• bullet")]);
     validate!(numbered_item_1, "1. number", [style::Print("1. number")]);
+    validate!(task_list_item_1, "- [ ] todo", [
+        StyledText::secondary_fg(),
+        style::Print("☐"),
+        StyledText::reset(),
+        style::Print(" todo"),
+    ]);
+    validate!(task_list_item_2, "- [x] done", [
+        StyledText::success_fg(),
+        style::Print("☑"),
+        StyledText::reset(),
+        style::Print(" done"),
+    ]);
+    validate!(
+        task_list_item_accessible_1,
+        "- [ ] todo",
+        [
+            StyledText::secondary_fg(),
+            style::Print("[ ]"),
+            StyledText::reset(),
+            style::Print(" todo"),
+        ],
+        false,
+        true
+    );
+    validate!(
+        task_list_item_accessible_2,
+        "- [x] done",
+        [
+            StyledText::success_fg(),
+            style::Print("[x]"),
+            StyledText::reset(),
+            style::Print(" done"),
+        ],
+        false,
+        true
+    );
+    validate!(
+        horizontal_rule_accessible_1,
+        "---",
+        [style::Print("-".repeat(80))],
+        false,
+        true
+    );
+    validate!(bulleted_item_link_1, "- [link](url)", [
+        style::Print("• "),
+        StyledText::info_fg(),
+        style::Print("link "),
+        StyledText::secondary_fg(),
+        style::Print("url"),
+        StyledText::reset(),
+    ]);
     validate!(blockquote_1, "> hello", [
         StyledText::secondary_fg(),
         style::Print("│ hello"),
@@ -824,4 +1146,82 @@ mod tests {
         [style::Print("+ % @ . ?")],
         true
     );
+
+    /// Renders the whole `input` without styles.
+    fn render(input: &str, terminal_width: Option<usize>) -> String {
+        let mut state = ParseState::new(terminal_width, Some(false));
+        state.ended = true;
+        let mut output = vec![];
+        let mut offset = 0;
+        loop {
+            let partial = Partial::new(&input[offset..]);
+            match interpret_markdown(partial, &mut output, &mut state) {
+                Ok(parsed) => {
+                    offset += parsed.offset_from(&partial);
+                    state.newline = state.set_newline;
+                    state.set_newline = false;
+                },
+                Err(err) => match err.into_inner() {
+                    Some(err) => panic!("{err}"),
+                    None => break,
+                },
+            }
+        }
+        String::from_utf8(strip_ansi_escapes::strip(output)).unwrap()
+    }
+
+    #[test]
+    fn test_table() {
+        let table = "| Name | Age |\n|:--|--:|\n| **Bob** | 42 |\n| Alice | 7 |\n";
+        assert_eq!(
+            render(&format!("{table}after\n"), Some(80)),
+            [
+                "┌───────┬─────┐",
+                "│ Name  │ Age │",
+                "├───────┼─────┤",
+                "│ Bob   │  42 │",
+                "│ Alice │   7 │",
+                "└───────┴─────┘",
+                "after",
+                ""
+            ]
+            .join("\n")
+        );
+
+        // Without the line after the table, it waits for the rest of the table
+        let mut state = ParseState::new(Some(80), Some(false));
+        let result = interpret_markdown(Partial::new(table), &mut vec![], &mut state);
+        assert!(matches!(result, Err(ErrMode::Incomplete(_))));
+
+        // The widest columns are wrapped to fit the terminal
+        assert_eq!(
+            render(
+                "| Key | Info |\n|---|---|\n| a | the quick brown fox jumps |\n",
+                Some(20)
+            ),
+            [
+                "┌─────┬────────────┐",
+                "│ Key │ Info       │",
+                "├─────┼────────────┤",
+                "│ a   │ the quick  │",
+                "│     │ brown fox  │",
+                "│     │ jumps      │",
+                "└─────┴────────────┘",
+                ""
+            ]
+            .join("\n")
+        );
+
+        // Lines starting with a pipe but without a delimiter row aren't tables
+        assert_eq!(render("| not a table\nhello\n", Some(80)), "| not a table\nhello\n");
+    }
+
+    #[test]
+    fn test_table_cells() {
+        assert_eq!(table_cells("| a | b \\| c |"), vec!["a", "b | c"]);
+        assert_eq!(table_cells("|a|"), vec!["a"]);
+        assert!(is_delimiter_row("|:---|:-:|--:|"));
+        assert!(!is_delimiter_row("| a | --- |"));
+        assert_eq!(wrap_cell("abcdefgh ij", 3), vec!["abc", "def", "gh", "ij"]);
+    }
 }
@@ -1,4 +1,8 @@
 use std::sync::Arc;
+use std::sync::atomic::{
+    AtomicUsize,
+    Ordering,
+};
 use std::time::{
     Duration,
     Instant,
@@ -7,6 +11,7 @@ use std::time::{
 };
 
 use eyre::Result;
+use opentelemetry::KeyValue;
 use serde::{
     Deserialize,
     Serialize,
@@ -18,6 +23,7 @@ use tokio::sync::{
 };
 use tokio_util::sync::CancellationToken;
 use tracing::{
+    Instrument,
     debug,
     error,
     info,
@@ -25,22 +31,36 @@ use tracing::{
     warn,
 };
 
+use super::cost::RequestUsage;
 use super::message::{
     AssistantMessage,
     AssistantToolUse,
 };
-use crate::api_client::ApiClient;
+use super::token_counter::TokenCounter;
 use crate::api_client::error::ConverseStreamError;
 use crate::api_client::model::{
     ChatResponseStream,
     ConversationState,
 };
+use crate::api_client::retry_policy::{
+    RetryPolicy,
+    is_transient,
+};
 use crate::api_client::send_message_output::SendMessageOutput;
+use crate::api_client::stream_timeouts::StreamTimeouts;
+use crate::api_client::{
+    ApiClient,
+    ApiClientError,
+};
 use crate::telemetry::ReasonCode;
 use crate::telemetry::core::{
     ChatConversationType,
     MessageMetaTag,
 };
+use crate::telemetry::otel::{
+    self,
+    Operation,
+};
 
 /// Error from sending a SendMessage request.
 #[derive(Debug, Error)]
@@ -87,6 +107,8 @@ impl RecvError {
             RecvErrorKind::Client(e) => e.status_code(),
             RecvErrorKind::Json(_) => None,
             RecvErrorKind::StreamTimeout { .. } => None,
+            RecvErrorKind::Stalled { .. } => None,
+            RecvErrorKind::ResponseTimeout { .. } => None,
             RecvErrorKind::UnexpectedToolUseEos { .. } => None,
             RecvErrorKind::Cancelled => None,
             RecvErrorKind::ToolValidationError { .. } => None,
@@ -100,6 +122,8 @@ impl ReasonCode for RecvError {
             RecvErrorKind::Client(_) => "RecvErrorApiClient".to_string(),
             RecvErrorKind::Json(_) => "RecvErrorJson".to_string(),
             RecvErrorKind::StreamTimeout { .. } => "RecvErrorStreamTimeout".to_string(),
+            RecvErrorKind::Stalled { .. } => "RecvErrorStalled".to_string(),
+            RecvErrorKind::ResponseTimeout { .. } => "RecvErrorResponseTimeout".to_string(),
             RecvErrorKind::UnexpectedToolUseEos { .. } => "RecvErrorUnexpectedToolUseEos".to_string(),
             RecvErrorKind::Cancelled => "Interrupted".to_string(),
             RecvErrorKind::ToolValidationError { .. } => "RecvErrorToolValidation".to_string(),
@@ -151,6 +175,12 @@ pub enum RecvErrorKind {
     /// The stream processing task was cancelled
     #[error("Stream handling was cancelled")]
     Cancelled,
+    /// No event was received for longer than the stall timeout, `api.stallTimeout`.
+    #[error("No response was received for {}s", .duration.as_secs())]
+    Stalled { duration: Duration },
+    /// The response took longer than the response timeout, `api.responseTimeout`.
+    #[error("The response took longer than {}s", .timeout.as_secs())]
+    ResponseTimeout { timeout: Duration },
     /// Tool validation failed due to invalid arguments
     #[error("Tool validation failed for tool: {} with id: {}", .name, .tool_use_id)]
     ToolValidationError {
@@ -168,9 +198,18 @@ pub enum RecvErrorKind {
 pub struct SendMessageStream {
     request_id: Option<String>,
     ev_rx: mpsc::Receiver<Result<ResponseEvent, RecvError>>,
+    /// First event, received while racing another stream.
+    first_event: Option<Result<ResponseEvent, RecvError>>,
     /// Used for graceful cleanup of the stream handler task. Required for setting request metadata
     /// on drop (e.g. in the sigint case).
     cancel_token: CancellationToken,
+    /// Total size (in bytes) of the response received so far, shared with the stream handler task.
+    received_response_size: Arc<AtomicUsize>,
+    /// Id of the model the request was sent to.
+    model_id: Option<String>,
+    /// Model and response size of the request raced against this one and cancelled, see
+    /// [Self::race].
+    losing_request: Option<(Option<String>, Arc<AtomicUsize>)>,
 }
 
 impl Drop for SendMessageStream {
@@ -222,58 +261,169 @@ impl SendMessageStream {
         let start_time = Instant::now();
         let start_time_sys = SystemTime::now();
         debug!(?start_time, "sending send_message request");
-        let response = client
-            .send_message(conversation_state)
-            .await
-            .map_err(|err| SendMessageError {
-                source: err,
-                request_metadata: RequestMetadata {
-                    message_id: message_id.clone(),
-                    request_start_timestamp_ms: system_time_to_unix_ms(start_time_sys),
-                    stream_end_timestamp_ms: system_time_to_unix_ms(SystemTime::now()),
-                    model_id: model_id.clone(),
-                    user_prompt_length,
-                    message_meta_tags: message_meta_tags.clone(),
-                    // Other fields are irrelevant if we can't get a successful response
-                    ..Default::default()
-                },
-            })?;
+        let timeouts = client.stream_timeouts();
+        let retry = StreamRetry {
+            client: client.clone(),
+            conversation_state: conversation_state.clone(),
+            policy: client.retry_policy(),
+            retries: 0,
+        };
+        let span = tracing::info_span!(
+            target: otel::SPAN_TARGET,
+            "model_request",
+            message_id,
+            model = model_id.as_deref().unwrap_or_default()
+        );
+        let response = client.send_message(conversation_state).instrument(span).await;
+        otel::record(Operation::Request, start_time.elapsed(), response.is_ok(), &[
+            KeyValue::new("model", model_id.clone().unwrap_or_default()),
+        ]);
+        let response = response.map_err(|err| SendMessageError {
+            source: err,
+            request_metadata: RequestMetadata {
+                message_id: message_id.clone(),
+                request_start_timestamp_ms: system_time_to_unix_ms(start_time_sys),
+                stream_end_timestamp_ms: system_time_to_unix_ms(SystemTime::now()),
+                model_id: model_id.clone(),
+                user_prompt_length,
+                message_meta_tags: message_meta_tags.clone(),
+                // Other fields are irrelevant if we can't get a successful response
+                ..Default::default()
+            },
+        })?;
         let elapsed = start_time.elapsed();
         debug!(?elapsed, "send_message succeeded");
 
         let request_id = response.request_id().map(str::to_string);
         let (ev_tx, ev_rx) = mpsc::channel(16);
+        let mut parser = ResponseParser::new(
+            response,
+            Some(retry),
+            timeouts,
+            message_id,
+            model_id,
+            user_prompt_length,
+            message_meta_tags,
+            ev_tx,
+            start_time,
+            start_time_sys,
+            cancel_token_clone,
+            request_metadata_lock,
+        );
+        let received_response_size = Arc::clone(&parser.received_response_size);
+        let model_id = parser.model_id.clone();
         tokio::spawn(async move {
-            ResponseParser::new(
-                response,
-                message_id,
-                model_id,
-                user_prompt_length,
-                message_meta_tags,
-                ev_tx,
-                start_time,
-                start_time_sys,
-                cancel_token_clone,
-                request_metadata_lock,
-            )
-            .try_recv()
-            .await;
+            parser.try_recv().await;
         });
 
         Ok(Self {
             request_id,
             cancel_token,
             ev_rx,
+            first_event: None,
+            received_response_size,
+            model_id,
+            losing_request: None,
         })
     }
 
+    /// Sends the request to both the model of `conversation_state` and `race_model_id` at once,
+    /// returning the stream of the model responding first. The other request is cancelled.
+    ///
+    /// Both streams update `request_metadata_lock`. The losing stream does so when cancelled,
+    /// before the winning one. The usage of the losing request is given by
+    /// [Self::take_losing_request].
+    pub async fn race(
+        client: &ApiClient,
+        conversation_state: ConversationState,
+        race_model_id: String,
+        request_metadata_lock: Arc<Mutex<Option<RequestMetadata>>>,
+        message_meta_tags: Option<Vec<MessageMetaTag>>,
+    ) -> Result<Self, SendMessageError> {
+        let mut race_state = conversation_state.clone();
+        race_state.user_input_message.model_id = Some(race_model_id);
+
+        let (primary, secondary) = tokio::join!(
+            Self::send_message(
+                client,
+                conversation_state,
+                Arc::clone(&request_metadata_lock),
+                message_meta_tags.clone()
+            ),
+            Self::send_message(client, race_state, request_metadata_lock, message_meta_tags),
+        );
+        match (primary, secondary) {
+            (Ok(primary), Ok(secondary)) => Ok(Self::first_to_respond(primary, secondary).await),
+            (Ok(stream), Err(err)) | (Err(err), Ok(stream)) => {
+                warn!(?err, "one of the raced requests failed");
+                Ok(stream)
+            },
+            (Err(err), Err(_)) => Err(err),
+        }
+    }
+
+    /// Returns the stream receiving its first event first, unless that event is an error.
+    async fn first_to_respond(mut primary: Self, mut secondary: Self) -> Self {
+        let first = tokio::select! {
+            ev = primary.ev_rx.recv() => Ok(ev),
+            ev = secondary.ev_rx.recv() => Err(ev),
+        };
+        let (first_event, mut winner, loser) = match first {
+            Ok(ev) => (ev, primary, secondary),
+            Err(ev) => (ev, secondary, primary),
+        };
+        if matches!(first_event, Some(Ok(_))) {
+            debug!(request_id = ?winner.request_id, "raced request responded first");
+            winner.losing_request = Some((loser.model_id.clone(), Arc::clone(&loser.received_response_size)));
+            drop(loser);
+            winner.first_event = first_event;
+            winner
+        } else {
+            debug!(?first_event, "raced request failed, using the other one");
+            let mut loser = loser;
+            loser.losing_request = Some((winner.model_id.clone(), Arc::clone(&winner.received_response_size)));
+            drop(winner);
+            loser
+        }
+    }
+
     pub async fn recv(&mut self) -> Option<Result<ResponseEvent, RecvError>> {
+        if let Some(ev) = self.first_event.take() {
+            return Some(ev);
+        }
         self.ev_rx.recv().await
     }
 
     pub fn request_id(&self) -> Option<&str> {
         self.request_id.as_deref()
     }
+
+    /// Returns the metadata of the request raced against this one and cancelled, holding its model
+    /// and the size of the response received before the cancellation. The backend reports no
+    /// token counts for it.
+    pub fn take_losing_request(&mut self) -> Option<RequestMetadata> {
+        let (model_id, response_size) = self.losing_request.take()?;
+        Some(RequestMetadata {
+            model_id,
+            response_size: response_size.load(Ordering::Relaxed),
+            ..Default::default()
+        })
+    }
+
+    /// Total size (in bytes) of the response received so far.
+    pub fn received_response_size(&self) -> usize {
+        self.received_response_size.load(Ordering::Relaxed)
+    }
+}
+
+/// What's needed to send a request again when its response stream fails before the first event.
+#[derive(Debug)]
+struct StreamRetry {
+    client: ApiClient,
+    conversation_state: ConversationState,
+    policy: RetryPolicy,
+    /// Number of times the request was sent again.
+    retries: u32,
 }
 
 /// State associated with parsing a [ChatResponseStream] into a [Message].
@@ -286,6 +436,9 @@ impl SendMessageStream {
 struct ResponseParser {
     /// The response to consume and parse into a sequence of [ResponseEvent].
     response: SendMessageOutput,
+    /// [None] if the request can't be sent again.
+    retry: Option<StreamRetry>,
+    timeouts: StreamTimeouts,
     event_tx: mpsc::Sender<Result<ResponseEvent, RecvError>>,
 
     /// Message identifier for the assistant's response. Randomly generated on creation.
@@ -317,7 +470,11 @@ struct ResponseParser {
     /// Time immediately before sending the request, as a [SystemTime].
     request_start_time_sys: SystemTime,
     /// Total size (in bytes) of the response received so far.
-    received_response_size: usize,
+    received_response_size: Arc<AtomicUsize>,
+    /// Number of input tokens of the request, once reported.
+    input_tokens: Option<usize>,
+    /// Number of output tokens of the response, once reported.
+    output_tokens: Option<usize>,
     time_to_first_chunk: Option<Duration>,
     time_between_chunks: Vec<Duration>,
 }
@@ -326,6 +483,8 @@ impl ResponseParser {
     #[allow(clippy::too_many_arguments)]
     fn new(
         response: SendMessageOutput,
+        retry: Option<StreamRetry>,
+        timeouts: StreamTimeouts,
         message_id: String,
         model_id: Option<String>,
         user_prompt_length: usize,
@@ -338,6 +497,8 @@ impl ResponseParser {
     ) -> Self {
         Self {
             response,
+            retry,
+            timeouts,
             message_id,
             model_id,
             user_prompt_length,
@@ -350,7 +511,9 @@ impl ResponseParser {
             parsing_tool_use: None,
             request_start_time,
             request_start_time_sys,
-            received_response_size: 0,
+            received_response_size: Arc::new(AtomicUsize::new(0)),
+            input_tokens: None,
+            output_tokens: None,
             time_to_first_chunk: None,
             time_between_chunks: Vec::new(),
             request_metadata,
@@ -594,7 +757,24 @@ impl ResponseParser {
         }
         trace!("Attempting to recv next event");
         let start = std::time::Instant::now();
-        let result = self.response.recv().await;
+        let result = loop {
+            let timeout = self.timeouts.next_event_timeout(self.request_start_time);
+            match tokio::time::timeout(timeout, self.response.recv()).await {
+                Err(_) if self.timeouts.response_timed_out(self.request_start_time) => {
+                    let timeout = self.timeouts.response.unwrap_or_default();
+                    return Err(self.error(RecvErrorKind::ResponseTimeout { timeout }));
+                },
+                Err(_) => return Err(self.error(RecvErrorKind::Stalled { duration: timeout })),
+                // Nothing was received yet, so the request can be sent again
+                Ok(Err(err)) if self.time_to_first_chunk.is_none() && is_transient(&err) => {
+                    match self.resend(&err).await {
+                        Some(response) => self.response = response,
+                        None => break Err(err),
+                    }
+                },
+                Ok(result) => break result,
+            }
+        };
         let duration = std::time::Instant::now().duration_since(start);
         match result {
             Ok(ev) => {
@@ -607,10 +787,18 @@ impl ResponseParser {
                 if let Some(r) = ev.as_ref() {
                     match r {
                         ChatResponseStream::AssistantResponseEvent { content } => {
-                            self.received_response_size += content.len();
+                            self.received_response_size.fetch_add(content.len(), Ordering::Relaxed);
                         },
                         ChatResponseStream::ToolUseEvent { input, .. } => {
-                            self.received_response_size += input.as_ref().map(String::len).unwrap_or_default();
+                            self.received_response_size
+                                .fetch_add(input.as_ref().map_or(0, String::len), Ordering::Relaxed);
+                        },
+                        ChatResponseStream::MetadataEvent {
+                            input_tokens,
+                            output_tokens,
+                        } => {
+                            self.input_tokens = input_tokens.or(self.input_tokens);
+                            self.output_tokens = output_tokens.or(self.output_tokens);
                         },
                         _ => {
                             warn!(?r, "received unexpected event from the response stream");
@@ -631,6 +819,38 @@ impl ResponseParser {
         }
     }
 
+    /// Sends the request again after the response stream failed with `err` before its first
+    /// event. Returns [None] once out of attempts, or if sending the request fails.
+    async fn resend(&mut self, err: &ApiClientError) -> Option<SendMessageOutput> {
+        let retry = self.retry.as_mut()?;
+        if retry.retries + 1 >= retry.policy.max_attempts {
+            return None;
+        }
+        retry.retries += 1;
+        let delay = retry.policy.backoff(retry.retries);
+        warn!(
+            ?err,
+            retries = retry.retries,
+            ?delay,
+            "response stream failed, retrying"
+        );
+        let notice = retry.policy.retry_notice(retry.retries, delay, err);
+        let _ = self
+            .event_tx
+            .send(Ok(ResponseEvent::Retry(notice)))
+            .await
+            .map_err(|err| error!(?err, "failed to send event to channel"));
+        tokio::time::sleep(delay).await;
+
+        match retry.client.send_message(retry.conversation_state.clone()).await {
+            Ok(response) => Some(response),
+            Err(err) => {
+                error!(?err, "failed to send the request again");
+                None
+            },
+        }
+    }
+
     /// Helper to create a new [RecvError] populated with the associated request id for the stream.
     fn error(&self, source: impl Into<RecvErrorKind>) -> RecvError {
         RecvError {
@@ -645,7 +865,7 @@ impl ResponseParser {
             message_id: self.message_id.clone(),
             time_to_first_chunk: self.time_to_first_chunk,
             time_between_chunks: self.time_between_chunks.clone(),
-            response_size: self.received_response_size,
+            response_size: self.received_response_size.load(Ordering::Relaxed),
             chat_conversation_type,
             request_start_timestamp_ms: system_time_to_unix_ms(self.request_start_time_sys),
             // We always end the stream when this method is called, so just set the end timestamp
@@ -659,6 +879,8 @@ impl ResponseParser {
                 .map(|t| (t.id.clone(), t.name.clone()))
                 .collect::<_>(),
             model_id: self.model_id.clone(),
+            input_tokens: self.input_tokens,
+            output_tokens: self.output_tokens,
         }
     }
 }
@@ -669,6 +891,9 @@ pub enum ResponseEvent {
     AssistantText(String),
     /// Notification that a tool use is being received.
     ToolUseStart { name: String },
+    /// Notification that the request failed before the first event of its response, and is sent
+    /// again after a delay. Holds the notice to show the user.
+    Retry(String),
     /// A tool use requested by the assistant. This should be displayed to the user as it is
     /// received.
     ToolUse(AssistantToolUse),
@@ -710,6 +935,36 @@ pub struct RequestMetadata {
     pub model_id: Option<String>,
     /// Meta tags for the request.
     pub message_meta_tags: Vec<MessageMetaTag>,
+    /// Number of input tokens of the request, as reported by the backend.
+    pub input_tokens: Option<usize>,
+    /// Number of output tokens of the response, as reported by the backend.
+    pub output_tokens: Option<usize>,
+}
+
+impl RequestMetadata {
+    /// Tokens used by the request, estimated from the sizes of the prompt and of the response when
+    /// the backend didn't report them.
+    pub fn usage(&self) -> RequestUsage {
+        let model_id = self.model_id.as_deref();
+        let mut estimated = false;
+        let mut estimate = |chars: usize| {
+            estimated = true;
+            TokenCounter::count_model_tokens(model_id, chars)
+        };
+        let input_tokens = match self.input_tokens {
+            Some(tokens) => tokens,
+            None => estimate(self.user_prompt_length),
+        };
+        let output_tokens = match self.output_tokens {
+            Some(tokens) => tokens,
+            None => estimate(self.response_size),
+        };
+        RequestUsage {
+            input_tokens,
+            output_tokens,
+            estimated,
+        }
+    }
 }
 
 fn system_time_to_unix_ms(time: SystemTime) -> u64 {
@@ -776,6 +1031,8 @@ mod tests {
         let mock = SendMessageOutput::Mock(events);
         let mut parser = ResponseParser::new(
             mock,
+            None,
+            StreamTimeouts::default(),
             "".to_string(),
             None,
             1,
@@ -832,6 +1089,8 @@ mod tests {
         let mock = SendMessageOutput::Mock(events);
         let mut parser = ResponseParser::new(
             mock,
+            None,
+            StreamTimeouts::default(),
             "".to_string(),
             None,
             1,
@@ -0,0 +1,122 @@
+//! Latencies of the requests and of the tool uses of the session, as shown by `/perf`.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use super::parser::RequestMetadata;
+
+/// Latency from which the tools of an MCP server are highlighted as slow.
+pub const SLOW_MCP_SERVER: Duration = Duration::from_secs(5);
+
+/// Durations measured for one kind of operation.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Latencies(Vec<Duration>);
+
+impl Latencies {
+    pub fn record(&mut self, duration: Duration) {
+        self.0.push(duration);
+    }
+
+    pub fn count(&self) -> usize {
+        self.0.len()
+    }
+
+    /// The `percentile` (0 to 100) of the durations, by nearest rank.
+    pub fn percentile(&self, percentile: f64) -> Option<Duration> {
+        let mut durations = self.0.clone();
+        durations.sort_unstable();
+        let rank = (percentile / 100.0 * durations.len() as f64).ceil() as usize;
+        durations.get(rank.saturating_sub(1)).copied()
+    }
+
+    pub fn max(&self) -> Option<Duration> {
+        self.0.iter().max().copied()
+    }
+}
+
+/// Latencies of the session.
+#[derive(Debug, Clone, Default)]
+pub struct PerfStats {
+    /// From sending each request to the end of its response stream.
+    pub requests: Latencies,
+    /// From sending each request to the first chunk of its response.
+    pub first_chunks: Latencies,
+    /// Execution of each tool, by the name the model uses.
+    pub tools: BTreeMap<String, Latencies>,
+    /// Execution of the tools of each MCP server.
+    pub mcp_servers: BTreeMap<String, Latencies>,
+}
+
+impl PerfStats {
+    pub fn record_request(&mut self, metadata: &RequestMetadata) {
+        let duration = metadata
+            .stream_end_timestamp_ms
+            .saturating_sub(metadata.request_start_timestamp_ms);
+        self.requests.record(Duration::from_millis(duration));
+        if let Some(first_chunk) = metadata.time_to_first_chunk {
+            self.first_chunks.record(first_chunk);
+        }
+    }
+
+    /// Records a tool use, along with the MCP server of the tool if it isn't built in.
+    pub fn record_tool(&mut self, name: &str, mcp_server: Option<&str>, duration: Duration) {
+        self.tools.entry(name.to_string()).or_default().record(duration);
+        if let Some(server) = mcp_server {
+            self.mcp_servers.entry(server.to_string()).or_default().record(duration);
+        }
+    }
+}
+
+/// Whether the tools of an MCP server took at least [SLOW_MCP_SERVER] at the 95th percentile.
+pub fn is_slow_mcp_server(latencies: &Latencies) -> bool {
+    latencies.percentile(95.0).is_some_and(|p95| p95 >= SLOW_MCP_SERVER)
+}
+
+/// Formats a latency as milliseconds below a second, as seconds otherwise.
+pub fn format_latency(duration: Duration) -> String {
+    if duration < Duration::from_secs(1) {
+        format!("{}ms", duration.as_millis())
+    } else {
+        format!("{:.1}s", duration.as_secs_f64())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percentiles() {
+        let mut latencies = Latencies::default();
+        assert_eq!(latencies.percentile(50.0), None);
+        for ms in [40, 10, 30, 20, 1000] {
+            latencies.record(Duration::from_millis(ms));
+        }
+        assert_eq!(latencies.count(), 5);
+        assert_eq!(latencies.percentile(50.0), Some(Duration::from_millis(30)));
+        assert_eq!(latencies.percentile(95.0), Some(Duration::from_millis(1000)));
+        assert_eq!(latencies.max(), Some(Duration::from_millis(1000)));
+    }
+
+    #[test]
+    fn test_record() {
+        let mut stats = PerfStats::default();
+        stats.record_request(&RequestMetadata {
+            request_start_timestamp_ms: 1_000,
+            stream_end_timestamp_ms: 4_500,
+            time_to_first_chunk: Some(Duration::from_millis(800)),
+            ..Default::default()
+        });
+        assert_eq!(stats.requests.max(), Some(Duration::from_millis(3_500)));
+        assert_eq!(stats.first_chunks.count(), 1);
+
+        stats.record_tool("fs_read", None, Duration::from_millis(5));
+        stats.record_tool("git___status", Some("git"), Duration::from_secs(6));
+        assert_eq!(stats.tools.len(), 2);
+        assert_eq!(stats.mcp_servers.len(), 1);
+        assert!(is_slow_mcp_server(&stats.mcp_servers["git"]));
+
+        assert_eq!(format_latency(Duration::from_millis(250)), "250ms");
+        assert_eq!(format_latency(Duration::from_millis(6_040)), "6.0s");
+    }
+}
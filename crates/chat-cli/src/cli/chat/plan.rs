@@ -0,0 +1,302 @@
+//! Plan mode, set with `--plan` or `/plan on`. The session runs as usual, except that tool uses
+//! that could modify files or run commands with side effects are recorded into a plan instead of
+//! being run. The plan is shown for review once the model is done, and `/plan apply` runs it.
+
+use std::fmt;
+use std::io::Write;
+use std::path::PathBuf;
+
+use crossterm::{
+    queue,
+    style,
+};
+
+use super::tools::fs_write::FsWrite;
+use super::tools::{
+    QueuedTool,
+    Tool,
+};
+use crate::os::Os;
+use crate::theme::StyledText;
+
+/// Instructions added to every prompt while plan mode is on.
+pub const PLAN_MODE_INSTRUCTIONS: &str = "The session is in plan mode: tool uses that could modify files or run \
+    commands with side effects are not run, but recorded into a plan that the user reviews before applying it. \
+    Read-only tools run as usual. Investigate as needed, then propose every change as tool uses in the order they \
+    should run, and finish with a short summary of the plan.";
+
+/// Whether a tool use is recorded into the plan instead of being run. MCP tools are recorded as
+/// their side effects are unknown.
+pub fn is_planned(tool: &Tool) -> bool {
+    !tool.is_read_only() || matches!(tool, Tool::Custom(_))
+}
+
+/// Result given to the model for a tool use recorded as the step `step` of the plan.
+pub fn recorded_result(step: usize) -> String {
+    format!(
+        "The tool use was not run as the session is in plan mode. It was recorded as step {step} of the plan, \
+        which the user reviews before applying it. Continue as if it succeeded, without relying on its output."
+    )
+}
+
+#[derive(Debug, Default)]
+pub struct Plan {
+    /// Whether tool uses are recorded instead of being run
+    pub enabled: bool,
+    steps: Vec<PlanStep>,
+    /// Number of steps already shown to the user
+    shown: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct PlanStep {
+    pub tool: QueuedTool,
+    pub action: PlanAction,
+}
+
+/// What a step of the plan does, as shown for review
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PlanAction {
+    Command(String),
+    File { operation: &'static str, path: PathBuf },
+    Other(String),
+}
+
+impl PlanAction {
+    fn new(os: &Os, tool: &QueuedTool) -> Self {
+        match &tool.tool {
+            Tool::ExecuteCommand(execute) => Self::Command(execute.command.clone()),
+            Tool::FsWrite(fs_write) => Self::File {
+                operation: match fs_write {
+                    FsWrite::Create { .. } => "Create",
+                    FsWrite::StrReplace { .. } | FsWrite::Insert { .. } => "Edit",
+                    FsWrite::Append { .. } => "Append to",
+                },
+                path: fs_write.path(os),
+            },
+            Tool::UseAws(use_aws) => Self::Other(format!(
+                "Call aws {} {} in {}",
+                use_aws.service_name, use_aws.operation_name, use_aws.region
+            )),
+            Tool::Custom(custom) => Self::Other(format!(
+                "Call {} with {}",
+                custom.namespaced_tool_name(),
+                tool.tool_input
+            )),
+            tool => Self::Other(format!("Use {}", tool.display_name())),
+        }
+    }
+}
+
+impl fmt::Display for PlanAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlanAction::Command(command) => write!(f, "Run `{command}`"),
+            PlanAction::File { operation, path } => write!(f, "{operation} {}", path.display()),
+            PlanAction::Other(description) => write!(f, "{description}"),
+        }
+    }
+}
+
+impl Plan {
+    pub fn steps(&self) -> &[PlanStep] {
+        &self.steps
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    /// Records a tool use as the next step, returning its number.
+    pub fn record(&mut self, os: &Os, tool: &QueuedTool) -> usize {
+        self.steps.push(PlanStep {
+            tool: tool.clone(),
+            action: PlanAction::new(os, tool),
+        });
+        self.steps.len()
+    }
+
+    /// Removes the steps from the plan, to apply them.
+    pub fn take_steps(&mut self) -> Vec<PlanStep> {
+        self.shown = 0;
+        std::mem::take(&mut self.steps)
+    }
+
+    /// Puts back steps that were not applied.
+    pub fn restore_steps(&mut self, steps: impl IntoIterator<Item = PlanStep>) {
+        self.steps.extend(steps);
+    }
+
+    pub fn clear(&mut self) {
+        self.steps.clear();
+        self.shown = 0;
+    }
+
+    /// Whether steps were recorded since the plan was last shown.
+    pub fn has_unshown_steps(&self) -> bool {
+        self.steps.len() > self.shown
+    }
+
+    /// Commands run by the plan, without duplicates
+    pub fn commands(&self) -> Vec<&str> {
+        let mut commands = Vec::new();
+        for step in &self.steps {
+            if let PlanAction::Command(command) = &step.action {
+                if !commands.contains(&command.as_str()) {
+                    commands.push(command.as_str());
+                }
+            }
+        }
+        commands
+    }
+
+    /// Files modified by the plan, without duplicates
+    pub fn files(&self) -> Vec<&PathBuf> {
+        let mut files = Vec::new();
+        for step in &self.steps {
+            if let PlanAction::File { path, .. } = &step.action {
+                if !files.contains(&path) {
+                    files.push(path);
+                }
+            }
+        }
+        files
+    }
+
+    pub fn show(&mut self, output: &mut impl Write) -> Result<(), std::io::Error> {
+        self.shown = self.steps.len();
+        if self.steps.is_empty() {
+            return queue!(
+                output,
+                StyledText::secondary_fg(),
+                style::Print("\nThe plan has no steps yet.\n\n"),
+                StyledText::reset(),
+            );
+        }
+
+        queue!(
+            output,
+            StyledText::emphasis_fg(),
+            style::Print(format!(
+                "\nPlan ({} step{}):\n",
+                self.steps.len(),
+                if self.steps.len() == 1 { "" } else { "s" }
+            )),
+            StyledText::reset(),
+        )?;
+        for (i, step) in self.steps.iter().enumerate() {
+            queue!(output, style::Print(format!("  {}. {}\n", i + 1, step.action)))?;
+        }
+
+        let commands = self.commands();
+        if !commands.is_empty() {
+            queue!(
+                output,
+                StyledText::secondary_fg(),
+                style::Print("\nCommands: "),
+                StyledText::reset(),
+                style::Print(commands.iter().map(|c| format!("`{c}`")).collect::<Vec<_>>().join(", ")),
+            )?;
+        }
+        let files = self.files();
+        if !files.is_empty() {
+            queue!(
+                output,
+                StyledText::secondary_fg(),
+                style::Print("\nFiles: "),
+                StyledText::reset(),
+                style::Print(
+                    files
+                        .iter()
+                        .map(|f| f.display().to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            )?;
+        }
+        queue!(
+            output,
+            StyledText::secondary_fg(),
+            style::Print("\n\nReview the plan, then run /plan apply to run it or /plan clear to discard it.\n\n"),
+            StyledText::reset(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::chat::tools::execute::ExecuteCommand;
+    use crate::cli::chat::tools::sanitize_path_tool_arg;
+
+    fn queued(tool: Tool) -> QueuedTool {
+        QueuedTool {
+            id: "id".to_string(),
+            name: tool.display_name(),
+            accepted: false,
+            tool,
+            tool_input: serde_json::json!({}),
+            hook_context: None,
+        }
+    }
+
+    fn execute(command: &str) -> Tool {
+        Tool::ExecuteCommand(ExecuteCommand {
+            command: command.to_string(),
+            summary: None,
+        })
+    }
+
+    fn fs_write(command: &str, path: &str) -> Tool {
+        Tool::FsWrite(
+            serde_json::from_value(serde_json::json!({
+                "command": command,
+                "path": path,
+                "file_text": "hello",
+                "old_str": "a",
+                "new_str": "b",
+            }))
+            .unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_is_planned() {
+        assert!(is_planned(&execute("git commit -m 'message'")));
+        assert!(!is_planned(&execute("ls -la")));
+        assert!(is_planned(&fs_write("create", "/file.txt")));
+    }
+
+    #[tokio::test]
+    async fn test_record() {
+        let os = Os::new().await.unwrap();
+        let mut plan = Plan::default();
+        assert_eq!(plan.record(&os, &queued(execute("cargo fmt"))), 1);
+        assert_eq!(plan.record(&os, &queued(fs_write("create", "/src/lib.rs"))), 2);
+        assert_eq!(plan.record(&os, &queued(fs_write("str_replace", "/src/lib.rs"))), 3);
+        assert_eq!(plan.record(&os, &queued(execute("cargo fmt"))), 4);
+
+        let path = sanitize_path_tool_arg(&os, "/src/lib.rs");
+        let actions = plan.steps().iter().map(|s| s.action.to_string()).collect::<Vec<_>>();
+        assert_eq!(actions, vec![
+            "Run `cargo fmt`".to_string(),
+            format!("Create {}", path.display()),
+            format!("Edit {}", path.display()),
+            "Run `cargo fmt`".to_string(),
+        ]);
+        assert_eq!(plan.commands(), vec!["cargo fmt"]);
+        assert_eq!(plan.files(), vec![&path]);
+
+        assert!(plan.has_unshown_steps());
+        let mut output = Vec::new();
+        plan.show(&mut output).unwrap();
+        assert!(String::from_utf8_lossy(&output).contains("4. Run `cargo fmt`"));
+        assert!(!plan.has_unshown_steps());
+
+        let mut steps = plan.take_steps();
+        assert!(plan.is_empty());
+        plan.restore_steps(steps.drain(2..));
+        assert_eq!(plan.steps().len(), 2);
+        assert!(plan.has_unshown_steps());
+    }
+}
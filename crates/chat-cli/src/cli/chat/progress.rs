@@ -0,0 +1,250 @@
+//! Progress of the steps of a turn, shown in place of a plain "Thinking..." spinner: the step in
+//! progress with how long it and the turn have been running, followed by a compact log of the
+//! steps already finished, e.g.
+//! `Running tool 2 of 3: execute_bash (4.0s, turn 12s) │ ✓ response 2.1s · ✓ fs_read 0.3s`.
+
+use std::time::{
+    Duration,
+    Instant,
+};
+
+use unicode_width::UnicodeWidthStr;
+
+use super::accessible;
+use crate::util::format_duration;
+
+/// Separates the step in progress from the log of finished steps.
+const SEPARATOR: &str = " │ ";
+
+/// Separates the finished steps of the log.
+const LOG_SEPARATOR: &str = " · ";
+
+/// Replaces the oldest finished steps that don't fit.
+const ELLIPSIS: &str = "… ";
+
+/// Columns taken by the frame of a spinner and the space after it.
+const SPINNER_WIDTH: usize = 2;
+
+/// A step of a turn.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Step {
+    /// Sending the request and streaming the response
+    Response,
+    /// Receiving the input of the tool use `name` written by the model
+    ReceivingTool { name: String },
+    /// Running the tool `name`, the `index`th (from 1) of the `total` tools of the response
+    RunningTool { index: usize, total: usize, name: String },
+    /// Running the hooks of `trigger`, e.g. `preToolUse`
+    RunningHooks { trigger: String },
+}
+
+impl Step {
+    fn description(&self) -> String {
+        match self {
+            Self::Response => "Streaming the response".to_string(),
+            Self::ReceivingTool { name } => format!("Receiving the input of {name}"),
+            Self::RunningTool { index, total, name } => format!("Running tool {index} of {total}: {name}"),
+            Self::RunningHooks { trigger } => format!("Running the {trigger} hooks"),
+        }
+    }
+
+    /// Short name of the step in the log.
+    fn label(&self) -> String {
+        match self {
+            Self::Response => "response".to_string(),
+            Self::ReceivingTool { name } => format!("{name} input"),
+            Self::RunningTool { name, .. } => name.clone(),
+            Self::RunningHooks { trigger } => format!("{trigger} hooks"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct FinishedStep {
+    step: Step,
+    elapsed: Duration,
+}
+
+/// The steps of the current turn.
+#[derive(Debug, Default)]
+pub struct TurnProgress {
+    turn_start: Option<Instant>,
+    current: Option<(Step, Instant)>,
+    finished: Vec<FinishedStep>,
+}
+
+impl TurnProgress {
+    /// Forgets the steps of the previous turn.
+    pub fn start_turn(&mut self) {
+        *self = Self {
+            turn_start: Some(Instant::now()),
+            ..Default::default()
+        };
+    }
+
+    /// Starts `step`, finishing the step in progress. Nothing changes if `step` is in progress
+    /// already.
+    pub fn start(&mut self, step: Step) {
+        if self.is_current(&step) {
+            return;
+        }
+        self.finish();
+        let now = Instant::now();
+        self.turn_start.get_or_insert(now);
+        self.current = Some((step, now));
+    }
+
+    /// Finishes the step in progress, if any, adding it to the log.
+    pub fn finish(&mut self) {
+        if let Some((step, start)) = self.current.take() {
+            self.finished.push(FinishedStep {
+                step,
+                elapsed: start.elapsed(),
+            });
+        }
+    }
+
+    pub fn is_current(&self, step: &Step) -> bool {
+        self.current.as_ref().is_some_and(|(current, _)| current == step)
+    }
+
+    /// [Self::status_line] fitting in the terminal next to the frame of a spinner.
+    pub fn terminal_line(&self) -> String {
+        let columns = crossterm::terminal::size().map_or(80, |(columns, _)| columns as usize);
+        self.status_line(columns.saturating_sub(SPINNER_WIDTH))
+    }
+
+    /// The step in progress and the log of finished steps, fitting in `width` columns.
+    pub fn status_line(&self, width: usize) -> String {
+        let now = Instant::now();
+        self.line(
+            self.current
+                .as_ref()
+                .map(|(step, start)| (step, now.duration_since(*start))),
+            self.turn_start.map(|start| now.duration_since(start)),
+            width,
+        )
+    }
+
+    fn line(&self, current: Option<(&Step, Duration)>, turn: Option<Duration>, width: usize) -> String {
+        let mut line = match current {
+            Some((step, elapsed)) => {
+                let mut times = format_duration(elapsed);
+                if let Some(turn) = turn.filter(|turn| turn.as_secs() > elapsed.as_secs()) {
+                    times.push_str(&format!(", turn {}", format_duration(turn)));
+                }
+                format!("{} ({times})", step.description())
+            },
+            None => "Thinking...".to_string(),
+        };
+
+        let separator = accessible::symbol(SEPARATOR, ". Finished: ");
+        let log_separator = accessible::symbol(LOG_SEPARATOR, ", ");
+        let ellipsis = accessible::symbol(ELLIPSIS, "... ");
+        let tick = accessible::symbol("✓ ", "");
+
+        // The most recent finished steps that fit
+        let mut log: Vec<String> = Vec::new();
+        let mut log_width = 0;
+        let mut dropped = false;
+        for finished in self.finished.iter().rev() {
+            let entry = format!("{tick}{} {}", finished.step.label(), format_duration(finished.elapsed));
+            let entry_width = entry.width() + if log.is_empty() { 0 } else { log_separator.width() };
+            let available = width.saturating_sub(line.width() + separator.width() + ellipsis.width());
+            if log_width + entry_width > available {
+                dropped = true;
+                break;
+            }
+            log.push(entry);
+            log_width += entry_width;
+        }
+        if !log.is_empty() {
+            log.reverse();
+            line.push_str(separator);
+            if dropped {
+                line.push_str(ellipsis);
+            }
+            line.push_str(&log.join(log_separator));
+        }
+        line
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn finished(step: Step, millis: u64) -> FinishedStep {
+        FinishedStep {
+            step,
+            elapsed: Duration::from_millis(millis),
+        }
+    }
+
+    #[test]
+    fn test_line() {
+        let progress = TurnProgress {
+            finished: vec![
+                finished(Step::Response, 2100),
+                finished(
+                    Step::RunningHooks {
+                        trigger: "preToolUse".to_string(),
+                    },
+                    200,
+                ),
+                finished(
+                    Step::RunningTool {
+                        index: 1,
+                        total: 3,
+                        name: "fs_read".to_string(),
+                    },
+                    300,
+                ),
+            ],
+            ..Default::default()
+        };
+        let step = Step::RunningTool {
+            index: 2,
+            total: 3,
+            name: "execute_bash".to_string(),
+        };
+        let current = Some((&step, Duration::from_secs(4)));
+
+        assert_eq!(
+            progress.line(current, Some(Duration::from_secs(12)), 200),
+            "Running tool 2 of 3: execute_bash (4.0s, turn 12s) │ ✓ response 2.1s · ✓ preToolUse hooks 0.2s · ✓ fs_read 0.3s"
+        );
+        // The oldest finished steps are dropped first
+        assert_eq!(
+            progress.line(current, Some(Duration::from_secs(12)), 80),
+            "Running tool 2 of 3: execute_bash (4.0s, turn 12s) │ … ✓ fs_read 0.3s"
+        );
+        assert_eq!(
+            progress.line(current, Some(Duration::from_secs(4)), 20),
+            "Running tool 2 of 3: execute_bash (4.0s)"
+        );
+        assert_eq!(TurnProgress::default().line(None, None, 80), "Thinking...");
+    }
+
+    #[test]
+    fn test_steps() {
+        let mut progress = TurnProgress::default();
+        progress.start_turn();
+        progress.start(Step::Response);
+        progress.start(Step::Response);
+        assert!(progress.finished.is_empty());
+
+        let tool = Step::ReceivingTool {
+            name: "fs_write".to_string(),
+        };
+        progress.start(tool.clone());
+        assert!(progress.is_current(&tool));
+        progress.finish();
+        assert!(!progress.is_current(&tool));
+        let steps = progress.finished.iter().map(|f| f.step.clone()).collect::<Vec<_>>();
+        assert_eq!(steps, vec![Step::Response, tool]);
+
+        progress.start_turn();
+        assert!(progress.finished.is_empty());
+    }
+}
@@ -1,6 +1,11 @@
 use std::borrow::Cow;
 use std::cell::RefCell;
 use std::path::PathBuf;
+use std::sync::atomic::{
+    AtomicBool,
+    AtomicUsize,
+    Ordering,
+};
 use std::sync::{
     Arc,
     Mutex,
@@ -37,15 +42,25 @@ use rustyline::{
     Editor,
     EventHandler,
     Helper,
-    Hinter,
     KeyCode,
     KeyEvent,
     Modifiers,
 };
 use winnow::stream::AsChar;
 
+use super::command_completion::{
+    CommandCompleter,
+    CommandCompletion,
+    DynamicArgument,
+};
+use super::consts::{
+    MAX_NUMBER_OF_IMAGES_PER_REQUEST,
+    MAX_USER_MESSAGE_SIZE,
+};
+use super::prompt_history::PromptHistorySearch;
 pub use super::prompt_parser::generate_prompt;
 use super::prompt_parser::parse_prompt_components;
+use super::token_counter::TokenCounter;
 use super::tool_manager::{
     PromptQuery,
     PromptQueryResult,
@@ -63,12 +78,12 @@ use crate::os::Os;
 use crate::util::paths::PathResolver;
 
 /// Shared state for clipboard paste operations triggered by Ctrl+V
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct PasteState {
     inner: Arc<Mutex<PasteStateInner>>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 struct PasteStateInner {
     paths: Vec<PathBuf>,
 }
@@ -95,6 +110,10 @@ impl PasteState {
         let mut inner = self.inner.lock().unwrap();
         inner.paths.clear();
     }
+
+    pub fn count(&self) -> usize {
+        self.inner.lock().unwrap().paths.len()
+    }
 }
 
 pub const COMMANDS: &[&str] = &[
@@ -109,8 +128,14 @@ pub const COMMANDS: &[&str] = &[
     "/tools untrust",
     "/tools trust-all",
     "/tools reset",
+    "/permissions",
+    "/permissions list",
+    "/permissions use",
+    "/permissions reset",
+    "/permissions edit",
     "/mcp",
     "/model",
+    "/model params",
     "/experiment",
     "/agent",
     "/agent help",
@@ -127,8 +152,16 @@ pub const COMMANDS: &[&str] = &[
     "/context show",
     "/context show --expand",
     "/context add",
+    "/context add-url",
+    "/context add-symbol",
+    "/context auto-evict",
     "/context rm",
     "/context clear",
+    "/context export",
+    "/context import",
+    "/context budget",
+    "/context budget set",
+    "/context budget reset",
     "/hooks",
     "/hooks help",
     "/hooks add",
@@ -139,11 +172,27 @@ pub const COMMANDS: &[&str] = &[
     "/hooks disable-all",
     "/compact",
     "/compact help",
+    "/compact history",
+    "/compact stats",
+    "/compact expand",
+    "/important",
+    "/schema",
+    "/schema set",
+    "/schema clear",
+    "/plan",
+    "/plan on",
+    "/plan off",
+    "/plan apply",
+    "/plan clear",
     "/usage",
+    "/quota",
+    "/perf",
     "/changelog",
     "/save",
     "/load",
     "/paste",
+    "/attach",
+    "/attach --clear",
     "/subscribe",
 ];
 
@@ -270,7 +319,12 @@ impl PromptCompleter {
 pub struct ChatCompleter {
     path_completer: PathCompleter,
     prompt_completer: PromptCompleter,
+    command_completer: CommandCompleter,
     available_commands: Vec<&'static str>,
+    /// Names of the tools of the session, completed after `/tools trust`
+    tool_names: Vec<String>,
+    /// Names of the custom commands, see [super::custom_commands]
+    custom_commands: Vec<String>,
 }
 
 impl ChatCompleter {
@@ -282,7 +336,34 @@ impl ChatCompleter {
         Self {
             path_completer: PathCompleter::new(),
             prompt_completer: PromptCompleter::new(sender, receiver),
+            command_completer: CommandCompleter::new(),
             available_commands,
+            tool_names: Vec::new(),
+            custom_commands: Vec::new(),
+        }
+    }
+
+    /// Completes the arguments of the slash command of `line`, [None] for free-form arguments.
+    fn complete_command_arguments(&self, line: &str, word: &str, start: usize) -> Option<Vec<String>> {
+        let preceding = line[..start].split_whitespace().collect::<Vec<_>>();
+        match self.command_completer.complete(&preceding, word)? {
+            CommandCompletion::Words(words) => Some(words),
+            CommandCompletion::Dynamic(DynamicArgument::ToolName) => Some(
+                self.tool_names
+                    .iter()
+                    .filter(|name| name.starts_with(word))
+                    .cloned()
+                    .collect(),
+            ),
+            CommandCompletion::Dynamic(DynamicArgument::PromptName) => {
+                let completions = self.prompt_completer.complete_prompt(word).ok()?;
+                Some(
+                    completions
+                        .into_iter()
+                        .map(|name| name.strip_prefix('@').map(str::to_string).unwrap_or(name))
+                        .collect(),
+                )
+            },
         }
     }
 }
@@ -299,15 +380,32 @@ impl Completer for ChatCompleter {
         let (start, word) = extract_word(line, pos, None, |c| c.is_space());
 
         // Handle command completion
-        if word.starts_with('/') {
-            return Ok(complete_command(self.available_commands.clone(), word, start));
+        if word.starts_with('/') && start == 0 {
+            let (start, mut completions) = complete_command(self.available_commands.clone(), word, start);
+            completions.extend(
+                self.custom_commands
+                    .iter()
+                    .map(|name| format!("/{name}"))
+                    .filter(|command| {
+                        command.starts_with(word) && !self.available_commands.contains(&command.as_str())
+                    }),
+            );
+            return Ok((start, completions));
+        }
+
+        // Handle the subcommands, flags and arguments of commands
+        if line.starts_with('/') {
+            if let Some(completions) = self.complete_command_arguments(line, word, start) {
+                if !completions.is_empty() {
+                    return Ok((start, completions));
+                }
+            }
         }
 
-        if line.starts_with('@') {
-            let search_word = line.strip_prefix('@').unwrap_or("");
+        if let Some(search_word) = word.strip_prefix('@').filter(|_| start == 0) {
             if let Ok(completions) = self.prompt_completer.complete_prompt(search_word) {
                 if !completions.is_empty() {
-                    return Ok((0, completions));
+                    return Ok((start, completions));
                 }
             }
         }
@@ -395,6 +493,122 @@ impl RustylineHinter for ChatHinter {
     }
 }
 
+/// Shown at the end of the input in block mode.
+const BLOCK_INPUT_NOTICE: &str = "  (block mode: Enter for a new line, Alt+Enter to send)";
+
+/// The block input mode, where Enter inserts new lines and Alt+Enter sends the prompt. Alt+Enter
+/// enters it, and so does pasting several lines, so that they aren't sent by accident before
+/// being reviewed. It ends with the prompt.
+#[derive(Debug, Default)]
+pub struct BlockInput {
+    active: AtomicBool,
+    /// Length and number of new lines of the input when last seen, to tell pastes from typing.
+    seen_len: AtomicUsize,
+    seen_newlines: AtomicUsize,
+}
+
+impl BlockInput {
+    pub fn is_active(&self) -> bool {
+        self.active.load(Ordering::Relaxed)
+    }
+
+    /// Ends block mode, for reading the next prompt.
+    pub fn reset(&self) {
+        self.active.store(false, Ordering::Relaxed);
+        self.seen_len.store(0, Ordering::Relaxed);
+        self.seen_newlines.store(0, Ordering::Relaxed);
+    }
+
+    /// Enters block mode when the input gained several characters including new lines at once,
+    /// other than by recalling a prompt of the history.
+    fn observe(&self, line: &str, ctx: &Context<'_>) {
+        let newlines = line.matches('\n').count();
+        let seen_len = self.seen_len.swap(line.len(), Ordering::Relaxed);
+        let seen_newlines = self.seen_newlines.swap(newlines, Ordering::Relaxed);
+        let recalled = ctx.history_index() < ctx.history().len();
+        if line.len() > seen_len + 1 && newlines > seen_newlines && !recalled {
+            self.active.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Handler of Alt+Enter, entering block mode with a new line or sending the prompt in block mode.
+struct BlockInputHandler {
+    block_input: Arc<BlockInput>,
+}
+
+impl rustyline::ConditionalEventHandler for BlockInputHandler {
+    fn handle(
+        &self,
+        _evt: &rustyline::Event,
+        _n: rustyline::RepeatCount,
+        _positive: bool,
+        _ctx: &rustyline::EventContext<'_>,
+    ) -> Option<Cmd> {
+        if self.block_input.active.swap(true, Ordering::Relaxed) {
+            // Sends the prompt without validating it
+            Some(Cmd::AcceptLine)
+        } else {
+            Some(Cmd::Insert(1, "\n".to_string()))
+        }
+    }
+}
+
+/// Estimated tokens of an image attached to a prompt, about the most an image is resized to.
+const IMAGE_TOKENS: usize = 1_600;
+
+/// Starts the hints warning about a prompt too large to be sent as is, shown in the warning color.
+const INPUT_TOKENS_WARNING: &str = "  ! ";
+
+/// The estimated tokens of the prompt being written, with its attached images, shown after the
+/// input.
+#[derive(Debug, Default)]
+pub struct InputTokens {
+    /// Whether the count is shown, the warnings are shown regardless
+    show: bool,
+    /// Images pasted with Ctrl+V into the prompt
+    paste_state: PasteState,
+    /// Images attached to the prompt with /attach
+    attached_images: usize,
+}
+
+impl InputTokens {
+    fn hint(&self, line: &str) -> Option<String> {
+        input_tokens_hint(line, self.paste_state.count() + self.attached_images, self.show)
+    }
+}
+
+/// The estimated tokens of the prompt `line` with `images` attached images, or a warning when the
+/// prompt exceeds what a single message can hold, past which it gets truncated or images are
+/// dropped. Commands aren't sent to the model as is, so they aren't counted.
+fn input_tokens_hint(line: &str, images: usize, show: bool) -> Option<String> {
+    let text = line.trim();
+    if text.is_empty() || text.starts_with(['/', '!', '@']) {
+        return None;
+    }
+
+    let tokens = TokenCounter::count_tokens(text) + images * IMAGE_TOKENS;
+    let with_images = match images {
+        0 => String::new(),
+        1 => " with 1 image".to_string(),
+        n => format!(" with {n} images"),
+    };
+    if text.len() > MAX_USER_MESSAGE_SIZE {
+        Some(format!(
+            "{INPUT_TOKENS_WARNING}~{tokens} tokens{with_images}, over the ~{} tokens of a message: it will be truncated",
+            TokenCounter::count_tokens_char_count(MAX_USER_MESSAGE_SIZE)
+        ))
+    } else if images > MAX_NUMBER_OF_IMAGES_PER_REQUEST {
+        Some(format!(
+            "{INPUT_TOKENS_WARNING}~{tokens} tokens{with_images}, over the {MAX_NUMBER_OF_IMAGES_PER_REQUEST} images of a message: the extra ones will be dropped"
+        ))
+    } else if show && tokens > 0 {
+        Some(format!("  ~{tokens} tokens{with_images}"))
+    } else {
+        None
+    }
+}
+
 /// Custom validator for multi-line input
 pub struct MultiLineValidator;
 
@@ -422,29 +636,95 @@ impl Validator for MultiLineValidator {
     }
 }
 
-#[derive(Helper, Completer, Hinter)]
+/// A hint of [ChatHelper], completing the input or telling about the input mode.
+pub enum ChatHint {
+    Completion(String),
+    Notice(String),
+}
+
+impl rustyline::hint::Hint for ChatHint {
+    fn display(&self) -> &str {
+        match self {
+            Self::Completion(completion) => completion,
+            Self::Notice(notice) => notice,
+        }
+    }
+
+    fn completion(&self) -> Option<&str> {
+        match self {
+            Self::Completion(completion) => Some(completion),
+            Self::Notice(_) => None,
+        }
+    }
+}
+
+#[derive(Helper, Completer)]
 pub struct ChatHelper {
     #[rustyline(Completer)]
     completer: ChatCompleter,
-    #[rustyline(Hinter)]
     hinter: ChatHinter,
     validator: MultiLineValidator,
+    block_input: Arc<BlockInput>,
+    input_tokens: InputTokens,
 }
 
 impl ChatHelper {
     pub fn get_history_path(&self) -> PathBuf {
         self.hinter.get_history_path()
     }
+
+    /// Sets the names of the tools completed after `/tools trust`.
+    pub fn set_tool_names(&mut self, tool_names: Vec<String>) {
+        self.completer.tool_names = tool_names;
+    }
+
+    /// Sets the names of the custom commands completed after `/`.
+    pub fn set_custom_commands(&mut self, names: Vec<String>) {
+        self.completer.custom_commands = names;
+    }
+
+    /// Sets the number of images attached to the next prompt, counted in its tokens.
+    pub fn set_attached_images(&mut self, count: usize) {
+        self.input_tokens.attached_images = count;
+    }
+
+    pub fn block_input(&self) -> &BlockInput {
+        &self.block_input
+    }
+}
+
+impl RustylineHinter for ChatHelper {
+    type Hint = ChatHint;
+
+    fn hint(&self, line: &str, pos: usize, ctx: &Context<'_>) -> Option<Self::Hint> {
+        // Hints are computed after every edit of the input
+        self.block_input.observe(line, ctx);
+        let tokens = (pos == line.len()).then(|| self.input_tokens.hint(line)).flatten();
+        if self.block_input.is_active() {
+            return (pos == line.len())
+                .then(|| ChatHint::Notice(format!("{}{BLOCK_INPUT_NOTICE}", tokens.unwrap_or_default())));
+        }
+        self.hinter
+            .hint(line, pos, ctx)
+            .map(ChatHint::Completion)
+            .or(tokens.map(ChatHint::Notice))
+    }
 }
 
 impl Validator for ChatHelper {
     fn validate(&self, os: &mut ValidationContext<'_>) -> rustyline::Result<ValidationResult> {
+        if self.block_input.is_active() {
+            return Ok(ValidationResult::Incomplete);
+        }
         self.validator.validate(os)
     }
 }
 
 impl Highlighter for ChatHelper {
     fn highlight_hint<'h>(&self, hint: &'h str) -> Cow<'h, str> {
+        if hint.starts_with(INPUT_TOKENS_WARNING) {
+            return Cow::Owned(crate::theme::StyledText::warning(hint));
+        }
         Cow::Owned(format!("\x1b[38;5;240m{hint}\x1b[m"))
     }
 
@@ -486,6 +766,11 @@ impl Highlighter for ChatHelper {
                 result.push_str(&colored_percentage);
             }
 
+            // Add cost part if present
+            if let Some(cost) = components.cost {
+                result.push_str(&StyledText::secondary(&format!("{cost} ")));
+            }
+
             // Add tangent indicator if present (tangent yellow)
             if components.tangent_mode {
                 result.push_str(&StyledText::tangent("↯ "));
@@ -577,10 +862,22 @@ pub fn rl(
     // Generate available commands based on enabled experiments
     let available_commands = get_available_commands(os);
 
+    let block_input = Arc::new(BlockInput::default());
+    let input_tokens = InputTokens {
+        show: os
+            .database
+            .settings
+            .get_bool(Setting::ChatShowInputTokens)
+            .unwrap_or(true),
+        paste_state: paste_state.clone(),
+        attached_images: 0,
+    };
     let h = ChatHelper {
         completer: ChatCompleter::new(sender, receiver, available_commands.clone()),
         hinter: ChatHinter::new(history_hints_enabled, history_path, available_commands),
         validator: MultiLineValidator,
+        block_input: Arc::clone(&block_input),
+        input_tokens,
     };
 
     let mut rl = Editor::with_config(config)?;
@@ -593,22 +890,22 @@ pub fn rl(
         }
     }
 
-    // Add custom keybinding for Ctrl+D to open delegate command (configurable)
+    // Add custom keybinding to open the /agents dashboard (configurable)
     if ExperimentManager::is_enabled(os, ExperimentName::Delegate) {
         if let Some(key) = os.database.settings.get_string(Setting::DelegateModeKey) {
             if key.len() == 1 {
                 rl.bind_sequence(
                     KeyEvent(KeyCode::Char(key.chars().next().unwrap()), Modifiers::CTRL),
-                    EventHandler::Simple(Cmd::Insert(1, "/delegate ".to_string())),
+                    EventHandler::Simple(Cmd::Insert(1, "/agents".to_string())),
                 );
             }
         };
     }
 
-    // Add custom keybinding for Alt+Enter to insert a newline
+    // Add custom keybinding for Alt+Enter to enter block mode with a newline, or send in block mode
     rl.bind_sequence(
         KeyEvent(KeyCode::Enter, Modifiers::ALT),
-        EventHandler::Simple(Cmd::Insert(1, "\n".to_string())),
+        EventHandler::Conditional(Box::new(BlockInputHandler { block_input })),
     );
 
     // Add custom keybinding for Ctrl+j to insert a newline
@@ -637,6 +934,12 @@ pub fn rl(
         EventHandler::Simple(Cmd::Insert(1, "/tangent".to_string())),
     );
 
+    // Add custom keybinding for Ctrl+R to search the prompts of previous sessions too
+    rl.bind_sequence(
+        KeyEvent(KeyCode::Char('r'), Modifiers::CTRL),
+        EventHandler::Conditional(Box::new(PromptHistorySearch::new(os.database.clone()))),
+    );
+
     // Add custom keybinding for Ctrl+V to paste images from clipboard
     rl.bind_sequence(
         KeyEvent(KeyCode::Char('v'), Modifiers::CTRL),
@@ -681,6 +984,26 @@ mod tests {
         assert!(completions.contains(&"/help".to_string()));
     }
 
+    #[tokio::test]
+    async fn test_chat_completer_command_arguments() {
+        let (prompt_request_sender, _) = tokio::sync::broadcast::channel::<PromptQuery>(5);
+        let (_, prompt_response_receiver) = tokio::sync::broadcast::channel::<PromptQueryResult>(5);
+        let mut completer = ChatCompleter::new(prompt_request_sender, prompt_response_receiver, vec!["/tools"]);
+        completer.tool_names = vec!["fs_read".to_string(), "fs_write".to_string(), "use_aws".to_string()];
+        let empty_history = DefaultHistory::new();
+        let ctx = Context::new(&empty_history);
+
+        let (start, completions) = completer.complete("/tools tr", 9, &ctx).unwrap();
+        assert_eq!(
+            (start, completions),
+            (7, vec!["trust".to_string(), "trust-all".to_string()])
+        );
+
+        let (start, completions) = completer.complete("/tools trust use_aws fs", 23, &ctx).unwrap();
+        assert_eq!(start, 21);
+        assert_eq!(completions, vec!["fs_read".to_string(), "fs_write".to_string()]);
+    }
+
     #[tokio::test]
     async fn test_chat_completer_no_completion() {
         let (prompt_request_sender, _) = tokio::sync::broadcast::channel::<PromptQuery>(5);
@@ -720,6 +1043,8 @@ mod tests {
             ),
             hinter: ChatHinter::new(true, PathBuf::new(), available_commands),
             validator: MultiLineValidator,
+            block_input: Arc::default(),
+            input_tokens: InputTokens::default(),
         };
 
         // Test basic prompt highlighting
@@ -744,6 +1069,8 @@ mod tests {
             ),
             hinter: ChatHinter::new(true, PathBuf::new(), available_commands),
             validator: MultiLineValidator,
+            block_input: Arc::default(),
+            input_tokens: InputTokens::default(),
         };
 
         // Test warning prompt highlighting
@@ -771,6 +1098,8 @@ mod tests {
             ),
             hinter: ChatHinter::new(true, PathBuf::new(), available_commands),
             validator: MultiLineValidator,
+            block_input: Arc::default(),
+            input_tokens: InputTokens::default(),
         };
 
         // Test profile prompt highlighting
@@ -798,6 +1127,8 @@ mod tests {
             ),
             hinter: ChatHinter::new(true, PathBuf::new(), available_commands),
             validator: MultiLineValidator,
+            block_input: Arc::default(),
+            input_tokens: InputTokens::default(),
         };
 
         // Test profile + warning prompt highlighting
@@ -830,6 +1161,8 @@ mod tests {
             ),
             hinter: ChatHinter::new(true, PathBuf::new(), available_commands),
             validator: MultiLineValidator,
+            block_input: Arc::default(),
+            input_tokens: InputTokens::default(),
         };
 
         // Test invalid prompt format (should return as-is)
@@ -854,6 +1187,8 @@ mod tests {
             ),
             hinter: ChatHinter::new(true, PathBuf::new(), available_commands),
             validator: MultiLineValidator,
+            block_input: Arc::default(),
+            input_tokens: InputTokens::default(),
         };
 
         // Test tangent mode prompt highlighting - ↯ yellow, > magenta
@@ -880,6 +1215,8 @@ mod tests {
             ),
             hinter: ChatHinter::new(true, PathBuf::new(), available_commands),
             validator: MultiLineValidator,
+            block_input: Arc::default(),
+            input_tokens: InputTokens::default(),
         };
 
         // Test tangent mode with warning - ↯ yellow, ! red, > magenta
@@ -911,6 +1248,8 @@ mod tests {
             ),
             hinter: ChatHinter::new(true, PathBuf::new(), available_commands),
             validator: MultiLineValidator,
+            block_input: Arc::default(),
+            input_tokens: InputTokens::default(),
         };
 
         // Test profile with tangent mode - [dev] cyan, ↯ yellow, > magenta
@@ -976,6 +1315,49 @@ mod tests {
         assert_eq!(hint, None);
     }
 
+    #[test]
+    fn test_block_input() {
+        let history = DefaultHistory::new();
+        let ctx = Context::new(&history);
+        let block_input = BlockInput::default();
+
+        // Typing, new lines included, doesn't enter block mode
+        for line in ["h", "hi", "hi\n", "hi\nt"] {
+            block_input.observe(line, &ctx);
+        }
+        assert!(!block_input.is_active());
+
+        // Pasting several lines does
+        block_input.observe("hi\nthere\nfn main() {}\n", &ctx);
+        assert!(block_input.is_active());
+
+        block_input.reset();
+        assert!(!block_input.is_active());
+        block_input.observe("pasted on one line", &ctx);
+        assert!(!block_input.is_active());
+    }
+
+    #[test]
+    fn test_input_tokens_hint() {
+        let prompt = "a".repeat(4_000);
+        assert_eq!(input_tokens_hint(&prompt, 0, true), Some("  ~1000 tokens".to_string()));
+        assert_eq!(
+            input_tokens_hint(&prompt, 2, true),
+            Some("  ~4200 tokens with 2 images".to_string())
+        );
+        assert_eq!(input_tokens_hint(&prompt, 0, false), None);
+        // Commands and short prompts aren't counted
+        assert_eq!(input_tokens_hint("/compact", 0, true), None);
+        assert_eq!(input_tokens_hint("hi", 0, true), None);
+
+        // The warnings are shown even without the count
+        let hint = input_tokens_hint(&"a".repeat(MAX_USER_MESSAGE_SIZE + 1), 0, false).unwrap();
+        assert!(hint.starts_with(INPUT_TOKENS_WARNING));
+        assert!(hint.ends_with("it will be truncated"));
+        let hint = input_tokens_hint(&prompt, MAX_NUMBER_OF_IMAGES_PER_REQUEST + 1, false).unwrap();
+        assert!(hint.ends_with("the extra ones will be dropped"));
+    }
+
     #[tokio::test]
     // If you get a unit test failure for key override, please consider using a new key binding instead.
     // The list of reserved keybindings here are the standard in UNIX world so please don't take them
@@ -0,0 +1,75 @@
+//! Reverse search of the prompts of the current and previous sessions, bound to Ctrl+R. Prompts
+//! are recorded in the database as they are read, see
+//! [crate::database::Database::add_prompt_history].
+
+use eyre::Result;
+use rustyline::{
+    Cmd,
+    ConditionalEventHandler,
+    EventContext,
+    Movement,
+    RepeatCount,
+};
+use tracing::warn;
+
+use crate::database::Database;
+
+/// Number of the most recent prompts searched.
+const MAX_SEARCHED_PROMPTS: usize = 10_000;
+
+/// Shows the lines of multi-line prompts on one line, to search and select them as a whole.
+const NEWLINE: &str = " ↵ ";
+
+pub struct PromptHistorySearch {
+    database: Database,
+}
+
+impl PromptHistorySearch {
+    pub fn new(database: Database) -> Self {
+        Self { database }
+    }
+}
+
+impl ConditionalEventHandler for PromptHistorySearch {
+    fn handle(&self, _evt: &rustyline::Event, _n: RepeatCount, _positive: bool, ctx: &EventContext<'_>) -> Option<Cmd> {
+        let prompts = match self.database.get_prompt_history(MAX_SEARCHED_PROMPTS) {
+            Ok(prompts) => prompts,
+            Err(err) => {
+                warn!(?err, "Failed to load the prompt history");
+                return Some(Cmd::Noop);
+            },
+        };
+        match search(&prompts, ctx.line()) {
+            Ok(Some(prompt)) => Some(Cmd::Replace(Movement::WholeBuffer, Some(prompt))),
+            // Cancelled or failed, keep the line being edited
+            _ => Some(Cmd::Noop),
+        }
+    }
+}
+
+/// Searches `prompts` starting with `query`, returning the selected prompt.
+fn search(prompts: &[String], query: &str) -> Result<Option<String>> {
+    if prompts.is_empty() {
+        return Ok(None);
+    }
+    let items: Vec<String> = prompts.iter().map(|prompt| prompt.replace('\n', NEWLINE)).collect();
+    let selected = select(&items, query)?;
+    Ok(selected.and_then(|item| items.iter().position(|i| *i == item).map(|i| prompts[i].clone())))
+}
+
+#[cfg(unix)]
+fn select(items: &[String], query: &str) -> Result<Option<String>> {
+    super::skim_integration::launch_skim_search(items, "Search history: ", query)
+}
+
+#[cfg(not(unix))]
+fn select(items: &[String], query: &str) -> Result<Option<String>> {
+    let index = dialoguer::FuzzySelect::with_theme(&crate::util::dialoguer_theme())
+        .with_prompt("Search history")
+        .with_initial_text(query)
+        .items(items)
+        .max_length(15)
+        .report(false)
+        .interact_opt()?;
+    Ok(index.map(|i| items[i].clone()))
+}
@@ -1,4 +1,5 @@
 use crate::cli::agent::DEFAULT_AGENT_NAME;
+use crate::cli::chat::cost::Cost;
 
 /// Components extracted from a prompt string
 #[derive(Debug, PartialEq)]
@@ -8,16 +9,19 @@ pub struct PromptComponents {
     pub warning: bool,
     pub tangent_mode: bool,
     pub usage_percentage: Option<f32>,
+    /// Estimated cost of the session, e.g. `$0.0123`
+    pub cost: Option<String>,
 }
 
 /// Parse prompt components from a plain text prompt
 pub fn parse_prompt_components(prompt: &str) -> Option<PromptComponents> {
-    // Expected format: "[agent] 6% !> " or "> " or "!> " or "[agent] ↯ > " or "6% ↯ > " etc.
+    // Expected format: "[agent] 6% $0.0123 !> " or "> " or "!> " or "[agent] ↯ > " or "6% ↯ > " etc.
     let mut delegate_notifier = None::<String>;
     let mut profile = None;
     let mut warning = false;
     let mut tangent_mode = false;
     let mut usage_percentage = None;
+    let mut cost = None;
 
     // Check if multi-line prompt (e.g., with rich notification)
     // Everything before the last line is treated as delegate_notifier
@@ -57,6 +61,16 @@ pub fn parse_prompt_components(prompt: &str) -> Option<PromptComponents> {
         }
     }
 
+    // Check for cost pattern (e.g., "$0.0123 ")
+    if remaining.starts_with('$') {
+        if let Some((amount, rest)) = remaining.split_once(' ') {
+            if amount[1..].parse::<f64>().is_ok() {
+                cost = Some(amount.to_string());
+                remaining = rest.trim_start();
+            }
+        }
+    }
+
     // Check for tangent mode ↯ first
     if let Some(after_tangent) = remaining.strip_prefix('↯') {
         tangent_mode = true;
@@ -77,6 +91,7 @@ pub fn parse_prompt_components(prompt: &str) -> Option<PromptComponents> {
             warning,
             tangent_mode,
             usage_percentage,
+            cost,
         })
     } else {
         None
@@ -88,6 +103,7 @@ pub fn generate_prompt(
     warning: bool,
     tangent_mode: bool,
     usage_percentage: Option<f32>,
+    cost: Option<f64>,
 ) -> String {
     // Generate plain text prompt that will be colored by highlight_prompt
     let warning_symbol = if warning { "!" } else { "" };
@@ -97,11 +113,12 @@ pub fn generate_prompt(
         .unwrap_or_default();
 
     let percentage_part = usage_percentage.map(|p| format!("{:.0}% ", p)).unwrap_or_default();
+    let cost_part = cost.map(|c| format!("{} ", Cost(c))).unwrap_or_default();
 
     if tangent_mode {
-        format!("{profile_part}{percentage_part}↯ {warning_symbol}> ")
+        format!("{profile_part}{percentage_part}{cost_part}↯ {warning_symbol}> ")
     } else {
-        format!("{profile_part}{percentage_part}{warning_symbol}> ")
+        format!("{profile_part}{percentage_part}{cost_part}{warning_symbol}> ")
     }
 }
 
@@ -112,43 +129,53 @@ mod tests {
     #[test]
     fn test_generate_prompt() {
         // Test default prompt (no profile)
-        assert_eq!(generate_prompt(None, false, false, None), "> ");
+        assert_eq!(generate_prompt(None, false, false, None, None), "> ");
         // Test default prompt with warning
-        assert_eq!(generate_prompt(None, true, false, None), "!> ");
+        assert_eq!(generate_prompt(None, true, false, None, None), "!> ");
         // Test tangent mode
-        assert_eq!(generate_prompt(None, false, true, None), "↯ > ");
+        assert_eq!(generate_prompt(None, false, true, None, None), "↯ > ");
         // Test tangent mode with warning
-        assert_eq!(generate_prompt(None, true, true, None), "↯ !> ");
+        assert_eq!(generate_prompt(None, true, true, None, None), "↯ !> ");
         // Test default profile (should be same as no profile)
-        assert_eq!(generate_prompt(Some(DEFAULT_AGENT_NAME), false, false, None), "> ");
+        assert_eq!(
+            generate_prompt(Some(DEFAULT_AGENT_NAME), false, false, None, None),
+            "> "
+        );
         // Test custom profile
         assert_eq!(
-            generate_prompt(Some("test-profile"), false, false, None),
+            generate_prompt(Some("test-profile"), false, false, None, None),
             "[test-profile] > "
         );
         // Test custom profile with tangent mode
         assert_eq!(
-            generate_prompt(Some("test-profile"), false, true, None),
+            generate_prompt(Some("test-profile"), false, true, None, None),
             "[test-profile] ↯ > "
         );
         // Test another custom profile with warning
-        assert_eq!(generate_prompt(Some("dev"), true, false, None), "[dev] !> ");
+        assert_eq!(generate_prompt(Some("dev"), true, false, None, None), "[dev] !> ");
         // Test custom profile with warning and tangent mode
-        assert_eq!(generate_prompt(Some("dev"), true, true, None), "[dev] ↯ !> ");
+        assert_eq!(generate_prompt(Some("dev"), true, true, None, None), "[dev] ↯ !> ");
         // Test custom profile with usage percentage
         assert_eq!(
-            generate_prompt(Some("rust-agent"), false, false, Some(6.2)),
+            generate_prompt(Some("rust-agent"), false, false, Some(6.2), None),
             "[rust-agent] 6% > "
         );
         // Test custom profile with usage percentage and warning
         assert_eq!(
-            generate_prompt(Some("rust-agent"), true, false, Some(15.7)),
+            generate_prompt(Some("rust-agent"), true, false, Some(15.7), None),
             "[rust-agent] 16% !> "
         );
         // Test usage percentage without profile
-        assert_eq!(generate_prompt(None, false, false, Some(25.3)), "25% > ");
+        assert_eq!(generate_prompt(None, false, false, Some(25.3), None), "25% > ");
         // Test usage percentage with tangent mode
-        assert_eq!(generate_prompt(None, false, true, Some(8.9)), "9% ↯ > ");
+        assert_eq!(generate_prompt(None, false, true, Some(8.9), None), "9% ↯ > ");
+        // Test cost with usage percentage and tangent mode
+        assert_eq!(
+            generate_prompt(Some("dev"), false, true, Some(8.9), Some(0.01234)),
+            "[dev] 9% $0.0123 ↯ > "
+        );
+        // Test cost with warning
+        assert_eq!(generate_prompt(None, true, false, None, Some(1.5)), "$1.50 !> ");
     }
 
     #[test]
@@ -228,6 +255,17 @@ mod tests {
         assert!(components.tangent_mode);
         assert_eq!(components.usage_percentage, Some(8.0));
 
+        // Test prompts with costs
+        let components = parse_prompt_components("[dev] 9% $0.0123 ↯ > ").unwrap();
+        assert_eq!(components.profile.as_deref(), Some("dev"));
+        assert_eq!(components.usage_percentage, Some(9.0));
+        assert_eq!(components.cost.as_deref(), Some("$0.0123"));
+        assert!(components.tangent_mode);
+
+        let components = parse_prompt_components("$1.50 !> ").unwrap();
+        assert_eq!(components.cost.as_deref(), Some("$1.50"));
+        assert!(components.warning);
+
         // Test invalid prompt
         assert!(parse_prompt_components("invalid").is_none());
     }
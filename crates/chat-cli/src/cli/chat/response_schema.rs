@@ -0,0 +1,161 @@
+//! JSON Schema that final answers must conform to, set with `--response-schema` or `/schema set`
+//! so that scripted invocations get machine-parseable answers.
+
+use std::path::{
+    Path,
+    PathBuf,
+};
+
+use serde_json::Value;
+use thiserror::Error;
+
+use crate::os::Os;
+
+/// Number of times an answer not matching the schema is requested again, unless configured with
+/// `chat.responseSchemaRetries`.
+pub const DEFAULT_RESPONSE_SCHEMA_RETRIES: usize = 2;
+
+#[derive(Debug, Error)]
+pub enum ResponseSchemaError {
+    #[error("Failed to read the schema at {}: {error}", path.display())]
+    Io { path: PathBuf, error: std::io::Error },
+    #[error("The schema at {} is not valid JSON: {error}", path.display())]
+    InvalidJson { path: PathBuf, error: serde_json::Error },
+    #[error("The schema at {} is not a valid JSON Schema: {error}", path.display())]
+    InvalidSchema {
+        path: PathBuf,
+        error: Box<jsonschema::ValidationError<'static>>,
+    },
+}
+
+#[derive(Debug)]
+pub struct ResponseSchema {
+    path: PathBuf,
+    schema: Value,
+    validator: jsonschema::Validator,
+}
+
+impl ResponseSchema {
+    pub async fn load(os: &Os, path: impl AsRef<Path>) -> Result<Self, ResponseSchemaError> {
+        let path = path.as_ref().to_path_buf();
+        let content = match os.fs.read_to_string(&path).await {
+            Ok(content) => content,
+            Err(error) => return Err(ResponseSchemaError::Io { path, error }),
+        };
+        Self::from_str(path, &content)
+    }
+
+    fn from_str(path: PathBuf, content: &str) -> Result<Self, ResponseSchemaError> {
+        let schema: Value = match serde_json::from_str(content) {
+            Ok(schema) => schema,
+            Err(error) => return Err(ResponseSchemaError::InvalidJson { path, error }),
+        };
+        let validator = match jsonschema::validator_for(&schema) {
+            Ok(validator) => validator,
+            Err(error) => {
+                return Err(ResponseSchemaError::InvalidSchema {
+                    path,
+                    error: Box::new(error),
+                });
+            },
+        };
+
+        Ok(Self {
+            path,
+            schema,
+            validator,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Instructions added to every prompt while the schema is set.
+    pub fn instructions(&self) -> String {
+        format!(
+            "Once you are done using tools, your final answer must be a single JSON document matching the \
+            following JSON Schema, with no other text and no markdown code fence:\n{}",
+            self.schema
+        )
+    }
+
+    /// Validates a final answer, returning a description of each mismatch otherwise.
+    ///
+    /// The answer may be wrapped in a markdown code fence, which models tend to add regardless of
+    /// the instructions.
+    pub fn validate(&self, answer: &str) -> Result<Value, Vec<String>> {
+        let instance: Value = serde_json::from_str(strip_code_fence(answer))
+            .map_err(|err| vec![format!("The answer is not a valid JSON document: {err}")])?;
+        let errors = self
+            .validator
+            .iter_errors(&instance)
+            .map(|err| match err.instance_path.as_str() {
+                "" => err.to_string(),
+                path => format!("{path}: {err}"),
+            })
+            .collect::<Vec<_>>();
+
+        if errors.is_empty() { Ok(instance) } else { Err(errors) }
+    }
+}
+
+/// Prompt requesting an answer again after it didn't match the schema.
+pub fn retry_prompt(errors: &[String]) -> String {
+    format!(
+        "Your previous answer does not match the required JSON Schema:\n{}\n\nAnswer again with only a JSON \
+        document matching the schema.",
+        errors.iter().map(|e| format!("- {e}")).collect::<Vec<_>>().join("\n")
+    )
+}
+
+fn strip_code_fence(answer: &str) -> &str {
+    let answer = answer.trim();
+    let Some(rest) = answer.strip_prefix("```") else {
+        return answer;
+    };
+    let Some(rest) = rest.strip_suffix("```") else {
+        return answer;
+    };
+    // Skip the language of the fence, e.g. ```json
+    match rest.split_once('\n') {
+        Some((_, body)) => body.trim(),
+        None => rest.trim(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCHEMA: &str = r#"{
+        "type": "object",
+        "properties": { "name": { "type": "string" }, "count": { "type": "integer" } },
+        "required": ["name", "count"]
+    }"#;
+
+    #[test]
+    fn test_validate() {
+        let schema = ResponseSchema::from_str(PathBuf::from("schema.json"), SCHEMA).unwrap();
+
+        assert!(schema.validate(r#"{"name": "a", "count": 1}"#).is_ok());
+        assert!(schema.validate("```json\n{\"name\": \"a\", \"count\": 1}\n```").is_ok());
+        assert_eq!(schema.validate("Here you go").unwrap_err().len(), 1);
+
+        let errors = schema.validate(r#"{"name": "a", "count": "one"}"#).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with("/count: "), "{}", errors[0]);
+    }
+
+    #[test]
+    fn test_invalid_schema() {
+        assert!(matches!(
+            ResponseSchema::from_str(PathBuf::from("schema.json"), "{"),
+            Err(ResponseSchemaError::InvalidJson { .. })
+        ));
+        assert!(matches!(
+            ResponseSchema::from_str(PathBuf::from("schema.json"), r#"{"type": 1}"#),
+            Err(ResponseSchemaError::InvalidSchema { .. })
+        ));
+    }
+}
@@ -0,0 +1,142 @@
+//! Limits on the number of turns, tool calls and duration of non-interactive sessions, set with
+//! `--max-turns`, `--max-tool-calls` and `--timeout`, so that automation can't loop on tool uses
+//! indefinitely.
+
+use std::fmt;
+use std::time::{
+    Duration,
+    Instant,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunLimit {
+    /// Maximum number of responses from the model
+    Turns(usize),
+    /// Maximum number of tool uses
+    ToolCalls(usize),
+    /// Maximum duration of the invocation, in seconds
+    Timeout(u64),
+}
+
+impl RunLimit {
+    /// Reason given in the interrupt of the run finished event.
+    pub fn reason(&self) -> &'static str {
+        match self {
+            RunLimit::Turns(_) => "maxTurns",
+            RunLimit::ToolCalls(_) => "maxToolCalls",
+            RunLimit::Timeout(_) => "timeout",
+        }
+    }
+
+    pub fn value(&self) -> u64 {
+        match self {
+            RunLimit::Turns(limit) | RunLimit::ToolCalls(limit) => *limit as u64,
+            RunLimit::Timeout(secs) => *secs,
+        }
+    }
+}
+
+impl fmt::Display for RunLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RunLimit::Turns(limit) => write!(f, "the limit of {limit} turns set with --max-turns"),
+            RunLimit::ToolCalls(limit) => write!(f, "the limit of {limit} tool calls set with --max-tool-calls"),
+            RunLimit::Timeout(secs) => write!(f, "the timeout of {secs} seconds set with --timeout"),
+        }
+    }
+}
+
+/// Counts the turns and tool calls of the session against its limits.
+#[derive(Debug, Default)]
+pub struct RunLimits {
+    pub max_turns: Option<usize>,
+    pub max_tool_calls: Option<usize>,
+    /// Start of the invocation and the number of seconds it may run for
+    timeout: Option<(Instant, u64)>,
+    turns: usize,
+    tool_calls: usize,
+}
+
+impl RunLimits {
+    pub fn new(max_turns: Option<usize>, max_tool_calls: Option<usize>) -> Self {
+        Self {
+            max_turns,
+            max_tool_calls,
+            ..Default::default()
+        }
+    }
+
+    /// Limits the invocation started at `start` to `timeout` seconds.
+    pub fn with_timeout(mut self, timeout: Option<u64>, start: Instant) -> Self {
+        self.timeout = timeout.map(|secs| (start, secs));
+        self
+    }
+
+    /// When the session times out, along with the limit reached then.
+    pub fn deadline(&self) -> Option<(Instant, RunLimit)> {
+        self.timeout
+            .map(|(start, secs)| (start + Duration::from_secs(secs), RunLimit::Timeout(secs)))
+    }
+
+    /// Records a response of the model requesting `tool_uses` tool uses, returning the limit that
+    /// running them would exceed. A response without tool uses ends the turn, so it never exceeds
+    /// a limit.
+    pub fn record_response(&mut self, tool_uses: usize) -> Option<RunLimit> {
+        self.turns += 1;
+        if tool_uses == 0 {
+            return None;
+        }
+        // Running the tools leads to another response from the model
+        if let Some(max_turns) = self.max_turns.filter(|max| self.turns >= *max) {
+            return Some(RunLimit::Turns(max_turns));
+        }
+        if let Some(max_tool_calls) = self.max_tool_calls.filter(|max| self.tool_calls + tool_uses > *max) {
+            return Some(RunLimit::ToolCalls(max_tool_calls));
+        }
+        self.tool_calls += tool_uses;
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_response() {
+        let mut limits = RunLimits::new(Some(3), None);
+        assert_eq!(limits.record_response(2), None);
+        assert_eq!(limits.record_response(1), None);
+        assert_eq!(limits.record_response(1), Some(RunLimit::Turns(3)));
+
+        let mut limits = RunLimits::new(Some(2), None);
+        assert_eq!(limits.record_response(1), None);
+        assert_eq!(limits.record_response(0), None, "final answers never exceed a limit");
+
+        let mut limits = RunLimits::new(None, Some(3));
+        assert_eq!(limits.record_response(2), None);
+        assert_eq!(limits.record_response(2), Some(RunLimit::ToolCalls(3)));
+        assert_eq!(limits.record_response(1), None);
+        assert_eq!(limits.record_response(1), Some(RunLimit::ToolCalls(3)));
+
+        let mut limits = RunLimits::default();
+        for _ in 0..100 {
+            assert_eq!(limits.record_response(10), None);
+        }
+    }
+
+    #[test]
+    fn test_deadline() {
+        let start = Instant::now();
+        assert_eq!(RunLimits::new(Some(3), None).deadline(), None);
+        assert_eq!(
+            RunLimits::default().with_timeout(Some(90), start).deadline(),
+            Some((start + Duration::from_secs(90), RunLimit::Timeout(90)))
+        );
+        assert_eq!(RunLimit::Timeout(90).reason(), "timeout");
+        assert_eq!(
+            RunLimit::Timeout(90).to_string(),
+            "the timeout of 90 seconds set with --timeout"
+        );
+    }
+}
@@ -0,0 +1,404 @@
+//! `q chat serve`, which keeps a chat session running behind a unix socket so that editor plugins
+//! and wrappers can send it prompts without starting a process per prompt.
+//!
+//! Clients exchange newline-delimited JSON-RPC 2.0 messages with the server. They call:
+//! - `prompt` with `{"text": "..."}` to send a prompt, or a slash command
+//! - `approve` with `{"decision": "yes" | "no" | "trust"}` to answer a tool approval request, which
+//!   fails unless a tool use is awaiting approval
+//! - `exit` to end the session
+//!
+//! The session streams its structured events, such as `textMessageContent`, `toolCallStart` and
+//! `runFinished`, as `event` notifications. A `metaEvent` with the `prompt_user` payload tells that
+//! the session waits for the next prompt or approval. Output that has no structured event yet is
+//! sent as `output` events with the text and the stream it was written to.
+//!
+//! One client receives the events at a time: a new connection takes them over from the previous
+//! one.
+
+use std::path::PathBuf;
+use std::sync::atomic::{
+    AtomicBool,
+    Ordering,
+};
+
+use clap::Args;
+use serde::Deserialize;
+use serde_json::{
+    Value,
+    json,
+};
+
+use super::input_source::InputSource;
+
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// Server error of `approve` when no tool use is awaiting approval
+const NO_PENDING_APPROVAL: i64 = -32000;
+
+#[derive(Debug, Clone, PartialEq, Eq, Args)]
+pub struct ServeArgs {
+    /// Path of the unix socket to listen on. Without it, `serve` is sent as a prompt
+    #[arg(long, value_name = "PATH")]
+    pub socket: Option<PathBuf>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Request {
+    /// Absent for notifications, which get no response
+    #[serde(default)]
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+/// Handles a JSON-RPC request of a client, returning the line to give to the session as input, if
+/// any, and the response to send back. `awaiting_approval` is cleared once the session is given an
+/// input, as it answers the pending approval.
+fn handle_request(line: &str, awaiting_approval: &AtomicBool) -> (Option<String>, Option<Value>) {
+    let request = match serde_json::from_str::<Request>(line) {
+        Ok(request) => request,
+        Err(err) => return (None, Some(error_response(Value::Null, PARSE_ERROR, &err.to_string()))),
+    };
+
+    let input = match request.method.as_str() {
+        "prompt" => match request.params["text"].as_str() {
+            Some(text) => Ok(text.to_string()),
+            None => Err((INVALID_PARAMS, "params.text must be a string".to_string())),
+        },
+        // Answers to the tool approval prompt of the session, which would otherwise be sent as prompts
+        "approve" if !awaiting_approval.load(Ordering::SeqCst) => {
+            Err((NO_PENDING_APPROVAL, "No tool use is awaiting approval".to_string()))
+        },
+        "approve" => match request.params["decision"].as_str() {
+            Some("yes") => Ok("y".to_string()),
+            Some("no") => Ok("n".to_string()),
+            Some("trust") => Ok("t".to_string()),
+            _ => Err((INVALID_PARAMS, "params.decision must be yes, no or trust".to_string())),
+        },
+        "exit" => Ok("/quit".to_string()),
+        method => Err((METHOD_NOT_FOUND, format!("Unknown method {method}"))),
+    };
+
+    if input.is_ok() {
+        awaiting_approval.store(false, Ordering::SeqCst);
+    }
+
+    let response = request.id.map(|id| match &input {
+        Ok(_) => json!({ "jsonrpc": "2.0", "id": id, "result": {} }),
+        Err((code, message)) => error_response(id, *code, message),
+    });
+    (input.ok(), response)
+}
+
+fn error_response(id: Value, code: i64, message: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message },
+    })
+}
+
+#[cfg(unix)]
+mod unix {
+    use std::io::{
+        BufRead,
+        BufReader,
+        Write,
+    };
+    use std::os::unix::net::{
+        UnixListener,
+        UnixStream,
+    };
+    use std::path::PathBuf;
+    use std::sync::atomic::AtomicBool;
+    use std::sync::mpsc::Sender;
+    use std::sync::{
+        Arc,
+        Mutex,
+    };
+
+    use chat_cli_ui::conduit::ViewEnd;
+    use chat_cli_ui::protocol::{
+        Event,
+        LegacyPassThroughOutput,
+    };
+    use eyre::{
+        Result,
+        bail,
+    };
+    use serde_json::{
+        Value,
+        json,
+    };
+    use tracing::{
+        debug,
+        warn,
+    };
+
+    use super::{
+        InputSource,
+        ServeArgs,
+        handle_request,
+    };
+
+    /// Client receiving the events of the session
+    type Client = Arc<Mutex<Option<UnixStream>>>;
+
+    /// Listens on the socket for as long as it is kept, removing the socket once dropped.
+    pub struct Server {
+        path: PathBuf,
+        client: Client,
+    }
+
+    impl ServeArgs {
+        /// Starts listening on the socket, returning the server along with the input source of the
+        /// session, which reads the prompts and approvals of clients.
+        pub fn listen(&self) -> Result<(Server, InputSource)> {
+            let Some(path) = self.socket.clone() else {
+                bail!("A socket to listen on must be given with --socket");
+            };
+            if path.exists() {
+                if UnixStream::connect(&path).is_ok() {
+                    bail!("Another session is already served at {}", path.display());
+                }
+                // Left by a server that is no longer running
+                std::fs::remove_file(&path)?;
+            }
+            let listener = UnixListener::bind(&path)?;
+
+            let (input_sender, input_receiver) = std::sync::mpsc::channel();
+            let awaiting_approval = Arc::new(AtomicBool::new(false));
+            let client = Client::default();
+            let accepting_client = client.clone();
+            let accepting_awaiting_approval = awaiting_approval.clone();
+            std::thread::spawn(move || {
+                for stream in listener.incoming() {
+                    match stream {
+                        Ok(stream) => {
+                            let client = accepting_client.clone();
+                            let input = input_sender.clone();
+                            let awaiting_approval = accepting_awaiting_approval.clone();
+                            std::thread::spawn(move || serve_client(stream, client, input, &awaiting_approval));
+                        },
+                        Err(err) => warn!(?err, "failed to accept a connection"),
+                    }
+                }
+            });
+
+            Ok((
+                Server { path, client },
+                InputSource::new_channel(input_receiver, awaiting_approval),
+            ))
+        }
+    }
+
+    impl Server {
+        /// Returns the view of the session, sending its events to the connected client.
+        pub fn view(&self) -> impl FnOnce(ViewEnd, Sender<()>) + Send + 'static {
+            let client = self.client.clone();
+            move |view_end, prompt_ack| {
+                while let Ok(event) = view_end.receiver.recv() {
+                    // Clients answer prompts whenever they want, so there is nothing to wait for
+                    if let Event::MetaEvent(meta) = &event {
+                        if meta.meta_type == "timing" && meta.payload == "prompt_user" {
+                            let _ = prompt_ack.send(());
+                        }
+                    }
+                    let params = match event {
+                        Event::LegacyPassThrough(output) => {
+                            let (stream, content) = match output {
+                                LegacyPassThroughOutput::Stdout(content) => ("stdout", content),
+                                LegacyPassThroughOutput::Stderr(content) => ("stderr", content),
+                            };
+                            let text = strip_ansi_escapes::strip(&content);
+                            json!({
+                                "type": "output",
+                                "stream": stream,
+                                "text": String::from_utf8_lossy(&text),
+                            })
+                        },
+                        event => serde_json::to_value(&event).unwrap_or_default(),
+                    };
+                    send(
+                        &client,
+                        &json!({ "jsonrpc": "2.0", "method": "event", "params": params }),
+                    );
+                }
+            }
+        }
+    }
+
+    impl Drop for Server {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+
+    /// Reads the requests of a client until it disconnects, after making it the client receiving
+    /// the events.
+    fn serve_client(stream: UnixStream, client: Client, input: Sender<String>, awaiting_approval: &AtomicBool) {
+        let (Ok(events), Ok(mut responses)) = (stream.try_clone(), stream.try_clone()) else {
+            warn!("failed to set up a connection");
+            return;
+        };
+        if let Ok(mut client) = client.lock() {
+            *client = Some(events);
+        }
+
+        for line in BufReader::new(stream).lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            let (line_input, response) = handle_request(&line, awaiting_approval);
+            if let Some(line_input) = line_input {
+                if input.send(line_input).is_err() {
+                    break;
+                }
+            }
+            if let Some(response) = response {
+                if writeln!(responses, "{response}").is_err() {
+                    break;
+                }
+            }
+        }
+        debug!("client disconnected");
+    }
+
+    fn send(client: &Client, message: &Value) {
+        let Ok(mut client) = client.lock() else {
+            return;
+        };
+        if let Some(stream) = client.as_mut() {
+            if writeln!(stream, "{message}").is_err() {
+                *client = None;
+            }
+        }
+    }
+}
+
+#[cfg(not(unix))]
+pub struct Server;
+
+#[cfg(not(unix))]
+impl ServeArgs {
+    pub fn listen(&self) -> eyre::Result<(Server, InputSource)> {
+        eyre::bail!("q chat serve is only supported on unix systems")
+    }
+}
+
+#[cfg(not(unix))]
+impl Server {
+    pub fn view(&self) -> impl FnOnce(chat_cli_ui::conduit::ViewEnd, std::sync::mpsc::Sender<()>) + Send + 'static {
+        |_, _| {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_handle_request() {
+        let awaiting_approval = AtomicBool::new(false);
+        assert_eq!(
+            handle_request(
+                r#"{"jsonrpc":"2.0","id":1,"method":"prompt","params":{"text":"hello"}}"#,
+                &awaiting_approval
+            ),
+            (
+                Some("hello".to_string()),
+                Some(json!({ "jsonrpc": "2.0", "id": 1, "result": {} }))
+            )
+        );
+        assert_eq!(
+            handle_request(r#"{"jsonrpc":"2.0","id":"a","method":"exit"}"#, &awaiting_approval).0,
+            Some("/quit".to_string())
+        );
+
+        let approve = r#"{"jsonrpc":"2.0","id":2,"method":"approve","params":{"decision":"trust"}}"#;
+        let (input, response) = handle_request(approve, &awaiting_approval);
+        assert_eq!(input, None, "no tool use is awaiting approval");
+        assert_eq!(response.unwrap()["error"]["code"], NO_PENDING_APPROVAL);
+        awaiting_approval.store(true, Ordering::SeqCst);
+        assert_eq!(handle_request(approve, &awaiting_approval).0, Some("t".to_string()));
+        assert_eq!(
+            handle_request(approve, &awaiting_approval).0,
+            None,
+            "the approval was already answered"
+        );
+
+        awaiting_approval.store(true, Ordering::SeqCst);
+        let (input, response) = handle_request(
+            r#"{"jsonrpc":"2.0","id":3,"method":"approve","params":{}}"#,
+            &awaiting_approval,
+        );
+        assert_eq!(input, None);
+        assert_eq!(response.unwrap()["error"]["code"], INVALID_PARAMS);
+        assert!(awaiting_approval.load(Ordering::SeqCst));
+        let (input, response) = handle_request(r#"{"jsonrpc":"2.0","id":4,"method":"cancel"}"#, &awaiting_approval);
+        assert_eq!(input, None);
+        assert_eq!(response.unwrap()["error"]["code"], METHOD_NOT_FOUND);
+        let (input, response) = handle_request("not json", &awaiting_approval);
+        assert_eq!(input, None);
+        assert_eq!(response.unwrap()["error"]["code"], PARSE_ERROR);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_serve() {
+        use std::io::{
+            BufRead,
+            BufReader,
+            Write,
+        };
+        use std::os::unix::net::UnixStream;
+
+        use chat_cli_ui::conduit::get_legacy_conduits;
+        use chat_cli_ui::protocol::{
+            Event,
+            MetaEvent,
+        };
+
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("q.sock");
+        let args = ServeArgs {
+            socket: Some(socket.clone()),
+        };
+        let (server, mut input) = args.listen().unwrap();
+        assert!(args.listen().is_err(), "the socket is already served");
+
+        let mut stream = UnixStream::connect(&socket).unwrap();
+        let mut lines = BufReader::new(stream.try_clone().unwrap()).lines();
+        writeln!(
+            stream,
+            r#"{{"jsonrpc":"2.0","id":1,"method":"prompt","params":{{"text":"hi"}}}}"#
+        )
+        .unwrap();
+        assert_eq!(input.read_line(None).unwrap().unwrap(), "hi");
+        let response: Value = serde_json::from_str(&lines.next().unwrap().unwrap()).unwrap();
+        assert_eq!(response["id"], 1);
+
+        let (view_end, _, stderr, _) = get_legacy_conduits(true);
+        let (prompt_ack_tx, prompt_ack_rx) = std::sync::mpsc::channel();
+        let view = server.view();
+        std::thread::spawn(move || view(view_end, prompt_ack_tx));
+        stderr
+            .send(Event::MetaEvent(MetaEvent {
+                meta_type: "timing".to_string(),
+                payload: Value::String("prompt_user".to_string()),
+            }))
+            .unwrap();
+        prompt_ack_rx.recv().unwrap();
+        let event: Value = serde_json::from_str(&lines.next().unwrap().unwrap()).unwrap();
+        assert_eq!(event["method"], "event");
+        assert_eq!(event["params"]["type"], "metaEvent");
+
+        drop(server);
+        assert!(!socket.exists());
+    }
+}
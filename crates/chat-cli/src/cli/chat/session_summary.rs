@@ -0,0 +1,133 @@
+//! Summary of the session printed when it ends, with `chat.sessionSummary`: how long it lasted,
+//! its turns, the tools it used, the files it modified and the tokens and estimated cost of its
+//! requests.
+
+use std::collections::{
+    BTreeMap,
+    BTreeSet,
+};
+use std::time::Instant;
+
+use super::cost::{
+    Cost,
+    CostTracker,
+};
+use crate::util::format_duration;
+
+/// Counts what happened in the session, along with the [CostTracker] of its requests.
+#[derive(Debug)]
+pub struct SessionSummary {
+    start: Instant,
+    /// Prompts sent to the model
+    turns: usize,
+    /// Tool uses run, by tool name
+    tool_calls: BTreeMap<String, usize>,
+    files_modified: BTreeSet<String>,
+}
+
+impl SessionSummary {
+    pub fn new(start: Instant) -> Self {
+        Self {
+            start,
+            turns: 0,
+            tool_calls: BTreeMap::new(),
+            files_modified: BTreeSet::new(),
+        }
+    }
+
+    pub fn record_turn(&mut self) {
+        self.turns += 1;
+    }
+
+    pub fn record_tool_use(&mut self, tool_name: &str) {
+        *self.tool_calls.entry(tool_name.to_string()).or_default() += 1;
+    }
+
+    pub fn record_file_modified(&mut self, path: String) {
+        self.files_modified.insert(path);
+    }
+
+    /// Whether the session sent anything to the model, i.e. whether there is anything to sum up.
+    pub fn is_empty(&self) -> bool {
+        self.turns == 0
+    }
+
+    /// The summary of the session ending at `now`.
+    pub fn format(&self, cost: &CostTracker, now: Instant) -> String {
+        let mut lines = vec![
+            "Session summary".to_string(),
+            format!("  Duration    {}", format_duration(now.duration_since(self.start))),
+            format!("  Turns       {}", self.turns),
+        ];
+
+        let tool_calls = self.tool_calls.values().sum::<usize>();
+        let mut by_tool = self.tool_calls.iter().collect::<Vec<_>>();
+        by_tool.sort_by(|(name_a, count_a), (name_b, count_b)| count_b.cmp(count_a).then(name_a.cmp(name_b)));
+        let by_tool = by_tool
+            .iter()
+            .map(|(name, count)| format!("{name} {count}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        lines.push(match tool_calls {
+            0 => "  Tool calls  0".to_string(),
+            _ => format!("  Tool calls  {tool_calls} ({by_tool})"),
+        });
+
+        lines.push(format!("  Files       {} modified", self.files_modified.len()));
+        lines.extend(self.files_modified.iter().map(|path| format!("                {path}")));
+
+        lines.push(format!(
+            "  Tokens      {} in, {} out{}",
+            cost.input_tokens,
+            cost.output_tokens,
+            if cost.estimated { " (estimated)" } else { "" }
+        ));
+        if cost.is_priced() {
+            lines.push(format!("  Cost        {} (estimated)", Cost(cost.total_cost)));
+        }
+        format!("{}\n", lines.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::cli::chat::cost::RequestUsage;
+
+    #[test]
+    fn test_format() {
+        let start = Instant::now();
+        let mut summary = SessionSummary::new(start);
+        assert!(summary.is_empty());
+        summary.record_turn();
+        summary.record_turn();
+        for tool in ["fs_read", "fs_write", "fs_read"] {
+            summary.record_tool_use(tool);
+        }
+        summary.record_file_modified("src/main.rs".to_string());
+        summary.record_file_modified("src/main.rs".to_string());
+
+        let mut cost = CostTracker::default();
+        cost.record(Some("claude-sonnet-4"), RequestUsage {
+            input_tokens: 1000,
+            output_tokens: 200,
+            estimated: false,
+        });
+        assert_eq!(
+            summary.format(&cost, start + Duration::from_secs(754)),
+            format!(
+                "Session summary\n\
+                \x20 Duration    12m34s\n\
+                \x20 Turns       2\n\
+                \x20 Tool calls  3 (fs_read 2, fs_write 1)\n\
+                \x20 Files       1 modified\n\
+                \x20               src/main.rs\n\
+                \x20 Tokens      1000 in, 200 out\n\
+                \x20 Cost        {} (estimated)\n",
+                Cost(cost.total_cost)
+            )
+        );
+    }
+}
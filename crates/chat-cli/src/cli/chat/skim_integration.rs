@@ -114,6 +114,19 @@ pub fn launch_skim_selector(items: &[String], prompt: &str, multi: bool) -> Resu
     }
 }
 
+/// Launch skim with the given items, starting the search with `query`, and return the selected item
+pub fn launch_skim_search(items: &[String], prompt: &str, query: &str) -> Result<Option<String>> {
+    let mut options = create_skim_options(prompt, false)?;
+    options.query = Some(query.to_string());
+    let item_reader = SkimItemReader::default();
+    let items = item_reader.of_bufread(Cursor::new(items.join("\n")));
+
+    match run_skim_with_options(&options, items)? {
+        Some(items) => Ok(extract_selections(items).into_iter().next()),
+        None => Ok(None), // User cancelled the search
+    }
+}
+
 /// Select files using skim
 pub fn select_files_with_skim() -> Result<Option<Vec<String>>> {
     // Create skim options with appropriate settings
@@ -0,0 +1,155 @@
+//! Tracking of the context entries added during a session that the model no longer uses.
+//!
+//! An entry counts as used on a turn when the model mentions it, or passes one of its paths to a
+//! tool, e.g. to read the file again. Entries unused for a number of turns are proposed for
+//! removal, or removed right away with `/context auto-evict on`.
+
+use std::collections::{
+    HashMap,
+    HashSet,
+};
+
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+/// Number of turns an entry may go unused before it is considered stale.
+pub const DEFAULT_EVICT_AFTER_TURNS: usize = 10;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StaleContextTracker {
+    /// Whether stale entries are removed automatically, rather than proposed for removal.
+    pub auto_evict: bool,
+    /// Number of turns an entry may go unused before it is considered stale.
+    pub evict_after_turns: usize,
+    /// Entries removed automatically so far.
+    pub evicted: Vec<String>,
+    turn: usize,
+    /// Turn each entry was last used on, keyed by the name the entry is removed with.
+    last_used: HashMap<String, usize>,
+    /// Entries already proposed for removal, so that they are only proposed once.
+    #[serde(skip)]
+    proposed: HashSet<String>,
+    /// Text and tool inputs of the model since the last turn.
+    #[serde(skip)]
+    model_output: String,
+}
+
+impl Default for StaleContextTracker {
+    fn default() -> Self {
+        Self {
+            auto_evict: false,
+            evict_after_turns: DEFAULT_EVICT_AFTER_TURNS,
+            evicted: Vec::new(),
+            turn: 0,
+            last_used: HashMap::new(),
+            proposed: HashSet::new(),
+            model_output: String::new(),
+        }
+    }
+}
+
+impl StaleContextTracker {
+    /// Records text written by the model, or the input of a tool it used.
+    pub fn record_model_output(&mut self, output: &str) {
+        self.model_output.push('\n');
+        self.model_output.push_str(output);
+    }
+
+    /// Starts a new turn. `entries` are the current context entries, along with the strings whose
+    /// presence in the model output since the last turn means the entry was used.
+    ///
+    /// Returns the entries that went unused for [Self::evict_after_turns] turns.
+    pub fn next_turn(&mut self, entries: &[(String, Vec<String>)]) -> Vec<String> {
+        self.turn += 1;
+        let model_output = std::mem::take(&mut self.model_output);
+
+        self.last_used.retain(|key, _| entries.iter().any(|(k, _)| k == key));
+        self.proposed.retain(|key| self.last_used.contains_key(key));
+
+        let mut stale = Vec::new();
+        for (key, references) in entries {
+            // Entries count as used on the turn they are first seen
+            let last_used = self.last_used.entry(key.clone()).or_insert(self.turn);
+            if references
+                .iter()
+                .any(|r| !r.is_empty() && model_output.contains(r.as_str()))
+            {
+                *last_used = self.turn;
+                self.proposed.remove(key);
+            }
+            if self.turn - *last_used >= self.evict_after_turns {
+                stale.push(key.clone());
+            }
+        }
+        stale
+    }
+
+    /// Returns the entries of `stale` that weren't proposed for removal yet, and marks them as
+    /// proposed.
+    pub fn take_new_proposals(&mut self, stale: Vec<String>) -> Vec<String> {
+        stale
+            .into_iter()
+            .filter(|key| self.proposed.insert(key.clone()))
+            .collect()
+    }
+
+    /// Records that `keys` were removed automatically.
+    pub fn record_evicted(&mut self, keys: &[String]) {
+        for key in keys {
+            self.last_used.remove(key);
+            self.proposed.remove(key);
+        }
+        self.evicted.extend(keys.iter().cloned());
+    }
+
+    /// Number of turns since the entry was last used, if it is tracked.
+    pub fn turns_unused(&self, key: &str) -> Option<usize> {
+        self.last_used.get(key).map(|last_used| self.turn - last_used)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stale_entries() {
+        let mut tracker = StaleContextTracker {
+            evict_after_turns: 2,
+            ..Default::default()
+        };
+        let entries = vec![
+            ("src/*.rs".to_string(), vec!["src/main.rs".to_string()]),
+            ("https://example.com".to_string(), vec![
+                "https://example.com".to_string(),
+            ]),
+        ];
+
+        assert!(tracker.next_turn(&entries).is_empty());
+        tracker.record_model_output(r#"{"path":"src/main.rs"}"#);
+        assert!(tracker.next_turn(&entries).is_empty());
+        assert_eq!(tracker.turns_unused("src/*.rs"), Some(0));
+        assert_eq!(tracker.turns_unused("https://example.com"), Some(1));
+
+        let stale = tracker.next_turn(&entries);
+        assert_eq!(stale, vec!["https://example.com".to_string()]);
+
+        // Stale entries are only proposed once, until they are used again
+        assert_eq!(tracker.take_new_proposals(stale), vec![
+            "https://example.com".to_string()
+        ]);
+        let stale = tracker.next_turn(&entries);
+        assert_eq!(stale.len(), 2);
+        assert_eq!(tracker.take_new_proposals(stale), vec!["src/*.rs".to_string()]);
+
+        tracker.record_model_output("As described in https://example.com, ...");
+        assert_eq!(tracker.next_turn(&entries), vec!["src/*.rs".to_string()]);
+
+        // Entries no longer in the context stop being tracked
+        assert!(tracker.next_turn(&entries[1..]).is_empty());
+        assert_eq!(tracker.turns_unused("src/*.rs"), None);
+    }
+}
@@ -0,0 +1,220 @@
+//! Status line at the bottom of the terminal, enabled with `chat.statusBar`, showing the active
+//! agent, the model, the context usage, the background agents and the estimated cost of the
+//! session. The line is kept out of the way of the rest of the output by a scroll region ending
+//! right above it.
+
+use std::io::{
+    self,
+    Write,
+};
+
+use crossterm::style::Print;
+use crossterm::{
+    cursor,
+    queue,
+    terminal,
+};
+use unicode_width::UnicodeWidthStr;
+
+use super::cost::Cost;
+use crate::theme::StyledText;
+
+/// Separates the parts of the status line.
+const SEPARATOR: &str = " │ ";
+
+/// Terminals with fewer rows don't show the status line.
+const MIN_ROWS: u16 = 5;
+
+/// What the status line shows.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Status {
+    pub agent: Option<String>,
+    pub model: Option<String>,
+    /// Percentage of the context window used
+    pub context_percentage: Option<f32>,
+    /// Delegated tasks queued or running in the background
+    pub background_agents: usize,
+    /// Estimated cost of the session, [None] if the model isn't priced
+    pub cost: Option<f64>,
+}
+
+impl Status {
+    /// The parts of the line, colored, dropping those from the end that don't fit in `width`.
+    pub fn line(&self, width: usize) -> String {
+        let mut parts = Vec::new();
+        if let Some(agent) = &self.agent {
+            parts.push((format!("[{agent}]"), StyledText::profile as fn(&str) -> String));
+        }
+        if let Some(model) = &self.model {
+            parts.push((model.clone(), StyledText::secondary));
+        }
+        if let Some(percentage) = self.context_percentage {
+            let color = match percentage {
+                p if p < 50.0 => StyledText::usage_low,
+                p if p < 90.0 => StyledText::usage_medium,
+                _ => StyledText::usage_high,
+            };
+            parts.push((format!("{percentage:.0}% context"), color));
+        }
+        if self.background_agents > 0 {
+            let plural = if self.background_agents == 1 { "" } else { "s" };
+            let text = format!("{} background agent{plural}", self.background_agents);
+            parts.push((text, StyledText::info));
+        }
+        if let Some(cost) = self.cost {
+            parts.push((Cost(cost).to_string(), StyledText::secondary));
+        }
+
+        let mut line = String::new();
+        let mut line_width = 0;
+        for (i, (text, color)) in parts.iter().enumerate() {
+            let separator = if i == 0 { "" } else { SEPARATOR };
+            let part_width = separator.width() + text.width();
+            if line_width + part_width > width {
+                break;
+            }
+            line.push_str(&StyledText::secondary(separator));
+            line.push_str(&color(text));
+            line_width += part_width;
+        }
+        line
+    }
+}
+
+/// The status line, drawn on the last row of the terminal.
+#[derive(Debug, Default)]
+pub struct StatusBar {
+    status: Status,
+    /// Rows of the terminal when the scroll region was set, [None] if not set
+    rows: Option<u16>,
+}
+
+impl StatusBar {
+    /// Shows `status`, if changed.
+    pub fn update(&mut self, output: impl Write, status: Status) -> io::Result<()> {
+        if self.rows.is_some() && self.status == status {
+            return self.redraw_if_resized(output);
+        }
+        self.status = status;
+        self.draw(output, terminal::size()?)
+    }
+
+    /// Draws the status line again if the terminal was resized, which resets its scroll region.
+    pub fn redraw_if_resized(&mut self, output: impl Write) -> io::Result<()> {
+        let size = terminal::size()?;
+        if self.rows == Some(size.1) {
+            return Ok(());
+        }
+        self.draw(output, size)
+    }
+
+    fn draw(&mut self, mut output: impl Write, (columns, rows): (u16, u16)) -> io::Result<()> {
+        if rows < MIN_ROWS {
+            return self.clear(output);
+        }
+        if self.rows != Some(rows) {
+            // Moves the line of the cursor up if it's the last, then leaves the last row out of
+            // the scroll region, which also moves the cursor to the top left
+            queue!(
+                output,
+                Print("\n"),
+                cursor::MoveUp(1),
+                cursor::SavePosition,
+                Print(format!("\x1b[1;{}r", rows - 1)),
+                cursor::RestorePosition,
+            )?;
+            self.rows = Some(rows);
+        }
+        queue!(
+            output,
+            cursor::SavePosition,
+            cursor::MoveTo(0, rows - 1),
+            terminal::Clear(terminal::ClearType::CurrentLine),
+            Print(self.status.line(columns as usize)),
+            cursor::RestorePosition,
+        )?;
+        output.flush()
+    }
+
+    /// Removes the status line and gives its row back to the scroll region.
+    pub fn clear(&mut self, mut output: impl Write) -> io::Result<()> {
+        let Some(rows) = self.rows.take() else {
+            return Ok(());
+        };
+        queue!(
+            output,
+            cursor::SavePosition,
+            Print("\x1b[r"),
+            cursor::MoveTo(0, rows - 1),
+            terminal::Clear(terminal::ClearType::CurrentLine),
+            cursor::RestorePosition,
+        )?;
+        output.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strip_ansi(text: &str) -> String {
+        String::from_utf8(strip_ansi_escapes::strip(text)).unwrap()
+    }
+
+    #[test]
+    fn test_line() {
+        let status = Status {
+            agent: Some("reviewer".to_string()),
+            model: Some("claude-sonnet-4".to_string()),
+            context_percentage: Some(42.4),
+            background_agents: 2,
+            cost: Some(0.0123),
+        };
+        assert_eq!(
+            strip_ansi(&status.line(200)),
+            "[reviewer] │ claude-sonnet-4 │ 42% context │ 2 background agents │ $0.0123"
+        );
+        // Parts that don't fit are dropped
+        assert_eq!(strip_ansi(&status.line(40)), "[reviewer] │ claude-sonnet-4");
+        assert_eq!(strip_ansi(&status.line(5)), "");
+
+        let status = Status {
+            background_agents: 1,
+            ..Default::default()
+        };
+        assert_eq!(strip_ansi(&status.line(80)), "1 background agent");
+    }
+
+    #[test]
+    fn test_draw() {
+        let mut bar = StatusBar {
+            status: Status {
+                model: Some("model".to_string()),
+                ..Default::default()
+            },
+            rows: None,
+        };
+        let mut output = Vec::new();
+        bar.draw(&mut output, (80, 24)).unwrap();
+        let drawn = String::from_utf8(output).unwrap();
+        assert!(drawn.contains("\x1b[1;23r"), "{drawn:?}");
+        assert!(drawn.contains("\x1b[24;1H"), "{drawn:?}");
+        assert!(drawn.contains("model"), "{drawn:?}");
+        assert_eq!(bar.rows, Some(24));
+
+        // The scroll region is only set again once resized
+        let mut output = Vec::new();
+        bar.draw(&mut output, (80, 24)).unwrap();
+        assert!(!String::from_utf8(output).unwrap().contains("\x1b[1;23r"));
+
+        let mut output = Vec::new();
+        bar.clear(&mut output).unwrap();
+        assert!(String::from_utf8(output).unwrap().contains("\x1b[r"));
+        assert_eq!(bar.rows, None);
+
+        // Too small for a status line
+        let mut output = Vec::new();
+        bar.draw(&mut output, (80, 3)).unwrap();
+        assert!(output.is_empty());
+    }
+}
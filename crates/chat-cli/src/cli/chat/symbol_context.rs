@@ -0,0 +1,471 @@
+//! Definitions of single symbols added to the context with `/context add-symbol`.
+//!
+//! Instead of the whole file, only the definition of the symbol is included, along with the imports
+//! of the file. Definitions are located in the syntax tree of the file as parsed by its tree-sitter
+//! grammar, and are extracted again on every turn so that they follow edits to the file.
+
+use std::path::Path;
+
+use eyre::{
+    Result,
+    bail,
+    eyre,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use tree_sitter::{
+    Node,
+    Parser,
+    Tree,
+};
+
+use crate::os::Os;
+
+/// The tree-sitter grammar of a language and the kinds of its syntax nodes.
+struct Language {
+    extensions: &'static [&'static str],
+    grammar: fn() -> tree_sitter::Language,
+    /// Top-level statements importing other modules.
+    imports: &'static [&'static str],
+    /// Nodes defining a symbol, named by their `name` or `declarator` field, or by the type they
+    /// implement.
+    definitions: &'static [&'static str],
+    /// Nodes wrapping a definition, which are extracted along with it, e.g. `export` statements.
+    wrappers: &'static [&'static str],
+    /// Comments, attributes and decorators, which are extracted along with the definition they
+    /// directly precede.
+    leading: &'static [&'static str],
+}
+
+const RUST: Language = Language {
+    extensions: &["rs"],
+    grammar: || tree_sitter_rust::LANGUAGE.into(),
+    imports: &["use_declaration", "extern_crate_declaration"],
+    definitions: &[
+        "function_item",
+        "function_signature_item",
+        "struct_item",
+        "enum_item",
+        "union_item",
+        "trait_item",
+        "impl_item",
+        "mod_item",
+        "type_item",
+        "const_item",
+        "static_item",
+        "macro_definition",
+    ],
+    wrappers: &[],
+    leading: &["line_comment", "block_comment", "attribute_item"],
+};
+
+const PYTHON: Language = Language {
+    extensions: &["py", "pyi"],
+    grammar: || tree_sitter_python::LANGUAGE.into(),
+    imports: &["import_statement", "import_from_statement", "future_import_statement"],
+    definitions: &["function_definition", "class_definition", "assignment"],
+    wrappers: &["decorated_definition", "expression_statement"],
+    leading: &["comment"],
+};
+
+const JS_DEFINITIONS: &[&str] = &[
+    "function_declaration",
+    "generator_function_declaration",
+    "class_declaration",
+    "abstract_class_declaration",
+    "interface_declaration",
+    "type_alias_declaration",
+    "enum_declaration",
+    "internal_module",
+    "method_definition",
+    "variable_declarator",
+];
+const JS_WRAPPERS: &[&str] = &["lexical_declaration", "variable_declaration", "export_statement"];
+
+const JAVASCRIPT: Language = Language {
+    extensions: &["js", "jsx", "mjs", "cjs"],
+    grammar: || tree_sitter_javascript::LANGUAGE.into(),
+    imports: &["import_statement"],
+    definitions: JS_DEFINITIONS,
+    wrappers: JS_WRAPPERS,
+    leading: &["comment"],
+};
+
+const TYPESCRIPT: Language = Language {
+    extensions: &["ts", "mts", "cts"],
+    grammar: || tree_sitter_typescript::LANGUAGE_TYPESCRIPT.into(),
+    ..JAVASCRIPT
+};
+
+const TSX: Language = Language {
+    extensions: &["tsx"],
+    grammar: || tree_sitter_typescript::LANGUAGE_TSX.into(),
+    ..JAVASCRIPT
+};
+
+const GO: Language = Language {
+    extensions: &["go"],
+    grammar: || tree_sitter_go::LANGUAGE.into(),
+    imports: &["package_clause", "import_declaration"],
+    definitions: &[
+        "function_declaration",
+        "method_declaration",
+        "type_spec",
+        "const_spec",
+        "var_spec",
+    ],
+    wrappers: &["type_declaration", "const_declaration", "var_declaration"],
+    leading: &["comment"],
+};
+
+const JAVA: Language = Language {
+    extensions: &["java"],
+    grammar: || tree_sitter_java::LANGUAGE.into(),
+    imports: &["package_declaration", "import_declaration"],
+    definitions: &[
+        "class_declaration",
+        "interface_declaration",
+        "enum_declaration",
+        "record_declaration",
+        "annotation_type_declaration",
+        "method_declaration",
+        "constructor_declaration",
+        "field_declaration",
+    ],
+    wrappers: &[],
+    leading: &["line_comment", "block_comment"],
+};
+
+const C_DEFINITIONS: &[&str] = &[
+    "function_definition",
+    "struct_specifier",
+    "union_specifier",
+    "enum_specifier",
+    "type_definition",
+    "preproc_def",
+    "preproc_function_def",
+    "class_specifier",
+    "namespace_definition",
+];
+
+const C: Language = Language {
+    extensions: &["c", "h"],
+    grammar: || tree_sitter_c::LANGUAGE.into(),
+    imports: &["preproc_include"],
+    definitions: C_DEFINITIONS,
+    wrappers: &[],
+    leading: &["comment"],
+};
+
+const CPP: Language = Language {
+    extensions: &["cc", "cpp", "cxx", "hh", "hpp", "hxx"],
+    grammar: || tree_sitter_cpp::LANGUAGE.into(),
+    wrappers: &["template_declaration"],
+    ..C
+};
+
+const LANGUAGES: &[Language] = &[RUST, PYTHON, JAVASCRIPT, TYPESCRIPT, TSX, GO, JAVA, C, CPP];
+
+/// A symbol added to the context, such as a function, type or class.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SymbolContext {
+    /// Path of the file defining the symbol, as given by the user.
+    pub path: String,
+    /// Name of the symbol. Members are given after their parent, as in `Agent::new` or
+    /// `Agent.run`.
+    pub symbol: String,
+}
+
+impl SymbolContext {
+    /// Parses a `<path>::<symbol>` specification.
+    pub fn parse(spec: &str) -> Result<Self> {
+        match spec.split_once("::") {
+            Some((path, symbol)) if !path.is_empty() && !symbol.is_empty() => Ok(Self {
+                path: path.to_string(),
+                symbol: symbol.to_string(),
+            }),
+            _ => bail!("expected <path>::<symbol>, e.g. src/main.rs::main"),
+        }
+    }
+
+    /// The specification the symbol was added with.
+    pub fn spec(&self) -> String {
+        format!("{}::{}", self.path, self.symbol)
+    }
+
+    /// Whether `s` refers to this symbol.
+    pub fn matches(&self, s: &str) -> bool {
+        self.spec() == s
+    }
+
+    /// Reads the file and extracts the definition of the symbol, along with the imports of the
+    /// file. Returns the name of the context entry and its content.
+    pub async fn extract(&self, os: &Os) -> Result<(String, String)> {
+        let path = if let Some(rest) = self.path.strip_prefix("~/") {
+            os.env
+                .home()
+                .ok_or_else(|| eyre!("Could not determine home directory"))?
+                .join(rest)
+        } else {
+            os.env.current_dir()?.join(&self.path)
+        };
+
+        let extension = Path::new(&self.path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or_default()
+            .to_lowercase();
+        let Some(language) = language(&extension) else {
+            bail!("symbols can't be extracted from '.{extension}' files");
+        };
+
+        let content = os.fs.read_to_string(&path).await?;
+        let (ranges, extracted) = extract_symbol(language, &content, &self.symbol)
+            .ok_or_else(|| eyre!("'{}' was not found in {}", self.symbol, self.path))?;
+
+        let lines = ranges
+            .iter()
+            .map(|(start, end)| format!("{}-{}", start + 1, end + 1))
+            .collect::<Vec<_>>()
+            .join(", ");
+        Ok((format!("{} (lines {})", self.spec(), lines), extracted))
+    }
+}
+
+fn language(extension: &str) -> Option<&'static Language> {
+    LANGUAGES.iter().find(|l| l.extensions.contains(&extension))
+}
+
+fn parse(language: &Language, content: &str) -> Option<Tree> {
+    let mut parser = Parser::new();
+    parser.set_language(&(language.grammar)()).ok()?;
+    parser.parse(content, None)
+}
+
+/// The name of the symbol defined by `node`, e.g. `Agent` for `impl<T> fmt::Debug for Agent<T>`.
+fn definition_name<'a>(node: Node<'_>, source: &'a str) -> Option<&'a str> {
+    // Specifiers without a body, as in `struct agent *a;`, only refer to the type
+    if node.kind().ends_with("_specifier") && node.child_by_field_name("body").is_none() {
+        return None;
+    }
+    let mut name = node;
+    while let Some(inner) = ["name", "declarator", "left", "type"]
+        .iter()
+        .find_map(|field| name.child_by_field_name(field))
+    {
+        name = inner;
+    }
+    (name != node).then(|| name.utf8_text(source.as_bytes()).ok()).flatten()
+}
+
+/// Collects the definitions of `name` nested in `node`, without looking into the definitions
+/// found.
+fn find_definitions<'t>(language: &Language, node: Node<'t>, source: &str, name: &str, found: &mut Vec<Node<'t>>) {
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        if language.definitions.contains(&child.kind()) && definition_name(child, source) == Some(name) {
+            found.push(child);
+        } else {
+            find_definitions(language, child, source, name, found);
+        }
+    }
+}
+
+/// The index of the last line of `node`, which ends at the start of the next line when it
+/// includes its line break.
+fn last_row(node: Node<'_>) -> usize {
+    let end = node.end_position();
+    match end.column {
+        0 if end.row > node.start_position().row => end.row - 1,
+        _ => end.row,
+    }
+}
+
+/// Returns the line range of the definition, including its wrappers and the comments, attributes
+/// and decorators directly preceding it.
+fn definition_lines(language: &Language, mut node: Node<'_>, lines: &[&str]) -> (usize, usize) {
+    while let Some(parent) = node.parent().filter(|p| language.wrappers.contains(&p.kind())) {
+        node = parent;
+    }
+
+    let mut start = node.start_position().row;
+    let mut previous = node.prev_named_sibling();
+    while let Some(sibling) = previous.filter(|s| language.leading.contains(&s.kind())) {
+        let position = sibling.start_position();
+        let on_own_line = lines
+            .get(position.row)
+            .and_then(|line| line.get(..position.column))
+            .is_some_and(|before| before.trim().is_empty());
+        if last_row(sibling) + 1 < start || !on_own_line {
+            break;
+        }
+        start = position.row;
+        previous = sibling.prev_named_sibling();
+    }
+    (start, last_row(node))
+}
+
+/// Returns the top-level import statements of the file.
+fn imports(language: &Language, tree: &Tree, source: &str) -> Vec<String> {
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    root.named_children(&mut cursor)
+        .filter(|node| language.imports.contains(&node.kind()))
+        .filter_map(|node| node.utf8_text(source.as_bytes()).ok())
+        .map(|statement| statement.trim_end().to_string())
+        .collect()
+}
+
+/// Returns the import statements of `content`, given the extension of its file. Empty for
+/// languages that aren't supported.
+pub(super) fn import_statements(extension: &str, content: &str) -> Vec<String> {
+    language(extension)
+        .and_then(|language| Some(imports(language, &parse(language, content)?, content)))
+        .unwrap_or_default()
+}
+
+/// Finds the definitions of `symbol` within `content`. Members are looked up within the
+/// definitions of their parents, e.g. `Agent::new` finds `fn new` within `impl Agent`.
+///
+/// Returns the line ranges of the definitions found, 0 indexed, and the extracted source: the
+/// imports of the file followed by the definitions.
+fn extract_symbol(language: &Language, content: &str, symbol: &str) -> Option<(Vec<(usize, usize)>, String)> {
+    let tree = parse(language, content)?;
+    let lines = content.lines().collect::<Vec<_>>();
+
+    let mut nodes = vec![tree.root_node()];
+    for name in symbol.split("::").flat_map(|s| s.split('.')).filter(|s| !s.is_empty()) {
+        let mut found = Vec::new();
+        for node in nodes {
+            find_definitions(language, node, content, name, &mut found);
+        }
+        if found.is_empty() {
+            return None;
+        }
+        nodes = found;
+    }
+
+    let ranges = nodes
+        .into_iter()
+        .map(|node| definition_lines(language, node, &lines))
+        .collect::<Vec<_>>();
+
+    let mut res = imports(language, &tree, content).join("\n");
+    for (start, end) in &ranges {
+        if !res.is_empty() {
+            res.push_str("\n\n");
+        }
+        res.push_str(&lines.get(*start..=*end).unwrap_or_default().join("\n"));
+    }
+
+    Some((ranges, res))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RUST_SOURCE: &str = r#"use std::fmt;
+use crate::{
+    a,
+    b,
+};
+
+/// An agent.
+#[derive(Debug)]
+pub struct Agent {
+    name: String, // the } in a comment
+}
+
+impl Agent {
+    /// Creates an agent.
+    pub fn new(name: &str) -> Self {
+        let brace = '}';
+        let s = "}";
+        Self { name: name.to_string() }
+    }
+
+    fn run(&self) {}
+}
+
+pub type Name = String;
+
+fn main() {
+    Agent::new("x").run();
+}
+"#;
+
+    #[test]
+    fn test_extract_rust_symbol() {
+        let imports = "use std::fmt;\nuse crate::{\n    a,\n    b,\n};\n\n";
+
+        let (ranges, content) = extract_symbol(&RUST, RUST_SOURCE, "Agent").unwrap();
+        assert_eq!(ranges, vec![(6, 10), (12, 21)]);
+        assert!(content.starts_with(&format!(
+            "{imports}/// An agent.\n#[derive(Debug)]\npub struct Agent {{\n    name: String, // the }} in a comment\n}}\n\nimpl Agent {{"
+        )));
+
+        let (ranges, content) = extract_symbol(&RUST, RUST_SOURCE, "Agent::new").unwrap();
+        assert_eq!(ranges, vec![(13, 18)]);
+        assert!(content.ends_with("        Self { name: name.to_string() }\n    }"));
+
+        let (_, content) = extract_symbol(&RUST, RUST_SOURCE, "Name").unwrap();
+        assert_eq!(content, format!("{imports}pub type Name = String;"));
+
+        assert!(extract_symbol(&RUST, RUST_SOURCE, "Agent::missing").is_none());
+        assert!(extract_symbol(&RUST, RUST_SOURCE, "fmt").is_none());
+    }
+
+    #[test]
+    fn test_extract_python_and_typescript_symbols() {
+        let python = "import os\nfrom typing import (\n    Any,\n)\n\n\nclass Agent:\n    \"\"\"An agent.\n\ndef fake():\n\"\"\"\n\n    @property\n    def name(\n        self,\n    ) -> str:\n        return 'x'\n\n    def run(self):\n        pass\n\n\ndef main():\n    Agent().run()\n";
+        let (ranges, content) = extract_symbol(&PYTHON, python, "Agent.name").unwrap();
+        assert_eq!(ranges, vec![(12, 16)]);
+        assert_eq!(
+            content,
+            "import os\nfrom typing import (\n    Any,\n)\n\n    @property\n    def name(\n        self,\n    ) -> str:\n        return 'x'"
+        );
+        let (ranges, _) = extract_symbol(&PYTHON, python, "Agent").unwrap();
+        assert_eq!(ranges, vec![(6, 19)]);
+        assert!(extract_symbol(&PYTHON, python, "fake").is_none());
+
+        let typescript = "import { a } from './a';\n\nexport class App {\n  render(): string {\n    return `}`;\n  }\n\n  run() {\n    this.render();\n  }\n}\n\nexport type Props = {\n  name: string;\n};\n";
+        let (ranges, _) = extract_symbol(&TYPESCRIPT, typescript, "App.render").unwrap();
+        assert_eq!(ranges, vec![(3, 5)]);
+        let (ranges, content) = extract_symbol(&TYPESCRIPT, typescript, "Props").unwrap();
+        assert_eq!(ranges, vec![(12, 14)]);
+        assert_eq!(
+            content,
+            "import { a } from './a';\n\nexport type Props = {\n  name: string;\n};"
+        );
+    }
+
+    #[test]
+    fn test_extract_go_and_c_symbols() {
+        let go = "package agent\n\nimport \"fmt\"\n\n// Agent runs prompts.\ntype Agent struct {\n\tName string\n}\n\nfunc (a *Agent) Run() {\n\tfmt.Println(\"}\")\n}\n";
+        let (ranges, content) = extract_symbol(&GO, go, "Agent").unwrap();
+        assert_eq!(ranges, vec![(4, 7)]);
+        assert!(content.starts_with("package agent\nimport \"fmt\"\n\n// Agent runs prompts."));
+        let (ranges, _) = extract_symbol(&GO, go, "Run").unwrap();
+        assert_eq!(ranges, vec![(9, 11)]);
+
+        let c = "#include <stdio.h>\n\nstruct agent {\n    int id;\n};\n\nstatic int *run(struct agent *a) {\n    return 0;\n}\n";
+        let (ranges, content) = extract_symbol(&C, c, "agent").unwrap();
+        assert_eq!(ranges, vec![(2, 4)], "the parameter type is not a definition");
+        assert_eq!(content, "#include <stdio.h>\n\nstruct agent {\n    int id;\n};");
+        let (ranges, _) = extract_symbol(&C, c, "run").unwrap();
+        assert_eq!(ranges, vec![(6, 8)]);
+    }
+
+    #[test]
+    fn test_parse_symbol_spec() {
+        let symbol = SymbolContext::parse("src/agent.rs::Agent::new").unwrap();
+        assert_eq!(symbol.path, "src/agent.rs");
+        assert_eq!(symbol.symbol, "Agent::new");
+        assert!(symbol.matches("src/agent.rs::Agent::new"));
+        assert!(SymbolContext::parse("src/agent.rs").is_err());
+        assert!(SymbolContext::parse("src/agent.rs::").is_err());
+    }
+}
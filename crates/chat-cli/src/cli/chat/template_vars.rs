@@ -0,0 +1,90 @@
+//! Variables interpolated in the prompt and resource paths of agents, so that one agent config can
+//! adapt to the environment it runs in. Variables are written `{{name}}` and resolved each time a
+//! request is sent:
+//! - `{{git_branch}}`: the branch checked out in the current directory
+//! - `{{os}}` and `{{arch}}`: the operating system and CPU architecture, e.g. `linux` and `x86_64`
+//! - `{{date}}`: the current date, e.g. `2025-01-31`
+//! - `{{cwd}}`: the current directory
+//! - `{{env:NAME}}`: the value of the environment variable `NAME`
+//!
+//! Unknown variables are left as is. Variables that can't be resolved, e.g. `{{git_branch}}`
+//! outside of a git repository, are replaced with an empty string.
+
+use std::sync::LazyLock;
+
+use chrono::Local;
+use regex::{
+    Captures,
+    Regex,
+};
+
+use super::git_context::current_branch;
+use crate::os::Os;
+
+static VARIABLE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\{\{\s*(git_branch|os|arch|date|cwd|env:[A-Za-z_][A-Za-z0-9_]*)\s*\}\}").unwrap());
+
+/// Replaces the variables of `template` with their current value.
+pub async fn interpolate(os: &Os, template: &str) -> String {
+    if !VARIABLE.is_match(template) {
+        return template.to_string();
+    }
+
+    let cwd = os.env.current_dir().ok();
+    // Only runs git when the branch is needed
+    let git_branch = match (template.contains("git_branch"), &cwd) {
+        (true, Some(cwd)) => current_branch(cwd).await.unwrap_or_default(),
+        _ => String::new(),
+    };
+
+    VARIABLE
+        .replace_all(template, |captures: &Captures<'_>| match &captures[1] {
+            "git_branch" => git_branch.clone(),
+            "os" => std::env::consts::OS.to_string(),
+            "arch" => std::env::consts::ARCH.to_string(),
+            "date" => Local::now().format("%Y-%m-%d").to_string(),
+            "cwd" => cwd
+                .as_ref()
+                .map(|cwd| cwd.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            var => var
+                .strip_prefix("env:")
+                .and_then(|name| os.env.get(name).ok())
+                .unwrap_or_default(),
+        })
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_interpolate() {
+        let os = Os::new().await.unwrap();
+        unsafe {
+            os.env.set_var("TEAM", "platform");
+        }
+
+        assert_eq!(
+            interpolate(
+                &os,
+                "You help the {{env:TEAM}} team on {{ os }}/{{arch}}.{{env:MISSING}}"
+            )
+            .await,
+            format!(
+                "You help the platform team on {}/{}.",
+                std::env::consts::OS,
+                std::env::consts::ARCH
+            )
+        );
+        assert_eq!(
+            interpolate(&os, "Today is {{date}}").await,
+            format!("Today is {}", Local::now().format("%Y-%m-%d"))
+        );
+        assert_eq!(
+            interpolate(&os, "{{unknown}} and {{ env:1 }} are kept").await,
+            "{{unknown}} and {{ env:1 }} are kept"
+        );
+    }
+}
@@ -1,4 +1,12 @@
-use std::ops::Deref;
+use std::collections::HashMap;
+use std::ops::{
+    Deref,
+    RangeInclusive,
+};
+use std::sync::{
+    LazyLock,
+    RwLock,
+};
 
 use super::message::{
     AssistantMessage,
@@ -66,6 +74,14 @@ impl From<CharCount> for TokenCount {
     }
 }
 
+impl TokenCount {
+    /// Estimates the number of tokens `chars` amount to for the model `model_id`, see
+    /// [TokenCounter::count_model_tokens].
+    pub fn for_model(model_id: Option<&str>, chars: CharCount) -> Self {
+        Self(TokenCounter::count_model_tokens(model_id, chars.value()))
+    }
+}
+
 impl std::fmt::Display for TokenCount {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.0)
@@ -75,6 +91,13 @@ impl std::fmt::Display for TokenCount {
 pub struct TokenCounter;
 
 impl TokenCounter {
+    /// Observed ratios outside of this range are ignored, e.g. for requests mostly made of images.
+    const CALIBRATION_RANGE: RangeInclusive<f64> = 1.0..=10.0;
+    /// Weight of each new observation in the calibrated ratio of a model.
+    const CALIBRATION_WEIGHT: f64 = 0.3;
+    /// Requests smaller than this are ignored when calibrating, as the tokens of the system prompt
+    /// and tool specs, which aren't counted in characters, make up most of them.
+    const MIN_CALIBRATION_CHARS: usize = 20_000;
     pub const TOKEN_TO_CHAR_RATIO: usize = 4;
 
     /// Estimates the number of tokens in the input content.
@@ -85,15 +108,72 @@ impl TokenCounter {
         Self::count_tokens_char_count(content.len())
     }
 
-    fn count_tokens_char_count(count: usize) -> usize {
+    pub fn count_tokens_char_count(count: usize) -> usize {
         (count / Self::TOKEN_TO_CHAR_RATIO + 5) / 10 * 10
     }
 
     pub const fn token_to_chars(token: usize) -> usize {
         token * Self::TOKEN_TO_CHAR_RATIO
     }
+
+    /// Number of characters per token of `model_id`. Calibrated from the usage returned by the
+    /// backend when known, [Self::TOKEN_TO_CHAR_RATIO] otherwise.
+    pub fn chars_per_token(model_id: Option<&str>) -> f64 {
+        model_id
+            .and_then(|id| CALIBRATION.read().ok()?.get(id).copied())
+            .unwrap_or(Self::TOKEN_TO_CHAR_RATIO as f64)
+    }
+
+    /// Same as [Self::count_tokens_char_count], using the calibrated ratio of `model_id`.
+    pub fn count_model_tokens(model_id: Option<&str>, count: usize) -> usize {
+        ((count as f64 / Self::chars_per_token(model_id)) as usize + 5) / 10 * 10
+    }
+
+    /// Same as [Self::token_to_chars], using the calibrated ratio of `model_id`.
+    pub fn model_tokens_to_chars(model_id: Option<&str>, tokens: usize) -> usize {
+        (tokens as f64 * Self::chars_per_token(model_id)) as usize
+    }
+
+    /// Updates the ratio of `model_id` with a request of `char_count` characters the backend
+    /// reported as `input_tokens` tokens. Returns whether the observation was used.
+    pub fn calibrate(model_id: &str, char_count: usize, input_tokens: usize) -> bool {
+        if char_count < Self::MIN_CALIBRATION_CHARS || input_tokens == 0 {
+            return false;
+        }
+        let observed = char_count as f64 / input_tokens as f64;
+        if !Self::CALIBRATION_RANGE.contains(&observed) {
+            return false;
+        }
+
+        let Ok(mut calibration) = CALIBRATION.write() else {
+            return false;
+        };
+        calibration
+            .entry(model_id.to_string())
+            .and_modify(|ratio| *ratio += (observed - *ratio) * Self::CALIBRATION_WEIGHT)
+            .or_insert(observed);
+        true
+    }
+
+    /// The calibrated ratios, by model id.
+    pub fn calibration() -> HashMap<String, f64> {
+        CALIBRATION.read().map(|c| c.clone()).unwrap_or_default()
+    }
+
+    /// Restores ratios calibrated in previous sessions.
+    pub fn set_calibration(calibration: HashMap<String, f64>) {
+        if let Ok(mut current) = CALIBRATION.write() {
+            *current = calibration
+                .into_iter()
+                .filter(|(_, ratio)| Self::CALIBRATION_RANGE.contains(ratio))
+                .collect();
+        }
+    }
 }
 
+/// Characters per token of each model, learned from the input tokens reported by the backend.
+static CALIBRATION: LazyLock<RwLock<HashMap<String, f64>>> = LazyLock::new(Default::default);
+
 /// A trait for types that represent some number of characters (aka bytes). For use in calculating
 /// context window size utilization.
 pub trait CharCounter {
@@ -190,6 +270,28 @@ mod tests {
         assert_eq!(count, (text.len() / 3 + 5) / 10 * 10);
     }
 
+    #[test]
+    fn test_calibration() {
+        let model = "test-calibration-model";
+        assert_eq!(TokenCounter::chars_per_token(Some(model)), 4.0);
+        assert_eq!(TokenCounter::chars_per_token(None), 4.0);
+
+        // Small requests and outliers are ignored
+        assert!(!TokenCounter::calibrate(model, 1_000, 500));
+        assert!(!TokenCounter::calibrate(model, 100_000, 1_000));
+        assert_eq!(TokenCounter::chars_per_token(Some(model)), 4.0);
+
+        assert!(TokenCounter::calibrate(model, 100_000, 40_000));
+        assert_eq!(TokenCounter::chars_per_token(Some(model)), 2.5);
+        assert_eq!(TokenCounter::count_model_tokens(Some(model), 100_000), 40_000);
+        assert_eq!(TokenCounter::model_tokens_to_chars(Some(model), 40_000), 100_000);
+
+        // Later observations move the ratio gradually
+        assert!(TokenCounter::calibrate(model, 100_000, 20_000));
+        assert!((TokenCounter::chars_per_token(Some(model)) - 3.25).abs() < 1e-9);
+        assert_eq!(TokenCounter::chars_per_token(Some("other-model")), 4.0);
+    }
+
     #[test]
     fn test_calculate_value_char_count() {
         // Test simple types
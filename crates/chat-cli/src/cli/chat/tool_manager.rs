@@ -63,6 +63,7 @@ use crate::cli::agent::{
     Agent,
     McpServerConfig,
 };
+use crate::cli::chat::accessible;
 use crate::cli::chat::cli::prompts::GetPromptError;
 use crate::cli::chat::consts::DUMMY_TOOL_NAME;
 use crate::cli::chat::message::AssistantToolUse;
@@ -78,6 +79,10 @@ use crate::cli::chat::tools::fs_write::FsWrite;
 use crate::cli::chat::tools::gh_issue::GhIssue;
 use crate::cli::chat::tools::introspect::Introspect;
 use crate::cli::chat::tools::knowledge::Knowledge;
+use crate::cli::chat::tools::spawn_subagent::{
+    SpawnSubagent,
+    describe_subagents,
+};
 use crate::cli::chat::tools::thinking::Thinking;
 use crate::cli::chat::tools::todo::TodoList;
 use crate::cli::chat::tools::use_aws::UseAws;
@@ -707,7 +712,8 @@ impl ToolManager {
         let tx = self.loading_status_sender.take();
         let notify = self.notify.take();
         self.schema = {
-            let tool_list = &self.agent.lock().await.tools;
+            let agent = self.agent.lock().await;
+            let tool_list = &agent.tools;
             let is_allow_all = tool_list.len() == 1 && tool_list.first().is_some_and(|n| n == "*");
             let is_allow_native = tool_list.iter().any(|t| t.as_str() == "@builtin");
             let mut tool_specs =
@@ -733,6 +739,11 @@ impl ToolManager {
             if !crate::cli::chat::tools::delegate::Delegate::is_enabled(os) {
                 tool_specs.remove("delegate");
             }
+            if !SpawnSubagent::is_enabled(os) || agent.subagents.is_empty() {
+                tool_specs.remove("spawn_subagent");
+            } else if let Some(spec) = tool_specs.get_mut("spawn_subagent") {
+                spec.description = describe_subagents(&spec.description, &agent);
+            }
 
             #[cfg(windows)]
             {
@@ -880,6 +891,9 @@ impl ToolManager {
             "todo_list" => Tool::Todo(serde_json::from_value::<TodoList>(value.args).map_err(map_err)?),
             // Note that this name is NO LONGER namespaced with server_name{DELIMITER}tool_name
             "delegate" => Tool::Delegate(serde_json::from_value::<Delegate>(value.args).map_err(map_err)?),
+            "spawn_subagent" => {
+                Tool::SpawnSubagent(serde_json::from_value::<SpawnSubagent>(value.args).map_err(map_err)?)
+            },
             name => {
                 // Note: tn_map also has tools that underwent no transformation. In otherwords, if
                 // it is a valid tool name, we should get a hit.
@@ -1176,46 +1190,26 @@ fn spawn_display_task(
                         Ok(Some(recv_result)) => match recv_result {
                             LoadingMsg::Done { name, time } => {
                                 complete += 1;
-                                execute!(
-                                    output,
-                                    cursor::MoveToColumn(0),
-                                    cursor::MoveUp(1),
-                                    terminal::Clear(terminal::ClearType::CurrentLine),
-                                )?;
+                                clear_init_message(&mut output)?;
                                 queue_success_message(&name, &time, &mut output)?;
                                 queue_init_message(spinner_logo_idx, complete, failed, total, &mut output)?;
                             },
                             LoadingMsg::Error { name, msg, time } => {
                                 failed += 1;
-                                execute!(
-                                    output,
-                                    cursor::MoveToColumn(0),
-                                    cursor::MoveUp(1),
-                                    terminal::Clear(terminal::ClearType::CurrentLine),
-                                )?;
+                                clear_init_message(&mut output)?;
                                 queue_failure_message(&name, &msg, time.as_str(), &mut output)?;
                                 queue_init_message(spinner_logo_idx, complete, failed, total, &mut output)?;
                             },
                             LoadingMsg::Warn { name, msg, time } => {
                                 complete += 1;
-                                execute!(
-                                    output,
-                                    cursor::MoveToColumn(0),
-                                    cursor::MoveUp(1),
-                                    terminal::Clear(terminal::ClearType::CurrentLine),
-                                )?;
+                                clear_init_message(&mut output)?;
                                 let msg = eyre::eyre!(msg.to_string());
                                 queue_warn_message(&name, &msg, time.as_str(), &mut output)?;
                                 queue_init_message(spinner_logo_idx, complete, failed, total, &mut output)?;
                             },
                             LoadingMsg::Terminate { still_loading } => {
                                 if !still_loading.is_empty() && total > 0 {
-                                    execute!(
-                                        output,
-                                        cursor::MoveToColumn(0),
-                                        cursor::MoveUp(1),
-                                        terminal::Clear(terminal::ClearType::CurrentLine),
-                                    )?;
+                                    clear_init_message(&mut output)?;
                                     let msg = still_loading.iter().fold(String::new(), |mut acc, server_name| {
                                         acc.push_str(format!("\n - {server_name}").as_str());
                                         acc
@@ -1224,27 +1218,19 @@ fn spawn_display_task(
                                     queue_incomplete_load_message(complete, total, &msg, &mut output)?;
                                 } else if total > 0 {
                                     // Clear the loading line if we have enabled servers
-                                    execute!(
-                                        output,
-                                        cursor::MoveToColumn(0),
-                                        cursor::MoveUp(1),
-                                        terminal::Clear(terminal::ClearType::CurrentLine),
-                                    )?;
+                                    clear_init_message(&mut output)?;
                                 }
                                 execute!(output, style::Print("\n"),)?;
                                 break;
                             },
                             LoadingMsg::SignInNotice { name } => {
-                                execute!(
-                                    output,
-                                    cursor::MoveToColumn(0),
-                                    cursor::MoveUp(1),
-                                    terminal::Clear(terminal::ClearType::CurrentLine),
-                                )?;
+                                clear_init_message(&mut output)?;
                                 queue_oauth_message(&name, &mut output)?;
                                 queue_init_message(spinner_logo_idx, complete, failed, total, &mut output)?;
                             },
                         },
+                        // The status line is printed once per change in accessible mode
+                        Err(_e) if accessible::is_enabled() => (),
                         Err(_e) => {
                             spinner_logo_idx = (spinner_logo_idx + 1) % SPINNER_CHARS.len();
                             execute!(
@@ -1903,7 +1889,7 @@ fn queue_success_message(name: &str, time_taken: &str, output: &mut impl Write)
     Ok(queue!(
         output,
         StyledText::success_fg(),
-        style::Print("✓ "),
+        style::Print(accessible::symbol("✓ ", "Done: ")),
         StyledText::info_fg(),
         style::Print(name),
         StyledText::reset(),
@@ -1914,6 +1900,20 @@ fn queue_success_message(name: &str, time_taken: &str, output: &mut impl Write)
     )?)
 }
 
+/// Clears the line of [queue_init_message] before it is printed again. In accessible mode, it is
+/// printed on a new line instead.
+fn clear_init_message(output: &mut impl Write) -> eyre::Result<()> {
+    if accessible::is_enabled() {
+        return Ok(());
+    }
+    Ok(execute!(
+        output,
+        cursor::MoveToColumn(0),
+        cursor::MoveUp(1),
+        terminal::Clear(terminal::ClearType::CurrentLine),
+    )?)
+}
+
 fn queue_init_message(
     spinner_logo_idx: usize,
     complete: usize,
@@ -1921,6 +1921,17 @@ fn queue_init_message(
     total: usize,
     output: &mut impl Write,
 ) -> eyre::Result<()> {
+    if accessible::is_enabled() {
+        let hint = if total > complete + failed {
+            " Press ctrl-c to start chatting now."
+        } else {
+            ""
+        };
+        return Ok(queue!(
+            output,
+            style::Print(format!("{complete} of {total} mcp servers initialized.{hint}\n"))
+        )?);
+    }
     if total == complete {
         queue!(output, StyledText::success_fg(), style::Print("✓"), StyledText::reset(),)?;
     } else if total == complete + failed {
@@ -1961,7 +1972,7 @@ fn queue_failure_message(
     Ok(queue!(
         output,
         StyledText::error_fg(),
-        style::Print("✗ "),
+        style::Print(accessible::symbol("✗ ", "Error: ")),
         StyledText::info_fg(),
         style::Print(name),
         StyledText::reset(),
@@ -1983,7 +1994,7 @@ fn queue_oauth_message(name: &str, output: &mut impl Write) -> eyre::Result<()>
     Ok(queue!(
         output,
         StyledText::warning_fg(),
-        style::Print("⚠ "),
+        style::Print(accessible::symbol("⚠ ", "Warning: ")),
         StyledText::info_fg(),
         style::Print(name),
         StyledText::reset(),
@@ -1995,7 +2006,7 @@ fn queue_oauth_message_with_link(name: &str, msg: &eyre::Report, output: &mut im
     Ok(queue!(
         output,
         StyledText::warning_fg(),
-        style::Print("⚠ "),
+        style::Print(accessible::symbol("⚠ ", "Warning: ")),
         StyledText::info_fg(),
         style::Print(name),
         StyledText::reset(),
@@ -2011,7 +2022,7 @@ fn queue_warn_message(name: &str, msg: &eyre::Report, time: &str, output: &mut i
     Ok(queue!(
         output,
         StyledText::warning_fg(),
-        style::Print("⚠ "),
+        style::Print(accessible::symbol("⚠ ", "Warning: ")),
         StyledText::info_fg(),
         style::Print(name),
         StyledText::reset(),
@@ -2029,7 +2040,7 @@ fn queue_disabled_message(name: &str, output: &mut impl Write) -> eyre::Result<(
     Ok(queue!(
         output,
         StyledText::secondary_fg(),
-        style::Print("○ "),
+        style::Print(accessible::symbol("○ ", "Disabled: ")),
         StyledText::info_fg(),
         style::Print(name),
         StyledText::reset(),
@@ -2047,9 +2058,9 @@ fn queue_incomplete_load_message(
     Ok(queue!(
         output,
         StyledText::warning_fg(),
-        style::Print("⚠"),
+        style::Print(accessible::symbol("⚠ ", "Warning: ")),
         StyledText::info_fg(),
-        style::Print(format!(" {}", complete)),
+        style::Print(complete.to_string()),
         StyledText::reset(),
         style::Print(" of "),
         StyledText::info_fg(),
@@ -0,0 +1,207 @@
+//! Digests replacing large tool results when compacting the conversation. Tool outputs, such as
+//! file contents and command output, take up most of the context window in agentic sessions, and
+//! the model rarely needs them verbatim once acted upon. A digest keeps what is needed to run the
+//! tool again: the tool and its input, the paths involved, the exit status and a short excerpt.
+
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+use super::message::{
+    AssistantToolUse,
+    ToolUseResult,
+    ToolUseResultBlock,
+};
+use super::token_counter::TokenCounter;
+use super::util::truncate_safe;
+use crate::api_client::model::ToolResultStatus;
+
+/// Tool results larger than this are replaced with a digest when compacting.
+pub const MAX_UNDIGESTED_TOOL_RESULT_SIZE: usize = TokenCounter::token_to_chars(2000);
+
+/// Tool results larger than this are replaced with a digest by the local fallback used when the
+/// summary can't be created.
+pub const MAX_LOCALLY_COMPACTED_TOOL_RESULT_SIZE: usize = 2_000;
+
+/// Number of lines kept from the start and from the end of the output.
+const EXCERPT_LINES: usize = 5;
+
+/// Maximum length of each line of the excerpts.
+const MAX_EXCERPT_LINE_LENGTH: usize = 200;
+
+/// Maximum number of paths listed.
+const MAX_PATHS: usize = 20;
+
+static PATH: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?:\.{0,2}/)?(?:[\w.-]+/)+[\w.-]+\.[A-Za-z0-9]{1,8}\b").unwrap());
+
+fn block_text(block: &ToolUseResultBlock) -> String {
+    match block {
+        ToolUseResultBlock::Json(value) => serde_json::to_string(value).unwrap_or_default(),
+        ToolUseResultBlock::Text(text) => text.clone(),
+    }
+}
+
+/// Text of a block as read by the model, with the string fields of JSON objects, such as the
+/// stdout of a command, unescaped.
+fn block_output(block: &ToolUseResultBlock) -> String {
+    match block {
+        ToolUseResultBlock::Json(serde_json::Value::Object(map)) => map
+            .iter()
+            .filter_map(|(key, value)| match value {
+                serde_json::Value::String(s) if s.is_empty() => None,
+                serde_json::Value::String(s) => Some(format!("{key}:\n{s}")),
+                value => Some(format!("{key}: {value}")),
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        block => block_text(block),
+    }
+}
+
+/// Size of the content of a tool result.
+pub fn tool_result_size(result: &ToolUseResult) -> usize {
+    result.content.iter().map(|block| block_text(block).len()).sum()
+}
+
+/// Whether `result` is a digest created by [digest_tool_result].
+pub fn is_digest(result: &ToolUseResult) -> bool {
+    matches!(result.content.as_slice(), [ToolUseResultBlock::Text(text)] if text.starts_with(DIGEST_HEADER))
+}
+
+const DIGEST_HEADER: &str = "[Tool output collapsed during compaction";
+
+/// Collects the string values of `input` stored under keys naming paths.
+fn input_paths(input: &serde_json::Value, key: Option<&str>, paths: &mut Vec<String>) {
+    match input {
+        serde_json::Value::String(s) if key.is_some_and(|k| k.contains("path") || k.contains("file")) => {
+            paths.push(s.clone());
+        },
+        serde_json::Value::Array(values) => {
+            for value in values {
+                input_paths(value, key, paths);
+            }
+        },
+        serde_json::Value::Object(map) => {
+            for (k, value) in map {
+                input_paths(value, Some(k.to_lowercase().as_str()), paths);
+            }
+        },
+        _ => {},
+    }
+}
+
+/// Returns a digest of `result`, produced by `tool_use` if known.
+pub fn digest_tool_result(result: &ToolUseResult, tool_use: Option<&AssistantToolUse>) -> String {
+    let output = result.content.iter().map(block_output).collect::<Vec<_>>().join("\n");
+    let lines = output.lines().collect::<Vec<_>>();
+
+    let mut res = format!(
+        "{DIGEST_HEADER}: {} chars, {} lines",
+        tool_result_size(result),
+        lines.len()
+    );
+    if let Some(tool_use) = tool_use {
+        res.push_str(&format!(", from {}", tool_use.name));
+    }
+    res.push_str("]\n");
+    if matches!(result.status, ToolResultStatus::Error) {
+        res.push_str("Status: error\n");
+    }
+
+    let exit_status = result.content.iter().find_map(|block| match block {
+        ToolUseResultBlock::Json(value) => value.get("exit_status").map(|s| match s {
+            serde_json::Value::String(s) => s.clone(),
+            other => other.to_string(),
+        }),
+        ToolUseResultBlock::Text(_) => None,
+    });
+    if let Some(exit_status) = exit_status {
+        res.push_str(&format!("Exit status: {exit_status}\n"));
+    }
+
+    let mut paths = Vec::new();
+    if let Some(tool_use) = tool_use {
+        input_paths(&tool_use.args, None, &mut paths);
+        if let Some(command) = tool_use.args.get("command").and_then(|c| c.as_str()) {
+            res.push_str(&format!(
+                "Command: {}\n",
+                truncate_safe(command, MAX_EXCERPT_LINE_LENGTH)
+            ));
+        }
+    }
+    paths.extend(PATH.find_iter(&output).map(|m| m.as_str().to_string()));
+    let mut seen = std::collections::HashSet::new();
+    paths.retain(|p| seen.insert(p.clone()));
+    if !paths.is_empty() {
+        res.push_str("Paths: ");
+        res.push_str(&paths.iter().take(MAX_PATHS).cloned().collect::<Vec<_>>().join(", "));
+        if paths.len() > MAX_PATHS {
+            res.push_str(&format!(" and {} more", paths.len() - MAX_PATHS));
+        }
+        res.push('\n');
+    }
+
+    let excerpt = |lines: &[&str]| {
+        lines
+            .iter()
+            .map(|line| format!("  {}\n", truncate_safe(line, MAX_EXCERPT_LINE_LENGTH)))
+            .collect::<String>()
+    };
+    if lines.len() > 2 * EXCERPT_LINES {
+        res.push_str("Start of the output:\n");
+        res.push_str(&excerpt(&lines[..EXCERPT_LINES]));
+        res.push_str("End of the output:\n");
+        res.push_str(&excerpt(&lines[lines.len() - EXCERPT_LINES..]));
+    } else {
+        res.push_str("Output:\n");
+        res.push_str(&excerpt(&lines));
+    }
+    res.push_str("Run the tool again if the full output is needed.");
+
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_digest_tool_result() {
+        let stdout = (0..400)
+            .map(|i| format!("src/module_{i}.rs:{i}: warning: unused variable"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let result = ToolUseResult {
+            tool_use_id: "1".to_string(),
+            content: vec![ToolUseResultBlock::Json(serde_json::json!({
+                "exit_status": "101",
+                "stdout": stdout,
+                "stderr": "",
+            }))],
+            status: ToolResultStatus::Success,
+        };
+        let tool_use = AssistantToolUse {
+            id: "1".to_string(),
+            name: "execute_bash".to_string(),
+            args: serde_json::json!({ "command": "cargo build", "working_dir": "crates/app" }),
+            ..Default::default()
+        };
+        assert!(tool_result_size(&result) > MAX_UNDIGESTED_TOOL_RESULT_SIZE);
+
+        let digest = digest_tool_result(&result, Some(&tool_use));
+        assert!(digest.len() < 2000, "{digest}");
+        assert!(digest.contains("from execute_bash]"));
+        assert!(digest.contains("Exit status: 101\n"));
+        assert!(digest.contains("Command: cargo build\n"));
+        assert!(digest.contains("Paths: src/module_0.rs, src/module_1.rs"));
+        assert!(digest.contains(" and 380 more\n"));
+
+        let result = ToolUseResult {
+            tool_use_id: "1".to_string(),
+            content: vec![ToolUseResultBlock::Text(digest)],
+            status: ToolResultStatus::Success,
+        };
+        assert!(is_digest(&result));
+    }
+}
@@ -1,32 +1,41 @@
 use std::borrow::Cow;
 use std::collections::HashMap;
 use std::io::Write;
+use std::time::Instant;
 
 use crossterm::{
     queue,
     style,
 };
 use eyre::Result;
+use opentelemetry::KeyValue;
 use rmcp::model::CallToolRequestParam;
 use schemars::JsonSchema;
 use serde::{
     Deserialize,
     Serialize,
 };
-use tracing::warn;
+use tracing::{
+    Instrument,
+    warn,
+};
 
 use super::InvokeOutput;
 use crate::cli::agent::{
     Agent,
     PermissionEvalResult,
 };
-use crate::cli::chat::CONTINUATION_LINE;
+use crate::cli::chat::continuation_line;
 use crate::cli::chat::token_counter::TokenCounter;
 use crate::mcp_client::{
     RunningService,
     oauth_util,
 };
 use crate::os::Os;
+use crate::telemetry::otel::{
+    self,
+    Operation,
+};
 use crate::theme::StyledText;
 use crate::util::MCP_SERVER_TOOL_DELIMITER;
 
@@ -132,7 +141,20 @@ impl CustomTool {
             arguments: self.params.clone(),
         };
 
-        let resp = self.client.call_tool(params.clone()).await?;
+        let start = Instant::now();
+        let span =
+            tracing::info_span!(target: otel::SPAN_TARGET, "mcp_call", server = self.server_name, tool = self.name);
+        let resp = self.client.call_tool(params.clone()).instrument(span).await;
+        otel::record(
+            Operation::McpCall,
+            start.elapsed(),
+            resp.as_ref().is_ok_and(|resp| resp.is_error.is_none_or(|v| !v)),
+            &[
+                KeyValue::new("server", self.server_name.clone()),
+                KeyValue::new("tool", self.name.clone()),
+            ],
+        );
+        let resp = resp?;
 
         if resp.is_error.is_none_or(|v| !v) {
             Ok(InvokeOutput {
@@ -146,7 +168,7 @@ impl CustomTool {
         }
     }
 
-    pub fn queue_description(&self, output: &mut impl Write) -> Result<()> {
+    pub fn queue_description(&self, agent: Option<&Agent>, output: &mut impl Write) -> Result<()> {
         queue!(
             output,
             style::Print("Running "),
@@ -154,11 +176,12 @@ impl CustomTool {
             style::Print(&self.name),
             StyledText::reset(),
         )?;
+
         if let Some(params) = &self.params {
             let params = match serde_json::to_string_pretty(params) {
                 Ok(params) => params
                     .split("\n")
-                    .map(|p| format!("{CONTINUATION_LINE} {p}"))
+                    .map(|p| format!("{} {p}", continuation_line()))
                     .collect::<Vec<_>>()
                     .join("\n"),
                 _ => format!("{:?}", params),
@@ -173,6 +196,9 @@ impl CustomTool {
         } else {
             queue!(output, style::Print("\n"))?;
         }
+        if let Some(hosts) = agent.and_then(|agent| self.network_hosts(agent)) {
+            super::queue_network_hosts(&hosts, output)?;
+        }
         Ok(())
     }
 
@@ -186,6 +212,22 @@ impl CustomTool {
         )
     }
 
+    /// Returns the host of the MCP server if it is reached over http, or [None] if the server runs
+    /// locally.
+    pub fn network_hosts(&self, agent: &Agent) -> Option<Vec<String>> {
+        let config = agent.mcp_servers.mcp_servers.get(&self.server_name)?;
+        match config.r#type {
+            TransportType::Http => Some(
+                url::Url::parse(&config.url)
+                    .ok()
+                    .and_then(|url| url.host_str().map(str::to_lowercase))
+                    .into_iter()
+                    .collect(),
+            ),
+            TransportType::Stdio => None,
+        }
+    }
+
     pub fn eval_perm(&self, _os: &Os, agent: &Agent) -> PermissionEvalResult {
         use crate::util::tool_permission_checker::is_tool_in_allowlist;
 
@@ -0,0 +1,257 @@
+//! Shared board of the delegated tasks. Tasks run concurrently, up to
+//! `chat.delegateMaxConcurrency` at a time, the others being queued until a running one finishes.
+//! Each task records the files it writes to so that the board can report the files several tasks
+//! running at the same time wrote to.
+
+use std::collections::{
+    BTreeMap,
+    BTreeSet,
+};
+
+use chrono::{
+    DateTime,
+    Duration,
+    Utc,
+};
+
+use super::{
+    AgentExecution,
+    AgentStatus,
+};
+
+/// Number of delegated tasks running at the same time when `chat.delegateMaxConcurrency` isn't set
+pub const DEFAULT_MAX_CONCURRENCY: usize = 3;
+
+/// Time after which finished tasks the user was notified of are removed from the board
+const STALE_AFTER_HOURS: i64 = 24;
+
+/// A file written to by several tasks that ran at the same time
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    pub path: String,
+    /// Ids of the tasks that wrote to the file
+    pub tasks: Vec<String>,
+}
+
+/// Id of the next task of `agent`, e.g. `rust-agent-3` after `rust-agent-2`.
+pub fn next_task_id(agent: &str, board: &[AgentExecution]) -> String {
+    let prefix = format!("{agent}-");
+    let last = board
+        .iter()
+        .filter_map(|execution| execution.id.strip_prefix(&prefix)?.parse::<u32>().ok())
+        .max()
+        .unwrap_or(0);
+    format!("{agent}-{}", last + 1)
+}
+
+/// Ids of the queued tasks to start, oldest first, so that at most `max_concurrency` tasks run.
+pub fn tasks_to_start(board: &[AgentExecution], max_concurrency: usize) -> Vec<String> {
+    let running = board
+        .iter()
+        .filter(|execution| matches!(execution.status, AgentStatus::Running | AgentStatus::Paused))
+        .count();
+    let mut queued = board
+        .iter()
+        .filter(|execution| execution.status == AgentStatus::Queued)
+        .collect::<Vec<_>>();
+    queued.sort_by_key(|execution| execution.launched_at);
+    queued
+        .into_iter()
+        .take(max_concurrency.saturating_sub(running))
+        .map(|execution| execution.id.clone())
+        .collect()
+}
+
+/// Whether a finished task the user was notified of can be removed from the board.
+pub fn is_stale(execution: &AgentExecution, now: DateTime<Utc>) -> bool {
+    execution.user_notified
+        && execution
+            .completed_at
+            .is_some_and(|completed_at| now - completed_at > Duration::hours(STALE_AFTER_HOURS))
+}
+
+/// Finds the files written to by tasks whose runs overlapped.
+pub fn find_conflicts(board: &[AgentExecution], now: DateTime<Utc>) -> Vec<Conflict> {
+    let mut writers = BTreeMap::<&str, Vec<&AgentExecution>>::new();
+    for execution in board {
+        for path in &execution.touched_files {
+            writers.entry(path.as_str()).or_default().push(execution);
+        }
+    }
+
+    let overlap = |a: &AgentExecution, b: &AgentExecution| {
+        a.launched_at < b.completed_at.unwrap_or(now) && b.launched_at < a.completed_at.unwrap_or(now)
+    };
+    writers
+        .into_iter()
+        .filter_map(|(path, executions)| {
+            let tasks = executions
+                .iter()
+                .filter(|a| executions.iter().any(|b| a.id != b.id && overlap(a, b)))
+                .map(|execution| execution.id.clone())
+                .collect::<BTreeSet<_>>();
+            (!tasks.is_empty()).then(|| Conflict {
+                path: path.to_string(),
+                tasks: tasks.into_iter().collect(),
+            })
+        })
+        .collect()
+}
+
+/// Formats the board, the tasks being listed in the order they were launched.
+pub fn format_board(
+    board: &[AgentExecution],
+    conflicts: &[Conflict],
+    max_concurrency: usize,
+    now: DateTime<Utc>,
+) -> String {
+    if board.is_empty() {
+        return "The task board is empty".to_string();
+    }
+
+    let count = |status: AgentStatus| board.iter().filter(|execution| execution.status == status).count();
+    let mut output = format!(
+        "Task board ({} running, {} queued, at most {max_concurrency} at a time):\n",
+        count(AgentStatus::Running),
+        count(AgentStatus::Queued)
+    );
+    let mut tasks = board.iter().collect::<Vec<_>>();
+    tasks.sort_by_key(|execution| execution.launched_at);
+    for execution in tasks {
+        let elapsed = execution.completed_at.unwrap_or(now) - execution.launched_at;
+        let status = match execution.status {
+            AgentStatus::Queued => "queued".to_string(),
+            status => format!("{status} {}m", elapsed.num_minutes()),
+        };
+        output.push_str(&format!(
+            "- {} [{status}] {} in {}: {}\n",
+            execution.id, execution.agent, execution.cwd, execution.task
+        ));
+    }
+
+    if !conflicts.is_empty() {
+        output.push_str("\nConflicts (files written to by tasks running at the same time):\n");
+        for conflict in conflicts {
+            output.push_str(&format!("- {} by {}\n", conflict.path, conflict.tasks.join(", ")));
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn execution(id: &str, status: AgentStatus, launched_min: i64, completed_min: Option<i64>) -> AgentExecution {
+        let start = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        AgentExecution {
+            id: id.to_string(),
+            agent: id.rsplit_once('-').map_or(id, |(agent, _)| agent).to_string(),
+            task: format!("task of {id}"),
+            status,
+            launched_at: start + Duration::minutes(launched_min),
+            completed_at: completed_min.map(|min| start + Duration::minutes(min)),
+            cwd: "/repo".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_next_task_id() {
+        let board = vec![
+            execution("rust-agent-1", AgentStatus::Completed, 0, Some(5)),
+            execution("rust-agent-4", AgentStatus::Running, 10, None),
+            execution("rust-3", AgentStatus::Running, 10, None),
+            // Tasks launched before the board was shared are named after their agent
+            execution("rust", AgentStatus::Completed, 0, Some(1)),
+        ];
+        assert_eq!(next_task_id("rust-agent", &board), "rust-agent-5");
+        assert_eq!(next_task_id("rust", &board), "rust-4");
+        assert_eq!(next_task_id("docs", &board), "docs-1");
+    }
+
+    #[test]
+    fn test_tasks_to_start() {
+        let board = vec![
+            execution("a-1", AgentStatus::Running, 0, None),
+            execution("a-2", AgentStatus::Queued, 3, None),
+            execution("b-1", AgentStatus::Queued, 1, None),
+            execution("b-2", AgentStatus::Queued, 2, None),
+            execution("c-1", AgentStatus::Completed, 0, Some(1)),
+        ];
+        assert_eq!(tasks_to_start(&board, 3), vec!["b-1", "b-2"]);
+        let mut paused = board.clone();
+        paused[0].status = AgentStatus::Paused;
+        assert_eq!(tasks_to_start(&paused, 3), vec!["b-1", "b-2"]);
+        assert_eq!(tasks_to_start(&board, 5), vec!["b-1", "b-2", "a-2"]);
+        assert!(tasks_to_start(&board, 1).is_empty());
+    }
+
+    #[test]
+    fn test_find_conflicts() {
+        let now = DateTime::from_timestamp(1_700_000_000, 0).unwrap() + Duration::minutes(60);
+        let touched = |mut execution: AgentExecution, paths: &[&str]| {
+            execution.touched_files = paths.iter().map(|path| (*path).to_string()).collect();
+            execution
+        };
+        let board = vec![
+            touched(execution("a-1", AgentStatus::Completed, 0, Some(10)), &[
+                "/repo/src/lib.rs",
+                "/repo/README.md",
+            ]),
+            touched(execution("b-1", AgentStatus::Running, 5, None), &[
+                "/repo/src/lib.rs",
+                "/repo/src/main.rs",
+            ]),
+            // Ran after a-1 finished
+            touched(execution("c-1", AgentStatus::Completed, 20, Some(30)), &[
+                "/repo/README.md",
+            ]),
+            touched(execution("d-1", AgentStatus::Running, 40, None), &["/repo/src/main.rs"]),
+        ];
+        assert_eq!(find_conflicts(&board, now), vec![
+            Conflict {
+                path: "/repo/src/lib.rs".to_string(),
+                tasks: vec!["a-1".to_string(), "b-1".to_string()],
+            },
+            Conflict {
+                path: "/repo/src/main.rs".to_string(),
+                tasks: vec!["b-1".to_string(), "d-1".to_string()],
+            },
+        ]);
+    }
+
+    #[test]
+    fn test_is_stale() {
+        let now = DateTime::from_timestamp(1_700_000_000, 0).unwrap() + Duration::days(2);
+        let mut finished = execution("a-1", AgentStatus::Completed, 0, Some(10));
+        assert!(!is_stale(&finished, now));
+        finished.user_notified = true;
+        assert!(is_stale(&finished, now));
+        assert!(!is_stale(&finished, now - Duration::days(1)));
+        assert!(!is_stale(&execution("b-1", AgentStatus::Running, 0, None), now));
+    }
+
+    #[test]
+    fn test_format_board() {
+        let now = DateTime::from_timestamp(1_700_000_000, 0).unwrap() + Duration::minutes(12);
+        let board = vec![
+            execution("b-1", AgentStatus::Queued, 11, None),
+            execution("a-1", AgentStatus::Running, 0, None),
+        ];
+        let conflicts = vec![Conflict {
+            path: "/repo/src/lib.rs".to_string(),
+            tasks: vec!["a-1".to_string(), "c-1".to_string()],
+        }];
+        assert_eq!(
+            format_board(&board, &conflicts, 1, now),
+            "Task board (1 running, 1 queued, at most 1 at a time):\n\
+            - a-1 [running 12m] a in /repo: task of a-1\n\
+            - b-1 [queued] b in /repo: task of b-1\n\
+            \n\
+            Conflicts (files written to by tasks running at the same time):\n\
+            - /repo/src/lib.rs by a-1, c-1\n"
+        );
+        assert_eq!(format_board(&[], &[], 3, now), "The task board is empty");
+    }
+}
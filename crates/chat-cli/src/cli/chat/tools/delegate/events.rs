@@ -0,0 +1,240 @@
+//! Structured events of the delegated tasks, so that clients of the structured output, such as the
+//! ones of `q chat serve`, follow the tasks running in the background: a task was spawned,
+//! finished a step, waits for an answer, or ended. Tasks run in processes of their own, so the
+//! session polls the board and the output of the tasks for what changed.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use chat_cli_ui::conduit::{
+    ControlEnd,
+    DestinationStdout,
+};
+use chat_cli_ui::protocol::{
+    Event,
+    SubagentFinished,
+    SubagentSpawned,
+    SubagentStepFinished,
+    SubagentWaiting,
+};
+
+use super::{
+    AgentExecution,
+    AgentStatus,
+    load_board,
+    mailbox,
+    mailbox_path,
+    read_output,
+};
+use crate::os::Os;
+
+/// Time between two looks at the board
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Tool uses of the task found in its output, i.e. its steps.
+fn steps(output: &str) -> Vec<&str> {
+    output
+        .lines()
+        .filter_map(|line| line.find("Using tool:").map(|start| line[start..].trim()))
+        .collect()
+}
+
+fn is_running(status: AgentStatus) -> bool {
+    matches!(status, AgentStatus::Queued | AgentStatus::Running | AgentStatus::Paused)
+}
+
+/// What was reported of a task.
+#[derive(Debug, Default)]
+struct Reported {
+    spawned: bool,
+    steps: usize,
+    question: Option<String>,
+    finished: bool,
+}
+
+/// Tells what changed about the tasks since they were last looked at.
+#[derive(Debug, Default)]
+pub struct EventTracker {
+    tasks: HashMap<String, Reported>,
+}
+
+impl EventTracker {
+    /// Starts from the board as it is, the tasks that already ended not being reported.
+    pub fn new(board: &[AgentExecution]) -> Self {
+        let tasks = board
+            .iter()
+            .filter(|execution| !is_running(execution.status))
+            .map(|execution| {
+                (execution.id.clone(), Reported {
+                    spawned: true,
+                    finished: true,
+                    ..Default::default()
+                })
+            })
+            .collect();
+        Self { tasks }
+    }
+
+    /// Whether there is nothing new to report about the task, without looking at its output.
+    pub fn is_settled(&self, execution: &AgentExecution) -> bool {
+        !is_running(execution.status) && self.tasks.get(&execution.id).is_some_and(|task| task.finished)
+    }
+
+    /// Events of what changed about the task, whose output is `output` and whose unanswered
+    /// question, if any, is `question`.
+    pub fn update(&mut self, execution: &AgentExecution, output: &str, question: Option<&str>) -> Vec<Event> {
+        let mut events = Vec::new();
+        let running = is_running(execution.status);
+        let task = self.tasks.entry(execution.id.clone()).or_default();
+        // New tasks, and interrupted ones continued with `q agent resume`
+        if !task.spawned || (task.finished && running) {
+            events.push(Event::SubagentSpawned(SubagentSpawned {
+                task_id: execution.id.clone(),
+                agent: execution.agent.clone(),
+                subagent: execution.subagent.clone(),
+                task: execution.task.clone(),
+                status: match execution.status {
+                    AgentStatus::Queued => AgentStatus::Queued.to_string(),
+                    _ => AgentStatus::Running.to_string(),
+                },
+            }));
+            task.spawned = true;
+            task.finished = false;
+        }
+
+        let steps = steps(output);
+        // A step finishes once the task moves on to the next one or ends
+        let finished_steps = if running {
+            steps.len().saturating_sub(1)
+        } else {
+            steps.len()
+        };
+        for (index, step) in steps.iter().enumerate().take(finished_steps).skip(task.steps) {
+            events.push(Event::SubagentStepFinished(SubagentStepFinished {
+                task_id: execution.id.clone(),
+                step: index + 1,
+                description: (*step).to_string(),
+            }));
+        }
+        task.steps = task.steps.max(finished_steps);
+
+        let question = question.filter(|_| running).map(str::to_string);
+        if question != task.question {
+            if let Some(question) = &question {
+                events.push(Event::SubagentWaiting(SubagentWaiting {
+                    task_id: execution.id.clone(),
+                    question: question.clone(),
+                }));
+            }
+            task.question = question;
+        }
+
+        if !running && !task.finished {
+            events.push(Event::SubagentFinished(SubagentFinished {
+                task_id: execution.id.clone(),
+                status: execution.status.to_string(),
+                exit_code: execution.exit_code,
+                summary: execution.summary.clone(),
+            }));
+            task.finished = true;
+        }
+        events
+    }
+}
+
+/// Sends the events of the delegated tasks to `events` until the session ends.
+pub async fn watch(os: Os, events: ControlEnd<DestinationStdout>) {
+    let mut tracker = EventTracker::new(&load_board(&os).await.unwrap_or_default());
+    loop {
+        tokio::time::sleep(POLL_INTERVAL).await;
+        let Ok(board) = load_board(&os).await else {
+            continue;
+        };
+        for execution in &board {
+            if tracker.is_settled(execution) {
+                continue;
+            }
+            let output = read_output(&os, &execution.id).await;
+            let messages = match mailbox_path(&os, &execution.id).await {
+                Ok(path) => mailbox::read(&os.fs.chroot_path(path)).await,
+                Err(_) => Vec::new(),
+            };
+            let question = mailbox::pending_question(&messages).map(|question| question.text.as_str());
+            for event in tracker.update(execution, &output, question) {
+                // The session ended
+                if events.send(event).is_err() {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn execution(id: &str, status: AgentStatus) -> AgentExecution {
+        AgentExecution {
+            id: id.to_string(),
+            agent: "rust-agent".to_string(),
+            task: "Fix the parser".to_string(),
+            status,
+            ..Default::default()
+        }
+    }
+
+    fn event_types(events: &[Event]) -> Vec<&'static str> {
+        events.iter().map(Event::event_type).collect()
+    }
+
+    #[test]
+    fn test_update() {
+        let done = execution("rust-agent-1", AgentStatus::Completed);
+        let mut tracker = EventTracker::new(std::slice::from_ref(&done));
+        assert!(tracker.is_settled(&done));
+
+        let mut task = execution("rust-agent-2", AgentStatus::Queued);
+        assert_eq!(event_types(&tracker.update(&task, "", None)), vec!["subagentSpawned"]);
+        assert!(tracker.update(&task, "", None).is_empty());
+
+        task.status = AgentStatus::Running;
+        let output = "🛠️  Using tool: fs_read\nreading\n🛠️  Using tool: fs_write\n";
+        let events = tracker.update(&task, output, None);
+        assert_eq!(events.len(), 1);
+        let Event::SubagentStepFinished(step) = &events[0] else {
+            panic!("expected a step, got {events:?}");
+        };
+        assert_eq!((step.step, step.description.as_str()), (1, "Using tool: fs_read"));
+
+        let question = "Paused: the task reached its limit of 20 turns.";
+        assert_eq!(event_types(&tracker.update(&task, output, Some(question))), vec![
+            "subagentWaiting"
+        ]);
+        assert!(tracker.update(&task, output, Some(question)).is_empty());
+
+        task.status = AgentStatus::Completed;
+        task.summary = Some("Fixed the parser".to_string());
+        assert_eq!(event_types(&tracker.update(&task, output, None)), vec![
+            "subagentStepFinished",
+            "subagentFinished"
+        ]);
+        assert!(tracker.is_settled(&task));
+
+        // Continued with q agent resume
+        task.status = AgentStatus::Running;
+        assert_eq!(event_types(&tracker.update(&task, output, None)), vec![
+            "subagentSpawned"
+        ]);
+    }
+
+    #[test]
+    fn test_update_finished_between_looks() {
+        let mut tracker = EventTracker::default();
+        let task = execution("rust-agent-1", AgentStatus::Failed);
+        assert_eq!(event_types(&tracker.update(&task, "", None)), vec![
+            "subagentSpawned",
+            "subagentFinished"
+        ]);
+    }
+}
@@ -0,0 +1,273 @@
+//! Handoff documents of delegated tasks. When a task finishes, what it did, the files it touched,
+//! the follow-ups it left and its open questions are written to `handoffs/<task id>-<time>.md` in
+//! the subagents directory at the root of the repository, or of the directory outside of one. The
+//! next session started in the same repository picks up the handoffs it wasn't given yet, keeping
+//! them in its conversation as context, and moves them to `handoffs/delivered`, which keeps the
+//! latest [MAX_DELIVERED] of them.
+
+use std::path::{
+    Path,
+    PathBuf,
+};
+
+use eyre::Result;
+use serde::Deserialize;
+
+use super::{
+    AgentExecution,
+    staging,
+    subagents_dir,
+};
+use crate::os::Os;
+use crate::util::paths::workspace::SUBAGENTS_DIR;
+
+/// Subdirectory of the subagents directory the handoffs are written to
+const HANDOFFS_DIR: &str = "handoffs";
+/// Subdirectory of [HANDOFFS_DIR] the handoffs given to a session are moved to
+const DELIVERED_DIR: &str = "delivered";
+/// Number of delivered handoffs kept
+const MAX_DELIVERED: usize = 50;
+
+/// What the model made of the output of a finished task.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HandoffNotes {
+    /// What the task did, in 2-3 sentences
+    #[serde(default)]
+    pub summary: String,
+    /// Work the task left to do
+    #[serde(default)]
+    pub follow_ups: Vec<String>,
+    /// Decisions the task couldn't make on its own
+    #[serde(default)]
+    pub open_questions: Vec<String>,
+}
+
+/// Prompt asking the model for the notes of the task.
+pub fn notes_prompt(task: &str, output: &str) -> String {
+    format!(
+        "Summarize this task execution for whoever picks up the work next. Reply with a JSON object only, \
+        with the keys \"summary\" (what happened in 2-3 sentences, focused on key outcomes), \"followUps\" \
+        (the work left to do, as a list of strings) and \"openQuestions\" (the decisions left to the user, \
+        as a list of strings).\n\nTask: {task}\n\nOutput:\n{output}"
+    )
+}
+
+/// Notes in the answer of the model, which may surround the JSON object with text. An answer
+/// without one is taken as the summary.
+pub fn parse_notes(answer: &str) -> HandoffNotes {
+    let json = answer
+        .find('{')
+        .zip(answer.rfind('}'))
+        .and_then(|(start, end)| answer.get(start..=end));
+    match json.and_then(|json| serde_json::from_str::<HandoffNotes>(json).ok()) {
+        Some(notes) if !notes.summary.trim().is_empty() => notes,
+        _ => HandoffNotes {
+            summary: answer.trim().to_string(),
+            ..Default::default()
+        },
+    }
+}
+
+/// Markdown list of `items`, or `None.`
+fn format_list(items: &[String]) -> String {
+    if items.is_empty() {
+        return "None.".to_string();
+    }
+    items
+        .iter()
+        .map(|item| format!("- {item}"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The handoff document of the finished task `execution`.
+pub fn format_handoff(execution: &AgentExecution, notes: &HandoffNotes) -> String {
+    let finished = execution
+        .completed_at
+        .map(|at| format!(" on {}", at.format("%Y-%m-%d %H:%M UTC")))
+        .unwrap_or_default();
+    let files = execution.touched_files.iter().cloned().collect::<Vec<_>>();
+    format!(
+        "# Handoff of task {} (agent {})\n\n\
+        Task: {}\n\
+        Status: {}{finished}, in {}\n\n\
+        ## What was done\n{}\n\n\
+        ## Files touched\n{}\n\n\
+        ## Follow-ups\n{}\n\n\
+        ## Open questions\n{}\n",
+        execution.id,
+        execution.agent,
+        execution.task,
+        execution.status,
+        execution.cwd,
+        notes.summary.trim(),
+        format_list(&files),
+        format_list(&notes.follow_ups),
+        format_list(&notes.open_questions),
+    )
+}
+
+/// Directory of the handoffs, shared by the sessions started anywhere in the same repository.
+async fn handoffs_dir(os: &Os) -> Result<PathBuf> {
+    match staging::repo_root(&os.env.current_dir()?).await {
+        Some(repo_root) => Ok(repo_root.join(SUBAGENTS_DIR).join(HANDOFFS_DIR)),
+        None => Ok(subagents_dir(os).await?.join(HANDOFFS_DIR)),
+    }
+}
+
+/// Name of the handoff document of `execution`, unique even when task ids are reused.
+fn file_name(execution: &AgentExecution) -> String {
+    let finished = execution.completed_at.unwrap_or_else(chrono::Utc::now);
+    format!(
+        "{}-{}-{}.md",
+        execution.id,
+        finished.format("%Y%m%dT%H%M%S"),
+        &uuid::Uuid::new_v4().simple().to_string()[..8]
+    )
+}
+
+/// Writes the handoff document of the finished task `execution`.
+pub async fn save(os: &Os, execution: &AgentExecution, notes: &HandoffNotes) -> Result<()> {
+    let dir = handoffs_dir(os).await?;
+    os.fs.create_dir_all(&dir).await?;
+    os.fs
+        .write(dir.join(file_name(execution)), format_handoff(execution, notes))
+        .await?;
+    Ok(())
+}
+
+/// Documents in `dir`, oldest first.
+async fn list_documents(os: &Os, dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut documents = Vec::new();
+    let mut entries = os.fs.read_dir(dir).await?;
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        // Entry paths include the chroot of the tests
+        let path = dir.join(entry.file_name());
+        if path.extension().is_some_and(|ext| ext == "md") {
+            let modified = entry.metadata().await.and_then(|metadata| metadata.modified()).ok();
+            documents.push((modified, path));
+        }
+    }
+    documents.sort();
+    Ok(documents.into_iter().map(|(_, path)| path).collect())
+}
+
+/// Handoffs of the tasks finished since a session was last started in this directory, oldest
+/// first, moving them to the delivered ones.
+pub async fn take_pending(os: &Os) -> Result<Vec<String>> {
+    let dir = handoffs_dir(os).await?;
+    if !os.fs.exists(&dir) {
+        return Ok(Vec::new());
+    }
+    let pending = list_documents(os, &dir).await?;
+    if pending.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let delivered_dir = dir.join(DELIVERED_DIR);
+    os.fs.create_dir_all(&delivered_dir).await?;
+    let mut handoffs = Vec::new();
+    for path in pending {
+        handoffs.push(os.fs.read_to_string(&path).await?);
+        if let Some(name) = path.file_name() {
+            os.fs.rename(&path, delivered_dir.join(name)).await?;
+        }
+    }
+    prune_delivered(os, &delivered_dir, MAX_DELIVERED).await?;
+    Ok(handoffs)
+}
+
+/// Removes the delivered handoffs but the latest `keep` ones.
+async fn prune_delivered(os: &Os, delivered_dir: &Path, keep: usize) -> Result<()> {
+    let delivered = list_documents(os, delivered_dir).await?;
+    for path in &delivered[..delivered.len().saturating_sub(keep)] {
+        os.fs.remove_file(path).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::cli::chat::tools::delegate::AgentStatus;
+
+    #[test]
+    fn test_parse_notes() {
+        assert_eq!(
+            parse_notes(
+                "Here it is:\n{\"summary\": \"Fixed the parser.\", \"followUps\": [\"Add tests\"], \"openQuestions\": []}"
+            ),
+            HandoffNotes {
+                summary: "Fixed the parser.".to_string(),
+                follow_ups: vec!["Add tests".to_string()],
+                open_questions: Vec::new(),
+            }
+        );
+        assert_eq!(parse_notes("  Fixed the parser.\n"), HandoffNotes {
+            summary: "Fixed the parser.".to_string(),
+            ..Default::default()
+        });
+        assert_eq!(parse_notes("{\"followUps\": []}").summary, "{\"followUps\": []}");
+    }
+
+    #[test]
+    fn test_format_handoff() {
+        let execution = AgentExecution {
+            id: "rust-agent-1".to_string(),
+            agent: "rust-agent".to_string(),
+            task: "Fix the parser".to_string(),
+            status: AgentStatus::Completed,
+            completed_at: Some(chrono::Utc.with_ymd_and_hms(2026, 5, 4, 13, 30, 0).unwrap()),
+            cwd: "/repo".to_string(),
+            touched_files: ["src/parser.rs".to_string()].into(),
+            ..Default::default()
+        };
+        let notes = HandoffNotes {
+            summary: "Fixed the parser.".to_string(),
+            follow_ups: vec!["Add tests".to_string()],
+            open_questions: Vec::new(),
+        };
+        assert_eq!(
+            format_handoff(&execution, &notes),
+            "# Handoff of task rust-agent-1 (agent rust-agent)\n\n\
+            Task: Fix the parser\n\
+            Status: completed on 2026-05-04 13:30 UTC, in /repo\n\n\
+            ## What was done\nFixed the parser.\n\n\
+            ## Files touched\n- src/parser.rs\n\n\
+            ## Follow-ups\n- Add tests\n\n\
+            ## Open questions\nNone.\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_take_pending() {
+        let os = Os::new().await.unwrap();
+        assert!(take_pending(&os).await.unwrap().is_empty());
+
+        let execution = AgentExecution {
+            id: "rust-agent-1".to_string(),
+            ..Default::default()
+        };
+        save(&os, &execution, &HandoffNotes::default()).await.unwrap();
+        let handoffs = take_pending(&os).await.unwrap();
+        assert_eq!(handoffs.len(), 1);
+        assert!(handoffs[0].starts_with("# Handoff of task rust-agent-1"));
+        assert!(take_pending(&os).await.unwrap().is_empty());
+
+        let delivered_dir = handoffs_dir(&os).await.unwrap().join(DELIVERED_DIR);
+        save(&os, &execution, &HandoffNotes::default()).await.unwrap();
+        assert_eq!(take_pending(&os).await.unwrap().len(), 1, "task ids may be reused");
+        let delivered = list_documents(&os, &delivered_dir).await.unwrap();
+        assert_eq!(delivered.len(), 2);
+        assert!(delivered.iter().all(|path| {
+            path.file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with("rust-agent-1-"))
+        }));
+
+        prune_delivered(&os, &delivered_dir, 1).await.unwrap();
+        assert_eq!(list_documents(&os, &delivered_dir).await.unwrap(), delivered[1..]);
+    }
+}
@@ -0,0 +1,318 @@
+//! Resource limits of delegated tasks, on their turns, tool calls, tokens, estimated cost and
+//! duration. The task checks them after every response of its model: once it reaches one of them,
+//! it pauses and asks the session that delegated it whether to carry on, rather than running on or
+//! failing without a word.
+//!
+//! The limits are given to the task in the `Q_DELEGATE_LIMITS` environment variable, as JSON.
+
+use std::fmt;
+use std::time::{
+    Duration,
+    Instant,
+};
+
+use eyre::Result;
+use schemars::JsonSchema;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use super::mailbox;
+use crate::cli::chat::cost::Cost;
+use crate::os::Os;
+use crate::util::consts::env_var::Q_DELEGATE_LIMITS;
+
+/// Answer to a limit question ending the task
+const STOP: &str = "stop";
+/// Answer to a limit question letting the task carry on
+const CONTINUE: &str = "continue";
+
+/// Caps on the resources a delegated task uses. Reaching one of them pauses the task until the
+/// delegating session decides whether it carries on.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct TaskLimits {
+    /// Maximum number of responses of the model
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(range(min = 1))]
+    pub max_turns: Option<usize>,
+    /// Maximum number of tool uses
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(range(min = 1))]
+    pub max_tool_calls: Option<usize>,
+    /// Maximum number of tokens sent to and received from the model
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(range(min = 1))]
+    pub max_tokens: Option<usize>,
+    /// Maximum estimated cost in US dollars, for models of known pricing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_cost: Option<f64>,
+    /// Maximum number of seconds the task runs for. Checked after every response of the model, so
+    /// a task running a long tool use overruns it until the tool use ends
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(range(min = 1))]
+    pub timeout: Option<u64>,
+}
+
+impl TaskLimits {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// The limit a task reached.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TaskLimit {
+    Turns(usize),
+    ToolCalls(usize),
+    Tokens(usize),
+    Cost(f64),
+    Timeout(u64),
+}
+
+impl fmt::Display for TaskLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TaskLimit::Turns(limit) => write!(f, "its limit of {limit} turns"),
+            TaskLimit::ToolCalls(limit) => write!(f, "its limit of {limit} tool calls"),
+            TaskLimit::Tokens(limit) => write!(f, "its limit of {limit} tokens"),
+            TaskLimit::Cost(limit) => write!(f, "its limit of {} of estimated cost", Cost(*limit)),
+            TaskLimit::Timeout(secs) => write!(f, "its limit of {secs} seconds"),
+        }
+    }
+}
+
+/// What the delegating session decided about a task that reached one of its limits.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Decision {
+    /// Carry on with the same limits again
+    Continue,
+    Stop,
+}
+
+/// Counts the resources the task used against its limits, since it started or was last allowed to
+/// carry on.
+#[derive(Debug)]
+pub struct TaskBudget {
+    limits: TaskLimits,
+    start: Instant,
+    turns: usize,
+    tool_calls: usize,
+    /// Tokens and cost of the session when the budget started
+    base_tokens: usize,
+    base_cost: f64,
+}
+
+impl TaskBudget {
+    pub fn new(limits: TaskLimits, start: Instant) -> Self {
+        Self {
+            limits,
+            start,
+            turns: 0,
+            tool_calls: 0,
+            base_tokens: 0,
+            base_cost: 0.0,
+        }
+    }
+
+    /// The budget of this session, when it runs a delegated task with limits.
+    pub fn from_env(os: &Os, start: Instant) -> Option<Self> {
+        let limits = os.env.get(Q_DELEGATE_LIMITS).ok()?;
+        match serde_json::from_str::<TaskLimits>(&limits) {
+            Ok(limits) if !limits.is_empty() => Some(Self::new(limits, start)),
+            Ok(_) => None,
+            Err(err) => {
+                tracing::warn!(?err, "Invalid limits of the delegated task");
+                None
+            },
+        }
+    }
+
+    /// Records a response of the model requesting `tool_uses` tool uses, the session having used
+    /// `tokens` tokens and `cost` US dollars so far. Returns the limit that running the tools would
+    /// exceed. A response without tool uses ends the task, so it never exceeds a limit.
+    pub fn record_response(&mut self, tool_uses: usize, tokens: usize, cost: f64, now: Instant) -> Option<TaskLimit> {
+        self.turns += 1;
+        if tool_uses == 0 {
+            return None;
+        }
+        let limits = &self.limits;
+        if let Some(max) = limits.max_turns.filter(|max| self.turns >= *max) {
+            return Some(TaskLimit::Turns(max));
+        }
+        if let Some(max) = limits.max_tool_calls.filter(|max| self.tool_calls + tool_uses > *max) {
+            return Some(TaskLimit::ToolCalls(max));
+        }
+        if let Some(max) = limits
+            .max_tokens
+            .filter(|max| tokens.saturating_sub(self.base_tokens) >= *max)
+        {
+            return Some(TaskLimit::Tokens(max));
+        }
+        if let Some(max) = limits.max_cost.filter(|max| cost - self.base_cost >= *max) {
+            return Some(TaskLimit::Cost(max));
+        }
+        if let Some(secs) = limits
+            .timeout
+            .filter(|secs| now.duration_since(self.start) >= Duration::from_secs(*secs))
+        {
+            return Some(TaskLimit::Timeout(secs));
+        }
+        self.tool_calls += tool_uses;
+        None
+    }
+
+    /// Grants the task the same limits again, from now on.
+    pub fn renew(&mut self, tokens: usize, cost: f64, now: Instant) {
+        *self = Self {
+            base_tokens: tokens,
+            base_cost: cost,
+            ..Self::new(self.limits, now)
+        };
+    }
+}
+
+/// Question asked to the delegating session once the task reached `limit`.
+pub fn limit_question(limit: &TaskLimit) -> String {
+    format!(
+        "Paused: the task reached {limit}. Answer \"{CONTINUE}\" to let it carry on with the same limits again, \"{STOP}\" to end it, or with guidance to carry on with."
+    )
+}
+
+/// Decision the answers to a limit question stand for: the task stops only when told so.
+pub fn parse_decision(answers: &[mailbox::Message]) -> Decision {
+    match answers.last() {
+        Some(answer) if answer.text.trim().trim_end_matches('.').eq_ignore_ascii_case(STOP) => Decision::Stop,
+        _ => Decision::Continue,
+    }
+}
+
+/// Pauses the task that reached `limit` until the delegating session decides whether it carries
+/// on. A task left without an answer stops. Answers other than the decision itself reach the model
+/// as guidance along with the results of its next tool use.
+pub async fn decide(os: &Os, limit: &TaskLimit) -> Result<Decision> {
+    let Some(answers) = mailbox::wait_for_answer(os, &limit_question(limit)).await? else {
+        return Ok(Decision::Stop);
+    };
+    let decision = parse_decision(&answers);
+    let is_keyword = |message: &mailbox::Message| {
+        let text = message.text.trim().trim_end_matches('.');
+        text.eq_ignore_ascii_case(CONTINUE) || text.eq_ignore_ascii_case(STOP)
+    };
+    if decision == Decision::Stop || answers.iter().all(is_keyword) {
+        mailbox::mark_received(answers.len());
+    }
+    Ok(decision)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::chat::tools::delegate::mailbox::{
+        Message,
+        Sender,
+    };
+
+    #[test]
+    fn test_record_response() {
+        let start = Instant::now();
+        let mut budget = TaskBudget::new(
+            TaskLimits {
+                max_turns: Some(3),
+                ..Default::default()
+            },
+            start,
+        );
+        assert_eq!(budget.record_response(2, 0, 0.0, start), None);
+        assert_eq!(
+            budget.record_response(0, 0, 0.0, start),
+            None,
+            "final answers never pause"
+        );
+        assert_eq!(budget.record_response(1, 0, 0.0, start), Some(TaskLimit::Turns(3)));
+
+        let mut budget = TaskBudget::new(
+            TaskLimits {
+                max_tool_calls: Some(3),
+                max_tokens: Some(1000),
+                max_cost: Some(0.5),
+                timeout: Some(60),
+                ..Default::default()
+            },
+            start,
+        );
+        assert_eq!(budget.record_response(2, 500, 0.1, start), None);
+        assert_eq!(
+            budget.record_response(2, 500, 0.1, start),
+            Some(TaskLimit::ToolCalls(3))
+        );
+        assert_eq!(
+            budget.record_response(1, 1200, 0.1, start),
+            Some(TaskLimit::Tokens(1000))
+        );
+        assert_eq!(budget.record_response(1, 900, 0.6, start), Some(TaskLimit::Cost(0.5)));
+        let later = start + Duration::from_secs(61);
+        assert_eq!(budget.record_response(1, 900, 0.1, later), Some(TaskLimit::Timeout(60)));
+
+        budget.renew(1200, 0.6, later);
+        assert_eq!(budget.record_response(3, 2000, 1.0, later), None);
+    }
+
+    #[tokio::test]
+    async fn test_from_env() {
+        let os = Os::new().await.unwrap();
+        let start = Instant::now();
+        assert!(TaskBudget::from_env(&os, start).is_none());
+        unsafe { os.env.set_var(Q_DELEGATE_LIMITS, r#"{"maxTurns":5,"maxCost":1.5}"#) };
+        let budget = TaskBudget::from_env(&os, start).unwrap();
+        assert_eq!(budget.limits, TaskLimits {
+            max_turns: Some(5),
+            max_cost: Some(1.5),
+            ..Default::default()
+        });
+        unsafe { os.env.set_var(Q_DELEGATE_LIMITS, "{}") };
+        assert!(TaskBudget::from_env(&os, start).is_none());
+    }
+
+    #[test]
+    fn test_limits_schema() {
+        let schema = serde_json::to_value(schemars::schema_for!(TaskLimits)).unwrap();
+        let validator = jsonschema::validator_for(&schema).unwrap();
+        assert!(validator.is_valid(&serde_json::json!({ "maxTurns": 1, "timeout": 60 })));
+        assert!(
+            !validator.is_valid(&serde_json::json!({ "maxTurns": 0 })),
+            "would pause at once"
+        );
+        assert!(!validator.is_valid(&serde_json::json!({ "timeout": 0 })));
+    }
+
+    #[test]
+    fn test_parse_decision() {
+        let answers = |texts: &[&str]| {
+            texts
+                .iter()
+                .map(|text| Message::new(Sender::Parent, text))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(parse_decision(&answers(&["Stop."])), Decision::Stop);
+        assert_eq!(parse_decision(&answers(&["continue"])), Decision::Continue);
+        assert_eq!(
+            parse_decision(&answers(&["Skip the integration tests"])),
+            Decision::Continue
+        );
+        assert_eq!(
+            parse_decision(&answers(&["stop", "actually, go on"])),
+            Decision::Continue
+        );
+    }
+
+    #[test]
+    fn test_limit_display() {
+        assert_eq!(TaskLimit::Turns(20).to_string(), "its limit of 20 turns");
+        assert_eq!(
+            TaskLimit::Cost(2.0).to_string(),
+            format!("its limit of {} of estimated cost", Cost(2.0))
+        );
+    }
+}
@@ -0,0 +1,254 @@
+//! Messages between a delegated task and the session that delegated it. The session sends the
+//! task guidance with `/agents tell` or the `tell` operation, which the task receives as a message
+//! of the user sent along with its next tool results. The task asks the user questions with the
+//! `ask` operation, waiting for the answer sent with `/agents tell`.
+//!
+//! Tasks run in processes of their own, so the messages of a task are appended to its
+//! `<task id>.messages` file, one JSON message per line.
+
+use std::path::Path;
+use std::sync::atomic::{
+    AtomicUsize,
+    Ordering,
+};
+use std::time::Duration;
+
+use chrono::{
+    DateTime,
+    Utc,
+};
+use eyre::Result;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use tokio::io::AsyncWriteExt;
+
+use crate::os::Os;
+use crate::util::consts::env_var::Q_DELEGATE_MAILBOX;
+
+/// Time a task waits for the answer to a question before carrying on without it
+const ASK_TIMEOUT: Duration = Duration::from_secs(15 * 60);
+/// Time between two reads of the messages of the task while waiting for an answer
+const ASK_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Number of the messages of the delegating session this task has received. A process runs at
+/// most one task.
+static RECEIVED: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Sender {
+    /// The session that delegated the task, i.e. the user or the model
+    Parent,
+    Task,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Message {
+    pub from: Sender,
+    pub text: String,
+    #[serde(with = "chrono::serde::ts_seconds")]
+    pub sent_at: DateTime<Utc>,
+}
+
+impl Message {
+    pub fn new(from: Sender, text: &str) -> Self {
+        Self {
+            from,
+            text: text.to_string(),
+            sent_at: Utc::now(),
+        }
+    }
+}
+
+/// Appends the message to the messages of a task.
+pub async fn send(path: &Path, message: &Message) -> Result<()> {
+    let mut file = tokio::fs::File::options().append(true).create(true).open(path).await?;
+    file.write_all(format!("{}\n", serde_json::to_string(message)?).as_bytes())
+        .await?;
+    // tokio writes in the background, the message must be written before it's read
+    file.flush().await?;
+    Ok(())
+}
+
+/// Messages of a task, in the order they were sent. Lines being written are skipped.
+pub async fn read(path: &Path) -> Vec<Message> {
+    tokio::fs::read_to_string(path)
+        .await
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect()
+}
+
+/// The last question of the task, when it wasn't answered yet.
+pub fn pending_question(messages: &[Message]) -> Option<&Message> {
+    messages.last().filter(|message| message.from == Sender::Task)
+}
+
+/// Messages of the delegating session sent after the first `received` ones.
+fn messages_after(messages: &[Message], received: usize) -> Vec<Message> {
+    messages
+        .iter()
+        .filter(|message| message.from == Sender::Parent)
+        .skip(received)
+        .cloned()
+        .collect()
+}
+
+/// Receives the messages the delegating session sent since the last call, when this session runs a
+/// delegated task.
+pub async fn receive(os: &Os) -> Vec<Message> {
+    let Ok(path) = os.env.get(Q_DELEGATE_MAILBOX) else {
+        return Vec::new();
+    };
+    let messages = messages_after(&read(Path::new(&path)).await, RECEIVED.load(Ordering::SeqCst));
+    RECEIVED.fetch_add(messages.len(), Ordering::SeqCst);
+    messages
+}
+
+/// Guidance given to the model of the task as a message of the user, along with its next tool
+/// results.
+pub fn format_guidance(messages: &[Message]) -> Option<String> {
+    if messages.is_empty() {
+        return None;
+    }
+    let texts = messages
+        .iter()
+        .map(|message| format!("- {}", message.text))
+        .collect::<Vec<_>>()
+        .join("\n");
+    Some(format!(
+        "Messages from the user who delegated this task, to take into account from now on:\n{texts}"
+    ))
+}
+
+/// Marks the first `count` messages not received yet as received, e.g. the answers to a question.
+pub fn mark_received(count: usize) {
+    RECEIVED.fetch_add(count, Ordering::SeqCst);
+}
+
+/// Sends the user a question, waiting for the answer sent with `/agents tell`. Returns the
+/// messages the task didn't receive yet, the answer last, without marking them as received, or
+/// [None] when the user didn't answer in time.
+pub async fn wait_for_answer(os: &Os, question: &str) -> Result<Option<Vec<Message>>> {
+    let Ok(path) = os.env.get(Q_DELEGATE_MAILBOX) else {
+        eyre::bail!("Only delegated tasks can ask questions to the user");
+    };
+    let path = Path::new(&path);
+    send(path, &Message::new(Sender::Task, question)).await?;
+
+    let deadline = tokio::time::Instant::now() + ASK_TIMEOUT;
+    while tokio::time::Instant::now() < deadline {
+        tokio::time::sleep(ASK_POLL_INTERVAL).await;
+        let messages = read(path).await;
+        if pending_question(&messages).is_none() {
+            // Along with the guidance sent before the question the task didn't receive yet
+            return Ok(Some(messages_after(&messages, RECEIVED.load(Ordering::SeqCst))));
+        }
+    }
+    Ok(None)
+}
+
+/// Asks the user a question, waiting for the answer sent with `/agents tell`.
+pub async fn ask(os: &Os, question: &str) -> Result<String> {
+    if os.env.get(Q_DELEGATE_MAILBOX).is_err() {
+        eyre::bail!("The ask operation is only available to delegated tasks");
+    }
+    match wait_for_answer(os, question).await? {
+        Some(answers) => {
+            mark_received(answers.len());
+            Ok(answers
+                .iter()
+                .map(|answer| answer.text.as_str())
+                .collect::<Vec<_>>()
+                .join("\n"))
+        },
+        None => Ok(format!(
+            "The user didn't answer within {} minutes. Carry on with your best judgement and mention the assumption you made in your final answer.",
+            ASK_TIMEOUT.as_secs() / 60
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(from: Sender, text: &str) -> Message {
+        Message {
+            from,
+            text: text.to_string(),
+            sent_at: DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_send_and_read() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("rust-1.messages");
+        assert!(read(&path).await.is_empty());
+
+        send(&path, &message(Sender::Parent, "Keep the public API"))
+            .await
+            .unwrap();
+        send(&path, &message(Sender::Task, "Can I rename the crate?"))
+            .await
+            .unwrap();
+        // Being written by the other process
+        std::io::Write::write_all(
+            &mut std::fs::OpenOptions::new().append(true).open(&path).unwrap(),
+            b"{\"from\":\"par",
+        )
+        .unwrap();
+        assert_eq!(read(&path).await, vec![
+            message(Sender::Parent, "Keep the public API"),
+            message(Sender::Task, "Can I rename the crate?"),
+        ]);
+    }
+
+    #[test]
+    fn test_pending_question() {
+        let question = message(Sender::Task, "Can I rename the crate?");
+        assert_eq!(
+            pending_question(&[message(Sender::Parent, "Keep the public API"), question.clone()]),
+            Some(&question)
+        );
+        assert_eq!(
+            pending_question(&[question, message(Sender::Parent, "No, keep the name")]),
+            None
+        );
+        assert_eq!(pending_question(&[]), None);
+    }
+
+    #[test]
+    fn test_messages_after() {
+        let messages = vec![
+            message(Sender::Parent, "Keep the public API"),
+            message(Sender::Task, "Can I rename the crate?"),
+            message(Sender::Parent, "No, keep the name"),
+        ];
+        assert_eq!(messages_after(&messages, 0).len(), 2);
+        assert_eq!(messages_after(&messages, 1), vec![message(
+            Sender::Parent,
+            "No, keep the name"
+        )]);
+        assert!(messages_after(&messages, 2).is_empty());
+    }
+
+    #[test]
+    fn test_format_guidance() {
+        assert_eq!(format_guidance(&[]), None);
+        assert_eq!(
+            format_guidance(&[
+                message(Sender::Parent, "Keep the public API"),
+                message(Sender::Parent, "Skip the benchmarks")
+            ])
+            .unwrap(),
+            "Messages from the user who delegated this task, to take into account from now on:\n\
+            - Keep the public API\n\
+            - Skip the benchmarks"
+        );
+    }
+}
@@ -0,0 +1,242 @@
+//! Map-reduce delegation with `/delegate map`: a task applied to every file matching a glob is
+//! split into shards of files, each delegated to a task of its own. The tasks run like the other
+//! delegated tasks, at most `chat.delegateMaxConcurrency` at a time. Once all the shards finished,
+//! their changes are merged and a last task validates the result as a whole.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use eyre::{
+    Result,
+    bail,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use super::{
+    AgentExecution,
+    AgentStatus,
+};
+
+/// Task of a map, either working on a shard of the files or validating the result.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MapTask {
+    /// Id shared by the tasks of the map, e.g. `map-1`
+    pub id: String,
+    /// Glob the files of the map matched, relative to the working directory
+    pub pattern: String,
+    /// Task applied to every file
+    pub prompt: String,
+    /// Index of the shard the task works on, from 0, `None` for the validation task
+    pub shard: Option<usize>,
+    pub shards: usize,
+}
+
+/// Files matching `pattern` in `cwd`, relative to it. Hidden files and directories, such as
+/// `.git`, are skipped unless the pattern names them.
+pub fn expand_glob(cwd: &Path, pattern: &str) -> Result<Vec<String>> {
+    let options = glob::MatchOptions {
+        require_literal_leading_dot: true,
+        ..Default::default()
+    };
+    let full_pattern = cwd.join(pattern);
+    let Ok(entries) = glob::glob_with(&full_pattern.to_string_lossy(), options) else {
+        bail!("Invalid glob pattern '{pattern}'");
+    };
+    let mut files = Vec::new();
+    for entry in entries {
+        let path = entry?;
+        if path.is_file() {
+            let relative = path.strip_prefix(cwd).unwrap_or(&path);
+            files.push(relative.to_string_lossy().to_string());
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Splits the files into at most `count` shards of about the same size, keeping neighboring files
+/// together.
+pub fn split_into_shards(files: &[String], count: usize) -> Vec<Vec<String>> {
+    let count = count.clamp(1, files.len().max(1));
+    let (size, remainder) = (files.len() / count, files.len() % count);
+    let mut shards = Vec::with_capacity(count);
+    let mut start = 0;
+    for index in 0..count {
+        let end = start + size + usize::from(index < remainder);
+        if end > start {
+            shards.push(files[start..end].to_vec());
+        }
+        start = end;
+    }
+    shards
+}
+
+/// Next id of a map on the board, e.g. `map-2`.
+pub fn next_map_id(board: &[AgentExecution]) -> String {
+    let last = board
+        .iter()
+        .filter_map(|execution| execution.map.as_ref()?.id.strip_prefix("map-")?.parse::<u32>().ok())
+        .max()
+        .unwrap_or(0);
+    format!("map-{}", last + 1)
+}
+
+/// The task given to the agent working on a shard.
+pub fn shard_task(prompt: &str, files: &[String]) -> String {
+    let list = files
+        .iter()
+        .map(|file| format!("- {file}"))
+        .collect::<Vec<_>>()
+        .join("\n");
+    format!("{prompt}\n\nOnly work on the following files, other tasks take care of the other ones:\n{list}")
+}
+
+/// The task given to the agent validating the result of the map, along with the outcome of every
+/// shard.
+pub fn validation_task(map: &MapTask, outcomes: &[String]) -> String {
+    format!(
+        "The following task was applied to the files matching {} by {} tasks, each working on a shard of the files:\n{}\n\n\
+        Outcome of the tasks:\n{}\n\n\
+        Validate the result as a whole: check that every file matching {} was handled consistently, run the relevant build \
+        or tests, and fix what is missing or inconsistent. Report what you checked and fixed.",
+        map.pattern,
+        map.shards,
+        map.prompt,
+        outcomes.join("\n"),
+        map.pattern
+    )
+}
+
+/// Maps whose shards all finished and whose result wasn't validated yet, with their shards in
+/// order. Interrupted shards may still be resumed, so their map waits for them.
+pub fn maps_to_reduce(board: &[AgentExecution]) -> Vec<(MapTask, Vec<&AgentExecution>)> {
+    let mut maps = BTreeMap::<&str, Vec<&AgentExecution>>::new();
+    for execution in board {
+        if let Some(map) = &execution.map {
+            maps.entry(map.id.as_str()).or_default().push(execution);
+        }
+    }
+    maps.into_values()
+        .filter(|tasks| {
+            tasks.iter().all(|task| {
+                task.map.as_ref().is_some_and(|map| map.shard.is_some())
+                    && matches!(
+                        task.status,
+                        AgentStatus::Completed | AgentStatus::Failed | AgentStatus::Cancelled
+                    )
+            })
+        })
+        .filter_map(|mut tasks| {
+            tasks.sort_by_key(|task| task.map.as_ref().and_then(|map| map.shard));
+            let map = tasks.first()?.map.clone()?;
+            Some((map, tasks))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files(count: usize) -> Vec<String> {
+        (0..count).map(|i| format!("src/{i}.rs")).collect()
+    }
+
+    fn map_task(shard: Option<usize>) -> MapTask {
+        MapTask {
+            id: "map-1".to_string(),
+            pattern: "src/**/*.rs".to_string(),
+            prompt: "Add the license header".to_string(),
+            shard,
+            shards: 2,
+        }
+    }
+
+    fn execution(id: &str, shard: Option<usize>, status: AgentStatus) -> AgentExecution {
+        AgentExecution {
+            id: id.to_string(),
+            status,
+            map: Some(map_task(shard)),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_expand_glob() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("src/cli")).unwrap();
+        std::fs::create_dir_all(dir.path().join(".git")).unwrap();
+        for file in ["src/lib.rs", "src/cli/mod.rs", "src/notes.md", ".git/config.rs"] {
+            std::fs::write(dir.path().join(file), "").unwrap();
+        }
+        assert_eq!(expand_glob(dir.path(), "**/*.rs").unwrap(), vec![
+            "src/cli/mod.rs".to_string(),
+            "src/lib.rs".to_string()
+        ]);
+        assert!(expand_glob(dir.path(), "*.py").unwrap().is_empty());
+        assert!(expand_glob(dir.path(), "src/[").is_err());
+    }
+
+    #[test]
+    fn test_split_into_shards() {
+        let shards = split_into_shards(&files(7), 3);
+        assert_eq!(shards.iter().map(Vec::len).collect::<Vec<_>>(), vec![3, 2, 2]);
+        assert_eq!(shards.concat(), files(7));
+
+        assert_eq!(split_into_shards(&files(2), 5).len(), 2);
+        assert_eq!(split_into_shards(&files(4), 0), vec![files(4)]);
+        assert!(split_into_shards(&[], 3).is_empty());
+    }
+
+    #[test]
+    fn test_next_map_id() {
+        assert_eq!(next_map_id(&[]), "map-1");
+        assert_eq!(
+            next_map_id(&[execution("rust-1", Some(0), AgentStatus::Running), AgentExecution {
+                map: Some(MapTask {
+                    id: "map-4".to_string(),
+                    ..map_task(None)
+                }),
+                ..Default::default()
+            }]),
+            "map-5"
+        );
+    }
+
+    #[test]
+    fn test_shard_task() {
+        assert_eq!(
+            shard_task("Add the license header", &files(2)),
+            "Add the license header\n\nOnly work on the following files, other tasks take care of the other ones:\n\
+            - src/0.rs\n- src/1.rs"
+        );
+    }
+
+    #[test]
+    fn test_maps_to_reduce() {
+        let running = [
+            execution("rust-2", Some(1), AgentStatus::Running),
+            execution("rust-1", Some(0), AgentStatus::Completed),
+        ];
+        assert!(maps_to_reduce(&running).is_empty());
+
+        let finished = [
+            execution("rust-2", Some(1), AgentStatus::Failed),
+            execution("rust-1", Some(0), AgentStatus::Completed),
+            execution("docs-1", None, AgentStatus::Completed),
+        ];
+        let maps = maps_to_reduce(&finished[..2]);
+        assert_eq!(maps.len(), 1);
+        assert_eq!(maps[0].0.id, "map-1");
+        assert_eq!(maps[0].1.iter().map(|task| task.id.as_str()).collect::<Vec<_>>(), vec![
+            "rust-1", "rust-2"
+        ]);
+
+        // Already validated
+        assert!(maps_to_reduce(&finished).is_empty());
+    }
+}
@@ -0,0 +1,1677 @@
+mod board;
+pub mod events;
+pub mod handoff;
+pub mod limits;
+pub mod mailbox;
+pub mod map;
+pub mod snapshot;
+pub mod staging;
+
+use std::collections::BTreeSet;
+use std::future::Future;
+use std::io::{
+    Write,
+    stdin,
+    stdout,
+};
+use std::path::{
+    Path,
+    PathBuf,
+};
+use std::pin::Pin;
+
+use chrono::Utc;
+use crossterm::style::Print;
+use crossterm::{
+    execute,
+    queue,
+    style,
+};
+use eyre::Result;
+use handoff::HandoffNotes;
+use limits::TaskLimits;
+use mailbox::{
+    Message,
+    Sender,
+};
+use map::MapTask;
+use schemars::JsonSchema;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use staging::{
+    FileChange,
+    StagedChanges,
+    StagingState,
+};
+use strum::{
+    Display,
+    EnumString,
+};
+
+use crate::cli::agent::Agents;
+use crate::cli::chat::tools::{
+    InvokeOutput,
+    OutputKind,
+    sanitize_path_tool_arg,
+};
+use crate::cli::experiment::experiment_manager::{
+    ExperimentManager,
+    ExperimentName,
+};
+use crate::cli::{
+    Agent,
+    DEFAULT_AGENT_NAME,
+};
+use crate::database::settings::Setting;
+use crate::os::Os;
+use crate::theme::StyledText;
+use crate::util::consts::env_var::{
+    Q_DELEGATE_LIMITS,
+    Q_DELEGATE_MAILBOX,
+    Q_DELEGATE_SNAPSHOT,
+    Q_DELEGATE_TOUCHED_FILES,
+    Q_SUBAGENT,
+};
+use crate::util::env_var::get_all_env_vars;
+use crate::util::paths::PathResolver;
+
+/// Launch and manage async agent processes. Delegate tasks to agents that run independently in
+/// background.
+///
+/// Operations:
+/// - launch: Start task with agent (requires task, agent optional - defaults to 'default_agent')
+/// - status: Check task status (task_id or agent optional - defaults to 'all')
+/// - board: Show all tasks and the files several running tasks wrote to
+/// - tell: Send guidance to a running task (task_id and message required)
+/// - ask: Ask the user a question from a delegated task, waiting for the answer (message required)
+/// - list: Show available agents
+///
+/// Tasks run concurrently up to `chat.delegateMaxConcurrency`, the others being queued. Files
+/// stored in the workspace subagents directory
+///
+/// Examples:
+/// - Launch: {"operation": "launch", "agent": "rust-agent", "task": "Create snake game"}
+/// - Launch in a directory: {"operation": "launch", "task": "Fix the docs", "working_directory":
+///   "docs"}
+/// - Status: {"operation": "status", "task_id": "rust-agent-1"}
+/// - List all: {"operation": "status"}
+/// - Tell: {"operation": "tell", "task_id": "rust-agent-1", "message": "Keep the public API"}
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Delegate {
+    /// Operation to perform: launch, status, board, tell, ask, or list
+    pub operation: Operation,
+    /// Agent name to use (optional - uses "q_cli_default" if not specified)
+    #[serde(default)]
+    pub agent: Option<String>,
+    /// Task description (required for launch operation)
+    #[serde(default)]
+    pub task: Option<String>,
+    /// Id of the task to check with the status operation, or to send the message to with the tell
+    /// operation, e.g. "rust-agent-1"
+    #[serde(default)]
+    pub task_id: Option<String>,
+    /// Directory the task runs in, relative to the current directory (launch operation only -
+    /// defaults to the current directory)
+    #[serde(default)]
+    pub working_directory: Option<String>,
+    /// Guidance sent to the task with the tell operation, or question asked to the user with the
+    /// ask operation
+    #[serde(default)]
+    pub message: Option<String>,
+    /// Caps on the turns, tool calls, tokens, cost and duration of the task (launch operation
+    /// only). The task pauses on reaching one, until told to continue or stop
+    #[serde(default)]
+    pub limits: Option<TaskLimits>,
+}
+
+#[derive(Serialize, Clone, Deserialize, Debug, Display, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum Operation {
+    /// Launch a new agent with a specified task
+    Launch,
+    /// Check the status of a specific agent or all agents if None is provided
+    Status,
+    /// List all available agents
+    List,
+    /// Show the shared board of all the tasks
+    Board,
+    /// Send guidance to a running task
+    Tell,
+    /// Ask the user a question from a delegated task, waiting for the answer
+    Ask,
+}
+
+impl Delegate {
+    pub fn is_enabled(os: &Os) -> bool {
+        ExperimentManager::is_enabled(os, ExperimentName::Delegate)
+    }
+
+    pub async fn invoke(&self, os: &Os, _output: &mut impl Write, agents: &Agents) -> Result<InvokeOutput> {
+        if !Self::is_enabled(os) {
+            return Ok(InvokeOutput {
+                output: OutputKind::Text(
+                    "Delegate tool is experimental and not enabled. Use /experiment to enable it.".to_string(),
+                ),
+            });
+        }
+
+        let result = match &self.operation {
+            Operation::Launch => {
+                let task = self
+                    .task
+                    .as_ref()
+                    .ok_or(eyre::eyre!("Task description is required for launch operation"))?;
+
+                let agent_name = self.agent.as_deref().unwrap_or(DEFAULT_AGENT_NAME);
+
+                launch_agent(
+                    os,
+                    agent_name,
+                    agents,
+                    task,
+                    self.working_directory.as_deref(),
+                    self.limits,
+                )
+                .await?
+            },
+            Operation::Status => match (&self.task_id, &self.agent) {
+                (Some(task_id), _) => status_task(os, task_id).await?,
+                (None, Some(agent_name)) => status_agent(os, agent_name).await?,
+                (None, None) => match status_all_agents(os).await {
+                    Ok(executions) => {
+                        if executions.is_empty() {
+                            "No new completed delegate tasks".to_string()
+                        } else {
+                            let task_ids: Vec<String> = executions.iter().map(|e| e.id.clone()).collect();
+                            format!("The following delegate tasks are ready: {}", task_ids.join(", "))
+                        }
+                    },
+                    Err(msg) => msg.to_string(),
+                },
+            },
+            Operation::List => agents.agents.keys().cloned().fold(
+                format!("Available agents: \n- {DEFAULT_AGENT_NAME}\n"),
+                |mut acc, name| {
+                    acc.push_str(&format!("- {name}\n"));
+                    acc
+                },
+            ),
+            Operation::Board => {
+                let board = load_board(os).await?;
+                let now = Utc::now();
+                board::format_board(&board, &board::find_conflicts(&board, now), max_concurrency(os), now)
+            },
+            Operation::Tell => {
+                let (Some(task_id), Some(message)) = (&self.task_id, &self.message) else {
+                    return Err(eyre::eyre!("task_id and message are required for the tell operation"));
+                };
+                tell_task(os, task_id, message).await?;
+                format!(
+                    "Sent the message to task '{task_id}', it receives it along with the results of its next tool use"
+                )
+            },
+            Operation::Ask => {
+                let question = self
+                    .message
+                    .as_ref()
+                    .ok_or(eyre::eyre!("message is required for the ask operation"))?;
+                format!("Answer of the user: {}", mailbox::ask(os, question).await?)
+            },
+        };
+
+        Ok(InvokeOutput {
+            output: OutputKind::Text(result),
+        })
+    }
+
+    pub fn queue_description(&self, output: &mut impl Write) -> Result<()> {
+        match self.operation {
+            Operation::Launch => queue!(output, style::Print("Delegating task to agent\n"))?,
+            Operation::Status => queue!(output, style::Print("Checking agent status\n"))?,
+            Operation::List => queue!(output, style::Print("Listing available agents\n"))?,
+            Operation::Board => queue!(output, style::Print("Showing the task board\n"))?,
+            Operation::Tell => queue!(
+                output,
+                style::Print(format!(
+                    "Sending a message to task {}\n",
+                    self.task_id.as_deref().unwrap_or_default()
+                ))
+            )?,
+            Operation::Ask => queue!(output, style::Print("Asking the user, waiting for the answer\n"))?,
+        }
+
+        Ok(())
+    }
+}
+
+/// Serializes the scheduling of the tasks within this process, see [lock_scheduler].
+static SCHEDULER: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+/// Held while scheduling the tasks, which happens on launch and when a task finishes.
+struct SchedulerLock {
+    _local: tokio::sync::MutexGuard<'static, ()>,
+    #[cfg(unix)]
+    _board: nix::fcntl::Flock<std::fs::File>,
+}
+
+/// Locks the board for the scheduling of the tasks, both within this process and between the
+/// sessions sharing the board, so that two sessions never start or adopt the same task.
+async fn lock_scheduler(os: &Os) -> Result<SchedulerLock> {
+    let local = SCHEDULER.lock().await;
+    #[cfg(unix)]
+    let board = {
+        let path = os.fs.chroot_path(subagents_dir(os).await?.join("scheduler.lock"));
+        let file = std::fs::File::options()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(path)?;
+        // Blocks until the other session releases it
+        tokio::task::spawn_blocking(move || {
+            nix::fcntl::Flock::lock(file, nix::fcntl::FlockArg::LockExclusive).map_err(|(_, errno)| errno)
+        })
+        .await??
+    };
+    #[cfg(not(unix))]
+    let _ = os;
+    Ok(SchedulerLock {
+        _local: local,
+        #[cfg(unix)]
+        _board: board,
+    })
+}
+
+pub async fn launch_agent(
+    os: &Os,
+    agent: &str,
+    agents: &Agents,
+    task: &str,
+    working_directory: Option<&str>,
+    limits: Option<TaskLimits>,
+) -> Result<String> {
+    validate_agent_availability(os, agent).await?;
+    let cwd = resolve_working_directory(os, working_directory).await?;
+
+    if agent == DEFAULT_AGENT_NAME {
+        // Show warning for default agent but no approval needed
+        display_default_agent_warning()?;
+    } else {
+        // Show agent info and require approval for specific agents
+        request_user_approval(agent, agents, task).await?;
+    }
+    display_unstaged_warning(os, &cwd).await?;
+
+    queue_task(os, AgentExecution {
+        agent: agent.to_string(),
+        task: task.to_string(),
+        cwd: cwd.to_string_lossy().to_string(),
+        limits: limits.filter(|limits| !limits.is_empty()),
+        ..Default::default()
+    })
+    .await
+}
+
+/// Launches a task of the sub-agent `subagent` of the active agent, whose permissions the task runs
+/// with. The tool use launching it was approved by the user beforehand.
+pub async fn launch_subagent(
+    os: &Os,
+    agents: &Agents,
+    subagent: &str,
+    task: &str,
+    working_directory: Option<&str>,
+) -> Result<String> {
+    let agent = agents
+        .get_active()
+        .ok_or(eyre::eyre!("No active agent to spawn the sub-agent {subagent} of"))?;
+    // Fails early for unknown sub-agents, the task loading the sub-agent itself
+    let limits = agent.subagents.get(subagent).and_then(|config| config.limits);
+    agent.subagent(subagent)?;
+    let cwd = resolve_working_directory(os, working_directory).await?;
+    display_unstaged_warning(os, &cwd).await?;
+    queue_task(os, AgentExecution {
+        agent: agent.name.clone(),
+        subagent: Some(subagent.to_string()),
+        task: task.to_string(),
+        cwd: cwd.to_string_lossy().to_string(),
+        limits: limits.filter(|limits| !limits.is_empty()),
+        ..Default::default()
+    })
+    .await
+}
+
+/// Applies `prompt` to every file matching `pattern` in the current directory, split into at most
+/// `shards` tasks of `agent` that run in the background. Once they all finished, their changes are
+/// merged and another task validates the result.
+pub async fn launch_map(
+    os: &Os,
+    agent: &str,
+    agents: &Agents,
+    pattern: &str,
+    prompt: &str,
+    shards: Option<usize>,
+) -> Result<String> {
+    let cwd = resolve_working_directory(os, None).await?;
+    let files = map::expand_glob(&os.fs.chroot_path(&cwd), pattern)?;
+    if files.is_empty() {
+        return Err(eyre::eyre!("No files found matching glob pattern '{}'", pattern));
+    }
+    let shards = map::split_into_shards(&files, shards.unwrap_or_else(|| max_concurrency(os)));
+
+    if agent == DEFAULT_AGENT_NAME {
+        display_default_agent_warning()?;
+    } else {
+        request_user_approval(agent, agents, prompt).await?;
+    }
+    display_unstaged_warning(os, &cwd).await?;
+
+    let map_id = map::next_map_id(&load_board(os).await?);
+    let mut tasks = Vec::new();
+    for (index, files) in shards.iter().enumerate() {
+        let execution = add_to_board(os, AgentExecution {
+            agent: agent.to_string(),
+            task: map::shard_task(prompt, files),
+            cwd: cwd.to_string_lossy().to_string(),
+            map: Some(MapTask {
+                id: map_id.clone(),
+                pattern: pattern.to_string(),
+                prompt: prompt.to_string(),
+                shard: Some(index),
+                shards: shards.len(),
+            }),
+            ..Default::default()
+        })
+        .await?;
+        tasks.push(format!("- {} ({} files)", execution.id, files.len()));
+    }
+    schedule(os).await?;
+
+    Ok(format!(
+        "✓ Split {} files matching {pattern} into {} tasks of agent '{agent}' as {map_id}, running at most {} at a time:
+{}
+
+Once they all finish, their changes are merged and another task validates the result.",
+        files.len(),
+        shards.len(),
+        max_concurrency(os),
+        tasks.join("\n")
+    ))
+}
+
+/// Serializes the reduction of the maps, which happens when a task finishes.
+static REDUCER: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+/// Merges the changes of the maps whose shards all finished, queuing the task validating their
+/// result.
+async fn reduce_maps(os: &Os) -> Result<()> {
+    let _lock = REDUCER.lock().await;
+    let board = load_board(os).await?;
+    for (map, shards) in map::maps_to_reduce(&board) {
+        let mut outcomes = Vec::new();
+        for shard in &shards {
+            let outcome = match shard.status {
+                AgentStatus::Completed if shard.has_pending_changes() => match merge_task(os, &shard.id).await {
+                    Ok(_) => "completed, changes merged".to_string(),
+                    Err(err) => format!("completed, changes left for the user to review: {err}"),
+                },
+                AgentStatus::Completed => "completed".to_string(),
+                status if shard.has_pending_changes() => format!("{status}, changes left for the user to review"),
+                status => status.to_string(),
+            };
+            outcomes.push(format!("- {}: {outcome}", shard.id));
+        }
+        let Some(first) = shards.first() else {
+            continue;
+        };
+        add_to_board(os, AgentExecution {
+            agent: first.agent.clone(),
+            subagent: first.subagent.clone(),
+            task: map::validation_task(&map, &outcomes),
+            cwd: first.cwd.clone(),
+            map: Some(MapTask { shard: None, ..map }),
+            ..Default::default()
+        })
+        .await?;
+    }
+    Ok(())
+}
+
+/// Adds the task to the board, then starts it if there's room for it.
+async fn queue_task(os: &Os, execution: AgentExecution) -> Result<String> {
+    let execution = add_to_board(os, execution).await?;
+    let started = schedule(os).await?;
+    Ok(format_launch_success(
+        &execution,
+        started.contains(&execution.id),
+        max_concurrency(os),
+    ))
+}
+
+/// Adds the task to the board as queued, giving it the next id of its agent.
+async fn add_to_board(os: &Os, mut execution: AgentExecution) -> Result<AgentExecution> {
+    let _lock = lock_scheduler(os).await?;
+    let now = Utc::now();
+    let mut board = load_board(os).await?;
+    for execution in board.iter().filter(|execution| board::is_stale(execution, now)) {
+        remove_agent_execution(os, &execution.id).await?;
+    }
+    board.retain(|execution| !board::is_stale(execution, now));
+
+    execution.id = board::next_task_id(execution.subagent.as_deref().unwrap_or(&execution.agent), &board);
+    execution.status = AgentStatus::Queued;
+    execution.launched_at = now;
+    save_agent_execution(os, &execution).await?;
+    Ok(execution)
+}
+
+fn format_launch_success(execution: &AgentExecution, started: bool, max_concurrency: usize) -> String {
+    let state = if started {
+        "launched successfully".to_string()
+    } else {
+        format!("queued as {max_concurrency} tasks are already running. It will start when one of them finishes")
+    };
+    format!(
+        "✓ Task '{}' of agent '{}' {state}.\nTask: {}\nWorking directory: {}\n\nYou will be notified when the task completes. The notification will include a summary. When the working directory is in a git repository, the task runs in a worktree of its own and its changes land in the working tree only once the user merges them with /agents merge. If you need the full output, you can ask to read the complete delegation result using the 'status' operation with the task id.",
+        execution.id, execution.agent, execution.task, execution.cwd
+    )
+}
+
+/// Maximum number of tasks running at the same time
+pub fn max_concurrency(os: &Os) -> usize {
+    os.database
+        .settings
+        .get_int_or(Setting::ChatDelegateMaxConcurrency, board::DEFAULT_MAX_CONCURRENCY)
+        .max(1)
+}
+
+async fn resolve_working_directory(os: &Os, working_directory: Option<&str>) -> Result<PathBuf> {
+    let cwd = match working_directory {
+        Some(dir) => sanitize_path_tool_arg(os, dir),
+        None => os.env.current_dir()?,
+    };
+    if !os
+        .fs
+        .symlink_metadata(&cwd)
+        .await
+        .is_ok_and(|metadata| metadata.is_dir())
+    {
+        return Err(eyre::eyre!("Working directory {} is not a directory", cwd.display()));
+    }
+    Ok(cwd)
+}
+
+/// Starts the queued tasks that can run without going over the maximum concurrency, returning
+/// their ids.
+pub async fn schedule(os: &Os) -> Result<Vec<String>> {
+    let _lock = lock_scheduler(os).await?;
+    let board = load_board(os).await?;
+    let to_start = board::tasks_to_start(&board, max_concurrency(os));
+    for id in &to_start {
+        if let Some(mut execution) = board.iter().find(|execution| &execution.id == id).cloned() {
+            if let Err(err) = spawn_agent_process(os, &mut execution, &board).await {
+                execution.status = AgentStatus::Failed;
+                execution.completed_at = Some(Utc::now());
+                execution.output = format!("Failed to start the task: {err}");
+                execution.summary = Some("Task failed to start".to_string());
+                save_agent_execution(os, &execution).await?;
+            }
+        }
+    }
+    Ok(to_start)
+}
+
+pub fn display_agent_info(agent: &str, task: &str, config: &AgentConfig) -> Result<()> {
+    let short_desc = truncate_description(config.description.as_deref().unwrap_or("No description"));
+
+    execute!(
+        stdout(),
+        Print(format!("Agent: {}\n", agent)),
+        Print(format!("Description: {}\n", short_desc)),
+        Print(format!("Task: {}\n", task)),
+    )?;
+
+    if !config.allowed_tools.is_empty() {
+        let tools: Vec<&str> = config.allowed_tools.iter().map(|s| s.as_str()).collect();
+        execute!(stdout(), Print(format!("Tools: {}\n", tools.join(", "))))?;
+    }
+
+    // Add appropriate security warning based on agent permissions
+    execute!(
+        stdout(),
+        Print("\n"),
+        StyledText::warning_fg(),
+        Print("! This task will run with the agent's specific tool permissions.\n\n"),
+        StyledText::reset(),
+    )?;
+
+    Ok(())
+}
+
+pub fn truncate_description(desc: &str) -> &str {
+    if let Some(pos) = desc.find('.') {
+        &desc[..pos + 1]
+    } else if desc.len() > 60 {
+        &desc[..57]
+    } else {
+        desc
+    }
+}
+
+pub fn display_default_agent_warning() -> Result<()> {
+    execute!(
+        stdout(),
+        Print("\n"),
+        StyledText::warning_fg(),
+        Print(
+            "! This task will run with trust-all permissions and can execute commands or consume system/cloud resources.\n\n"
+        ),
+        StyledText::reset(),
+    )?;
+    Ok(())
+}
+
+/// Warns that the changes of a task running in `cwd` land in it directly, as they're only staged
+/// for review in a git repository.
+async fn display_unstaged_warning(os: &Os, cwd: &Path) -> Result<()> {
+    if staging::repo_root(&os.fs.chroot_path(cwd)).await.is_none() {
+        execute!(
+            stdout(),
+            StyledText::warning_fg(),
+            Print(format!(
+                "! {} is not in a git repository, the changes of this task are written to it directly without review.\n\n",
+                cwd.display()
+            )),
+            StyledText::reset(),
+        )?;
+    }
+    Ok(())
+}
+
+pub fn get_user_confirmation() -> Result<bool> {
+    execute!(
+        stdout(),
+        StyledText::warning_fg(),
+        Print("Continue? [y/N]: "),
+        StyledText::reset(),
+    )?;
+
+    let mut input = String::new();
+    stdin().read_line(&mut input)?;
+    let input = input.trim().to_lowercase();
+
+    if input == "y" || input == "yes" {
+        println!();
+        Ok(true)
+    } else {
+        Ok(false)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Display, EnumString)]
+#[strum(serialize_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum AgentStatus {
+    /// Waiting for a running task to finish
+    Queued,
+    Running,
+    /// Suspended from `/agents`, still counting as a running task
+    Paused,
+    Completed,
+    Failed,
+    Cancelled,
+    /// Its process died before it finished, e.g. when the computer restarted. Continues from its
+    /// last checkpoint with `q agent resume`
+    Interrupted,
+}
+
+impl Default for AgentStatus {
+    fn default() -> Self {
+        Self::Running
+    }
+}
+
+impl AgentStatus {
+    // No methods currently needed - all functionality is in format_status
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+pub struct AgentExecution {
+    /// Id of the task on the board, e.g. `rust-agent-1`. Tasks saved before there could be several
+    /// per agent are identified by their agent.
+    #[serde(default)]
+    pub id: String,
+    #[serde(default)]
+    pub agent: String,
+    #[serde(default)]
+    pub task: String,
+    #[serde(default)]
+    pub status: AgentStatus,
+    #[serde(default, with = "chrono::serde::ts_seconds")]
+    pub launched_at: chrono::DateTime<chrono::Utc>,
+    #[serde(default, with = "chrono::serde::ts_seconds_option")]
+    pub completed_at: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(default)]
+    pub pid: u32,
+    /// Process of the session following the task until it ends, see [recover]
+    #[serde(default)]
+    pub monitor_pid: u32,
+    #[serde(default)]
+    pub exit_code: Option<i32>,
+    #[serde(default)]
+    pub output: String,
+    #[serde(default)]
+    pub user_notified: bool,
+    #[serde(default)]
+    pub summary: Option<String>,
+    #[serde(default = "default_unknown_string")]
+    pub cwd: String,
+    /// Files the task wrote to, recorded by the task itself while it runs
+    #[serde(skip)]
+    pub touched_files: BTreeSet<String>,
+    /// Sub-agent of the agent the task runs as, when spawned with the spawn_subagent tool
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subagent: Option<String>,
+    /// Caps on the resources of the task, which pauses on reaching one until told to continue or
+    /// stop
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limits: Option<TaskLimits>,
+    /// Map the task is part of, when launched with `/delegate map`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub map: Option<MapTask>,
+    /// Worktree the task runs in when launched in a git repository, its changes waiting there to
+    /// be merged with `/agents merge`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub staged: Option<StagedChanges>,
+}
+
+fn default_unknown_string() -> String {
+    "Unknown".to_string()
+}
+
+impl AgentExecution {
+    /// Whether the changes of the task are waiting to be merged or discarded.
+    pub fn has_pending_changes(&self) -> bool {
+        self.staged
+            .as_ref()
+            .is_some_and(|staged| staged.state == StagingState::Pending)
+    }
+
+    pub fn format_status(&self) -> String {
+        let status = self.format_run_status();
+        match &self.staged {
+            Some(staged) if staged.state == StagingState::Pending => format!(
+                "{status}\n\nThe changes of the task are staged in {} and don't apply to the working tree until the user reviews and merges them with /agents merge {}.",
+                staged.worktree.display(),
+                self.id
+            ),
+            _ => status,
+        }
+    }
+
+    fn format_run_status(&self) -> String {
+        match self.status {
+            AgentStatus::Queued => {
+                format!(
+                    "Task '{}' of agent '{}' is queued until a running task finishes.",
+                    self.id, self.agent
+                )
+            },
+            AgentStatus::Running => {
+                format!(
+                    "Task '{}' of agent '{}' is still running. Please wait...",
+                    self.id, self.agent
+                )
+            },
+            AgentStatus::Paused => {
+                format!(
+                    "Task '{}' of agent '{}' was paused by the user. It continues once resumed from /agents.",
+                    self.id, self.agent
+                )
+            },
+            AgentStatus::Cancelled => {
+                format!(
+                    "Task '{}' of agent '{}' was cancelled by the user.\n\nOutput:\n{}",
+                    self.id, self.agent, self.output
+                )
+            },
+            AgentStatus::Completed => {
+                format!(
+                    "Task '{}' of agent '{}' completed successfully.\n\nOutput:\n{}",
+                    self.id, self.agent, self.output
+                )
+            },
+            AgentStatus::Failed => {
+                format!(
+                    "Task '{}' of agent '{}' failed.\nExit code: {}\n\nError:\n{}",
+                    self.id,
+                    self.agent,
+                    self.exit_code.unwrap_or(-1),
+                    self.output
+                )
+            },
+            AgentStatus::Interrupted => {
+                format!(
+                    "Task '{}' of agent '{}' was interrupted before it finished. The user can continue it with q agent resume {}.\n\nOutput so far:\n{}",
+                    self.id, self.agent, self.id, self.output
+                )
+            },
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AgentConfig {
+    pub description: Option<String>,
+    #[serde(rename = "allowedTools")]
+    pub allowed_tools: Vec<String>,
+}
+
+impl From<&Agent> for AgentConfig {
+    fn from(value: &Agent) -> Self {
+        Self {
+            description: value.description.clone(),
+            allowed_tools: value.allowed_tools.iter().cloned().collect::<Vec<String>>(),
+        }
+    }
+}
+
+/// Starts a queued task, telling it about the other tasks of the board.
+async fn spawn_agent_process(os: &Os, execution: &mut AgentExecution, board: &[AgentExecution]) -> Result<()> {
+    let run_dir = stage_changes(os, execution).await?;
+    let child = spawn_task_process(os, execution, &task_prompt(execution, board), &run_dir, false).await?;
+
+    // Start monitoring with the actual child process
+    tokio::spawn(monitor_child_process(child, execution.clone(), os.clone()));
+
+    Ok(())
+}
+
+/// Runs `prompt` as the task in `run_dir`, continuing the conversation of its last checkpoint when
+/// `resume` is set.
+async fn spawn_task_process(
+    os: &Os,
+    execution: &mut AgentExecution,
+    prompt: &str,
+    run_dir: &Path,
+    resume: bool,
+) -> Result<tokio::process::Child> {
+    // Run Q chat with specific agent in background, non-interactive
+    let mut cmd = tokio::process::Command::new("q");
+    cmd.args(["chat", "--non-interactive"]);
+    if execution.agent == DEFAULT_AGENT_NAME && execution.subagent.is_none() {
+        cmd.arg("--trust-all-tools");
+    }
+    if resume {
+        cmd.arg("--resume");
+    }
+    cmd.args(["--agent", &execution.agent, prompt]);
+
+    // Redirect to the log of the task (runs silently), which `/agents` follows while it runs. The
+    // output of resumed tasks follows the one of their first run
+    let log = std::fs::File::options()
+        .create(true)
+        .write(true)
+        .append(resume)
+        .truncate(!resume)
+        .open(os.fs.chroot_path(output_log_path(os, &execution.id).await?))?;
+    cmd.stdout(log.try_clone()?);
+    cmd.stderr(log);
+    cmd.stdin(std::process::Stdio::null()); // No user input
+    cmd.envs(get_all_env_vars());
+    cmd.env(Q_DELEGATE_TOUCHED_FILES, touched_files_path(os, &execution.id).await?);
+    cmd.env(
+        Q_DELEGATE_MAILBOX,
+        os.fs.chroot_path(mailbox_path(os, &execution.id).await?),
+    );
+    let snapshot = os.fs.chroot_path(snapshot_path(os, &execution.id).await?);
+    // Recorded again when the resumed task ends
+    let _ = std::fs::remove_file(snapshot::exit_code_path(&snapshot));
+    cmd.env(Q_DELEGATE_SNAPSHOT, snapshot);
+    // Not inherited from the session delegating the task, when it's a task itself
+    match &execution.subagent {
+        Some(subagent) => cmd.env(Q_SUBAGENT, subagent),
+        None => cmd.env_remove(Q_SUBAGENT),
+    };
+    match &execution.limits {
+        Some(limits) => cmd.env(Q_DELEGATE_LIMITS, serde_json::to_string(limits)?),
+        None => cmd.env_remove(Q_DELEGATE_LIMITS),
+    };
+    cmd.current_dir(run_dir);
+
+    #[cfg(not(windows))]
+    cmd.process_group(0);
+
+    let child = cmd.spawn()?;
+    execution.pid = child.id().ok_or(eyre::eyre!("Process spawned had already exited"))?;
+    execution.monitor_pid = std::process::id();
+    execution.status = AgentStatus::Running;
+    if !resume {
+        // Runs are timed from their start for the conflict detection, not from when they were
+        // queued
+        execution.launched_at = Utc::now();
+    }
+    execution.completed_at = None;
+
+    save_agent_execution(os, execution).await?;
+    Ok(child)
+}
+
+/// Creates the worktree of the task when it runs in a git repository, so that its changes are
+/// reviewed before landing in the working tree, returning the directory the task runs in.
+async fn stage_changes(os: &Os, execution: &mut AgentExecution) -> Result<PathBuf> {
+    let cwd = os.fs.chroot_path(&execution.cwd);
+    let worktrees_dir = os.fs.chroot_path(subagents_dir(os).await?.join("worktrees"));
+    match StagedChanges::create(&cwd, &worktrees_dir, &execution.id).await? {
+        Some((staged, run_dir)) => {
+            execution.staged = Some(staged);
+            Ok(run_dir)
+        },
+        None => Ok(cwd),
+    }
+}
+
+/// The task given to the agent, followed by the other tasks running or queued, so that agents
+/// working at the same time avoid editing the same files.
+fn task_prompt(execution: &AgentExecution, board: &[AgentExecution]) -> String {
+    let others = board
+        .iter()
+        .filter(|other| {
+            other.id != execution.id
+                && matches!(
+                    other.status,
+                    AgentStatus::Running | AgentStatus::Paused | AgentStatus::Queued
+                )
+        })
+        .map(|other| format!("- {} ({}, in {}): {}", other.id, other.status, other.cwd, other.task))
+        .collect::<Vec<_>>();
+    if others.is_empty() {
+        return execution.task.clone();
+    }
+    format!(
+        "{}\n\nOther agents are working on the following tasks at the same time. Avoid modifying the files \
+        these tasks are likely to change:\n{}",
+        execution.task,
+        others.join("\n")
+    )
+}
+
+/// Records a file written to by this session when it runs a delegated task, for the conflict
+/// detection of the task board.
+pub fn record_touched_file(os: &Os, path: &Path) {
+    let Ok(touched_files) = os.env.get(Q_DELEGATE_TOUCHED_FILES) else {
+        return;
+    };
+    let result = std::fs::File::options()
+        .append(true)
+        .create(true)
+        .open(touched_files)
+        .and_then(|mut file| writeln!(file, "{}", path.display()));
+    if let Err(err) = result {
+        tracing::warn!(?err, "Failed to record the file written to by the delegated task");
+    }
+}
+
+/// Summarizes the task execution, along with the follow-ups and open questions it left for its
+/// handoff.
+async fn generate_notes(task: &str, output: &str) -> Result<HandoffNotes> {
+    let summary_prompt = handoff::notes_prompt(task, output);
+
+    // Run Q chat with summary prompt in non-interactive mode
+    let mut cmd = tokio::process::Command::new("q");
+    cmd.args(["chat", "--non-interactive", &summary_prompt]);
+    cmd.stdout(std::process::Stdio::piped());
+    cmd.stderr(std::process::Stdio::piped());
+    cmd.stdin(std::process::Stdio::null());
+    cmd.envs(std::env::vars());
+    // The summary isn't part of the task that may be running this
+    for var in [
+        Q_DELEGATE_TOUCHED_FILES,
+        Q_DELEGATE_MAILBOX,
+        Q_DELEGATE_SNAPSHOT,
+        Q_DELEGATE_LIMITS,
+        Q_SUBAGENT,
+    ] {
+        cmd.env_remove(var);
+    }
+
+    let output = cmd.output().await?;
+
+    if output.status.success() {
+        let raw_summary = String::from_utf8_lossy(&output.stdout);
+        // Strip ANSI codes and remove leading "> " prefix
+        let clean_summary = strip_ansi_escapes::strip_str(&raw_summary)
+            .trim()
+            .trim_start_matches('>')
+            .trim()
+            .to_string();
+
+        if !clean_summary.is_empty() {
+            Ok(handoff::parse_notes(&clean_summary))
+        } else {
+            Err(eyre::eyre!("Empty summary generated"))
+        }
+    } else {
+        Err(eyre::eyre!("Summary generation failed"))
+    }
+}
+
+/// Waits for the task to finish, then starts the tasks queued behind it. Boxed as it spawns
+/// itself through [schedule].
+fn monitor_child_process(
+    child: tokio::process::Child,
+    execution: AgentExecution,
+    os: Os,
+) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+    Box::pin(async move {
+        wait_child_process(child, execution, &os).await;
+        if let Err(e) = reduce_maps(&os).await {
+            eprintln!("Failed to merge the delegated map: {}", e);
+        }
+        if let Err(e) = schedule(&os).await {
+            eprintln!("Failed to start the queued agent executions: {}", e);
+        }
+    })
+}
+
+async fn wait_child_process(mut child: tokio::process::Child, execution: AgentExecution, os: &Os) {
+    let status = child.wait().await.map(|status| status.code());
+    finish_task(os, execution, status).await;
+}
+
+/// Records the outcome of a task whose process exited with `status`, summarizing what it did.
+async fn finish_task(os: &Os, mut execution: AgentExecution, status: std::io::Result<Option<i32>>) {
+    execution.completed_at = Some(Utc::now());
+    execution.output = read_output(os, &execution.id).await;
+
+    // Cancelled from `/agents` while running
+    if load_task(os, &execution.id)
+        .await
+        .is_ok_and(|saved| saved.is_some_and(|saved| saved.status == AgentStatus::Cancelled))
+    {
+        execution.status = AgentStatus::Cancelled;
+        execution.exit_code = status.ok().flatten();
+        execution.user_notified = true;
+        execution.summary = Some("Task cancelled by the user".to_string());
+        if let Err(e) = save_agent_execution(os, &execution).await {
+            eprintln!("Failed to save agent execution: {}", e);
+        }
+        return;
+    }
+
+    match status {
+        Ok(code) => {
+            execution.status = if code == Some(0) {
+                AgentStatus::Completed
+            } else {
+                AgentStatus::Failed
+            };
+            execution.exit_code = code;
+
+            // Nothing to review
+            if let Some(staged) = execution.staged.as_mut() {
+                if staged.changes().await.is_ok_and(|changes| changes.is_empty()) {
+                    let _ = staged.discard().await;
+                }
+            }
+
+            // Generate summary with retry logic
+            let notes = match generate_notes(&execution.task, &execution.output).await {
+                Ok(notes) => notes,
+                Err(_) => {
+                    // Retry once
+                    match generate_notes(&execution.task, &execution.output).await {
+                        Ok(notes) => notes,
+                        Err(_) => HandoffNotes {
+                            summary: "Summary unavailable - see full details in agent execution file".to_string(),
+                            ..Default::default()
+                        },
+                    }
+                },
+            };
+            execution.summary = Some(notes.summary.clone());
+
+            // Handed off to the next session started in this directory
+            if let Ok(Some(saved)) = load_task(os, &execution.id).await {
+                execution.touched_files = saved.touched_files;
+            }
+            if let Err(e) = handoff::save(os, &execution, &notes).await {
+                eprintln!("Failed to save the handoff of the task: {}", e);
+            }
+
+            // Save to workspace subagents directory
+            if let Err(e) = save_agent_execution(os, &execution).await {
+                eprintln!("Failed to save agent execution: {}", e);
+            }
+        },
+        Err(e) => {
+            execution.status = AgentStatus::Failed;
+            execution.exit_code = Some(-1);
+            execution.output = format!("Failed to wait for process: {}", e);
+            execution.summary = Some("Task failed to complete due to process error".to_string());
+
+            // Save to workspace subagents directory
+            if let Err(e) = save_agent_execution(os, &execution).await {
+                eprintln!("Failed to save agent execution: {}", e);
+            }
+        },
+    }
+}
+
+/// Time between two checks of whether a task started by an earlier session still runs
+const ADOPTED_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// Picks up the board where an earlier session left it: follows the tasks still running, settles
+/// the ones that ended in the meantime and starts the queued tasks. Returns the tasks found
+/// interrupted.
+///
+/// Tasks followed by another session still running are left to it.
+pub async fn recover(os: &Os) -> Result<Vec<AgentExecution>> {
+    let mut interrupted = Vec::new();
+    {
+        let _lock = lock_scheduler(os).await?;
+        for mut execution in load_board(os).await? {
+            if !matches!(execution.status, AgentStatus::Running | AgentStatus::Paused)
+                || is_monitored_elsewhere(&execution)
+            {
+                continue;
+            }
+            let exited = execution.pid == 0 || !is_process_alive(execution.pid);
+            if exited && read_exit_code(os, &execution.id).await.is_none() {
+                interrupted.push(interrupt_task(os, execution).await?);
+            } else {
+                execution.monitor_pid = std::process::id();
+                save_agent_execution(os, &execution).await?;
+                tokio::spawn(monitor_adopted_process(execution, os.clone()));
+            }
+        }
+    }
+    schedule(os).await?;
+    Ok(interrupted)
+}
+
+/// Whether another session still running follows the task.
+fn is_monitored_elsewhere(execution: &AgentExecution) -> bool {
+    // Processes are assumed alive where they can't be checked, which would leave the task to no one
+    cfg!(unix)
+        && execution.monitor_pid != 0
+        && execution.monitor_pid != std::process::id()
+        && is_process_alive(execution.monitor_pid)
+}
+
+/// Waits for a task started by an earlier session to end. Its process isn't a child of this one,
+/// so it is polled, its exit code being read from the file the task records it in.
+async fn monitor_adopted_process(execution: AgentExecution, os: Os) {
+    while execution.pid != 0 && is_process_alive(execution.pid) {
+        tokio::time::sleep(ADOPTED_POLL_INTERVAL).await;
+    }
+    if let Err(e) = settle_dead_task(&os, execution).await {
+        eprintln!("Failed to save agent execution: {}", e);
+    }
+    if let Err(e) = reduce_maps(&os).await {
+        eprintln!("Failed to merge the delegated map: {}", e);
+    }
+    if let Err(e) = schedule(&os).await {
+        eprintln!("Failed to start the queued agent executions: {}", e);
+    }
+}
+
+/// Settles a task whose process is gone: finished when it recorded its exit code, interrupted
+/// otherwise.
+async fn settle_dead_task(os: &Os, execution: AgentExecution) -> Result<AgentExecution> {
+    match read_exit_code(os, &execution.id).await {
+        Some(code) => {
+            let id = execution.id.clone();
+            finish_task(os, execution.clone(), Ok(Some(code))).await;
+            Ok(load_task(os, &id).await?.unwrap_or(execution))
+        },
+        None => interrupt_task(os, execution).await,
+    }
+}
+
+async fn interrupt_task(os: &Os, mut execution: AgentExecution) -> Result<AgentExecution> {
+    execution.status = AgentStatus::Interrupted;
+    execution.completed_at = Some(Utc::now());
+    execution.output = read_output(os, &execution.id).await;
+    execution.summary = Some("Task interrupted before it finished".to_string());
+    save_agent_execution(os, &execution).await?;
+    Ok(execution)
+}
+
+async fn read_exit_code(os: &Os, task_id: &str) -> Option<i32> {
+    let snapshot = snapshot_path(os, task_id).await.ok()?;
+    snapshot::read_exit_code(&os.fs.chroot_path(snapshot::exit_code_path(&snapshot)))
+}
+
+/// Continues the interrupted task `task_id` in the background, from its last checkpoint.
+pub async fn continue_task(os: &Os, task_id: &str) -> Result<AgentExecution> {
+    let _lock = lock_scheduler(os).await?;
+    let mut execution = load_task(os, task_id)
+        .await?
+        .ok_or(eyre::eyre!("No task found with id '{}'", task_id))?;
+    if matches!(execution.status, AgentStatus::Running | AgentStatus::Paused)
+        && (execution.pid == 0 || !is_process_alive(execution.pid))
+    {
+        execution = settle_dead_task(os, execution).await?;
+    }
+    if execution.status != AgentStatus::Interrupted {
+        return Err(eyre::eyre!(
+            "Task '{}' is {}, only interrupted tasks can be resumed",
+            task_id,
+            execution.status
+        ));
+    }
+
+    let cwd = os.fs.chroot_path(&execution.cwd);
+    let run_dir = match &execution.staged {
+        Some(staged) if staged.state == StagingState::Pending => staged.run_dir(&cwd)?,
+        Some(_) => {
+            return Err(eyre::eyre!(
+                "The changes of task '{}' were already merged or discarded",
+                task_id
+            ));
+        },
+        None => cwd,
+    };
+    let prompt = format!(
+        "The session running your task was interrupted before you finished. Continue the task where you left off. The task was:\n{}",
+        execution.task
+    );
+    execution.summary = None;
+    // The task runs on after this process exits, the next session following it until it ends
+    spawn_task_process(os, &mut execution, &prompt, &run_dir, true).await?;
+    Ok(execution)
+}
+
+/// Output of the task so far, without the colors.
+pub async fn read_output(os: &Os, task_id: &str) -> String {
+    match output_log_path(os, task_id).await {
+        Ok(path) => os
+            .fs
+            .read(path)
+            .await
+            .map(|output| strip_ansi_escapes::strip_str(String::from_utf8_lossy(&output)))
+            .unwrap_or_default(),
+        Err(_) => String::new(),
+    }
+}
+
+/// What the task is doing, i.e. the last tool it used, found in its output.
+pub fn current_step(output: &str) -> Option<&str> {
+    output
+        .lines()
+        .rev()
+        .find_map(|line| line.find("Using tool:").map(|start| line[start..].trim()))
+}
+
+/// The last `count` non-empty lines of the output.
+pub fn output_tail(output: &str, count: usize) -> Vec<&str> {
+    let mut tail = output
+        .lines()
+        .rev()
+        .filter(|line| !line.trim().is_empty())
+        .take(count)
+        .collect::<Vec<_>>();
+    tail.reverse();
+    tail
+}
+
+/// Suspends a running task until [resume_task].
+pub async fn pause_task(os: &Os, task_id: &str) -> Result<()> {
+    let mut execution = running_task(os, task_id, AgentStatus::Running).await?;
+    signal_task(&execution, TaskSignal::Pause)?;
+    execution.status = AgentStatus::Paused;
+    save_agent_execution(os, &execution).await
+}
+
+pub async fn resume_task(os: &Os, task_id: &str) -> Result<()> {
+    let mut execution = running_task(os, task_id, AgentStatus::Paused).await?;
+    signal_task(&execution, TaskSignal::Resume)?;
+    execution.status = AgentStatus::Running;
+    save_agent_execution(os, &execution).await
+}
+
+/// Stops a running or paused task, or removes a queued one from the queue.
+pub async fn cancel_task(os: &Os, task_id: &str) -> Result<()> {
+    let mut execution = load_task(os, task_id)
+        .await?
+        .ok_or(eyre::eyre!("No task found with id '{}'", task_id))?;
+    match execution.status {
+        AgentStatus::Queued => {
+            execution.completed_at = Some(Utc::now());
+            execution.summary = Some("Task cancelled by the user before it started".to_string());
+            execution.user_notified = true;
+        },
+        AgentStatus::Running | AgentStatus::Paused => {
+            // Saved before signalling so that the monitor of the task sees that it was cancelled
+            execution.status = AgentStatus::Cancelled;
+            save_agent_execution(os, &execution).await?;
+            signal_task(&execution, TaskSignal::Cancel)?;
+            // The monitor may have recorded the end of the task in the meantime
+            let mut execution = load_task(os, task_id).await?.unwrap_or(execution);
+            execution.status = AgentStatus::Cancelled;
+            return save_agent_execution(os, &execution).await;
+        },
+        AgentStatus::Interrupted => {
+            execution.summary = Some("Interrupted task cancelled by the user".to_string());
+            execution.user_notified = true;
+        },
+        status => return Err(eyre::eyre!("Task '{}' is already {}", task_id, status)),
+    }
+    execution.status = AgentStatus::Cancelled;
+    save_agent_execution(os, &execution).await
+}
+
+async fn running_task(os: &Os, task_id: &str, status: AgentStatus) -> Result<AgentExecution> {
+    match load_task(os, task_id).await? {
+        Some(execution) if execution.status == status => Ok(execution),
+        Some(execution) => Err(eyre::eyre!("Task '{}' is {}", task_id, execution.status)),
+        None => Err(eyre::eyre!("No task found with id '{}'", task_id)),
+    }
+}
+
+enum TaskSignal {
+    Pause,
+    Resume,
+    Cancel,
+}
+
+/// Signals the process group of the task, which includes the tools it runs.
+#[cfg(unix)]
+fn signal_task(execution: &AgentExecution, signal: TaskSignal) -> Result<()> {
+    use nix::sys::signal::{
+        Signal,
+        killpg,
+    };
+    use nix::unistd::Pid;
+
+    let pgid = Pid::from_raw(execution.pid as i32);
+    match signal {
+        TaskSignal::Pause => killpg(pgid, Signal::SIGSTOP)?,
+        TaskSignal::Resume => killpg(pgid, Signal::SIGCONT)?,
+        TaskSignal::Cancel => {
+            killpg(pgid, Signal::SIGTERM)?;
+            // Paused processes only handle the termination once continued
+            killpg(pgid, Signal::SIGCONT)?;
+        },
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn signal_task(execution: &AgentExecution, signal: TaskSignal) -> Result<()> {
+    match signal {
+        TaskSignal::Cancel => {
+            let status = std::process::Command::new("taskkill")
+                .args(["/PID", &execution.pid.to_string(), "/T", "/F"])
+                .output()?
+                .status;
+            if !status.success() {
+                return Err(eyre::eyre!("Failed to stop the process {}", execution.pid));
+            }
+            Ok(())
+        },
+        TaskSignal::Pause | TaskSignal::Resume => Err(eyre::eyre!("Pausing tasks is not supported on this platform")),
+    }
+}
+
+/// Diff of the changes of the finished task `task_id`, colored for the terminal.
+pub async fn diff_task(os: &Os, task_id: &str) -> Result<String> {
+    staged_changes(os, task_id).await?.1.diff().await
+}
+
+/// Applies the changes of the finished task `task_id` to the working tree.
+pub async fn merge_task(os: &Os, task_id: &str) -> Result<Vec<FileChange>> {
+    let (mut execution, mut staged) = staged_changes(os, task_id).await?;
+    let changes = staged.changes().await?;
+    staged.merge().await?;
+    execution.staged = Some(staged);
+    save_agent_execution(os, &execution).await?;
+    Ok(changes)
+}
+
+/// Drops the changes of the finished task `task_id`.
+pub async fn discard_task(os: &Os, task_id: &str) -> Result<()> {
+    let (mut execution, mut staged) = staged_changes(os, task_id).await?;
+    staged.discard().await?;
+    execution.staged = Some(staged);
+    save_agent_execution(os, &execution).await
+}
+
+async fn staged_changes(os: &Os, task_id: &str) -> Result<(AgentExecution, StagedChanges)> {
+    let execution = load_task(os, task_id)
+        .await?
+        .ok_or(eyre::eyre!("No task found with id '{}'", task_id))?;
+    if matches!(
+        execution.status,
+        AgentStatus::Queued | AgentStatus::Running | AgentStatus::Paused
+    ) {
+        return Err(eyre::eyre!(
+            "Task '{}' is {}, its changes can be reviewed once it finishes",
+            task_id,
+            execution.status
+        ));
+    }
+    let staged = execution.staged.clone().ok_or(eyre::eyre!(
+        "Task '{}' didn't run in a git repository, its changes were written to the working tree directly",
+        task_id
+    ))?;
+    Ok((execution, staged))
+}
+
+/// Sends guidance, or the answer to its question, to the task `task_id`.
+pub async fn tell_task(os: &Os, task_id: &str, text: &str) -> Result<()> {
+    let execution = load_task(os, task_id)
+        .await?
+        .ok_or(eyre::eyre!("No task found with id '{}'", task_id))?;
+    if !matches!(
+        execution.status,
+        AgentStatus::Queued | AgentStatus::Running | AgentStatus::Paused | AgentStatus::Interrupted
+    ) {
+        return Err(eyre::eyre!(
+            "Task '{}' is {}, it can't receive messages",
+            task_id,
+            execution.status
+        ));
+    }
+    mailbox::send(
+        &os.fs.chroot_path(mailbox_path(os, task_id).await?),
+        &Message::new(Sender::Parent, text),
+    )
+    .await
+}
+
+/// Questions of the running tasks waiting for an answer, by task id.
+pub async fn pending_questions(os: &Os) -> Result<Vec<(String, Message)>> {
+    let mut questions = Vec::new();
+    for execution in load_board(os).await? {
+        if !matches!(execution.status, AgentStatus::Running | AgentStatus::Paused) {
+            continue;
+        }
+        let messages = mailbox::read(&os.fs.chroot_path(mailbox_path(os, &execution.id).await?)).await;
+        if let Some(question) = mailbox::pending_question(&messages) {
+            questions.push((execution.id, question.clone()));
+        }
+    }
+    Ok(questions)
+}
+
+pub async fn status_task(os: &Os, task_id: &str) -> Result<String> {
+    let board = load_board(os).await?;
+    match board.iter().find(|execution| execution.id == task_id) {
+        Some(execution) => format_task_status(os, execution.clone(), &board).await,
+        None => Ok(format!("No task found with id '{}'", task_id)),
+    }
+}
+
+/// Status of the last task launched with `agent`
+pub async fn status_agent(os: &Os, agent: &str) -> Result<String> {
+    let board = load_board(os).await?;
+    match board
+        .iter()
+        .filter(|execution| execution.agent == agent)
+        .max_by_key(|execution| execution.launched_at)
+    {
+        Some(execution) => format_task_status(os, execution.clone(), &board).await,
+        None => Ok(format!("No execution found for agent '{}'", agent)),
+    }
+}
+
+async fn format_task_status(os: &Os, mut execution: AgentExecution, board: &[AgentExecution]) -> Result<String> {
+    // If status is running, check if PID is still alive
+    if matches!(execution.status, AgentStatus::Running | AgentStatus::Paused)
+        && execution.pid != 0
+        && !is_process_alive(execution.pid)
+    {
+        execution = settle_dead_task(os, execution).await?;
+    }
+
+    let mut status = execution.format_status();
+    let conflicts = board::find_conflicts(board, Utc::now())
+        .into_iter()
+        .filter(|conflict| conflict.tasks.contains(&execution.id))
+        .collect::<Vec<_>>();
+    if !conflicts.is_empty() {
+        status.push_str("\n\nConflicts with other tasks running at the same time:\n");
+        for conflict in conflicts {
+            status.push_str(&format!("- {} also written to by ", conflict.path));
+            status.push_str(
+                &conflict
+                    .tasks
+                    .iter()
+                    .filter(|id| **id != execution.id)
+                    .cloned()
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            );
+            status.push('\n');
+        }
+    }
+    Ok(status)
+}
+
+pub async fn status_all_agents(os: &Os) -> Result<Vec<AgentExecution>> {
+    let mut unnotified_executions = Vec::new();
+
+    for mut execution in load_board(os).await? {
+        // Check if running tasks have died (only check if running > 5 minutes to avoid overhead)
+        if matches!(execution.status, AgentStatus::Running | AgentStatus::Paused) && execution.pid != 0 {
+            let running_duration = chrono::Utc::now().signed_duration_since(execution.launched_at);
+
+            // Only check PID if task has been running for more than 5 minutes
+            if running_duration.num_minutes() > 5 && !is_process_alive(execution.pid) {
+                match settle_dead_task(os, execution.clone()).await {
+                    Ok(settled) => execution = settled,
+                    Err(e) => eprintln!("Failed to update dead agent execution: {}", e),
+                }
+            }
+        }
+
+        // Only include completed/failed tasks that haven't been shown to user
+        if matches!(execution.status, AgentStatus::Completed | AgentStatus::Failed) && !execution.user_notified {
+            unnotified_executions.push(execution);
+        }
+    }
+
+    Ok(unnotified_executions)
+}
+
+#[allow(unused_variables)]
+fn is_process_alive(pid: u32) -> bool {
+    #[cfg(unix)]
+    {
+        // Use `kill -0` to check if process exists without actually killing it
+        std::process::Command::new("kill")
+            .args(["-0", &pid.to_string()])
+            .output()
+            .map(|output| output.status.success())
+            .unwrap_or(false)
+    }
+
+    #[cfg(not(unix))]
+    {
+        // For non-Unix systems, assume process is alive (fallback)
+        true
+    }
+}
+
+pub async fn validate_agent_availability(_os: &Os, _agent: &str) -> Result<()> {
+    // For now, accept any agent name (no need to print here, will show in approval)
+    Ok(())
+}
+
+pub async fn request_user_approval(agent: &str, agents: &Agents, task: &str) -> Result<()> {
+    let config = agents
+        .agents
+        .get(agent)
+        .ok_or(eyre::eyre!("No agent by the name {agent} found"))?
+        .into();
+    display_agent_info(agent, task, &config)?;
+    get_user_confirmation()?;
+
+    Ok(())
+}
+
+/// Loads all the tasks of the board, with the files they wrote to.
+pub async fn load_board(os: &Os) -> Result<Vec<AgentExecution>> {
+    let mut dir_walker = os.fs.read_dir(subagents_dir(os).await?).await?;
+    let mut board = Vec::new();
+
+    while let Ok(Some(file)) = dir_walker.next_entry().await {
+        let path = file.path();
+        if path.extension().is_none_or(|ext| ext != "json") {
+            continue;
+        }
+        let bytes = os.fs.read(&path).await?;
+        let mut execution = serde_json::from_slice::<AgentExecution>(&bytes)?;
+        if execution.id.is_empty() {
+            execution.id = execution.agent.clone();
+        }
+        if let Ok(touched_files) = os.fs.read_to_string(touched_files_path(os, &execution.id).await?).await {
+            execution.touched_files = touched_files
+                .lines()
+                .map(|path| match &execution.staged {
+                    Some(staged) => staged.original_path(path),
+                    None => path.to_string(),
+                })
+                .collect();
+        }
+        board.push(execution);
+    }
+
+    Ok(board)
+}
+
+pub async fn load_task(os: &Os, task_id: &str) -> Result<Option<AgentExecution>> {
+    Ok(load_board(os)
+        .await?
+        .into_iter()
+        .find(|execution| execution.id == task_id))
+}
+
+pub async fn save_agent_execution(os: &Os, execution: &AgentExecution) -> Result<()> {
+    let id = if execution.id.is_empty() {
+        &execution.agent
+    } else {
+        &execution.id
+    };
+    let file_path = subagents_dir(os).await?.join(format!("{}.json", id));
+    let content = serde_json::to_string_pretty(execution)?;
+    os.fs.write(&file_path, content).await?;
+    Ok(())
+}
+
+async fn remove_agent_execution(os: &Os, task_id: &str) -> Result<()> {
+    if let Some(mut execution) = load_task(os, task_id).await? {
+        if let Some(staged) = execution.staged.as_mut() {
+            staged.discard().await?;
+        }
+    }
+    let dir = subagents_dir(os).await?;
+    os.fs.remove_file(dir.join(format!("{}.json", task_id))).await?;
+    for path in [
+        touched_files_path(os, task_id).await?,
+        output_log_path(os, task_id).await?,
+        mailbox_path(os, task_id).await?,
+        snapshot_path(os, task_id).await?,
+        snapshot::exit_code_path(&snapshot_path(os, task_id).await?),
+    ] {
+        if os.fs.exists(&path) {
+            os.fs.remove_file(path).await?;
+        }
+    }
+    Ok(())
+}
+
+async fn touched_files_path(os: &Os, task_id: &str) -> Result<PathBuf> {
+    Ok(subagents_dir(os).await?.join(format!("{}.touched", task_id)))
+}
+
+async fn snapshot_path(os: &Os, task_id: &str) -> Result<PathBuf> {
+    Ok(subagents_dir(os).await?.join(format!("{}.snapshot", task_id)))
+}
+
+async fn mailbox_path(os: &Os, task_id: &str) -> Result<PathBuf> {
+    Ok(subagents_dir(os).await?.join(format!("{}.messages", task_id)))
+}
+
+async fn output_log_path(os: &Os, task_id: &str) -> Result<PathBuf> {
+    Ok(subagents_dir(os).await?.join(format!("{}.log", task_id)))
+}
+
+pub async fn subagents_dir(os: &Os) -> Result<PathBuf> {
+    Ok(PathResolver::new(os).workspace().ensure_subagents_dir().await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn get_schema() {
+        let schema = schemars::schema_for!(Delegate);
+        println!("{}", serde_json::to_string_pretty(&schema).unwrap());
+    }
+
+    #[test]
+    fn test_task_prompt() {
+        let execution = |id: &str, status: AgentStatus, task: &str| AgentExecution {
+            id: id.to_string(),
+            status,
+            task: task.to_string(),
+            cwd: "/repo".to_string(),
+            ..Default::default()
+        };
+        let task = execution("docs-1", AgentStatus::Queued, "Update the docs");
+        assert_eq!(task_prompt(&task, &[task.clone()]), "Update the docs");
+
+        let board = vec![
+            task.clone(),
+            execution("rust-1", AgentStatus::Running, "Fix the tests"),
+            execution("rust-2", AgentStatus::Completed, "Add a CLI"),
+        ];
+        assert_eq!(
+            task_prompt(&task, &board),
+            "Update the docs\n\nOther agents are working on the following tasks at the same time. Avoid modifying \
+            the files these tasks are likely to change:\n- rust-1 (running, in /repo): Fix the tests"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_load_board() {
+        let os = Os::new().await.unwrap();
+        let dir = subagents_dir(&os).await.unwrap();
+        // Saved before tasks had ids
+        os.fs
+            .write(
+                dir.join("rust.json"),
+                r#"{"agent": "rust", "task": "Fix the tests", "status": "completed", "launched_at": 1700000000}"#,
+            )
+            .await
+            .unwrap();
+        save_agent_execution(&os, &AgentExecution {
+            id: "docs-1".to_string(),
+            agent: "docs".to_string(),
+            status: AgentStatus::Running,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        os.fs
+            .write(dir.join("docs-1.touched"), "/repo/README.md\n/repo/docs/index.md\n")
+            .await
+            .unwrap();
+
+        let mut board = load_board(&os).await.unwrap();
+        board.sort_by(|a, b| a.id.cmp(&b.id));
+        assert_eq!(
+            board.iter().map(|execution| execution.id.as_str()).collect::<Vec<_>>(),
+            vec!["docs-1", "rust"]
+        );
+        assert_eq!(
+            board[0].touched_files,
+            BTreeSet::from(["/repo/README.md".to_string(), "/repo/docs/index.md".to_string()])
+        );
+        assert!(board[1].touched_files.is_empty());
+    }
+}
@@ -0,0 +1,90 @@
+//! Checkpoints of delegated tasks, so that a task outlives the session that delegated it. The task
+//! saves its conversation to its `<task id>.snapshot` file after every turn and records its exit
+//! code in `<task id>.exit` when it ends. A task whose process died without an exit code was
+//! interrupted, and continues from its last snapshot with `q agent resume <task id>`.
+
+use std::path::{
+    Path,
+    PathBuf,
+};
+
+use crate::cli::chat::conversation::ConversationState;
+use crate::os::Os;
+use crate::util::consts::env_var::Q_DELEGATE_SNAPSHOT;
+
+/// Snapshot file of this session, when it runs a delegated task.
+fn snapshot_path(os: &Os) -> Option<PathBuf> {
+    os.env.get(Q_DELEGATE_SNAPSHOT).ok().map(PathBuf::from)
+}
+
+/// Exit code file going along with the snapshot file `snapshot`.
+pub fn exit_code_path(snapshot: &Path) -> PathBuf {
+    snapshot.with_extension("exit")
+}
+
+/// Whether this session runs a delegated task, whose conversation is checkpointed.
+pub fn is_enabled(os: &Os) -> bool {
+    snapshot_path(os).is_some()
+}
+
+/// Checkpoints the conversation of the task. Written to a temporary file first, so that a task
+/// dying halfway through leaves the previous snapshot intact.
+pub fn save(os: &Os, conversation: &ConversationState) {
+    let Some(path) = snapshot_path(os) else {
+        return;
+    };
+    let tmp = path.with_extension("snapshot.tmp");
+    let result = serde_json::to_vec(conversation)
+        .map_err(std::io::Error::from)
+        .and_then(|bytes| std::fs::write(&tmp, bytes))
+        .and_then(|()| std::fs::rename(&tmp, &path));
+    if let Err(err) = result {
+        tracing::warn!(?err, "Failed to checkpoint the conversation of the delegated task");
+    }
+}
+
+/// The last checkpoint of the task, when this session resumes it.
+pub fn load(os: &Os) -> Option<ConversationState> {
+    let bytes = std::fs::read(snapshot_path(os)?).ok()?;
+    match serde_json::from_slice(&bytes) {
+        Ok(conversation) => Some(conversation),
+        Err(err) => {
+            tracing::warn!(?err, "Failed to read the checkpoint of the delegated task");
+            None
+        },
+    }
+}
+
+/// Records the exit code of the task, telling the session that delegated it that the task ended
+/// rather than being interrupted.
+pub fn record_exit(os: &Os, code: i32) {
+    let Some(path) = snapshot_path(os) else {
+        return;
+    };
+    if let Err(err) = std::fs::write(exit_code_path(&path), code.to_string()) {
+        tracing::warn!(?err, "Failed to record the exit code of the delegated task");
+    }
+}
+
+/// Exit code the task recorded in the exit code file `path`.
+pub fn read_exit_code(path: &Path) -> Option<i32> {
+    std::fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_exit_code() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = exit_code_path(&dir.path().join("rust-1.snapshot"));
+        assert_eq!(path, dir.path().join("rust-1.exit"));
+        assert_eq!(read_exit_code(&path), None);
+
+        std::fs::write(&path, "3").unwrap();
+        assert_eq!(read_exit_code(&path), Some(3));
+        std::fs::write(&path, "").unwrap();
+        assert_eq!(read_exit_code(&path), None);
+    }
+}
@@ -0,0 +1,490 @@
+//! Staging of the changes of delegated tasks. Tasks launched in a git repository run in a
+//! worktree of their own, created from the working tree as it was when they started, including
+//! its untracked files, so that their changes only land in the working tree once reviewed and
+//! merged with `/agents merge`.
+
+use std::path::{
+    Path,
+    PathBuf,
+};
+use std::process::{
+    Output,
+    Stdio,
+};
+
+use eyre::{
+    Result,
+    bail,
+    eyre,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use tokio::io::AsyncWriteExt as _;
+use tokio::process::Command;
+
+use crate::theme::StyledText;
+
+/// Worktree of a task and the commit it was created from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StagedChanges {
+    /// Root of the repository the changes are merged into
+    pub repo_root: PathBuf,
+    pub worktree: PathBuf,
+    /// Commit of the working tree when the task started, including the uncommitted changes and
+    /// the untracked files
+    pub base: String,
+    #[serde(default)]
+    pub state: StagingState,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StagingState {
+    /// Waiting for the review of the user
+    #[default]
+    Pending,
+    Merged,
+    Discarded,
+}
+
+/// A file changed by a task, as listed by `git diff --name-status`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileChange {
+    pub status: char,
+    pub path: String,
+}
+
+impl StagedChanges {
+    /// Creates the worktree of the task `task_id` in `worktrees_dir` when `cwd` is in a git
+    /// repository, returning it along with the directory the task runs in.
+    pub async fn create(cwd: &Path, worktrees_dir: &Path, task_id: &str) -> Result<Option<(Self, PathBuf)>> {
+        let Some(repo_root) = repo_root(cwd).await else {
+            return Ok(None);
+        };
+        let base = snapshot(&repo_root, worktrees_dir, task_id).await?;
+
+        let worktree = worktrees_dir.join(task_id);
+        git(&repo_root, &[
+            "worktree",
+            "add",
+            "--detach",
+            &worktree.to_string_lossy(),
+            &base,
+        ])
+        .await?;
+
+        let staged = Self {
+            repo_root,
+            worktree,
+            base,
+            state: StagingState::Pending,
+        };
+        let run_dir = staged.run_dir(cwd)?;
+        Ok(Some((staged, run_dir)))
+    }
+
+    /// Directory of the worktree matching `cwd` in the repository, which the task runs in.
+    pub fn run_dir(&self, cwd: &Path) -> Result<PathBuf> {
+        let relative = cwd
+            .canonicalize()?
+            .strip_prefix(self.repo_root.canonicalize()?)
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        Ok(self.worktree.join(relative))
+    }
+
+    /// Path in the repository of a file the task wrote to in its worktree, so that the files of
+    /// tasks running in different worktrees can be compared.
+    pub fn original_path(&self, path: &str) -> String {
+        match Path::new(path).strip_prefix(&self.worktree) {
+            Ok(relative) => self.repo_root.join(relative).to_string_lossy().to_string(),
+            Err(_) => path.to_string(),
+        }
+    }
+
+    /// Files changed by the task, relative to the root of the repository.
+    pub async fn changes(&self) -> Result<Vec<FileChange>> {
+        let output = self.diff_cached(&["--name-status"]).await?;
+        Ok(stdout(&output)
+            .lines()
+            .filter_map(|line| {
+                let (status, path) = line.split_once('\t')?;
+                Some(FileChange {
+                    status: status.chars().next()?,
+                    // Renames list the old and new paths
+                    path: path.rsplit('\t').next().unwrap_or(path).to_string(),
+                })
+            })
+            .collect())
+    }
+
+    /// The full diff of the changes, colored for the terminal.
+    pub async fn diff(&self) -> Result<String> {
+        Ok(String::from_utf8_lossy(&self.diff_cached(&["--color=always"]).await?.stdout).into_owned())
+    }
+
+    /// Applies the changes to the working tree of the repository, then removes the worktree.
+    pub async fn merge(&mut self) -> Result<()> {
+        let patch = self.diff_cached(&["--binary"]).await?.stdout;
+        if !patch.is_empty() {
+            git_with(
+                &self.repo_root,
+                &["apply", "--whitespace=nowarn", "-"],
+                None,
+                Some(patch),
+            )
+            .await
+            .map_err(|err| eyre!("The changes don't apply to the working tree anymore: {err}"))?;
+        }
+        self.remove_worktree().await?;
+        self.state = StagingState::Merged;
+        Ok(())
+    }
+
+    /// Drops the changes, removing the worktree.
+    pub async fn discard(&mut self) -> Result<()> {
+        self.remove_worktree().await?;
+        self.state = StagingState::Discarded;
+        Ok(())
+    }
+
+    async fn remove_worktree(&self) -> Result<()> {
+        if self.worktree.exists() {
+            git(&self.repo_root, &[
+                "worktree",
+                "remove",
+                "--force",
+                &self.worktree.to_string_lossy(),
+            ])
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// Stages everything in the worktree, including new files, to diff it with the base.
+    async fn diff_cached(&self, args: &[&str]) -> Result<Output> {
+        if !self.worktree.exists() {
+            bail!("The changes were already {}", match self.state {
+                StagingState::Merged => "merged",
+                _ => "discarded",
+            });
+        }
+        git(&self.worktree, &["add", "-A"]).await?;
+        let mut diff_args = vec!["diff", "--cached", self.base.as_str()];
+        diff_args.extend(args);
+        git(&self.worktree, &diff_args).await
+    }
+}
+
+/// Commits the working tree of the repository without touching it, or its index: the changes of
+/// the tracked files with `git stash create`, then the untracked files that aren't ignored, which
+/// the stash leaves out, added to a scratch index.
+///
+/// The directory of the worktrees, holding the state of the tasks too, isn't part of it.
+async fn snapshot(repo_root: &Path, worktrees_dir: &Path, task_id: &str) -> Result<String> {
+    let stash = git(repo_root, &["stash", "create"]).await?;
+    let base = match stdout(&stash) {
+        base if base.is_empty() => stdout(&git(repo_root, &["rev-parse", "HEAD"]).await?),
+        base => base,
+    };
+
+    let excluded = worktrees_dir
+        .parent()
+        .and_then(|dir| dir.canonicalize().ok())
+        .and_then(|dir| Some(dir.strip_prefix(repo_root.canonicalize().ok()?).ok()?.to_path_buf()));
+    let untracked = git(repo_root, &["ls-files", "--others", "--exclude-standard", "-z"]).await?;
+    let untracked = untracked
+        .stdout
+        .split(|byte| *byte == 0)
+        .filter(|path| !path.is_empty())
+        // Nested repositories, such as worktrees, are listed as directories
+        .filter(|path| !path.ends_with(b"/"))
+        .filter(|path| {
+            excluded
+                .as_ref()
+                .is_none_or(|excluded| !Path::new(&*String::from_utf8_lossy(path)).starts_with(excluded))
+        })
+        .collect::<Vec<_>>();
+    if untracked.is_empty() {
+        return Ok(base);
+    }
+
+    std::fs::create_dir_all(worktrees_dir)?;
+    let index = worktrees_dir.join(format!("{task_id}.index"));
+    let tree = async {
+        git_with(repo_root, &["read-tree", &base], Some(&index), None).await?;
+        let pathspecs = untracked.join(&0);
+        git_with(
+            repo_root,
+            &["add", "--pathspec-from-file=-", "--pathspec-file-nul"],
+            Some(&index),
+            Some(pathspecs),
+        )
+        .await?;
+        Ok::<_, eyre::Report>(stdout(&git_with(repo_root, &["write-tree"], Some(&index), None).await?))
+    }
+    .await;
+    let _ = std::fs::remove_file(&index);
+
+    let commit = git(repo_root, &[
+        "-c",
+        "user.name=Amazon Q",
+        "-c",
+        "user.email=q@localhost",
+        "commit-tree",
+        &tree?,
+        "-p",
+        &base,
+        "-m",
+        "Untracked files",
+    ])
+    .await?;
+    Ok(stdout(&commit))
+}
+
+/// Lists the changes like `/checkpoint expand` does.
+pub fn format_changes(changes: &[FileChange]) -> String {
+    changes
+        .iter()
+        .map(|change| match change.status {
+            'A' => StyledText::success(&format!("  + {} (added)\n", change.path)),
+            'D' => StyledText::error(&format!("  - {} (deleted)\n", change.path)),
+            'R' => StyledText::warning(&format!("  ~ {} (renamed)\n", change.path)),
+            _ => StyledText::warning(&format!("  ~ {} (modified)\n", change.path)),
+        })
+        .collect()
+}
+
+/// Root of the git repository `dir` is in.
+pub async fn repo_root(dir: &Path) -> Option<PathBuf> {
+    let toplevel = git(dir, &["rev-parse", "--show-toplevel"]).await.ok()?;
+    Some(PathBuf::from(stdout(&toplevel)))
+}
+
+async fn git(dir: &Path, args: &[&str]) -> Result<Output> {
+    git_with(dir, args, None, None).await
+}
+
+/// Runs git in `dir`, with the scratch `index` instead of the index of the repository if any,
+/// writing `input` to its STDIN.
+async fn git_with(dir: &Path, args: &[&str], index: Option<&Path>, input: Option<Vec<u8>>) -> Result<Output> {
+    let mut command = Command::new("git");
+    command
+        .args(args)
+        .current_dir(dir)
+        .stdin(if input.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    if let Some(index) = index {
+        command.env("GIT_INDEX_FILE", index);
+    }
+    let mut child = command.spawn()?;
+    if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+        stdin.write_all(&input).await?;
+    }
+    let output = child.wait_with_output().await?;
+    if !output.status.success() {
+        bail!(
+            "git {} failed: {}",
+            args.first().unwrap_or(&""),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(output)
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn init_repo(dir: &Path) {
+        for args in [&["init", "-q"][..], &["config", "user.name", "Q"], &[
+            "config",
+            "user.email",
+            "qcli@local",
+        ]] {
+            git(dir, args).await.unwrap();
+        }
+        std::fs::write(dir.join("README.md"), "# Project\n").unwrap();
+        std::fs::create_dir(dir.join("src")).unwrap();
+        std::fs::write(dir.join("src/lib.rs"), "pub fn answer() -> u32 {\n    42\n}\n").unwrap();
+        git(dir, &["add", "-A"]).await.unwrap();
+        git(dir, &["commit", "-q", "-m", "Initial commit"]).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_stage_and_merge() {
+        let repo = tempfile::tempdir().unwrap();
+        let worktrees = tempfile::tempdir().unwrap();
+        init_repo(repo.path()).await;
+        // Uncommitted changes are part of the base of the task
+        std::fs::write(repo.path().join("README.md"), "# Project\n\nDraft\n").unwrap();
+
+        let (mut staged, run_dir) = StagedChanges::create(&repo.path().join("src"), worktrees.path(), "rust-1")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(run_dir, worktrees.path().join("rust-1").join("src"));
+        assert_eq!(
+            std::fs::read_to_string(staged.worktree.join("README.md")).unwrap(),
+            "# Project\n\nDraft\n"
+        );
+
+        std::fs::write(run_dir.join("lib.rs"), "pub fn answer() -> u32 {\n    43\n}\n").unwrap();
+        std::fs::write(run_dir.join("main.rs"), "fn main() {}\n").unwrap();
+        std::fs::remove_file(staged.worktree.join("README.md")).unwrap();
+        assert_eq!(staged.changes().await.unwrap(), vec![
+            FileChange {
+                status: 'D',
+                path: "README.md".to_string()
+            },
+            FileChange {
+                status: 'M',
+                path: "src/lib.rs".to_string()
+            },
+            FileChange {
+                status: 'A',
+                path: "src/main.rs".to_string()
+            },
+        ]);
+        // Nothing lands in the working tree before the merge
+        assert_eq!(
+            std::fs::read_to_string(repo.path().join("src/lib.rs")).unwrap(),
+            "pub fn answer() -> u32 {\n    42\n}\n"
+        );
+
+        staged.merge().await.unwrap();
+        assert_eq!(staged.state, StagingState::Merged);
+        assert!(!staged.worktree.exists());
+        assert!(!repo.path().join("README.md").exists());
+        assert_eq!(
+            std::fs::read_to_string(repo.path().join("src/lib.rs")).unwrap(),
+            "pub fn answer() -> u32 {\n    43\n}\n"
+        );
+        assert!(repo.path().join("src/main.rs").exists());
+        assert!(staged.changes().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_stage_untracked_files() {
+        let repo = tempfile::tempdir().unwrap();
+        init_repo(repo.path()).await;
+        std::fs::write(repo.path().join(".gitignore"), "target/\n").unwrap();
+        std::fs::write(repo.path().join("src/new.rs"), "pub fn new() {}\n").unwrap();
+        std::fs::create_dir(repo.path().join("target")).unwrap();
+        std::fs::write(repo.path().join("target/build.log"), "built\n").unwrap();
+        // The worktrees and the state of the tasks live in the repository
+        let worktrees = repo.path().join(".amazonq/.subagents/worktrees");
+        std::fs::create_dir_all(&worktrees).unwrap();
+        std::fs::write(repo.path().join(".amazonq/.subagents/rust-0.json"), "{}").unwrap();
+        let (mut other, _) = StagedChanges::create(repo.path(), &worktrees, "rust-0")
+            .await
+            .unwrap()
+            .unwrap();
+
+        let (mut staged, run_dir) = StagedChanges::create(repo.path(), &worktrees, "rust-1")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(run_dir.join("src/new.rs")).unwrap(),
+            "pub fn new() {}\n"
+        );
+        assert!(run_dir.join(".gitignore").exists());
+        assert!(!run_dir.join("target").exists());
+        assert!(!run_dir.join(".amazonq").exists());
+        // Untracked files aren't changes of the task, and the index of the repository is untouched
+        assert_eq!(staged.changes().await.unwrap(), vec![]);
+        assert!(!worktrees.join("rust-1.index").exists());
+        assert_eq!(
+            stdout(
+                &git(repo.path(), &["status", "--porcelain", "--untracked-files=no"])
+                    .await
+                    .unwrap()
+            ),
+            ""
+        );
+
+        std::fs::write(run_dir.join("src/new.rs"), "pub fn new() -> u32 {\n    1\n}\n").unwrap();
+        staged.merge().await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(repo.path().join("src/new.rs")).unwrap(),
+            "pub fn new() -> u32 {\n    1\n}\n"
+        );
+        other.discard().await.unwrap();
+    }
+
+    #[test]
+    fn test_original_path() {
+        let staged = StagedChanges {
+            repo_root: PathBuf::from("/repo"),
+            worktree: PathBuf::from("/repo/.amazonq/.subagents/worktrees/rust-1"),
+            base: "HEAD".to_string(),
+            state: StagingState::Pending,
+        };
+        assert_eq!(
+            staged.original_path("/repo/.amazonq/.subagents/worktrees/rust-1/src/lib.rs"),
+            "/repo/src/lib.rs"
+        );
+        assert_eq!(staged.original_path("/elsewhere/notes.md"), "/elsewhere/notes.md");
+    }
+
+    #[tokio::test]
+    async fn test_merge_conflict_and_discard() {
+        let repo = tempfile::tempdir().unwrap();
+        let worktrees = tempfile::tempdir().unwrap();
+        init_repo(repo.path()).await;
+
+        let (mut staged, run_dir) = StagedChanges::create(repo.path(), worktrees.path(), "rust-1")
+            .await
+            .unwrap()
+            .unwrap();
+        std::fs::write(run_dir.join("src/lib.rs"), "pub fn answer() -> u32 {\n    43\n}\n").unwrap();
+        // Changed by the user in the meantime
+        std::fs::write(repo.path().join("src/lib.rs"), "pub fn answer() -> u32 {\n    44\n}\n").unwrap();
+
+        assert!(staged.merge().await.is_err());
+        assert_eq!(staged.state, StagingState::Pending);
+        assert_eq!(
+            std::fs::read_to_string(repo.path().join("src/lib.rs")).unwrap(),
+            "pub fn answer() -> u32 {\n    44\n}\n"
+        );
+
+        staged.discard().await.unwrap();
+        assert_eq!(staged.state, StagingState::Discarded);
+        assert!(!staged.worktree.exists());
+    }
+
+    #[tokio::test]
+    async fn test_not_a_repository() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(
+            StagedChanges::create(dir.path(), dir.path(), "rust-1").await.unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_format_changes() {
+        let changes = vec![
+            FileChange {
+                status: 'A',
+                path: "src/main.rs".to_string(),
+            },
+            FileChange {
+                status: 'M',
+                path: "src/lib.rs".to_string(),
+            },
+        ];
+        assert_eq!(
+            strip_ansi_escapes::strip_str(format_changes(&changes)),
+            "  + src/main.rs (added)\n  ~ src/lib.rs (modified)\n"
+        );
+    }
+}
@@ -19,6 +19,7 @@ use crate::cli::chat::tools::{
     InvokeOutput,
     MAX_TOOL_RESPONSE_SIZE,
     OutputKind,
+    protected_paths,
 };
 use crate::cli::chat::util::truncate_safe;
 use crate::os::Os;
@@ -41,6 +42,15 @@ pub const READONLY_COMMANDS: &[&str] = &[
     "ls", "cat", "echo", "pwd", "which", "head", "tail", "find", "grep", "dir", "type",
 ];
 
+/// Programs that connect to the hosts given to them as URLs, `[user@]host[:path]` or host names.
+const NETWORK_CLIENTS: &[&str] = &[
+    "curl", "wget", "http", "https", "xh", "nc", "ncat", "netcat", "telnet", "ssh", "scp", "sftp", "rsync", "ftp",
+    "ping",
+];
+
+/// Subcommands of git that talk to a remote.
+const GIT_NETWORK_SUBCOMMANDS: &[&str] = &["clone", "fetch", "pull", "push", "ls-remote"];
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct ExecuteCommand {
     pub command: String,
@@ -48,6 +58,56 @@ pub struct ExecuteCommand {
 }
 
 impl ExecuteCommand {
+    /// Returns the hosts the command connects to if it is recognized as a network client, or
+    /// [None] otherwise. The list is empty if the hosts could not be determined, e.g. for `git
+    /// push` to a named remote.
+    pub fn network_hosts(&self) -> Option<Vec<String>> {
+        let args = shlex::split(&self.command)
+            .unwrap_or_else(|| self.command.split_whitespace().map(str::to_string).collect());
+
+        let mut is_network_client = false;
+        let mut hosts = Vec::new();
+        for segment in args.split(|arg| matches!(arg.as_str(), "|" | "||" | "&&" | ";" | "&")) {
+            // Skip environment variable assignments and wrappers to get to the actual program
+            let mut words = segment.iter().skip_while(|w| {
+                (w.contains('=') && !w.starts_with('-')) || matches!(w.as_str(), "sudo" | "env" | "time" | "exec")
+            });
+            let Some(program) = words.next() else {
+                continue;
+            };
+            let program = program.rsplit('/').next().unwrap_or(program);
+            let rest = words.collect::<Vec<_>>();
+
+            let is_client = match program {
+                "git" => rest
+                    .iter()
+                    .find(|w| !w.starts_with('-'))
+                    .is_some_and(|sub| GIT_NETWORK_SUBCOMMANDS.contains(&sub.as_str())),
+                program => NETWORK_CLIENTS.contains(&program),
+            };
+            if !is_client {
+                continue;
+            }
+            is_network_client = true;
+
+            let remote_hosts = rest.iter().filter_map(|w| host_from_remote(w)).collect::<Vec<_>>();
+            if remote_hosts.is_empty() {
+                // e.g. `ping example.com` or `nc example.com 80`
+                hosts.extend(
+                    rest.iter()
+                        .filter(|w| !w.starts_with('-'))
+                        .find_map(|w| host_from_name(w)),
+                );
+            } else {
+                hosts.extend(remote_hosts);
+            }
+        }
+
+        hosts.sort();
+        hosts.dedup();
+        is_network_client.then_some(hosts)
+    }
+
     pub fn requires_acceptance(&self, allowed_commands: Option<&Vec<String>>, allow_read_only: bool) -> bool {
         // Always require acceptance for multi-line commands.
         if self.command.contains("\n") || self.command.contains("\r") {
@@ -179,6 +239,10 @@ impl ExecuteCommand {
             StyledText::reset(),
         )?;
 
+        if let Some(hosts) = self.network_hosts() {
+            super::queue_network_hosts(&hosts, output)?;
+        }
+
         // Add the summary if available
         if let Some(ref summary) = self.summary {
             super::display_purpose(Some(summary), output)?;
@@ -189,9 +253,9 @@ impl ExecuteCommand {
         Ok(())
     }
 
-    pub async fn validate(&mut self, _os: &Os) -> Result<()> {
+    pub async fn validate(&mut self, os: &Os) -> Result<()> {
         // TODO: probably some small amount of PATH checking
-        Ok(())
+        protected_paths::check_command(os, &self.command)
     }
 
     pub fn eval_perm(&self, _os: &Os, agent: &Agent) -> PermissionEvalResult {
@@ -289,6 +353,47 @@ pub fn format_output(output: &str, max_size: usize) -> String {
     )
 }
 
+/// Extracts the host of a URL (`https://host/path`) or of a remote in the form `user@host[:path]`
+/// or `host:path`.
+fn host_from_remote(arg: &str) -> Option<String> {
+    if arg.contains("://") {
+        return url::Url::parse(arg)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_lowercase));
+    }
+
+    let (user, rest) = match arg.split_once('@') {
+        Some((user, rest)) => (Some(user), rest),
+        None => (None, arg),
+    };
+    let (host, path) = match rest.split_once(':') {
+        Some((host, path)) => (host, Some(path)),
+        None => (rest, None),
+    };
+    if user.is_none() && path.is_none() {
+        return None;
+    }
+    // Without a user, require something that looks like a domain so that e.g. `-H "Accept: */*"`
+    // isn't mistaken for a remote
+    if !is_host_name(host) || (user.is_none() && !host.contains('.')) {
+        return None;
+    }
+
+    Some(host.to_lowercase())
+}
+
+/// Extracts the host of a bare host name argument, optionally followed by a port or path.
+fn host_from_name(arg: &str) -> Option<String> {
+    let host = arg.split(['/', ':']).next()?;
+    (is_host_name(host) && (host.contains('.') || host == "localhost")).then(|| host.to_lowercase())
+}
+
+fn is_host_name(host: &str) -> bool {
+    !host.is_empty()
+        && !host.starts_with(['.', '-'])
+        && host.chars().all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-')
+}
+
 #[cfg(test)]
 mod tests {
     use std::collections::HashMap;
@@ -747,4 +852,36 @@ mod tests {
         assert!(user_agent_value.contains("ExistingValue"));
         assert!(user_agent_value.contains(USER_AGENT_APP_NAME));
     }
+
+    #[test]
+    fn test_network_hosts() {
+        let cases: &[(&str, Option<&[&str]>)] = &[
+            ("ls -la", None),
+            ("git status", None),
+            ("cat notes.md | grep curl", None),
+            ("curl -s https://api.github.com/repos", Some(&["api.github.com"])),
+            ("curl -H 'Accept: */*' example.com/path", Some(&["example.com"])),
+            (
+                "wget -O out.json https://Example.com/a && ssh deploy@10.0.0.1",
+                Some(&["10.0.0.1", "example.com"]),
+            ),
+            ("scp build.tar.gz user@host.internal:/tmp", Some(&["host.internal"])),
+            (
+                "git clone git@github.com:aws/amazon-q-developer-cli.git",
+                Some(&["github.com"]),
+            ),
+            ("git push origin main", Some(&[])),
+            ("FOO=bar /usr/bin/nc localhost 8080", Some(&["localhost"])),
+            ("sudo ping -c 1 8.8.8.8", Some(&["8.8.8.8"])),
+        ];
+
+        for (command, expected) in cases {
+            let tool = serde_json::from_value::<ExecuteCommand>(serde_json::json!({
+                "command": command,
+            }))
+            .unwrap();
+            let expected = expected.map(|hosts| hosts.iter().copied().map(str::to_string).collect::<Vec<_>>());
+            assert_eq!(tool.network_hosts(), expected, "{command}");
+        }
+    }
 }
@@ -41,6 +41,8 @@ pub async fn run_command<W: Write>(
         .stdin(Stdio::inherit())
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
+        // Stops the command when the tool use is cancelled, e.g. by --timeout
+        .kill_on_drop(true)
         .spawn()
         .wrap_err_with(|| format!("Unable to spawn command '{}'", command))?;
 
@@ -27,6 +27,7 @@ use super::{
     MAX_TOOL_RESPONSE_SIZE,
     OutputKind,
     format_path,
+    protected_paths,
     sanitize_path_tool_arg,
 };
 use crate::cli::agent::{
@@ -40,7 +41,7 @@ use crate::cli::chat::util::images::{
     pre_process,
 };
 use crate::cli::chat::{
-    CONTINUATION_LINE,
+    continuation_line,
     sanitize_unicode_tags,
 };
 use crate::os::Os;
@@ -320,7 +321,7 @@ impl FsRead {
             queue!(
                 updates,
                 style::Print("\n"),
-                style::Print(CONTINUATION_LINE),
+                style::Print(continuation_line()),
                 style::Print("\n")
             )?;
             super::queue_function_result(
@@ -395,6 +396,7 @@ pub struct FsImage {
 impl FsImage {
     pub async fn validate(&mut self, os: &Os) -> Result<()> {
         for path in &self.image_paths {
+            protected_paths::check_path(os, path)?;
             let path = sanitize_path_tool_arg(os, path);
             if let Some(path) = path.to_str() {
                 let processed_path = pre_process(path);
@@ -447,6 +449,7 @@ impl FsLine {
     const DEFAULT_START_LINE: i32 = 1;
 
     pub async fn validate(&mut self, os: &Os) -> Result<()> {
+        protected_paths::check_path(os, &self.path)?;
         let path = sanitize_path_tool_arg(os, &self.path);
         if !path.exists() {
             bail!("'{}' does not exist", self.path);
@@ -577,6 +580,7 @@ impl FsSearch {
     const MATCHING_LINE_PREFIX: &str = "→ ";
 
     pub async fn validate(&mut self, os: &Os) -> Result<()> {
+        protected_paths::check_path(os, &self.path)?;
         let path = sanitize_path_tool_arg(os, &self.path);
         let relative_path = format_path(os.env.current_dir()?, &path);
         if !path.exists() {
@@ -676,6 +680,7 @@ impl FsDirectory {
     const DEFAULT_DEPTH: usize = 0;
 
     pub async fn validate(&mut self, os: &Os) -> Result<()> {
+        protected_paths::check_path(os, &self.path)?;
         let path = sanitize_path_tool_arg(os, &self.path);
         let relative_path = format_path(os.env.current_dir()?, &path);
         if !path.exists() {
@@ -4,14 +4,12 @@ use std::path::{
     Path,
     PathBuf,
 };
-use std::sync::LazyLock;
 
 use crossterm::queue;
 use crossterm::style::{
     self,
 };
 use eyre::{
-    ContextCompat as _,
     Result,
     bail,
     eyre,
@@ -19,13 +17,7 @@ use eyre::{
 use globset::GlobSetBuilder;
 use serde::Deserialize;
 use similar::DiffableStr;
-use syntect::easy::HighlightLines;
-use syntect::highlighting::ThemeSet;
-use syntect::parsing::SyntaxSet;
-use syntect::util::{
-    LinesWithEndings,
-    as_24_bit_terminal_escaped,
-};
+use syntect::util::LinesWithEndings;
 use tracing::{
     error,
     warn,
@@ -34,8 +26,8 @@ use tracing::{
 use super::{
     InvokeOutput,
     format_path,
+    protected_paths,
     sanitize_path_tool_arg,
-    supports_truecolor,
 };
 use crate::cli::agent::{
     Agent,
@@ -43,15 +35,13 @@ use crate::cli::agent::{
 };
 use crate::cli::chat::line_tracker::FileLineTracker;
 use crate::os::Os;
-use crate::theme::{
-    StyledText,
-    theme,
-};
+use crate::theme::StyledText;
 use crate::util::paths;
 use crate::util::tool_permission_checker::is_tool_in_allowlist;
-
-static SYNTAX_SET: LazyLock<SyntaxSet> = LazyLock::new(SyntaxSet::load_defaults_newlines);
-static THEME_SET: LazyLock<ThemeSet> = LazyLock::new(ThemeSet::load_defaults);
+use crate::util::ui::diff::{
+    DiffOptions,
+    print_diff,
+};
 
 #[derive(Debug, Clone, Deserialize)]
 #[serde(tag = "command")]
@@ -310,13 +300,11 @@ impl FsWrite {
                 let path = sanitize_path_tool_arg(os, path);
                 let relative_path = format_path(cwd, &path);
                 let prev = if os.fs.exists(&path) {
-                    let file = os.fs.read_to_string_sync(&path)?;
-                    stylize_output_if_able(&path, &file)
+                    os.fs.read_to_string_sync(&path)?
                 } else {
-                    Default::default()
+                    String::new()
                 };
-                let new = stylize_output_if_able(&relative_path, &file_text);
-                print_diff(output, &prev, &new, 1)?;
+                print_diff(output, &relative_path, &prev, &file_text, DiffOptions::default())?;
 
                 // Display summary as purpose if available after the diff
                 super::display_purpose(self.get_summary(), output)?;
@@ -343,9 +331,10 @@ impl FsWrite {
                 let old = [prefix, insert_line_content, suffix].join("");
                 let new = [prefix, insert_line_content, new_str, suffix].join("");
 
-                let old = stylize_output_if_able(&relative_path, &old);
-                let new = stylize_output_if_able(&relative_path, &new);
-                print_diff(output, &old, &new, start_line)?;
+                print_diff(output, &relative_path, &old, &new, DiffOptions {
+                    start_line,
+                    ..Default::default()
+                })?;
 
                 // Display summary as purpose if available after the diff
                 super::display_purpose(self.get_summary(), output)?;
@@ -362,9 +351,10 @@ impl FsWrite {
                     Some((start_line, end_line)) => (start_line, end_line),
                     _ => (0, 0),
                 };
-                let old_str = stylize_output_if_able(&relative_path, old_str);
-                let new_str = stylize_output_if_able(&relative_path, new_str);
-                print_diff(output, &old_str, &new_str, start_line)?;
+                print_diff(output, &relative_path, old_str, new_str, DiffOptions {
+                    start_line,
+                    ..Default::default()
+                })?;
 
                 // Display summary as purpose if available after the diff
                 super::display_purpose(self.get_summary(), output)?;
@@ -375,8 +365,10 @@ impl FsWrite {
                 let path = sanitize_path_tool_arg(os, path);
                 let relative_path = format_path(cwd, &path);
                 let start_line = os.fs.read_to_string_sync(&path)?.lines().count() + 1;
-                let file = stylize_output_if_able(&relative_path, new_str);
-                print_diff(output, &Default::default(), &file, start_line)?;
+                print_diff(output, &relative_path, "", new_str, DiffOptions {
+                    start_line,
+                    ..Default::default()
+                })?;
 
                 // Display summary as purpose if available after the diff
                 super::display_purpose(self.get_summary(), output)?;
@@ -387,6 +379,12 @@ impl FsWrite {
     }
 
     pub async fn validate(&mut self, os: &Os) -> Result<()> {
+        let (FsWrite::Create { path, .. }
+        | FsWrite::StrReplace { path, .. }
+        | FsWrite::Insert { path, .. }
+        | FsWrite::Append { path, .. }) = self;
+        protected_paths::check_path(os, path)?;
+
         match self {
             FsWrite::Create { path, .. } => {
                 if path.is_empty() {
@@ -633,114 +631,6 @@ fn get_lines_with_context(
     )
 }
 
-/// Prints a git-diff style comparison between `old_str` and `new_str`.
-/// - `start_line` - 1-indexed line number that `old_str` and `new_str` start at.
-fn print_diff(
-    output: &mut impl Write,
-    old_str: &StylizedFile,
-    new_str: &StylizedFile,
-    start_line: usize,
-) -> Result<()> {
-    let diff = similar::TextDiff::from_lines(&old_str.content, &new_str.content);
-
-    // First, get the gutter width required for both the old and new lines.
-    let (mut max_old_i, mut max_new_i) = (1, 1);
-    for change in diff.iter_all_changes() {
-        if let Some(i) = change.old_index() {
-            max_old_i = i + start_line;
-        }
-        if let Some(i) = change.new_index() {
-            max_new_i = i + start_line;
-        }
-    }
-    let old_line_num_width = terminal_width_required_for_line_count(max_old_i);
-    let new_line_num_width = terminal_width_required_for_line_count(max_new_i);
-
-    // Now, print
-    fn fmt_index(i: Option<usize>, start_line: usize) -> String {
-        match i {
-            Some(i) => (i + start_line).to_string(),
-            _ => " ".to_string(),
-        }
-    }
-    for change in diff.iter_all_changes() {
-        // Define the colors per line.
-        let (text_color, gutter_bg_color, line_bg_color) = match (change.tag(), new_str.truecolor) {
-            (similar::ChangeTag::Equal, true) => (style::Color::Reset, new_str.gutter_bg, new_str.line_bg),
-            (similar::ChangeTag::Delete, true) => (
-                style::Color::Reset,
-                style::Color::Rgb { r: 79, g: 40, b: 40 },
-                style::Color::Rgb { r: 36, g: 25, b: 28 },
-            ),
-            (similar::ChangeTag::Insert, true) => (
-                style::Color::Reset,
-                style::Color::Rgb { r: 40, g: 67, b: 43 },
-                style::Color::Rgb { r: 24, g: 38, b: 30 },
-            ),
-            (similar::ChangeTag::Equal, false) => (style::Color::Reset, new_str.gutter_bg, new_str.line_bg),
-            (similar::ChangeTag::Delete, false) => (theme().status.error, new_str.gutter_bg, new_str.line_bg),
-            (similar::ChangeTag::Insert, false) => (theme().status.success, new_str.gutter_bg, new_str.line_bg),
-        };
-        // Define the change tag character to print, if any.
-        let sign = match change.tag() {
-            similar::ChangeTag::Equal => " ",
-            similar::ChangeTag::Delete => "-",
-            similar::ChangeTag::Insert => "+",
-        };
-
-        let old_i_str = fmt_index(change.old_index(), start_line);
-        let new_i_str = fmt_index(change.new_index(), start_line);
-
-        // Print the gutter and line numbers.
-        queue!(output, style::SetBackgroundColor(gutter_bg_color))?;
-        queue!(
-            output,
-            style::SetForegroundColor(text_color),
-            style::Print(sign),
-            style::Print(" ")
-        )?;
-        queue!(
-            output,
-            style::Print(format!(
-                "{:>old_line_num_width$}",
-                old_i_str,
-                old_line_num_width = old_line_num_width
-            ))
-        )?;
-        if sign == " " {
-            queue!(output, style::Print(", "))?;
-        } else {
-            queue!(output, style::Print("  "))?;
-        }
-        queue!(
-            output,
-            style::Print(format!(
-                "{:>new_line_num_width$}",
-                new_i_str,
-                new_line_num_width = new_line_num_width
-            ))
-        )?;
-        // Print the line.
-        queue!(
-            output,
-            StyledText::reset(),
-            style::Print(":"),
-            style::SetForegroundColor(text_color),
-            style::SetBackgroundColor(line_bg_color),
-            style::Print(" "),
-            style::Print(change),
-            StyledText::reset(),
-        )?;
-    }
-    queue!(
-        output,
-        crossterm::terminal::Clear(crossterm::terminal::ClearType::UntilNewLine),
-        style::Print("\n"),
-    )?;
-
-    Ok(())
-}
-
 /// Returns a 1-indexed line number range of the start and end of `needle` inside `file`.
 fn line_number_at(file: impl AsRef<str>, needle: impl AsRef<str>) -> Option<(usize, usize)> {
     let file = file.as_ref();
@@ -754,110 +644,6 @@ fn line_number_at(file: impl AsRef<str>, needle: impl AsRef<str>) -> Option<(usi
     }
 }
 
-/// Returns the number of terminal cells required for displaying line numbers. This is used to
-/// determine how many characters the gutter should allocate when displaying line numbers for a
-/// text file.
-///
-/// For example, `10` and `99` both take 2 cells, whereas `100` and `999` take 3.
-fn terminal_width_required_for_line_count(line_count: usize) -> usize {
-    line_count.to_string().chars().count()
-}
-
-fn stylize_output_if_able(path: impl AsRef<Path>, file_text: &str) -> StylizedFile {
-    if supports_truecolor() {
-        match stylized_file(path, file_text) {
-            Ok(s) => return s,
-            Err(err) => {
-                error!(?err, "unable to syntax highlight the output");
-            },
-        }
-    }
-    StylizedFile {
-        truecolor: false,
-        content: file_text.to_string(),
-        gutter_bg: style::Color::Reset,
-        line_bg: style::Color::Reset,
-    }
-}
-
-/// Represents a [String] that is potentially stylized with truecolor escape codes.
-#[derive(Debug)]
-struct StylizedFile {
-    /// Whether or not the file is stylized with 24bit color.
-    truecolor: bool,
-    /// File content. If [Self::truecolor] is true, then it has escape codes for styling with 24bit
-    /// color.
-    content: String,
-    /// Background color for the gutter.
-    gutter_bg: style::Color,
-    /// Background color for the line content.
-    line_bg: style::Color,
-}
-
-impl Default for StylizedFile {
-    fn default() -> Self {
-        Self {
-            truecolor: false,
-            content: Default::default(),
-            gutter_bg: style::Color::Reset,
-            line_bg: style::Color::Reset,
-        }
-    }
-}
-
-/// Returns a 24bit terminal escaped syntax-highlighted [String] of the file pointed to by `path`,
-/// if able.
-fn stylized_file(path: impl AsRef<Path>, file_text: impl AsRef<str>) -> Result<StylizedFile> {
-    let ps = &*SYNTAX_SET;
-    let ts = &*THEME_SET;
-
-    let extension = path
-        .as_ref()
-        .extension()
-        .wrap_err("missing extension")?
-        .to_str()
-        .wrap_err("not utf8")?;
-
-    let syntax = ps
-        .find_syntax_by_extension(extension)
-        .wrap_err_with(|| format!("missing extension: {}", extension))?;
-
-    let theme = &ts.themes["base16-ocean.dark"];
-    let mut highlighter = HighlightLines::new(syntax, theme);
-    let file_text = file_text.as_ref().lines();
-    let mut file = String::new();
-    for line in file_text {
-        let mut ranges = Vec::new();
-        ranges.append(&mut highlighter.highlight_line(line, ps)?);
-        let mut escaped_line = as_24_bit_terminal_escaped(&ranges[..], false);
-        escaped_line.push_str(&format!(
-            "{}\n",
-            crossterm::terminal::Clear(crossterm::terminal::ClearType::UntilNewLine),
-        ));
-        file.push_str(&escaped_line);
-    }
-
-    let (line_bg, gutter_bg) = match (theme.settings.background, theme.settings.gutter) {
-        (Some(line_bg), Some(gutter_bg)) => (line_bg, gutter_bg),
-        (Some(line_bg), None) => (line_bg, line_bg),
-        _ => bail!("missing theme"),
-    };
-    Ok(StylizedFile {
-        truecolor: true,
-        content: file,
-        gutter_bg: syntect_to_crossterm_color(gutter_bg),
-        line_bg: syntect_to_crossterm_color(line_bg),
-    })
-}
-
-fn syntect_to_crossterm_color(syntect: syntect::highlighting::Color) -> style::Color {
-    style::Color::Rgb {
-        r: syntect.r,
-        g: syntect.g,
-        b: syntect.b,
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use std::collections::HashMap;
@@ -1232,16 +1018,6 @@ mod tests {
         assert_eq!(get_lines_with_context(content, 4, 100, 2), ("World!\nhow\n", 2, "", 6));
     }
 
-    #[test]
-    fn test_gutter_width() {
-        assert_eq!(terminal_width_required_for_line_count(1), 1);
-        assert_eq!(terminal_width_required_for_line_count(9), 1);
-        assert_eq!(terminal_width_required_for_line_count(10), 2);
-        assert_eq!(terminal_width_required_for_line_count(99), 2);
-        assert_eq!(terminal_width_required_for_line_count(100), 3);
-        assert_eq!(terminal_width_required_for_line_count(999), 3);
-    }
-
     #[tokio::test]
     async fn test_fs_write_with_tilde_paths() {
         // Create a test context
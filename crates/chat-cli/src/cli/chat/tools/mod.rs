@@ -6,6 +6,8 @@ pub mod fs_write;
 pub mod gh_issue;
 pub mod introspect;
 pub mod knowledge;
+pub mod protected_paths;
+pub mod spawn_subagent;
 pub mod thinking;
 pub mod todo;
 pub mod use_aws;
@@ -46,6 +48,7 @@ use serde::{
     Deserialize,
     Serialize,
 };
+use spawn_subagent::SpawnSubagent;
 use thinking::Thinking;
 use todo::TodoList;
 use tracing::error;
@@ -71,7 +74,7 @@ use crate::theme::{
 };
 
 pub const DEFAULT_APPROVE: [&str; 0] = [];
-pub const NATIVE_TOOLS: [&str; 9] = [
+pub const NATIVE_TOOLS: [&str; 10] = [
     "fs_read",
     "fs_write",
     #[cfg(windows)]
@@ -84,8 +87,13 @@ pub const NATIVE_TOOLS: [&str; 9] = [
     "thinking",
     "todo_list",
     "delegate",
+    "spawn_subagent",
 ];
 
+/// Native tools that are always denied in read-only mode. Other tools, such as `execute_bash` and
+/// `use_aws`, are denied depending on the arguments supplied. See [Tool::is_read_only].
+pub const MUTATING_NATIVE_TOOLS: [&str; 3] = ["fs_write", "delegate", "spawn_subagent"];
+
 /// Represents an executable tool use.
 #[allow(clippy::large_enum_variant)]
 #[derive(Debug, Clone)]
@@ -101,6 +109,7 @@ pub enum Tool {
     Thinking(Thinking),
     Todo(TodoList),
     Delegate(Delegate),
+    SpawnSubagent(SpawnSubagent),
 }
 
 impl Tool {
@@ -121,13 +130,25 @@ impl Tool {
             Tool::Thinking(_) => "thinking (prerelease)",
             Tool::Todo(_) => "todo_list",
             Tool::Delegate(_) => "delegate",
+            Tool::SpawnSubagent(_) => "spawn_subagent",
         }
         .to_owned()
     }
 
     /// Whether or not the tool should prompt the user to accept before [Self::invoke] is called.
     pub fn requires_acceptance(&self, os: &Os, agent: &Agent) -> PermissionEvalResult {
-        match self {
+        let network_hosts = match agent.network_policy.is_unrestricted() {
+            true => None,
+            false => self.network_hosts(agent),
+        };
+        if let Some(reasons) = network_hosts
+            .as_ref()
+            .and_then(|hosts| agent.network_policy.denied_reasons(hosts))
+        {
+            return PermissionEvalResult::Deny(reasons);
+        }
+
+        let result = match self {
             Tool::FsRead(fs_read) => fs_read.eval_perm(os, agent),
             Tool::FsWrite(fs_write) => fs_write.eval_perm(os, agent),
             Tool::ExecuteCommand(execute_command) => execute_command.eval_perm(os, agent),
@@ -139,6 +160,46 @@ impl Tool {
             Tool::Todo(_) => PermissionEvalResult::Allow,
             Tool::Knowledge(knowledge) => knowledge.eval_perm(os, agent),
             Tool::Delegate(_) => PermissionEvalResult::Allow, // Allow delegate tool
+            Tool::SpawnSubagent(spawn_subagent) => spawn_subagent.eval_perm(os, agent),
+        };
+
+        // Hosts that can't be determined can't be checked against the network policy either
+        match (network_hosts, result) {
+            (Some(hosts), PermissionEvalResult::Allow) if hosts.is_empty() => PermissionEvalResult::Ask,
+            (_, result) => result,
+        }
+    }
+
+    /// The hosts the tool use connects to, if it reaches out over the network. The list is empty
+    /// when the tool is known to use the network but its hosts could not be determined.
+    pub fn network_hosts(&self, agent: &Agent) -> Option<Vec<String>> {
+        match self {
+            Tool::ExecuteCommand(execute_command) => execute_command.network_hosts(),
+            Tool::Custom(custom_tool) => custom_tool.network_hosts(agent),
+            _ => None,
+        }
+    }
+
+    /// Whether the tool use is free of side effects, i.e. it cannot modify files or run commands
+    /// that change state. Used for enforcing read-only mode.
+    ///
+    /// MCP tools are not described by us and thus are left to the usual permission checks.
+    pub fn is_read_only(&self) -> bool {
+        match self {
+            Tool::FsWrite(_) | Tool::SpawnSubagent(_) => false,
+            Tool::ExecuteCommand(execute_command) => !execute_command.requires_acceptance(None, true),
+            Tool::UseAws(use_aws) => !use_aws.requires_acceptance(),
+            Tool::Delegate(delegate) => !matches!(
+                delegate.operation,
+                delegate::Operation::Launch | delegate::Operation::Tell
+            ),
+            Tool::FsRead(_)
+            | Tool::Custom(_)
+            | Tool::GhIssue(_)
+            | Tool::Introspect(_)
+            | Tool::Knowledge(_)
+            | Tool::Thinking(_)
+            | Tool::Todo(_) => true,
         }
     }
 
@@ -163,11 +224,18 @@ impl Tool {
             Tool::Thinking(think) => think.invoke(stdout).await,
             Tool::Todo(todo) => todo.invoke(os, stdout).await,
             Tool::Delegate(delegate) => delegate.invoke(os, stdout, agents).await,
+            Tool::SpawnSubagent(spawn_subagent) => spawn_subagent.invoke(os, stdout, agents).await,
         }
     }
 
-    /// Queues up a tool's intention in a human readable format
-    pub async fn queue_description(&self, os: &Os, output: &mut ControlEnd<DestinationStdout>) -> Result<()> {
+    /// Queues up a tool's intention in a human readable format. `agent` is the active agent, if
+    /// any, which configures the MCP servers of custom tools.
+    pub async fn queue_description(
+        &self,
+        os: &Os,
+        agent: Option<&Agent>,
+        output: &mut ControlEnd<DestinationStdout>,
+    ) -> Result<()> {
         if output.should_send_structured_event {
             let mut buf = Vec::<u8>::new();
 
@@ -176,13 +244,14 @@ impl Tool {
                 Tool::FsWrite(fs_write) => fs_write.queue_description(os, &mut buf),
                 Tool::ExecuteCommand(execute_command) => execute_command.queue_description(&mut buf),
                 Tool::UseAws(use_aws) => use_aws.queue_description(&mut buf),
-                Tool::Custom(custom_tool) => custom_tool.queue_description(&mut buf),
+                Tool::Custom(custom_tool) => custom_tool.queue_description(agent, &mut buf),
                 Tool::GhIssue(gh_issue) => gh_issue.queue_description(&mut buf),
                 Tool::Introspect(_) => Introspect::queue_description(&mut buf),
                 Tool::Knowledge(knowledge) => knowledge.queue_description(os, &mut buf).await,
                 Tool::Thinking(thinking) => thinking.queue_description(&mut buf),
                 Tool::Todo(_) => Ok(()),
                 Tool::Delegate(delegate) => delegate.queue_description(&mut buf),
+                Tool::SpawnSubagent(spawn_subagent) => spawn_subagent.queue_description(&mut buf),
             }?;
 
             let tool_call_args = ToolCallArgs {
@@ -201,13 +270,14 @@ impl Tool {
                 Tool::FsWrite(fs_write) => fs_write.queue_description(os, output),
                 Tool::ExecuteCommand(execute_command) => execute_command.queue_description(output),
                 Tool::UseAws(use_aws) => use_aws.queue_description(output),
-                Tool::Custom(custom_tool) => custom_tool.queue_description(output),
+                Tool::Custom(custom_tool) => custom_tool.queue_description(agent, output),
                 Tool::GhIssue(gh_issue) => gh_issue.queue_description(output),
                 Tool::Introspect(_) => Introspect::queue_description(output),
                 Tool::Knowledge(knowledge) => knowledge.queue_description(os, output).await,
                 Tool::Thinking(thinking) => thinking.queue_description(output),
                 Tool::Todo(_) => Ok(()),
                 Tool::Delegate(delegate) => delegate.queue_description(output),
+                Tool::SpawnSubagent(spawn_subagent) => spawn_subagent.queue_description(output),
             }?;
         };
 
@@ -228,6 +298,7 @@ impl Tool {
             Tool::Thinking(think) => think.validate(os).await,
             Tool::Todo(todo) => todo.validate(os).await,
             Tool::Delegate(_) => Ok(()), // No validation needed for delegate tool
+            Tool::SpawnSubagent(spawn_subagent) => spawn_subagent.validate(os).await,
         }
     }
 
@@ -334,6 +405,8 @@ pub struct QueuedTool {
     pub accepted: bool,
     pub tool: Tool,
     pub tool_input: serde_json::Value,
+    /// `additionalContext` given by the preToolUse hooks, added to the tool result
+    pub hook_context: Option<String>,
 }
 
 /// The schema specification describing a tool's fields.
@@ -444,10 +517,21 @@ fn format_path(cwd: impl AsRef<Path>, path: impl AsRef<Path>) -> String {
         .unwrap_or(path.as_ref().to_string_lossy().to_string())
 }
 
-fn supports_truecolor() -> bool {
-    // Simple override to disable truecolor since shell_color doesn't use Context.
-    !crate::util::env_var::is_truecolor_disabled()
-        && shell_color::get_color_support().contains(shell_color::ColorSupport::TERM24BIT)
+/// Helper function to display the hosts a tool use connects to, as given by [Tool::network_hosts]
+pub fn queue_network_hosts(hosts: &[String], updates: &mut impl Write) -> Result<()> {
+    queue!(
+        updates,
+        style::Print("Connects to: "),
+        StyledText::warning_fg(),
+        style::Print(if hosts.is_empty() {
+            "unknown host".to_string()
+        } else {
+            hosts.join(", ")
+        }),
+        StyledText::reset(),
+        style::Print("\n"),
+    )?;
+    Ok(())
 }
 
 /// Helper function to display a purpose if available (for execute commands)
@@ -455,9 +539,9 @@ pub fn display_purpose(purpose: Option<&String>, updates: &mut impl Write) -> Re
     if let Some(purpose) = purpose {
         queue!(
             updates,
-            style::Print(super::CONTINUATION_LINE),
+            style::Print(super::continuation_line()),
             style::Print("\n"),
-            style::Print(super::PURPOSE_ARROW),
+            style::Print(super::purpose_arrow()),
             StyledText::info_fg(),
             style::Print("Purpose: "),
             StyledText::reset(),
@@ -480,9 +564,9 @@ pub fn queue_function_result(result: &str, updates: &mut impl Write, is_error: b
 
     // Determine symbol and color
     let (symbol, color) = match (is_error, use_bullet) {
-        (true, _) => (super::ERROR_EXCLAMATION, theme().status.error),
-        (false, true) => (super::TOOL_BULLET, theme().ui.secondary_text),
-        (false, false) => (super::SUCCESS_TICK, theme().status.success),
+        (true, _) => (super::error_exclamation(), theme().status.error),
+        (false, true) => (super::tool_bullet(), theme().ui.secondary_text),
+        (false, false) => (super::success_tick(), theme().status.success),
     };
 
     queue!(updates, style::Print("\n"))?;
@@ -570,6 +654,94 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_is_read_only() {
+        let execute = |command: &str| {
+            Tool::ExecuteCommand(ExecuteCommand {
+                command: command.to_string(),
+                summary: None,
+            })
+        };
+        assert!(execute("ls -la").is_read_only());
+        assert!(execute("cat Cargo.toml | grep version").is_read_only());
+        assert!(!execute("git commit -m 'message'").is_read_only());
+        assert!(!execute("echo hi > file.txt").is_read_only());
+
+        let fs_write = serde_json::from_value::<FsWrite>(serde_json::json!({
+            "command": "create",
+            "path": "/file.txt",
+            "file_text": "hello",
+        }))
+        .unwrap();
+        assert!(!Tool::FsWrite(fs_write).is_read_only());
+
+        let use_aws = |operation_name: &str| {
+            Tool::UseAws(UseAws {
+                service_name: "s3".to_string(),
+                operation_name: operation_name.to_string(),
+                parameters: None,
+                region: "us-west-2".to_string(),
+                profile_name: None,
+                label: None,
+            })
+        };
+        assert!(use_aws("list-buckets").is_read_only());
+        assert!(!use_aws("delete-bucket").is_read_only());
+    }
+
+    #[tokio::test]
+    async fn test_network_policy_requires_acceptance() {
+        use crate::cli::agent::NetworkPolicy;
+
+        let os = Os::new().await.unwrap();
+        let execute = |command: &str| {
+            Tool::ExecuteCommand(ExecuteCommand {
+                command: command.to_string(),
+                summary: None,
+            })
+        };
+        let mut agent = Agent {
+            allowed_tools: ["execute_bash".to_string(), "execute_cmd".to_string()].into(),
+            ..Default::default()
+        };
+
+        // Unrestricted by default
+        assert!(matches!(
+            execute("curl https://example.com").requires_acceptance(&os, &agent),
+            PermissionEvalResult::Allow
+        ));
+
+        agent.network_policy = NetworkPolicy::Allowlist {
+            allowed_domains: vec!["github.com".to_string()],
+        };
+        assert!(matches!(
+            execute("curl https://api.github.com").requires_acceptance(&os, &agent),
+            PermissionEvalResult::Allow
+        ));
+        assert!(matches!(
+            execute("curl https://example.com").requires_acceptance(&os, &agent),
+            PermissionEvalResult::Deny(_)
+        ));
+        assert!(matches!(
+            execute("git push origin main").requires_acceptance(&os, &agent),
+            PermissionEvalResult::Ask
+        ));
+        assert!(matches!(
+            execute("ls").requires_acceptance(&os, &agent),
+            PermissionEvalResult::Allow
+        ));
+
+        agent.network_policy = NetworkPolicy::None;
+        assert!(matches!(
+            execute("curl https://api.github.com").requires_acceptance(&os, &agent),
+            PermissionEvalResult::Deny(_)
+        ));
+        assert!(matches!(
+            execute("ls").requires_acceptance(&os, &agent),
+            PermissionEvalResult::Allow
+        ));
+    }
+
     #[tokio::test]
     async fn test_format_path() {
         async fn assert_paths(cwd: &str, path: &str, expected: &str) {
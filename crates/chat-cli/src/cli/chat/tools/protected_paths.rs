@@ -0,0 +1,233 @@
+//! Paths that tools are never allowed to touch, regardless of the permissions granted to them.
+//!
+//! Unlike `deniedPaths` in the agent's tool settings, this list cannot be changed by the user or
+//! the agent config, and is checked while validating the tool use - before any permission
+//! evaluation takes place. This means that `--trust-all-tools` and `/tools trust-all` have no
+//! effect on it.
+
+use std::path::{
+    Component,
+    Path,
+    PathBuf,
+};
+
+use eyre::{
+    Result,
+    bail,
+};
+
+use super::sanitize_path_tool_arg;
+use crate::os::Os;
+use crate::util::paths::{
+    GlobalPaths,
+    home_dir,
+};
+
+/// Paths relative to the home directory that contain credential material.
+const HOME_PROTECTED_PATHS: &[&str] = &[".ssh", ".aws/credentials"];
+
+/// Suffixes of the files SQLite keeps next to the CLI's database.
+const DATABASE_SIDE_FILE_SUFFIXES: &[&str] = &["-wal", "-shm", "-journal"];
+
+/// A single entry of the denylist.
+#[derive(Debug, Clone)]
+struct ProtectedPath {
+    /// The absolute location of the protected path.
+    full: PathBuf,
+    /// The trailing components used to recognize the path when it is only mentioned partially,
+    /// e.g. `.ssh/id_rsa` in a shell command.
+    tail: PathBuf,
+}
+
+fn protected_paths(os: &Os) -> Vec<ProtectedPath> {
+    let mut res = Vec::new();
+
+    if let Ok(home) = home_dir(os) {
+        for rel in HOME_PROTECTED_PATHS {
+            res.push(ProtectedPath {
+                full: home.join(rel),
+                tail: PathBuf::from(rel),
+            });
+        }
+    }
+
+    if let Ok(database) = GlobalPaths::database_path_static() {
+        let database = os.fs.chroot_path(database);
+        let mut files = vec![database.clone()];
+        if let Some(name) = database.file_name().map(|n| n.to_string_lossy().to_string()) {
+            files.extend(
+                DATABASE_SIDE_FILE_SUFFIXES
+                    .iter()
+                    .map(|suffix| database.with_file_name(format!("{name}{suffix}"))),
+            );
+        }
+        for file in files {
+            // The database lives under a directory named after the app, use both so that an
+            // unrelated file with the same name isn't flagged.
+            let tail = file.components().rev().take(2).collect::<Vec<_>>();
+            res.push(ProtectedPath {
+                tail: tail.into_iter().rev().collect(),
+                full: file,
+            });
+        }
+    }
+
+    res
+}
+
+/// Returns an error if `path` (as provided by the model) resolves to a protected path, or to a
+/// location inside one.
+pub fn check_path(os: &Os, path: impl AsRef<Path>) -> Result<()> {
+    let path = sanitize_path_tool_arg(os, path.as_ref());
+    let path = match path.is_absolute() {
+        true => path,
+        false => os.env.current_dir()?.join(path),
+    };
+    let resolved = resolve(&path);
+
+    for protected in protected_paths(os) {
+        if resolved.starts_with(resolve(&protected.full)) || path.starts_with(&protected.full) {
+            bail!(
+                "'{}' is a protected path and cannot be accessed by any tool",
+                path.display()
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns an error if any of the arguments of `command` refers to a protected path.
+///
+/// This is a best effort inspection of the words of the command: arguments are expanded (`~` and
+/// environment variables), checked as paths, and additionally matched against the trailing
+/// components of every protected path so that relative references such as `cd ~ && cat
+/// .ssh/id_rsa` are caught too.
+pub fn check_command(os: &Os, command: &str) -> Result<()> {
+    let protected = protected_paths(os);
+    let words = shlex::split(command).unwrap_or_else(|| command.split_whitespace().map(String::from).collect());
+
+    for word in words {
+        // Split on the characters that commonly glue a path to something else, e.g. redirects
+        // (`>~/.ssh/config`), options (`--file=~/.aws/credentials`) or command separators.
+        for candidate in word
+            .split(['=', '<', '>', '|', '&', ';', '(', ')', '`', '"', '\''])
+            .filter(|c| !c.is_empty())
+        {
+            let expanded = shellexpand::env_with_context_no_errors(candidate, |var: &str| os.env.get(var).ok());
+            let expanded = expanded.as_ref();
+            if !expanded.contains(std::path::MAIN_SEPARATOR) && !expanded.contains('/') && !expanded.starts_with('~') {
+                // Bare words can only match a protected path through the current directory.
+                if !protected
+                    .iter()
+                    .any(|p| p.tail.components().count() == 1 && p.tail == Path::new(expanded))
+                {
+                    continue;
+                }
+            }
+
+            if check_path(os, expanded).is_err() {
+                bail!(
+                    "The command references '{candidate}', which is a protected path that cannot be accessed by any tool"
+                );
+            }
+
+            let components = normalize(Path::new(expanded));
+            let components = components.components().collect::<Vec<_>>();
+            for p in &protected {
+                let tail = p.tail.components().collect::<Vec<_>>();
+                if components.windows(tail.len()).any(|w| w == tail.as_slice()) {
+                    bail!(
+                        "The command references '{candidate}', which is a protected path that cannot be accessed by any tool"
+                    );
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Resolves symlinks for the longest existing ancestor of `path`, so that paths which don't exist
+/// yet (e.g. a file about to be created) are still resolved through symlinked directories.
+fn resolve(path: &Path) -> PathBuf {
+    let path = normalize(path);
+    let mut rest = Vec::new();
+    let mut current = path.as_path();
+    loop {
+        if let Ok(canonical) = current.canonicalize() {
+            return rest.into_iter().rev().fold(canonical, |acc, c| acc.join(c));
+        }
+        match (current.parent(), current.file_name()) {
+            (Some(parent), Some(name)) => {
+                rest.push(name.to_owned());
+                current = parent;
+            },
+            _ => return path,
+        }
+    }
+}
+
+/// Lexically resolves `.` and `..` components.
+fn normalize(path: &Path) -> PathBuf {
+    let mut res = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {},
+            Component::ParentDir => {
+                res.pop();
+            },
+            c => res.push(c),
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_check_path() {
+        let os = Os::new().await.unwrap();
+
+        assert!(check_path(&os, "~/.ssh").is_err());
+        assert!(check_path(&os, "~/.ssh/id_rsa").is_err());
+        assert!(check_path(&os, "~/.ssh/../.ssh/authorized_keys").is_err());
+        assert!(check_path(&os, "~/.aws/credentials").is_err());
+        assert!(check_path(&os, GlobalPaths::database_path_static().unwrap()).is_err());
+
+        assert!(check_path(&os, "~/.aws/config").is_ok());
+        assert!(check_path(&os, "~/project/.sshrc").is_ok());
+        assert!(check_path(&os, "/tmp/credentials").is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_check_command() {
+        let os = Os::new().await.unwrap();
+
+        for command in [
+            "cat ~/.ssh/id_rsa",
+            "cat $HOME/.ssh/id_rsa",
+            "cd ~ && cat .ssh/id_ed25519",
+            "echo key >> ~/.ssh/authorized_keys",
+            "echo key >~/.ssh/authorized_keys",
+            "aws configure --file=~/.aws/credentials",
+            "cp ~/.aws/credentials /tmp/creds",
+            "sqlite3 ~/.local/share/amazon-q/data.sqlite3 .dump",
+        ] {
+            assert!(check_command(&os, command).is_err(), "{command} should be denied");
+        }
+
+        for command in [
+            "ls -la",
+            "cat README.md",
+            "ssh-keygen --help",
+            "aws s3 ls",
+            "grep -r credentials src/",
+            "cat ~/.aws/config",
+        ] {
+            assert!(check_command(&os, command).is_ok(), "{command} should be allowed");
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use std::io::Write;
+
+use crossterm::{
+    queue,
+    style,
+};
+use eyre::Result;
+use serde::Deserialize;
+
+use super::delegate::{
+    Delegate,
+    launch_subagent,
+};
+use super::{
+    InvokeOutput,
+    OutputKind,
+};
+use crate::cli::agent::{
+    Agent,
+    Agents,
+    PermissionEvalResult,
+};
+use crate::os::Os;
+use crate::theme::StyledText;
+use crate::util::tool_permission_checker::is_tool_in_allowlist;
+
+/// Hands a task to one of the sub-agents declared in the `subagents` of the active agent. The task
+/// runs in the background like the ones launched with the delegate tool, with the narrower
+/// permissions of the sub-agent.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SpawnSubagent {
+    /// Name of the sub-agent, as declared in the agent config
+    pub name: String,
+    pub task: String,
+    pub working_directory: Option<String>,
+}
+
+impl SpawnSubagent {
+    /// Sub-agents run as delegated tasks, and are thus part of the delegate experiment.
+    pub fn is_enabled(os: &Os) -> bool {
+        Delegate::is_enabled(os)
+    }
+
+    pub fn eval_perm(&self, os: &Os, agent: &Agent) -> PermissionEvalResult {
+        _ = self;
+        _ = os;
+
+        if is_tool_in_allowlist(&agent.allowed_tools, "spawn_subagent", None) {
+            PermissionEvalResult::Allow
+        } else {
+            PermissionEvalResult::Ask
+        }
+    }
+
+    pub async fn invoke(&self, os: &Os, _output: &mut impl Write, agents: &Agents) -> Result<InvokeOutput> {
+        let result = launch_subagent(os, agents, &self.name, &self.task, self.working_directory.as_deref()).await?;
+        Ok(InvokeOutput {
+            output: OutputKind::Text(result),
+        })
+    }
+
+    pub fn queue_description(&self, output: &mut impl Write) -> Result<()> {
+        queue!(
+            output,
+            style::Print("Spawning sub-agent "),
+            StyledText::success_fg(),
+            style::Print(&self.name),
+            StyledText::reset(),
+            style::Print(format!(": {}\n", self.task)),
+        )?;
+        Ok(())
+    }
+
+    pub async fn validate(&self, _os: &Os) -> Result<()> {
+        if self.task.trim().is_empty() {
+            eyre::bail!("The task of the sub-agent can't be empty");
+        }
+        Ok(())
+    }
+}
+
+/// Description of the spawn_subagent tool, listing the sub-agents of the agent.
+pub fn describe_subagents(description: &str, agent: &Agent) -> String {
+    let mut subagents = agent.subagents.iter().collect::<Vec<_>>();
+    subagents.sort_by_key(|(name, _)| name.as_str());
+    let list = subagents
+        .into_iter()
+        .map(|(name, config)| match &config.description {
+            Some(purpose) => format!("- {name}: {purpose}"),
+            None => format!("- {name}"),
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    format!("{description}\n\nAvailable sub-agents:\n{list}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::agent::SubagentConfig;
+
+    #[test]
+    fn test_describe_subagents() {
+        let mut agent = Agent::default();
+        agent.subagents.insert("tester".to_string(), SubagentConfig::default());
+        agent.subagents.insert("reviewer".to_string(), SubagentConfig {
+            description: Some("Reviews diffs".to_string()),
+            ..Default::default()
+        });
+        assert_eq!(
+            describe_subagents("Spawn a sub-agent.", &agent),
+            "Spawn a sub-agent.\n\nAvailable sub-agents:\n- reviewer: Reviews diffs\n- tester"
+        );
+    }
+}
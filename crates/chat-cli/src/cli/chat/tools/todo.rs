@@ -6,7 +6,6 @@ use std::time::{
     UNIX_EPOCH,
 };
 
-use crossterm::style::Stylize;
 use crossterm::{
     queue,
     style,
@@ -80,7 +79,7 @@ impl TodoListState {
 
     /// Displays the TodoListState as a to-do list
     pub fn display_list(&self, output: &mut impl Write) -> Result<()> {
-        queue!(output, style::Print("TODO:\n".yellow()))?;
+        queue!(output, style::Print(StyledText::warning("TODO:\n")))?;
         for (index, task) in self.tasks.iter().enumerate() {
             queue_next_without_newline(output, task.task_description.clone(), task.completed)?;
             if index < self.tasks.len() - 1 {
@@ -235,7 +234,7 @@ impl TodoList {
         if let Some(id) = self.get_id() {
             if !os.fs.exists(id_to_path(os, &id)?) {
                 let error_string = "No todo list exists with the given ID";
-                queue!(output, style::Print(error_string.yellow()))?;
+                queue!(output, style::Print(StyledText::warning(error_string)))?;
                 return Ok(InvokeOutput {
                     output: super::OutputKind::Text(error_string.to_string()),
                 });
@@ -352,7 +351,10 @@ impl TodoList {
                 (state, id.clone())
             },
             TodoList::Lookup => {
-                queue!(output, style::Print("Finding existing todo lists...".yellow()))?;
+                queue!(
+                    output,
+                    style::Print(StyledText::warning("Finding existing todo lists..."))
+                )?;
                 let (todo_lists, _) = get_all_todos(os).await?;
                 if !todo_lists.is_empty() {
                     let mut displays = Vec::new();
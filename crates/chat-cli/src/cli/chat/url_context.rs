@@ -0,0 +1,275 @@
+//! Web pages added to the context with `/context add-url`.
+//!
+//! Pages are fetched once when added and kept with the rest of the context, rather than fetched
+//! again on every turn. HTML is reduced to its readable text, formatted as markdown.
+
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use chrono::{
+    DateTime,
+    Utc,
+};
+use eyre::{
+    Result,
+    bail,
+    eyre,
+};
+use regex::{
+    Captures,
+    Regex,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use super::token_counter::TokenCounter;
+use super::util::truncate_safe_in_place;
+
+/// Maximum size of the text kept from a page.
+const MAX_URL_CONTENT_CHARS: usize = TokenCounter::token_to_chars(10_000);
+
+/// Maximum size of a response that is downloaded at all.
+const MAX_DOWNLOAD_BYTES: usize = 5 * 1024 * 1024;
+
+const FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+const TRUNCATION_MARKER: &str = "\n... (page truncated)";
+
+/// Elements that hold no readable content, or only the boilerplate surrounding it.
+const BOILERPLATE_ELEMENTS: &[&str] = &[
+    "script", "style", "noscript", "template", "svg", "iframe", "nav", "header", "footer", "aside", "form",
+];
+
+/// A web page added to the context.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UrlContext {
+    pub name: String,
+    pub url: String,
+    pub content: String,
+    pub fetched_at: DateTime<Utc>,
+}
+
+impl UrlContext {
+    /// The name the page is listed under in the context.
+    pub fn entry_name(&self) -> String {
+        format!("{} <{}>", self.name, self.url)
+    }
+
+    /// Whether `s` refers to this page, either by its url or by its name.
+    pub fn matches(&self, s: &str) -> bool {
+        self.url == s || self.name == s
+    }
+}
+
+/// Fetches `url` and extracts its readable content. The page title is used as the name of the
+/// entry unless `name` is given.
+pub async fn fetch_url(url: &str, name: Option<String>) -> Result<UrlContext> {
+    let parsed = url::Url::parse(url)?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        bail!("only http and https URLs are supported");
+    }
+
+    let client = crate::request::new_client()?;
+    let mut response = client
+        .get(parsed.clone())
+        .timeout(FETCH_TIMEOUT)
+        .send()
+        .await?
+        .error_for_status()?;
+
+    let content_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("text/html")
+        .to_lowercase();
+    let is_html = content_type.contains("html");
+    if !is_html && !content_type.starts_with("text/") && !content_type.contains("json") && !content_type.contains("xml")
+    {
+        bail!("unsupported content type '{content_type}', only text pages can be added to the context");
+    }
+
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        body.extend_from_slice(&chunk);
+        if body.len() > MAX_DOWNLOAD_BYTES {
+            bail!("the page is larger than {} MB", MAX_DOWNLOAD_BYTES / (1024 * 1024));
+        }
+    }
+    let body = String::from_utf8_lossy(&body);
+
+    let (title, mut content) = match is_html {
+        true => html_to_markdown(&body),
+        false => (None, body.to_string()),
+    };
+    if content.trim().is_empty() {
+        return Err(eyre!("no readable content was found"));
+    }
+    truncate_safe_in_place(&mut content, MAX_URL_CONTENT_CHARS, TRUNCATION_MARKER);
+
+    let name = name.or(title).unwrap_or_else(|| {
+        format!(
+            "{}{}",
+            parsed.host_str().unwrap_or_default(),
+            parsed.path().trim_end_matches('/')
+        )
+    });
+
+    Ok(UrlContext {
+        name,
+        url: url.to_string(),
+        content,
+        fetched_at: Utc::now(),
+    })
+}
+
+fn regex(pattern: &str) -> Regex {
+    Regex::new(pattern).expect("valid regex")
+}
+
+static TITLE: LazyLock<Regex> = LazyLock::new(|| regex(r"(?is)<title[^>]*>(.*?)</title\s*>"));
+static MAIN_CONTENT: LazyLock<[Regex; 3]> =
+    LazyLock::new(|| ["article", "main", "body"].map(|tag| regex(&format!(r"(?is)<{tag}\b[^>]*>(.*)</{tag}\s*>"))));
+static BOILERPLATE: LazyLock<Vec<Regex>> = LazyLock::new(|| {
+    BOILERPLATE_ELEMENTS
+        .iter()
+        .map(|tag| regex(&format!(r"(?is)<{tag}\b[^>]*>.*?</{tag}\s*>")))
+        .collect()
+});
+static COMMENT: LazyLock<Regex> = LazyLock::new(|| regex(r"(?s)<!--.*?-->"));
+static PRE: LazyLock<Regex> = LazyLock::new(|| regex(r"(?is)<pre\b[^>]*>(.*?)</pre\s*>"));
+static HEADING: LazyLock<Regex> = LazyLock::new(|| regex(r"(?is)<h([1-6])\b[^>]*>(.*?)</h[1-6]\s*>"));
+static LIST_ITEM: LazyLock<Regex> = LazyLock::new(|| regex(r"(?i)<li\b[^>]*>"));
+static LINE_BREAK: LazyLock<Regex> = LazyLock::new(|| regex(r"(?i)<br\s*/?>"));
+static BLOCK: LazyLock<Regex> =
+    LazyLock::new(|| regex(r"(?i)</?(p|div|section|table|tr|ul|ol|dl|dt|dd|blockquote|figure|figcaption|hr)\b[^>]*>"));
+static CODE: LazyLock<Regex> = LazyLock::new(|| regex(r"(?is)<code\b[^>]*>(.*?)</code\s*>"));
+static TAG: LazyLock<Regex> = LazyLock::new(|| regex(r"(?s)<[^>]*>"));
+static ENTITY: LazyLock<Regex> = LazyLock::new(|| regex(r"&(#[0-9]+|#[xX][0-9a-fA-F]+|[a-zA-Z]+);"));
+static BLANK_LINES: LazyLock<Regex> = LazyLock::new(|| regex(r"\n{3,}"));
+
+fn decode_entities(s: &str) -> String {
+    ENTITY
+        .replace_all(s, |c: &Captures<'_>| {
+            let entity = &c[1];
+            let decoded = match entity {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "nbsp" => Some(' '),
+                _ if entity.starts_with("#x") || entity.starts_with("#X") => {
+                    u32::from_str_radix(&entity[2..], 16).ok().and_then(char::from_u32)
+                },
+                _ if entity.starts_with('#') => entity[1..].parse().ok().and_then(char::from_u32),
+                _ => None,
+            };
+            decoded.map_or_else(|| c[0].to_string(), String::from)
+        })
+        .to_string()
+}
+
+/// Strips the tags of an html fragment and collapses its whitespace.
+fn inline_text(html: &str) -> String {
+    decode_entities(&TAG.replace_all(html, ""))
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Reduces an html page to the markdown formatted text of its main content. Returns the title of
+/// the page, if any, and the text.
+fn html_to_markdown(html: &str) -> (Option<String>, String) {
+    let title = TITLE
+        .captures(html)
+        .map(|c| inline_text(&c[1]))
+        .filter(|t| !t.is_empty());
+
+    let mut html = COMMENT.replace_all(html, "").to_string();
+    for re in BOILERPLATE.iter() {
+        html = re.replace_all(&html, "").to_string();
+    }
+    if let Some(main) = MAIN_CONTENT.iter().find_map(|re| re.captures(&html)) {
+        html = main[1].to_string();
+    }
+
+    // Code blocks are set aside so that their whitespace survives.
+    let mut code_blocks = Vec::new();
+    let html = PRE.replace_all(&html, |c: &Captures<'_>| {
+        code_blocks.push(decode_entities(&TAG.replace_all(&c[1], "")));
+        format!("\n\u{0}{}\u{0}\n", code_blocks.len() - 1)
+    });
+    let html = HEADING.replace_all(&html, |c: &Captures<'_>| {
+        let level = c[1].parse::<usize>().unwrap_or(1);
+        format!("\n\n{} {}\n\n", "#".repeat(level), inline_text(&c[2]))
+    });
+    let html = CODE.replace_all(&html, |c: &Captures<'_>| format!("`{}`", inline_text(&c[1])));
+    let html = LIST_ITEM.replace_all(&html, "\n- ");
+    let html = LINE_BREAK.replace_all(&html, "\n");
+    let html = BLOCK.replace_all(&html, "\n\n");
+    let text = decode_entities(&TAG.replace_all(&html, ""));
+
+    let mut res = String::new();
+    for line in text.lines() {
+        let line = line.split_whitespace().collect::<Vec<_>>().join(" ");
+        match line
+            .strip_prefix('\u{0}')
+            .and_then(|l| l.strip_suffix('\u{0}'))
+            .and_then(|i| i.parse::<usize>().ok())
+            .and_then(|i| code_blocks.get(i))
+        {
+            Some(code) => res.push_str(&format!("```\n{}\n```\n", code.trim_matches('\n'))),
+            None => {
+                res.push_str(&line);
+                res.push('\n');
+            },
+        }
+    }
+
+    (title, BLANK_LINES.replace_all(res.trim(), "\n\n").to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_html_to_markdown() {
+        let html = r#"<!DOCTYPE html>
+<html>
+<head><title>Widgets &amp; Gadgets</title><style>body { color: red; }</style></head>
+<body>
+  <nav><a href="/">Home</a> | <a href="/docs">Docs</a></nav>
+  <article>
+    <h1>Using   widgets</h1>
+    <p>Widgets are <b>great</b>.<br>Call <code>widget.run()</code> to start.</p>
+    <!-- a comment -->
+    <ul><li>Fast</li><li>Cheap &#38; cheerful</li></ul>
+    <pre><code>fn main() {
+    run();
+}</code></pre>
+    <script>track();</script>
+  </article>
+  <footer>Copyright</footer>
+</body>
+</html>"#;
+
+        let (title, text) = html_to_markdown(html);
+        assert_eq!(title.as_deref(), Some("Widgets & Gadgets"));
+        assert_eq!(
+            text,
+            "# Using widgets\n\nWidgets are great.\nCall `widget.run()` to start.\n\n- Fast\n- Cheap & cheerful\n\n```\nfn main() {\n    run();\n}\n```"
+        );
+    }
+
+    #[test]
+    fn test_decode_entities() {
+        assert_eq!(
+            decode_entities("a &lt;b&gt; &#x41;&#66; &unknown;"),
+            "a <b> AB &unknown;"
+        );
+    }
+}
@@ -0,0 +1,381 @@
+//! Inline display of images in terminals supporting the kitty, iTerm2 or sixel graphics
+//! protocols, like the images returned by tools and the local images that responses mention.
+//! Other terminals show the paths of the images instead.
+
+use std::collections::BTreeMap;
+use std::io::{
+    Cursor,
+    Write,
+};
+use std::path::Path;
+
+use base64::Engine as _;
+use base64::engine::general_purpose::STANDARD;
+use crossterm::style::Print;
+use crossterm::{
+    queue,
+    terminal,
+};
+use eyre::Result;
+use image::imageops::FilterType;
+use image::{
+    DynamicImage,
+    RgbImage,
+};
+use tracing::{
+    debug,
+    warn,
+};
+
+use super::images::{
+    RichImageBlock,
+    is_supported_image_type,
+};
+use crate::api_client::model::ImageSource;
+use crate::cli::chat::accessible;
+use crate::database::settings::{
+    Setting,
+    Settings,
+};
+use crate::os::Env;
+use crate::theme::StyledText;
+use crate::util::consts::env_var::{
+    KITTY_WINDOW_ID,
+    LC_TERMINAL,
+    TERM,
+    TERM_PROGRAM,
+    TMUX,
+};
+
+/// Widest and tallest an image is displayed, in terminal cells.
+const MAX_COLUMNS: u32 = 60;
+const MAX_ROWS: u32 = 20;
+
+/// Size of a terminal cell in pixels when the terminal doesn't tell.
+const DEFAULT_CELL_SIZE: (u32, u32) = (8, 16);
+
+/// Number of the images a response mentions that are displayed.
+pub const MAX_REFERENCED_IMAGES: usize = 4;
+
+/// Size of the chunks of kitty graphics escape sequences.
+const KITTY_CHUNK_SIZE: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageProtocol {
+    Kitty,
+    Iterm2,
+    Sixel,
+}
+
+impl ImageProtocol {
+    /// The protocol of the `chat.inlineImages` setting, detected from the environment if unset or
+    /// `auto`, [None] if `off`.
+    pub fn load(settings: &Settings, env: &Env) -> Option<Self> {
+        match settings.get_string(Setting::ChatInlineImages).as_deref() {
+            None | Some("auto") => Self::detect(env),
+            Some("kitty") => Some(Self::Kitty),
+            Some("iterm2") => Some(Self::Iterm2),
+            Some("sixel") => Some(Self::Sixel),
+            Some("off") => None,
+            Some(value) => {
+                warn!(%value, "Invalid value of {}, detecting the protocol", Setting::ChatInlineImages);
+                Self::detect(env)
+            },
+        }
+    }
+
+    /// The protocol of the terminal, if it supports one.
+    pub fn detect(env: &Env) -> Option<Self> {
+        let term = env.get(TERM).unwrap_or_default();
+        let program = env.get(TERM_PROGRAM).unwrap_or_default();
+
+        // Multiplexers don't pass the escape sequences through
+        if env.get_os(TMUX).is_some() || term.starts_with("screen") || term.starts_with("tmux") {
+            return None;
+        }
+
+        if env.get_os(KITTY_WINDOW_ID).is_some() || term.contains("kitty") || term.contains("ghostty") {
+            Some(Self::Kitty)
+        } else if program == "iTerm.app" || program == "WezTerm" || env.get(LC_TERMINAL).is_ok_and(|t| t == "iTerm2") {
+            Some(Self::Iterm2)
+        } else if term.contains("sixel") || term == "foot" || term.starts_with("foot-") || program == "mlterm" {
+            Some(Self::Sixel)
+        } else {
+            None
+        }
+    }
+}
+
+/// Shows `image` inline with `protocol`, or its path without.
+pub fn display_image(
+    output: &mut impl Write,
+    image: &RichImageBlock,
+    protocol: Option<ImageProtocol>,
+) -> std::io::Result<()> {
+    let (block, metadata) = image;
+    if let (Some(protocol), ImageSource::Bytes(bytes)) = (protocol, &block.source) {
+        match encode(bytes, protocol, cell_size()) {
+            Ok(sequence) => {
+                queue!(output, Print(sequence), Print("\n"))?;
+                return Ok(());
+            },
+            Err(err) => debug!(?err, "Unable to display the image inline"),
+        }
+    }
+
+    let name = match metadata.filepath.as_str() {
+        "" => &metadata.filename,
+        path => path,
+    };
+    queue!(
+        output,
+        StyledText::secondary_fg(),
+        Print(format!(
+            "{}{name} ({})\n",
+            accessible::symbol("🖼  ", "Image: "),
+            format_size(metadata.size)
+        )),
+        StyledText::reset(),
+    )?;
+    Ok(())
+}
+
+/// Returns the existing local images mentioned in `text`, like the screenshots a response refers
+/// to.
+pub fn referenced_image_paths(text: &str) -> Vec<String> {
+    let mut paths = Vec::new();
+    for word in text.split_whitespace() {
+        // The target of markdown links and images
+        let word = word.rsplit_once("](").map_or(word, |(_, target)| target);
+        let word = word
+            .trim_start_matches(|c: char| "`\"'([<*".contains(c))
+            .trim_end_matches(|c: char| "`\"')]>*,;:.".contains(c));
+        let word = word.strip_prefix("file://").unwrap_or(word);
+        let path = shellexpand::tilde(word).to_string();
+        if is_supported_image_type(&path) && Path::new(&path).is_file() && !paths.contains(&path) {
+            paths.push(path);
+        }
+    }
+    paths
+}
+
+/// Size of a terminal cell in pixels, and width of the terminal in cells.
+fn cell_size() -> (u32, u32, u32) {
+    let (cell_width, cell_height) = DEFAULT_CELL_SIZE;
+    match terminal::window_size() {
+        Ok(size) if size.width > 0 && size.height > 0 && size.columns > 0 && size.rows > 0 => (
+            u32::from(size.width / size.columns).max(1),
+            u32::from(size.height / size.rows).max(1),
+            u32::from(size.columns),
+        ),
+        Ok(size) => (cell_width, cell_height, u32::from(size.columns)),
+        Err(_) => (cell_width, cell_height, MAX_COLUMNS),
+    }
+}
+
+/// Returns the escape sequence displaying the image of `bytes`, scaled down to fit
+/// [MAX_COLUMNS] and [MAX_ROWS].
+fn encode(
+    bytes: &[u8],
+    protocol: ImageProtocol,
+    (cell_width, cell_height, columns): (u32, u32, u32),
+) -> Result<String> {
+    let image = image::load_from_memory(bytes)?;
+    let max_width = MAX_COLUMNS.min(columns.max(1)) * cell_width;
+    let max_height = MAX_ROWS * cell_height;
+    let image = if image.width() > max_width || image.height() > max_height {
+        image.resize(max_width, max_height, FilterType::Triangle)
+    } else {
+        image
+    };
+    let columns = image.width().div_ceil(cell_width);
+
+    Ok(match protocol {
+        ImageProtocol::Kitty => kitty(&png(&image)?, columns),
+        ImageProtocol::Iterm2 => iterm2(&png(&image)?, columns),
+        ImageProtocol::Sixel => sixel(&image.to_rgb8()),
+    })
+}
+
+fn png(image: &DynamicImage) -> Result<Vec<u8>> {
+    let mut bytes = Cursor::new(Vec::new());
+    image.write_to(&mut bytes, image::ImageFormat::Png)?;
+    Ok(bytes.into_inner())
+}
+
+/// Transmits and displays the PNG `png` over `columns` cells, in chunks as the protocol requires.
+fn kitty(png: &[u8], columns: u32) -> String {
+    let data = STANDARD.encode(png);
+    let chunks = (0..data.len()).step_by(KITTY_CHUNK_SIZE).collect::<Vec<_>>();
+    let mut sequence = String::new();
+    for (i, start) in chunks.iter().enumerate() {
+        let chunk = &data[*start..(start + KITTY_CHUNK_SIZE).min(data.len())];
+        let more = u8::from(i + 1 < chunks.len());
+        match i {
+            0 => sequence.push_str(&format!("\x1b_Gf=100,a=T,c={columns},m={more};{chunk}\x1b\\")),
+            _ => sequence.push_str(&format!("\x1b_Gm={more};{chunk}\x1b\\")),
+        }
+    }
+    sequence
+}
+
+fn iterm2(png: &[u8], columns: u32) -> String {
+    format!(
+        "\x1b]1337;File=inline=1;size={};width={columns};preserveAspectRatio=1:{}\x07",
+        png.len(),
+        STANDARD.encode(png)
+    )
+}
+
+/// Encodes `image` as sixels, with its colors reduced to a 6x6x6 color cube.
+fn sixel(image: &RgbImage) -> String {
+    let (width, height) = image.dimensions();
+    let level = |channel: u8| (u16::from(channel) * 5 + 127) / 255;
+    let color = |x: u32, y: u32| {
+        let pixel = image.get_pixel(x, y);
+        level(pixel[0]) * 36 + level(pixel[1]) * 6 + level(pixel[2])
+    };
+
+    let mut sequence = format!("\x1bPq\"1;1;{width};{height}");
+    for i in 0..216 {
+        sequence.push_str(&format!("#{i};2;{};{};{}", i / 36 * 20, i / 6 % 6 * 20, i % 6 * 20));
+    }
+    // Each band of 6 rows is drawn color by color, returning to its start in between
+    for band in (0..height).step_by(6) {
+        let mut sixels: BTreeMap<u16, Vec<u8>> = BTreeMap::new();
+        for y in band..(band + 6).min(height) {
+            for x in 0..width {
+                let row = sixels.entry(color(x, y)).or_insert_with(|| vec![0; width as usize]);
+                row[x as usize] |= 1 << (y - band);
+            }
+        }
+        for (color, row) in sixels {
+            sequence.push_str(&format!("#{color}"));
+            push_sixels(&mut sequence, &row);
+            sequence.push('$');
+        }
+        sequence.push('-');
+    }
+    sequence.push_str("\x1b\\");
+    sequence
+}
+
+/// Pushes the sixels of `row`, run-length encoded.
+fn push_sixels(sequence: &mut String, row: &[u8]) {
+    let mut i = 0;
+    while i < row.len() {
+        let run = row[i..].iter().take_while(|sixel| **sixel == row[i]).count();
+        let sixel = char::from(0x3f + row[i]);
+        match run {
+            1..=3 => (0..run).for_each(|_| sequence.push(sixel)),
+            _ => sequence.push_str(&format!("!{run}{sixel}")),
+        }
+        i += run;
+    }
+}
+
+fn format_size(size: u64) -> String {
+    match size {
+        s if s < 1024 => format!("{s} B"),
+        s if s < 1024 * 1024 => format!("{:.1} KB", s as f64 / 1024.0),
+        s => format!("{:.1} MB", s as f64 / (1024.0 * 1024.0)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgb;
+
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        let detect = |vars: &[(&str, &str)]| ImageProtocol::detect(&Env::from_slice(vars));
+        assert_eq!(detect(&[("TERM", "xterm-kitty")]), Some(ImageProtocol::Kitty));
+        assert_eq!(
+            detect(&[("TERM", "xterm-256color"), ("TERM_PROGRAM", "iTerm.app")]),
+            Some(ImageProtocol::Iterm2)
+        );
+        assert_eq!(detect(&[("TERM", "foot")]), Some(ImageProtocol::Sixel));
+        assert_eq!(detect(&[("TERM", "xterm-256color")]), None);
+        assert_eq!(
+            detect(&[("TERM", "xterm-kitty"), ("TMUX", "/tmp/tmux-1000/default")]),
+            None
+        );
+    }
+
+    #[test]
+    fn test_sixel() {
+        // A red pixel above a blue one, then two white pixels
+        let mut image = RgbImage::new(2, 2);
+        image.put_pixel(0, 0, Rgb([255, 0, 0]));
+        image.put_pixel(0, 1, Rgb([0, 0, 255]));
+        image.put_pixel(1, 0, Rgb([255, 255, 255]));
+        image.put_pixel(1, 1, Rgb([255, 255, 255]));
+        let sequence = sixel(&image);
+        assert!(sequence.starts_with("\x1bPq\"1;1;2;2#0;2;0;0;0"));
+        assert!(sequence.ends_with("#5A?$#180@?$#215?B$-\x1b\\"));
+
+        let mut row = String::new();
+        push_sixels(&mut row, &[1, 1, 1, 1, 1, 2, 2]);
+        assert_eq!(row, "!5@AA");
+    }
+
+    #[test]
+    fn test_encode() {
+        let mut bytes = Cursor::new(Vec::new());
+        DynamicImage::new_rgb8(800, 100)
+            .write_to(&mut bytes, image::ImageFormat::Png)
+            .unwrap();
+        let bytes = bytes.into_inner();
+
+        // Scaled down to 60 columns of 8 pixels
+        let sequence = encode(&bytes, ImageProtocol::Kitty, (8, 16, 200)).unwrap();
+        assert!(sequence.starts_with("\x1b_Gf=100,a=T,c=60,m="));
+        assert!(sequence.ends_with("\x1b\\"));
+        let sequence = encode(&bytes, ImageProtocol::Iterm2, (8, 16, 40)).unwrap();
+        assert!(sequence.starts_with("\x1b]1337;File=inline=1;size="));
+        assert!(sequence.contains(";width=40;"));
+
+        assert!(encode(b"not an image", ImageProtocol::Sixel, (8, 16, 80)).is_err());
+    }
+
+    #[test]
+    fn test_kitty_chunks() {
+        let sequence = kitty(&[0; 4000], 10);
+        assert_eq!(sequence.matches("\x1b_G").count(), 2);
+        assert!(sequence.contains("m=1;"));
+        assert!(sequence.contains("\x1b_Gm=0;"));
+    }
+
+    #[test]
+    fn test_display_image_accessible() {
+        accessible::enable_for_test();
+        let image = (
+            crate::api_client::model::ImageBlock {
+                format: crate::api_client::model::ImageFormat::Png,
+                source: ImageSource::Bytes(vec![0; 2048]),
+            },
+            crate::cli::chat::util::images::ImageMetadata {
+                filepath: "/tmp/screenshot.png".to_string(),
+                size: 2048,
+                filename: "screenshot.png".to_string(),
+            },
+        );
+        let mut output = vec![];
+        display_image(&mut output, &image, None).unwrap();
+        let output = strip_ansi_escapes::strip_str(String::from_utf8(output).unwrap());
+        assert!(output.starts_with("Image: /tmp/screenshot.png ("), "{output}");
+    }
+
+    #[test]
+    fn test_referenced_image_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let image = dir.path().join("screenshot.png");
+        std::fs::write(&image, b"").unwrap();
+        let image = image.to_string_lossy().to_string();
+
+        let text = format!("Saved it to `{image}`. See ![the result]({image}) and {image}, not /missing.png");
+        assert_eq!(referenced_image_paths(&text), vec![image]);
+    }
+}
@@ -1,5 +1,8 @@
 use std::fs;
-use std::io::Write;
+use std::io::{
+    Cursor,
+    Write,
+};
 use std::path::Path;
 use std::str::FromStr;
 
@@ -7,6 +10,13 @@ use crossterm::execute;
 use crossterm::style::{
     self,
 };
+use eyre::{
+    Result,
+    bail,
+    eyre,
+};
+use image::DynamicImage;
+use image::imageops::FilterType;
 use serde::{
     Deserialize,
     Serialize,
@@ -18,6 +28,7 @@ use crate::api_client::model::{
     ImageSource,
 };
 use crate::cli::chat::consts::{
+    MAX_IMAGE_DIMENSION,
     MAX_IMAGE_SIZE,
     MAX_NUMBER_OF_IMAGES_PER_REQUEST,
 };
@@ -133,6 +144,80 @@ pub fn handle_images_from_paths(output: &mut impl Write, paths: &[String]) -> Ri
     valid_images
 }
 
+/// Loads the image at `path` to attach it to a user message.
+///
+/// Unlike [get_image_block_from_file_path], the content of the file is checked to be an image of
+/// a supported format. Images larger than [MAX_IMAGE_SIZE] bytes or [MAX_IMAGE_DIMENSION] pixels
+/// are downscaled, and re-encoded as JPEG.
+pub fn load_image_attachment(path: &str) -> Result<RichImageBlock> {
+    let file_path = Path::new(path);
+    if !file_path.is_file() {
+        bail!("{path} does not exist or is not a file");
+    }
+    if !is_supported_image_type(path) {
+        bail!("unsupported image type, the supported types are jpg, jpeg, png, gif and webp");
+    }
+
+    let mut bytes = fs::read(file_path)?;
+    let mut format = match image::guess_format(&bytes) {
+        Ok(image::ImageFormat::Png) => ImageFormat::Png,
+        Ok(image::ImageFormat::Jpeg) => ImageFormat::Jpeg,
+        Ok(image::ImageFormat::Gif) => ImageFormat::Gif,
+        Ok(image::ImageFormat::WebP) => ImageFormat::Webp,
+        _ => bail!("{path} is not a jpg, png, gif or webp image"),
+    };
+
+    let image = image::load_from_memory(&bytes).map_err(|e| eyre!("failed to decode {path}: {e}"))?;
+    if bytes.len() > MAX_IMAGE_SIZE || image.width() > MAX_IMAGE_DIMENSION || image.height() > MAX_IMAGE_DIMENSION {
+        bytes = downscale(&image)?;
+        format = ImageFormat::Jpeg;
+    }
+
+    let filename = file_path.file_name().unwrap_or_default().to_string_lossy().to_string();
+    Ok((
+        ImageBlock {
+            format,
+            source: ImageSource::Bytes(bytes.clone()),
+        },
+        ImageMetadata {
+            filepath: path.to_string(),
+            size: bytes.len() as u64,
+            filename,
+        },
+    ))
+}
+
+/// Encodes `image` as a JPEG that fits within [MAX_IMAGE_DIMENSION] and [MAX_IMAGE_SIZE]. JPEG is
+/// used since images this large are usually photos, for which it is far smaller than PNG.
+fn downscale(image: &DynamicImage) -> Result<Vec<u8>> {
+    let mut max_side = MAX_IMAGE_DIMENSION.min(image.width().max(image.height()));
+    loop {
+        let resized = image.resize(max_side, max_side, FilterType::Triangle);
+        let mut bytes = Cursor::new(Vec::new());
+        DynamicImage::ImageRgb8(resized.to_rgb8()).write_to(&mut bytes, image::ImageFormat::Jpeg)?;
+        let bytes = bytes.into_inner();
+        if bytes.len() <= MAX_IMAGE_SIZE || max_side <= 256 {
+            return Ok(bytes);
+        }
+        max_side /= 2;
+    }
+}
+
+/// Returns the paths of existing image files mentioned in `input`, such as the paths that
+/// terminals insert when files are dragged onto them.
+pub fn detect_image_paths(input: &str) -> Vec<String> {
+    let words = shlex::split(input).unwrap_or_else(|| input.split_whitespace().map(String::from).collect());
+    let mut paths = Vec::new();
+    for word in words {
+        let word = word.strip_prefix("file://").unwrap_or(&word);
+        let path = shellexpand::tilde(word).to_string();
+        if is_supported_image_type(&path) && Path::new(&path).is_file() && !paths.contains(&path) {
+            paths.push(path);
+        }
+    }
+    paths
+}
+
 /// This function checks if the file path has a supported image type
 /// and returns true if it does, otherwise false.
 /// Supported image types are: jpg, jpeg, png, gif, webp
@@ -220,6 +305,52 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_load_image_attachment() {
+        let temp_dir = tempfile::tempdir().unwrap();
+
+        let small_path = temp_dir.path().join("small.png");
+        image::RgbaImage::new(20, 10).save(&small_path).unwrap();
+        let (block, metadata) = load_image_attachment(&small_path.to_string_lossy()).unwrap();
+        assert_eq!(block.format, ImageFormat::Png);
+        assert_eq!(metadata.filename, "small.png");
+        assert_eq!(metadata.size, std::fs::metadata(&small_path).unwrap().len());
+
+        let wide_path = temp_dir.path().join("wide.png");
+        image::RgbaImage::new(MAX_IMAGE_DIMENSION * 2, 10)
+            .save(&wide_path)
+            .unwrap();
+        let (block, _) = load_image_attachment(&wide_path.to_string_lossy()).unwrap();
+        assert_eq!(block.format, ImageFormat::Jpeg);
+        let ImageSource::Bytes(bytes) = block.source else {
+            panic!("Expected ImageSource::Bytes");
+        };
+        let resized = image::load_from_memory(&bytes).unwrap();
+        assert_eq!((resized.width(), resized.height()), (MAX_IMAGE_DIMENSION, 5));
+
+        let fake_path = temp_dir.path().join("fake.png");
+        std::fs::write(&fake_path, b"fake_image_data").unwrap();
+        assert!(load_image_attachment(&fake_path.to_string_lossy()).is_err());
+        assert!(load_image_attachment(&temp_dir.path().join("missing.png").to_string_lossy()).is_err());
+    }
+
+    #[test]
+    fn test_detect_image_paths() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let image_path = temp_dir.path().join("my screenshot.png");
+        std::fs::write(&image_path, b"fake_image_data").unwrap();
+        let image_path = image_path.to_string_lossy().to_string();
+
+        let escaped = image_path.replace(' ', "\\ ");
+        assert_eq!(detect_image_paths(&format!("what is in {escaped}")), vec![
+            image_path.clone()
+        ]);
+        assert_eq!(detect_image_paths(&format!("'{image_path}' and '{image_path}'")), vec![
+            image_path.clone()
+        ]);
+        assert!(detect_image_paths("look at missing.png").is_empty());
+    }
+
     #[test]
     fn test_handle_images_from_paths() {
         let temp_dir = tempfile::tempdir().unwrap();
@@ -32,7 +32,7 @@ impl IssueCreator {
         let warning = |text: &String| {
             format!("<This will be visible to anyone. Do not include personal or sensitive information>\n\n{text}")
         };
-        let diagnostics = Diagnostics::new(&os.env).await;
+        let diagnostics = Diagnostics::new(&os.env, &os.database).await;
 
         let os = match &diagnostics.system_info.os {
             Some(os) => os.to_string(),
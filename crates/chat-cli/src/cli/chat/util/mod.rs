@@ -1,4 +1,5 @@
 pub mod clipboard;
+pub mod image_display;
 pub mod images;
 pub mod issue;
 #[cfg(test)]
@@ -15,7 +16,6 @@ use aws_smithy_types::{
 use eyre::Result;
 
 use super::ChatError;
-use super::token_counter::TokenCounter;
 use crate::util::env_var::get_term;
 
 pub fn truncate_safe(s: &str, max_bytes: usize) -> &str {
@@ -54,6 +54,10 @@ pub fn truncate_safe_in_place(s: &mut String, max_bytes: usize, suffix: &str) {
 }
 
 pub fn animate_output(output: &mut impl Write, bytes: &[u8]) -> Result<(), ChatError> {
+    if super::accessible::is_enabled() {
+        output.write_all(bytes)?;
+        return Ok(());
+    }
     for b in bytes.chunks(12) {
         output.write_all(b)?;
         std::thread::sleep(Duration::from_millis(16));
@@ -157,30 +161,6 @@ fn should_play_bell() -> bool {
     false
 }
 
-/// This is a simple greedy algorithm that drops the largest files first
-/// until the total size is below the limit
-///
-/// # Arguments
-/// * `files` - A mutable reference to a vector of tuples: (filename, content). This file will be
-///   sorted but the content will not be changed.
-///
-/// Returns the dropped files
-pub fn drop_matched_context_files(files: &mut [(String, String)], limit: usize) -> Result<Vec<(String, String)>> {
-    files.sort_by(|a, b| TokenCounter::count_tokens(&b.1).cmp(&TokenCounter::count_tokens(&a.1)));
-    let mut total_size = 0;
-    let mut dropped_files = Vec::new();
-
-    for (filename, content) in files.iter() {
-        let size = TokenCounter::count_tokens(content);
-        if total_size + size > limit {
-            dropped_files.push((filename.clone(), content.clone()));
-        } else {
-            total_size += size;
-        }
-    }
-    Ok(dropped_files)
-}
-
 pub fn serde_value_to_document(value: serde_json::Value) -> Document {
     match value {
         serde_json::Value::Null => Document::Null,
@@ -272,27 +252,6 @@ mod tests {
         }
     }
 
-    #[test]
-    fn test_drop_matched_context_files() {
-        let mut files = vec![
-            ("file1".to_string(), "This is a test file".to_string()),
-            (
-                "file3".to_string(),
-                "Yet another test file that's has the largest context file".to_string(),
-            ),
-        ];
-        let limit = 9;
-
-        let dropped_files = drop_matched_context_files(&mut files, limit).unwrap();
-        assert_eq!(dropped_files.len(), 1);
-        assert_eq!(dropped_files[0].0, "file3");
-        assert_eq!(files.len(), 2);
-
-        for (filename, _) in dropped_files.iter() {
-            files.retain(|(f, _)| f != filename);
-        }
-        assert_eq!(files.len(), 1);
-    }
     #[test]
     fn is_hidden_recognises_all_ranges() {
         let samples = ['\u{E0000}', '\u{200B}', '\u{2028}', '\u{205F}', '\u{FFF0}'];
@@ -0,0 +1,308 @@
+//! `q chat watch-files`, which runs a prompt with `q chat --no-interactive` whenever files matching
+//! globs change, for assistants that keep the tests passing or the docs up to date.
+//!
+//! Changes are debounced so that a burst of writes, e.g. saving several files or checking out a
+//! branch, triggers a single run. Each run is a new turn of the conversation started by the first
+//! one. Changes made while a prompt runs, e.g. by the agent itself, don't trigger another run.
+
+use std::collections::BTreeSet;
+use std::path::{
+    Path,
+    PathBuf,
+};
+use std::process::Stdio;
+use std::time::Duration;
+
+use clap::Args;
+use crossterm::{
+    execute,
+    style,
+};
+use globset::{
+    Glob,
+    GlobSet,
+    GlobSetBuilder,
+};
+use notify::{
+    EventKind,
+    RecursiveMode,
+    Watcher,
+};
+use thiserror::Error;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::mpsc::{
+    UnboundedReceiver,
+    unbounded_channel,
+};
+
+use super::ChatExitCode;
+use super::batch::PromptDefaults;
+use super::template_vars::interpolate;
+use crate::os::Os;
+use crate::theme::StyledText;
+
+/// Placeholder of the prompt file replaced with the files that changed
+const CHANGED_FILES_VAR: &str = "{{changed_files}}";
+
+#[derive(Debug, Clone, PartialEq, Eq, Args)]
+pub struct WatchFilesArgs {
+    /// Glob of the files to watch, relative to the current directory. Can be given several times
+    #[arg(long = "glob", value_name = "GLOB", required = true)]
+    pub globs: Vec<String>,
+    /// File of the prompt to run, in which {{changed_files}} is replaced with the files that
+    /// changed, one per line
+    #[arg(long, value_name = "FILE")]
+    pub prompt_file: PathBuf,
+    /// Time to wait after the last change before running the prompt, in milliseconds
+    #[arg(long, value_name = "MS", default_value_t = 500)]
+    pub debounce_ms: u64,
+}
+
+#[derive(Debug, Error)]
+pub enum WatchError {
+    #[error("Invalid glob '{glob}': {error}")]
+    InvalidGlob { glob: String, error: globset::Error },
+    #[error("Failed to read the prompt file at {}: {error}", path.display())]
+    PromptFile { path: PathBuf, error: std::io::Error },
+    #[error("Failed to watch the files: {0}")]
+    Watch(#[from] notify::Error),
+}
+
+impl WatchFilesArgs {
+    /// Runs the prompt on every change until interrupted with ctrl+c.
+    pub async fn execute(&self, os: &Os, defaults: &PromptDefaults) -> eyre::Result<ChatExitCode> {
+        let globs = build_globs(&self.globs)?;
+        // Read now to report a missing prompt file before waiting for changes
+        self.read_prompt_file(os).await?;
+        // Canonical so that the paths of the events, e.g. under /private on macOS, start with it
+        let cwd = os.env.current_dir()?.canonicalize()?;
+        let exe = os.env.current_exe()?;
+        let debounce = Duration::from_millis(self.debounce_ms);
+        let mut stderr = std::io::stderr();
+
+        let (tx, mut rx) = unbounded_channel();
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            if let Ok(event) = event {
+                if !matches!(event.kind, EventKind::Access(_)) {
+                    for path in event.paths {
+                        let _ = tx.send(path);
+                    }
+                }
+            }
+        })
+        .map_err(WatchError::from)?;
+        watcher
+            .watch(&cwd, RecursiveMode::Recursive)
+            .map_err(WatchError::from)?;
+
+        execute!(
+            stderr,
+            StyledText::secondary_fg(),
+            style::Print(format!(
+                "Watching {} for changes. Press ctrl+c to stop\n",
+                self.globs.join(", ")
+            )),
+            StyledText::reset(),
+        )?;
+
+        let mut runs = 0;
+        loop {
+            let changed = tokio::select! {
+                changed = next_changes(&mut rx, &cwd, &globs, debounce) => changed,
+                _ = tokio::signal::ctrl_c() => break,
+            };
+            // The watcher stopped
+            let Some(changed) = changed else {
+                break;
+            };
+
+            // Read again on every run so that edits to the prompt apply to the next runs. Editors
+            // saving atomically replace the file, so it may be briefly missing
+            let prompt_file = match self.read_prompt_file(os).await {
+                Ok(prompt_file) => prompt_file,
+                Err(err) => {
+                    execute!(
+                        stderr,
+                        StyledText::warning_fg(),
+                        style::Print(format!("{err}, skipping the changes\n")),
+                        StyledText::reset(),
+                    )?;
+                    continue;
+                },
+            };
+
+            runs += 1;
+            execute!(
+                stderr,
+                StyledText::emphasis_fg(),
+                style::Print(format!(
+                    "\n[run {runs}] {}\n",
+                    changed
+                        .iter()
+                        .map(|path| path.to_string_lossy())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )),
+                StyledText::reset(),
+            )?;
+
+            let prompt = render_prompt(os, &prompt_file, &changed).await;
+            let mut child = tokio::process::Command::new(&exe)
+                .args(defaults.command_args(runs > 1))
+                .stdin(Stdio::piped())
+                .kill_on_drop(true)
+                .spawn()?;
+            if let Some(mut stdin) = child.stdin.take() {
+                stdin.write_all(prompt.as_bytes()).await?;
+            }
+            let status = tokio::select! {
+                status = child.wait() => status?,
+                _ = tokio::signal::ctrl_c() => break,
+            };
+
+            execute!(
+                stderr,
+                if status.success() {
+                    StyledText::success_fg()
+                } else {
+                    StyledText::error_fg()
+                },
+                style::Print(match status.code() {
+                    Some(0) => format!("[run {runs}] ✓\n"),
+                    Some(code) => format!("[run {runs}] ✗ exit code {code}\n"),
+                    None => format!("[run {runs}] ✗ terminated\n"),
+                }),
+                StyledText::reset(),
+            )?;
+
+            // Drops the changes made during the run, waiting for the last events to come in
+            tokio::time::sleep(debounce).await;
+            while rx.try_recv().is_ok() {}
+        }
+
+        Ok(ChatExitCode::Success)
+    }
+
+    async fn read_prompt_file(&self, os: &Os) -> Result<String, WatchError> {
+        os.fs
+            .read_to_string(&self.prompt_file)
+            .await
+            .map_err(|error| WatchError::PromptFile {
+                path: self.prompt_file.clone(),
+                error,
+            })
+    }
+}
+
+fn build_globs(globs: &[String]) -> Result<GlobSet, WatchError> {
+    let mut builder = GlobSetBuilder::new();
+    for glob in globs {
+        builder.add(Glob::new(glob).map_err(|error| WatchError::InvalidGlob {
+            glob: glob.clone(),
+            error,
+        })?);
+    }
+    builder.build().map_err(|error| WatchError::InvalidGlob {
+        glob: globs.join(", "),
+        error,
+    })
+}
+
+/// Waits for changes to files matching `globs`, returning them relative to `cwd` once no change
+/// came in for `debounce`. Returns [None] when the watcher stopped.
+async fn next_changes(
+    rx: &mut UnboundedReceiver<PathBuf>,
+    cwd: &Path,
+    globs: &GlobSet,
+    debounce: Duration,
+) -> Option<BTreeSet<PathBuf>> {
+    let mut changed = BTreeSet::new();
+    loop {
+        let path = if changed.is_empty() {
+            rx.recv().await?
+        } else {
+            match tokio::time::timeout(debounce, rx.recv()).await {
+                Ok(Some(path)) => path,
+                Ok(None) | Err(_) => return Some(changed),
+            }
+        };
+        let relative = path.strip_prefix(cwd).unwrap_or(&path);
+        if globs.is_match(relative) {
+            changed.insert(relative.to_path_buf());
+        }
+    }
+}
+
+async fn render_prompt(os: &Os, template: &str, changed: &BTreeSet<PathBuf>) -> String {
+    let files = changed
+        .iter()
+        .map(|path| path.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("\n");
+    interpolate(os, &template.replace(CHANGED_FILES_VAR, &files)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_next_changes() {
+        let globs = build_globs(&["src/**/*.rs".to_string(), "Cargo.toml".to_string()]).unwrap();
+        let cwd = Path::new("/repo");
+        let (tx, mut rx) = unbounded_channel();
+        for path in [
+            "/repo/src/lib.rs",
+            "/repo/target/debug/build.rs",
+            "/repo/src/cli/mod.rs",
+            "/repo/src/lib.rs",
+            "/repo/README.md",
+            "/repo/Cargo.toml",
+        ] {
+            tx.send(PathBuf::from(path)).unwrap();
+        }
+
+        let changed = next_changes(&mut rx, cwd, &globs, Duration::from_millis(10)).await;
+        assert_eq!(
+            changed,
+            Some(BTreeSet::from([
+                PathBuf::from("Cargo.toml"),
+                PathBuf::from("src/cli/mod.rs"),
+                PathBuf::from("src/lib.rs"),
+            ]))
+        );
+
+        drop(tx);
+        assert_eq!(
+            next_changes(&mut rx, cwd, &globs, Duration::from_millis(10)).await,
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_render_prompt() {
+        let os = Os::new().await.unwrap();
+        let changed = BTreeSet::from([PathBuf::from("src/lib.rs"), PathBuf::from("src/main.rs")]);
+        assert_eq!(
+            render_prompt(
+                &os,
+                "Fix the tests on {{os}} after changes to:\n{{changed_files}}",
+                &changed
+            )
+            .await,
+            format!(
+                "Fix the tests on {} after changes to:\nsrc/lib.rs\nsrc/main.rs",
+                std::env::consts::OS
+            )
+        );
+    }
+
+    #[test]
+    fn test_build_globs() {
+        assert!(build_globs(&["src/**/*.rs".to_string()]).is_ok());
+        assert!(matches!(
+            build_globs(&["src/[.rs".to_string()]),
+            Err(WatchError::InvalidGlob { glob, .. }) if glob == "src/[.rs"
+        ));
+    }
+}
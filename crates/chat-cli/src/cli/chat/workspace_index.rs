@@ -0,0 +1,764 @@
+//! Index of the files of a workspace, built with `q index build`.
+//!
+//! The index maps every file of the workspace to its top-level symbols and to the workspace files
+//! it imports. Once built, the entries related to each prompt are added to the context, which
+//! helps with questions about large repositories without including whole files.
+
+use std::collections::{
+    HashMap,
+    HashSet,
+    VecDeque,
+};
+use std::path::{
+    Path,
+    PathBuf,
+};
+use std::sync::{
+    Arc,
+    LazyLock,
+    Mutex,
+};
+use std::time::SystemTime;
+
+use chrono::{
+    DateTime,
+    Utc,
+};
+use eyre::Result;
+use globset::{
+    GlobSet,
+    GlobSetBuilder,
+};
+use regex::Regex;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use tracing::warn;
+
+use super::directory_summary::{
+    MAX_PARSED_FILE_SIZE,
+    SKIPPED_DIRECTORIES,
+    top_level_symbols,
+};
+use super::symbol_context::import_statements;
+use super::token_counter::TokenCounter;
+use crate::os::Os;
+use crate::util::paths::{
+    PathResolver,
+    add_gitignore_globs,
+};
+
+/// Maximum number of files indexed.
+const MAX_INDEXED_FILES: usize = 50_000;
+
+/// Maximum number of files included in the slice of the index added to a prompt.
+const MAX_SLICE_FILES: usize = 12;
+
+/// Maximum size of the slice of the index added to a prompt.
+const MAX_SLICE_CHARS: usize = TokenCounter::token_to_chars(2000);
+
+/// Maximum number of symbols, and of importing files, listed for a file of the slice.
+const MAX_SLICE_SYMBOLS: usize = 15;
+const MAX_SLICE_IMPORTERS: usize = 8;
+
+/// Files scoring less than this against a prompt are not considered related to it.
+const MIN_RELEVANCE_SCORE: usize = 4;
+
+/// Words too common in prompts to tell files apart.
+const STOP_WORDS: &[&str] = &[
+    "the", "and", "for", "with", "this", "that", "what", "how", "does", "from", "into", "where", "which", "why",
+    "when", "are", "was", "can", "you", "please", "should", "would", "could", "about", "file", "files", "code",
+    "function", "make", "add", "fix", "use", "used", "using", "get", "set", "new", "all", "not", "there", "here",
+    "have", "has",
+];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WorkspaceIndex {
+    /// When the index was built.
+    pub built_at: DateTime<Utc>,
+    pub files: Vec<IndexedFile>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IndexedFile {
+    /// Path of the file, relative to the root of the workspace and separated by `/`.
+    pub path: String,
+    /// Top-level symbols, formatted as `<kind> <name>`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub symbols: Vec<String>,
+    /// Workspace files, or directories ending with `/`, imported by the file.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub imports: Vec<String>,
+}
+
+/// Path of the index of the current workspace.
+pub fn index_path(os: &Os) -> Result<PathBuf> {
+    Ok(PathResolver::new(os).workspace().index_file()?)
+}
+
+/// Reads the patterns of the `.gitignore` file at the root of the workspace.
+async fn gitignore(os: &Os, root: &Path) -> GlobSet {
+    let mut builder = GlobSetBuilder::new();
+    if let Ok(content) = os.fs.read_to_string(root.join(".gitignore")).await {
+        for line in content.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') || line.starts_with('!') {
+                continue;
+            }
+            let pattern = line.trim_end_matches('/');
+            // Patterns without a slash other than a trailing one match at any depth
+            let pattern = match pattern.strip_prefix('/') {
+                Some(anchored) => anchored.to_string(),
+                None if pattern.contains('/') => pattern.to_string(),
+                None => format!("**/{pattern}"),
+            };
+            if let Err(e) = add_gitignore_globs(&mut builder, &pattern) {
+                warn!(?e, "Ignoring invalid .gitignore pattern {line}");
+            }
+        }
+    }
+    builder.build().unwrap_or_else(|_| GlobSet::empty())
+}
+
+/// Walks the workspace at `root` and builds its index. Hidden files, files ignored by the
+/// `.gitignore` of the workspace and common build and dependency directories are skipped.
+pub async fn build_index(os: &Os, root: &Path) -> Result<WorkspaceIndex> {
+    let ignored = gitignore(os, root).await;
+
+    // (relative path, size)
+    let mut paths: Vec<(String, u64)> = Vec::new();
+    let mut dir_queue = VecDeque::from([root.to_path_buf()]);
+    'outer: while let Some(dir) = dir_queue.pop_front() {
+        let mut read_dir = os.fs.read_dir(&dir).await?;
+        while let Some(entry) = read_dir.next_entry().await? {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name.starts_with('.') {
+                continue;
+            }
+            let Ok(metadata) = entry.metadata().await else {
+                continue;
+            };
+            let relative = relative_path(root, &entry.path());
+            if ignored.is_match(&relative) {
+                continue;
+            }
+
+            if metadata.is_dir() {
+                if !SKIPPED_DIRECTORIES.contains(&name.as_str()) {
+                    dir_queue.push_back(entry.path());
+                }
+            } else if metadata.is_file() {
+                if paths.len() >= MAX_INDEXED_FILES {
+                    warn!("Only the first {MAX_INDEXED_FILES} files of the workspace are indexed");
+                    break 'outer;
+                }
+                paths.push((relative, metadata.len()));
+            }
+        }
+    }
+    paths.sort();
+
+    let mut files = Vec::new();
+    let mut statements = Vec::new();
+    for (path, size) in &paths {
+        let content = match *size <= MAX_PARSED_FILE_SIZE {
+            true => os
+                .fs
+                .read(root.join(path))
+                .await
+                .ok()
+                .and_then(|c| String::from_utf8(c).ok()),
+            false => None,
+        };
+        let extension = extension(path);
+        let content = content.unwrap_or_default();
+        files.push(IndexedFile {
+            path: path.clone(),
+            symbols: top_level_symbols(extension, &content),
+            imports: Vec::new(),
+        });
+        statements.push(import_statements(extension, &content));
+    }
+
+    let resolver = ImportResolver::new(paths.iter().map(|(path, _)| path.as_str()));
+    let imports = files
+        .iter()
+        .zip(&statements)
+        .map(|(file, statements)| {
+            let mut imports = Vec::new();
+            for statement in statements {
+                for import in resolver.resolve(&file.path, statement) {
+                    if import != file.path && !imports.contains(&import) {
+                        imports.push(import);
+                    }
+                }
+            }
+            imports
+        })
+        .collect::<Vec<_>>();
+    for (file, imports) in files.iter_mut().zip(imports) {
+        file.imports = imports;
+    }
+
+    Ok(WorkspaceIndex {
+        built_at: Utc::now(),
+        files,
+    })
+}
+
+/// Path of `path` relative to `root`, separated by `/` on every platform.
+fn relative_path(root: &Path, path: &Path) -> String {
+    path.strip_prefix(root)
+        .unwrap_or(path)
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn extension(path: &str) -> &str {
+    let name = file_name(path);
+    name.rsplit_once('.').map(|(_, ext)| ext).unwrap_or_default()
+}
+
+fn file_name(path: &str) -> &str {
+    path.rsplit_once('/').map_or(path, |(_, name)| name)
+}
+
+fn parent_dir(path: &str) -> &str {
+    path.rsplit_once('/').map_or("", |(dir, _)| dir)
+}
+
+/// Joins `relative` to `dir`, resolving `.` and `..`. Returns [None] for paths leaving the
+/// workspace.
+fn join_path(dir: &str, relative: &str) -> Option<String> {
+    let mut components = dir.split('/').filter(|c| !c.is_empty()).collect::<Vec<_>>();
+    for component in relative.split('/') {
+        match component {
+            "" | "." => {},
+            ".." => {
+                components.pop()?;
+            },
+            component => components.push(component),
+        }
+    }
+    Some(components.join("/"))
+}
+
+static JS_IMPORT: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(?:\bfrom|^import|\brequire\()\s*['"]([^'"]+)['"]"#).unwrap());
+static QUOTED: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#""([^"]+)""#).unwrap());
+static ALIAS: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\s+as\s+[A-Za-z_][A-Za-z0-9_]*").unwrap());
+
+/// Resolves import statements to the workspace files they refer to.
+struct ImportResolver<'a> {
+    files: HashSet<&'a str>,
+    /// Files by file name.
+    files_by_name: HashMap<&'a str, Vec<&'a str>>,
+    /// Directories containing files, by directory name.
+    dirs_by_name: HashMap<&'a str, HashSet<&'a str>>,
+}
+
+impl<'a> ImportResolver<'a> {
+    fn new(paths: impl Iterator<Item = &'a str>) -> Self {
+        let mut resolver = Self {
+            files: HashSet::new(),
+            files_by_name: HashMap::new(),
+            dirs_by_name: HashMap::new(),
+        };
+        for path in paths {
+            resolver.files.insert(path);
+            resolver.files_by_name.entry(file_name(path)).or_default().push(path);
+            let dir = parent_dir(path);
+            resolver.dirs_by_name.entry(file_name(dir)).or_default().insert(dir);
+        }
+        resolver
+    }
+
+    /// Returns the first of `candidates` that is a workspace file.
+    fn first_file(&self, candidates: impl IntoIterator<Item = String>) -> Option<String> {
+        candidates.into_iter().find(|c| self.files.contains(c.as_str()))
+    }
+
+    /// Files named like the last component of `suffix` whose path ends with `suffix`.
+    fn files_ending_with(&self, suffix: &str) -> Vec<String> {
+        self.files_by_name
+            .get(file_name(suffix))
+            .into_iter()
+            .flatten()
+            .filter(|path| **path == suffix || path.ends_with(&format!("/{suffix}")))
+            .map(|path| (*path).to_string())
+            .collect()
+    }
+
+    /// Directories whose path ends with `suffix`, formatted with a trailing `/`.
+    fn dirs_ending_with(&self, suffix: &str) -> Vec<String> {
+        self.dirs_by_name
+            .get(file_name(suffix))
+            .into_iter()
+            .flatten()
+            .filter(|dir| **dir == suffix || dir.ends_with(&format!("/{suffix}")))
+            .map(|dir| format!("{dir}/"))
+            .collect()
+    }
+
+    fn resolve(&self, file: &str, statement: &str) -> Vec<String> {
+        let dir = parent_dir(file);
+        match extension(file) {
+            "rs" => self.resolve_rust(file, statement),
+            "py" | "pyi" => self.resolve_python(dir, statement),
+            "js" | "jsx" | "mjs" | "cjs" | "ts" | "tsx" | "mts" | "cts" => JS_IMPORT
+                .captures_iter(statement)
+                .filter(|c| c[1].starts_with('.'))
+                .filter_map(|c| join_path(dir, &c[1]))
+                .filter_map(|base| {
+                    let mut candidates = vec![base.clone()];
+                    for ext in ["ts", "tsx", "js", "jsx", "mjs", "cjs", "d.ts"] {
+                        candidates.push(format!("{base}.{ext}"));
+                        candidates.push(format!("{base}/index.{ext}"));
+                    }
+                    self.first_file(candidates)
+                })
+                .collect(),
+            "go" => QUOTED
+                .captures_iter(statement)
+                .flat_map(|c| {
+                    // Matches on the last two components of the package path, as the module
+                    // prefix usually isn't part of the workspace paths
+                    let segments = c[1].split('/').collect::<Vec<_>>();
+                    let suffix = segments[segments.len().saturating_sub(2)..].join("/");
+                    self.dirs_ending_with(&suffix)
+                })
+                .collect(),
+            "java" | "kt" | "kts" | "scala" => {
+                let statement = statement
+                    .trim_start_matches("import")
+                    .trim()
+                    .trim_start_matches("static ")
+                    .trim_end_matches(';');
+                let statement = ALIAS.replace(statement, "");
+                let segments = statement.trim().split('.').collect::<Vec<_>>();
+                match segments.split_last() {
+                    Some((&"*", package)) => self.dirs_ending_with(&package.join("/")),
+                    Some((class, package)) => ["java", "kt", "scala"]
+                        .iter()
+                        .flat_map(|ext| {
+                            let mut path = package.to_vec();
+                            let name = format!("{class}.{ext}");
+                            path.push(&name);
+                            self.files_ending_with(&path.join("/"))
+                        })
+                        .collect(),
+                    None => Vec::new(),
+                }
+            },
+            "c" | "h" | "cc" | "cpp" | "cxx" | "hh" | "hpp" | "hxx" => QUOTED
+                .captures_iter(statement)
+                .flat_map(|c| match join_path(dir, &c[1]).and_then(|p| self.first_file([p])) {
+                    Some(path) => vec![path],
+                    None => self.files_ending_with(&c[1]),
+                })
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn resolve_rust(&self, file: &str, statement: &str) -> Vec<String> {
+        let statement = ALIAS.replace_all(statement, "");
+        let statement = statement
+            .split_whitespace()
+            .collect::<String>()
+            .trim_start_matches("pub(crate)")
+            .trim_start_matches("pub")
+            .trim_end_matches(';')
+            .to_string();
+        let Some(statement) = statement.strip_prefix("use") else {
+            return Vec::new();
+        };
+
+        // Directory holding the submodules of the module defined by `file`
+        let name = file_name(file);
+        let module_dir = match name {
+            "mod.rs" | "lib.rs" | "main.rs" => parent_dir(file).to_string(),
+            _ => format!("{}/{}", parent_dir(file), name.trim_end_matches(".rs"))
+                .trim_start_matches('/')
+                .to_string(),
+        };
+        let crate_dir = match file.rfind("src/") {
+            Some(i) => &file[..i + 3],
+            None => parent_dir(file),
+        };
+
+        let mut res = Vec::new();
+        for path in expand_use_tree(statement) {
+            let segments = path.split("::").filter(|s| !s.is_empty()).collect::<Vec<_>>();
+            let (base, rest) = match segments.split_first() {
+                Some((&"crate", rest)) => (crate_dir.to_string(), rest),
+                Some((&"self", rest)) => (module_dir.clone(), rest),
+                Some((&"super", rest)) => (parent_dir(&module_dir).to_string(), rest),
+                _ => continue,
+            };
+            // Items are imported from their module, so the longest path naming a module wins
+            let resolved = (1..=rest.len()).rev().find_map(|n| {
+                let module = join_path(&base, &rest[..n].join("/"))?;
+                self.first_file([format!("{module}.rs"), format!("{module}/mod.rs")])
+            });
+            if let Some(resolved) = resolved {
+                res.push(resolved);
+            }
+        }
+        res
+    }
+
+    fn resolve_python(&self, dir: &str, statement: &str) -> Vec<String> {
+        let statement = statement
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .replace(['(', ')'], "");
+        let statement = ALIAS.replace_all(&statement, "");
+
+        // (module, names imported from it)
+        let (module, names) = match statement.strip_prefix("from ") {
+            Some(rest) => match rest.split_once(" import ") {
+                Some((module, names)) => (module.trim(), names.split(',').map(str::trim).collect::<Vec<_>>()),
+                None => return Vec::new(),
+            },
+            None => match statement.strip_prefix("import ") {
+                Some(modules) => {
+                    return modules
+                        .split(',')
+                        .filter_map(|module| self.resolve_python_module(dir, module.trim()))
+                        .collect();
+                },
+                None => return Vec::new(),
+            },
+        };
+
+        // Names may be submodules of the module, or items defined in it
+        let mut res = Vec::new();
+        for name in names {
+            let module = match module.ends_with('.') {
+                true => format!("{module}{name}"),
+                false => format!("{module}.{name}"),
+            };
+            if let Some(path) = self.resolve_python_module(dir, &module) {
+                res.push(path);
+            }
+        }
+        if res.is_empty() {
+            res.extend(self.resolve_python_module(dir, module));
+        }
+        res
+    }
+
+    fn resolve_python_module(&self, dir: &str, module: &str) -> Option<String> {
+        let dots = module.len() - module.trim_start_matches('.').len();
+        let path = module.trim_start_matches('.').replace('.', "/");
+        let bases = match dots {
+            0 => vec![String::new(), "src".to_string()],
+            dots => vec![join_path(dir, &"../".repeat(dots - 1))?],
+        };
+        self.first_file(bases.iter().flat_map(|base| {
+            let module = join_path(base, &path).unwrap_or_default();
+            [format!("{module}.py"), format!("{module}/__init__.py")]
+        }))
+    }
+}
+
+/// Expands the braces of a use tree, e.g. `a::{b, c::{d, e}}` into `a::b`, `a::c::d` and
+/// `a::c::e`. Expects the tree without whitespace.
+fn expand_use_tree(tree: &str) -> Vec<String> {
+    let Some(open) = tree.find('{') else {
+        return vec![tree.trim_end_matches("::self").to_string()];
+    };
+    let Some(close) = tree.rfind('}') else {
+        return Vec::new();
+    };
+    let (prefix, inner) = (&tree[..open], &tree[open + 1..close]);
+
+    let mut items = Vec::new();
+    let (mut depth, mut start) = (0, 0);
+    for (i, c) in inner.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            ',' if depth == 0 => {
+                items.push(&inner[start..i]);
+                start = i + 1;
+            },
+            _ => {},
+        }
+    }
+    items.push(&inner[start..]);
+
+    items
+        .into_iter()
+        .filter(|item| !item.is_empty())
+        .flat_map(|item| expand_use_tree(&format!("{prefix}{item}")))
+        .collect()
+}
+
+/// Splits `s` into lowercase words, splitting identifiers on underscores and case changes.
+fn words(s: &str) -> Vec<String> {
+    let mut res = Vec::new();
+    for identifier in s.split(|c: char| !c.is_alphanumeric()) {
+        let mut word = String::new();
+        let mut prev_lowercase = false;
+        for c in identifier.chars() {
+            if c.is_uppercase() && prev_lowercase && !word.is_empty() {
+                res.push(std::mem::take(&mut word));
+            }
+            prev_lowercase = c.is_lowercase() || c.is_ascii_digit();
+            word.extend(c.to_lowercase());
+        }
+        if !word.is_empty() {
+            res.push(word);
+        }
+    }
+    res
+}
+
+/// How related `file` is to a prompt, given the words and path-like tokens of the prompt.
+fn relevance(file: &IndexedFile, terms: &HashSet<String>, paths: &[&str]) -> usize {
+    if paths
+        .iter()
+        .any(|p| file.path == *p || file.path.ends_with(&format!("/{p}")))
+    {
+        return 100;
+    }
+
+    let stem = file_name(&file.path)
+        .split('.')
+        .next()
+        .unwrap_or_default()
+        .to_lowercase();
+    let path_words = words(&file.path);
+    let symbols = file
+        .symbols
+        .iter()
+        .map(|s| s.rsplit(' ').next().unwrap_or_default())
+        .map(|name| (name.to_lowercase(), words(name)))
+        .collect::<Vec<_>>();
+
+    let mut score = 0;
+    for term in terms {
+        score += match () {
+            _ if stem == *term => 6,
+            _ if path_words.contains(term) => 2,
+            _ => 0,
+        };
+        score += match () {
+            _ if symbols.iter().any(|(name, _)| name == term) => 5,
+            _ if symbols.iter().any(|(_, words)| words.contains(term)) => 2,
+            _ => 0,
+        };
+    }
+    score
+}
+
+/// Returns the entries of `index` related to `prompt`, formatted for the context, or [None] if
+/// none are.
+pub fn relevant_slice(index: &WorkspaceIndex, prompt: &str) -> Option<String> {
+    let terms = words(prompt)
+        .into_iter()
+        .filter(|w| w.len() >= 3 && !STOP_WORDS.contains(&w.as_str()))
+        .collect::<HashSet<_>>();
+    let paths = prompt
+        .split_whitespace()
+        .map(|token| token.trim_matches(|c: char| "`'\"()[],:;".contains(c)))
+        .filter(|token| token.contains('/') || token.contains('.'))
+        .map(|token| token.trim_start_matches("./").trim_end_matches('.'))
+        .filter(|token| !token.is_empty())
+        .collect::<Vec<_>>();
+
+    let mut scored = index
+        .files
+        .iter()
+        .map(|file| (relevance(file, &terms, &paths), file))
+        .filter(|(score, _)| *score >= MIN_RELEVANCE_SCORE)
+        .collect::<Vec<_>>();
+    if scored.is_empty() {
+        return None;
+    }
+    scored.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.path.cmp(&b.1.path)));
+
+    let mut importers: HashMap<&str, Vec<&str>> = HashMap::new();
+    for file in &index.files {
+        for import in &file.imports {
+            importers.entry(import.as_str()).or_default().push(&file.path);
+        }
+    }
+
+    let mut res = format!(
+        "Index of the workspace ({} files, built {}). Entries related to the request, read the files for their content:\n",
+        index.files.len(),
+        index.built_at.format("%Y-%m-%d %H:%M UTC")
+    );
+    for (_, file) in scored.into_iter().take(MAX_SLICE_FILES) {
+        let mut entry = format!("{}\n", file.path);
+        if !file.symbols.is_empty() {
+            let mut symbols = file
+                .symbols
+                .iter()
+                .take(MAX_SLICE_SYMBOLS)
+                .cloned()
+                .collect::<Vec<_>>()
+                .join(", ");
+            if file.symbols.len() > MAX_SLICE_SYMBOLS {
+                symbols.push_str(&format!(" and {} more", file.symbols.len() - MAX_SLICE_SYMBOLS));
+            }
+            entry.push_str(&format!("  symbols: {symbols}\n"));
+        }
+        if !file.imports.is_empty() {
+            entry.push_str(&format!("  imports: {}\n", file.imports.join(", ")));
+        }
+        if let Some(importers) = importers.get(file.path.as_str()) {
+            let mut imported_by = importers
+                .iter()
+                .take(MAX_SLICE_IMPORTERS)
+                .copied()
+                .collect::<Vec<_>>()
+                .join(", ");
+            if importers.len() > MAX_SLICE_IMPORTERS {
+                imported_by.push_str(&format!(" and {} more", importers.len() - MAX_SLICE_IMPORTERS));
+            }
+            entry.push_str(&format!("  imported by: {imported_by}\n"));
+        }
+
+        if res.len() + entry.len() > MAX_SLICE_CHARS {
+            break;
+        }
+        res.push_str(&entry);
+    }
+
+    Some(res)
+}
+
+/// The last index loaded, along with its path and modification time.
+static INDEX_CACHE: Mutex<Option<(PathBuf, SystemTime, Arc<WorkspaceIndex>)>> = Mutex::new(None);
+
+/// Loads the index of the current workspace, if it was built. The index is only read again once
+/// it changes.
+pub async fn load_index(os: &Os) -> Option<Arc<WorkspaceIndex>> {
+    let path = index_path(os).ok()?;
+    let modified = os.fs.symlink_metadata(&path).await.ok()?.modified().ok()?;
+    if let Ok(cache) = INDEX_CACHE.lock() {
+        if let Some((cached_path, cached_modified, index)) = cache.as_ref() {
+            if *cached_path == path && *cached_modified == modified {
+                return Some(index.clone());
+            }
+        }
+    }
+
+    let index = match serde_json::from_str::<WorkspaceIndex>(&os.fs.read_to_string(&path).await.ok()?) {
+        Ok(index) => Arc::new(index),
+        Err(e) => {
+            warn!(?e, "Failed to read the workspace index at {}", path.display());
+            return None;
+        },
+    };
+    if let Ok(mut cache) = INDEX_CACHE.lock() {
+        *cache = Some((path, modified, index.clone()));
+    }
+    Some(index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_use_tree() {
+        assert_eq!(expand_use_tree("crate::a::{b,c::{d,e},self}"), vec![
+            "crate::a::b",
+            "crate::a::c::d",
+            "crate::a::c::e",
+            "crate::a",
+        ]);
+        assert_eq!(expand_use_tree("super::x"), vec!["super::x"]);
+    }
+
+    #[tokio::test]
+    async fn test_build_index() -> Result<()> {
+        let os = Os::new().await.unwrap();
+        for (path, content) in [
+            ("ws/.gitignore", "/generated\n*.log\n"),
+            (
+                "ws/src/lib.rs",
+                "pub mod context;\nmod util;\n\nuse crate::context::{ContextManager, budget::Budget};\n",
+            ),
+            (
+                "ws/src/context/mod.rs",
+                "pub mod budget;\nuse super::util::helper;\n\npub struct ContextManager;\n",
+            ),
+            ("ws/src/context/budget.rs", "pub struct Budget;\n"),
+            ("ws/src/util.rs", "pub fn helper() {}\n"),
+            (
+                "ws/app/main.py",
+                "from .models import Agent\nimport app.views\n\ndef run():\n    pass\n",
+            ),
+            ("ws/app/models.py", "class Agent:\n    pass\n"),
+            ("ws/app/views.py", "def index():\n    pass\n"),
+            (
+                "ws/web/index.ts",
+                "import { render } from './render';\nexport function main() {}\n",
+            ),
+            ("ws/web/render.tsx", "export function render() {}\n"),
+            ("ws/generated/out.rs", "fn generated() {}\n"),
+            ("ws/debug.log", "log\n"),
+        ] {
+            os.fs.create_dir_all(Path::new(path).parent().unwrap()).await?;
+            os.fs.write(path, content).await?;
+        }
+
+        let root = PathBuf::from(os.fs.chroot_path_str("ws"));
+        let index = build_index(&os, &root).await?;
+        let file = |path: &str| index.files.iter().find(|f| f.path == path).unwrap();
+
+        assert_eq!(index.files.iter().map(|f| f.path.as_str()).collect::<Vec<_>>(), vec![
+            "app/main.py",
+            "app/models.py",
+            "app/views.py",
+            "src/context/budget.rs",
+            "src/context/mod.rs",
+            "src/lib.rs",
+            "src/util.rs",
+            "web/index.ts",
+            "web/render.tsx",
+        ]);
+        assert_eq!(file("src/lib.rs").imports, vec![
+            "src/context/mod.rs",
+            "src/context/budget.rs"
+        ]);
+        assert_eq!(file("src/context/mod.rs").imports, vec!["src/util.rs"]);
+        assert_eq!(file("src/context/mod.rs").symbols, vec![
+            "mod budget",
+            "struct ContextManager"
+        ]);
+        assert_eq!(file("app/main.py").imports, vec!["app/models.py", "app/views.py"]);
+        assert_eq!(file("web/index.ts").imports, vec!["web/render.tsx"]);
+
+        let slice = relevant_slice(&index, "How does the ContextManager compute its budget?").unwrap();
+        assert!(slice.contains("\nsrc/context/mod.rs\n  symbols: mod budget, struct ContextManager\n  imports: src/util.rs\n  imported by: src/lib.rs\n"));
+        assert!(slice.contains("\nsrc/context/budget.rs\n"));
+        assert!(!slice.contains("app/"));
+        assert!(relevant_slice(&index, "What is the weather like?").is_none());
+
+        let slice = relevant_slice(&index, "Explain `web/render.tsx`").unwrap();
+        assert!(slice.contains("\nweb/render.tsx\n"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_words() {
+        assert_eq!(words("ContextManager::from_agent(HTTPServer)"), vec![
+            "context",
+            "manager",
+            "from",
+            "agent",
+            "httpserver"
+        ]);
+    }
+}
@@ -0,0 +1,231 @@
+//! `q completions <shell>`, printing the completion script of a shell for all the commands.
+//!
+//! The script is generated by clap_complete. For bash, zsh and fish, it also completes the values
+//! of `--agent` with the configured agents and of `--model` with the models last listed by the
+//! backend, by running `q completions --values agent|model` while completing.
+
+use std::io::{
+    Write,
+    stdout,
+};
+use std::process::ExitCode;
+
+use clap::{
+    Args,
+    Command,
+    CommandFactory,
+    ValueEnum,
+};
+use clap_complete::Shell;
+use eyre::Result;
+
+use super::Cli;
+use super::agent::Agents;
+use super::chat::cli::model::fallback_model_names;
+use crate::os::Os;
+use crate::util::CLI_BINARY_NAME;
+
+#[derive(Debug, Args, PartialEq, Eq)]
+pub struct CompletionsArgs {
+    /// Shell to print the completion script of
+    #[arg(value_enum, required_unless_present = "values")]
+    shell: Option<Shell>,
+    /// Name of the command completed by the script
+    #[arg(long, default_value = CLI_BINARY_NAME)]
+    bin_name: String,
+    /// Print the values completed for an option instead, one per line, used by the scripts
+    #[arg(long, value_enum, hide = true, conflicts_with = "shell")]
+    values: Option<DynamicValues>,
+}
+
+/// Options completed with values only known at runtime.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum DynamicValues {
+    Agent,
+    Model,
+}
+
+impl DynamicValues {
+    fn from_long(long: &str) -> Option<Self> {
+        match long {
+            "agent" => Some(Self::Agent),
+            "model" => Some(Self::Model),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Agent => "agent",
+            Self::Model => "model",
+        }
+    }
+}
+
+impl CompletionsArgs {
+    pub async fn execute(self, os: &mut Os) -> Result<ExitCode> {
+        let mut stdout = stdout().lock();
+        match (self.values, self.shell) {
+            (Some(values), _) => {
+                for value in dynamic_values(os, values).await {
+                    writeln!(stdout, "{value}")?;
+                }
+            },
+            (None, Some(shell)) => {
+                let mut command = Cli::command();
+                clap_complete::generate(shell, &mut command, &self.bin_name, &mut stdout);
+                stdout.write_all(dynamic_script(shell, &command, &self.bin_name).as_bytes())?;
+            },
+            (None, None) => unreachable!("clap requires the shell without --values"),
+        }
+        stdout.flush()?;
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+async fn dynamic_values(os: &mut Os, values: DynamicValues) -> Vec<String> {
+    match values {
+        DynamicValues::Agent => {
+            let (agents, _) = Agents::load(os, None, true, &mut std::io::sink(), true).await;
+            let mut names = agents.agents.into_keys().collect::<Vec<_>>();
+            names.sort();
+            names
+        },
+        DynamicValues::Model => match os.database.get_model_names() {
+            Ok(Some(names)) if !names.is_empty() => names,
+            _ => fallback_model_names(),
+        },
+    }
+}
+
+/// The subcommand paths and [DynamicValues] of the options completed dynamically.
+fn dynamic_options(command: &Command) -> Vec<(Vec<String>, DynamicValues)> {
+    fn walk(command: &Command, path: &mut Vec<String>, options: &mut Vec<(Vec<String>, DynamicValues)>) {
+        for arg in command.get_arguments() {
+            if let Some(values) = arg
+                .get_long()
+                .and_then(DynamicValues::from_long)
+                .filter(|_| arg.get_action().takes_values())
+            {
+                options.push((path.clone(), values));
+            }
+        }
+        for subcommand in command.get_subcommands() {
+            path.push(subcommand.get_name().to_string());
+            walk(subcommand, path, options);
+            path.pop();
+        }
+    }
+
+    let mut options = Vec::new();
+    walk(command, &mut Vec::new(), &mut options);
+    options
+}
+
+/// Completes the values of the [dynamic_options] of `command`, after the script of clap_complete.
+fn dynamic_script(shell: Shell, command: &Command, bin_name: &str) -> String {
+    let options = dynamic_options(command);
+    let mut longs = options.iter().map(|(_, values)| *values).collect::<Vec<_>>();
+    longs.sort_by_key(|values| values.name());
+    longs.dedup();
+    let function = format!("_{}", bin_name.replace('-', "__"));
+
+    match shell {
+        Shell::Bash => {
+            let cases = longs
+                .iter()
+                .map(|values| {
+                    let name = values.name();
+                    format!(
+                        "        --{name})\n            COMPREPLY=($(compgen -W \"$({bin_name} completions --values {name} 2>/dev/null)\" -- \"${{COMP_WORDS[COMP_CWORD]}}\"))\n            return 0\n            ;;\n"
+                    )
+                })
+                .collect::<String>();
+            format!(
+                "\n{function}_dynamic() {{\n    case \"${{COMP_WORDS[COMP_CWORD-1]}}\" in\n{cases}    esac\n    {function} \"$@\"\n}}\n\ncomplete -F {function}_dynamic -o bashdefault -o default {bin_name}\n"
+            )
+        },
+        Shell::Zsh => {
+            let cases = longs
+                .iter()
+                .map(|values| {
+                    let name = values.name();
+                    format!(
+                        "        --{name})\n            compadd -- ${{(f)\"$({bin_name} completions --values {name} 2>/dev/null)\"}}\n            return\n            ;;\n"
+                    )
+                })
+                .collect::<String>();
+            format!(
+                "\n{function}_dynamic() {{\n    case \"$words[CURRENT-1]\" in\n{cases}    esac\n    {function} \"$@\"\n}}\n\ncompdef {function}_dynamic {bin_name}\n"
+            )
+        },
+        Shell::Fish => {
+            let name = bin_name.replace('-', "_");
+            options
+                .iter()
+                .map(|(path, values)| {
+                    let condition = match path.split_first() {
+                        None => format!("__fish_{name}_needs_command"),
+                        Some((first, [])) => format!("__fish_{name}_using_subcommand {first}"),
+                        Some((first, rest)) => format!(
+                            "__fish_{name}_using_subcommand {first}; and __fish_seen_subcommand_from {}",
+                            rest.join(" ")
+                        ),
+                    };
+                    let long = values.name();
+                    format!(
+                        "complete -c {bin_name} -n \"{condition}\" -l {long} -f -a \"({bin_name} completions --values {long} 2>/dev/null)\"\n"
+                    )
+                })
+                .collect()
+        },
+        _ => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dynamic_options() {
+        let options = dynamic_options(&Cli::command());
+        assert!(options.contains(&(vec!["chat".to_string()], DynamicValues::Agent)));
+        assert!(options.contains(&(vec!["chat".to_string()], DynamicValues::Model)));
+    }
+
+    #[test]
+    fn test_dynamic_script() {
+        let mut command = Cli::command();
+        let mut script = Vec::new();
+        clap_complete::generate(Shell::Bash, &mut command, "q", &mut script);
+        let mut script = String::from_utf8(script).unwrap();
+        script.push_str(&dynamic_script(Shell::Bash, &command, "q"));
+        assert!(script.contains("_q() {"));
+        assert!(script.contains("--agent)\n            COMPREPLY=($(compgen -W \"$(q completions --values agent"));
+        assert!(script.ends_with("complete -F _q_dynamic -o bashdefault -o default q\n"));
+
+        let script = dynamic_script(Shell::Zsh, &command, "q");
+        assert!(script.contains("compadd -- ${(f)\"$(q completions --values model 2>/dev/null)\"}"));
+        assert!(script.ends_with("compdef _q_dynamic q\n"));
+
+        let script = dynamic_script(Shell::Fish, &command, "q");
+        assert!(script.contains(
+            "complete -c q -n \"__fish_q_using_subcommand chat\" -l agent -f -a \"(q completions --values agent 2>/dev/null)\"\n"
+        ));
+
+        assert!(dynamic_script(Shell::PowerShell, &command, "q").is_empty());
+    }
+
+    #[test]
+    fn test_parse() {
+        use clap::Parser;
+
+        use crate::util::CHAT_BINARY_NAME;
+
+        assert!(Cli::try_parse_from([CHAT_BINARY_NAME, "completions", "bash"]).is_ok());
+        assert!(Cli::try_parse_from([CHAT_BINARY_NAME, "completions", "--values", "agent"]).is_ok());
+        assert!(Cli::try_parse_from([CHAT_BINARY_NAME, "completions"]).is_err());
+        assert!(Cli::try_parse_from([CHAT_BINARY_NAME, "completions", "bash", "--values", "model"]).is_err());
+    }
+}
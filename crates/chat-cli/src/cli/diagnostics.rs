@@ -51,7 +51,7 @@ impl DiagnosticArgs {
             })?;
         }
 
-        let diagnostics = Diagnostics::new(&os.env).await;
+        let diagnostics = Diagnostics::new(&os.env, &os.database).await;
 
         if let Some(mut sp) = spinner {
             sp.stop();
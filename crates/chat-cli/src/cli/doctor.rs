@@ -0,0 +1,513 @@
+use std::io::{
+    IsTerminal,
+    stdout,
+};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::{
+    Duration,
+    Instant,
+};
+
+use chrono::{
+    TimeDelta,
+    Utc,
+};
+use clap::Args;
+use crossterm::{
+    queue,
+    style,
+};
+use eyre::Result;
+use serde::Serialize;
+
+use super::OutputFormat;
+use super::agent::{
+    Agents,
+    McpLaunch,
+    launch_mcp_server,
+};
+use crate::api_client::Endpoint;
+use crate::cli::chat::error_hints::ErrorCluster;
+use crate::database::settings::Setting;
+use crate::os::Os;
+use crate::os::diagnostics::Diagnostics;
+use crate::theme::StyledText;
+use crate::util::paths::{
+    GlobalPaths,
+    PathResolver,
+};
+
+/// Time given to the service to respond to the network check.
+const NETWORK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Time given to each MCP server to start and list its tools.
+const MCP_LAUNCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Errors seen this long ago or less are reported.
+const RECENT_ERRORS: TimeDelta = TimeDelta::days(7);
+
+#[derive(Debug, Clone, PartialEq, Eq, Args)]
+pub struct DoctorArgs {
+    /// Also write a diagnostics bundle with the results of the checks and the output of q
+    /// diagnostic to this file, with the home directory and user name redacted
+    #[arg(long, value_name = "PATH")]
+    bundle: Option<PathBuf>,
+    /// The format of the output
+    #[arg(long, short, value_enum, default_value_t)]
+    format: OutputFormat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Status {
+    Pass,
+    Warn,
+    Fail,
+}
+
+/// Result of one of the checks.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct Check {
+    name: &'static str,
+    status: Status,
+    detail: String,
+    /// What to do about a warning or a failure.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fix: Option<String>,
+}
+
+impl Check {
+    fn pass(name: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: Status::Pass,
+            detail: detail.into(),
+            fix: None,
+        }
+    }
+
+    fn warn(name: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: Status::Warn,
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+
+    fn fail(name: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: Status::Fail,
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Bundle<'a> {
+    checks: &'a [Check],
+    diagnostics: Diagnostics,
+}
+
+impl DoctorArgs {
+    pub async fn execute(&self, os: &mut Os) -> Result<ExitCode> {
+        let checks = vec![
+            check_auth(os).await,
+            check_network(os).await,
+            check_database(os),
+            check_mcp_servers(os).await,
+            check_shadow_repos(os).await,
+            check_terminal(os),
+            check_settings(os),
+            check_recent_errors(os),
+        ];
+
+        self.format.print(|| format_checks(&checks), || &checks);
+
+        if let Some(path) = &self.bundle {
+            let bundle = Bundle {
+                checks: &checks,
+                diagnostics: Diagnostics::new(&os.env, &os.database).await,
+            };
+            let contents = redact(os, &serde_json::to_string_pretty(&bundle)?);
+            os.fs.write(path, contents).await?;
+            eprintln!("Wrote the diagnostics bundle to {}", path.display());
+        }
+
+        Ok(match checks.iter().any(|check| check.status == Status::Fail) {
+            true => ExitCode::FAILURE,
+            false => ExitCode::SUCCESS,
+        })
+    }
+}
+
+fn format_checks(checks: &[Check]) -> String {
+    let mut out = Vec::new();
+    let name_width = checks.iter().map(|check| check.name.len()).max().unwrap_or_default();
+    for check in checks {
+        let (icon, color) = match check.status {
+            Status::Pass => ("✓", StyledText::success_fg()),
+            Status::Warn => ("!", StyledText::warning_fg()),
+            Status::Fail => ("✗", StyledText::error_fg()),
+        };
+        let _ = queue!(
+            out,
+            color,
+            style::Print(format!("{icon} ")),
+            StyledText::reset(),
+            style::Print(format!("{:<name_width$}  {}\n", check.name, check.detail)),
+        );
+        if let Some(fix) = &check.fix {
+            let _ = queue!(
+                out,
+                StyledText::secondary_fg(),
+                style::Print(format!("  {:<name_width$}  → {fix}\n", "")),
+                StyledText::reset(),
+            );
+        }
+    }
+    String::from_utf8_lossy(&out).trim_end().to_string()
+}
+
+async fn check_auth(os: &mut Os) -> Check {
+    const NAME: &str = "Authentication";
+    match crate::auth::is_logged_in(&mut os.database).await {
+        true => Check::pass(NAME, "Logged in"),
+        false => Check::fail(NAME, "Not logged in, or the login expired", "Log in with q login"),
+    }
+}
+
+async fn check_network(os: &Os) -> Check {
+    const NAME: &str = "Network";
+    let endpoint = Endpoint::configured_value(&os.database);
+    let client = match crate::request::new_client() {
+        Ok(client) => client,
+        Err(err) => {
+            return Check::fail(
+                NAME,
+                format!("Failed to create an HTTP client: {err}"),
+                "Report it with q issue",
+            );
+        },
+    };
+
+    let start = Instant::now();
+    // Any response means that the service can be reached
+    match client.get(endpoint.url()).timeout(NETWORK_TIMEOUT).send().await {
+        Ok(_) => Check::pass(
+            NAME,
+            format!("Reached {} in {}ms", endpoint.url(), start.elapsed().as_millis()),
+        ),
+        Err(err) => Check::fail(
+            NAME,
+            format!("Failed to reach {}: {}", endpoint.url(), error_chain(&err)),
+            "Check your network connection and proxy settings (HTTPS_PROXY, NO_PROXY)",
+        ),
+    }
+}
+
+/// `err` followed by its sources, which tell why a request failed.
+fn error_chain(err: &dyn std::error::Error) -> String {
+    let mut chain = err.to_string();
+    let mut source = err.source();
+    while let Some(err) = source {
+        chain.push_str(&format!(": {err}"));
+        source = err.source();
+    }
+    chain
+}
+
+fn check_database(os: &Os) -> Check {
+    const NAME: &str = "Database";
+    let path = GlobalPaths::database_path_static()
+        .map_or_else(|_| "the database".to_string(), |path| path.display().to_string());
+    match os.database.integrity_check() {
+        Ok(problems) if problems.is_empty() => Check::pass(NAME, "Passed the integrity check"),
+        Ok(problems) => Check::fail(
+            NAME,
+            format!("Failed the integrity check: {}", problems.join("; ")),
+            format!("Move {path} aside, it is recreated when q next starts and you will have to log in again"),
+        ),
+        Err(err) => Check::fail(
+            NAME,
+            format!("Failed to run the integrity check: {err}"),
+            format!("Check the permissions of {path}"),
+        ),
+    }
+}
+
+async fn check_mcp_servers(os: &mut Os) -> Check {
+    const NAME: &str = "MCP servers";
+    let (agents, _) = Agents::load(os, None, true, &mut std::io::sink(), true).await;
+    let mut servers = agents
+        .agents
+        .into_values()
+        .flat_map(|agent| agent.mcp_servers.mcp_servers)
+        .filter(|(_, config)| !config.disabled)
+        .collect::<Vec<_>>();
+    servers.sort_by(|a, b| a.0.cmp(&b.0));
+    servers.dedup_by(|a, b| a.0 == b.0);
+    if servers.is_empty() {
+        return Check::pass(NAME, "No MCP servers are configured");
+    }
+
+    let os = &*os;
+    let launches = servers
+        .iter()
+        .map(|(name, config)| async move { (name, launch_mcp_server(os, name, config, MCP_LAUNCH_TIMEOUT).await) });
+    let (mut problems, mut logins) = (Vec::new(), Vec::new());
+    for (name, launch) in futures::future::join_all(launches).await {
+        match launch {
+            McpLaunch::Launched(_) => (),
+            McpLaunch::NeedsLogin => logins.push(name.as_str()),
+            McpLaunch::Failed(err) => problems.push(format!("{name}: {err}")),
+        }
+    }
+    if !problems.is_empty() {
+        return Check::fail(
+            NAME,
+            problems.join("; "),
+            "Install the missing commands, or fix the servers in their agent configuration (q mcp list)",
+        );
+    }
+    match logins.is_empty() {
+        true => Check::pass(NAME, format!("The {} enabled servers can be launched", servers.len())),
+        false => Check::warn(
+            NAME,
+            format!("{} need you to log in", logins.join(", ")),
+            "Run q chat to log in",
+        ),
+    }
+}
+
+async fn check_shadow_repos(os: &Os) -> Check {
+    const NAME: &str = "Checkpoints";
+    let Ok(dir) = PathResolver::new(os).global().shadow_repo_dir() else {
+        return Check::warn(NAME, "No home directory", "Set HOME");
+    };
+    let dir = os.fs.chroot_path(dir);
+    let git = tokio::process::Command::new("git").arg("--version").output().await;
+    if !git.is_ok_and(|output| output.status.success()) {
+        return Check::warn(NAME, "git is not installed", "Install git to use checkpoints");
+    }
+    let Ok(mut entries) = tokio::fs::read_dir(&dir).await else {
+        return Check::pass(NAME, "No checkpoints were created");
+    };
+
+    let mut repos = 0;
+    let mut broken = Vec::new();
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        if !entry.file_type().await.is_ok_and(|file_type| file_type.is_dir()) {
+            continue;
+        }
+        repos += 1;
+        let output = tokio::process::Command::new("git")
+            .arg(format!("--git-dir={}", entry.path().display()))
+            .args(["rev-parse", "--is-bare-repository"])
+            .output()
+            .await;
+        if !output.is_ok_and(|output| output.status.success()) {
+            broken.push(entry.file_name().to_string_lossy().to_string());
+        }
+    }
+    match broken.is_empty() {
+        true => Check::pass(NAME, format!("{repos} checkpoint repositories are healthy")),
+        false => Check::warn(
+            NAME,
+            format!(
+                "{} of {repos} checkpoint repositories are broken: {}",
+                broken.len(),
+                broken.join(", ")
+            ),
+            format!("Delete them from {}", dir.display()),
+        ),
+    }
+}
+
+fn check_terminal(os: &Os) -> Check {
+    const NAME: &str = "Terminal";
+    if !stdout().is_terminal() {
+        return Check::pass(NAME, "Not a terminal, output is plain");
+    }
+
+    let term = os.env.get("TERM").unwrap_or_default();
+    let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .find_map(|var| os.env.get(var).ok().filter(|value| !value.is_empty()))
+        .unwrap_or_default();
+    let size = crossterm::terminal::size().ok();
+
+    let mut problems = Vec::new();
+    if term == "dumb" {
+        problems.push("TERM is dumb, colors and the prompt editor are limited".to_string());
+    }
+    if !cfg!(windows) && !locale.to_lowercase().replace('-', "").contains("utf8") {
+        problems.push(format!("the locale '{locale}' isn't UTF-8, symbols may not show"));
+    }
+    if let Some((width, _)) = size.filter(|(width, _)| *width < 60) {
+        problems.push(format!("the terminal is only {width} columns wide"));
+    }
+
+    let description = format!(
+        "TERM={}, {}",
+        if term.is_empty() { "unset" } else { &term },
+        size.map_or("unknown size".to_string(), |(width, height)| format!(
+            "{width}x{height}"
+        ))
+    );
+    match problems.is_empty() {
+        true => Check::pass(NAME, description),
+        false => Check::warn(
+            NAME,
+            format!("{description}: {}", problems.join("; ")),
+            "Use a terminal with a UTF-8 locale, e.g. export LANG=en_US.UTF-8",
+        ),
+    }
+}
+
+fn check_settings(os: &Os) -> Check {
+    const NAME: &str = "Settings";
+    let problems = settings_problems(os.database.settings.map());
+    match problems.is_empty() {
+        true => Check::pass(NAME, format!("{} settings are set", os.database.settings.map().len())),
+        false => Check::warn(NAME, problems.join("; "), "Fix them with q settings open"),
+    }
+}
+
+fn settings_problems(settings: &serde_json::Map<String, serde_json::Value>) -> Vec<String> {
+    let mut problems = Vec::new();
+    for (key, value) in settings {
+        match Setting::try_from(key.as_str()) {
+            Err(_) => problems.push(format!("unknown setting {key}")),
+            Ok(_) if value.is_null() => problems.push(format!("{key} is null")),
+            Ok(_) => (),
+        }
+    }
+    problems
+}
+
+fn check_recent_errors(os: &Os) -> Check {
+    let clusters = os.database.get_error_clusters().ok().flatten().unwrap_or_default();
+    recent_errors(&clusters, Utc::now())
+}
+
+fn recent_errors(clusters: &[ErrorCluster], now: chrono::DateTime<Utc>) -> Check {
+    const NAME: &str = "Recent errors";
+    let recent = clusters
+        .iter()
+        .filter(|cluster| now - cluster.last_seen <= RECENT_ERRORS)
+        .collect::<Vec<_>>();
+    if recent.is_empty() {
+        return Check::pass(NAME, "No errors in chat in the last 7 days");
+    }
+
+    let detail = recent
+        .iter()
+        .map(|cluster| match cluster.status_code {
+            Some(status) => format!("{} (HTTP {status}) x{}", cluster.reason_code, cluster.count),
+            None => format!("{} x{}", cluster.reason_code, cluster.count),
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    let mut hints = recent.iter().filter_map(|cluster| cluster.hint()).collect::<Vec<_>>();
+    hints.dedup();
+    match hints.is_empty() {
+        true => Check::pass(NAME, detail),
+        false => Check::warn(NAME, detail, hints.join(". ")),
+    }
+}
+
+/// Replaces the home directory and the user name in `text`.
+fn redact(os: &Os, text: &str) -> String {
+    let mut text = text.to_string();
+    if let Some(home) = os.env.home() {
+        let home = home.to_string_lossy();
+        if !home.is_empty() {
+            text = text.replace(home.as_ref(), "~");
+        }
+    }
+    for var in ["USER", "USERNAME"] {
+        if let Ok(user) = os.env.get(var) {
+            if user.len() > 2 {
+                text = text.replace(&user, "<user>");
+            }
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::chat::error_hints::KnownIssue;
+    use crate::cli::chat::tools::custom_tool::CustomToolConfig;
+    use crate::os::Env;
+
+    #[test]
+    fn test_settings_problems() {
+        let settings = serde_json::json!({
+            "chat.defaultModel": "claude-sonnet-4",
+            "chat.unknownSetting": true,
+            "chat.enableThinking": null,
+        });
+        let problems = settings_problems(settings.as_object().unwrap());
+        assert_eq!(problems, [
+            "unknown setting chat.unknownSetting",
+            "chat.enableThinking is null"
+        ]);
+    }
+
+    #[test]
+    fn test_recent_errors() {
+        let now = Utc::now();
+        let cluster = |reason_code: &str, issue, days| ErrorCluster {
+            reason_code: reason_code.to_string(),
+            status_code: None,
+            issue,
+            count: 3,
+            first_seen: now - TimeDelta::days(days),
+            last_seen: now - TimeDelta::days(days),
+        };
+        assert_eq!(recent_errors(&[], now).status, Status::Pass);
+        assert_eq!(
+            recent_errors(&[cluster("StdIoError", None, 30)], now).status,
+            Status::Pass
+        );
+
+        let check = recent_errors(
+            &[
+                cluster("RecvErrorStalled", Some(KnownIssue::Connectivity), 1),
+                cluster("StdIoError", None, 2),
+            ],
+            now,
+        );
+        assert_eq!(check.status, Status::Warn);
+        assert_eq!(check.detail, "RecvErrorStalled x3, StdIoError x3");
+        assert_eq!(check.fix.as_deref(), Some(KnownIssue::Connectivity.hint()));
+    }
+
+    #[tokio::test]
+    async fn test_redact() {
+        let mut os = Os::new().await.unwrap();
+        os.env = Env::from_slice(&[("HOME", "/home/jdoe"), ("USER", "jdoe")]);
+        assert_eq!(
+            redact(&os, r#"{"cwd": "/home/jdoe/src", "user": "jdoe"}"#),
+            r#"{"cwd": "~/src", "user": "<user>"}"#
+        );
+    }
+
+    #[tokio::test]
+    async fn test_launch_missing_mcp_server() {
+        let os = Os::new().await.unwrap();
+        let config =
+            serde_json::from_value::<CustomToolConfig>(serde_json::json!({ "command": "not-a-command-q-knows" }))
+                .unwrap();
+        assert!(matches!(
+            launch_mcp_server(&os, "missing", &config, MCP_LAUNCH_TIMEOUT).await,
+            McpLaunch::Failed(_)
+        ));
+    }
+}
@@ -14,6 +14,7 @@ pub enum ExperimentName {
     Checkpoint,
     ContextUsageIndicator,
     Delegate,
+    GitContext,
 }
 
 impl ExperimentName {
@@ -26,6 +27,7 @@ impl ExperimentName {
             Self::Checkpoint => "Checkpoint",
             Self::ContextUsageIndicator => "Context Usage Indicator",
             Self::Delegate => "Delegate",
+            Self::GitContext => "Git Context",
         }
     }
 }
@@ -115,6 +117,13 @@ static AVAILABLE_EXPERIMENTS: &[Experiment] = &[
         description: "Enables launching and managing asynchronous subagent processes",
         setting_key: Setting::EnabledDelegate,
         enabled: true,
+        commands: &["/agents", "/delegate map"],
+    },
+    Experiment {
+        experiment_name: ExperimentName::GitContext,
+        description: "Includes the current git branch, status and diff in the context of every turn",
+        setting_key: Setting::EnabledGitContext,
+        enabled: true,
         commands: &[],
     },
 ];
@@ -0,0 +1,271 @@
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use agent::hooks::{
+    HookDecision,
+    HookJsonOutput,
+};
+use anstream::println;
+use clap::Subcommand;
+use eyre::{
+    Result,
+    bail,
+};
+
+use crate::cli::agent::Agents;
+use crate::cli::agent::hook::HookTrigger;
+use crate::cli::chat::cli::hooks::{
+    HookExecutor,
+    NOTIFICATION_APPROVAL_NEEDED,
+    NOTIFICATION_TURN_COMPLETE,
+    ToolContext,
+    hook_event,
+    hook_matches_notification,
+    hook_matches_tool,
+};
+use crate::mcp_client::substitute_env_vars;
+use crate::os::Os;
+use crate::theme::StyledText;
+
+/// Prompt given to userPromptSubmit hooks unless one is given with `--prompt`
+const TEST_PROMPT: &str = "This is a test prompt";
+
+/// Hooks run commands at points of the lifecycle of an agent, as configured in the `hooks` field of
+/// its config.
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Eq, Subcommand)]
+pub enum HooksSubcommand {
+    /// Run the hooks of an agent for a synthetic event, showing the event given to each hook and
+    /// its result
+    Test {
+        /// Trigger of the hooks to run, e.g. preToolUse
+        #[arg(value_parser = parse_trigger)]
+        trigger: HookTrigger,
+        /// Agent whose hooks to run. Defaults to the default agent
+        #[arg(long)]
+        agent: Option<String>,
+        /// Name of the tool used, for preToolUse and postToolUse hooks
+        #[arg(long)]
+        tool: Option<String>,
+        /// JSON file with the input of the tool
+        #[arg(long, requires = "tool")]
+        input: Option<PathBuf>,
+        /// Prompt submitted, for userPromptSubmit hooks
+        #[arg(long)]
+        prompt: Option<String>,
+        /// Type of notification, for notification hooks: approval_needed (default) or
+        /// turn_complete
+        #[arg(long, value_parser = [NOTIFICATION_APPROVAL_NEEDED, NOTIFICATION_TURN_COMPLETE])]
+        notification: Option<String>,
+    },
+}
+
+impl HooksSubcommand {
+    pub async fn execute(self, os: &mut Os) -> Result<ExitCode> {
+        let Self::Test {
+            trigger,
+            agent,
+            tool,
+            input,
+            prompt,
+            notification,
+        } = self;
+
+        let tool_context = match (trigger, tool) {
+            (HookTrigger::PreToolUse | HookTrigger::PostToolUse, Some(tool_name)) => {
+                let tool_input = match &input {
+                    Some(path) => serde_json::from_str(&os.fs.read_to_string(path).await?)?,
+                    None => serde_json::json!({}),
+                };
+                Some(ToolContext {
+                    tool_name,
+                    tool_input,
+                    tool_response: (trigger == HookTrigger::PostToolUse)
+                        .then(|| serde_json::json!({ "success": true, "result": [] })),
+                })
+            },
+            (HookTrigger::PreToolUse | HookTrigger::PostToolUse, None) => {
+                bail!("--tool is required to test {trigger} hooks")
+            },
+            (_, Some(_)) => bail!("--tool only applies to preToolUse and postToolUse hooks"),
+            (_, None) => None,
+        };
+        let prompt = match trigger {
+            HookTrigger::UserPromptSubmit => Some(prompt.unwrap_or(TEST_PROMPT.to_string())),
+            _ => prompt,
+        };
+        let event_input = match (trigger, notification) {
+            (HookTrigger::Notification, notification) => Some(test_notification(
+                notification.as_deref().unwrap_or(NOTIFICATION_APPROVAL_NEEDED),
+            )),
+            (_, Some(_)) => bail!("--notification only applies to notification hooks"),
+            (_, None) => test_event_input(trigger),
+        };
+        let notification_type = event_input
+            .as_ref()
+            .and_then(|input| input["notification_type"].as_str());
+
+        let (agents, _) = Agents::load(os, agent.as_deref(), true, &mut std::io::stderr(), true).await;
+        let Some(agent) = agents.get_active() else {
+            bail!("No agent to test the hooks of");
+        };
+        let hooks = agent
+            .hooks
+            .get(&trigger)
+            .into_iter()
+            .flatten()
+            .filter(|hook| {
+                tool_context
+                    .as_ref()
+                    .is_none_or(|ctx| hook_matches_tool(hook, &ctx.tool_name, &ctx.tool_input))
+                    && notification_type.is_none_or(|kind| hook_matches_notification(hook, kind))
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        if hooks.is_empty() {
+            println!(
+                "The agent {} has no {trigger} hooks{}",
+                StyledText::brand(&agent.name),
+                match (&tool_context, notification_type) {
+                    (Some(ctx), _) => format!(" matching {}", ctx.tool_name),
+                    (None, Some(kind)) => format!(" matching {kind}"),
+                    (None, None) => String::new(),
+                }
+            );
+            return Ok(ExitCode::SUCCESS);
+        }
+
+        let cwd = os.env.current_dir()?.to_string_lossy().to_string();
+        let mut failed = false;
+        for mut hook in hooks {
+            for value in hook.env.values_mut() {
+                *value = substitute_env_vars(value, &os.env);
+            }
+            let event = hook_event(
+                trigger,
+                &cwd,
+                prompt.as_deref(),
+                tool_context.clone(),
+                event_input.as_ref(),
+            );
+
+            println!(
+                "\n{} {}{}",
+                StyledText::emphasis(&format!("{trigger} hook:")),
+                StyledText::command(&hook.command),
+                if hook.blocking { "" } else { " (non-blocking)" }
+            );
+            println!("{}", StyledText::secondary("Event given through STDIN:"));
+            println!("{}", serde_json::to_string_pretty(&event)?);
+
+            let (_, result, duration) = HookExecutor::run_hook(
+                (trigger, hook),
+                &cwd,
+                prompt.as_deref(),
+                tool_context.clone(),
+                event_input.as_ref(),
+            )
+            .await;
+            let (exit_code, output) = match result {
+                Ok(result) => result,
+                Err(err) => {
+                    failed = true;
+                    println!(
+                        "{}",
+                        StyledText::error(&format!("Failed after {:.2} s: {err}", duration.as_secs_f32()))
+                    );
+                    continue;
+                },
+            };
+
+            let exit = format!("Exit code {exit_code} after {:.2} s", duration.as_secs_f32());
+            match exit_code {
+                0 => println!("{}", StyledText::success(&exit)),
+                2 if trigger == HookTrigger::PreToolUse => {
+                    println!("{}", StyledText::warning(&format!("{exit}, blocking the tool")));
+                },
+                _ => {
+                    failed = true;
+                    println!("{}", StyledText::error(&exit));
+                },
+            }
+            let stream = if exit_code == 0 { "STDOUT" } else { "STDERR" };
+            match output.trim() {
+                "" => println!("{}", StyledText::secondary(&format!("No {stream} output"))),
+                output => println!("{}\n{output}", StyledText::secondary(&format!("{stream}:"))),
+            }
+            if exit_code == 0 {
+                print_decision(HookJsonOutput::parse(&output));
+            }
+        }
+
+        Ok(if failed { ExitCode::FAILURE } else { ExitCode::SUCCESS })
+    }
+}
+
+fn print_decision(json: Option<HookJsonOutput>) {
+    let Some(json) = json else {
+        println!(
+            "{}",
+            StyledText::secondary("No structured output, the exit code applies")
+        );
+        return;
+    };
+    let decision = match json.decision {
+        Some(HookDecision::Block) => StyledText::warning("block"),
+        Some(HookDecision::Allow) => StyledText::success("allow"),
+        None => StyledText::secondary("none"),
+    };
+    println!("Decision: {decision}");
+    if let Some(reason) = json.reason {
+        println!("Reason: {reason}");
+    }
+    if let Some(context) = json.additional_context {
+        println!("Additional context: {context}");
+    }
+}
+
+/// Fields added to the hook event of triggers that have some, see [HookExecutor::run_hooks].
+fn test_event_input(trigger: HookTrigger) -> Option<serde_json::Value> {
+    match trigger {
+        HookTrigger::PreCompact => Some(serde_json::json!({ "history_length": 0 })),
+        HookTrigger::SessionEnd => Some(serde_json::json!({
+            "conversation_id": uuid::Uuid::new_v4().to_string(),
+            "reason": "exit",
+        })),
+        HookTrigger::OnError => Some(serde_json::json!({
+            "error": {
+                "message": "This is a test error",
+                "reason": "TestError",
+                "reason_desc": null,
+                "status_code": null,
+            }
+        })),
+        _ => None,
+    }
+}
+
+/// Fields added to the hook event of [HookTrigger::Notification] hooks for the given type of
+/// notification.
+fn test_notification(notification_type: &str) -> serde_json::Value {
+    if notification_type == NOTIFICATION_TURN_COMPLETE {
+        serde_json::json!({
+            "notification_type": NOTIFICATION_TURN_COMPLETE,
+            "message": "Turn completed in 95 s with 3 tool uses",
+            "duration_seconds": 95,
+            "tool_uses": 3,
+            "summary": "This is a test summary",
+        })
+    } else {
+        serde_json::json!({
+            "notification_type": NOTIFICATION_APPROVAL_NEEDED,
+            "message": "execute_bash needs your approval",
+            "tool_name": "execute_bash",
+            "tool_input": { "command": "echo test" },
+        })
+    }
+}
+
+fn parse_trigger(trigger: &str) -> Result<HookTrigger, String> {
+    serde_json::from_value(serde_json::Value::String(trigger.to_string())).map_err(|err| err.to_string())
+}
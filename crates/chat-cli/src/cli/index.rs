@@ -0,0 +1,65 @@
+use std::process::ExitCode;
+use std::time::Instant;
+
+use anstream::println;
+use clap::Subcommand;
+use eyre::Result;
+
+use crate::cli::chat::workspace_index::{
+    build_index,
+    index_path,
+    load_index,
+};
+use crate::os::Os;
+use crate::theme::StyledText;
+
+/// The index of the current workspace is added to chat prompts once built: each prompt includes the
+/// files related to it, along with their symbols and imports.
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Eq, Subcommand)]
+pub enum IndexSubcommand {
+    /// Index the files, symbols and imports of the current workspace
+    Build,
+    /// Show the status of the index of the current workspace
+    Status,
+}
+
+impl IndexSubcommand {
+    pub async fn execute(self, os: &Os) -> Result<ExitCode> {
+        let path = index_path(os)?;
+        match self {
+            Self::Build => {
+                let start = Instant::now();
+                let index = build_index(os, &os.env.current_dir()?).await?;
+                if let Some(parent) = path.parent() {
+                    os.fs.create_dir_all(parent).await?;
+                }
+                os.fs.write(&path, serde_json::to_string(&index)?).await?;
+
+                let symbols = index.files.iter().map(|f| f.symbols.len()).sum::<usize>();
+                let imports = index.files.iter().map(|f| f.imports.len()).sum::<usize>();
+                println!(
+                    "Indexed {} files, {symbols} symbols and {imports} imports in {:.1}s to {}",
+                    index.files.len(),
+                    start.elapsed().as_secs_f64(),
+                    StyledText::brand(&path.display().to_string()),
+                );
+            },
+            Self::Status => match load_index(os).await {
+                Some(index) => println!(
+                    "{} files indexed on {} in {}\nRun {} after large changes to refresh it",
+                    index.files.len(),
+                    index.built_at.format("%Y-%m-%d %H:%M UTC"),
+                    path.display(),
+                    StyledText::command("q index build"),
+                ),
+                None => println!(
+                    "This workspace is not indexed. Run {} to index it",
+                    StyledText::command("q index build")
+                ),
+            },
+        }
+
+        Ok(ExitCode::SUCCESS)
+    }
+}
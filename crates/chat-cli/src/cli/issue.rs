@@ -1,15 +1,23 @@
 use std::process::ExitCode;
 
 use clap::Args;
-use eyre::Result;
+use eyre::{
+    Result,
+    bail,
+};
 
+use crate::cli::chat::util::issue::IssueCreator;
 use crate::os::Os;
+use crate::util::crash::latest_bundle;
 
 #[derive(Clone, Debug, Args, PartialEq, Eq)]
 pub struct IssueArgs {
     /// Force issue creation
     #[arg(long, short = 'f')]
     force: bool,
+    /// Report the last crash, with the details of its crash report
+    #[arg(long)]
+    crash: bool,
     /// Issue description
     description: Vec<String>,
 }
@@ -18,6 +26,26 @@ impl IssueArgs {
     pub async fn execute(&self, os: &Os) -> Result<ExitCode> {
         let joined_description = self.description.join(" ").trim().to_owned();
 
+        if self.crash {
+            let Some((path, bundle)) = latest_bundle()? else {
+                bail!("No crash report was found");
+            };
+            let _ = IssueCreator {
+                title: Some(match joined_description.len() {
+                    0 => bundle.issue_title(),
+                    _ => joined_description,
+                }),
+                expected_behavior: None,
+                actual_behavior: Some(bundle.issue_details(&path)),
+                steps_to_reproduce: None,
+                additional_environment: Some(bundle.issue_environment()),
+            }
+            .create_url(os)
+            .await;
+
+            return Ok(ExitCode::SUCCESS);
+        }
+
         let issue_title = match joined_description.len() {
             0 => dialoguer::Input::with_theme(&crate::util::dialoguer_theme())
                 .with_prompt("Issue Title")
@@ -25,7 +53,7 @@ impl IssueArgs {
             _ => joined_description,
         };
 
-        let _ = crate::cli::chat::util::issue::IssueCreator {
+        let _ = IssueCreator {
             title: Some(issue_title),
             expected_behavior: None,
             actual_behavior: None,
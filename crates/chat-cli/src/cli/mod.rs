@@ -5,13 +5,20 @@ use crate::util::env_var::{
 };
 mod agent;
 pub mod chat;
+mod completions;
 mod debug;
 mod diagnostics;
+mod doctor;
 pub mod experiment;
 pub mod feed;
+mod hooks;
+mod index;
 mod issue;
 mod mcp;
+mod review;
 mod settings;
+mod telemetry;
+mod usage;
 mod user;
 
 use std::fmt::Display;
@@ -26,7 +33,10 @@ pub use agent::{
     Agent,
     DEFAULT_AGENT_NAME,
 };
-use anstream::println;
+use anstream::{
+    eprintln,
+    println,
+};
 pub use chat::ConversationState;
 pub use chat::tools::todo::TodoListState;
 use clap::{
@@ -47,17 +57,31 @@ use tracing::{
     debug,
 };
 
-use crate::cli::chat::ChatArgs;
+use crate::agent::Backend;
+use crate::cli::chat::{
+    ChatArgs,
+    ChatExitCode,
+};
+use crate::cli::hooks::HooksSubcommand;
+use crate::cli::index::IndexSubcommand;
 use crate::cli::mcp::McpSubcommand;
+use crate::cli::review::ReviewArgs;
+use crate::cli::telemetry::TelemetrySubcommand;
+use crate::cli::usage::UsageSubcommand;
 use crate::cli::user::{
     LoginArgs,
     WhoamiArgs,
 };
+use crate::database::settings::Settings;
 use crate::logging::{
     LogArgs,
     initialize_logging,
 };
-use crate::os::Os;
+use crate::os::{
+    Env,
+    Os,
+};
+use crate::telemetry::otel::OtelConfig;
 use crate::util::paths::logs_dir;
 use crate::util::{
     CLI_BINARY_NAME,
@@ -93,6 +117,7 @@ impl OutputFormat {
 
 /// The Amazon Q CLI
 #[deny(missing_docs)]
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, PartialEq, Subcommand)]
 pub enum RootSubcommand {
     /// Manage agents
@@ -113,6 +138,9 @@ pub enum RootSubcommand {
     /// Run diagnostic tests
     #[command(alias("diagnostics"))]
     Diagnostic(diagnostics::DiagnosticArgs),
+    /// Check the login, network, database, MCP servers, checkpoints, terminal and settings,
+    /// printing what to do about the problems found
+    Doctor(doctor::DoctorArgs),
     /// Create a new Github issue
     Issue(issue::IssueArgs),
     /// Version
@@ -126,6 +154,24 @@ pub enum RootSubcommand {
     /// Model Context Protocol (MCP)
     #[command(subcommand)]
     Mcp(McpSubcommand),
+    /// Index the workspace to help chat find related files
+    #[command(subcommand)]
+    Index(IndexSubcommand),
+    /// Test the hooks of agents
+    #[command(subcommand)]
+    Hooks(HooksSubcommand),
+    /// Review a git diff, printing the findings as JSON or SARIF. Exits with 1 when a finding is at
+    /// least as severe as --fail-on
+    Review(ReviewArgs),
+    /// Export the telemetry events recorded locally with telemetry.localOnly, or show which fields
+    /// telemetry includes
+    #[command(subcommand)]
+    Telemetry(TelemetrySubcommand),
+    /// Report the conversations, tokens, estimated costs, tools and errors of the last days
+    #[command(subcommand)]
+    Usage(UsageSubcommand),
+    /// Print the completion script of a shell, e.g. `q completions zsh > ~/.zfunc/_q`
+    Completions(completions::CompletionsArgs),
 }
 
 impl RootSubcommand {
@@ -137,16 +183,27 @@ impl RootSubcommand {
     }
 
     pub fn requires_auth(&self) -> bool {
-        matches!(self, Self::Chat(_) | Self::Profile)
+        match self {
+            // The other backends are authenticated with their own credentials
+            Self::Chat(args) => args.backend.backend == Backend::Rts,
+            Self::Profile => true,
+            _ => false,
+        }
     }
 
     pub async fn execute(self, os: &mut Os) -> Result<ExitCode> {
         // Check for auth on subcommands that require it.
         if self.requires_auth() && !crate::auth::is_logged_in(&mut os.database).await {
-            bail!(
+            let message = format!(
                 "You are not logged in, please log in with {}",
                 StyledText::command(&format!("{CLI_BINARY_NAME} login"))
             );
+            // Chat has its own exit code for auth failures, see [ChatExitCode]
+            if matches!(self, Self::Chat(_)) {
+                eprintln!("{} {message}", StyledText::error("error:"));
+                return Ok(ChatExitCode::AuthFailure.into());
+            }
+            bail!(message);
         }
 
         // Daily heartbeat check
@@ -165,6 +222,7 @@ impl RootSubcommand {
         match self {
             Self::Agent(args) => args.execute(os).await,
             Self::Diagnostic(args) => args.execute(os).await,
+            Self::Doctor(args) => args.execute(os).await,
             Self::Login(args) => args.execute(os).await,
             Self::Logout => user::logout(os).await,
             Self::Whoami(args) => args.execute(os).await,
@@ -174,6 +232,12 @@ impl RootSubcommand {
             Self::Version { changelog } => Cli::print_version(changelog),
             Self::Chat(args) => args.execute(os).await,
             Self::Mcp(args) => args.execute(os, &mut std::io::stderr()).await,
+            Self::Index(subcommand) => subcommand.execute(os).await,
+            Self::Hooks(subcommand) => subcommand.execute(os).await,
+            Self::Review(args) => args.execute(os).await,
+            Self::Telemetry(subcommand) => subcommand.execute(os).await,
+            Self::Usage(subcommand) => subcommand.execute(os).await,
+            Self::Completions(args) => args.execute(os).await,
         }
     }
 }
@@ -195,9 +259,16 @@ impl Display for RootSubcommand {
             Self::Profile => "profile",
             Self::Settings(_) => "settings",
             Self::Diagnostic(_) => "diagnostic",
+            Self::Doctor(_) => "doctor",
             Self::Issue(_) => "issue",
             Self::Version { .. } => "version",
             Self::Mcp(_) => "mcp",
+            Self::Index(_) => "index",
+            Self::Hooks(_) => "hooks",
+            Self::Review(_) => "review",
+            Self::Telemetry(_) => "telemetry",
+            Self::Usage(_) => "usage",
+            Self::Completions(_) => "completions",
         };
 
         write!(f, "{name}")
@@ -241,6 +312,7 @@ impl Cli {
                 _ => None,
             },
             delete_old_log_file: false,
+            otel: OtelConfig::load(&Env::new(), &Settings::new().await.unwrap_or_default()),
         });
 
         // Check for region support.
@@ -253,6 +325,7 @@ impl Cli {
         debug!(command =? std::env::args().collect::<Vec<_>>(), "Command being ran");
 
         let mut os = Os::new().await?;
+        crate::theme::init(&os.database.settings);
         let result = subcommand.execute(&mut os).await;
 
         let telemetry_result = os.telemetry.finish().await;
@@ -341,6 +414,8 @@ impl Cli {
 
 #[cfg(test)]
 mod test {
+    use std::path::PathBuf;
+
     use chat::WrapMode::{
         Always,
         Auto,
@@ -348,6 +423,22 @@ mod test {
     };
 
     use super::*;
+    use crate::agent::BackendArgs;
+    use crate::cli::agent::hook::HookTrigger;
+    use crate::cli::chat::{
+        BatchArgs,
+        ChatSubcommand,
+        InputFormat,
+        ServeArgs,
+        WatchFilesArgs,
+    };
+    use crate::cli::review::{
+        Forge,
+        ReviewFormat,
+        Severity,
+    };
+    use crate::cli::telemetry::PolicySubcommand;
+    use crate::cli::usage::ReportFormat;
     use crate::util::CHAT_BINARY_NAME;
     use crate::util::test::assert_parse;
 
@@ -381,12 +472,26 @@ mod test {
             subcommand: Some(RootSubcommand::Chat(ChatArgs {
                 resume: false,
                 input: None,
+                input_format: InputFormat::Text,
                 agent: None,
                 model: None,
                 trust_all_tools: false,
                 trust_tools: None,
+                read_only: false,
+                plan: false,
                 no_interactive: false,
                 wrap: None,
+                accessible: false,
+                attach: vec![],
+                model_params: Default::default(),
+                backend: Default::default(),
+                max_cost: None,
+                max_turns: None,
+                max_tool_calls: None,
+                timeout: None,
+                response_schema: None,
+                approval_policy: None,
+                subcommand: None,
             })),
             verbose: 2,
             help_all: false,
@@ -421,12 +526,26 @@ mod test {
             RootSubcommand::Chat(ChatArgs {
                 resume: false,
                 input: None,
+                input_format: InputFormat::Text,
                 agent: Some("my-profile".to_string()),
                 model: None,
                 trust_all_tools: false,
                 trust_tools: None,
+                read_only: false,
+                plan: false,
                 no_interactive: false,
                 wrap: None,
+                accessible: false,
+                attach: vec![],
+                model_params: Default::default(),
+                backend: Default::default(),
+                max_cost: None,
+                max_turns: None,
+                max_tool_calls: None,
+                timeout: None,
+                response_schema: None,
+                approval_policy: None,
+                subcommand: None,
             })
         );
     }
@@ -438,12 +557,26 @@ mod test {
             RootSubcommand::Chat(ChatArgs {
                 resume: false,
                 input: Some("Hello".to_string()),
+                input_format: InputFormat::Text,
                 agent: Some("my-profile".to_string()),
                 model: None,
                 trust_all_tools: false,
                 trust_tools: None,
+                read_only: false,
+                plan: false,
                 no_interactive: false,
                 wrap: None,
+                accessible: false,
+                attach: vec![],
+                model_params: Default::default(),
+                backend: Default::default(),
+                max_cost: None,
+                max_turns: None,
+                max_tool_calls: None,
+                timeout: None,
+                response_schema: None,
+                approval_policy: None,
+                subcommand: None,
             })
         );
     }
@@ -455,12 +588,26 @@ mod test {
             RootSubcommand::Chat(ChatArgs {
                 resume: false,
                 input: None,
+                input_format: InputFormat::Text,
                 agent: Some("my-profile".to_string()),
                 model: None,
                 trust_all_tools: true,
                 trust_tools: None,
+                read_only: false,
+                plan: false,
                 no_interactive: false,
                 wrap: None,
+                accessible: false,
+                attach: vec![],
+                model_params: Default::default(),
+                backend: Default::default(),
+                max_cost: None,
+                max_turns: None,
+                max_tool_calls: None,
+                timeout: None,
+                response_schema: None,
+                approval_policy: None,
+                subcommand: None,
             })
         );
     }
@@ -472,12 +619,26 @@ mod test {
             RootSubcommand::Chat(ChatArgs {
                 resume: true,
                 input: None,
+                input_format: InputFormat::Text,
                 agent: None,
                 model: None,
                 trust_all_tools: false,
                 trust_tools: None,
+                read_only: false,
+                plan: false,
                 no_interactive: true,
                 wrap: None,
+                accessible: false,
+                attach: vec![],
+                model_params: Default::default(),
+                backend: Default::default(),
+                max_cost: None,
+                max_turns: None,
+                max_tool_calls: None,
+                timeout: None,
+                response_schema: None,
+                approval_policy: None,
+                subcommand: None,
             })
         );
         assert_parse!(
@@ -485,12 +646,26 @@ mod test {
             RootSubcommand::Chat(ChatArgs {
                 resume: true,
                 input: None,
+                input_format: InputFormat::Text,
                 agent: None,
                 model: None,
                 trust_all_tools: false,
                 trust_tools: None,
+                read_only: false,
+                plan: false,
                 no_interactive: true,
                 wrap: None,
+                accessible: false,
+                attach: vec![],
+                model_params: Default::default(),
+                backend: Default::default(),
+                max_cost: None,
+                max_turns: None,
+                max_tool_calls: None,
+                timeout: None,
+                response_schema: None,
+                approval_policy: None,
+                subcommand: None,
             })
         );
     }
@@ -502,12 +677,26 @@ mod test {
             RootSubcommand::Chat(ChatArgs {
                 resume: false,
                 input: None,
+                input_format: InputFormat::Text,
                 agent: None,
                 model: None,
                 trust_all_tools: true,
                 trust_tools: None,
+                read_only: false,
+                plan: false,
                 no_interactive: false,
                 wrap: None,
+                accessible: false,
+                attach: vec![],
+                model_params: Default::default(),
+                backend: Default::default(),
+                max_cost: None,
+                max_turns: None,
+                max_tool_calls: None,
+                timeout: None,
+                response_schema: None,
+                approval_policy: None,
+                subcommand: None,
             })
         );
     }
@@ -519,12 +708,26 @@ mod test {
             RootSubcommand::Chat(ChatArgs {
                 resume: false,
                 input: None,
+                input_format: InputFormat::Text,
                 agent: None,
                 model: None,
                 trust_all_tools: false,
                 trust_tools: Some(vec!["".to_string()]),
+                read_only: false,
+                plan: false,
                 no_interactive: false,
                 wrap: None,
+                accessible: false,
+                attach: vec![],
+                model_params: Default::default(),
+                backend: Default::default(),
+                max_cost: None,
+                max_turns: None,
+                max_tool_calls: None,
+                timeout: None,
+                response_schema: None,
+                approval_policy: None,
+                subcommand: None,
             })
         );
     }
@@ -536,12 +739,26 @@ mod test {
             RootSubcommand::Chat(ChatArgs {
                 resume: false,
                 input: None,
+                input_format: InputFormat::Text,
                 agent: None,
                 model: None,
                 trust_all_tools: false,
                 trust_tools: Some(vec!["fs_read".to_string(), "fs_write".to_string()]),
+                read_only: false,
+                plan: false,
                 no_interactive: false,
                 wrap: None,
+                accessible: false,
+                attach: vec![],
+                model_params: Default::default(),
+                backend: Default::default(),
+                max_cost: None,
+                max_turns: None,
+                max_tool_calls: None,
+                timeout: None,
+                response_schema: None,
+                approval_policy: None,
+                subcommand: None,
             })
         );
     }
@@ -553,12 +770,26 @@ mod test {
             RootSubcommand::Chat(ChatArgs {
                 resume: false,
                 input: None,
+                input_format: InputFormat::Text,
                 agent: None,
                 model: None,
                 trust_all_tools: false,
                 trust_tools: None,
+                read_only: false,
+                plan: false,
                 no_interactive: false,
                 wrap: Some(Never),
+                accessible: false,
+                attach: vec![],
+                model_params: Default::default(),
+                backend: Default::default(),
+                max_cost: None,
+                max_turns: None,
+                max_tool_calls: None,
+                timeout: None,
+                response_schema: None,
+                approval_policy: None,
+                subcommand: None,
             })
         );
         assert_parse!(
@@ -566,12 +797,26 @@ mod test {
             RootSubcommand::Chat(ChatArgs {
                 resume: false,
                 input: None,
+                input_format: InputFormat::Text,
                 agent: None,
                 model: None,
                 trust_all_tools: false,
                 trust_tools: None,
+                read_only: false,
+                plan: false,
                 no_interactive: false,
                 wrap: Some(Always),
+                accessible: false,
+                attach: vec![],
+                model_params: Default::default(),
+                backend: Default::default(),
+                max_cost: None,
+                max_turns: None,
+                max_tool_calls: None,
+                timeout: None,
+                response_schema: None,
+                approval_policy: None,
+                subcommand: None,
             })
         );
         assert_parse!(
@@ -579,13 +824,511 @@ mod test {
             RootSubcommand::Chat(ChatArgs {
                 resume: false,
                 input: None,
+                input_format: InputFormat::Text,
                 agent: None,
                 model: None,
                 trust_all_tools: false,
                 trust_tools: None,
+                read_only: false,
+                plan: false,
                 no_interactive: false,
                 wrap: Some(Auto),
+                accessible: false,
+                attach: vec![],
+                model_params: Default::default(),
+                backend: Default::default(),
+                max_cost: None,
+                max_turns: None,
+                max_tool_calls: None,
+                timeout: None,
+                response_schema: None,
+                approval_policy: None,
+                subcommand: None,
+            })
+        );
+    }
+
+    #[test]
+    fn test_chat_with_read_only() {
+        assert_parse!(
+            ["chat", "--read-only"],
+            RootSubcommand::Chat(ChatArgs {
+                resume: false,
+                input: None,
+                input_format: InputFormat::Text,
+                agent: None,
+                model: None,
+                trust_all_tools: false,
+                trust_tools: None,
+                read_only: true,
+                plan: false,
+                no_interactive: false,
+                wrap: None,
+                accessible: false,
+                attach: vec![],
+                model_params: Default::default(),
+                backend: Default::default(),
+                max_cost: None,
+                max_turns: None,
+                max_tool_calls: None,
+                timeout: None,
+                response_schema: None,
+                approval_policy: None,
+                subcommand: None,
+            })
+        );
+    }
+
+    #[test]
+    fn test_chat_with_plan() {
+        assert_parse!(
+            ["chat", "--plan"],
+            RootSubcommand::Chat(ChatArgs {
+                resume: false,
+                input: None,
+                input_format: InputFormat::Text,
+                agent: None,
+                model: None,
+                trust_all_tools: false,
+                trust_tools: None,
+                read_only: false,
+                plan: true,
+                no_interactive: false,
+                wrap: None,
+                accessible: false,
+                attach: vec![],
+                model_params: Default::default(),
+                backend: Default::default(),
+                max_cost: None,
+                max_turns: None,
+                max_tool_calls: None,
+                timeout: None,
+                response_schema: None,
+                approval_policy: None,
+                subcommand: None,
+            })
+        );
+    }
+
+    #[test]
+    fn test_chat_with_backend() {
+        let args = RootSubcommand::Chat(ChatArgs {
+            resume: false,
+            input: None,
+            input_format: InputFormat::Text,
+            agent: None,
+            model: Some("amazon.nova-pro-v1:0".to_string()),
+            trust_all_tools: false,
+            trust_tools: None,
+            read_only: false,
+            plan: false,
+            no_interactive: false,
+            wrap: None,
+            accessible: false,
+            attach: vec![],
+            model_params: Default::default(),
+            backend: BackendArgs {
+                backend: Backend::Bedrock,
+                endpoint: None,
+            },
+            max_cost: None,
+            max_turns: None,
+            max_tool_calls: None,
+            timeout: None,
+            response_schema: None,
+            approval_policy: None,
+            subcommand: None,
+        });
+        // Bedrock is authenticated with the AWS credentials instead of the Q login
+        assert!(!args.requires_auth());
+        assert!(RootSubcommand::Chat(ChatArgs::default()).requires_auth());
+        assert_parse!(
+            ["chat", "--backend", "bedrock", "--model", "amazon.nova-pro-v1:0"],
+            args
+        );
+    }
+
+    #[test]
+    fn test_chat_with_approval_policy() {
+        assert_parse!(
+            ["chat", "--no-interactive", "--approval-policy", "ci/policy.yaml"],
+            RootSubcommand::Chat(ChatArgs {
+                resume: false,
+                input: None,
+                input_format: InputFormat::Text,
+                agent: None,
+                model: None,
+                trust_all_tools: false,
+                trust_tools: None,
+                read_only: false,
+                plan: false,
+                no_interactive: true,
+                wrap: None,
+                accessible: false,
+                attach: vec![],
+                model_params: Default::default(),
+                backend: Default::default(),
+                max_cost: None,
+                max_turns: None,
+                max_tool_calls: None,
+                timeout: None,
+                response_schema: None,
+                approval_policy: Some(PathBuf::from("ci/policy.yaml")),
+                subcommand: None,
+            })
+        );
+    }
+
+    #[test]
+    fn test_chat_with_input_format() {
+        assert_parse!(
+            ["chat", "--no-interactive", "--input-format", "json"],
+            RootSubcommand::Chat(ChatArgs {
+                resume: false,
+                input: None,
+                input_format: InputFormat::Json,
+                agent: None,
+                model: None,
+                trust_all_tools: false,
+                trust_tools: None,
+                read_only: false,
+                plan: false,
+                no_interactive: true,
+                wrap: None,
+                accessible: false,
+                attach: vec![],
+                model_params: Default::default(),
+                backend: Default::default(),
+                max_cost: None,
+                max_turns: None,
+                max_tool_calls: None,
+                timeout: None,
+                response_schema: None,
+                approval_policy: None,
+                subcommand: None,
+            })
+        );
+        assert!(Cli::try_parse_from(["q", "chat", "--input-format", "json"]).is_err());
+        assert!(Cli::try_parse_from(["q", "chat", "--no-interactive", "--input-format", "json", "hi"]).is_err());
+    }
+
+    #[test]
+    fn test_chat_with_run_limits() {
+        assert_parse!(
+            [
+                "chat",
+                "--no-interactive",
+                "--max-turns",
+                "5",
+                "--max-tool-calls",
+                "20",
+                "--timeout",
+                "600",
+                "hi"
+            ],
+            RootSubcommand::Chat(ChatArgs {
+                resume: false,
+                input: Some("hi".to_string()),
+                input_format: InputFormat::Text,
+                agent: None,
+                model: None,
+                trust_all_tools: false,
+                trust_tools: None,
+                read_only: false,
+                plan: false,
+                no_interactive: true,
+                wrap: None,
+                accessible: false,
+                attach: vec![],
+                model_params: Default::default(),
+                backend: Default::default(),
+                max_cost: None,
+                max_turns: Some(5),
+                max_tool_calls: Some(20),
+                timeout: Some(600),
+                response_schema: None,
+                approval_policy: None,
+                subcommand: None,
+            })
+        );
+        assert!(Cli::try_parse_from(["q", "chat", "--max-turns", "5"]).is_err());
+        assert!(Cli::try_parse_from(["q", "chat", "--timeout", "600"]).is_err());
+    }
+
+    #[test]
+    fn test_chat_serve() {
+        assert_parse!(
+            ["chat", "--agent", "dev", "serve", "--socket", "/tmp/q.sock"],
+            RootSubcommand::Chat(ChatArgs {
+                resume: false,
+                input: None,
+                input_format: InputFormat::Text,
+                agent: Some("dev".to_string()),
+                model: None,
+                trust_all_tools: false,
+                trust_tools: None,
+                read_only: false,
+                plan: false,
+                no_interactive: false,
+                wrap: None,
+                accessible: false,
+                attach: vec![],
+                model_params: Default::default(),
+                backend: Default::default(),
+                max_cost: None,
+                max_turns: None,
+                max_tool_calls: None,
+                timeout: None,
+                response_schema: None,
+                approval_policy: None,
+                subcommand: Some(ChatSubcommand::Serve(ServeArgs {
+                    socket: Some(PathBuf::from("/tmp/q.sock")),
+                })),
+            })
+        );
+        // Sent as a prompt without a socket
+        let Some(RootSubcommand::Chat(args)) = Cli::try_parse_from(["q", "chat", "serve"]).unwrap().subcommand else {
+            panic!("q chat serve should parse to a chat");
+        };
+        assert_eq!(args.subcommand, Some(ChatSubcommand::Serve(ServeArgs { socket: None })));
+    }
+
+    #[test]
+    fn test_chat_batch() {
+        assert_parse!(
+            [
+                "chat",
+                "--trust-all-tools",
+                "batch",
+                "prompts.yaml",
+                "--output-dir",
+                "results",
+                "--fail-fast"
+            ],
+            RootSubcommand::Chat(ChatArgs {
+                resume: false,
+                input: None,
+                input_format: InputFormat::Text,
+                agent: None,
+                model: None,
+                trust_all_tools: true,
+                trust_tools: None,
+                read_only: false,
+                plan: false,
+                no_interactive: false,
+                wrap: None,
+                accessible: false,
+                attach: vec![],
+                model_params: Default::default(),
+                backend: Default::default(),
+                max_cost: None,
+                max_turns: None,
+                max_tool_calls: None,
+                timeout: None,
+                response_schema: None,
+                approval_policy: None,
+                subcommand: Some(ChatSubcommand::Batch(BatchArgs {
+                    file: PathBuf::from("prompts.yaml"),
+                    output_dir: PathBuf::from("results"),
+                    fail_fast: true,
+                })),
+            })
+        );
+        assert!(Cli::try_parse_from(["q", "chat", "batch"]).is_err());
+    }
+
+    #[test]
+    fn test_chat_watch_files() {
+        assert_parse!(
+            [
+                "chat",
+                "--trust-all-tools",
+                "watch-files",
+                "--glob",
+                "src/**/*.rs",
+                "--glob",
+                "tests/*.rs",
+                "--prompt-file",
+                "fix.md"
+            ],
+            RootSubcommand::Chat(ChatArgs {
+                resume: false,
+                input: None,
+                input_format: InputFormat::Text,
+                agent: None,
+                model: None,
+                trust_all_tools: true,
+                trust_tools: None,
+                read_only: false,
+                plan: false,
+                no_interactive: false,
+                wrap: None,
+                accessible: false,
+                attach: vec![],
+                model_params: Default::default(),
+                backend: Default::default(),
+                max_cost: None,
+                max_turns: None,
+                max_tool_calls: None,
+                timeout: None,
+                response_schema: None,
+                approval_policy: None,
+                subcommand: Some(ChatSubcommand::WatchFiles(WatchFilesArgs {
+                    globs: vec!["src/**/*.rs".to_string(), "tests/*.rs".to_string()],
+                    prompt_file: PathBuf::from("fix.md"),
+                    debounce_ms: 500,
+                })),
+            })
+        );
+        assert!(Cli::try_parse_from(["q", "chat", "watch-files", "--prompt-file", "fix.md"]).is_err());
+    }
+
+    #[test]
+    fn test_index() {
+        assert_parse!(["index", "build"], RootSubcommand::Index(IndexSubcommand::Build));
+        assert_parse!(["index", "status"], RootSubcommand::Index(IndexSubcommand::Status));
+    }
+
+    #[test]
+    fn test_hooks_test() {
+        assert_parse!(
+            [
+                "hooks",
+                "test",
+                "preToolUse",
+                "--tool",
+                "fs_write",
+                "--input",
+                "input.json"
+            ],
+            RootSubcommand::Hooks(HooksSubcommand::Test {
+                trigger: HookTrigger::PreToolUse,
+                agent: None,
+                tool: Some("fs_write".to_string()),
+                input: Some(std::path::PathBuf::from("input.json")),
+                prompt: None,
+                notification: None,
+            })
+        );
+        assert_parse!(
+            ["hooks", "test", "userPromptSubmit", "--agent", "dev"],
+            RootSubcommand::Hooks(HooksSubcommand::Test {
+                trigger: HookTrigger::UserPromptSubmit,
+                agent: Some("dev".to_string()),
+                tool: None,
+                input: None,
+                prompt: None,
+                notification: None,
+            })
+        );
+        assert_parse!(
+            ["hooks", "test", "notification", "--notification", "turn_complete"],
+            RootSubcommand::Hooks(HooksSubcommand::Test {
+                trigger: HookTrigger::Notification,
+                agent: None,
+                tool: None,
+                input: None,
+                prompt: None,
+                notification: Some("turn_complete".to_string()),
+            })
+        );
+        assert!(Cli::try_parse_from(["q", "hooks", "test", "beforeEverything"]).is_err());
+        assert!(Cli::try_parse_from(["q", "hooks", "test", "notification", "--notification", "idle"]).is_err());
+    }
+
+    #[test]
+    fn test_telemetry_export() {
+        assert_parse!(
+            ["telemetry", "export", "--since", "7d"],
+            RootSubcommand::Telemetry(TelemetrySubcommand::Export {
+                since: Some(std::time::Duration::from_secs(7 * 24 * 60 * 60)),
+                output: None,
+            })
+        );
+        assert!(Cli::try_parse_from(["q", "telemetry", "export", "--since", "a week"]).is_err());
+    }
+
+    #[test]
+    fn test_usage_report() {
+        assert_parse!(
+            ["usage", "report"],
+            RootSubcommand::Usage(UsageSubcommand::Report {
+                since: std::time::Duration::from_secs(7 * 24 * 60 * 60),
+                format: ReportFormat::Table,
+            })
+        );
+        assert_parse!(
+            ["usage", "report", "--since", "30d", "--format", "csv"],
+            RootSubcommand::Usage(UsageSubcommand::Report {
+                since: std::time::Duration::from_secs(30 * 24 * 60 * 60),
+                format: ReportFormat::Csv,
+            })
+        );
+    }
+
+    #[test]
+    fn test_telemetry_policy() {
+        assert_parse!(
+            ["telemetry", "policy", "show", "--format", "json"],
+            RootSubcommand::Telemetry(TelemetrySubcommand::Policy(PolicySubcommand::Show {
+                format: OutputFormat::Json,
+            }))
+        );
+    }
+
+    #[test]
+    fn test_review() {
+        assert_parse!(
+            ["review", "--staged"],
+            RootSubcommand::Review(ReviewArgs {
+                staged: true,
+                range: None,
+                format: ReviewFormat::Json,
+                fail_on: Severity::High,
+                agent: None,
+                model: None,
+                post: None,
+                repo: None,
+                pr: None,
+            })
+        );
+        assert_parse!(
+            [
+                "review",
+                "--range",
+                "main..HEAD",
+                "--format",
+                "sarif",
+                "--fail-on",
+                "medium"
+            ],
+            RootSubcommand::Review(ReviewArgs {
+                staged: false,
+                range: Some("main..HEAD".to_string()),
+                format: ReviewFormat::Sarif,
+                fail_on: Severity::Medium,
+                agent: None,
+                model: None,
+                post: None,
+                repo: None,
+                pr: None,
+            })
+        );
+        assert_parse!(
+            ["review", "--post", "gitlab", "--repo", "group/project", "--pr", "12"],
+            RootSubcommand::Review(ReviewArgs {
+                staged: false,
+                range: None,
+                format: ReviewFormat::Json,
+                fail_on: Severity::High,
+                agent: None,
+                model: None,
+                post: Some(Forge::Gitlab),
+                repo: Some("group/project".to_string()),
+                pr: Some(12),
             })
         );
+        assert!(Cli::try_parse_from(["q", "review", "--staged", "--range", "main..HEAD"]).is_err());
+        assert!(Cli::try_parse_from(["q", "review", "--pr", "12"]).is_err());
     }
 }
@@ -0,0 +1,458 @@
+//! Posting of review findings as comments on the pull request (GitHub) or merge request (GitLab)
+//! being reviewed, for `q review --post`. Findings already posted on the same line with the same
+//! text are skipped, so that a CI job reviewing every push doesn't repeat itself.
+
+use clap::ValueEnum;
+use percent_encoding::{
+    NON_ALPHANUMERIC,
+    utf8_percent_encode,
+};
+use reqwest::{
+    Client,
+    RequestBuilder,
+};
+use serde::Deserialize;
+use serde_json::{
+    Value,
+    json,
+};
+use thiserror::Error;
+
+use super::Finding;
+use crate::os::Env;
+
+/// Ends the comments posted for findings, to tell them apart from the comments of reviewers
+const COMMENT_MARKER: &str = "<!-- q-review -->";
+
+const GITHUB_API_URL: &str = "https://api.github.com";
+const GITLAB_API_URL: &str = "https://gitlab.com/api/v4";
+
+/// Number of comments requested per page when listing the existing ones
+const PAGE_SIZE: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Forge {
+    /// Pull request comments, with the token in GITHUB_TOKEN or GH_TOKEN
+    Github,
+    /// Merge request discussions, with the token in GITLAB_TOKEN
+    Gitlab,
+}
+
+#[derive(Debug, Error)]
+pub enum ForgeError {
+    #[error("Set {0} to post the findings")]
+    MissingToken(&'static str),
+    #[error("Set --repo to post the findings, or run in a {0} CI job")]
+    MissingRepo(&'static str),
+    #[error("Set --pr to post the findings, or run in a {0} CI job for a pull or merge request")]
+    MissingNumber(&'static str),
+    #[error(transparent)]
+    Request(#[from] reqwest::Error),
+    #[error("{url} answered with status {status}: {body}")]
+    Status { url: String, status: u16, body: String },
+}
+
+/// Pull or merge request to post the findings on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForgeTarget {
+    pub forge: Forge,
+    pub api_url: String,
+    /// `owner/name` on GitHub, the project id or path on GitLab
+    pub repo: String,
+    pub number: u64,
+    pub token: String,
+}
+
+/// Outcome of posting the findings
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct PostSummary {
+    pub posted: usize,
+    pub duplicates: usize,
+    /// Findings that couldn't be posted, usually as their line is not part of the diff, with the
+    /// error
+    pub failed: Vec<(Finding, String)>,
+}
+
+/// A comment already posted on a line
+#[derive(Debug, PartialEq, Eq)]
+struct LineComment {
+    path: String,
+    line: Option<u64>,
+    body: String,
+}
+
+impl ForgeTarget {
+    /// Resolves the target from the arguments, falling back to the variables set by GitHub Actions
+    /// and GitLab CI.
+    pub fn from_env(forge: Forge, env: &Env, repo: Option<String>, number: Option<u64>) -> Result<Self, ForgeError> {
+        match forge {
+            Forge::Github => Ok(Self {
+                forge,
+                api_url: env.get("GITHUB_API_URL").unwrap_or_else(|_| GITHUB_API_URL.to_string()),
+                repo: repo
+                    .or_else(|| env.get("GITHUB_REPOSITORY").ok())
+                    .ok_or(ForgeError::MissingRepo("GitHub Actions"))?,
+                number: number
+                    .or_else(|| env.get("GITHUB_REF").ok().and_then(|r| pull_number_of_ref(&r)))
+                    .ok_or(ForgeError::MissingNumber("GitHub Actions"))?,
+                token: env
+                    .get("GITHUB_TOKEN")
+                    .or_else(|_| env.get("GH_TOKEN"))
+                    .ok()
+                    .ok_or(ForgeError::MissingToken("GITHUB_TOKEN"))?,
+            }),
+            Forge::Gitlab => Ok(Self {
+                forge,
+                api_url: env.get("CI_API_V4_URL").unwrap_or_else(|_| GITLAB_API_URL.to_string()),
+                repo: repo
+                    .or_else(|| env.get("CI_PROJECT_ID").ok())
+                    .ok_or(ForgeError::MissingRepo("GitLab"))?,
+                number: number
+                    .or_else(|| env.get("CI_MERGE_REQUEST_IID").ok().and_then(|n| n.parse().ok()))
+                    .ok_or(ForgeError::MissingNumber("GitLab"))?,
+                token: env
+                    .get("GITLAB_TOKEN")
+                    .ok()
+                    .ok_or(ForgeError::MissingToken("GITLAB_TOKEN"))?,
+            }),
+        }
+    }
+
+    /// Posts a comment for each finding not posted yet.
+    pub async fn post_findings(&self, client: &Client, findings: &[Finding]) -> Result<PostSummary, ForgeError> {
+        let mut summary = PostSummary::default();
+        if findings.is_empty() {
+            return Ok(summary);
+        }
+
+        let position = self.position(client).await?;
+        let existing = self.existing_comments(client).await?;
+        for finding in findings {
+            let body = comment_body(finding);
+            let posted = existing
+                .iter()
+                .any(|c| c.path == finding.file && c.line == Some(finding.line) && c.body.trim() == body.trim());
+            if posted {
+                summary.duplicates += 1;
+                continue;
+            }
+
+            match self.post_comment(client, &position, finding, &body).await {
+                Ok(()) => summary.posted += 1,
+                Err(err) => summary.failed.push((finding.clone(), err.to_string())),
+            }
+        }
+        Ok(summary)
+    }
+
+    fn base_url(&self) -> String {
+        let api_url = self.api_url.trim_end_matches('/');
+        match self.forge {
+            Forge::Github => format!("{api_url}/repos/{}/pulls/{}", self.repo, self.number),
+            Forge::Gitlab => format!(
+                "{api_url}/projects/{}/merge_requests/{}",
+                utf8_percent_encode(&self.repo, NON_ALPHANUMERIC),
+                self.number
+            ),
+        }
+    }
+
+    fn authorize(&self, request: RequestBuilder) -> RequestBuilder {
+        match self.forge {
+            Forge::Github => request
+                .bearer_auth(&self.token)
+                .header("Accept", "application/vnd.github+json")
+                .header("X-GitHub-Api-Version", "2022-11-28"),
+            Forge::Gitlab => request.header("PRIVATE-TOKEN", &self.token),
+        }
+    }
+
+    async fn send(&self, request: RequestBuilder) -> Result<Value, ForgeError> {
+        let response = self.authorize(request).send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(ForgeError::Status {
+                url: response.url().to_string(),
+                status: status.as_u16(),
+                body: response.text().await.unwrap_or_default(),
+            });
+        }
+        Ok(response.json().await?)
+    }
+
+    /// Commits the comments are positioned against: the head commit on GitHub, the diff refs on
+    /// GitLab.
+    async fn position(&self, client: &Client) -> Result<Value, ForgeError> {
+        let pull = self.send(client.get(self.base_url())).await?;
+        Ok(match self.forge {
+            Forge::Github => json!({ "commit_id": pull["head"]["sha"] }),
+            Forge::Gitlab => pull["diff_refs"].clone(),
+        })
+    }
+
+    async fn existing_comments(&self, client: &Client) -> Result<Vec<LineComment>, ForgeError> {
+        let url = match self.forge {
+            Forge::Github => format!("{}/comments", self.base_url()),
+            Forge::Gitlab => format!("{}/discussions", self.base_url()),
+        };
+
+        let mut comments = Vec::new();
+        for page in 1.. {
+            let request = client
+                .get(&url)
+                .query(&[("per_page", PAGE_SIZE.to_string()), ("page", page.to_string())]);
+            let items = match self.send(request).await? {
+                Value::Array(items) => items,
+                _ => break,
+            };
+            for item in &items {
+                match self.forge {
+                    Forge::Github => comments.extend(LineComment::from_github(item)),
+                    Forge::Gitlab => comments.extend(
+                        item["notes"]
+                            .as_array()
+                            .into_iter()
+                            .flatten()
+                            .filter_map(LineComment::from_gitlab),
+                    ),
+                }
+            }
+            if items.len() < PAGE_SIZE {
+                break;
+            }
+        }
+        Ok(comments)
+    }
+
+    async fn post_comment(
+        &self,
+        client: &Client,
+        position: &Value,
+        finding: &Finding,
+        body: &str,
+    ) -> Result<(), ForgeError> {
+        let request = match self.forge {
+            Forge::Github => client.post(format!("{}/comments", self.base_url())).json(&json!({
+                "body": body,
+                "commit_id": position["commit_id"],
+                "path": finding.file,
+                "line": finding.line,
+                "side": "RIGHT",
+            })),
+            Forge::Gitlab => client.post(format!("{}/discussions", self.base_url())).json(&json!({
+                "body": body,
+                "position": {
+                    "position_type": "text",
+                    "base_sha": position["base_sha"],
+                    "start_sha": position["start_sha"],
+                    "head_sha": position["head_sha"],
+                    "new_path": finding.file,
+                    "new_line": finding.line,
+                },
+            })),
+        };
+        self.send(request).await.map(|_| ())
+    }
+}
+
+impl LineComment {
+    fn from_github(comment: &Value) -> Option<Self> {
+        #[derive(Deserialize)]
+        struct Comment {
+            path: String,
+            line: Option<u64>,
+            body: String,
+        }
+        let comment = Comment::deserialize(comment).ok()?;
+        Some(Self {
+            path: comment.path,
+            line: comment.line,
+            body: comment.body,
+        })
+    }
+
+    fn from_gitlab(note: &Value) -> Option<Self> {
+        Some(Self {
+            path: note["position"]["new_path"].as_str()?.to_string(),
+            line: note["position"]["new_line"].as_u64(),
+            body: note["body"].as_str()?.to_string(),
+        })
+    }
+}
+
+/// Number of the pull request of a GitHub Actions ref, e.g. `refs/pull/12/merge`
+fn pull_number_of_ref(git_ref: &str) -> Option<u64> {
+    git_ref.strip_prefix("refs/pull/")?.split('/').next()?.parse().ok()
+}
+
+fn comment_body(finding: &Finding) -> String {
+    let severity = finding.severity.to_possible_value().expect("no skipped variants");
+    let mut body = format!("**{}**: {}", severity.get_name(), finding.message);
+    if let Some(suggestion) = &finding.suggestion {
+        body.push_str("\n\n**Suggestion:** ");
+        body.push_str(suggestion);
+    }
+    body.push_str("\n\n");
+    body.push_str(COMMENT_MARKER);
+    body
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::review::Severity;
+
+    fn finding(line: u64, message: &str) -> Finding {
+        Finding {
+            file: "src/lib.rs".to_string(),
+            line,
+            severity: Severity::High,
+            message: message.to_string(),
+            suggestion: None,
+        }
+    }
+
+    #[test]
+    fn test_from_env() {
+        let env = Env::from_slice(&[
+            ("GITHUB_REPOSITORY", "octo/repo"),
+            ("GITHUB_REF", "refs/pull/42/merge"),
+            ("GITHUB_TOKEN", "secret"),
+        ]);
+        assert_eq!(
+            ForgeTarget::from_env(Forge::Github, &env, None, None).unwrap(),
+            ForgeTarget {
+                forge: Forge::Github,
+                api_url: GITHUB_API_URL.to_string(),
+                repo: "octo/repo".to_string(),
+                number: 42,
+                token: "secret".to_string(),
+            }
+        );
+        assert_eq!(
+            ForgeTarget::from_env(Forge::Github, &env, Some("other/repo".to_string()), Some(7))
+                .unwrap()
+                .number,
+            7
+        );
+        assert!(matches!(
+            ForgeTarget::from_env(Forge::Gitlab, &env, None, None),
+            Err(ForgeError::MissingRepo(_))
+        ));
+
+        let env = Env::from_slice(&[("GITHUB_REPOSITORY", "octo/repo"), ("GITHUB_REF", "refs/heads/main")]);
+        assert!(matches!(
+            ForgeTarget::from_env(Forge::Github, &env, None, None),
+            Err(ForgeError::MissingNumber(_))
+        ));
+
+        let env = Env::from_slice(&[
+            ("CI_API_V4_URL", "https://gitlab.example.com/api/v4"),
+            ("CI_PROJECT_ID", "12"),
+            ("CI_MERGE_REQUEST_IID", "3"),
+        ]);
+        assert!(matches!(
+            ForgeTarget::from_env(Forge::Gitlab, &env, None, None),
+            Err(ForgeError::MissingToken("GITLAB_TOKEN"))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_post_findings_github() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/repos/octo/repo/pulls/1")
+            .match_header("authorization", "Bearer secret")
+            .with_body(r#"{"head": {"sha": "abc"}}"#)
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/repos/octo/repo/pulls/1/comments")
+            .match_query(mockito::Matcher::Any)
+            .with_body(
+                json!([{ "path": "src/lib.rs", "line": 1, "body": comment_body(&finding(1, "old")) }]).to_string(),
+            )
+            .create_async()
+            .await;
+        let post = server
+            .mock("POST", "/repos/octo/repo/pulls/1/comments")
+            .match_body(mockito::Matcher::PartialJson(json!({
+                "commit_id": "abc",
+                "path": "src/lib.rs",
+                "line": 2,
+                "side": "RIGHT",
+            })))
+            .with_status(201)
+            .with_body("{}")
+            .expect(1)
+            .create_async()
+            .await;
+
+        let target = ForgeTarget {
+            forge: Forge::Github,
+            api_url: server.url(),
+            repo: "octo/repo".to_string(),
+            number: 1,
+            token: "secret".to_string(),
+        };
+        let summary = target
+            .post_findings(&Client::new(), &[finding(1, "old"), finding(2, "new")])
+            .await
+            .unwrap();
+
+        post.assert_async().await;
+        assert_eq!(summary, PostSummary {
+            posted: 1,
+            duplicates: 1,
+            failed: vec![],
+        });
+    }
+
+    #[tokio::test]
+    async fn test_post_findings_gitlab() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/projects/group%2Fproject/merge_requests/5")
+            .match_header("private-token", "secret")
+            .with_body(r#"{"diff_refs": {"base_sha": "a", "start_sha": "b", "head_sha": "c"}}"#)
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/projects/group%2Fproject/merge_requests/5/discussions")
+            .match_query(mockito::Matcher::Any)
+            .with_body("[]")
+            .create_async()
+            .await;
+        server
+            .mock("POST", "/projects/group%2Fproject/merge_requests/5/discussions")
+            .match_body(mockito::Matcher::PartialJson(json!({
+                "position": { "head_sha": "c", "new_path": "src/lib.rs", "new_line": 9 },
+            })))
+            .with_status(400)
+            .with_body(r#"{"message": "line_code can't be blank"}"#)
+            .create_async()
+            .await;
+
+        let target = ForgeTarget {
+            forge: Forge::Gitlab,
+            api_url: server.url(),
+            repo: "group/project".to_string(),
+            number: 5,
+            token: "secret".to_string(),
+        };
+        let summary = target
+            .post_findings(&Client::new(), &[finding(9, "bug")])
+            .await
+            .unwrap();
+
+        assert_eq!(summary.posted, 0);
+        assert_eq!(summary.failed.len(), 1);
+        assert!(summary.failed[0].1.contains("400"), "{}", summary.failed[0].1);
+    }
+
+    #[test]
+    fn test_pull_number_of_ref() {
+        assert_eq!(pull_number_of_ref("refs/pull/12/merge"), Some(12));
+        assert_eq!(pull_number_of_ref("refs/heads/main"), None);
+    }
+}
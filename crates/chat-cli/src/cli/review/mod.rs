@@ -0,0 +1,359 @@
+//! `q review`, which reviews a git diff with `q chat --no-interactive` and outputs the findings as
+//! JSON or SARIF, exiting with 1 when a finding is at least as severe as `--fail-on` so that
+//! pre-commit hooks and CI jobs can gate on it.
+
+mod forge;
+mod sarif;
+
+use std::io::Write as _;
+use std::process::{
+    ExitCode,
+    Stdio,
+};
+
+use anstream::{
+    eprintln,
+    println,
+};
+use clap::{
+    Args,
+    ValueEnum,
+};
+use eyre::{
+    Result,
+    bail,
+};
+pub use forge::Forge;
+use forge::ForgeTarget;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use tokio::io::AsyncWriteExt;
+
+use crate::cli::chat::ChatExitCode;
+use crate::cli::chat::util::truncate_safe;
+use crate::os::Os;
+use crate::theme::StyledText;
+
+/// Largest diff, in bytes, given to the model. Larger diffs are truncated.
+const MAX_DIFF_BYTES: usize = 200_000;
+
+/// JSON Schema the answer of the review must match
+const FINDINGS_SCHEMA: &str = r#"{
+    "type": "object",
+    "properties": {
+        "findings": {
+            "type": "array",
+            "items": {
+                "type": "object",
+                "properties": {
+                    "file": { "type": "string" },
+                    "line": { "type": "integer", "minimum": 1 },
+                    "severity": { "enum": ["info", "low", "medium", "high", "critical"] },
+                    "message": { "type": "string" },
+                    "suggestion": { "type": "string" }
+                },
+                "required": ["file", "line", "severity", "message"]
+            }
+        }
+    },
+    "required": ["findings"]
+}"#;
+
+#[derive(Debug, Clone, PartialEq, Eq, Args)]
+pub struct ReviewArgs {
+    /// Reviews the staged changes instead of all the changes of the working tree
+    #[arg(long, conflicts_with = "range")]
+    pub staged: bool,
+    /// Reviews the changes of a range of commits, e.g. main..HEAD
+    #[arg(long, value_name = "A..B")]
+    pub range: Option<String>,
+    /// Format of the findings printed to stdout
+    #[arg(long, value_enum, default_value_t)]
+    pub format: ReviewFormat,
+    /// Exits with 1 when a finding is at least this severe
+    #[arg(long, value_enum, value_name = "SEVERITY", default_value_t = Severity::High)]
+    pub fail_on: Severity,
+    /// Agent reviewing the changes. Defaults to the default agent
+    #[arg(long)]
+    pub agent: Option<String>,
+    /// Model reviewing the changes
+    #[arg(long)]
+    pub model: Option<String>,
+    /// Posts the findings as comments on the pull request (github) or merge request (gitlab),
+    /// skipping those already posted
+    #[arg(long, value_enum, value_name = "FORGE")]
+    pub post: Option<Forge>,
+    /// Repository to post to: owner/name on GitHub, the project id or path on GitLab. Defaults to
+    /// the repository of the CI job
+    #[arg(long, requires = "post")]
+    pub repo: Option<String>,
+    /// Pull or merge request to post to. Defaults to the one of the CI job
+    #[arg(long, value_name = "NUMBER", requires = "post")]
+    pub pr: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ReviewFormat {
+    /// `{"findings": [...]}`
+    #[default]
+    Json,
+    /// SARIF 2.1.0, as read by code scanning tools
+    Sarif,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Finding {
+    pub file: String,
+    /// Line of the file after the changes
+    pub line: u64,
+    pub severity: Severity,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub suggestion: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Review {
+    pub findings: Vec<Finding>,
+}
+
+impl ReviewArgs {
+    pub async fn execute(self, os: &Os) -> Result<ExitCode> {
+        let diff = self.diff().await?;
+        let review = if diff.trim().is_empty() {
+            eprintln!("{}", StyledText::secondary("No changes to review"));
+            Review::default()
+        } else {
+            match self.run_review(os, &diff).await? {
+                Ok(review) => review,
+                Err(exit_code) => return Ok(exit_code),
+            }
+        };
+
+        match self.format {
+            ReviewFormat::Json => println!("{}", serde_json::to_string_pretty(&review)?),
+            ReviewFormat::Sarif => println!("{}", serde_json::to_string_pretty(&sarif::to_sarif(&review))?),
+        }
+
+        if let Some(forge) = self.post {
+            let target = ForgeTarget::from_env(forge, &os.env, self.repo.clone(), self.pr)?;
+            let summary = target
+                .post_findings(&crate::request::new_client()?, &review.findings)
+                .await?;
+            eprintln!(
+                "{}",
+                StyledText::secondary(&format!(
+                    "Posted {} finding(s) to #{}, {} already posted",
+                    summary.posted, target.number, summary.duplicates
+                ))
+            );
+            for (finding, err) in &summary.failed {
+                eprintln!(
+                    "{} Failed to post the finding on {}:{}: {err}",
+                    StyledText::warning("warning:"),
+                    finding.file,
+                    finding.line
+                );
+            }
+        }
+
+        let failing = review.findings.iter().filter(|f| f.severity >= self.fail_on).count();
+        if failing > 0 {
+            eprintln!(
+                "{} {failing} finding(s) are at least of {} severity",
+                StyledText::error("error:"),
+                self.fail_on
+                    .to_possible_value()
+                    .expect("no skipped variants")
+                    .get_name()
+            );
+            return Ok(ExitCode::FAILURE);
+        }
+        Ok(ExitCode::SUCCESS)
+    }
+
+    /// Arguments of `git diff` selecting the changes to review
+    fn diff_args(&self) -> Vec<String> {
+        let mut args = vec![
+            "diff".to_string(),
+            "--no-color".to_string(),
+            "--no-ext-diff".to_string(),
+        ];
+        match (&self.range, self.staged) {
+            (Some(range), _) => args.push(range.clone()),
+            (None, true) => args.push("--cached".to_string()),
+            (None, false) => args.push("HEAD".to_string()),
+        }
+        args
+    }
+
+    async fn diff(&self) -> Result<String> {
+        let output = tokio::process::Command::new("git")
+            .args(self.diff_args())
+            .stdin(Stdio::null())
+            .output()
+            .await?;
+        if !output.status.success() {
+            bail!("git diff failed: {}", String::from_utf8_lossy(&output.stderr).trim());
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    /// Runs `q chat --no-interactive` on the diff, returning the exit code of the review as an
+    /// error if it didn't give findings.
+    async fn run_review(&self, os: &Os, diff: &str) -> Result<Result<Review, ExitCode>> {
+        let mut schema = tempfile::NamedTempFile::new()?;
+        schema.write_all(FINDINGS_SCHEMA.as_bytes())?;
+
+        let mut args = vec![
+            "chat".to_string(),
+            "--no-interactive".to_string(),
+            // Reviews can read the repository to understand the changes, but not modify it
+            "--trust-all-tools".to_string(),
+            "--read-only".to_string(),
+            format!("--response-schema={}", schema.path().display()),
+        ];
+        if let Some(agent) = &self.agent {
+            args.push(format!("--agent={agent}"));
+        }
+        if let Some(model) = &self.model {
+            args.push(format!("--model={model}"));
+        }
+
+        let mut child = tokio::process::Command::new(os.env.current_exe()?)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(review_prompt(diff).as_bytes()).await?;
+        }
+        let output = child.wait_with_output().await?;
+        if !output.status.success() {
+            return Ok(Err(ExitCode::from(output.status.code().unwrap_or(1) as u8)));
+        }
+
+        match parse_review(&String::from_utf8_lossy(&output.stdout)) {
+            Some(review) => Ok(Ok(review)),
+            None => {
+                eprintln!("{} the review did not give findings", StyledText::error("error:"));
+                Ok(Err(ChatExitCode::ModelError.into()))
+            },
+        }
+    }
+}
+
+fn review_prompt(diff: &str) -> String {
+    let truncated = truncate_safe(diff, MAX_DIFF_BYTES);
+    let mut prompt = String::from(
+        "Review the following git diff as a senior engineer would before it is merged. Look for bugs, security \
+        issues, performance problems and unclear code in the changed lines; read the surrounding files if you \
+        need more context. Report each problem as a finding with the file path as shown in the diff, the line \
+        number in the file after the changes, a severity (info, low, medium, high or critical), a message \
+        describing the problem and, when you can, a suggestion to fix it. Answer with no findings if the changes \
+        look good.\n\n```diff\n",
+    );
+    prompt.push_str(truncated);
+    prompt.push_str("\n```");
+    if truncated.len() < diff.len() {
+        prompt.push_str("\n\nThe diff was truncated as it is too large. Only review the part shown.");
+    }
+    prompt
+}
+
+/// Finds the findings in the output of `q chat`, which is the last JSON document printed as the
+/// answer may follow text printed while the model used tools.
+fn parse_review(output: &str) -> Option<Review> {
+    let mut review = None;
+    let mut offset = 0;
+    for line in output.split_inclusive('\n') {
+        if line.trim_start().starts_with('{') {
+            let mut stream = serde_json::Deserializer::from_str(&output[offset..]).into_iter::<Review>();
+            if let Some(Ok(parsed)) = stream.next() {
+                review = Some(parsed);
+            }
+        }
+        offset += line.len();
+    }
+    review
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args() -> ReviewArgs {
+        ReviewArgs {
+            staged: false,
+            range: None,
+            format: ReviewFormat::Json,
+            fail_on: Severity::High,
+            agent: None,
+            model: None,
+            post: None,
+            repo: None,
+            pr: None,
+        }
+    }
+
+    #[test]
+    fn test_diff_args() {
+        assert_eq!(args().diff_args(), vec!["diff", "--no-color", "--no-ext-diff", "HEAD"]);
+        assert_eq!(ReviewArgs { staged: true, ..args() }.diff_args(), vec![
+            "diff",
+            "--no-color",
+            "--no-ext-diff",
+            "--cached"
+        ]);
+        assert_eq!(
+            ReviewArgs {
+                range: Some("main..HEAD".to_string()),
+                ..args()
+            }
+            .diff_args(),
+            vec!["diff", "--no-color", "--no-ext-diff", "main..HEAD"]
+        );
+    }
+
+    #[test]
+    fn test_parse_review() {
+        let output = r#"I'll read the file first.
+Reading file: src/lib.rs
+{"findings": [{"file": "src/lib.rs", "line": 3, "severity": "high", "message": "Panics on empty input", "suggestion": "Return an error"}]}
+"#;
+        assert_eq!(
+            parse_review(output),
+            Some(Review {
+                findings: vec![Finding {
+                    file: "src/lib.rs".to_string(),
+                    line: 3,
+                    severity: Severity::High,
+                    message: "Panics on empty input".to_string(),
+                    suggestion: Some("Return an error".to_string()),
+                }]
+            })
+        );
+        assert_eq!(parse_review("{\n  \"findings\": []\n}\n"), Some(Review::default()));
+        assert_eq!(parse_review("Looks good to me"), None);
+    }
+
+    #[test]
+    fn test_severity_order() {
+        assert!(Severity::Critical > Severity::High);
+        assert!(Severity::Medium >= Severity::Medium);
+        assert!(Severity::Info < Severity::Low);
+    }
+}
@@ -0,0 +1,98 @@
+//! Conversion of review findings to [SARIF 2.1.0](https://docs.oasis-open.org/sarif/sarif/v2.1.0/sarif-v2.1.0.html).
+
+use serde_json::{
+    Value,
+    json,
+};
+
+use super::{
+    Review,
+    Severity,
+};
+
+const RULE_ID: &str = "q-review";
+
+pub fn to_sarif(review: &Review) -> Value {
+    let results = review
+        .findings
+        .iter()
+        .map(|finding| {
+            let mut text = finding.message.clone();
+            if let Some(suggestion) = &finding.suggestion {
+                text.push_str("\n\nSuggestion: ");
+                text.push_str(suggestion);
+            }
+            json!({
+                "ruleId": RULE_ID,
+                "level": level(finding.severity),
+                "message": { "text": text },
+                "locations": [{
+                    "physicalLocation": {
+                        "artifactLocation": { "uri": finding.file },
+                        "region": { "startLine": finding.line },
+                    }
+                }],
+                "properties": { "severity": finding.severity },
+            })
+        })
+        .collect::<Vec<_>>();
+
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "Amazon Q Developer CLI",
+                    "informationUri": "https://github.com/aws/amazon-q-developer-cli",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": [{
+                        "id": RULE_ID,
+                        "shortDescription": { "text": "Code review finding" },
+                    }],
+                }
+            },
+            "results": results,
+        }],
+    })
+}
+
+fn level(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Info | Severity::Low => "note",
+        Severity::Medium => "warning",
+        Severity::High | Severity::Critical => "error",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::review::Finding;
+
+    #[test]
+    fn test_to_sarif() {
+        let sarif = to_sarif(&Review {
+            findings: vec![Finding {
+                file: "src/main.rs".to_string(),
+                line: 12,
+                severity: Severity::Medium,
+                message: "Unchecked unwrap".to_string(),
+                suggestion: Some("Propagate the error with ?".to_string()),
+            }],
+        });
+
+        let result = &sarif["runs"][0]["results"][0];
+        assert_eq!(result["level"], "warning");
+        assert_eq!(
+            result["message"]["text"],
+            "Unchecked unwrap\n\nSuggestion: Propagate the error with ?"
+        );
+        assert_eq!(
+            result["locations"][0]["physicalLocation"]["artifactLocation"]["uri"],
+            "src/main.rs"
+        );
+        assert_eq!(result["locations"][0]["physicalLocation"]["region"]["startLine"], 12);
+        assert_eq!(result["properties"]["severity"], "medium");
+    }
+}
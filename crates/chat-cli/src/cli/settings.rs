@@ -18,6 +18,7 @@ use strum::IntoEnumIterator;
 use super::OutputFormat;
 use crate::database::settings::Setting;
 use crate::os::Os;
+use crate::theme::StyledText;
 use crate::util::paths::GlobalPaths;
 
 #[derive(Clone, Debug, Subcommand, PartialEq, Eq)]
@@ -137,10 +138,10 @@ fn collect_settings(os: &Os) -> Vec<SettingInfo> {
 /// Print settings list in plain text format with colors
 fn print_settings_plain(settings: &[SettingInfo]) {
     for setting in settings {
-        println!("{}", setting.key.as_str().cyan().bold());
+        println!("{}", StyledText::brand(setting.key.as_str()).bold());
         println!("  Description: {}", setting.description);
         match &setting.current_value {
-            Some(value) => println!("  Current: {}", value.to_string().green()),
+            Some(value) => println!("  Current: {}", StyledText::success(&value.to_string())),
             None => println!("  Current: {}", "not set".dim()),
         }
         println!();
@@ -218,10 +219,10 @@ impl SettingsArgs {
                     if self.delete {
                         return Err(eyre::eyre!(
                             "the argument {} requires a {}\n Usage: q settings {} {}",
-                            "'--delete'".yellow(),
-                            "<KEY>".green(),
-                            "--delete".yellow(),
-                            "<KEY>".green()
+                            StyledText::warning("'--delete'"),
+                            StyledText::command("<KEY>"),
+                            StyledText::warning("--delete"),
+                            StyledText::command("<KEY>")
                         ));
                     }
                     return Ok(ExitCode::SUCCESS);
@@ -231,9 +232,9 @@ impl SettingsArgs {
                 match (&self.value, self.delete) {
                     (Some(_), true) => Err(eyre::eyre!(
                         "the argument {} cannot be used with {}\n Usage: q settings {} {key}",
-                        "'--delete'".yellow(),
-                        "'[VALUE]'".yellow(),
-                        "--delete".yellow()
+                        StyledText::warning("'--delete'"),
+                        StyledText::warning("'[VALUE]'"),
+                        StyledText::warning("--delete")
                     )),
                     (None, false) => match os.database.settings.get(key) {
                         Some(value) => {
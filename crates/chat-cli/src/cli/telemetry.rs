@@ -0,0 +1,157 @@
+use std::io::Write as _;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::Duration;
+
+use anstream::eprintln;
+use chrono::{
+    DateTime,
+    TimeDelta,
+    Utc,
+};
+use clap::Subcommand;
+use eyre::Result;
+use serde::Serialize;
+
+use super::OutputFormat;
+use crate::database::settings::Setting;
+use crate::os::Os;
+use crate::telemetry::local::{
+    events_since,
+    parse_period,
+    read_events,
+};
+use crate::telemetry::policy::{
+    PromptPolicy,
+    TelemetryMode,
+    TelemetryPolicy,
+};
+use crate::theme::StyledText;
+use crate::util::paths::GlobalPaths;
+
+/// With `telemetry.localOnly`, telemetry events are recorded on this machine only, never sent.
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Eq, Subcommand)]
+pub enum TelemetrySubcommand {
+    /// Print the telemetry events recorded locally, one JSON object per line
+    Export {
+        /// Only export the events of this last period, e.g. 7d, 12h or 30m
+        #[arg(long, value_parser = parse_period)]
+        since: Option<Duration>,
+        /// Write the events to this file rather than to stdout
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Show which fields telemetry events include
+    #[command(subcommand)]
+    Policy(PolicySubcommand),
+}
+
+impl TelemetrySubcommand {
+    pub async fn execute(self, os: &Os) -> Result<ExitCode> {
+        match self {
+            Self::Export { since, output } => export(os, since, output).await,
+            Self::Policy(subcommand) => subcommand.execute(os),
+        }
+    }
+}
+
+async fn export(os: &Os, since: Option<Duration>, output: Option<PathBuf>) -> Result<ExitCode> {
+    let path = os.fs.chroot_path(GlobalPaths::telemetry_events_path_static()?);
+    let Some(contents) = read_events(&path).await? else {
+        if os.database.settings.get_bool(Setting::TelemetryLocalOnly) != Some(true) {
+            eprintln!(
+                "No telemetry events are recorded locally. Record them instead of sending them with {}",
+                StyledText::command("q settings telemetry.localOnly true")
+            );
+        }
+        return Ok(ExitCode::SUCCESS);
+    };
+
+    let since = since
+        .and_then(|period| TimeDelta::from_std(period).ok())
+        .and_then(|period| Utc::now().checked_sub_signed(period))
+        .unwrap_or(DateTime::<Utc>::MIN_UTC);
+    let mut events = events_since(&contents, since).join("\n");
+    if !events.is_empty() {
+        events.push('\n');
+    }
+
+    match output {
+        Some(output) => {
+            os.fs.write(&output, &events).await?;
+            eprintln!("Exported {} events to {}", events.lines().count(), output.display());
+        },
+        None => {
+            let mut stdout = std::io::stdout().lock();
+            stdout.write_all(events.as_bytes())?;
+            stdout.flush()?;
+        },
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// The telemetry policy is set with the `telemetry.prompts`, `telemetry.toolNames`,
+/// `telemetry.modelIds` and `telemetry.filePaths` settings.
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Eq, Subcommand)]
+pub enum PolicySubcommand {
+    /// Show the effective telemetry policy
+    Show {
+        /// Output format
+        #[arg(long, short, value_enum, default_value_t)]
+        format: OutputFormat,
+    },
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct EffectivePolicy {
+    mode: TelemetryMode,
+    #[serde(flatten)]
+    policy: TelemetryPolicy,
+}
+
+impl PolicySubcommand {
+    fn execute(self, os: &Os) -> Result<ExitCode> {
+        let Self::Show { format } = self;
+        let effective = EffectivePolicy {
+            mode: TelemetryMode::load(&os.database.settings),
+            policy: TelemetryPolicy::load(&os.database.settings),
+        };
+        format.print(|| format_policy(&effective), || &effective);
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+fn format_policy(effective: &EffectivePolicy) -> String {
+    let included = |include: bool| if include { "included" } else { "excluded" };
+    let policy = &effective.policy;
+    let rows = [
+        (
+            "Prompts",
+            match policy.prompts {
+                PromptPolicy::Lengths => "lengths only",
+                PromptPolicy::None => "excluded",
+            },
+            Setting::TelemetryPrompts,
+        ),
+        ("Tool names", included(policy.tool_names), Setting::TelemetryToolNames),
+        ("Model ids", included(policy.model_ids), Setting::TelemetryModelIds),
+        ("File paths", included(policy.file_paths), Setting::TelemetryFilePaths),
+    ];
+
+    let mut text = format!("Telemetry: {}\n\n", match effective.mode {
+        TelemetryMode::Sent => "sent to AWS",
+        TelemetryMode::LocalOnly => "recorded locally only (telemetry.localOnly)",
+        TelemetryMode::Disabled => "disabled",
+    });
+    for (field, value, setting) in rows {
+        text.push_str(&format!(
+            "{field:<12}{value:<14}{}\n",
+            StyledText::secondary(setting.as_ref())
+        ));
+    }
+    text.push_str("\nThe contents of prompts, responses and files are never included.");
+    text
+}
@@ -0,0 +1,460 @@
+//! `q usage report`: the usage of the last days, aggregated from the conversations saved in the
+//! database and from the telemetry events recorded locally with `telemetry.localOnly`.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::Write as _;
+use std::process::ExitCode;
+use std::time::Duration;
+
+use chrono::{
+    DateTime,
+    Local,
+    TimeDelta,
+    Utc,
+};
+use clap::{
+    Subcommand,
+    ValueEnum,
+};
+use eyre::Result;
+use serde::Serialize;
+
+use crate::cli::ConversationState;
+use crate::cli::chat::cost::{
+    Cost,
+    CostTracker,
+};
+use crate::os::Os;
+use crate::telemetry::core::{
+    Event,
+    EventType,
+    TelemetryResult,
+};
+use crate::telemetry::local::{
+    events_since,
+    parse_period,
+    read_events,
+};
+use crate::theme::StyledText;
+use crate::util::paths::GlobalPaths;
+
+/// Number of tools listed in the report.
+const TOP_TOOLS: usize = 10;
+
+/// Reports of the usage of Q
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Eq, Subcommand)]
+pub enum UsageSubcommand {
+    /// Report the conversations, tokens, estimated costs, top tools and error rates of a period
+    Report {
+        /// Period to report, e.g. 7d, 30d or 12h
+        #[arg(long, value_parser = parse_period, default_value = "7d")]
+        since: Duration,
+        /// Format of the report
+        #[arg(long, value_enum, default_value_t)]
+        format: ReportFormat,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ReportFormat {
+    /// Tables for the terminal
+    #[default]
+    Table,
+    /// The whole report as JSON
+    Json,
+    /// One row per conversation, for spreadsheets
+    Csv,
+}
+
+impl UsageSubcommand {
+    pub async fn execute(self, os: &Os) -> Result<ExitCode> {
+        let Self::Report { since, format } = self;
+        let since = TimeDelta::from_std(since)
+            .ok()
+            .and_then(|period| Utc::now().checked_sub_signed(period))
+            .unwrap_or(DateTime::<Utc>::MIN_UTC);
+
+        let conversations = os.database.get_all_conversations()?;
+        let path = os.fs.chroot_path(GlobalPaths::telemetry_events_path_static()?);
+        let events = read_events(&path).await?.map(|contents| {
+            events_since(&contents, since)
+                .into_iter()
+                .filter_map(|line| serde_json::from_str::<Event>(line).ok())
+                .collect::<Vec<_>>()
+        });
+
+        let report = UsageReport::new(&conversations, events.as_deref(), since);
+        let output = match format {
+            ReportFormat::Table => report.table(),
+            ReportFormat::Json => format!("{}\n", serde_json::to_string_pretty(&report)?),
+            ReportFormat::Csv => report.csv(),
+        };
+        let mut stdout = std::io::stdout().lock();
+        stdout.write_all(output.as_bytes())?;
+        stdout.flush()?;
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct UsageReport {
+    since: DateTime<Utc>,
+    /// Conversations with requests in the period, most recently active first.
+    conversations: Vec<ConversationUsage>,
+    totals: UsageTotals,
+    top_tools: Vec<ToolUsage>,
+    /// [None] if no telemetry events are recorded locally.
+    errors: Option<ErrorRates>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ConversationUsage {
+    conversation_id: String,
+    /// Directory the conversation was saved for.
+    directory: String,
+    last_active: DateTime<Utc>,
+    requests: usize,
+    input_tokens: usize,
+    output_tokens: usize,
+    /// [None] if none of the requests were sent to a model with known pricing.
+    cost_usd: Option<f64>,
+    /// Whether any of the token counts were estimated from the size of the messages.
+    estimated: bool,
+    tool_uses: usize,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct UsageTotals {
+    conversations: usize,
+    requests: usize,
+    input_tokens: usize,
+    output_tokens: usize,
+    cost_usd: Option<f64>,
+    estimated: bool,
+    tool_uses: usize,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+struct ToolUsage {
+    name: String,
+    uses: usize,
+}
+
+#[derive(Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ErrorRates {
+    requests: usize,
+    failed_requests: usize,
+    tool_uses: usize,
+    failed_tool_uses: usize,
+}
+
+impl UsageReport {
+    fn new(conversations: &[(String, ConversationState)], events: Option<&[Event]>, since: DateTime<Utc>) -> Self {
+        let since_ms = u64::try_from(since.timestamp_millis()).unwrap_or_default();
+        let mut total = CostTracker::default();
+        let mut tools = BTreeMap::<&str, usize>::new();
+        let mut usages = Vec::new();
+
+        for (directory, state) in conversations {
+            let mut cost = CostTracker::default();
+            let mut last_active_ms = 0;
+            let mut tool_uses = 0;
+            for metadata in state
+                .request_metadata()
+                .filter(|metadata| metadata.request_start_timestamp_ms >= since_ms)
+            {
+                let usage = metadata.usage();
+                cost.record(metadata.model_id.as_deref(), usage);
+                total.record(metadata.model_id.as_deref(), usage);
+                last_active_ms = last_active_ms.max(metadata.request_start_timestamp_ms);
+                for (_, name) in &metadata.tool_use_ids_and_names {
+                    *tools.entry(name).or_default() += 1;
+                    tool_uses += 1;
+                }
+            }
+            if cost.requests == 0 {
+                continue;
+            }
+
+            let report = cost.report();
+            usages.push(ConversationUsage {
+                conversation_id: state.conversation_id().to_string(),
+                directory: directory.clone(),
+                last_active: DateTime::from_timestamp_millis(last_active_ms as i64).unwrap_or_default(),
+                requests: report.requests,
+                input_tokens: report.input_tokens,
+                output_tokens: report.output_tokens,
+                cost_usd: report.total_cost_usd,
+                estimated: report.estimated,
+                tool_uses,
+            });
+        }
+        usages.sort_by(|a, b| b.last_active.cmp(&a.last_active));
+
+        let mut top_tools = tools
+            .into_iter()
+            .map(|(name, uses)| ToolUsage {
+                name: name.to_string(),
+                uses,
+            })
+            .collect::<Vec<_>>();
+        // Stable, so tools used as often stay sorted by name
+        top_tools.sort_by(|a, b| b.uses.cmp(&a.uses));
+        top_tools.truncate(TOP_TOOLS);
+
+        let total = total.report();
+        Self {
+            since,
+            totals: UsageTotals {
+                conversations: usages.len(),
+                requests: total.requests,
+                input_tokens: total.input_tokens,
+                output_tokens: total.output_tokens,
+                cost_usd: total.total_cost_usd,
+                estimated: total.estimated,
+                tool_uses: usages.iter().map(|usage| usage.tool_uses).sum(),
+            },
+            conversations: usages,
+            top_tools,
+            errors: events.map(ErrorRates::new),
+        }
+    }
+
+    fn table(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "Usage since {}\n",
+            self.since.with_timezone(&Local).format("%Y-%m-%d %H:%M")
+        );
+        let row = |out: &mut String, columns: [&str; 7]| {
+            let [last_active, requests, input, output, cost, tools, directory] = columns;
+            let _ = writeln!(
+                out,
+                "{last_active:<16}  {requests:>8}  {input:>12}  {output:>13}  {cost:>9}  {tools:>9}  {directory}"
+            );
+        };
+        let cost = |cost: Option<f64>, estimated: bool| match cost {
+            Some(cost) if estimated => format!("~{}", Cost(cost)),
+            Some(cost) => Cost(cost).to_string(),
+            None => "-".to_string(),
+        };
+
+        row(&mut out, [
+            "Last active",
+            "Requests",
+            "Input tokens",
+            "Output tokens",
+            "Cost",
+            "Tool uses",
+            "Directory",
+        ]);
+        for usage in &self.conversations {
+            row(&mut out, [
+                &usage
+                    .last_active
+                    .with_timezone(&Local)
+                    .format("%Y-%m-%d %H:%M")
+                    .to_string(),
+                &usage.requests.to_string(),
+                &usage.input_tokens.to_string(),
+                &usage.output_tokens.to_string(),
+                &cost(usage.cost_usd, usage.estimated),
+                &usage.tool_uses.to_string(),
+                &usage.directory,
+            ]);
+        }
+        let totals = &self.totals;
+        row(&mut out, [
+            "Total",
+            &totals.requests.to_string(),
+            &totals.input_tokens.to_string(),
+            &totals.output_tokens.to_string(),
+            &cost(totals.cost_usd, totals.estimated),
+            &totals.tool_uses.to_string(),
+            &format!("{} conversations", totals.conversations),
+        ]);
+        if totals.estimated {
+            out.push_str("Costs marked ~ include token counts estimated from the size of the messages\n");
+        }
+
+        if !self.top_tools.is_empty() {
+            out.push_str("\nTop tools\n");
+            let width = self
+                .top_tools
+                .iter()
+                .map(|tool| tool.name.len())
+                .max()
+                .unwrap_or_default();
+            for tool in &self.top_tools {
+                let _ = writeln!(out, "  {:<width$}  {:>6}", tool.name, tool.uses);
+            }
+        }
+
+        out.push_str("\nErrors\n");
+        match &self.errors {
+            Some(errors) => {
+                let rate = |failed: usize, count: usize| match count {
+                    0 => format!("{failed} of {count}"),
+                    _ => format!("{failed} of {count} ({:.1}%)", failed as f64 * 100.0 / count as f64),
+                };
+                let _ = writeln!(out, "  Requests   {}", rate(errors.failed_requests, errors.requests));
+                let _ = writeln!(out, "  Tool uses  {}", rate(errors.failed_tool_uses, errors.tool_uses));
+            },
+            None => {
+                let _ = writeln!(
+                    out,
+                    "  Error rates come from the telemetry events recorded locally, record them with {}",
+                    StyledText::command("q settings telemetry.localOnly true")
+                );
+            },
+        }
+        out
+    }
+
+    fn csv(&self) -> String {
+        let mut out = String::from(
+            "conversation_id,directory,last_active,requests,input_tokens,output_tokens,cost_usd,estimated,tool_uses\n",
+        );
+        for usage in &self.conversations {
+            let _ = writeln!(
+                out,
+                "{},{},{},{},{},{},{},{},{}",
+                csv_field(&usage.conversation_id),
+                csv_field(&usage.directory),
+                usage.last_active.to_rfc3339(),
+                usage.requests,
+                usage.input_tokens,
+                usage.output_tokens,
+                usage.cost_usd.map(|cost| format!("{cost:.6}")).unwrap_or_default(),
+                usage.estimated,
+                usage.tool_uses,
+            );
+        }
+        out
+    }
+}
+
+impl ErrorRates {
+    fn new(events: &[Event]) -> Self {
+        let mut errors = Self::default();
+        for event in events {
+            match &event.ty {
+                EventType::ChatAddedMessage { result, .. } => {
+                    errors.requests += 1;
+                    if *result == TelemetryResult::Failed {
+                        errors.failed_requests += 1;
+                    }
+                },
+                EventType::ToolUseSuggested {
+                    is_success: Some(is_success),
+                    ..
+                } => {
+                    errors.tool_uses += 1;
+                    if !is_success {
+                        errors.failed_tool_uses += 1;
+                    }
+                },
+                _ => (),
+            }
+        }
+        errors
+    }
+}
+
+/// Quotes a CSV field if needed.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::telemetry::core::ChatAddedMessageParams;
+
+    fn tool_use(is_success: Option<bool>) -> Event {
+        Event::new(EventType::ToolUseSuggested {
+            conversation_id: "conv".to_string(),
+            utterance_id: None,
+            user_input_id: None,
+            tool_use_id: None,
+            tool_name: Some("fs_read".to_string()),
+            is_accepted: true,
+            is_trusted: true,
+            is_valid: Some(true),
+            is_success,
+            reason_desc: None,
+            is_custom_tool: false,
+            input_token_size: None,
+            output_token_size: None,
+            custom_tool_call_latency: None,
+            model: None,
+            execution_duration: None,
+            turn_duration: None,
+            aws_service_name: None,
+            aws_operation_name: None,
+            subagent_name: None,
+        })
+    }
+
+    #[test]
+    fn test_error_rates() {
+        let message = |result| {
+            Event::new(EventType::ChatAddedMessage {
+                conversation_id: "conv".to_string(),
+                result,
+                data: ChatAddedMessageParams::default(),
+            })
+        };
+        let events = [
+            message(TelemetryResult::Succeeded),
+            message(TelemetryResult::Failed),
+            message(TelemetryResult::Succeeded),
+            tool_use(Some(true)),
+            tool_use(Some(false)),
+            tool_use(None),
+            Event::new(EventType::DailyHeartbeat {}),
+        ];
+
+        // Events are read back from the file they are recorded in
+        let events = events
+            .iter()
+            .map(|event| serde_json::from_value(serde_json::to_value(event).unwrap()).unwrap())
+            .collect::<Vec<Event>>();
+        assert_eq!(ErrorRates::new(&events), ErrorRates {
+            requests: 3,
+            failed_requests: 1,
+            tool_uses: 2,
+            failed_tool_uses: 1,
+        });
+    }
+
+    #[test]
+    fn test_empty_report() {
+        let report = UsageReport::new(&[], None, Utc::now());
+        assert_eq!(report.totals.conversations, 0);
+        assert_eq!(report.totals.cost_usd, None);
+        assert!(report.table().contains("telemetry.localOnly"));
+        assert_eq!(report.csv().lines().count(), 1);
+
+        let report = UsageReport::new(&[], Some(&[]), Utc::now());
+        assert!(report.table().contains("Requests   0 of 0"));
+    }
+
+    #[test]
+    fn test_csv_field() {
+        assert_eq!(csv_field("/home/me/src"), "/home/me/src");
+        assert_eq!(csv_field("/home/me/a,b"), "\"/home/me/a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+}
@@ -46,6 +46,26 @@ pub mod ui_text {
         warning
     }
 
+    /// Notice shown at the start of a session launched with `--read-only`
+    pub fn read_only_notice() -> String {
+        let mut notice = String::new();
+
+        notice.push_str(&StyledText::warning("Read-only mode is enabled."));
+        notice.push_str(" Tools that modify files or run commands with side effects will be denied.");
+
+        notice
+    }
+
+    /// Notice shown at the start of a session launched with `--plan`
+    pub fn plan_mode_notice() -> String {
+        let mut notice = String::new();
+
+        notice.push_str(&StyledText::warning("Plan mode is enabled."));
+        notice.push_str(" Tools that modify files or run commands with side effects will be recorded into a plan for review instead of being run.");
+
+        notice
+    }
+
     /// Rate limit reached message
     pub fn limit_reached_text() -> String {
         format!(
@@ -91,9 +111,17 @@ pub mod ui_text {
             StyledText::primary("Ctrl(^) + j"),
             StyledText::secondary("Insert new-line to provide multi-line prompt")
         ));
+        help.push('\n');
+
+        // Block mode tip
+        help.push_str(&format!(
+            "{}   {}",
+            StyledText::primary("Alt(⌥) + Enter(⏎)"),
+            StyledText::secondary("Enter block mode, where Enter inserts new-lines")
+        ));
         help.push_str(&format!(
             "\n                    {}",
-            StyledText::secondary("Alternatively, [Alt(⌥) + Enter(⏎)]")
+            StyledText::secondary("Send with Alt(⌥) + Enter(⏎) again. Pasting several lines enters it too")
         ));
         help.push('\n');
 
@@ -113,6 +141,14 @@ pub mod ui_text {
         ));
         help.push('\n');
 
+        // History search tip
+        help.push_str(&format!(
+            "{}         {}",
+            StyledText::primary("Ctrl(^) + r"),
+            StyledText::secondary("Search the prompts of this and previous sessions")
+        ));
+        help.push('\n');
+
         // Tangent mode tip
         help.push_str(&format!(
             "{}         {}",
@@ -194,8 +230,17 @@ more accurate and helpful responses.
 
 Notes:
 • You can add specific files or use glob patterns (e.g., \"*.py\", \"src/**/*.js\")
+• Directories added with /context add are summarized as a tree with a synopsis of each file (size, lines
+  and top-level symbols) rather than included in full
+• Use /context add-url to add the readable content of a web page, such as API documentation. Pages are
+  fetched once, run /context add-url again to refresh them
+• Use /context add-symbol <path>::<symbol> (e.g. src/lib.rs::Agent::new) to add a single function, type
+  or class along with the imports of its file, rather than the whole file
+• Context added during the session that the model hasn't mentioned or read for a while is proposed for
+  removal. Use /context auto-evict on to remove it automatically
 • Agent rules apply only to the current agent 
-• Context changes are NOT preserved between chat sessions. To make these changes permanent, edit the agent config file.", super::PRODUCT_NAME, super::PRODUCT_NAME)
+• The context size limit is split between agent resources, session files, MCP resources and hook output, see /context budget
+• Context changes are NOT preserved between chat sessions, except when resuming a conversation. To make these changes permanent, edit the agent config file, or share them with /context export and /context import.", super::PRODUCT_NAME, super::PRODUCT_NAME)
     }
 
     /// Full tools command long help text
@@ -206,6 +251,20 @@ trust so that no confirmation is required.
 Refer to the documentation for how to configure tools with your agent: https://github.com/aws/amazon-q-developer-cli/blob/main/docs/agent-format.md#tools-field", super::PRODUCT_NAME)
     }
 
+    /// Full permissions command long help text
+    pub fn permissions_long_help() -> String {
+        "Permission profiles are named sets of allowedTools and toolsSettings defined by the active agent.
+Switching to a profile replaces the agent's top level permissions for the rest of the session,
+without having to change agents.
+
+Refer to the documentation for how to configure permission profiles with your agent: https://github.com/aws/amazon-q-developer-cli/blob/main/docs/agent-format.md#permissionprofiles-field
+
+Notes:
+• Tools trusted during the session are reset when switching profiles
+• Tools awaiting your approval are re-evaluated against the new profile
+• Use /permissions edit to change the agent's allowedTools and toolsSettings interactively".to_string()
+    }
+
     /// Full hooks command long help text
     pub fn hooks_long_help() -> String {
         format!("Use context hooks to specify shell commands to run. The output from these 
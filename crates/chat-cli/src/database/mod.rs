@@ -1,5 +1,6 @@
 pub mod settings;
 
+use std::collections::HashMap;
 use std::ops::Deref;
 use std::path::Path;
 use std::str::FromStr;
@@ -31,10 +32,12 @@ use tracing::{
     error,
     info,
     trace,
+    warn,
 };
 use uuid::Uuid;
 
 use crate::cli::ConversationState;
+use crate::cli::chat::error_hints::ErrorCluster;
 use crate::util::env_var::is_integ_test;
 use crate::util::paths::{
     DirectoryError,
@@ -63,6 +66,11 @@ const IDC_REGION_KEY: &str = "auth.idc.region";
 const CUSTOMIZATION_STATE_KEY: &str = "api.selectedCustomization";
 const PROFILE_MIGRATION_KEY: &str = "profile.Migrated";
 const HEARTBEAT_DATE_KEY: &str = "telemetry.lastHeartbeatDate";
+const TOKEN_CALIBRATION_KEY: &str = "chat.tokenCalibration";
+const ERROR_CLUSTERS_KEY: &str = "chat.errorClusters";
+const MODEL_NAMES_KEY: &str = "chat.modelNames";
+/// Number of prompts kept in the prompt history, the oldest ones being dropped.
+const MAX_PROMPT_HISTORY: i64 = 10_000;
 
 const MIGRATIONS: &[Migration] = migrations![
     "000_migration_table",
@@ -72,7 +80,8 @@ const MIGRATIONS: &[Migration] = migrations![
     "004_state_table",
     "005_auth_table",
     "006_make_state_blob",
-    "007_conversations_table"
+    "007_conversations_table",
+    "008_prompt_history_table"
 ];
 
 #[derive(Debug, serde::Deserialize, serde::Serialize)]
@@ -287,6 +296,54 @@ impl Database {
         Ok(())
     }
 
+    /// Get the characters per token calibrated for each model
+    pub fn get_token_calibration(&self) -> Result<Option<HashMap<String, f64>>, DatabaseError> {
+        self.get_json_entry(Table::State, TOKEN_CALIBRATION_KEY)
+    }
+
+    /// Set the characters per token calibrated for each model
+    pub fn set_token_calibration(&self, calibration: &HashMap<String, f64>) -> Result<(), DatabaseError> {
+        self.set_json_entry(Table::State, TOKEN_CALIBRATION_KEY, calibration)?;
+        Ok(())
+    }
+
+    /// Get the names of the models last listed by the backend, completed by the shell completions
+    pub fn get_model_names(&self) -> Result<Option<Vec<String>>, DatabaseError> {
+        self.get_json_entry(Table::State, MODEL_NAMES_KEY)
+    }
+
+    /// Set the names of the models last listed by the backend
+    pub fn set_model_names(&self, names: &[String]) -> Result<(), DatabaseError> {
+        self.set_json_entry(Table::State, MODEL_NAMES_KEY, names)?;
+        Ok(())
+    }
+
+    /// Runs the integrity check of SQLite, returning the problems found.
+    pub fn integrity_check(&self) -> Result<Vec<String>, DatabaseError> {
+        let conn = self.pool.get()?;
+        let mut stmt = conn.prepare("PRAGMA integrity_check")?;
+        let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+        let mut problems = Vec::new();
+        for row in rows {
+            let row = row?;
+            if row != "ok" {
+                problems.push(row);
+            }
+        }
+        Ok(problems)
+    }
+
+    /// Get the clusters of the errors hit in chat
+    pub fn get_error_clusters(&self) -> Result<Option<Vec<ErrorCluster>>, DatabaseError> {
+        self.get_json_entry(Table::State, ERROR_CLUSTERS_KEY)
+    }
+
+    /// Set the clusters of the errors hit in chat
+    pub fn set_error_clusters(&self, clusters: &[ErrorCluster]) -> Result<(), DatabaseError> {
+        self.set_json_entry(Table::State, ERROR_CLUSTERS_KEY, clusters)?;
+        Ok(())
+    }
+
     /// Get changelog show count from state table
     pub fn get_changelog_show_count(&self) -> Result<Option<i64>, DatabaseError> {
         self.get_entry::<i64>(Table::State, "changelog.showCount")
@@ -399,6 +456,46 @@ impl Database {
         self.set_json_entry(Table::Conversations, path, state)
     }
 
+    /// Get all the saved chat conversations, along with the paths they were saved for.
+    /// Conversations that can't be deserialized, e.g. ones saved by older versions, are skipped.
+    pub fn get_all_conversations(&self) -> Result<Vec<(String, ConversationState)>, DatabaseError> {
+        Ok(self
+            .all_entries(Table::Conversations)?
+            .into_iter()
+            .filter_map(|(path, value)| match value.as_str().map(serde_json::from_str) {
+                Some(Ok(state)) => Some((path, state)),
+                Some(Err(err)) => {
+                    warn!(?err, path, "skipping conversation that failed to deserialize");
+                    None
+                },
+                None => None,
+            })
+            .collect())
+    }
+
+    /// Records a prompt of the user, for searching the prompts of previous sessions.
+    pub fn add_prompt_history(&self, prompt: &str) -> Result<(), DatabaseError> {
+        let conn = self.pool.get()?;
+        conn.execute(
+            "INSERT INTO prompt_history (prompt, time) VALUES (?1, strftime('%s', 'now'))",
+            [prompt],
+        )?;
+        conn.execute(
+            "DELETE FROM prompt_history WHERE id <= (SELECT MAX(id) FROM prompt_history) - ?1",
+            [MAX_PROMPT_HISTORY],
+        )?;
+        Ok(())
+    }
+
+    /// Returns up to `limit` distinct prompts of the prompt history, the most recent first.
+    pub fn get_prompt_history(&self, limit: usize) -> Result<Vec<String>, DatabaseError> {
+        let conn = self.pool.get()?;
+        let mut stmt =
+            conn.prepare("SELECT prompt FROM prompt_history GROUP BY prompt ORDER BY MAX(id) DESC LIMIT ?1")?;
+        let prompts = stmt.query_map([limit], |row| row.get(0))?;
+        Ok(prompts.collect::<Result<_, _>>()?)
+    }
+
     pub async fn get_secret(&self, key: &str) -> Result<Option<Secret>, DatabaseError> {
         trace!(key, "getting secret");
         Ok(self.get_entry::<String>(Table::Auth, key)?.map(Into::into))
@@ -631,6 +728,20 @@ mod tests {
         assert!(db.get_entry::<bool>(Table::State, "bool").unwrap().is_some());
     }
 
+    #[tokio::test]
+    async fn test_prompt_history() {
+        let db = Database::new().await.unwrap();
+        for prompt in ["explain main.rs", "add tests", "explain main.rs", "fix the build"] {
+            db.add_prompt_history(prompt).unwrap();
+        }
+        assert_eq!(db.get_prompt_history(10).unwrap(), vec![
+            "fix the build",
+            "explain main.rs",
+            "add tests"
+        ]);
+        assert_eq!(db.get_prompt_history(1).unwrap(), vec!["fix the build"]);
+    }
+
     #[tokio::test]
     #[ignore = "not on ci"]
     async fn test_set_password() {
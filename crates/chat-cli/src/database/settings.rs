@@ -20,6 +20,22 @@ use crate::util::paths::GlobalPaths;
 pub enum Setting {
     #[strum(message = "Enable/disable telemetry collection (boolean)")]
     TelemetryEnabled,
+    #[strum(message = "Record telemetry to a local file only, never sending it (boolean)")]
+    TelemetryLocalOnly,
+    #[strum(message = "OTLP endpoint to export traces and metrics to (string)")]
+    TelemetryOtlpEndpoint,
+    #[strum(message = "Headers sent to the OTLP endpoint, as key1=value1,key2=value2 (string)")]
+    TelemetryOtlpHeaders,
+    #[strum(
+        message = "Whether telemetry includes the lengths of prompts and responses, \"lengths\" or \"none\" (string)"
+    )]
+    TelemetryPrompts,
+    #[strum(message = "Include the names of tools and MCP servers in telemetry (boolean)")]
+    TelemetryToolNames,
+    #[strum(message = "Include the ids of models in telemetry (boolean)")]
+    TelemetryModelIds,
+    #[strum(message = "Include file paths found in error messages in telemetry (boolean)")]
+    TelemetryFilePaths,
     #[strum(message = "Legacy client identifier for telemetry (string)")]
     OldClientId,
     #[strum(message = "Share content with CodeWhisperer service (boolean)")]
@@ -48,7 +64,7 @@ pub enum Setting {
     EnabledTangentMode,
     #[strum(message = "Key binding for tangent mode toggle (single character)")]
     TangentModeKey,
-    #[strum(message = "Key binding for delegate command (single character)")]
+    #[strum(message = "Key binding for the /agents dashboard of delegated tasks (single character)")]
     DelegateModeKey,
 
     #[strum(message = "Auto-enter tangent mode for introspect questions (boolean)")]
@@ -57,10 +73,46 @@ pub enum Setting {
     ChatGreetingEnabled,
     #[strum(message = "API request timeout in seconds (number)")]
     ApiTimeout,
+    #[strum(message = "Maximum number of attempts of API requests failing with transient errors (number)")]
+    ApiRetryMaxAttempts,
+    #[strum(message = "Maximum delay between retries of API requests in milliseconds (number)")]
+    ApiRetryMaxBackoff,
+    #[strum(message = "Maximum time to receive a whole response in seconds, unbounded if unset (number)")]
+    ApiResponseTimeout,
+    #[strum(message = "Maximum time without receiving any part of a response in seconds (number)")]
+    ApiStallTimeout,
     #[strum(message = "Enable edit mode for chat interface (boolean)")]
     ChatEditMode,
     #[strum(message = "Enable desktop notifications (boolean)")]
     ChatEnableNotifications,
+    #[strum(
+        message = "Minimum duration of a turn in seconds for notification hooks to be told it completed (number, default 30)"
+    )]
+    ChatNotificationTurnSeconds,
+    #[strum(
+        message = "Seconds without progress after which slow responses and tool uses report heartbeats, 0 to disable (number, default 10)"
+    )]
+    ChatHeartbeatSeconds,
+    #[strum(message = "Color theme: dark, light, high-contrast or no-color (string, default dark)")]
+    ChatTheme,
+    #[strum(
+        message = "Colors overriding those of the theme, e.g. {\"status.error\": \"#ff5555\", \"ui.secondaryText\": \"grey\"} (object)"
+    )]
+    ChatThemeColors,
+    #[strum(message = "Protocol displaying images inline: auto, kitty, iterm2, sixel or off (string, default auto)")]
+    ChatInlineImages,
+    #[strum(
+        message = "Show a status line at the bottom of the terminal with the agent, model, context usage, background agents and cost (boolean)"
+    )]
+    ChatStatusBar,
+    #[strum(
+        message = "Where notifications are sent: bell, desktop and webhook, e.g. [\"bell\", \"desktop\"] (array, default [\"bell\"] with chat.enableNotifications)"
+    )]
+    ChatNotifiers,
+    #[strum(message = "Events notified: approval_needed, turn_complete and delegate_finished (array, default all)")]
+    ChatNotificationEvents,
+    #[strum(message = "URL the webhook notifier posts the notifications to as JSON (string)")]
+    ChatNotificationWebhook,
     #[strum(message = "CodeWhisperer service endpoint URL (string)")]
     ApiCodeWhispererService,
     #[strum(message = "Q service endpoint URL (string)")]
@@ -73,22 +125,44 @@ pub enum Setting {
     McpLoadedBefore,
     #[strum(message = "Show context usage percentage in prompt (boolean)")]
     EnabledContextUsageIndicator,
+    #[strum(message = "Show the estimated cost of the session in the prompt (boolean)")]
+    ChatShowCost,
+    #[strum(message = "Print a summary of the session when it ends (boolean)")]
+    ChatSessionSummary,
     #[strum(message = "Default AI model for conversations (string)")]
     ChatDefaultModel,
+    #[strum(message = "Model answering short prompts alongside the current one, keeping the fastest response (string)")]
+    ChatRaceModel,
+    #[strum(message = "Number of times an answer not matching the response schema is requested again (number)")]
+    ChatResponseSchemaRetries,
+    #[strum(message = "Percentages of the monthly allowance at which to warn, e.g. [80, 95] (array)")]
+    ChatQuotaWarningThresholds,
     #[strum(message = "Disable markdown formatting in chat (boolean)")]
     ChatDisableMarkdownRendering,
     #[strum(message = "Default agent configuration (string)")]
     ChatDefaultAgent,
     #[strum(message = "Disable automatic conversation summarization (boolean)")]
     ChatDisableAutoCompaction,
+    #[strum(
+        message = "Context window usage percentage at which older history is summarized in the background, 0 to disable (number)"
+    )]
+    ChatBackgroundCompactionThreshold,
     #[strum(message = "Show conversation history hints (boolean)")]
     ChatEnableHistoryHints,
+    #[strum(
+        message = "Show the estimated tokens of the prompt being written next to it, the warnings of too large prompts are always shown (boolean, default true)"
+    )]
+    ChatShowInputTokens,
     #[strum(message = "Enable the todo list feature (boolean)")]
     EnabledTodoList,
     #[strum(message = "Enable the checkpoint feature (boolean)")]
     EnabledCheckpoint,
     #[strum(message = "Enable the delegate tool for subagent management (boolean)")]
     EnabledDelegate,
+    #[strum(message = "Maximum number of delegated tasks running at the same time (number)")]
+    ChatDelegateMaxConcurrency,
+    #[strum(message = "Include the git branch, status and diff in the context of every turn (boolean)")]
+    EnabledGitContext,
     #[strum(message = "Specify UI variant to use (string)")]
     UiMode,
 }
@@ -97,6 +171,13 @@ impl AsRef<str> for Setting {
     fn as_ref(&self) -> &'static str {
         match self {
             Self::TelemetryEnabled => "telemetry.enabled",
+            Self::TelemetryLocalOnly => "telemetry.localOnly",
+            Self::TelemetryOtlpEndpoint => "telemetry.otlpEndpoint",
+            Self::TelemetryOtlpHeaders => "telemetry.otlpHeaders",
+            Self::TelemetryPrompts => "telemetry.prompts",
+            Self::TelemetryToolNames => "telemetry.toolNames",
+            Self::TelemetryModelIds => "telemetry.modelIds",
+            Self::TelemetryFilePaths => "telemetry.filePaths",
             Self::OldClientId => "telemetryClientId",
             Self::ShareCodeWhispererContent => "codeWhisperer.shareCodeWhispererContentWithAWS",
             Self::EnabledThinking => "chat.enableThinking",
@@ -116,22 +197,44 @@ impl AsRef<str> for Setting {
             Self::IntrospectTangentMode => "introspect.tangentMode",
             Self::ChatGreetingEnabled => "chat.greeting.enabled",
             Self::ApiTimeout => "api.timeout",
+            Self::ApiRetryMaxAttempts => "api.retry.maxAttempts",
+            Self::ApiRetryMaxBackoff => "api.retry.maxBackoff",
+            Self::ApiResponseTimeout => "api.responseTimeout",
+            Self::ApiStallTimeout => "api.stallTimeout",
             Self::ChatEditMode => "chat.editMode",
             Self::ChatEnableNotifications => "chat.enableNotifications",
+            Self::ChatNotificationTurnSeconds => "chat.notificationTurnSeconds",
+            Self::ChatHeartbeatSeconds => "chat.heartbeatSeconds",
+            Self::ChatTheme => "chat.theme",
+            Self::ChatThemeColors => "chat.themeColors",
+            Self::ChatInlineImages => "chat.inlineImages",
+            Self::ChatStatusBar => "chat.statusBar",
+            Self::ChatNotifiers => "chat.notifiers",
+            Self::ChatNotificationEvents => "chat.notificationEvents",
+            Self::ChatNotificationWebhook => "chat.notificationWebhook",
             Self::ApiCodeWhispererService => "api.codewhisperer.service",
             Self::ApiQService => "api.q.service",
             Self::McpInitTimeout => "mcp.initTimeout",
             Self::McpNoInteractiveTimeout => "mcp.noInteractiveTimeout",
             Self::McpLoadedBefore => "mcp.loadedBefore",
             Self::ChatDefaultModel => "chat.defaultModel",
+            Self::ChatRaceModel => "chat.raceModel",
+            Self::ChatResponseSchemaRetries => "chat.responseSchemaRetries",
+            Self::ChatQuotaWarningThresholds => "chat.quotaWarningThresholds",
             Self::ChatDisableMarkdownRendering => "chat.disableMarkdownRendering",
             Self::ChatDefaultAgent => "chat.defaultAgent",
             Self::ChatDisableAutoCompaction => "chat.disableAutoCompaction",
+            Self::ChatBackgroundCompactionThreshold => "chat.backgroundCompactionThreshold",
             Self::ChatEnableHistoryHints => "chat.enableHistoryHints",
+            Self::ChatShowInputTokens => "chat.showInputTokens",
             Self::EnabledTodoList => "chat.enableTodoList",
             Self::EnabledCheckpoint => "chat.enableCheckpoint",
             Self::EnabledContextUsageIndicator => "chat.enableContextUsageIndicator",
+            Self::ChatShowCost => "chat.showCost",
+            Self::ChatSessionSummary => "chat.sessionSummary",
             Self::EnabledDelegate => "chat.enableDelegate",
+            Self::ChatDelegateMaxConcurrency => "chat.delegateMaxConcurrency",
+            Self::EnabledGitContext => "chat.enableGitContext",
             Self::UiMode => "chat.uiMode",
         }
     }
@@ -149,6 +252,13 @@ impl TryFrom<&str> for Setting {
     fn try_from(value: &str) -> Result<Self, Self::Error> {
         match value {
             "telemetry.enabled" => Ok(Self::TelemetryEnabled),
+            "telemetry.localOnly" => Ok(Self::TelemetryLocalOnly),
+            "telemetry.otlpEndpoint" => Ok(Self::TelemetryOtlpEndpoint),
+            "telemetry.otlpHeaders" => Ok(Self::TelemetryOtlpHeaders),
+            "telemetry.prompts" => Ok(Self::TelemetryPrompts),
+            "telemetry.toolNames" => Ok(Self::TelemetryToolNames),
+            "telemetry.modelIds" => Ok(Self::TelemetryModelIds),
+            "telemetry.filePaths" => Ok(Self::TelemetryFilePaths),
             "telemetryClientId" => Ok(Self::OldClientId),
             "codeWhisperer.shareCodeWhispererContentWithAWS" => Ok(Self::ShareCodeWhispererContent),
             "chat.enableThinking" => Ok(Self::EnabledThinking),
@@ -167,21 +277,43 @@ impl TryFrom<&str> for Setting {
             "introspect.tangentMode" => Ok(Self::IntrospectTangentMode),
             "chat.greeting.enabled" => Ok(Self::ChatGreetingEnabled),
             "api.timeout" => Ok(Self::ApiTimeout),
+            "api.retry.maxAttempts" => Ok(Self::ApiRetryMaxAttempts),
+            "api.retry.maxBackoff" => Ok(Self::ApiRetryMaxBackoff),
+            "api.responseTimeout" => Ok(Self::ApiResponseTimeout),
+            "api.stallTimeout" => Ok(Self::ApiStallTimeout),
             "chat.editMode" => Ok(Self::ChatEditMode),
             "chat.enableNotifications" => Ok(Self::ChatEnableNotifications),
+            "chat.notificationTurnSeconds" => Ok(Self::ChatNotificationTurnSeconds),
+            "chat.heartbeatSeconds" => Ok(Self::ChatHeartbeatSeconds),
+            "chat.theme" => Ok(Self::ChatTheme),
+            "chat.themeColors" => Ok(Self::ChatThemeColors),
+            "chat.inlineImages" => Ok(Self::ChatInlineImages),
+            "chat.statusBar" => Ok(Self::ChatStatusBar),
+            "chat.notifiers" => Ok(Self::ChatNotifiers),
+            "chat.notificationEvents" => Ok(Self::ChatNotificationEvents),
+            "chat.notificationWebhook" => Ok(Self::ChatNotificationWebhook),
             "api.codewhisperer.service" => Ok(Self::ApiCodeWhispererService),
             "api.q.service" => Ok(Self::ApiQService),
             "mcp.initTimeout" => Ok(Self::McpInitTimeout),
             "mcp.noInteractiveTimeout" => Ok(Self::McpNoInteractiveTimeout),
             "mcp.loadedBefore" => Ok(Self::McpLoadedBefore),
             "chat.defaultModel" => Ok(Self::ChatDefaultModel),
+            "chat.raceModel" => Ok(Self::ChatRaceModel),
+            "chat.responseSchemaRetries" => Ok(Self::ChatResponseSchemaRetries),
+            "chat.quotaWarningThresholds" => Ok(Self::ChatQuotaWarningThresholds),
             "chat.disableMarkdownRendering" => Ok(Self::ChatDisableMarkdownRendering),
             "chat.defaultAgent" => Ok(Self::ChatDefaultAgent),
             "chat.disableAutoCompaction" => Ok(Self::ChatDisableAutoCompaction),
+            "chat.backgroundCompactionThreshold" => Ok(Self::ChatBackgroundCompactionThreshold),
             "chat.enableHistoryHints" => Ok(Self::ChatEnableHistoryHints),
+            "chat.showInputTokens" => Ok(Self::ChatShowInputTokens),
             "chat.enableTodoList" => Ok(Self::EnabledTodoList),
             "chat.enableCheckpoint" => Ok(Self::EnabledCheckpoint),
             "chat.enableContextUsageIndicator" => Ok(Self::EnabledContextUsageIndicator),
+            "chat.showCost" => Ok(Self::ChatShowCost),
+            "chat.sessionSummary" => Ok(Self::ChatSessionSummary),
+            "chat.delegateMaxConcurrency" => Ok(Self::ChatDelegateMaxConcurrency),
+            "chat.enableGitContext" => Ok(Self::EnabledGitContext),
             "chat.uiMode" => Ok(Self::UiMode),
             _ => Err(DatabaseError::InvalidSetting(value.to_string())),
         }
@@ -0,0 +1,5 @@
+CREATE TABLE prompt_history (
+    id INTEGER PRIMARY KEY,
+    prompt TEXT NOT NULL,
+    time INTEGER NOT NULL
+);
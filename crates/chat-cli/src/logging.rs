@@ -1,26 +1,55 @@
+use std::collections::VecDeque;
+use std::fmt::Write as _;
 use std::fs::File;
 use std::path::Path;
-use std::sync::Mutex;
+use std::sync::atomic::{
+    AtomicBool,
+    Ordering,
+};
+use std::sync::{
+    Mutex,
+    PoisonError,
+};
 
 use thiserror::Error;
-use tracing::info;
+use tracing::field::{
+    Field,
+    Visit,
+};
 use tracing::level_filters::LevelFilter;
+use tracing::{
+    Event,
+    Subscriber,
+    info,
+};
 use tracing_appender::non_blocking::WorkerGuard;
 use tracing_subscriber::filter::Directive;
+use tracing_subscriber::layer::Context;
 use tracing_subscriber::prelude::*;
 use tracing_subscriber::{
     EnvFilter,
+    Layer,
     Registry,
     fmt,
 };
 
+use crate::telemetry::otel::{
+    self,
+    OtelConfig,
+    OtelGuard,
+};
 use crate::util::env_var::get_log_level as get_env_log_level;
 
 const MAX_FILE_SIZE: u64 = 10 * 1024 * 1024;
 const DEFAULT_FILTER: LevelFilter = LevelFilter::ERROR;
+/// Number of log events kept in memory for the crash bundles
+const MAX_RECENT_EVENTS: usize = 100;
 
 static Q_LOG_LEVEL_GLOBAL: Mutex<Option<String>> = Mutex::new(None);
 static MAX_LEVEL: Mutex<Option<LevelFilter>> = Mutex::new(None);
+/// Whether the spans of [otel::SPAN_TARGET] are exported, and so always let through
+static OTEL_EXPORT: AtomicBool = AtomicBool::new(false);
+static RECENT_EVENTS: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
 static ENV_FILTER_RELOADABLE_HANDLE: Mutex<Option<tracing_subscriber::reload::Handle<EnvFilter, Registry>>> =
     Mutex::new(None);
 
@@ -44,6 +73,8 @@ pub struct LogArgs<T: AsRef<Path>> {
     pub log_file_path: Option<T>,
     /// Whether we should delete the log file at each launch.
     pub delete_old_log_file: bool,
+    /// Where traces and metrics are exported to with OpenTelemetry. When not set, they aren't.
+    pub otel: Option<OtelConfig>,
 }
 
 /// The log guard maintains tracing guards which send log information to other threads.
@@ -55,6 +86,7 @@ pub struct LogGuard {
     _file_guard: Option<WorkerGuard>,
     _stdout_guard: Option<WorkerGuard>,
     _mcp_file_guard: Option<WorkerGuard>,
+    _otel_guard: Option<OtelGuard>,
 }
 
 /// Initialize our application level logging using the given LogArgs.
@@ -64,6 +96,19 @@ pub struct LogGuard {
 /// On success, this returns a guard which must be kept alive.
 #[inline]
 pub fn initialize_logging<T: AsRef<Path>>(args: LogArgs<T>) -> Result<LogGuard, Error> {
+    // The export layer is set up first, as the filter lets its spans through once it runs
+    let (otel_layer, _otel_guard) = match args.otel.as_ref().map(otel::init) {
+        Some(Ok((tracer, guard))) => {
+            OTEL_EXPORT.store(true, Ordering::Relaxed);
+            (Some(tracing_opentelemetry::layer().with_tracer(tracer)), Some(guard))
+        },
+        Some(Err(err)) => {
+            eprintln!("Failed to export traces and metrics with OpenTelemetry: {err}");
+            (None, None)
+        },
+        None => (None, None),
+    };
+
     let filter_layer = create_filter_layer();
     let (reloadable_filter_layer, reloadable_handle) = tracing_subscriber::reload::Layer::new(filter_layer);
     ENV_FILTER_RELOADABLE_HANDLE.lock().unwrap().replace(reloadable_handle);
@@ -164,8 +209,10 @@ pub fn initialize_logging<T: AsRef<Path>>(args: LogArgs<T>) -> Result<LogGuard,
     // Finally, initialize our logging
     let subscriber = tracing_subscriber::registry()
         .with(reloadable_filter_layer)
+        .with(RecentEventsLayer)
         .with(file_layer)
-        .with(stdout_layer);
+        .with(stdout_layer)
+        .with(otel_layer);
 
     if let Some(mcp_server_layer) = mcp_server_layer {
         subscriber.with(mcp_server_layer).init();
@@ -173,6 +220,7 @@ pub fn initialize_logging<T: AsRef<Path>>(args: LogArgs<T>) -> Result<LogGuard,
             _file_guard,
             _stdout_guard,
             _mcp_file_guard,
+            _otel_guard,
         });
     }
 
@@ -182,6 +230,7 @@ pub fn initialize_logging<T: AsRef<Path>>(args: LogArgs<T>) -> Result<LogGuard,
         _file_guard,
         _stdout_guard,
         _mcp_file_guard,
+        _otel_guard,
     })
 }
 
@@ -240,6 +289,56 @@ pub fn get_log_level_max() -> LevelFilter {
     }
 }
 
+/// The last log events, oldest first.
+pub fn recent_events() -> Vec<String> {
+    // Not waiting on the lock, as this is called when panicking, possibly while logging
+    match RECENT_EVENTS.try_lock() {
+        Ok(events) => events.iter().cloned().collect(),
+        Err(std::sync::TryLockError::Poisoned(events)) => events.into_inner().iter().cloned().collect(),
+        Err(std::sync::TryLockError::WouldBlock) => Vec::new(),
+    }
+}
+
+/// Keeps the last [MAX_RECENT_EVENTS] log events in memory.
+struct RecentEventsLayer;
+
+impl<S: Subscriber> Layer<S> for RecentEventsLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        let mut line = format!(
+            "{} {} {}:",
+            chrono::Utc::now().format("%Y-%m-%dT%H:%M:%S%.3fZ"),
+            metadata.level(),
+            metadata.target()
+        );
+        event.record(&mut EventVisitor(&mut line));
+
+        let mut events = RECENT_EVENTS.lock().unwrap_or_else(PoisonError::into_inner);
+        if events.len() == MAX_RECENT_EVENTS {
+            events.pop_front();
+        }
+        events.push_back(line);
+    }
+}
+
+struct EventVisitor<'a>(&'a mut String);
+
+impl Visit for EventVisitor<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "message" => write!(self.0, " {value}").ok(),
+            name => write!(self.0, " {name}={value:?}").ok(),
+        };
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        match field.name() {
+            "message" => write!(self.0, " {value:?}").ok(),
+            name => write!(self.0, " {name}={value:?}").ok(),
+        };
+    }
+}
+
 fn create_filter_layer() -> EnvFilter {
     let directive = Directive::from(DEFAULT_FILTER);
 
@@ -249,11 +348,18 @@ fn create_filter_layer() -> EnvFilter {
         .clone()
         .or_else(|| get_env_log_level(&crate::os::Env::new()).ok());
 
-    match log_level {
+    let filter = match log_level {
         Some(level) => EnvFilter::builder()
             .with_default_directive(directive)
             .parse_lossy(level),
         None => EnvFilter::default().add_directive(directive),
+    };
+    match OTEL_EXPORT.load(Ordering::Relaxed) {
+        true => match format!("{}=info", otel::SPAN_TARGET).parse() {
+            Ok(directive) => filter.add_directive(directive),
+            Err(_) => filter,
+        },
+        false => filter,
     }
 }
 
@@ -283,6 +389,7 @@ mod tests {
             log_to_stdout: true,
             log_file_path: Some(&log_path),
             delete_old_log_file: true,
+            otel: None,
         })
         .unwrap();
 
@@ -309,5 +416,13 @@ mod tests {
         ] {
             assert!(logs.contains(i));
         }
+
+        // The events are also kept in memory for the crash bundles
+        let events = recent_events();
+        assert!(
+            events
+                .iter()
+                .any(|event| event.contains("ERROR") && event.ends_with(" mno"))
+        );
     }
 }
@@ -37,6 +37,13 @@ fn main() -> Result<ExitCode> {
     };
 
     let verbose = parsed.verbose > 0;
+    let command = parsed
+        .subcommand
+        .as_ref()
+        .map_or_else(|| "chat".to_string(), ToString::to_string);
+    util::crash::set_command(&command);
+    util::crash::install_panic_hook();
+
     let runtime = tokio::runtime::Builder::new_multi_thread().enable_all().build()?;
     let result = runtime.block_on(parsed.execute());
 
@@ -49,6 +56,17 @@ fn main() -> Result<ExitCode> {
                 eprintln!("{} {err}", StyledText::error("error:"));
             }
 
+            // Errors ending chat sessions are unexpected, unlike those of the other commands
+            if command == "chat" {
+                if let Ok(path) = util::crash::write_error_bundle(&err) {
+                    eprintln!(
+                        "A crash report was written to {}\nReport the crash with: {} issue --crash",
+                        path.display(),
+                        util::CLI_BINARY_NAME
+                    );
+                }
+            }
+
             Ok(ExitCode::FAILURE)
         },
     }
@@ -110,7 +110,7 @@ macro_rules! paginated_fetch {
 }
 
 /// Substitutes environment variables in the format ${env:VAR_NAME} with their actual values
-fn substitute_env_vars(input: &str, env: &crate::os::Env) -> String {
+pub fn substitute_env_vars(input: &str, env: &crate::os::Env) -> String {
     // Create a regex to match ${env:VAR_NAME} pattern
     let re = Regex::new(r"\$\{env:([^}]+)\}").unwrap();
 
@@ -10,6 +10,8 @@ use sysinfo::{
 use time::OffsetDateTime;
 use time::format_description::well_known::Rfc3339;
 
+use crate::cli::chat::error_hints::ErrorCluster;
+use crate::database::Database;
 use crate::os::Env;
 use crate::telemetry::InstallMethod;
 use crate::util::consts::build::HASH;
@@ -196,6 +198,23 @@ impl CurrentEnvironment {
     }
 }
 
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ErrorClusterDiagnostic {
+    #[serde(flatten)]
+    pub cluster: ErrorCluster,
+    pub hint: Option<&'static str>,
+}
+
+/// The reason code of an error cluster as reported. Some reason codes are whole error messages,
+/// which may include paths, hosts or account details: only the ones that are codes are kept.
+fn redact_reason_code(reason_code: &str) -> String {
+    match !reason_code.is_empty() && reason_code.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        true => reason_code.to_string(),
+        false => "<redacted>".to_string(),
+    }
+}
+
 #[derive(Clone, Debug, Serialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct Diagnostics {
@@ -205,15 +224,34 @@ pub struct Diagnostics {
     pub environment: CurrentEnvironment,
     #[serde(flatten)]
     pub environment_variables: EnvVarDiagnostic,
+    /// Errors hit in chat, clustered by reason code and status code
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<ErrorClusterDiagnostic>,
 }
 
 impl Diagnostics {
-    pub async fn new(env: &Env) -> Diagnostics {
+    pub async fn new(env: &Env, database: &Database) -> Diagnostics {
+        let errors = database
+            .get_error_clusters()
+            .ok()
+            .flatten()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|cluster| ErrorClusterDiagnostic {
+                hint: cluster.hint(),
+                cluster: ErrorCluster {
+                    reason_code: redact_reason_code(&cluster.reason_code),
+                    ..cluster
+                },
+            })
+            .collect();
+
         Diagnostics {
             build_details: BuildDetails::new(),
             system_info: SystemInfo::new(),
             environment: CurrentEnvironment::new(env).await,
             environment_variables: EnvVarDiagnostic::new(),
+            errors,
         }
     }
 
@@ -225,12 +263,30 @@ impl Diagnostics {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::os::Os;
 
     #[tokio::test]
     async fn test_diagnostics_user_readable() {
-        let env = Env::new();
-        let diagnostics = Diagnostics::new(&env).await;
+        let os = Os::new().await.unwrap();
+        let diagnostics = Diagnostics::new(&os.env, &os.database).await;
         let toml = diagnostics.user_readable().unwrap();
         assert!(!toml.is_empty());
+
+        crate::cli::chat::error_hints::record_error(&os.database, "QuotaBreachError", Some(429)).unwrap();
+        let diagnostics = Diagnostics::new(&os.env, &os.database).await;
+        let toml = diagnostics.user_readable().unwrap();
+        assert!(toml.contains("reason-code = \"QuotaBreachError\""), "{toml}");
+        assert!(toml.contains("hint = "), "{toml}");
+
+        crate::cli::chat::error_hints::record_error(
+            &os.database,
+            "dispatch failure: connect to proxy.corp.example.com failed",
+            None,
+        )
+        .unwrap();
+        let diagnostics = Diagnostics::new(&os.env, &os.database).await;
+        let toml = diagnostics.user_readable().unwrap();
+        assert!(!toml.contains("proxy.corp.example.com"), "{toml}");
+        assert!(toml.contains("reason-code = \"<redacted>\""), "{toml}");
     }
 }
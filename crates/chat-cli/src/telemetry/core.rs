@@ -26,6 +26,7 @@ use crate::telemetry::definitions::metrics::{
     CodewhispererterminalAuthFailed,
     CodewhispererterminalChatSlashCommandExecuted,
     CodewhispererterminalCliSubcommandExecuted,
+    CodewhispererterminalHistoryCompacted,
     CodewhispererterminalMcpServerInit,
     CodewhispererterminalRefreshCredentials,
     CodewhispererterminalToolUseSuggested,
@@ -307,6 +308,30 @@ impl Event {
                 }
                 .into_metric_datum(),
             ),
+            EventType::HistoryCompacted {
+                conversation_id,
+                args:
+                    HistoryCompactedArgs {
+                        trigger,
+                        tokens_before,
+                        tokens_after,
+                        messages_folded,
+                        duration_ms,
+                    },
+            } => Some(
+                CodewhispererterminalHistoryCompacted {
+                    create_time: self.created_time,
+                    credential_start_url: self.credential_start_url.map(Into::into),
+                    value: None,
+                    amazonq_conversation_id: Some(conversation_id.into()),
+                    codewhispererterminal_compaction_trigger: Some(trigger.into()),
+                    codewhispererterminal_tokens_before_compaction: Some(tokens_before.into()),
+                    codewhispererterminal_tokens_after_compaction: Some(tokens_after.into()),
+                    codewhispererterminal_messages_folded: Some(messages_folded.into()),
+                    codewhispererterminal_compaction_duration_ms: Some(duration_ms.into()),
+                }
+                .into_metric_datum(),
+            ),
             EventType::ToolUseSuggested {
                 conversation_id,
                 utterance_id,
@@ -327,6 +352,7 @@ impl Event {
                 turn_duration,
                 aws_service_name,
                 aws_operation_name,
+                subagent_name,
             } => Some(
                 CodewhispererterminalToolUseSuggested {
                     create_time: self.created_time,
@@ -359,6 +385,7 @@ impl Event {
                     codewhispererterminal_client_application: self.client_application.map(Into::into),
                     codewhispererterminal_aws_service_name: aws_service_name.map(Into::into),
                     codewhispererterminal_aws_operation_name: aws_operation_name.map(Into::into),
+                    codewhispererterminal_subagent_name: subagent_name.map(Into::into),
                 }
                 .into_metric_datum(),
             ),
@@ -576,6 +603,18 @@ pub struct ChatAddedMessageParams {
     pub message_meta_tags: Vec<MessageMetaTag>,
 }
 
+/// Savings of a compaction of the conversation history.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize, Default)]
+pub struct HistoryCompactedArgs {
+    /// What triggered the compaction: manual, overflow or background
+    pub trigger: String,
+    pub tokens_before: i64,
+    pub tokens_after: i64,
+    /// Number of history entries replaced by the summary
+    pub messages_folded: i64,
+    pub duration_ms: i64,
+}
+
 /// Optional fields for tangent mode session telemetry event.
 #[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize, Default)]
 pub struct TangentModeSessionArgs {
@@ -658,6 +697,10 @@ pub enum EventType {
         result: TelemetryResult,
         args: TangentModeSessionArgs,
     },
+    HistoryCompacted {
+        conversation_id: String,
+        args: HistoryCompactedArgs,
+    },
     ToolUseSuggested {
         conversation_id: String,
         utterance_id: Option<String>,
@@ -678,6 +721,8 @@ pub enum EventType {
         turn_duration: Option<Duration>,
         aws_service_name: Option<String>,
         aws_operation_name: Option<String>,
+        /// Sub-agent of the agent config the session runs as, for attributing its tool uses
+        subagent_name: Option<String>,
     },
     AgentContribution {
         conversation_id: String,
@@ -747,6 +792,7 @@ pub struct ToolUseEventBuilder {
     pub turn_duration: Option<Duration>,
     pub aws_service_name: Option<String>,
     pub aws_operation_name: Option<String>,
+    pub subagent_name: Option<String>,
 }
 
 impl ToolUseEventBuilder {
@@ -771,6 +817,7 @@ impl ToolUseEventBuilder {
             turn_duration: None,
             aws_service_name: None,
             aws_operation_name: None,
+            subagent_name: None,
         }
     }
 
@@ -788,6 +835,11 @@ impl ToolUseEventBuilder {
         self.tool_name.replace(name);
         self
     }
+
+    pub fn subagent_name(mut self, name: Option<String>) -> Self {
+        self.subagent_name = name;
+        self
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
@@ -0,0 +1,178 @@
+//! Local-only telemetry. With `telemetry.localOnly`, telemetry events are appended to a JSON Lines
+//! file on this machine and never sent: `q telemetry export` prints them for whoever wants the
+//! metrics themselves. Once the file reaches [MAX_FILE_SIZE], it is rotated out to `<file>.1`,
+//! replacing the one rotated out before.
+
+use std::path::{
+    Path,
+    PathBuf,
+};
+use std::time::Duration;
+
+use chrono::{
+    DateTime,
+    Utc,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use tokio::io::AsyncWriteExt;
+
+use super::core::Event;
+
+/// Size of the file of the recorded events it is rotated out at.
+const MAX_FILE_SIZE: u64 = 10 * 1024 * 1024;
+
+/// A recorded event, along with the time it was created at.
+#[derive(Debug, Serialize)]
+struct Record<'a> {
+    time: DateTime<Utc>,
+    #[serde(flatten)]
+    event: &'a Event,
+}
+
+/// The part of a record the export filters on.
+#[derive(Debug, Deserialize)]
+struct RecordTime {
+    time: DateTime<Utc>,
+}
+
+/// The line of `event` in the file of the recorded events.
+fn record_line(event: &Event) -> serde_json::Result<String> {
+    let time = event.created_time.map_or_else(Utc::now, DateTime::from);
+    Ok(format!("{}\n", serde_json::to_string(&Record { time, event })?))
+}
+
+/// The file the events recorded at `path` are rotated out to.
+fn rotated_path(path: &Path) -> PathBuf {
+    let mut rotated = path.as_os_str().to_owned();
+    rotated.push(".1");
+    PathBuf::from(rotated)
+}
+
+/// Appends `event` to the file of the recorded events at `path`, rotating the file out first once
+/// it reached [MAX_FILE_SIZE].
+pub async fn record(path: &Path, event: &Event) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    if tokio::fs::metadata(path)
+        .await
+        .is_ok_and(|metadata| metadata.len() >= MAX_FILE_SIZE)
+    {
+        tokio::fs::rename(path, rotated_path(path)).await?;
+    }
+    let line = record_line(event)?;
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await?;
+    file.write_all(line.as_bytes()).await?;
+    // The write may still be in flight otherwise when the file is dropped
+    file.flush().await?;
+    Ok(())
+}
+
+/// The events recorded at `path`, the ones rotated out first, or `None` if none were recorded.
+pub async fn read_events(path: &Path) -> std::io::Result<Option<String>> {
+    let mut events = None::<String>;
+    for path in [rotated_path(path), path.to_path_buf()] {
+        match tokio::fs::read_to_string(&path).await {
+            Ok(contents) => {
+                let events = events.get_or_insert_default();
+                if !events.is_empty() && !events.ends_with('\n') {
+                    events.push('\n');
+                }
+                events.push_str(&contents);
+            },
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => (),
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(events)
+}
+
+/// Lines of the recorded events created at or after `since`. Lines that aren't records are
+/// skipped.
+pub fn events_since(contents: &str, since: DateTime<Utc>) -> Vec<&str> {
+    contents
+        .lines()
+        .filter(|line| serde_json::from_str::<RecordTime>(line).is_ok_and(|record| record.time >= since))
+        .collect()
+}
+
+/// Parses a period such as `7d`, `12h`, `30m`, `45s` or `2w`.
+pub fn parse_period(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let unit_start = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+    let (count, unit) = value.split_at(unit_start);
+    let Ok(count) = count.parse::<u64>() else {
+        return Err(format!("'{value}' is not a period such as 7d, 12h or 30m"));
+    };
+    let unit_secs = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return Err(format!("'{value}' is not a period such as 7d, 12h or 30m")),
+    };
+    Ok(Duration::from_secs(count.saturating_mul(unit_secs)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::telemetry::EventType;
+
+    #[test]
+    fn test_parse_period() {
+        assert_eq!(parse_period("7d"), Ok(Duration::from_secs(7 * 24 * 60 * 60)));
+        assert_eq!(parse_period("12h"), Ok(Duration::from_secs(12 * 60 * 60)));
+        assert_eq!(parse_period("2w"), Ok(Duration::from_secs(14 * 24 * 60 * 60)));
+        assert!(parse_period("7").is_err());
+        assert!(parse_period("d").is_err());
+        assert!(parse_period("7 days").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_record_and_export() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("telemetry/events.jsonl");
+        let now = Utc::now();
+        let mut old = Event::new(EventType::UserLoggedIn {});
+        old.created_time = Some((now - chrono::TimeDelta::days(10)).into());
+        record(&path, &old).await.unwrap();
+        record(&path, &Event::new(EventType::DailyHeartbeat {})).await.unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(contents.lines().count(), 2);
+        let recent = events_since(&contents, now - chrono::TimeDelta::days(7));
+        assert_eq!(recent.len(), 1);
+        let event = serde_json::from_str::<serde_json::Value>(recent[0]).unwrap();
+        assert_eq!(event["type"], "dailyHeartbeat");
+
+        assert_eq!(events_since("not a record\n", DateTime::<Utc>::MIN_UTC).len(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.jsonl");
+        assert_eq!(read_events(&path).await.unwrap(), None);
+
+        record(&path, &Event::new(EventType::UserLoggedIn {})).await.unwrap();
+        let file = std::fs::File::options().append(true).open(&path).unwrap();
+        file.set_len(MAX_FILE_SIZE).unwrap();
+        record(&path, &Event::new(EventType::DailyHeartbeat {})).await.unwrap();
+
+        assert_eq!(std::fs::metadata(rotated_path(&path)).unwrap().len(), MAX_FILE_SIZE);
+        assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 1);
+        let events = read_events(&path).await.unwrap().unwrap();
+        let records = events_since(&events, DateTime::<Utc>::MIN_UTC);
+        assert_eq!(records.len(), 2);
+        assert!(records[0].contains("userLoggedIn"), "the rotated events come first");
+    }
+}
@@ -3,14 +3,19 @@ pub mod core;
 pub mod definitions;
 pub mod endpoint;
 mod install_method;
+pub mod local;
+pub mod otel;
+pub mod policy;
 
 use core::{
     AgentConfigInitArgs,
     ChatAddedMessageParams,
+    HistoryCompactedArgs,
     RecordUserTurnCompletionArgs,
     TangentModeSessionArgs,
     ToolUseEventBuilder,
 };
+use std::path::PathBuf;
 use std::str::FromStr;
 
 use amzn_codewhisperer_client::types::{
@@ -39,6 +44,10 @@ pub use install_method::{
     InstallMethod,
     get_install_method,
 };
+use policy::{
+    TelemetryMode,
+    TelemetryPolicy,
+};
 use tokio::sync::mpsc;
 use tokio::task::JoinHandle;
 use tokio::time::error::Elapsed;
@@ -75,6 +84,7 @@ pub use crate::telemetry::core::{
     TelemetryResult,
 };
 use crate::util::env_var::get_cli_client_application;
+use crate::util::paths::GlobalPaths;
 use crate::util::system_info::os_version;
 
 #[derive(thiserror::Error, Debug)]
@@ -359,6 +369,17 @@ impl TelemetryThread {
         Ok(self.tx.send(telemetry_event)?)
     }
 
+    pub async fn send_history_compacted(
+        &self,
+        database: &Database,
+        conversation_id: String,
+        args: HistoryCompactedArgs,
+    ) -> Result<(), TelemetryError> {
+        let mut telemetry_event = Event::new(EventType::HistoryCompacted { conversation_id, args });
+        set_event_metadata(database, &mut telemetry_event).await;
+        Ok(self.tx.send(telemetry_event)?)
+    }
+
     pub async fn send_tool_use_suggested(
         &self,
         database: &Database,
@@ -384,6 +405,7 @@ impl TelemetryThread {
             turn_duration: event.turn_duration,
             aws_service_name: event.aws_service_name,
             aws_operation_name: event.aws_operation_name,
+            subagent_name: event.subagent_name,
         });
         set_event_metadata(database, &mut telemetry_event).await;
 
@@ -509,13 +531,16 @@ struct TelemetryClient {
     telemetry_enabled: bool,
     codewhisperer_client: Option<ApiClient>,
     toolkit_telemetry_client: Option<ToolkitTelemetryClient>,
+    /// File the events are recorded to instead of being sent, with `telemetry.localOnly`
+    local_events: Option<PathBuf>,
+    policy: TelemetryPolicy,
 }
 
 impl TelemetryClient {
     async fn new(env: &Env, fs: &Fs, database: &mut Database) -> Result<Self, TelemetryError> {
-        let telemetry_enabled = !cfg!(test)
-            && !crate::util::env_var::is_telemetry_disabled()
-            && database.settings.get_bool(Setting::TelemetryEnabled).unwrap_or(true);
+        let mode = TelemetryMode::load(&database.settings);
+        let local_only = !cfg!(test) && mode == TelemetryMode::LocalOnly;
+        let telemetry_enabled = !cfg!(test) && mode == TelemetryMode::Sent;
 
         // If telemetry is disabled we do not emit using toolkit_telemetry
         let toolkit_telemetry_client = if telemetry_enabled {
@@ -564,26 +589,49 @@ impl TelemetryClient {
             })
         }
 
-        // cw telemetry is only available with bearer token auth.
-        let codewhisperer_client = if crate::util::env_var::is_sigv4_enabled(&Env::new()) {
+        // cw telemetry is only available with bearer token auth. Local-only telemetry doesn't even
+        // send the opt out.
+        let codewhisperer_client = if local_only || crate::util::env_var::is_sigv4_enabled(&Env::new()) {
             None
         } else {
             Some(ApiClient::new(env, fs, database, None).await?)
         };
 
+        let local_events = if local_only {
+            match GlobalPaths::telemetry_events_path_static() {
+                Ok(path) => Some(fs.chroot_path(path)),
+                Err(err) => {
+                    error!(%err, "Failed to find the file of the local telemetry events");
+                    None
+                },
+            }
+        } else {
+            None
+        };
+
         Ok(Self {
             client_id: client_id(env, database, telemetry_enabled)?,
             telemetry_enabled,
             toolkit_telemetry_client,
             codewhisperer_client,
+            local_events,
+            policy: TelemetryPolicy::load(&database.settings),
         })
     }
 
     /// Sends a telemetry event to both the CW and toolkit API's. If the clients do not exist, then
-    /// telemetry is not sent.
+    /// telemetry is not sent. Local-only telemetry records the event instead.
     ///
-    /// See [TelemetryClient::new] for which conditions the clients are created for.
-    async fn send_event(&self, event: Event) {
+    /// See [TelemetryClient::new] for which conditions the clients are created for. The fields
+    /// excluded by the telemetry policy are removed first, whichever way the event goes.
+    async fn send_event(&self, mut event: Event) {
+        self.policy.apply(&mut event);
+        if let Some(path) = &self.local_events {
+            if let Err(err) = local::record(path, &event).await {
+                error!(%err, "Failed to record telemetry event locally");
+            }
+            return;
+        }
         self.send_cw_telemetry_event(&event).await;
         self.send_telemetry_toolkit_metric(event).await;
     }
@@ -0,0 +1,248 @@
+//! OpenTelemetry export of traces and metrics, for teams watching the agents they run in their own
+//! observability stack. Requests to the model, tool uses, MCP calls and hook runs are traced as
+//! spans of the [SPAN_TARGET] target and timed as histograms, both exported with OTLP over HTTP.
+//!
+//! The export is enabled by the `telemetry.otlpEndpoint` setting or the standard
+//! `OTEL_EXPORTER_OTLP_ENDPOINT` environment variables, independently of the telemetry sent to
+//! AWS.
+
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use opentelemetry::KeyValue;
+use opentelemetry::metrics::{
+    Histogram,
+    MeterProvider as _,
+};
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_otlp::{
+    ExporterBuildError,
+    WithExportConfig,
+    WithHttpConfig,
+};
+use opentelemetry_sdk::Resource;
+use opentelemetry_sdk::metrics::SdkMeterProvider;
+use opentelemetry_sdk::trace::{
+    SdkTracer,
+    SdkTracerProvider,
+};
+use tracing::error;
+
+use crate::database::settings::{
+    Setting,
+    Settings,
+};
+use crate::os::Env;
+
+/// Target of the spans exported, which the log filter always lets through when exporting
+pub const SPAN_TARGET: &str = "otel";
+
+/// Name of the service the traces and metrics are of, unless `OTEL_SERVICE_NAME` is set
+const SERVICE_NAME: &str = "q-cli";
+const INSTRUMENTATION_SCOPE: &str = "q_cli";
+
+/// Environment variables enabling the export without the setting
+const ENDPOINT_VARS: &[&str] = &[
+    "OTEL_EXPORTER_OTLP_ENDPOINT",
+    "OTEL_EXPORTER_OTLP_TRACES_ENDPOINT",
+    "OTEL_EXPORTER_OTLP_METRICS_ENDPOINT",
+];
+
+static METRICS: OnceLock<Metrics> = OnceLock::new();
+
+/// Where the traces and metrics are exported to.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OtelConfig {
+    /// Base URL of the OTLP endpoint from the settings, the signals being sent to `/v1/traces` and
+    /// `/v1/metrics` under it. `None` leaves the endpoint to the `OTEL_EXPORTER_OTLP_*` environment
+    /// variables.
+    pub endpoint: Option<String>,
+    /// Headers sent along, e.g. for authentication
+    pub headers: HashMap<String, String>,
+}
+
+impl OtelConfig {
+    /// The export configured in the settings or the environment, if any.
+    pub fn load(env: &Env, settings: &Settings) -> Option<Self> {
+        let endpoint = settings
+            .get_string(Setting::TelemetryOtlpEndpoint)
+            .filter(|endpoint| !endpoint.trim().is_empty());
+        if endpoint.is_none() && !ENDPOINT_VARS.iter().any(|var| env.get(var).is_ok()) {
+            return None;
+        }
+        let headers = settings
+            .get_string(Setting::TelemetryOtlpHeaders)
+            .map(|headers| parse_headers(&headers))
+            .unwrap_or_default();
+        Some(Self { endpoint, headers })
+    }
+
+    fn signal_endpoint(&self, signal: &str) -> Option<String> {
+        self.endpoint
+            .as_ref()
+            .map(|endpoint| format!("{}/v1/{signal}", endpoint.trim_end_matches('/')))
+    }
+}
+
+/// Parses headers written as `key1=value1,key2=value2`, like `OTEL_EXPORTER_OTLP_HEADERS`.
+pub fn parse_headers(headers: &str) -> HashMap<String, String> {
+    headers
+        .split(',')
+        .filter_map(|header| {
+            let (key, value) = header.split_once('=')?;
+            let key = key.trim();
+            (!key.is_empty()).then(|| (key.to_string(), value.trim().to_string()))
+        })
+        .collect()
+}
+
+/// What is timed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    /// Request to the model, until its response starts
+    Request,
+    Tool,
+    McpCall,
+    Hook,
+}
+
+#[derive(Debug)]
+struct Metrics {
+    request_duration: Histogram<f64>,
+    tool_duration: Histogram<f64>,
+    mcp_call_duration: Histogram<f64>,
+    hook_duration: Histogram<f64>,
+}
+
+/// Records how long `operation` took and whether it succeeded, when exporting.
+pub fn record(operation: Operation, duration: Duration, success: bool, attributes: &[KeyValue]) {
+    let Some(metrics) = METRICS.get() else {
+        return;
+    };
+    let histogram = match operation {
+        Operation::Request => &metrics.request_duration,
+        Operation::Tool => &metrics.tool_duration,
+        Operation::McpCall => &metrics.mcp_call_duration,
+        Operation::Hook => &metrics.hook_duration,
+    };
+    let mut attributes = attributes.to_vec();
+    attributes.push(KeyValue::new("success", success));
+    histogram.record(duration.as_secs_f64(), &attributes);
+}
+
+/// Keeps the export running. Dropping it flushes what wasn't exported yet.
+#[must_use]
+#[derive(Debug)]
+pub struct OtelGuard {
+    tracer_provider: SdkTracerProvider,
+    meter_provider: SdkMeterProvider,
+}
+
+impl Drop for OtelGuard {
+    fn drop(&mut self) {
+        if let Err(err) = self.tracer_provider.shutdown() {
+            error!(%err, "Failed to export the remaining traces");
+        }
+        if let Err(err) = self.meter_provider.shutdown() {
+            error!(%err, "Failed to export the remaining metrics");
+        }
+    }
+}
+
+/// Starts exporting to `config`, returning the tracer the spans are exported with.
+pub fn init(config: &OtelConfig) -> Result<(SdkTracer, OtelGuard), ExporterBuildError> {
+    let service_name = std::env::var("OTEL_SERVICE_NAME").unwrap_or_else(|_| SERVICE_NAME.to_string());
+    let resource = Resource::builder()
+        .with_service_name(service_name)
+        .with_attribute(KeyValue::new("service.version", env!("CARGO_PKG_VERSION")))
+        .build();
+
+    let mut span_exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .with_headers(config.headers.clone());
+    if let Some(endpoint) = config.signal_endpoint("traces") {
+        span_exporter = span_exporter.with_endpoint(endpoint);
+    }
+    let tracer_provider = SdkTracerProvider::builder()
+        .with_batch_exporter(span_exporter.build()?)
+        .with_resource(resource.clone())
+        .build();
+
+    let mut metric_exporter = opentelemetry_otlp::MetricExporter::builder()
+        .with_http()
+        .with_headers(config.headers.clone());
+    if let Some(endpoint) = config.signal_endpoint("metrics") {
+        metric_exporter = metric_exporter.with_endpoint(endpoint);
+    }
+    let meter_provider = SdkMeterProvider::builder()
+        .with_periodic_exporter(metric_exporter.build()?)
+        .with_resource(resource)
+        .build();
+
+    let meter = meter_provider.meter(INSTRUMENTATION_SCOPE);
+    let histogram = |name: &'static str, description: &'static str| {
+        meter
+            .f64_histogram(name)
+            .with_unit("s")
+            .with_description(description)
+            .build()
+    };
+    METRICS.get_or_init(|| Metrics {
+        request_duration: histogram("q.request.duration", "Time until the model starts responding"),
+        tool_duration: histogram("q.tool.duration", "Duration of tool uses"),
+        mcp_call_duration: histogram("q.mcp.call.duration", "Duration of MCP tool calls"),
+        hook_duration: histogram("q.hook.duration", "Duration of hook runs"),
+    });
+
+    let tracer = tracer_provider.tracer(INSTRUMENTATION_SCOPE);
+    Ok((tracer, OtelGuard {
+        tracer_provider,
+        meter_provider,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_headers() {
+        assert_eq!(
+            parse_headers("x-api-key=secret, x-team = platform,invalid,=empty"),
+            HashMap::from([
+                ("x-api-key".to_string(), "secret".to_string()),
+                ("x-team".to_string(), "platform".to_string()),
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_load() {
+        let env = Env::from_slice(&[]);
+        let mut settings = Settings::default();
+        assert_eq!(OtelConfig::load(&env, &settings), None);
+        assert_eq!(
+            OtelConfig::load(
+                &Env::from_slice(&[("OTEL_EXPORTER_OTLP_ENDPOINT", "http://collector:4318")]),
+                &settings
+            ),
+            Some(OtelConfig::default())
+        );
+
+        settings
+            .set(Setting::TelemetryOtlpEndpoint, "http://collector:4318/")
+            .await
+            .unwrap();
+        settings
+            .set(Setting::TelemetryOtlpHeaders, "x-api-key=secret")
+            .await
+            .unwrap();
+        let config = OtelConfig::load(&env, &settings).unwrap();
+        assert_eq!(
+            config.signal_endpoint("traces").as_deref(),
+            Some("http://collector:4318/v1/traces")
+        );
+        assert_eq!(config.headers.get("x-api-key").map(String::as_str), Some("secret"));
+    }
+}
@@ -0,0 +1,299 @@
+//! Which fields telemetry events include, from the `telemetry.prompts`, `telemetry.toolNames`,
+//! `telemetry.modelIds` and `telemetry.filePaths` settings. The policy is applied to every event
+//! before it is sent or recorded locally, see [TelemetryPolicy::apply].
+
+use std::sync::LazyLock;
+
+use regex::Regex;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use tracing::warn;
+
+use super::core::{
+    Event,
+    EventType,
+};
+use crate::database::settings::{
+    Setting,
+    Settings,
+};
+
+/// What replaces the names of the MCP servers excluded from events, the field being required.
+const REDACTED: &str = "redacted";
+
+/// Absolute, home relative and relative paths, with either separator.
+static PATH: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?:[A-Za-z]:|~|\.{1,2})?[\w.@+-]*(?:[/\\]+[\w.@+-]+)+[/\\]?").unwrap());
+
+/// Whether telemetry is sent, recorded locally or neither.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TelemetryMode {
+    Sent,
+    LocalOnly,
+    Disabled,
+}
+
+impl TelemetryMode {
+    pub fn load(settings: &Settings) -> Self {
+        if crate::util::env_var::is_telemetry_disabled() {
+            Self::Disabled
+        } else if settings.get_bool(Setting::TelemetryLocalOnly).unwrap_or(false) {
+            Self::LocalOnly
+        } else if settings.get_bool(Setting::TelemetryEnabled).unwrap_or(true) {
+            Self::Sent
+        } else {
+            Self::Disabled
+        }
+    }
+}
+
+/// What telemetry includes about prompts, whose contents are never included.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PromptPolicy {
+    /// The lengths of prompts, responses and context files
+    #[default]
+    Lengths,
+    None,
+}
+
+/// The fields telemetry events include.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TelemetryPolicy {
+    pub prompts: PromptPolicy,
+    /// Names of tools and MCP servers
+    pub tool_names: bool,
+    pub model_ids: bool,
+    /// Paths in the reasons of errors
+    pub file_paths: bool,
+}
+
+impl Default for TelemetryPolicy {
+    fn default() -> Self {
+        Self {
+            prompts: PromptPolicy::Lengths,
+            tool_names: true,
+            model_ids: true,
+            file_paths: true,
+        }
+    }
+}
+
+impl TelemetryPolicy {
+    pub fn load(settings: &Settings) -> Self {
+        let default = Self::default();
+        let prompts = match settings.get_string(Setting::TelemetryPrompts).as_deref() {
+            None | Some("lengths") => PromptPolicy::Lengths,
+            Some("none") => PromptPolicy::None,
+            // The strictest policy for values that can't be honored
+            Some(value) => {
+                warn!(%value, "Invalid value of {}, excluding prompt lengths", Setting::TelemetryPrompts);
+                PromptPolicy::None
+            },
+        };
+        Self {
+            prompts,
+            tool_names: settings
+                .get_bool(Setting::TelemetryToolNames)
+                .unwrap_or(default.tool_names),
+            model_ids: settings
+                .get_bool(Setting::TelemetryModelIds)
+                .unwrap_or(default.model_ids),
+            file_paths: settings
+                .get_bool(Setting::TelemetryFilePaths)
+                .unwrap_or(default.file_paths),
+        }
+    }
+
+    /// Removes the fields of `event` the policy excludes.
+    pub fn apply(&self, event: &mut Event) {
+        let prompt_lengths = self.prompts == PromptPolicy::Lengths;
+        let clear = |include: bool, field: &mut Option<String>| {
+            if !include {
+                *field = None;
+            }
+        };
+        let scrub = |field: &mut Option<String>| {
+            if !self.file_paths {
+                if let Some(text) = field {
+                    *text = PATH.replace_all(text, "<path>").into_owned();
+                }
+            }
+        };
+
+        match &mut event.ty {
+            EventType::ChatStart { model, .. } | EventType::ChatEnd { model, .. } => clear(self.model_ids, model),
+            EventType::ChatAddedMessage { data, .. } => {
+                clear(self.model_ids, &mut data.model);
+                clear(self.tool_names, &mut data.tool_name);
+                scrub(&mut data.reason_desc);
+                if !prompt_lengths {
+                    data.context_file_length = None;
+                    data.assistant_response_length = None;
+                    // The number of chunks gives the length of the response away
+                    data.time_between_chunks_ms = None;
+                }
+            },
+            EventType::RecordUserTurnCompletion { args, .. } => {
+                scrub(&mut args.reason_desc);
+                if !prompt_lengths {
+                    args.user_prompt_length = 0;
+                    args.assistant_response_length = 0;
+                }
+            },
+            EventType::ToolUseSuggested {
+                tool_name,
+                model,
+                reason_desc,
+                ..
+            } => {
+                clear(self.tool_names, tool_name);
+                clear(self.model_ids, model);
+                scrub(reason_desc);
+            },
+            EventType::AgentContribution { tool_name, .. } => clear(self.tool_names, tool_name),
+            EventType::McpServerInit {
+                server_name,
+                init_failure_reason,
+                all_tool_names,
+                loaded_tool_names,
+                ..
+            } => {
+                if !self.tool_names {
+                    *server_name = REDACTED.to_string();
+                    *all_tool_names = None;
+                    *loaded_tool_names = None;
+                }
+                scrub(init_failure_reason);
+            },
+            EventType::MessageResponseError {
+                reason_desc,
+                context_file_length,
+                ..
+            } => {
+                scrub(reason_desc);
+                if !prompt_lengths {
+                    *context_file_length = None;
+                }
+            },
+            EventType::ChatSlashCommandExecuted { reason, .. } | EventType::RefreshCredentials { reason, .. } => {
+                scrub(reason);
+            },
+            EventType::UserLoggedIn {}
+            | EventType::AuthFailed { .. }
+            | EventType::CliSubcommandExecuted { .. }
+            | EventType::TangentModeSession { .. }
+            | EventType::HistoryCompacted { .. }
+            | EventType::AgentConfigInit { .. }
+            | EventType::DidSelectProfile { .. }
+            | EventType::ProfileState { .. }
+            | EventType::DailyHeartbeat {} => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::telemetry::TelemetryResult;
+    use crate::telemetry::core::ChatAddedMessageParams;
+
+    const STRICT: TelemetryPolicy = TelemetryPolicy {
+        prompts: PromptPolicy::None,
+        tool_names: false,
+        model_ids: false,
+        file_paths: false,
+    };
+
+    #[test]
+    fn test_apply_chat_added_message() {
+        let mut event = Event::new(EventType::ChatAddedMessage {
+            conversation_id: "conversation".to_string(),
+            result: TelemetryResult::Failed,
+            data: ChatAddedMessageParams {
+                model: Some("claude-sonnet-4".to_string()),
+                tool_name: Some("fs_read".to_string()),
+                reason_desc: Some("failed to read /home/alice/notes.md: denied".to_string()),
+                context_file_length: Some(120),
+                assistant_response_length: Some(42),
+                time_between_chunks_ms: Some(vec![1.0, 2.0]),
+                ..Default::default()
+            },
+        });
+        let unchanged = event.clone();
+        TelemetryPolicy::default().apply(&mut event);
+        assert_eq!(event, unchanged);
+
+        STRICT.apply(&mut event);
+        let EventType::ChatAddedMessage { data, .. } = event.ty else {
+            unreachable!()
+        };
+        assert_eq!(data.model, None);
+        assert_eq!(data.tool_name, None);
+        assert_eq!(data.reason_desc.as_deref(), Some("failed to read <path>: denied"));
+        assert_eq!(data.context_file_length, None);
+        assert_eq!(data.assistant_response_length, None);
+        assert_eq!(data.time_between_chunks_ms, None);
+    }
+
+    #[test]
+    fn test_apply_mcp_server_init() {
+        let mut event = Event::new(EventType::McpServerInit {
+            conversation_id: "conversation".to_string(),
+            server_name: "internal-wiki".to_string(),
+            init_failure_reason: Some(r"C:\Users\alice\server.exe not found".to_string()),
+            number_of_tools: 1,
+            all_tool_names: Some("search".to_string()),
+            loaded_tool_names: Some("search".to_string()),
+            all_tools_count: 1,
+        });
+        STRICT.apply(&mut event);
+        let EventType::McpServerInit {
+            server_name,
+            init_failure_reason,
+            all_tool_names,
+            ..
+        } = event.ty
+        else {
+            unreachable!()
+        };
+        assert_eq!(server_name, REDACTED);
+        assert_eq!(init_failure_reason.as_deref(), Some("<path> not found"));
+        assert_eq!(all_tool_names, None);
+    }
+
+    #[test]
+    fn test_paths() {
+        for (text, expected) in [
+            ("open ~/.aws/amazonq/agents/x.json", "open <path>"),
+            ("no such file: ./src/main.rs", "no such file: <path>"),
+            ("read src/lib.rs and /etc/hosts", "read <path> and <path>"),
+            ("timed out after 5s", "timed out after 5s"),
+        ] {
+            assert_eq!(PATH.replace_all(text, "<path>"), expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_load() {
+        let mut settings = Settings::new().await.unwrap();
+        assert_eq!(TelemetryPolicy::load(&settings), TelemetryPolicy::default());
+        assert_eq!(TelemetryMode::load(&settings), TelemetryMode::Sent);
+
+        settings.set(Setting::TelemetryPrompts, "none").await.unwrap();
+        settings.set(Setting::TelemetryModelIds, false).await.unwrap();
+        settings.set(Setting::TelemetryLocalOnly, true).await.unwrap();
+        let policy = TelemetryPolicy::load(&settings);
+        assert_eq!(policy.prompts, PromptPolicy::None);
+        assert!(!policy.model_ids);
+        assert!(policy.tool_names);
+        assert_eq!(TelemetryMode::load(&settings), TelemetryMode::LocalOnly);
+
+        settings.set(Setting::TelemetryPrompts, "everything").await.unwrap();
+        assert_eq!(TelemetryPolicy::load(&settings).prompts, PromptPolicy::None);
+    }
+}
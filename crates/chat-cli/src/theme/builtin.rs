@@ -0,0 +1,138 @@
+//! Built-in themes selectable with the `chat.theme` setting
+
+use crossterm::style::Color;
+
+use super::{
+    DiffColors,
+    InteractiveColors,
+    StatusColors,
+    Theme,
+    UiColors,
+};
+
+/// Names of the built-in themes, the first being the default.
+pub const BUILTIN_THEMES: &[&str] = &["dark", "light", "high-contrast", "no-color"];
+
+impl Theme {
+    /// Returns the built-in theme named `name`, see [BUILTIN_THEMES].
+    pub fn builtin(name: &str) -> Option<Self> {
+        match name {
+            "dark" => Some(Self::default()),
+            "light" => Some(Self::light()),
+            "high-contrast" => Some(Self::high_contrast()),
+            "no-color" => Some(Self::no_color()),
+            _ => None,
+        }
+    }
+
+    /// Darker colors, readable on light backgrounds
+    pub fn light() -> Self {
+        Self {
+            status: StatusColors {
+                error: Color::DarkRed,
+                warning: Color::DarkYellow,
+                success: Color::DarkGreen,
+                info: Color::DarkBlue,
+            },
+            ui: UiColors {
+                primary_brand: Color::DarkCyan,
+                primary_text: Color::Black,
+                secondary_text: Color::DarkGrey,
+                emphasis: Color::DarkMagenta,
+                command_highlight: Color::DarkGreen,
+            },
+            interactive: InteractiveColors {
+                prompt_symbol: Color::DarkMagenta,
+                profile_indicator: Color::DarkCyan,
+                tangent_indicator: Color::DarkYellow,
+                usage_low: Color::DarkGreen,
+                usage_medium: Color::DarkYellow,
+                usage_high: Color::DarkRed,
+            },
+            diff: DiffColors {
+                syntax_theme: Some("InspiredGitHub"),
+                removed_gutter: Color::Rgb { r: 255, g: 205, b: 210 },
+                removed_line: Color::Rgb { r: 255, g: 235, b: 233 },
+                removed_words: Color::Rgb { r: 255, g: 180, b: 185 },
+                added_gutter: Color::Rgb { r: 190, g: 240, b: 200 },
+                added_line: Color::Rgb { r: 230, g: 255, b: 237 },
+                added_words: Color::Rgb { r: 170, g: 235, b: 185 },
+            },
+            colored: true,
+        }
+    }
+
+    /// Bright colors only, with no muted text
+    pub fn high_contrast() -> Self {
+        Self {
+            status: StatusColors {
+                error: Color::Red,
+                warning: Color::Yellow,
+                success: Color::Green,
+                info: Color::Cyan,
+            },
+            ui: UiColors {
+                primary_brand: Color::Cyan,
+                primary_text: Color::White,
+                secondary_text: Color::Grey,
+                emphasis: Color::Yellow,
+                command_highlight: Color::Green,
+            },
+            interactive: InteractiveColors {
+                prompt_symbol: Color::Yellow,
+                profile_indicator: Color::Cyan,
+                tangent_indicator: Color::Yellow,
+                usage_low: Color::Green,
+                usage_medium: Color::Yellow,
+                usage_high: Color::Red,
+            },
+            diff: DiffColors {
+                syntax_theme: Some("base16-eighties.dark"),
+                removed_gutter: Color::Rgb { r: 140, g: 0, b: 0 },
+                removed_line: Color::Rgb { r: 80, g: 0, b: 0 },
+                removed_words: Color::Rgb { r: 180, g: 30, b: 30 },
+                added_gutter: Color::Rgb { r: 0, g: 110, b: 0 },
+                added_line: Color::Rgb { r: 0, g: 60, b: 0 },
+                added_words: Color::Rgb { r: 0, g: 150, b: 40 },
+            },
+            colored: true,
+        }
+    }
+
+    /// The terminal's default color everywhere, also disabling syntax highlighting
+    pub fn no_color() -> Self {
+        Self {
+            status: StatusColors {
+                error: Color::Reset,
+                warning: Color::Reset,
+                success: Color::Reset,
+                info: Color::Reset,
+            },
+            ui: UiColors {
+                primary_brand: Color::Reset,
+                primary_text: Color::Reset,
+                secondary_text: Color::Reset,
+                emphasis: Color::Reset,
+                command_highlight: Color::Reset,
+            },
+            interactive: InteractiveColors {
+                prompt_symbol: Color::Reset,
+                profile_indicator: Color::Reset,
+                tangent_indicator: Color::Reset,
+                usage_low: Color::Reset,
+                usage_medium: Color::Reset,
+                usage_high: Color::Reset,
+            },
+            diff: DiffColors {
+                syntax_theme: None,
+                removed_gutter: Color::Reset,
+                removed_line: Color::Reset,
+                removed_words: Color::Reset,
+                added_gutter: Color::Reset,
+                added_line: Color::Reset,
+                added_words: Color::Reset,
+            },
+            colored: false,
+        }
+    }
+}
@@ -47,6 +47,25 @@ pub struct InteractiveColors {
     pub usage_high: Color,
 }
 
+/// Colors of the diffs of file edits and checkpoints, shown on truecolor terminals only
+#[derive(Debug, Clone)]
+pub struct DiffColors {
+    /// Syntect theme highlighting the syntax, no highlighting if [None]
+    pub syntax_theme: Option<&'static str>,
+    /// Background of the line numbers of removed lines
+    pub removed_gutter: Color,
+    /// Background of removed lines
+    pub removed_line: Color,
+    /// Background of the removed words of changed lines
+    pub removed_words: Color,
+    /// Background of the line numbers of added lines
+    pub added_gutter: Color,
+    /// Background of added lines
+    pub added_line: Color,
+    /// Background of the added words of changed lines
+    pub added_words: Color,
+}
+
 impl Default for StatusColors {
     fn default() -> Self {
         Self {
@@ -82,3 +101,58 @@ impl Default for InteractiveColors {
         }
     }
 }
+
+impl Default for DiffColors {
+    fn default() -> Self {
+        Self {
+            syntax_theme: Some("base16-ocean.dark"),
+            removed_gutter: Color::Rgb { r: 79, g: 40, b: 40 },
+            removed_line: Color::Rgb { r: 36, g: 25, b: 28 },
+            removed_words: Color::Rgb { r: 110, g: 45, b: 48 },
+            added_gutter: Color::Rgb { r: 40, g: 67, b: 43 },
+            added_line: Color::Rgb { r: 24, g: 38, b: 30 },
+            added_words: Color::Rgb { r: 42, g: 95, b: 52 },
+        }
+    }
+}
+
+/// Parses a color of the `chat.themeColors` setting: a name like `red` or `dark_grey`, `reset`
+/// for the terminal's default color, an ANSI value from 0 to 255, or `#rrggbb`.
+pub fn parse_color(value: &str) -> Option<Color> {
+    let value = value.trim();
+    if let Some(hex) = value.strip_prefix('#') {
+        if hex.len() != 6 {
+            return None;
+        }
+        let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+        return Some(Color::Rgb {
+            r: channel(0)?,
+            g: channel(2)?,
+            b: channel(4)?,
+        });
+    }
+    if let Ok(value) = value.parse::<u8>() {
+        return Some(Color::AnsiValue(value));
+    }
+    match value.to_lowercase().as_str() {
+        "reset" | "default" => Some(Color::Reset),
+        name => Color::try_from(name).ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_color() {
+        assert_eq!(parse_color("red"), Some(Color::Red));
+        assert_eq!(parse_color("Dark_Grey"), Some(Color::DarkGrey));
+        assert_eq!(parse_color("reset"), Some(Color::Reset));
+        assert_eq!(parse_color("208"), Some(Color::AnsiValue(208)));
+        assert_eq!(parse_color("#1e90ff"), Some(Color::Rgb { r: 30, g: 144, b: 255 }));
+        assert_eq!(parse_color("#1e90f"), None);
+        assert_eq!(parse_color("#gg0000"), None);
+        assert_eq!(parse_color("ultraviolet"), None);
+    }
+}
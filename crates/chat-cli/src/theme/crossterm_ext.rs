@@ -19,31 +19,27 @@ impl StyledText {
 
     /// Create error-styled text
     pub fn error(text: &str) -> String {
-        format!("\x1b[{}m{}\x1b[0m", color_to_ansi_code(theme().status.error), text)
+        paint(theme().status.error, text)
     }
 
     /// Create info-styled text
     pub fn info(text: &str) -> String {
-        format!("\x1b[{}m{}\x1b[0m", color_to_ansi_code(theme().status.info), text)
+        paint(theme().status.info, text)
     }
 
     /// Create warning-styled text
     pub fn warning(text: &str) -> String {
-        format!("\x1b[{}m{}\x1b[0m", color_to_ansi_code(theme().status.warning), text)
+        paint(theme().status.warning, text)
     }
 
     /// Create emphasis-styled text
     pub fn emphasis(text: &str) -> String {
-        format!("\x1b[{}m{}\x1b[0m", color_to_ansi_code(theme().ui.emphasis), text)
+        paint(theme().ui.emphasis, text)
     }
 
     /// Create command-styled text
     pub fn command(text: &str) -> String {
-        format!(
-            "\x1b[{}m{}\x1b[0m",
-            color_to_ansi_code(theme().ui.command_highlight),
-            text
-        )
+        paint(theme().ui.command_highlight, text)
     }
 
     // ===== Interactive element string methods =====
@@ -51,76 +47,52 @@ impl StyledText {
 
     /// Create prompt-styled text
     pub fn prompt(text: &str) -> String {
-        format!(
-            "\x1b[{}m{}\x1b[0m",
-            color_to_ansi_code(theme().interactive.prompt_symbol),
-            text
-        )
+        paint(theme().interactive.prompt_symbol, text)
     }
 
     /// Create profile-styled text
     pub fn profile(text: &str) -> String {
-        format!(
-            "\x1b[{}m{}\x1b[0m",
-            color_to_ansi_code(theme().interactive.profile_indicator),
-            text
-        )
+        paint(theme().interactive.profile_indicator, text)
     }
 
     /// Create tangent-styled text
     pub fn tangent(text: &str) -> String {
-        format!(
-            "\x1b[{}m{}\x1b[0m",
-            color_to_ansi_code(theme().interactive.tangent_indicator),
-            text
-        )
+        paint(theme().interactive.tangent_indicator, text)
     }
 
     /// Create usage-low-styled text
     pub fn usage_low(text: &str) -> String {
-        format!(
-            "\x1b[{}m{}\x1b[0m",
-            color_to_ansi_code(theme().interactive.usage_low),
-            text
-        )
+        paint(theme().interactive.usage_low, text)
     }
 
     /// Create usage-medium-styled text
     pub fn usage_medium(text: &str) -> String {
-        format!(
-            "\x1b[{}m{}\x1b[0m",
-            color_to_ansi_code(theme().interactive.usage_medium),
-            text
-        )
+        paint(theme().interactive.usage_medium, text)
     }
 
     /// Create usage-high-styled text
     pub fn usage_high(text: &str) -> String {
-        format!(
-            "\x1b[{}m{}\x1b[0m",
-            color_to_ansi_code(theme().interactive.usage_high),
-            text
-        )
+        paint(theme().interactive.usage_high, text)
     }
 
     /// Create brand-styled text (primary brand color)
     pub fn brand(text: &str) -> String {
-        format!("\x1b[{}m{}\x1b[0m", color_to_ansi_code(theme().ui.primary_brand), text)
+        paint(theme().ui.primary_brand, text)
     }
 
     /// Create primary-styled text (primary text color)
     pub fn primary(text: &str) -> String {
-        format!("\x1b[{}m{}\x1b[0m", color_to_ansi_code(theme().ui.primary_text), text)
+        paint(theme().ui.primary_text, text)
     }
 
     /// Create secondary-styled text (muted/helper text)
     pub fn secondary(text: &str) -> String {
-        format!("\x1b[{}m{}\x1b[0m", color_to_ansi_code(theme().ui.secondary_text), text)
+        paint(theme().ui.secondary_text, text)
     }
 
     /// Create success-styled text
     pub fn success(text: &str) -> String {
-        format!("\x1b[{}m{}\x1b[0m", color_to_ansi_code(theme().status.success), text)
+        paint(theme().status.success, text)
     }
 
     // ===== Low-level crossterm command methods =====
@@ -266,6 +238,16 @@ impl ThemeSource for StyledText {
     }
 }
 
+/// Colors `text` with `color`, leaving it as is with the terminal's default color.
+fn paint(color: Color, text: &str) -> String {
+    match color {
+        Color::Reset => text.to_string(),
+        Color::Rgb { r, g, b } => format!("\x1b[38;2;{r};{g};{b}m{text}\x1b[0m"),
+        Color::AnsiValue(value) => format!("\x1b[38;5;{value}m{text}\x1b[0m"),
+        color => format!("\x1b[{}m{}\x1b[0m", color_to_ansi_code(color), text),
+    }
+}
+
 /// Convert a crossterm Color to ANSI color code
 fn color_to_ansi_code(color: Color) -> u8 {
     match color {
@@ -4,14 +4,32 @@
 //! definitions throughout the codebase with semantic theme references. The theme system
 //! maintains backward compatibility with existing color systems (color_print, crossterm)
 //! while providing a consistent API for color usage.
+//!
+//! The theme is one of the [builtin] themes selected by the `chat.theme` setting, with the colors
+//! of the `chat.themeColors` setting overriding its own, see [Theme::load].
 
+pub mod builtin;
 pub mod colors;
 pub mod crossterm_ext;
 
-use std::sync::LazyLock;
+use std::sync::{
+    LazyLock,
+    OnceLock,
+};
 
+pub use builtin::*;
 pub use colors::*;
+use crossterm::style::Color;
 pub use crossterm_ext::*;
+use serde_json::Value;
+use tracing::warn;
+
+use crate::database::settings::{
+    Setting,
+    Settings,
+};
+use crate::os::Env;
+use crate::util::consts::env_var::NO_COLOR;
 
 /// Main theme configuration containing all color categories
 #[derive(Debug, Clone)]
@@ -22,14 +40,28 @@ pub struct Theme {
     pub ui: UiColors,
     /// Colors for interactive elements (prompts, indicators, etc.)
     pub interactive: InteractiveColors,
+    /// Colors of the diffs (syntax highlighting, added and removed lines)
+    pub diff: DiffColors,
+    /// Whether the output is colored at all, false for the `no-color` theme
+    pub colored: bool,
 }
 
 /// Global theme instance available throughout the application
 pub static DEFAULT_THEME: LazyLock<Theme> = LazyLock::new(Theme::default);
 
+/// Theme loaded from the settings by [init]
+static THEME: OnceLock<Theme> = OnceLock::new();
+
 /// Get a reference to the global theme instance
 pub fn theme() -> &'static Theme {
-    &DEFAULT_THEME
+    THEME.get().unwrap_or(&DEFAULT_THEME)
+}
+
+/// Loads the theme of the settings, returned by [theme] from then on.
+pub fn init(settings: &Settings) {
+    if THEME.set(Theme::load(settings, &Env::new())).is_err() {
+        warn!("The theme was already loaded");
+    }
 }
 
 impl Default for Theme {
@@ -39,6 +71,108 @@ impl Default for Theme {
             status: StatusColors::default(),
             ui: UiColors::default(),
             interactive: InteractiveColors::default(),
+            diff: DiffColors::default(),
+            colored: true,
+        }
+    }
+}
+
+impl Theme {
+    /// Loads the theme named by `chat.theme`, or `no-color` if unset and `NO_COLOR` is set, with
+    /// the colors of `chat.themeColors` overriding its own.
+    pub fn load(settings: &Settings, env: &Env) -> Self {
+        let mut theme = match settings.get_string(Setting::ChatTheme) {
+            Some(name) => Self::builtin(&name).unwrap_or_else(|| {
+                warn!(
+                    %name,
+                    expected = ?BUILTIN_THEMES,
+                    "Unknown theme of {}, using the default theme",
+                    Setting::ChatTheme
+                );
+                Self::default()
+            }),
+            None if env.get_os(NO_COLOR).is_some_and(|value| !value.is_empty()) => Self::no_color(),
+            None => Self::default(),
+        };
+
+        if let Some(Value::Object(colors)) = settings.get(Setting::ChatThemeColors) {
+            for (key, value) in colors {
+                match (theme.color_mut(key), value.as_str().and_then(parse_color)) {
+                    (Some(color), Some(value)) => *color = value,
+                    _ => warn!(%key, %value, "Invalid color of {}, ignoring it", Setting::ChatThemeColors),
+                }
+            }
+        }
+        theme
+    }
+
+    /// Returns the color named `key` in `chat.themeColors`, e.g. `status.error` or
+    /// `ui.secondaryText`.
+    fn color_mut(&mut self, key: &str) -> Option<&mut Color> {
+        Some(match key {
+            "status.error" => &mut self.status.error,
+            "status.warning" => &mut self.status.warning,
+            "status.success" => &mut self.status.success,
+            "status.info" => &mut self.status.info,
+            "ui.primaryBrand" => &mut self.ui.primary_brand,
+            "ui.primaryText" => &mut self.ui.primary_text,
+            "ui.secondaryText" => &mut self.ui.secondary_text,
+            "ui.emphasis" => &mut self.ui.emphasis,
+            "ui.commandHighlight" => &mut self.ui.command_highlight,
+            "interactive.promptSymbol" => &mut self.interactive.prompt_symbol,
+            "interactive.profileIndicator" => &mut self.interactive.profile_indicator,
+            "interactive.tangentIndicator" => &mut self.interactive.tangent_indicator,
+            "interactive.usageLow" => &mut self.interactive.usage_low,
+            "interactive.usageMedium" => &mut self.interactive.usage_medium,
+            "interactive.usageHigh" => &mut self.interactive.usage_high,
+            "diff.removedGutter" => &mut self.diff.removed_gutter,
+            "diff.removedLine" => &mut self.diff.removed_line,
+            "diff.removedWords" => &mut self.diff.removed_words,
+            "diff.addedGutter" => &mut self.diff.added_gutter,
+            "diff.addedLine" => &mut self.diff.added_line,
+            "diff.addedWords" => &mut self.diff.added_words,
+            _ => return None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_load() {
+        let mut settings = Settings::new().await.unwrap();
+        let env = Env::from_slice(&[]);
+        assert_eq!(Theme::load(&settings, &env).status.error, Color::Red);
+        assert!(!Theme::load(&settings, &Env::from_slice(&[("NO_COLOR", "1")])).colored);
+
+        settings.set(Setting::ChatTheme, "light").await.unwrap();
+        settings
+            .set(
+                Setting::ChatThemeColors,
+                serde_json::json!({ "status.error": "#ff0000", "ui.secondaryText": "dark_cyan", "diff.addedLine": "#e6ffed", "ui.unknown": "red" }),
+            )
+            .await
+            .unwrap();
+        let theme = Theme::load(&settings, &env);
+        assert_eq!(theme.status.error, Color::Rgb { r: 255, g: 0, b: 0 });
+        assert_eq!(theme.ui.secondary_text, Color::DarkCyan);
+        assert_eq!(theme.status.success, Color::DarkGreen);
+        assert_eq!(theme.diff.added_line, Color::Rgb { r: 230, g: 255, b: 237 });
+
+        // The theme of the setting wins over NO_COLOR
+        assert!(Theme::load(&settings, &Env::from_slice(&[("NO_COLOR", "1")])).colored);
+
+        settings.set(Setting::ChatTheme, "neon").await.unwrap();
+        assert_eq!(Theme::load(&settings, &env).status.success, Color::Green);
+    }
+
+    #[test]
+    fn test_builtin() {
+        for name in BUILTIN_THEMES {
+            assert!(Theme::builtin(name).is_some(), "{name}");
         }
+        assert!(Theme::builtin("neon").is_none());
     }
 }
@@ -96,6 +96,21 @@ pub mod env_var {
         /// Telemetry client ID
         Q_TELEMETRY_CLIENT_ID = "Q_TELEMETRY_CLIENT_ID",
 
+        /// File in which a delegated task records the files it writes to
+        Q_DELEGATE_TOUCHED_FILES = "Q_DELEGATE_TOUCHED_FILES",
+
+        /// File of the messages between a delegated task and the session that delegated it
+        Q_DELEGATE_MAILBOX = "Q_DELEGATE_MAILBOX",
+
+        /// File a delegated task checkpoints its conversation to, to be resumed when interrupted
+        Q_DELEGATE_SNAPSHOT = "Q_DELEGATE_SNAPSHOT",
+
+        /// Limits of a delegated task on its turns, tool calls, tokens, cost and duration, as JSON
+        Q_DELEGATE_LIMITS = "Q_DELEGATE_LIMITS",
+
+        /// Sub-agent of the agent config a delegated task runs as
+        Q_SUBAGENT = "Q_SUBAGENT",
+
         /// Amazon Q SigV4 authentication
         AMAZON_Q_SIGV4 = "AMAZON_Q_SIGV4",
 
@@ -108,6 +123,21 @@ pub mod env_var {
         /// Terminal type
         TERM = "TERM",
 
+        /// Disables colored output when set, see <https://no-color.org>
+        NO_COLOR = "NO_COLOR",
+
+        /// Terminal application, e.g. `iTerm.app`
+        TERM_PROGRAM = "TERM_PROGRAM",
+
+        /// Terminal application set by iTerm2, also over SSH
+        LC_TERMINAL = "LC_TERMINAL",
+
+        /// Set in the windows of the kitty terminal
+        KITTY_WINDOW_ID = "KITTY_WINDOW_ID",
+
+        /// Set inside tmux sessions
+        TMUX = "TMUX",
+
         /// AWS region
         AWS_REGION = "AWS_REGION",
 
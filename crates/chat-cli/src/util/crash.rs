@@ -0,0 +1,427 @@
+//! Crash bundles: what the CLI was doing when it panicked or chat exited with an error, written
+//! locally so that `q issue --crash` can turn them into actionable bug reports.
+
+use std::backtrace::Backtrace;
+use std::panic::PanicHookInfo;
+use std::path::{
+    Path,
+    PathBuf,
+};
+use std::sync::{
+    Mutex,
+    PoisonError,
+};
+
+use chrono::{
+    DateTime,
+    Utc,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use super::UtilError;
+use super::paths::crashes_dir;
+use crate::database::settings::Setting;
+use crate::logging::recent_events;
+
+/// Number of crash bundles kept, the oldest ones being deleted first.
+const MAX_BUNDLES: usize = 10;
+
+/// Number of backtrace lines and log events included in issues, as the issue is created from a URL
+/// whose length is limited.
+const MAX_ISSUE_LINES: usize = 30;
+
+static SESSION: Mutex<SessionMetadata> = Mutex::new(SessionMetadata {
+    version: String::new(),
+    os: String::new(),
+    command: None,
+    conversation_id: None,
+    model: None,
+});
+
+/// What the CLI was running.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionMetadata {
+    pub version: String,
+    pub os: String,
+    pub command: Option<String>,
+    pub conversation_id: Option<String>,
+    pub model: Option<String>,
+}
+
+/// Records the subcommand being run, for the crash bundles.
+pub fn set_command(command: &str) {
+    SESSION.lock().unwrap_or_else(PoisonError::into_inner).command = Some(command.to_string());
+}
+
+/// Records the conversation of the chat session, for the crash bundles.
+pub fn set_conversation(conversation_id: &str, model: Option<&str>) {
+    let mut session = SESSION.lock().unwrap_or_else(PoisonError::into_inner);
+    session.conversation_id = Some(conversation_id.to_string());
+    session.model = model.map(str::to_string);
+}
+
+fn session() -> SessionMetadata {
+    let mut session = SESSION.lock().unwrap_or_else(PoisonError::into_inner).clone();
+    session.version = env!("CARGO_PKG_VERSION").to_string();
+    session.os = format!("{} {}", std::env::consts::OS, std::env::consts::ARCH);
+    session
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CrashKind {
+    Panic,
+    Error,
+}
+
+/// What the CLI was doing when it crashed, with the home directory, the user name and the secret
+/// settings redacted.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CrashBundle {
+    pub kind: CrashKind,
+    pub time: DateTime<Utc>,
+    pub message: String,
+    /// Where the panic happened.
+    pub location: Option<String>,
+    pub backtrace: Option<String>,
+    /// The causes of the error, outermost first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub causes: Vec<String>,
+    pub session: SessionMetadata,
+    /// The last log events, oldest first.
+    pub recent_events: Vec<String>,
+    /// The settings that were set.
+    pub settings: serde_json::Map<String, serde_json::Value>,
+}
+
+impl CrashBundle {
+    pub fn from_panic(info: &PanicHookInfo<'_>) -> Self {
+        let payload = info.payload();
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|message| (*message).to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "Box<dyn Any>".to_string());
+        Self::new(CrashKind::Panic, message)
+            .with_location(info.location().map(ToString::to_string))
+            .with_backtrace(Backtrace::force_capture().to_string())
+    }
+
+    pub fn from_error(err: &eyre::Report) -> Self {
+        let mut bundle = Self::new(CrashKind::Error, err.to_string());
+        bundle.causes = err.chain().skip(1).map(ToString::to_string).collect();
+        bundle
+    }
+
+    fn new(kind: CrashKind, message: String) -> Self {
+        Self {
+            kind,
+            time: Utc::now(),
+            message,
+            location: None,
+            backtrace: None,
+            causes: Vec::new(),
+            session: session(),
+            recent_events: recent_events(),
+            settings: redacted_settings(read_settings().unwrap_or_default()),
+        }
+    }
+
+    fn with_location(mut self, location: Option<String>) -> Self {
+        self.location = location;
+        self
+    }
+
+    fn with_backtrace(mut self, backtrace: String) -> Self {
+        self.backtrace = Some(backtrace);
+        self
+    }
+
+    /// Replaces the home directory and the user name in the texts of the bundle.
+    fn redact(mut self, home: Option<&str>, user: Option<&str>) -> Self {
+        let redact = |text: &mut String| {
+            if let Some(home) = home.filter(|home| !home.is_empty()) {
+                *text = text.replace(home, "~");
+            }
+            if let Some(user) = user.filter(|user| user.len() > 2) {
+                *text = text.replace(user, "<user>");
+            }
+        };
+        redact(&mut self.message);
+        self.location
+            .iter_mut()
+            .chain(self.backtrace.iter_mut())
+            .for_each(redact);
+        self.causes.iter_mut().for_each(redact);
+        self.recent_events.iter_mut().for_each(redact);
+        for value in self.settings.values_mut() {
+            if let serde_json::Value::String(text) = value {
+                redact(text);
+            }
+        }
+        self
+    }
+
+    /// Writes the bundle to the crashes directory, deleting the oldest bundles past
+    /// [MAX_BUNDLES].
+    pub fn write(self) -> Result<PathBuf, UtilError> {
+        let home = dirs::home_dir().map(|home| home.to_string_lossy().to_string());
+        let user = std::env::var("USER").or_else(|_| std::env::var("USERNAME")).ok();
+        let bundle = self.redact(home.as_deref(), user.as_deref());
+
+        let dir = crashes_dir()?;
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join(format!("crash-{}.json", bundle.time.format("%Y%m%dT%H%M%S%.3fZ")));
+        std::fs::write(&path, serde_json::to_vec_pretty(&bundle)?)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)).ok();
+        }
+
+        let mut bundles = list_bundles(&dir)?;
+        while bundles.len() > MAX_BUNDLES {
+            std::fs::remove_file(bundles.remove(0)).ok();
+        }
+        Ok(path)
+    }
+
+    pub fn issue_title(&self) -> String {
+        match self.kind {
+            CrashKind::Panic => format!("Crash: {}", first_line(&self.message)),
+            CrashKind::Error => format!("Chat exited with an error: {}", first_line(&self.message)),
+        }
+    }
+
+    /// What happened, for the actual behavior of an issue.
+    pub fn issue_details(&self, path: &Path) -> String {
+        let mut details = format!("{}\n", self.message);
+        if let Some(location) = &self.location {
+            details.push_str(&format!("at {location}\n"));
+        }
+        for cause in &self.causes {
+            details.push_str(&format!("caused by: {cause}\n"));
+        }
+        if let Some(backtrace) = &self.backtrace {
+            details.push_str("\n```\n[backtrace]\n");
+            details.push_str(&code_lines(backtrace.lines().take(MAX_ISSUE_LINES)));
+            details.push_str("```\n");
+        }
+        if !self.recent_events.is_empty() {
+            details.push_str("\n```\n[recent-events]\n");
+            details.push_str(&code_lines(
+                self.recent_events
+                    .iter()
+                    .skip(self.recent_events.len().saturating_sub(MAX_ISSUE_LINES))
+                    .map(String::as_str),
+            ));
+            details.push_str("```\n");
+        }
+        details.push_str(&format!(
+            "\nThe full crash report is in {}, attach it if it helps.",
+            path.display()
+        ));
+        details
+    }
+
+    /// The session and the settings, for the environment of an issue.
+    pub fn issue_environment(&self) -> String {
+        let mut environment = format!(
+            "[crash]\ntime: {}\nversion: {}\nos: {}\n",
+            self.time.to_rfc3339(),
+            self.session.version,
+            self.session.os
+        );
+        if let Some(command) = &self.session.command {
+            environment.push_str(&format!("command: {command}\n"));
+        }
+        if let Some(conversation_id) = &self.session.conversation_id {
+            environment.push_str(&format!("conversation: {conversation_id}\n"));
+        }
+        if let Some(model) = &self.session.model {
+            environment.push_str(&format!("model: {model}\n"));
+        }
+        if !self.settings.is_empty() {
+            environment.push_str("\n[settings]\n");
+            for (key, value) in &self.settings {
+                environment.push_str(&format!("{key}: {value}\n"));
+            }
+        }
+        environment
+    }
+}
+
+fn first_line(text: &str) -> &str {
+    text.lines().next().unwrap_or_default()
+}
+
+fn code_lines<'a>(lines: impl Iterator<Item = &'a str>) -> String {
+    lines
+        .map(|line| format!("{}\n", line.replace("```", r"\```")))
+        .collect()
+}
+
+fn read_settings() -> Option<serde_json::Map<String, serde_json::Value>> {
+    let path = crate::util::paths::GlobalPaths::settings_path_static().ok()?;
+    serde_json::from_slice(&std::fs::read(path).ok()?).ok()
+}
+
+/// Keeps the known settings, redacting those whose values may be secrets.
+fn redacted_settings(
+    settings: serde_json::Map<String, serde_json::Value>,
+) -> serde_json::Map<String, serde_json::Value> {
+    settings
+        .into_iter()
+        .filter_map(|(key, value)| {
+            let setting = Setting::try_from(key.as_str()).ok()?;
+            match is_secret(&setting) {
+                true => Some((key, serde_json::Value::String("<redacted>".to_string()))),
+                false => Some((key, value)),
+            }
+        })
+        .collect()
+}
+
+/// Whether the value of a setting may be a secret.
+fn is_secret(setting: &Setting) -> bool {
+    matches!(
+        setting,
+        Setting::TelemetryOtlpEndpoint
+            | Setting::TelemetryOtlpHeaders
+            | Setting::OldClientId
+            | Setting::ChatNotificationWebhook
+    )
+}
+
+/// The crash bundles of `dir`, oldest first.
+fn list_bundles(dir: &Path) -> Result<Vec<PathBuf>, std::io::Error> {
+    let mut bundles = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with("crash-"))
+                && path.extension().is_some_and(|extension| extension == "json")
+        })
+        .collect::<Vec<_>>();
+    // The names start with the time of the crash
+    bundles.sort();
+    Ok(bundles)
+}
+
+/// The most recent crash bundle, if any.
+pub fn latest_bundle() -> Result<Option<(PathBuf, CrashBundle)>, UtilError> {
+    let dir = crashes_dir()?;
+    if !dir.exists() {
+        return Ok(None);
+    }
+    let Some(path) = list_bundles(&dir)?.pop() else {
+        return Ok(None);
+    };
+    let bundle = serde_json::from_slice(&std::fs::read(&path)?)?;
+    Ok(Some((path, bundle)))
+}
+
+/// Writes a crash bundle for every panic, after the output of the current panic hook.
+pub fn install_panic_hook() {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        previous(info);
+        if let Ok(path) = CrashBundle::from_panic(info).write() {
+            eprintln!(
+                "A crash report was written to {}\nReport the crash with: {} issue --crash",
+                path.display(),
+                crate::util::CLI_BINARY_NAME
+            );
+        }
+    }));
+}
+
+/// Writes a crash bundle for an error that ended chat.
+pub fn write_error_bundle(err: &eyre::Report) -> Result<PathBuf, UtilError> {
+    CrashBundle::from_error(err).write()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bundle() -> CrashBundle {
+        CrashBundle {
+            kind: CrashKind::Panic,
+            time: Utc::now(),
+            message: "index out of bounds\nthe len is 0".to_string(),
+            location: Some("/home/alice/src/main.rs:1:1".to_string()),
+            backtrace: Some("0: main\n   at /home/alice/src/main.rs:1:1\n1: ```start```".to_string()),
+            causes: Vec::new(),
+            session: SessionMetadata {
+                version: "1.0.0".to_string(),
+                os: "linux x86_64".to_string(),
+                command: Some("chat".to_string()),
+                conversation_id: Some("abc".to_string()),
+                model: None,
+            },
+            recent_events: vec!["ERROR chat_cli: failed for alice".to_string()],
+            settings: serde_json::Map::new(),
+        }
+    }
+
+    #[test]
+    fn test_redact() {
+        let bundle = bundle().redact(Some("/home/alice"), Some("alice"));
+        assert_eq!(bundle.location.as_deref(), Some("~/src/main.rs:1:1"));
+        assert!(!bundle.backtrace.unwrap().contains("alice"));
+        assert_eq!(bundle.recent_events, vec!["ERROR chat_cli: failed for <user>"]);
+    }
+
+    #[test]
+    fn test_redacted_settings() {
+        let settings = serde_json::json!({
+            "chat.defaultModel": "claude-sonnet-4",
+            "telemetry.otlpHeaders": "authorization=Bearer secret",
+            "chat.notificationWebhook": "https://hooks.example.com/T0/secret",
+            "some.unknownSetting": "value",
+        });
+        let serde_json::Value::Object(settings) = settings else {
+            unreachable!()
+        };
+        assert_eq!(
+            serde_json::Value::Object(redacted_settings(settings)),
+            serde_json::json!({
+                "chat.defaultModel": "claude-sonnet-4",
+                "telemetry.otlpHeaders": "<redacted>",
+                "chat.notificationWebhook": "<redacted>",
+            })
+        );
+    }
+
+    #[test]
+    fn test_issue() {
+        let bundle = bundle();
+        assert_eq!(bundle.issue_title(), "Crash: index out of bounds");
+        let details = bundle.issue_details(Path::new("/tmp/crash.json"));
+        assert!(details.contains("at /home/alice/src/main.rs:1:1"));
+        assert!(details.contains(r"1: \```start\```"));
+        assert!(details.contains("[recent-events]"));
+        assert!(bundle.issue_environment().contains("conversation: abc"));
+    }
+
+    #[test]
+    fn test_list_bundles() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in [
+            "crash-20250102T000000.000Z.json",
+            "crash-20250101T000000.000Z.json",
+            "other.json",
+        ] {
+            std::fs::write(dir.path().join(name), "{}").unwrap();
+        }
+        let bundles = list_bundles(dir.path()).unwrap();
+        assert_eq!(bundles.len(), 2);
+        assert!(bundles[0].ends_with("crash-20250101T000000.000Z.json"));
+    }
+}
@@ -1,4 +1,5 @@
 pub mod consts;
+pub mod crash;
 pub mod editor;
 pub mod env_var;
 pub mod file_uri;
@@ -105,6 +106,17 @@ pub fn dialoguer_theme() -> ColorfulTheme {
     }
 }
 
+/// Formats a duration for the user, e.g. `0.3s`, `42s`, `2m05s` or `3h07m`.
+pub fn format_duration(duration: std::time::Duration) -> String {
+    let seconds = duration.as_secs();
+    match seconds {
+        0..10 => format!("{:.1}s", duration.as_secs_f64()),
+        10..60 => format!("{seconds}s"),
+        60..3600 => format!("{}m{:02}s", seconds / 60, seconds % 60),
+        _ => format!("{}h{:02}m", seconds / 3600, seconds % 3600 / 60),
+    }
+}
+
 /// A writer that discards all data written to it.
 pub struct NullWriter;
 
@@ -119,3 +131,18 @@ impl Write for NullWriter {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(Duration::from_millis(300)), "0.3s");
+        assert_eq!(format_duration(Duration::from_secs(42)), "42s");
+        assert_eq!(format_duration(Duration::from_secs(125)), "2m05s");
+        assert_eq!(format_duration(Duration::from_secs(3 * 3600 + 420)), "3h07m");
+    }
+}
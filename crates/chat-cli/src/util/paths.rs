@@ -45,10 +45,12 @@ pub mod workspace {
     //! Project-level paths (relative to current working directory)
     pub const AGENTS_DIR: &str = ".amazonq/cli-agents";
     pub const PROMPTS_DIR: &str = ".amazonq/prompts";
+    pub const COMMANDS_DIR: &str = ".amazonq/commands";
     pub const MCP_CONFIG: &str = ".amazonq/mcp.json";
     pub const TODO_LISTS_DIR: &str = ".amazonq/cli-todo-lists";
     pub const SUBAGENTS_DIR: &str = ".amazonq/.subagents";
     pub const RULES_PATTERN: &str = ".amazonq/rules/**/*.md";
+    pub const INDEX_FILE: &str = ".amazonq/index.json";
 
     // Default documentation files for agent resources
     pub const DEFAULT_AGENT_RESOURCES: &[&str] = &["file://AmazonQ.md", "file://AGENTS.md", "file://README.md"];
@@ -58,6 +60,7 @@ pub mod global {
     //! User-level paths (relative to home directory)
     pub const AGENTS_DIR: &str = ".aws/amazonq/cli-agents";
     pub const PROMPTS_DIR: &str = ".aws/amazonq/prompts";
+    pub const COMMANDS_DIR: &str = ".aws/amazonq/commands";
     pub const MCP_CONFIG: &str = ".aws/amazonq/mcp.json";
     pub const SHADOW_REPO_DIR: &str = ".aws/amazonq/cli-checkouts";
     pub const CLI_BASH_HISTORY: &str = ".aws/amazonq/.cli_bash_history";
@@ -150,6 +153,11 @@ pub fn logs_dir() -> Result<PathBuf> {
     }
 }
 
+/// The directory of the crash bundles
+pub fn crashes_dir() -> Result<PathBuf> {
+    Ok(logs_dir()?.join("crashes"))
+}
+
 /// Canonicalizes path given by expanding the path given
 pub fn canonicalizes_path(os: &Os, path_as_str: &str) -> Result<String> {
     let context = |input: &str| Ok(os.env.get(input).ok());
@@ -237,6 +245,10 @@ impl<'a> WorkspacePaths<'a> {
         Ok(self.os.env.current_dir()?.join(workspace::PROMPTS_DIR))
     }
 
+    pub fn commands_dir(&self) -> Result<PathBuf> {
+        Ok(self.os.env.current_dir()?.join(workspace::COMMANDS_DIR))
+    }
+
     pub fn mcp_config(&self) -> Result<PathBuf> {
         Ok(self.os.env.current_dir()?.join(workspace::MCP_CONFIG))
     }
@@ -249,6 +261,10 @@ impl<'a> WorkspacePaths<'a> {
         Ok(self.os.env.current_dir()?.join(workspace::SUBAGENTS_DIR))
     }
 
+    pub fn index_file(&self) -> Result<PathBuf> {
+        Ok(self.os.env.current_dir()?.join(workspace::INDEX_FILE))
+    }
+
     pub async fn ensure_subagents_dir(&self) -> Result<PathBuf> {
         let dir = self.subagents_dir()?;
         if !dir.exists() {
@@ -272,6 +288,10 @@ impl<'a> GlobalPaths<'a> {
         Ok(home_dir(self.os)?.join(global::PROMPTS_DIR))
     }
 
+    pub fn commands_dir(&self) -> Result<PathBuf> {
+        Ok(home_dir(self.os)?.join(global::COMMANDS_DIR))
+    }
+
     pub fn mcp_config(&self) -> Result<PathBuf> {
         Ok(home_dir(self.os)?.join(global::MCP_CONFIG))
     }
@@ -330,4 +350,12 @@ impl<'a> GlobalPaths<'a> {
             .join("amazon-q")
             .join("data.sqlite3"))
     }
+
+    /// Static method for the file of the telemetry events recorded with `telemetry.localOnly`
+    pub fn telemetry_events_path_static() -> Result<PathBuf> {
+        Ok(dirs::data_local_dir()
+            .ok_or(DirectoryError::NoHomeDirectory)?
+            .join("amazon-q")
+            .join("telemetry-events.jsonl"))
+    }
 }
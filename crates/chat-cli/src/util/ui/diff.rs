@@ -0,0 +1,561 @@
+//! Unified diffs with syntax highlighting, line numbers and highlighting of the changed words,
+//! for the previews of file edits and the diffs between checkpoints.
+
+use std::io::Write;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::LazyLock;
+
+use crossterm::queue;
+use crossterm::style::{
+    self,
+    Attribute,
+    Color,
+};
+use crossterm::terminal::{
+    Clear,
+    ClearType,
+};
+use eyre::Result;
+use similar::{
+    ChangeTag,
+    DiffOp,
+    TextDiff,
+};
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{
+    Style,
+    Theme as SyntaxTheme,
+    ThemeSet,
+};
+use syntect::parsing::SyntaxSet;
+use syntect::util::LinesWithEndings;
+use tracing::error;
+
+use crate::theme::{
+    DiffColors,
+    StyledText,
+    theme,
+};
+
+static SYNTAX_SET: LazyLock<SyntaxSet> = LazyLock::new(SyntaxSet::load_defaults_newlines);
+static THEME_SET: LazyLock<ThemeSet> = LazyLock::new(ThemeSet::load_defaults);
+
+/// Changed lines whose words are less similar than this aren't highlighted word by word, most of
+/// the line having changed.
+const MIN_WORD_DIFF_RATIO: f32 = 0.5;
+
+pub fn supports_truecolor() -> bool {
+    // Simple override to disable truecolor since shell_color doesn't use Context.
+    theme().colored
+        && !crate::util::env_var::is_truecolor_disabled()
+        && shell_color::get_color_support().contains(shell_color::ColorSupport::TERM24BIT)
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct DiffOptions {
+    /// 1-indexed line number of the first line of the compared texts.
+    pub start_line: usize,
+    /// Lines of context shown around the changes, every line if [None].
+    pub context_lines: Option<usize>,
+    /// Whether to highlight the syntax and the backgrounds with 24bit colors.
+    pub truecolor: bool,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        Self {
+            start_line: 1,
+            context_lines: None,
+            truecolor: supports_truecolor(),
+        }
+    }
+}
+
+/// Prints the diff of `old` and `new`, both contents of the file at `path`, whose extension
+/// selects the syntax highlighting.
+pub fn print_diff(
+    output: &mut impl Write,
+    path: impl AsRef<Path>,
+    old: &str,
+    new: &str,
+    options: DiffOptions,
+) -> Result<()> {
+    let diff = TextDiff::from_lines(old, new);
+    let colors = &theme().diff;
+    let syntax_theme = syntax_theme(colors, options.truecolor);
+    let palette = Palette::new(syntax_theme, colors);
+    let old_lines = highlight(path.as_ref(), old, syntax_theme);
+    let new_lines = highlight(path.as_ref(), new, syntax_theme);
+    let gutter = Gutter {
+        start_line: options.start_line,
+        old_width: terminal_width_required_for_line_count(old_lines.len() + options.start_line),
+        new_width: terminal_width_required_for_line_count(new_lines.len() + options.start_line),
+    };
+
+    let groups = match options.context_lines {
+        Some(context_lines) => diff.grouped_ops(context_lines),
+        None => vec![diff.ops().to_vec()],
+    };
+    for group in groups.iter().filter(|group| !group.is_empty()) {
+        if options.context_lines.is_some() {
+            print_hunk_header(output, group, options.start_line)?;
+        }
+        for op in group {
+            match *op {
+                DiffOp::Equal {
+                    old_index,
+                    new_index,
+                    len,
+                } => {
+                    for i in 0..len {
+                        let line = Line {
+                            tag: ChangeTag::Equal,
+                            old_index: Some(old_index + i),
+                            new_index: Some(new_index + i),
+                            spans: &old_lines[old_index + i],
+                            changed_words: &[],
+                        };
+                        line.print(output, &gutter, &palette)?;
+                    }
+                },
+                DiffOp::Delete { old_index, old_len, .. } => {
+                    for (i, spans) in old_lines.iter().enumerate().skip(old_index).take(old_len) {
+                        Line::deleted(i, spans, &[]).print(output, &gutter, &palette)?;
+                    }
+                },
+                DiffOp::Insert { new_index, new_len, .. } => {
+                    for (i, spans) in new_lines.iter().enumerate().skip(new_index).take(new_len) {
+                        Line::inserted(i, spans, &[]).print(output, &gutter, &palette)?;
+                    }
+                },
+                DiffOp::Replace {
+                    old_index,
+                    old_len,
+                    new_index,
+                    new_len,
+                } => {
+                    // Pairs the replaced lines in order, like git does
+                    let changed_words = (0..old_len.min(new_len))
+                        .map(|i| {
+                            word_diff(
+                                &line_text(&old_lines[old_index + i]),
+                                &line_text(&new_lines[new_index + i]),
+                            )
+                        })
+                        .collect::<Vec<_>>();
+                    for i in 0..old_len {
+                        let words = changed_words.get(i).map_or(&[][..], |(old, _)| old);
+                        Line::deleted(old_index + i, &old_lines[old_index + i], words)
+                            .print(output, &gutter, &palette)?;
+                    }
+                    for i in 0..new_len {
+                        let words = changed_words.get(i).map_or(&[][..], |(_, new)| new);
+                        Line::inserted(new_index + i, &new_lines[new_index + i], words)
+                            .print(output, &gutter, &palette)?;
+                    }
+                },
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn print_hunk_header(output: &mut impl Write, group: &[DiffOp], start_line: usize) -> Result<()> {
+    let (Some(first), Some(last)) = (group.first(), group.last()) else {
+        return Ok(());
+    };
+    let old = first.old_range().start..last.old_range().end;
+    let new = first.new_range().start..last.new_range().end;
+    queue!(
+        output,
+        StyledText::secondary_fg(),
+        style::Print(format!(
+            "@@ -{},{} +{},{} @@\n",
+            old.start + start_line,
+            old.len(),
+            new.start + start_line,
+            new.len()
+        )),
+        StyledText::reset(),
+    )?;
+    Ok(())
+}
+
+/// Returns the number of terminal cells required for displaying line numbers up to
+/// `line_count`.
+///
+/// For example, `10` and `99` both take 2 cells, whereas `100` and `999` take 3.
+fn terminal_width_required_for_line_count(line_count: usize) -> usize {
+    line_count.to_string().chars().count()
+}
+
+/// Spans of a line, styled by the syntax highlighting if any.
+type Spans<'a> = Vec<(Option<Style>, &'a str)>;
+
+/// The syntect theme of the diff `colors`, if any and `truecolor`.
+fn syntax_theme(colors: &DiffColors, truecolor: bool) -> Option<&'static SyntaxTheme> {
+    colors
+        .syntax_theme
+        .filter(|_| truecolor)
+        .and_then(|name| THEME_SET.themes.get(name))
+}
+
+/// Splits `text` into lines, highlighting their syntax with `syntax_theme` if any.
+fn highlight<'a>(path: &Path, text: &'a str, syntax_theme: Option<&SyntaxTheme>) -> Vec<Spans<'a>> {
+    let lines = LinesWithEndings::from(text);
+    let Some(syntax_theme) = syntax_theme else {
+        return lines.map(|line| vec![(None, line)]).collect();
+    };
+
+    let syntax = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .and_then(|extension| SYNTAX_SET.find_syntax_by_extension(extension))
+        .unwrap_or_else(|| SYNTAX_SET.find_syntax_plain_text());
+    let mut highlighter = HighlightLines::new(syntax, syntax_theme);
+    lines
+        .map(|line| match highlighter.highlight_line(line, &SYNTAX_SET) {
+            Ok(ranges) => ranges.into_iter().map(|(style, text)| (Some(style), text)).collect(),
+            Err(err) => {
+                error!(?err, "unable to syntax highlight the diff");
+                vec![(None, line)]
+            },
+        })
+        .collect()
+}
+
+fn line_text(spans: &Spans<'_>) -> String {
+    spans.iter().map(|(_, text)| *text).collect()
+}
+
+/// Returns the byte ranges of the words changed between `old` and `new`, unless most of the line
+/// changed.
+fn word_diff(old: &str, new: &str) -> (Vec<Range<usize>>, Vec<Range<usize>>) {
+    let diff = TextDiff::from_words(old, new);
+    let (mut old_words, mut new_words) = (Vec::new(), Vec::new());
+    if diff.ratio() < MIN_WORD_DIFF_RATIO {
+        return (old_words, new_words);
+    }
+
+    let (mut old_pos, mut new_pos) = (0, 0);
+    for change in diff.iter_all_changes() {
+        let len = change.value().len();
+        match change.tag() {
+            ChangeTag::Equal => {
+                old_pos += len;
+                new_pos += len;
+            },
+            ChangeTag::Delete => {
+                push_range(&mut old_words, old_pos..old_pos + len);
+                old_pos += len;
+            },
+            ChangeTag::Insert => {
+                push_range(&mut new_words, new_pos..new_pos + len);
+                new_pos += len;
+            },
+        }
+    }
+    (old_words, new_words)
+}
+
+/// Pushes `range`, merging it with the last range if they are adjacent.
+fn push_range(ranges: &mut Vec<Range<usize>>, range: Range<usize>) {
+    match ranges.last_mut() {
+        Some(last) if last.end == range.start => last.end = range.end,
+        _ => ranges.push(range),
+    }
+}
+
+/// Splits `range` at the boundaries of the sorted `highlighted` ranges, telling whether each
+/// piece is highlighted.
+fn split_highlighted(range: Range<usize>, highlighted: &[Range<usize>]) -> Vec<(Range<usize>, bool)> {
+    let mut pieces = Vec::new();
+    let mut pos = range.start;
+    for highlight in highlighted {
+        if highlight.end <= pos || highlight.start >= range.end {
+            continue;
+        }
+        let (start, end) = (highlight.start.max(pos), highlight.end.min(range.end));
+        if start > pos {
+            pieces.push((pos..start, false));
+        }
+        pieces.push((start..end, true));
+        pos = end;
+    }
+    if pos < range.end {
+        pieces.push((pos..range.end, false));
+    }
+    pieces
+}
+
+/// Widths of the line numbers.
+struct Gutter {
+    start_line: usize,
+    old_width: usize,
+    new_width: usize,
+}
+
+/// Colors of the lines of a diff, with the backgrounds of the theme's [DiffColors] only along
+/// with a syntax highlighting.
+struct Palette<'a> {
+    truecolor: bool,
+    gutter_bg: Color,
+    line_bg: Color,
+    colors: &'a DiffColors,
+}
+
+impl<'a> Palette<'a> {
+    fn new(syntax_theme: Option<&SyntaxTheme>, colors: &'a DiffColors) -> Self {
+        if let Some(settings) = syntax_theme.map(|syntax_theme| &syntax_theme.settings) {
+            if let Some(line_bg) = settings.background {
+                return Self {
+                    truecolor: true,
+                    gutter_bg: to_crossterm_color(settings.gutter.unwrap_or(line_bg)),
+                    line_bg: to_crossterm_color(line_bg),
+                    colors,
+                };
+            }
+        }
+        Self {
+            truecolor: false,
+            gutter_bg: Color::Reset,
+            line_bg: Color::Reset,
+            colors,
+        }
+    }
+
+    /// Returns the text, gutter background, line background and changed words background colors.
+    fn colors(&self, tag: ChangeTag) -> (Color, Color, Color, Option<Color>) {
+        match (tag, self.truecolor) {
+            (ChangeTag::Equal, _) => (Color::Reset, self.gutter_bg, self.line_bg, None),
+            (ChangeTag::Delete, true) => (
+                Color::Reset,
+                self.colors.removed_gutter,
+                self.colors.removed_line,
+                Some(self.colors.removed_words),
+            ),
+            (ChangeTag::Insert, true) => (
+                Color::Reset,
+                self.colors.added_gutter,
+                self.colors.added_line,
+                Some(self.colors.added_words),
+            ),
+            (ChangeTag::Delete, false) => (theme().status.error, self.gutter_bg, self.line_bg, None),
+            (ChangeTag::Insert, false) => (theme().status.success, self.gutter_bg, self.line_bg, None),
+        }
+    }
+}
+
+struct Line<'a> {
+    tag: ChangeTag,
+    /// 0-indexed line numbers in the old and new texts.
+    old_index: Option<usize>,
+    new_index: Option<usize>,
+    spans: &'a Spans<'a>,
+    changed_words: &'a [Range<usize>],
+}
+
+impl<'a> Line<'a> {
+    fn deleted(index: usize, spans: &'a Spans<'a>, changed_words: &'a [Range<usize>]) -> Self {
+        Self {
+            tag: ChangeTag::Delete,
+            old_index: Some(index),
+            new_index: None,
+            spans,
+            changed_words,
+        }
+    }
+
+    fn inserted(index: usize, spans: &'a Spans<'a>, changed_words: &'a [Range<usize>]) -> Self {
+        Self {
+            tag: ChangeTag::Insert,
+            old_index: None,
+            new_index: Some(index),
+            spans,
+            changed_words,
+        }
+    }
+
+    fn print(&self, output: &mut impl Write, gutter: &Gutter, palette: &Palette<'_>) -> Result<()> {
+        let (text_color, gutter_bg, line_bg, changed_bg) = palette.colors(self.tag);
+        let sign = match self.tag {
+            ChangeTag::Equal => " ",
+            ChangeTag::Delete => "-",
+            ChangeTag::Insert => "+",
+        };
+        let line_number = |index: Option<usize>| index.map_or(String::new(), |i| (i + gutter.start_line).to_string());
+
+        // The gutter and line numbers.
+        queue!(
+            output,
+            style::SetBackgroundColor(gutter_bg),
+            style::SetForegroundColor(text_color),
+            style::Print(format!(
+                "{sign} {:>old_width$}{}{:>new_width$}",
+                line_number(self.old_index),
+                if self.tag == ChangeTag::Equal { ", " } else { "  " },
+                line_number(self.new_index),
+                old_width = gutter.old_width,
+                new_width = gutter.new_width,
+            )),
+            StyledText::reset(),
+            style::Print(":"),
+            style::SetBackgroundColor(line_bg),
+            style::Print(" "),
+        )?;
+
+        // The line.
+        let mut offset = 0;
+        for (style, text) in self.spans {
+            let span = offset..offset + text.len();
+            offset = span.end;
+            for (piece, changed) in split_highlighted(span.clone(), self.changed_words) {
+                let piece = text[piece.start - span.start..piece.end - span.start].trim_end_matches(['\n', '\r']);
+                let color = style.map_or(text_color, |style| to_crossterm_color(style.foreground));
+                queue!(output, style::SetForegroundColor(color))?;
+                match (changed, changed_bg) {
+                    (true, Some(changed_bg)) => queue!(output, style::SetBackgroundColor(changed_bg))?,
+                    (true, None) => queue!(output, style::SetAttribute(Attribute::Reverse))?,
+                    (false, _) => queue!(
+                        output,
+                        style::SetAttribute(Attribute::NoReverse),
+                        style::SetBackgroundColor(line_bg)
+                    )?,
+                }
+                queue!(output, style::Print(piece))?;
+            }
+        }
+        queue!(
+            output,
+            style::SetAttribute(Attribute::NoReverse),
+            style::SetBackgroundColor(line_bg),
+            Clear(ClearType::UntilNewLine),
+            StyledText::reset(),
+            style::Print("\n"),
+        )?;
+        Ok(())
+    }
+}
+
+fn to_crossterm_color(color: syntect::highlighting::Color) -> Color {
+    Color::Rgb {
+        r: color.r,
+        g: color.g,
+        b: color.b,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(old: &str, new: &str, options: DiffOptions) -> String {
+        let mut output = Vec::new();
+        print_diff(&mut output, "file.rs", old, new, options).unwrap();
+        String::from_utf8(strip_ansi_escapes::strip(output)).unwrap()
+    }
+
+    #[test]
+    fn test_print_diff() {
+        let options = DiffOptions {
+            start_line: 9,
+            context_lines: None,
+            truecolor: false,
+        };
+        assert_eq!(
+            render("a\nb\nc\n", "a\nB\nc\nd\n", options),
+            [
+                "   9,  9: a",
+                "- 10    : b",
+                "+     10: B",
+                "  11, 11: c",
+                "+     12: d",
+                ""
+            ]
+            .join("\n")
+        );
+
+        // Only the hunks with their context, and truecolor doesn't change the text
+        let old = (1..=20).map(|i| format!("line {i}\n")).collect::<String>();
+        let new = old.replace("line 3\n", "line three\n").replace("line 18\n", "");
+        let options = DiffOptions {
+            start_line: 1,
+            context_lines: Some(1),
+            truecolor: true,
+        };
+        assert_eq!(
+            render(&old, &new, options),
+            [
+                "@@ -2,3 +2,3 @@",
+                "   2,  2: line 2",
+                "-  3    : line 3",
+                "+      3: line three",
+                "   4,  4: line 4",
+                "@@ -17,3 +17,2 @@",
+                "  17, 17: line 17",
+                "- 18    : line 18",
+                "  19, 18: line 19",
+                ""
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_palette() {
+        use crate::theme::Theme;
+
+        // The backgrounds follow the theme
+        let light = Theme::light().diff;
+        let palette = Palette::new(syntax_theme(&light, true), &light);
+        assert!(palette.truecolor);
+        assert_eq!(palette.colors(ChangeTag::Delete).2, light.removed_line);
+        assert_eq!(palette.colors(ChangeTag::Insert).3, Some(light.added_words));
+
+        // No highlighting nor backgrounds without truecolor or with the no-color theme
+        assert!(syntax_theme(&DiffColors::default(), false).is_none());
+        let no_color = Theme::no_color().diff;
+        assert!(syntax_theme(&no_color, true).is_none());
+        let palette = Palette::new(None, &no_color);
+        assert!(!palette.truecolor);
+        assert_eq!(palette.colors(ChangeTag::Delete).1, Color::Reset);
+        assert_eq!(highlight(Path::new("file.rs"), "fn main() {}\n", None), vec![vec![(
+            None,
+            "fn main() {}\n"
+        )]]);
+    }
+
+    #[test]
+    fn test_gutter_width() {
+        assert_eq!(terminal_width_required_for_line_count(1), 1);
+        assert_eq!(terminal_width_required_for_line_count(9), 1);
+        assert_eq!(terminal_width_required_for_line_count(10), 2);
+        assert_eq!(terminal_width_required_for_line_count(99), 2);
+        assert_eq!(terminal_width_required_for_line_count(100), 3);
+        assert_eq!(terminal_width_required_for_line_count(999), 3);
+    }
+
+    #[test]
+    fn test_word_diff() {
+        let (old, new) = word_diff("let value = compute(a, b);\n", "let value = compute(a, c);\n");
+        // Words are separated by whitespace
+        assert_eq!(old, vec![23..26]);
+        assert_eq!(new, vec![23..26]);
+
+        // Lines mostly changed aren't highlighted word by word
+        assert_eq!(word_diff("fn main() {}\n", "struct Foo;\n"), (vec![], vec![]));
+    }
+
+    #[test]
+    fn test_split_highlighted() {
+        assert_eq!(split_highlighted(0..10, &[2..4, 6..12]), vec![
+            (0..2, false),
+            (2..4, true),
+            (4..6, false),
+            (6..10, true)
+        ]);
+        assert_eq!(split_highlighted(4..6, &[2..4, 8..9]), vec![(4..6, false)]);
+    }
+}
@@ -1,3 +1,5 @@
+pub mod diff;
+
 use std::io::Write;
 
 use crossterm::execute;